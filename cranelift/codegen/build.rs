@@ -65,7 +65,19 @@ fn main() {
         crate_dir.join("build.rs").to_str().unwrap()
     );
 
-    if let Err(err) = meta::generate(&old_backend_isas, &new_backend_isas, &out_dir) {
+    // Likewise for the backends' lowering rules.
+    let src_dir = crate_dir.join("src");
+    println!(
+        "cargo:rerun-if-changed={}",
+        src_dir.join("isa/x64/lower.rules").to_str().unwrap()
+    );
+
+    if let Err(err) = meta::generate(
+        &old_backend_isas,
+        &new_backend_isas,
+        src_dir.to_str().unwrap(),
+        &out_dir,
+    ) {
         eprintln!("Error: {}", err);
         process::exit(1);
     }
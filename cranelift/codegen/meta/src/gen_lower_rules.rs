@@ -0,0 +1,261 @@
+//! Generate instruction lowering from the rules in a backend's `lower.rules`.
+//!
+//! The generated file defines a function
+//! `lower<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) -> bool` which
+//! lowers `insn` if there are rules for its opcode and controlling type, and
+//! returns whether it did. It's included in a module of the backend which must
+//! provide the extractors and constructors the rules use, and
+//! `matches_input(ctx, input, opcode) -> Option<IRInst>` to match nested
+//! instructions.
+
+use crate::cdsl::instructions::AllInstructions;
+use crate::error;
+use crate::rules::{self, CheckedExpr, ClifType, Lowering, Pat};
+use crate::srcgen::Formatter;
+
+use std::fs;
+
+/// Allocates the names of temporaries within a rule.
+struct Temps(usize);
+
+impl Temps {
+    fn next(&mut self) -> String {
+        self.0 += 1;
+        format!("t{}", self.0)
+    }
+}
+
+/// Emits an `if let` for each refutable pattern in `pats`, matched against
+/// the `Value` each is paired with, and then `body` within all of them.
+fn gen_patterns(
+    pats: &[(&Pat, String)],
+    temps: &mut Temps,
+    fmt: &mut Formatter,
+    body: &mut dyn FnMut(&mut Temps, &mut Formatter),
+) {
+    let ((pat, src), rest) = match pats.split_first() {
+        Some(first) => first,
+        None => return body(temps, fmt),
+    };
+    match pat {
+        Pat::Wildcard => gen_patterns(rest, temps, fmt, body),
+        Pat::Bind(var) => {
+            fmtln!(fmt, "let {} = {};", var, src);
+            gen_patterns(rest, temps, fmt, body);
+        }
+        Pat::Extract { name, outputs } => {
+            let mut nested = Vec::new();
+            let names: Vec<String> = outputs
+                .iter()
+                .map(|output| match output {
+                    Pat::Wildcard => "_".to_string(),
+                    Pat::Bind(var) => var.clone(),
+                    Pat::Extract { .. } | Pat::Inst { .. } => {
+                        let temp = temps.next();
+                        nested.push((output, temp.clone()));
+                        temp
+                    }
+                })
+                .collect();
+            let binding = if names.len() == 1 {
+                names[0].clone()
+            } else {
+                format!("({})", names.join(", "))
+            };
+            fmtln!(fmt, "if let Some({}) = {}(ctx, {}) {{", binding, name, src);
+            fmt.indent(|fmt| {
+                nested.extend(rest.iter().map(|(pat, src)| (*pat, src.clone())));
+                gen_patterns(&nested, temps, fmt, body);
+            });
+            fmtln!(fmt, "}");
+        }
+        Pat::Inst { opcode, inputs } => {
+            let inst = temps.next();
+            fmtln!(
+                fmt,
+                "if let Some({}) = matches_input(ctx, {}, Opcode::{}) {{",
+                inst,
+                src,
+                opcode
+            );
+            fmt.indent(|fmt| {
+                let mut nested: Vec<(&Pat, String)> = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| {
+                        (
+                            input,
+                            format!("InsnInput {{ insn: {}, input: {} }}", inst, i),
+                        )
+                    })
+                    .collect();
+                nested.extend(rest.iter().map(|(pat, src)| (*pat, src.clone())));
+                gen_patterns(&nested, temps, fmt, body);
+            });
+            fmtln!(fmt, "}");
+        }
+    }
+}
+
+/// Emits `let` statements evaluating `expr` from left to right, and returns
+/// the Rust expression holding its value. Every call gets its own temporary
+/// so that arguments never borrow `ctx` while it's in use.
+fn gen_expr(expr: &CheckedExpr, temps: &mut Temps, fmt: &mut Formatter) -> String {
+    match expr {
+        CheckedExpr::Var(var) => var.clone(),
+        CheckedExpr::Const(path) => path.clone(),
+        CheckedExpr::Call { name, args } => {
+            let mut call_args = vec!["ctx".to_string()];
+            for arg in args {
+                call_args.push(gen_expr(arg, temps, fmt));
+            }
+            let temp = temps.next();
+            fmtln!(fmt, "let {} = {}({});", temp, name, call_args.join(", "));
+            temp
+        }
+        CheckedExpr::Let { bindings, body } => {
+            for (var, value) in bindings {
+                let value = gen_expr(value, temps, fmt);
+                fmtln!(fmt, "let {} = {};", var, value);
+            }
+            gen_expr(body, temps, fmt)
+        }
+    }
+}
+
+/// Emits statements evaluating the `Unit` expression `expr`.
+fn gen_unit_expr(expr: &CheckedExpr, temps: &mut Temps, fmt: &mut Formatter) {
+    match expr {
+        CheckedExpr::Call { name, args } => {
+            let mut call_args = vec!["ctx".to_string()];
+            for arg in args {
+                call_args.push(gen_expr(arg, temps, fmt));
+            }
+            fmtln!(fmt, "{}({});", name, call_args.join(", "));
+        }
+        CheckedExpr::Let { bindings, body } => {
+            for (var, value) in bindings {
+                let value = gen_expr(value, temps, fmt);
+                fmtln!(fmt, "let {} = {};", var, value);
+            }
+            gen_unit_expr(body, temps, fmt)
+        }
+        CheckedExpr::Var(_) | CheckedExpr::Const(_) => {}
+    }
+}
+
+fn uses_dst(expr: &CheckedExpr) -> bool {
+    match expr {
+        CheckedExpr::Var(var) => var == "dst",
+        CheckedExpr::Const(_) => false,
+        CheckedExpr::Call { args, .. } => args.iter().any(uses_dst),
+        CheckedExpr::Let { bindings, body } => {
+            bindings.iter().any(|(_, value)| uses_dst(value)) || uses_dst(body)
+        }
+    }
+}
+
+fn gen_lowering(lowering: &Lowering, rules_file: &str, fmt: &mut Formatter) {
+    fmtln!(fmt, "Opcode::{} => {{", lowering.opcode);
+    fmt.indent(|fmt| {
+        match lowering.ctrl_input {
+            Some(input) => fmtln!(fmt, "let ty = ctx.input_ty(insn, {});", input),
+            None => fmtln!(fmt, "let ty = ctx.output_ty(insn, 0);"),
+        }
+        for i in 0..lowering.num_inputs {
+            fmtln!(fmt, "let i{} = InsnInput {{ insn, input: {} }};", i, i);
+        }
+
+        // Group the types which try the same rules into one match arm.
+        let mut groups: Vec<(Vec<ClifType>, Vec<usize>)> = Vec::new();
+        for &ty in &lowering.types {
+            let rules = lowering.rules_for(ty);
+            match groups.iter_mut().find(|(_, r)| *r == rules) {
+                Some((types, _)) => types.push(ty),
+                None => groups.push((vec![ty], rules)),
+            }
+        }
+
+        fmtln!(fmt, "match ty {");
+        fmt.indent(|fmt| {
+            for (types, rules) in &groups {
+                let types: Vec<String> = types.iter().map(ClifType::rust_name).collect();
+                fmtln!(fmt, "{} => {{", types.join(" | "));
+                fmt.indent(|fmt| {
+                    for &i in rules {
+                        let rule = &lowering.rules[i];
+                        fmt.comment(format!("{}:{}", rules_file, rule.line));
+                        let pats: Vec<(&Pat, String)> = rule
+                            .inputs
+                            .iter()
+                            .enumerate()
+                            .map(|(i, pat)| (pat, format!("i{}", i)))
+                            .collect();
+                        // Only the last rule can be irrefutable, so every other rule's
+                        // bindings are scoped by its `if let`s.
+                        gen_patterns(&pats, &mut Temps(0), fmt, &mut |temps, fmt| {
+                            if uses_dst(&rule.expr) {
+                                fmtln!(
+                                    fmt,
+                                    "let dst = get_output_reg(ctx, InsnOutput { insn, output: 0 });"
+                                );
+                            }
+                            gen_unit_expr(&rule.expr, temps, fmt);
+                            fmtln!(fmt, "return true;");
+                        });
+                    }
+                });
+                fmtln!(fmt, "}");
+            }
+            fmtln!(fmt, "_ => {}");
+        });
+        fmtln!(fmt, "}");
+    });
+    fmtln!(fmt, "}");
+}
+
+fn gen_lower(lowerings: &[Lowering], rules_file: &str, fmt: &mut Formatter) {
+    fmt.doc_comment(format!(
+        "Lowers `insn` with the rules in `{}`, returning whether a rule applied.",
+        rules_file
+    ));
+    fmtln!(fmt, "#[allow(unused_variables)]");
+    fmtln!(
+        fmt,
+        "pub(crate) fn lower<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) -> bool {"
+    );
+    fmt.indent(|fmt| {
+        fmtln!(fmt, "match ctx.data(insn).opcode() {");
+        fmt.indent(|fmt| {
+            for lowering in lowerings {
+                gen_lowering(lowering, rules_file, fmt);
+            }
+            fmtln!(fmt, "_ => {}");
+        });
+        fmtln!(fmt, "}");
+        fmtln!(fmt, "false");
+    });
+    fmtln!(fmt, "}");
+}
+
+/// Generates `filename` from the rules file at `rules_path`.
+pub(crate) fn generate(
+    all_instructions: &AllInstructions,
+    rules_path: &str,
+    filename: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let src = fs::read_to_string(rules_path)?;
+    let lowerings = rules::parse(&src)
+        .and_then(|defs| rules::check(&defs, all_instructions))
+        .map_err(|e| error::Error::with_msg(format!("{}:{}", rules_path, e)))?;
+
+    let rules_file = rules_path
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(rules_path);
+    let mut fmt = Formatter::new();
+    gen_lower(&lowerings, rules_file, &mut fmt);
+    fmt.update_file(filename, out_dir)?;
+    Ok(())
+}
@@ -6,6 +6,7 @@ use std::fmt;
 mod arm32;
 mod arm64;
 mod riscv;
+mod riscv64;
 mod s390x;
 pub(crate) mod x86;
 
@@ -13,6 +14,7 @@ pub(crate) mod x86;
 #[derive(PartialEq, Copy, Clone)]
 pub enum Isa {
     Riscv,
+    Riscv64,
     X86,
     Arm32,
     Arm64,
@@ -32,6 +34,7 @@ impl Isa {
     pub fn from_arch(arch: &str) -> Option<Self> {
         match arch {
             "riscv" => Some(Isa::Riscv),
+            "riscv64" | "riscv64gc" => Some(Isa::Riscv64),
             "aarch64" => Some(Isa::Arm64),
             "s390x" => Some(Isa::S390x),
             x if ["x86_64", "i386", "i586", "i686"].contains(&x) => Some(Isa::X86),
@@ -42,7 +45,14 @@ impl Isa {
 
     /// Returns all supported isa targets.
     pub fn all() -> &'static [Isa] {
-        &[Isa::Riscv, Isa::X86, Isa::Arm32, Isa::Arm64, Isa::S390x]
+        &[
+            Isa::Riscv,
+            Isa::Riscv64,
+            Isa::X86,
+            Isa::Arm32,
+            Isa::Arm64,
+            Isa::S390x,
+        ]
     }
 }
 
@@ -51,6 +61,7 @@ impl fmt::Display for Isa {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Isa::Riscv => write!(f, "riscv"),
+            Isa::Riscv64 => write!(f, "riscv64"),
             Isa::X86 => write!(f, "x86"),
             Isa::Arm32 => write!(f, "arm32"),
             Isa::Arm64 => write!(f, "arm64"),
@@ -63,6 +74,7 @@ pub(crate) fn define(isas: &[Isa], shared_defs: &mut SharedDefinitions) -> Vec<T
     isas.iter()
         .map(|isa| match isa {
             Isa::Riscv => riscv::define(shared_defs),
+            Isa::Riscv64 => riscv64::define(shared_defs),
             Isa::X86 => x86::define(shared_defs),
             Isa::Arm32 => arm32::define(shared_defs),
             Isa::Arm64 => arm64::define(shared_defs),
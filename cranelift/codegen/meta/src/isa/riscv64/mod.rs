@@ -0,0 +1,46 @@
+use crate::cdsl::instructions::InstructionPredicateMap;
+use crate::cdsl::isa::TargetIsa;
+use crate::cdsl::recipes::Recipes;
+use crate::cdsl::regs::IsaRegsBuilder;
+use crate::cdsl::settings::{SettingGroup, SettingGroupBuilder};
+
+use crate::shared::Definitions as SharedDefinitions;
+
+fn define_settings(_shared: &SettingGroup) -> SettingGroup {
+    let mut settings = SettingGroupBuilder::new("riscv64");
+
+    // The baseline architecture is RV64GC, so we list only extensions beyond
+    // it here.
+    let has_zbb = settings.add_bool(
+        "has_zbb",
+        "Has the Zbb basic bit-manipulation extension.",
+        "",
+        false,
+    );
+
+    settings.add_preset(
+        "rva22u64",
+        "RVA22U64 application profile.",
+        preset!(has_zbb),
+    );
+
+    settings.build()
+}
+
+pub(crate) fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
+    let settings = define_settings(&shared_defs.settings);
+    let regs = IsaRegsBuilder::new().build();
+    let recipes = Recipes::new();
+    let encodings_predicates = InstructionPredicateMap::new();
+
+    let cpu_modes = vec![];
+
+    TargetIsa::new(
+        "riscv64",
+        settings,
+        regs,
+        recipes,
+        cpu_modes,
+        encodings_predicates,
+    )
+}
@@ -11,11 +11,13 @@ mod gen_binemit;
 mod gen_encodings;
 mod gen_inst;
 mod gen_legalizer;
+mod gen_lower_rules;
 mod gen_registers;
 mod gen_settings;
 mod gen_types;
 
 mod default_map;
+mod rules;
 mod shared;
 mod unique_table;
 
@@ -25,9 +27,13 @@ pub fn isa_from_arch(arch: &str) -> Result<isa::Isa, String> {
 }
 
 /// Generates all the Rust source files used in Cranelift from the meta-language.
+///
+/// Backends' lowering rules are read from `src_dir`, the `src` directory of
+/// `cranelift-codegen`.
 pub fn generate(
     old_backend_isas: &[isa::Isa],
     new_backend_isas: &[isa::Isa],
+    src_dir: &str,
     out_dir: &str,
 ) -> Result<(), error::Error> {
     // Create all the definitions:
@@ -97,6 +103,20 @@ pub fn generate(
         )?;
     }
 
+    // The x64 backend is built whenever x86 is, with either backend.
+    if old_backend_isas
+        .iter()
+        .chain(new_backend_isas)
+        .any(|isa| *isa == isa::Isa::X86)
+    {
+        gen_lower_rules::generate(
+            &shared_defs.all_instructions,
+            &format!("{}/isa/x64/lower.rules", src_dir),
+            "lower_rules-x64.rs",
+            &out_dir,
+        )?;
+    }
+
     for isa in new_backend_isas {
         match isa {
             isa::Isa::X86 => {
@@ -119,6 +139,9 @@ pub fn generate(
             isa::Isa::S390x => {
                 // s390x doesn't have platform-specific settings.
             }
+            isa::Isa::Riscv64 => {
+                // riscv64 doesn't have platform-specific settings.
+            }
             isa::Isa::Arm32 | isa::Isa::Riscv => todo!(),
         }
     }
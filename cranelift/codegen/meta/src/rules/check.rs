@@ -0,0 +1,755 @@
+//! Semantic checks for lowering rules.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use super::{Def, Expr as AstExpr, Ident, Pattern, Pos, Rule};
+use crate::cdsl::instructions::{AllInstructions, Instruction};
+use crate::cdsl::typevar::TypeSet;
+
+type CheckResult<T> = Result<T, String>;
+
+fn error<T>(pos: Pos, msg: impl Into<String>) -> CheckResult<T> {
+    Err(format!("{}: {}", pos, msg.into()))
+}
+
+/// The types built into the language.
+const BUILTIN_TYPES: &[&str] = &["Value", "Type", "WritableRegs", "Unit"];
+
+/// A concrete CLIF type, such as `I32` or `F32X4`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct ClifType {
+    kind: char,
+    bits: u16,
+    lanes: u16,
+}
+
+impl ClifType {
+    fn parse(name: &str) -> Option<ClifType> {
+        let kind = name.chars().next()?;
+        let (bits, lanes): (_, u16) = match name[1..].find('X') {
+            Some(x) => (&name[1..x + 1], name[x + 2..].parse().ok()?),
+            None => (&name[1..], 1),
+        };
+        let bits: u16 = bits.parse().ok()?;
+        let valid_bits = match kind {
+            'I' => [8, 16, 32, 64, 128].contains(&bits),
+            'B' => [1, 8, 16, 32, 64, 128].contains(&bits),
+            'F' => [32, 64].contains(&bits),
+            'R' => [32, 64].contains(&bits) && lanes == 1,
+            _ => false,
+        };
+        if !valid_bits || !lanes.is_power_of_two() || lanes > 256 {
+            return None;
+        }
+        Some(ClifType { kind, bits, lanes })
+    }
+
+    fn in_typeset(&self, ts: &TypeSet) -> bool {
+        let widths = match self.kind {
+            'I' => &ts.ints,
+            'B' => &ts.bools,
+            'F' => &ts.floats,
+            _ => &ts.refs,
+        };
+        ts.lanes.contains(&self.lanes) && widths.contains(&self.bits)
+    }
+
+    /// The Rust expression for this type.
+    pub fn rust_name(&self) -> String {
+        format!("types::{}", self)
+    }
+}
+
+impl fmt::Display for ClifType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.kind, self.bits)?;
+        if self.lanes > 1 {
+            write!(f, "X{}", self.lanes)?;
+        }
+        Ok(())
+    }
+}
+
+/// A checked pattern.
+#[derive(Debug)]
+pub(crate) enum Pat {
+    Wildcard,
+    /// Binds the Rust variable of the given name.
+    Bind(String),
+    /// Calls an extractor and matches its outputs.
+    Extract {
+        name: String,
+        outputs: Vec<Pat>,
+    },
+    /// Matches a value produced by the instruction with the given `Opcode`
+    /// variant, and its value inputs.
+    Inst {
+        opcode: String,
+        inputs: Vec<Pat>,
+    },
+}
+
+impl Pat {
+    fn is_irrefutable(&self) -> bool {
+        match self {
+            Pat::Wildcard | Pat::Bind(_) => true,
+            Pat::Extract { .. } | Pat::Inst { .. } => false,
+        }
+    }
+}
+
+/// A checked expression, where variables have been renamed to the Rust
+/// variables holding them.
+#[derive(Debug)]
+pub(crate) enum Expr {
+    Var(String),
+    /// A Rust path to an enum variant.
+    Const(String),
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+    Let {
+        bindings: Vec<(String, Expr)>,
+        body: Box<Expr>,
+    },
+}
+
+/// A checked rule.
+#[derive(Debug)]
+pub(crate) struct CheckedRule {
+    pub line: usize,
+    pub prio: i64,
+    pub types: BTreeSet<ClifType>,
+    pub inputs: Vec<Pat>,
+    pub expr: Expr,
+}
+
+impl CheckedRule {
+    /// Whether this rule applies to every instruction it's tried on.
+    pub fn is_irrefutable(&self) -> bool {
+        self.inputs.iter().all(Pat::is_irrefutable)
+    }
+}
+
+/// The rules lowering one CLIF instruction.
+#[derive(Debug)]
+pub(crate) struct Lowering {
+    /// The instruction's `Opcode` variant.
+    pub opcode: String,
+    /// The index of the value input whose type is the controlling type, or
+    /// `None` if it's the type of the first result.
+    pub ctrl_input: Option<usize>,
+    pub num_inputs: usize,
+    /// The controlling types lowered by the rules, in declaration order.
+    pub types: Vec<ClifType>,
+    /// The rules in the order they're tried.
+    pub rules: Vec<CheckedRule>,
+}
+
+impl Lowering {
+    /// Returns the rules tried for the controlling type `ty`, up to and
+    /// including the first one that always applies.
+    pub fn rules_for(&self, ty: ClifType) -> Vec<usize> {
+        let mut rules = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.types.contains(&ty) {
+                rules.push(i);
+                if rule.is_irrefutable() {
+                    break;
+                }
+            }
+        }
+        rules
+    }
+}
+
+enum Term {
+    Extractor { arg: String, outputs: Vec<String> },
+    Constructor { args: Vec<String>, ret: String },
+}
+
+struct Env<'a> {
+    instructions: HashMap<&'a str, &'a Instruction>,
+    types: BTreeSet<String>,
+    typeclasses: HashMap<String, Vec<ClifType>>,
+    terms: HashMap<String, Term>,
+}
+
+impl Env<'_> {
+    fn check_type(&self, ident: &Ident) -> CheckResult<String> {
+        if self.types.contains(&ident.name) {
+            Ok(ident.name.clone())
+        } else {
+            error(ident.pos, format!("unknown type `{}`", ident.name))
+        }
+    }
+
+    /// Resolves type and typeclass names to a set of CLIF types.
+    fn clif_types(&self, idents: &[Ident]) -> CheckResult<Vec<ClifType>> {
+        let mut types = Vec::new();
+        for ident in idents {
+            if let Some(class) = self.typeclasses.get(&ident.name) {
+                types.extend(class.iter().cloned());
+            } else if let Some(ty) = ClifType::parse(&ident.name) {
+                types.push(ty);
+            } else {
+                return error(
+                    ident.pos,
+                    format!("unknown CLIF type or typeclass `{}`", ident.name),
+                );
+            }
+        }
+        Ok(types)
+    }
+
+    fn instruction(&self, ident: &Ident) -> CheckResult<&Instruction> {
+        match self.instructions.get(ident.name.as_str()) {
+            Some(inst) => Ok(inst),
+            None => error(
+                ident.pos,
+                format!("unknown instruction or extractor `{}`", ident.name),
+            ),
+        }
+    }
+}
+
+/// The variables in scope within a rule, mapping names to their Rust
+/// variable and type.
+type Scope = HashMap<String, (String, String)>;
+
+fn bind(scope: &mut Scope, ident: &Ident, ty: &str) -> CheckResult<String> {
+    if ident.name == "ty" || ident.name == "dst" {
+        return error(ident.pos, format!("`{}` is a reserved name", ident.name));
+    }
+    let var = format!("v_{}", ident.name);
+    if scope
+        .insert(ident.name.clone(), (var.clone(), ty.to_string()))
+        .is_some()
+    {
+        return error(ident.pos, format!("`{}` is bound twice", ident.name));
+    }
+    Ok(var)
+}
+
+fn check_pattern(env: &Env, scope: &mut Scope, pat: &Pattern, ty: &str) -> CheckResult<Pat> {
+    match pat {
+        Pattern::Wildcard => Ok(Pat::Wildcard),
+        Pattern::Var(ident) => Ok(Pat::Bind(bind(scope, ident, ty)?)),
+        Pattern::Term { head, args } => match env.terms.get(&head.name) {
+            Some(Term::Extractor { arg, outputs }) => {
+                if arg != ty {
+                    return error(
+                        head.pos,
+                        format!("`{}` extracts from `{}`, not `{}`", head.name, arg, ty),
+                    );
+                }
+                if args.len() != outputs.len() {
+                    return error(
+                        head.pos,
+                        format!(
+                            "`{}` has {} outputs but is matched against {} patterns",
+                            head.name,
+                            outputs.len(),
+                            args.len()
+                        ),
+                    );
+                }
+                Ok(Pat::Extract {
+                    name: head.name.clone(),
+                    outputs: args
+                        .iter()
+                        .zip(outputs)
+                        .map(|(arg, ty)| check_pattern(env, scope, arg, ty))
+                        .collect::<CheckResult<_>>()?,
+                })
+            }
+            Some(Term::Constructor { .. }) => error(
+                head.pos,
+                format!("constructor `{}` can't be used in a pattern", head.name),
+            ),
+            None => {
+                let inst = env.instruction(head)?;
+                if ty != "Value" {
+                    return error(
+                        head.pos,
+                        format!("`{}` matches a `Value`, not `{}`", head.name, ty),
+                    );
+                }
+                let inputs = check_inputs(env, scope, head, inst, args)?;
+                Ok(Pat::Inst {
+                    opcode: inst.camel_name.clone(),
+                    inputs,
+                })
+            }
+        },
+    }
+}
+
+/// Checks the patterns `args` for the value inputs of `inst`.
+fn check_inputs(
+    env: &Env,
+    scope: &mut Scope,
+    head: &Ident,
+    inst: &Instruction,
+    args: &[Pattern],
+) -> CheckResult<Vec<Pat>> {
+    if inst.format.has_value_list {
+        return error(
+            head.pos,
+            format!(
+                "`{}` has variable arguments and can't be matched",
+                head.name
+            ),
+        );
+    }
+    if args.len() != inst.value_opnums.len() {
+        return error(
+            head.pos,
+            format!(
+                "`{}` has {} value inputs but is matched against {} patterns",
+                head.name,
+                inst.value_opnums.len(),
+                args.len()
+            ),
+        );
+    }
+    args.iter()
+        .map(|arg| check_pattern(env, scope, arg, "Value"))
+        .collect()
+}
+
+fn check_expr(env: &Env, scope: &Scope, expr: &AstExpr) -> CheckResult<(Expr, String)> {
+    match expr {
+        AstExpr::Var(ident) => match scope.get(&ident.name) {
+            Some((var, ty)) => Ok((Expr::Var(var.clone()), ty.clone())),
+            None => error(ident.pos, format!("unknown variable `{}`", ident.name)),
+        },
+        AstExpr::Const { ty, variant } => {
+            let ty = env.check_type(ty)?;
+            if BUILTIN_TYPES.contains(&ty.as_str()) {
+                return error(variant.pos, format!("`{}` has no constants", ty));
+            }
+            Ok((Expr::Const(format!("{}::{}", ty, variant.name)), ty))
+        }
+        AstExpr::Call { head, args } => match env.terms.get(&head.name) {
+            Some(Term::Constructor { args: params, ret }) => {
+                if args.len() != params.len() {
+                    return error(
+                        head.pos,
+                        format!(
+                            "`{}` takes {} arguments but {} were given",
+                            head.name,
+                            params.len(),
+                            args.len()
+                        ),
+                    );
+                }
+                let mut checked = Vec::new();
+                for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+                    let (arg, ty) = check_expr(env, scope, arg)?;
+                    if &ty != param {
+                        return error(
+                            head.pos,
+                            format!(
+                                "argument {} of `{}` should be a `{}`, not a `{}`",
+                                i + 1,
+                                head.name,
+                                param,
+                                ty
+                            ),
+                        );
+                    }
+                    checked.push(arg);
+                }
+                Ok((
+                    Expr::Call {
+                        name: head.name.clone(),
+                        args: checked,
+                    },
+                    ret.clone(),
+                ))
+            }
+            Some(Term::Extractor { .. }) => error(
+                head.pos,
+                format!("extractor `{}` can't be used in an expression", head.name),
+            ),
+            None => error(head.pos, format!("unknown constructor `{}`", head.name)),
+        },
+        AstExpr::Let { bindings, body } => {
+            let mut inner = scope.clone();
+            let mut checked = Vec::new();
+            for (name, value) in bindings {
+                // Bindings are evaluated in order, each seeing the previous.
+                let (value, ty) = check_expr(env, &inner, value)?;
+                checked.push((bind(&mut inner, name, &ty)?, value));
+            }
+            let (body, ty) = check_expr(env, &inner, body)?;
+            Ok((
+                Expr::Let {
+                    bindings: checked,
+                    body: Box::new(body),
+                },
+                ty,
+            ))
+        }
+    }
+}
+
+fn check_rule(env: &Env, lowerings: &mut [Lowering], rule: &Rule) -> CheckResult<()> {
+    let (head, args) = match &rule.pattern {
+        Pattern::Term { head, args } => (head, args),
+        _ => return error(rule.pos, "a rule must match an instruction"),
+    };
+    let inst = env.instruction(head)?;
+    let lowering = match lowerings.iter_mut().find(|l| l.opcode == inst.camel_name) {
+        Some(lowering) => lowering,
+        None => {
+            return error(
+                head.pos,
+                format!("`{}` has no `lower` declaration", head.name),
+            )
+        }
+    };
+
+    let mut types = BTreeSet::new();
+    for ty in env.clif_types(&rule.types)? {
+        if !lowering.types.contains(&ty) {
+            return error(
+                rule.pos,
+                format!(
+                    "rule matches `{}` with type `{}`, which its `lower` declaration doesn't include",
+                    head.name, ty
+                ),
+            );
+        }
+        types.insert(ty);
+    }
+
+    let mut scope = Scope::new();
+    let inputs = check_inputs(env, &mut scope, head, inst, args)?;
+    scope.insert("ty".to_string(), ("ty".to_string(), "Type".to_string()));
+    if !inst.value_results.is_empty() {
+        scope.insert(
+            "dst".to_string(),
+            ("dst".to_string(), "WritableRegs".to_string()),
+        );
+    }
+    let (expr, ty) = check_expr(env, &scope, &rule.expr)?;
+    if ty != "Unit" {
+        return error(
+            rule.pos,
+            format!("a rule's expression should be a `Unit`, not a `{}`", ty),
+        );
+    }
+
+    lowering.rules.push(CheckedRule {
+        line: rule.pos.line,
+        prio: rule.prio,
+        types,
+        inputs,
+        expr,
+    });
+    Ok(())
+}
+
+/// Checks `defs`, returning the lowerings they define.
+pub(crate) fn check(
+    defs: &[Def],
+    all_instructions: &AllInstructions,
+) -> CheckResult<Vec<Lowering>> {
+    let mut env = Env {
+        instructions: all_instructions
+            .values()
+            .map(|inst| (inst.name.as_str(), inst))
+            .collect(),
+        types: BUILTIN_TYPES.iter().map(|s| s.to_string()).collect(),
+        typeclasses: HashMap::new(),
+        terms: HashMap::new(),
+    };
+
+    // Declarations come first so that rules can refer to any of them.
+    for def in defs {
+        match def {
+            Def::Type(name) => {
+                if !env.types.insert(name.name.clone()) {
+                    return error(name.pos, format!("type `{}` is defined twice", name.name));
+                }
+            }
+            Def::TypeClass { name, types } => {
+                if ClifType::parse(&name.name).is_some() {
+                    return error(name.pos, format!("`{}` is a CLIF type", name.name));
+                }
+                let types = env.clif_types(types)?;
+                if env.typeclasses.insert(name.name.clone(), types).is_some() {
+                    return error(
+                        name.pos,
+                        format!("typeclass `{}` is defined twice", name.name),
+                    );
+                }
+            }
+            Def::Extractor { name, arg, outputs } => {
+                let term = Term::Extractor {
+                    arg: env.check_type(arg)?,
+                    outputs: outputs
+                        .iter()
+                        .map(|ty| env.check_type(ty))
+                        .collect::<CheckResult<_>>()?,
+                };
+                declare_term(&mut env, name, term)?;
+            }
+            Def::Constructor { name, args, ret } => {
+                let term = Term::Constructor {
+                    args: args
+                        .iter()
+                        .map(|ty| env.check_type(ty))
+                        .collect::<CheckResult<_>>()?,
+                    ret: env.check_type(ret)?,
+                };
+                declare_term(&mut env, name, term)?;
+            }
+            Def::Lower { .. } | Def::Rule(_) => {}
+        }
+    }
+
+    let mut lowerings = Vec::new();
+    for def in defs {
+        if let Def::Lower { opcode, types } = def {
+            let inst = env.instruction(opcode)?;
+            if lowerings
+                .iter()
+                .any(|l: &Lowering| l.opcode == inst.camel_name)
+            {
+                return error(
+                    opcode.pos,
+                    format!("`{}` has more than one `lower` declaration", opcode.name),
+                );
+            }
+            let poly = match &inst.polymorphic_info {
+                Some(poly) => poly,
+                None => {
+                    return error(
+                        opcode.pos,
+                        format!(
+                            "`{}` isn't polymorphic, which rules don't support yet",
+                            opcode.name
+                        ),
+                    )
+                }
+            };
+            let typeset = poly.ctrl_typevar.get_typeset();
+            let mut lowered = Vec::new();
+            for ty in env.clif_types(types)? {
+                if !ty.in_typeset(&typeset) {
+                    return error(
+                        opcode.pos,
+                        format!("`{}` can't have the controlling type `{}`", opcode.name, ty),
+                    );
+                }
+                if lowered.contains(&ty) {
+                    return error(opcode.pos, format!("type `{}` is listed twice", ty));
+                }
+                lowered.push(ty);
+            }
+            lowerings.push(Lowering {
+                opcode: inst.camel_name.clone(),
+                ctrl_input: if poly.use_typevar_operand {
+                    Some(inst.format.typevar_operand.unwrap_or(0))
+                } else {
+                    None
+                },
+                num_inputs: inst.value_opnums.len(),
+                types: lowered,
+                rules: Vec::new(),
+            });
+        }
+    }
+
+    for def in defs {
+        if let Def::Rule(rule) = def {
+            check_rule(&env, &mut lowerings, rule)?;
+        }
+    }
+
+    for (lowering, def) in lowerings
+        .iter_mut()
+        .zip(defs.iter().filter_map(|def| match def {
+            Def::Lower { opcode, .. } => Some(opcode),
+            _ => None,
+        }))
+    {
+        // A stable sort keeps rules of the same priority in source order.
+        lowering.rules.sort_by_key(|rule| -rule.prio);
+
+        for &ty in &lowering.types {
+            let covered = lowering
+                .rules_for(ty)
+                .last()
+                .map_or(false, |&i| lowering.rules[i].is_irrefutable());
+            if !covered {
+                return error(
+                    def.pos,
+                    format!(
+                        "no rule always applies to `{}` with type `{}`",
+                        def.name, ty
+                    ),
+                );
+            }
+        }
+
+        for (i, rule) in lowering.rules.iter().enumerate() {
+            let reachable = rule
+                .types
+                .iter()
+                .any(|&ty| lowering.rules_for(ty).contains(&i));
+            if !reachable {
+                return error(
+                    Pos {
+                        line: rule.line,
+                        col: 1,
+                    },
+                    "rule is unreachable: for each of its types, an earlier rule always applies",
+                );
+            }
+        }
+    }
+
+    Ok(lowerings)
+}
+
+fn declare_term(env: &mut Env, name: &Ident, term: Term) -> CheckResult<()> {
+    if env.instructions.contains_key(name.name.as_str()) {
+        return error(
+            name.pos,
+            format!("`{}` is the name of a CLIF instruction", name.name),
+        );
+    }
+    if env.terms.insert(name.name.clone(), term).is_some() {
+        return error(name.pos, format!("`{}` is defined twice", name.name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::parse;
+    use crate::shared;
+
+    const PRELUDE: &str = r#"
+        (type Reg)
+        (type u32)
+        (typeclass int I8 I16 I32 I64)
+        (extractor simm32 Value (u32))
+        (constructor put_in_reg (Value) Reg)
+        (constructor add (Reg Reg WritableRegs) Unit)
+        (constructor add_imm (Reg u32 WritableRegs) Unit)
+    "#;
+
+    fn check_src(src: &str) -> CheckResult<Vec<Lowering>> {
+        let prelude = PRELUDE.trim_end();
+        let defs = parse(&format!("{}\n{}", prelude, src)).unwrap();
+        let shared_defs = shared::define();
+        check(&defs, &shared_defs.all_instructions).map_err(|e| {
+            // Make positions relative to `src`.
+            let (line, rest) = e.split_at(e.find(':').unwrap());
+            let line: usize = line.parse().unwrap();
+            format!("{}{}", line - prelude.lines().count(), rest)
+        })
+    }
+
+    #[test]
+    fn orders_rules() {
+        let lowerings = check_src(
+            r#"
+            (lower iadd int)
+            (rule int (iadd x y) (add (put_in_reg x) (put_in_reg y) dst))
+            (rule 1 (I32 I64) (iadd x (simm32 k)) (add_imm (put_in_reg x) k dst))
+            "#,
+        )
+        .unwrap();
+        assert_eq!(lowerings.len(), 1);
+        let iadd = &lowerings[0];
+        assert_eq!(iadd.opcode, "Iadd");
+        assert_eq!(iadd.rules[0].prio, 1);
+        assert!(!iadd.rules[0].is_irrefutable());
+        let i8 = ClifType::parse("I8").unwrap();
+        let i64 = ClifType::parse("I64").unwrap();
+        assert_eq!(iadd.rules_for(i8), vec![1]);
+        assert_eq!(iadd.rules_for(i64), vec![0, 1]);
+    }
+
+    #[test]
+    fn catches_missing_combinations() {
+        assert_eq!(
+            check_src(
+                r#"
+                (lower iadd int)
+                (rule (I8 I16 I32) (iadd x y) (add (put_in_reg x) (put_in_reg y) dst))
+                (rule I64 (iadd x (simm32 k)) (add_imm (put_in_reg x) k dst))
+                "#
+            )
+            .unwrap_err(),
+            "2:24: no rule always applies to `iadd` with type `I64`"
+        );
+        assert_eq!(
+            check_src("(lower iadd F32)").unwrap_err(),
+            "1:8: `iadd` can't have the controlling type `F32`"
+        );
+        assert_eq!(
+            check_src(
+                r#"
+                (lower iadd I32)
+                (rule I64 (iadd x y) (add (put_in_reg x) (put_in_reg y) dst))
+                "#
+            )
+            .unwrap_err(),
+            "3:17: rule matches `iadd` with type `I64`, which its `lower` declaration doesn't include"
+        );
+    }
+
+    #[test]
+    fn catches_unreachable_rules() {
+        assert_eq!(
+            check_src(
+                r#"
+                (lower iadd I32)
+                (rule I32 (iadd x y) (add (put_in_reg x) (put_in_reg y) dst))
+                (rule I32 (iadd x (simm32 k)) (add_imm (put_in_reg x) k dst))
+                "#
+            )
+            .unwrap_err(),
+            "4:1: rule is unreachable: for each of its types, an earlier rule always applies"
+        );
+    }
+
+    #[test]
+    fn checks_terms() {
+        let rule = |body: &str| check_src(&format!("(lower iadd I32)\n(rule I32 {})", body));
+        assert_eq!(
+            rule("(iadd x) (add (put_in_reg x) (put_in_reg x) dst)").unwrap_err(),
+            "2:12: `iadd` has 2 value inputs but is matched against 1 patterns"
+        );
+        assert_eq!(
+            rule("(iadd x x) (add (put_in_reg x) (put_in_reg x) dst)").unwrap_err(),
+            "2:19: `x` is bound twice"
+        );
+        assert_eq!(
+            rule("(iadd x y) (add (put_in_reg x) y dst)").unwrap_err(),
+            "2:23: argument 2 of `add` should be a `Reg`, not a `Value`"
+        );
+        assert_eq!(
+            rule("(iadd x (put_in_reg y)) (add x y dst)").unwrap_err(),
+            "2:20: constructor `put_in_reg` can't be used in a pattern"
+        );
+        assert_eq!(
+            rule("(iadd x y) (put_in_reg x)").unwrap_err(),
+            "2:1: a rule's expression should be a `Unit`, not a `Reg`"
+        );
+        assert_eq!(
+            rule("(iadd x (ishl y _)) (add (put_in_reg x) (put_in_reg y) z)").unwrap_err(),
+            "2:66: unknown variable `z`"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! A term-rewriting language for lowering CLIF instructions to machine
+//! instructions.
+//!
+//! A backend lists its lowering rules in a `lower.rules` file next to its
+//! `lower.rs`. The rules are parsed and checked when `cranelift-codegen` is
+//! built, and compiled to a Rust function that the backend calls before
+//! falling back to its hand-written lowering. The file is a sequence of
+//! S-expressions; `;` starts a comment that runs to the end of the line.
+//!
+//! ```text
+//! ;; Types of the values flowing through patterns and expressions. `Value`
+//! ;; (a CLIF value), `Type` (a CLIF type), `WritableRegs` (the registers of
+//! ;; an instruction's result) and `Unit` are built in.
+//! (type Reg)
+//! (type AluRmiROpcode)
+//!
+//! ;; Named sets of CLIF types.
+//! (typeclass int_scalar I8 I16 I32 I64)
+//!
+//! ;; Extractors are side-effect free Rust functions which take the matched
+//! ;; value and return `Option` of their outputs (a tuple if there are
+//! ;; several). They can only appear in patterns.
+//! (extractor simm32 Value (u32))
+//!
+//! ;; Constructors are Rust functions which may emit instructions. They can
+//! ;; only appear in expressions.
+//! (constructor put_in_reg (Value) Reg)
+//! (constructor alu_rmi_r (Type AluRmiROpcode Reg RegMemImm WritableRegs) Unit)
+//!
+//! ;; The controlling types with which `iadd` is lowered by rules. Every type
+//! ;; listed here must have a rule that always applies to it.
+//! (lower iadd int_scalar I128)
+//!
+//! ;; Rules have an optional priority (0 by default), the controlling types
+//! ;; they apply to, a pattern over the instruction being lowered and an
+//! ;; expression that lowers it. Within an expression, `ty` is the controlling
+//! ;; type and `dst` the registers of the instruction's first result. Enum
+//! ;; constants are written `Type.Variant`.
+//! (rule 1 int_scalar (iadd (simm32 k) y)
+//!       (alu_rmi_r ty AluRmiROpcode.Add (put_in_reg y) (rmi_imm k) dst))
+//! (rule int_scalar (iadd x y)
+//!       (let ((lhs (put_in_reg x)))
+//!         (alu_rmi_r ty AluRmiROpcode.Add lhs (put_in_reg_mem_imm y) dst)))
+//! ```
+//!
+//! Patterns are `_`, a variable, an extractor applied to patterns for its
+//! outputs, or a CLIF instruction applied to patterns for its value inputs,
+//! which matches a value produced by that instruction. Rules are tried from
+//! the highest priority down, and in source order within a priority. The
+//! expression of the first rule whose pattern matches is evaluated with its
+//! arguments evaluated from left to right.
+//!
+//! Besides name resolution, arities and types, the build fails if a type
+//! listed by a `lower` declaration isn't in the instruction's type set, if it
+//! isn't covered by a rule that always applies, or if a rule can never apply
+//! because rules before it always apply to every type it matches.
+
+mod check;
+mod parser;
+
+pub(crate) use check::{check, ClifType, Expr as CheckedExpr, Lowering, Pat};
+pub(crate) use parser::parse;
+
+use std::fmt;
+
+/// A position within a rules file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// An identifier and where it appears.
+#[derive(Clone, Debug)]
+pub(crate) struct Ident {
+    pub name: String,
+    pub pos: Pos,
+}
+
+/// A top-level definition.
+#[derive(Debug)]
+pub(crate) enum Def {
+    Type(Ident),
+    TypeClass {
+        name: Ident,
+        types: Vec<Ident>,
+    },
+    Extractor {
+        name: Ident,
+        arg: Ident,
+        outputs: Vec<Ident>,
+    },
+    Constructor {
+        name: Ident,
+        args: Vec<Ident>,
+        ret: Ident,
+    },
+    Lower {
+        opcode: Ident,
+        types: Vec<Ident>,
+    },
+    Rule(Rule),
+}
+
+/// A lowering rule.
+#[derive(Debug)]
+pub(crate) struct Rule {
+    pub pos: Pos,
+    pub prio: i64,
+    pub types: Vec<Ident>,
+    pub pattern: Pattern,
+    pub expr: Expr,
+}
+
+/// The left-hand side of a rule.
+#[derive(Debug)]
+pub(crate) enum Pattern {
+    Wildcard,
+    Var(Ident),
+    Term { head: Ident, args: Vec<Pattern> },
+}
+
+/// The right-hand side of a rule.
+#[derive(Debug)]
+pub(crate) enum Expr {
+    Var(Ident),
+    Const {
+        ty: Ident,
+        variant: Ident,
+    },
+    Call {
+        head: Ident,
+        args: Vec<Expr>,
+    },
+    Let {
+        bindings: Vec<(Ident, Expr)>,
+        body: Box<Expr>,
+    },
+}
@@ -0,0 +1,303 @@
+//! Parser for lowering rules.
+
+use super::{Def, Expr, Ident, Pattern, Pos, Rule};
+
+/// An S-expression.
+#[derive(Debug)]
+enum SExpr {
+    Atom(Ident),
+    Int(i64, Pos),
+    List(Vec<SExpr>, Pos),
+}
+
+impl SExpr {
+    fn pos(&self) -> Pos {
+        match self {
+            SExpr::Atom(ident) => ident.pos,
+            SExpr::Int(_, pos) | SExpr::List(_, pos) => *pos,
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, String>;
+
+fn error<T>(pos: Pos, msg: impl Into<String>) -> ParseResult<T> {
+    Err(format!("{}: {}", pos, msg.into()))
+}
+
+/// Splits `src` into S-expressions.
+fn read(src: &str) -> ParseResult<Vec<SExpr>> {
+    let mut stack: Vec<(Vec<SExpr>, Pos)> = vec![(Vec::new(), Pos { line: 0, col: 0 })];
+    let mut chars = src.chars().peekable();
+    let (mut line, mut col) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let pos = Pos { line, col };
+        if c == '\n' {
+            chars.next();
+            line += 1;
+            col = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            col += 1;
+            continue;
+        }
+        match c {
+            ';' => {
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '(' => {
+                chars.next();
+                col += 1;
+                stack.push((Vec::new(), pos));
+            }
+            ')' => {
+                chars.next();
+                col += 1;
+                if stack.len() == 1 {
+                    return error(pos, "unbalanced `)`");
+                }
+                let (list, start) = stack.pop().unwrap();
+                stack.last_mut().unwrap().0.push(SExpr::List(list, start));
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == ';' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                    col += 1;
+                }
+                let sexpr = if token.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+                    match token.parse() {
+                        Ok(n) => SExpr::Int(n, pos),
+                        Err(_) => return error(pos, format!("invalid integer `{}`", token)),
+                    }
+                } else if token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+                {
+                    SExpr::Atom(Ident { name: token, pos })
+                } else {
+                    return error(pos, format!("invalid token `{}`", token));
+                };
+                stack.last_mut().unwrap().0.push(sexpr);
+            }
+        }
+    }
+
+    if stack.len() > 1 {
+        return error(stack.last().unwrap().1, "unclosed `(`");
+    }
+    Ok(stack.pop().unwrap().0)
+}
+
+fn ident(sexpr: &SExpr, what: &str) -> ParseResult<Ident> {
+    match sexpr {
+        SExpr::Atom(ident) if !ident.name.contains('.') => Ok(ident.clone()),
+        _ => error(sexpr.pos(), format!("expected {}", what)),
+    }
+}
+
+fn idents(sexprs: &[SExpr], what: &str) -> ParseResult<Vec<Ident>> {
+    sexprs.iter().map(|s| ident(s, what)).collect()
+}
+
+/// Parses a list of identifiers, or a single identifier standing for a list
+/// of one.
+fn ident_list(sexpr: &SExpr, what: &str) -> ParseResult<Vec<Ident>> {
+    match sexpr {
+        SExpr::List(items, _) => idents(items, what),
+        _ => Ok(vec![ident(sexpr, what)?]),
+    }
+}
+
+fn pattern(sexpr: &SExpr) -> ParseResult<Pattern> {
+    match sexpr {
+        SExpr::Atom(ident) if ident.name == "_" => Ok(Pattern::Wildcard),
+        SExpr::Atom(_) => Ok(Pattern::Var(ident(sexpr, "a variable")?)),
+        SExpr::List(items, pos) => match items.split_first() {
+            Some((head, args)) => Ok(Pattern::Term {
+                head: ident(head, "an extractor or instruction name")?,
+                args: args.iter().map(pattern).collect::<ParseResult<_>>()?,
+            }),
+            None => error(*pos, "empty pattern"),
+        },
+        SExpr::Int(_, pos) => error(*pos, "integer literals can't be matched"),
+    }
+}
+
+fn expr(sexpr: &SExpr) -> ParseResult<Expr> {
+    match sexpr {
+        SExpr::Atom(ident) => match ident.name.find('.') {
+            Some(dot) => Ok(Expr::Const {
+                ty: Ident {
+                    name: ident.name[..dot].to_string(),
+                    pos: ident.pos,
+                },
+                variant: Ident {
+                    name: ident.name[dot + 1..].to_string(),
+                    pos: Pos {
+                        line: ident.pos.line,
+                        col: ident.pos.col + dot + 1,
+                    },
+                },
+            }),
+            None => Ok(Expr::Var(ident.clone())),
+        },
+        SExpr::List(items, pos) => match items.split_first() {
+            Some((SExpr::Atom(head), rest)) if head.name == "let" => match rest {
+                [SExpr::List(bindings, _), body] => Ok(Expr::Let {
+                    bindings: bindings
+                        .iter()
+                        .map(|binding| match binding {
+                            SExpr::List(pair, _) if pair.len() == 2 => {
+                                Ok((ident(&pair[0], "a variable")?, expr(&pair[1])?))
+                            }
+                            _ => error(binding.pos(), "expected `(name expr)`"),
+                        })
+                        .collect::<ParseResult<_>>()?,
+                    body: Box::new(expr(body)?),
+                }),
+                _ => error(*pos, "expected `(let ((name expr) ...) body)`"),
+            },
+            Some((head, args)) => Ok(Expr::Call {
+                head: ident(head, "a constructor name")?,
+                args: args.iter().map(expr).collect::<ParseResult<_>>()?,
+            }),
+            None => error(*pos, "empty expression"),
+        },
+        SExpr::Int(_, pos) => error(*pos, "integer literals aren't supported in expressions"),
+    }
+}
+
+fn def(sexpr: &SExpr) -> ParseResult<Def> {
+    let (items, pos) = match sexpr {
+        SExpr::List(items, pos) => (items, *pos),
+        _ => return error(sexpr.pos(), "expected a definition"),
+    };
+    let keyword = match items.first() {
+        Some(SExpr::Atom(ident)) => ident.name.as_str(),
+        _ => return error(pos, "expected a definition"),
+    };
+    let args = &items[1..];
+    match (keyword, args) {
+        ("type", [name]) => Ok(Def::Type(ident(name, "a type name")?)),
+        ("typeclass", [name, types @ ..]) => Ok(Def::TypeClass {
+            name: ident(name, "a typeclass name")?,
+            types: idents(types, "a type name")?,
+        }),
+        ("extractor", [name, arg, outputs]) => Ok(Def::Extractor {
+            name: ident(name, "an extractor name")?,
+            arg: ident(arg, "a type name")?,
+            outputs: ident_list(outputs, "a type name")?,
+        }),
+        ("constructor", [name, SExpr::List(args, _), ret]) => Ok(Def::Constructor {
+            name: ident(name, "a constructor name")?,
+            args: idents(args, "a type name")?,
+            ret: ident(ret, "a type name")?,
+        }),
+        ("lower", [opcode, types @ ..]) => Ok(Def::Lower {
+            opcode: ident(opcode, "an instruction name")?,
+            types: idents(types, "a type or typeclass name")?,
+        }),
+        ("rule", rest) => {
+            let (prio, rest) = match rest {
+                [SExpr::Int(prio, _), rest @ ..] => (*prio, rest),
+                _ => (0, rest),
+            };
+            match rest {
+                [types, pat, body] => Ok(Def::Rule(Rule {
+                    pos,
+                    prio,
+                    types: ident_list(types, "a type or typeclass name")?,
+                    pattern: pattern(pat)?,
+                    expr: expr(body)?,
+                })),
+                _ => error(pos, "expected `(rule [priority] types pattern expr)`"),
+            }
+        }
+        ("type", _) | ("typeclass", _) | ("extractor", _) | ("constructor", _) | ("lower", _) => {
+            error(pos, format!("malformed `{}` definition", keyword))
+        }
+        _ => error(pos, format!("unknown definition `{}`", keyword)),
+    }
+}
+
+/// Parses the definitions in the rules file `src`.
+pub(crate) fn parse(src: &str) -> ParseResult<Vec<Def>> {
+    read(src)?.iter().map(def).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(src: &str) -> String {
+        parse(src).unwrap_err()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let defs = parse(
+            r#"
+            ; A comment.
+            (type Reg)
+            (rule -1 (I32 I64) (iadd (simm32 k) _)
+                  (let ((r (put_in_reg k))) (emit Op.Add r)))
+            "#,
+        )
+        .unwrap();
+        assert_eq!(defs.len(), 2);
+        match &defs[1] {
+            Def::Rule(rule) => {
+                assert_eq!(rule.prio, -1);
+                assert_eq!(rule.pos, Pos { line: 4, col: 13 });
+                assert_eq!(rule.types.len(), 2);
+                match &rule.pattern {
+                    Pattern::Term { head, args } => {
+                        assert_eq!(head.name, "iadd");
+                        assert!(matches!(args[1], Pattern::Wildcard));
+                    }
+                    _ => panic!("expected a term"),
+                }
+                match &rule.expr {
+                    Expr::Let { bindings, body } => {
+                        assert_eq!(bindings[0].0.name, "r");
+                        match &**body {
+                            Expr::Call { args, .. } => match &args[0] {
+                                Expr::Const { ty, variant } => {
+                                    assert_eq!(ty.name, "Op");
+                                    assert_eq!(variant.name, "Add");
+                                    assert_eq!(variant.pos, Pos { line: 5, col: 54 });
+                                }
+                                _ => panic!("expected a constant"),
+                            },
+                            _ => panic!("expected a call"),
+                        }
+                    }
+                    _ => panic!("expected a let"),
+                }
+            }
+            _ => panic!("expected a rule"),
+        }
+    }
+
+    #[test]
+    fn reports_positions() {
+        assert_eq!(parse_err("(type Reg"), "1:1: unclosed `(`");
+        assert_eq!(parse_err("\n  )"), "2:3: unbalanced `)`");
+        assert_eq!(parse_err("(frob x)"), "1:1: unknown definition `frob`");
+        assert_eq!(
+            parse_err("(rule I32 (iadd x) 3)"),
+            "1:20: integer literals aren't supported in expressions"
+        );
+        assert_eq!(parse_err("(type $x)"), "1:7: invalid token `$x`");
+    }
+}
@@ -101,6 +101,24 @@ fn define_control_flow(
         );
     }
 
+    ig.push(
+        Inst::new(
+            "br_unwind",
+            r#"
+        Landing pad of the preceding call.
+
+        This must directly follow a `call` or `call_indirect`. If the callee
+        returns normally, execution continues with the next instruction. If
+        an exception unwinds the stack through the call instead, the unwinder
+        resumes execution at the start of `block`, with all registers
+        clobbered.
+        "#,
+            &formats.jump,
+        )
+        .operands_in(vec![block, args])
+        .is_branch(true),
+    );
+
     let iB = &TypeVar::new(
         "iB",
         "A scalar integer type",
@@ -473,6 +491,49 @@ fn define_control_flow(
         .is_call(true),
     );
 
+    let FN = &Operand::new("FN", &entities.func_ref)
+        .with_doc("function to call, declared by `function`");
+    let args = &Operand::new("args", &entities.varargs).with_doc("call arguments");
+    ig.push(
+        Inst::new(
+            "return_call",
+            r#"
+        Direct tail call.
+
+        Transfer control to a function which has been declared in the
+        preamble, replacing the current function's frame, so that the callee
+        returns directly to the current function's caller. The argument types
+        must match the function's signature, and the function's return types
+        and calling convention must match the current function's.
+        "#,
+            &formats.call,
+        )
+        .operands_in(vec![FN, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
+    let SIG = &Operand::new("SIG", &entities.sig_ref).with_doc("function signature");
+    let callee = &Operand::new("callee", iAddr).with_doc("address of function to call");
+    let args = &Operand::new("args", &entities.varargs).with_doc("call arguments");
+    ig.push(
+        Inst::new(
+            "return_call_indirect",
+            r#"
+        Indirect tail call.
+
+        Transfer control to the function pointed to by `callee`, replacing the
+        current function's frame, like `return_call`. The called function must
+        match the specified signature, and the signature's return types and
+        calling convention must match the current function's.
+        "#,
+            &formats.call_indirect,
+        )
+        .operands_in(vec![SIG, callee, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
     let FN = &Operand::new("FN", &entities.func_ref)
         .with_doc("function to call, declared by `function`");
     let addr = &Operand::new("addr", iAddr);
@@ -164,6 +164,25 @@ pub(crate) fn define() -> SettingGroup {
         false,
     );
 
+    settings.add_bool(
+        "enable_growing_tail_calls",
+        "Allow tail calls to pass more stack arguments than the caller received.",
+        r#"
+            Without this, the stack arguments of a `return_call` or
+            `return_call_indirect` must fit in the caller's own incoming
+            argument area. With it, a function that makes such a tail call
+            enlarges that area in its prologue, and a tail call leaves the
+            stack pointer just below the callee's arguments, which end where
+            the caller's did. A callee may then return with the stack pointer
+            moved, so every call recomputes it from the frame pointer after
+            returning.
+
+            All code that calls or tail calls each other must agree on this
+            setting.
+        "#,
+        false,
+    );
+
     settings.add_enum(
         "tls_model",
         "Defines the model used to perform TLS accesses.",
@@ -60,6 +60,8 @@ pub enum Reloc {
     Arm64Call,
     /// RISC-V call target
     RiscvCall,
+    /// RISC-V call target via an `auipc`+`jalr` pair (R_RISCV_CALL_PLT)
+    RiscvCallPlt,
     /// s390x PC-relative 4-byte offset
     S390xPCRel32Dbl,
 
@@ -94,6 +96,7 @@ impl fmt::Display for Reloc {
             Self::X86CallPLTRel4 => write!(f, "CallPLTRel4"),
             Self::X86GOTPCRel4 => write!(f, "GOTPCRel4"),
             Self::Arm32Call | Self::Arm64Call | Self::RiscvCall => write!(f, "Call"),
+            Self::RiscvCallPlt => write!(f, "CallPlt"),
 
             Self::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
             Self::MachOX86_64Tlv => write!(f, "MachOX86_64Tlv"),
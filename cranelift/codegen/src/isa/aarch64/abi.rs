@@ -683,6 +683,37 @@ impl ABIMachineSpec for AArch64MachineDeps {
         insts
     }
 
+    fn gen_grow_incoming_args(size: u32) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+
+        // Copy the frame record `size` bytes down and point FP at the copy:
+        // `sub sp, sp, #size`, `ldp x16, x17, [fp]`, `stp x16, x17, [sp]`
+        // and `mov fp, sp`.
+        insts.extend(Inst::gen_set_sp(stack_reg(), -(size as i64)));
+        insts.push(Inst::LoadP64 {
+            rt: writable_spilltmp_reg(),
+            rt2: writable_tmp2_reg(),
+            mem: PairAMode::SignedOffset(fp_reg(), SImm7Scaled::zero(types::I64)),
+            flags: MemFlags::trusted(),
+        });
+        insts.push(Inst::StoreP64 {
+            rt: spilltmp_reg(),
+            rt2: tmp2_reg(),
+            mem: PairAMode::SignedOffset(stack_reg(), SImm7Scaled::zero(types::I64)),
+            flags: MemFlags::trusted(),
+        });
+        insts.push(Inst::AluRRImm12 {
+            alu_op: ALUOp::Add64,
+            rd: writable_fp_reg(),
+            rn: stack_reg(),
+            imm12: Imm12 {
+                bits: 0,
+                shift12: false,
+            },
+        });
+        insts
+    }
+
     fn gen_probestack(_: u32) -> SmallInstVec<Self::I> {
         // TODO: implement if we ever require stack probes on an AArch64 host
         // (unlikely unless Lucet is ported)
@@ -1113,6 +1144,49 @@ impl ABIMachineSpec for AArch64MachineDeps {
         insts
     }
 
+    fn gen_return_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        _callee_conv: isa::CallConv,
+        sp_adjust: u32,
+    ) -> SmallInstVec<Inst> {
+        // x9 is caller-saved and never holds an argument, so the epilogue
+        // leaves it alone; x16 and x17 may be used by the epilogue itself.
+        let tmp = writable_xreg(9);
+        let uses = uses.into_boxed_slice();
+        let mut insts = SmallVec::new();
+        match &dest {
+            &CallDest::ExtName(ref name, RelocDistance::Near) => {
+                insts.push(Inst::ReturnCall {
+                    dest: Box::new(name.clone()),
+                    uses,
+                    sp_adjust,
+                });
+            }
+            &CallDest::ExtName(ref name, RelocDistance::Far) => {
+                insts.push(Inst::LoadExtName {
+                    rd: tmp,
+                    name: Box::new(name.clone()),
+                    offset: 0,
+                });
+                insts.push(Inst::ReturnCallInd {
+                    rn: tmp.to_reg(),
+                    uses,
+                    sp_adjust,
+                });
+            }
+            &CallDest::Reg(reg) => {
+                insts.push(Inst::gen_move(tmp, *reg, I64));
+                insts.push(Inst::ReturnCallInd {
+                    rn: tmp.to_reg(),
+                    uses,
+                    sp_adjust,
+                });
+            }
+        }
+        insts
+    }
+
     fn gen_memcpy(
         call_conv: isa::CallConv,
         dst: Reg,
@@ -1178,6 +1252,23 @@ impl ABIMachineSpec for AArch64MachineDeps {
         caller_saved
     }
 
+    fn get_regs_clobbered_by_unwind(call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>> {
+        let mut callee_saved = Vec::new();
+        for i in 0..29 {
+            let x = writable_xreg(i);
+            if is_reg_saved_in_prologue(call_conv_of_callee, x.to_reg().to_real_reg()) {
+                callee_saved.push(x);
+            }
+        }
+        for i in 0..32 {
+            let v = writable_vreg(i);
+            if is_reg_saved_in_prologue(call_conv_of_callee, v.to_reg().to_real_reg()) {
+                callee_saved.push(v);
+            }
+        }
+        callee_saved
+    }
+
     fn get_ext_mode(
         call_conv: isa::CallConv,
         specified: ir::ArgumentExtension,
@@ -1261,6 +1352,13 @@ fn is_reg_saved_in_prologue(call_conv: isa::CallConv, r: RealReg) -> bool {
     }
 }
 
+/// Does a call with the given defs clobber registers that a function with the
+/// given ABI has to save in its prologue?
+pub(crate) fn clobbers_callee_saves(call_conv: isa::CallConv, defs: &[Writable<Reg>]) -> bool {
+    defs.iter()
+        .any(|r| is_reg_saved_in_prologue(call_conv, r.to_reg().to_real_reg()))
+}
+
 /// Return the set of all integer and vector registers that must be saved in the
 /// prologue and restored in the epilogue, given the set of all registers
 /// written by the function's body.
@@ -2416,6 +2416,12 @@ impl MachInstEmit for Inst {
                 // Emit the jump itself.
                 sink.put4(enc_jump26(0b000101, dest.as_offset26_or_zero()));
             }
+            &Inst::JumpUnwind { dest, landing_pad } => {
+                // The landing pad expects SP where it is between calls.
+                let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+                sink.add_exception_handler(landing_pad.as_label().unwrap(), fp_to_sp as u32);
+                Inst::Jump { dest }.emit(sink, emit_info, state);
+            }
             &Inst::Ret => {
                 sink.put4(0xd65f03c0);
             }
@@ -2432,6 +2438,7 @@ impl MachInstEmit for Inst {
                 if info.opcode.is_call() {
                     sink.add_call_site(loc, info.opcode);
                 }
+                emit_restore_sp_after_call(sink, emit_info, state);
             }
             &Inst::CallInd { ref info } => {
                 if let Some(s) = state.take_stack_map() {
@@ -2442,6 +2449,30 @@ impl MachInstEmit for Inst {
                 if info.opcode.is_call() {
                     sink.add_call_site(loc, info.opcode);
                 }
+                emit_restore_sp_after_call(sink, emit_info, state);
+            }
+            &Inst::ReturnCall {
+                ref dest,
+                sp_adjust,
+                ..
+            } => {
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        inst.emit(sink, emit_info, state);
+                    }
+                }
+                let loc = state.cur_srcloc();
+                sink.add_reloc(loc, Reloc::Arm64Call, dest, 0);
+                sink.put4(enc_jump26(0b000101, 0));
+            }
+            &Inst::ReturnCallInd { rn, sp_adjust, .. } => {
+                if sp_adjust > 0 {
+                    debug_assert_ne!(rn, spilltmp_reg());
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        inst.emit(sink, emit_info, state);
+                    }
+                }
+                sink.put4(0b1101011_0000_11111_000000_00000_00000 | (machreg_to_gpr(rn) << 5));
             }
             &Inst::CondBr {
                 taken,
@@ -2743,3 +2774,20 @@ impl MachInstEmit for Inst {
         self.print_with_state(mb_rru, state)
     }
 }
+
+/// With growing tail calls, a callee may return with SP moved, so recompute it
+/// from FP right after a call returns. This has to be part of the call itself,
+/// as the register allocator may reload spilled values right after it.
+fn emit_restore_sp_after_call(
+    sink: &mut MachBuffer<Inst>,
+    emit_info: &EmitInfo,
+    state: &mut EmitState,
+) {
+    if !emit_info.flags().enable_growing_tail_calls() {
+        return;
+    }
+    let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+    for inst in Inst::gen_set_sp(fp_reg(), -fp_to_sp) {
+        inst.emit(sink, emit_info, state);
+    }
+}
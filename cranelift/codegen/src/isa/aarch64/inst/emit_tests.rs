@@ -4953,6 +4953,34 @@ fn test_aarch64_binemit() {
         "blr x10",
     ));
 
+    insns.push((
+        Inst::ReturnCallInd {
+            rn: xreg(9),
+            uses: Box::new([]),
+            sp_adjust: 0,
+        },
+        "20011FD6",
+        "br x9",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rn: xreg(9),
+            uses: Box::new([]),
+            sp_adjust: 32,
+        },
+        "FF83009120011FD6",
+        "add sp, sp, #32 ; br x9",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rn: xreg(9),
+            uses: Box::new([]),
+            sp_adjust: 4097,
+        },
+        "300082D2FF63308B20011FD6",
+        "movz x16, #4097 ; add sp, sp, x16, UXTX ; br x9",
+    ));
+
     insns.push((
         Inst::IndirectBr {
             rn: xreg(3),
@@ -6080,6 +6108,33 @@ fn test_aarch64_binemit() {
     }
 }
 
+#[test]
+fn test_aarch64_binemit_growing_tail_calls() {
+    // With growing tail calls, every call recomputes SP from FP once it
+    // returns, as part of the call instruction itself.
+    use crate::settings::Configurable;
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_growing_tail_calls").unwrap();
+    let emit_info = EmitInfo::new(settings::Flags::new(flag_builder));
+
+    let insn = Inst::CallInd {
+        info: Box::new(CallIndInfo {
+            rn: xreg(10),
+            uses: Vec::new(),
+            defs: Vec::new(),
+            opcode: Opcode::CallIndirect,
+            caller_callconv: CallConv::SystemV,
+            callee_callconv: CallConv::SystemV,
+        }),
+    };
+    let mut sink = test_utils::TestCodeSink::new();
+    let mut buffer = MachBuffer::new();
+    insn.emit(&mut buffer, &emit_info, &mut Default::default());
+    let buffer = buffer.finish();
+    buffer.emit(&mut sink);
+    assert_eq!("40013FD6BF030091", sink.stringify());
+}
+
 #[test]
 fn test_cond_invert() {
     for cond in vec![
@@ -30,7 +30,7 @@ pub mod args;
 pub use self::args::*;
 pub mod emit;
 pub use self::emit::*;
-use crate::isa::aarch64::abi::AArch64MachineDeps;
+use crate::isa::aarch64::abi::{clobbers_callee_saves, AArch64MachineDeps};
 
 pub mod unwind;
 
@@ -1236,6 +1236,21 @@ pub enum Inst {
     CallInd {
         info: Box<CallIndInfo>,
     },
+    /// A tail call, following the epilogue: a `b` with a `Reloc::Arm64Call` relocation, so
+    /// like `Call` this only allows a +/- 128MB offset. SP is first moved `sp_adjust` bytes up,
+    /// using x16 if that doesn't fit an immediate.
+    ReturnCall {
+        dest: Box<ExternalName>,
+        uses: Box<[Reg]>,
+        sp_adjust: u32,
+    },
+    /// An indirect tail call, following the epilogue. `rn` must not be restored by the
+    /// epilogue, and must not be x16.
+    ReturnCallInd {
+        rn: Reg,
+        uses: Box<[Reg]>,
+        sp_adjust: u32,
+    },
 
     // ---- branches (exactly one must appear at end of BB) ----
     /// A machine return instruction.
@@ -1250,6 +1265,13 @@ pub enum Inst {
         dest: BranchTarget,
     },
 
+    /// An unconditional branch after a call, recording `landing_pad` as where
+    /// execution resumes instead if the stack unwinds through that call.
+    JumpUnwind {
+        dest: BranchTarget,
+        landing_pad: BranchTarget,
+    },
+
     /// A conditional branch. Contains two targets; at emission time, both are emitted, but
     /// the MachBuffer knows to truncate the trailing branch if fallthrough. We optimize the
     /// choice of taken/not_taken (inverting the branch polarity as needed) based on the
@@ -1398,6 +1420,40 @@ fn inst_size_test() {
 }
 
 impl Inst {
+    /// Create instructions that set SP to `rn` plus `offset`, using x16 if the offset doesn't
+    /// fit an immediate.
+    pub fn gen_set_sp(rn: Reg, offset: i64) -> SmallVec<[Inst; 4]> {
+        let abs_offset = if offset < 0 {
+            -offset as u64
+        } else {
+            offset as u64
+        };
+        let alu_op = if offset < 0 {
+            ALUOp::Sub64
+        } else {
+            ALUOp::Add64
+        };
+        if let Some(imm12) = Imm12::maybe_from_u64(abs_offset) {
+            smallvec![Inst::AluRRImm12 {
+                alu_op,
+                rd: writable_stack_reg(),
+                rn,
+                imm12,
+            }]
+        } else {
+            let tmp = writable_spilltmp_reg();
+            let mut insts = Inst::load_constant(tmp, abs_offset);
+            insts.push(Inst::AluRRRExtend {
+                alu_op,
+                rd: writable_stack_reg(),
+                rn,
+                rm: tmp.to_reg(),
+                extendop: ExtendOp::UXTX,
+            });
+            insts
+        }
+    }
+
     /// Create an instruction that loads a constant, using one of serveral options (MOVZ, MOVN,
     /// logical immediate, or constant pool).
     pub fn load_constant(rd: Writable<Reg>, value: u64) -> SmallVec<[Inst; 4]> {
@@ -2210,7 +2266,8 @@ fn aarch64_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_def(rd);
             collector.add_use(rn);
         }
-        &Inst::Jump { .. } | &Inst::Ret | &Inst::EpiloguePlaceholder => {}
+        &Inst::Jump { .. } | &Inst::JumpUnwind { .. } | &Inst::Ret | &Inst::EpiloguePlaceholder => {
+        }
         &Inst::Call { ref info, .. } => {
             collector.add_uses(&*info.uses);
             collector.add_defs(&*info.defs);
@@ -2220,6 +2277,13 @@ fn aarch64_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_defs(&*info.defs);
             collector.add_use(info.rn);
         }
+        &Inst::ReturnCall { ref uses, .. } => {
+            collector.add_uses(uses);
+        }
+        &Inst::ReturnCallInd { rn, ref uses, .. } => {
+            collector.add_uses(uses);
+            collector.add_use(rn);
+        }
         &Inst::CondBr { ref kind, .. } => match kind {
             CondBrKind::Zero(rt) | CondBrKind::NotZero(rt) => {
                 collector.add_use(*rt);
@@ -3052,7 +3116,7 @@ fn aarch64_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             map_def(mapper, rd);
             map_use(mapper, rn);
         }
-        &mut Inst::Jump { .. } => {}
+        &mut Inst::Jump { .. } | &mut Inst::JumpUnwind { .. } => {}
         &mut Inst::Call { ref mut info } => {
             for r in info.uses.iter_mut() {
                 map_use(mapper, r);
@@ -3071,6 +3135,21 @@ fn aarch64_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             }
             map_use(mapper, &mut info.rn);
         }
+        &mut Inst::ReturnCall { ref mut uses, .. } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+        }
+        &mut Inst::ReturnCallInd {
+            ref mut rn,
+            ref mut uses,
+            ..
+        } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+            map_use(mapper, rn);
+        }
         &mut Inst::CondBr { ref mut kind, .. } => {
             map_br(mapper, kind);
         }
@@ -3153,13 +3232,20 @@ impl MachInst for Inst {
         // that the callee clobbers, the caller is also allowed to clobber. This
         // both saves work and enables us to more precisely follow the
         // half-caller-save, half-callee-save SysV ABI for some vector
-        // registers.
+        // registers. That doesn't hold for a call that an exception can unwind
+        // through to a landing pad, which also clobbers the callee-saves.
         //
         // See the note in [crate::isa::aarch64::abi::is_caller_save_reg] for
         // more information on this ABI-implementation hack.
         match self {
-            &Inst::Call { ref info } => info.caller_callconv != info.callee_callconv,
-            &Inst::CallInd { ref info } => info.caller_callconv != info.callee_callconv,
+            &Inst::Call { ref info } => {
+                info.caller_callconv != info.callee_callconv
+                    || clobbers_callee_saves(info.caller_callconv, &info.defs)
+            }
+            &Inst::CallInd { ref info } => {
+                info.caller_callconv != info.callee_callconv
+                    || clobbers_callee_saves(info.caller_callconv, &info.defs)
+            }
             _ => true,
         }
     }
@@ -3167,7 +3253,11 @@ impl MachInst for Inst {
     fn is_term<'a>(&'a self) -> MachTerminator<'a> {
         match self {
             &Inst::Ret | &Inst::EpiloguePlaceholder => MachTerminator::Ret,
+            &Inst::ReturnCall { .. } | &Inst::ReturnCallInd { .. } => MachTerminator::TailCall,
             &Inst::Jump { dest } => MachTerminator::Uncond(dest.as_label().unwrap()),
+            &Inst::JumpUnwind { dest, landing_pad } => {
+                MachTerminator::Cond(dest.as_label().unwrap(), landing_pad.as_label().unwrap())
+            }
             &Inst::CondBr {
                 taken, not_taken, ..
             } => MachTerminator::Cond(taken.as_label().unwrap(), not_taken.as_label().unwrap()),
@@ -4508,12 +4598,43 @@ impl Inst {
                 let rn = info.rn.show_rru(mb_rru);
                 format!("blr {}", rn)
             }
+            &Inst::ReturnCall { sp_adjust, .. } => {
+                let mut ret = String::new();
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        ret.push_str(&inst.show_rru(mb_rru));
+                        ret.push_str(" ; ");
+                    }
+                }
+                ret.push_str("b 0");
+                ret
+            }
+            &Inst::ReturnCallInd { rn, sp_adjust, .. } => {
+                let mut ret = String::new();
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        ret.push_str(&inst.show_rru(mb_rru));
+                        ret.push_str(" ; ");
+                    }
+                }
+                let rn = rn.show_rru(mb_rru);
+                ret.push_str(&format!("br {}", rn));
+                ret
+            }
             &Inst::Ret => "ret".to_string(),
             &Inst::EpiloguePlaceholder => "epilogue placeholder".to_string(),
             &Inst::Jump { ref dest } => {
                 let dest = dest.show_rru(mb_rru);
                 format!("b {}", dest)
             }
+            &Inst::JumpUnwind {
+                ref dest,
+                ref landing_pad,
+            } => {
+                let dest = dest.show_rru(mb_rru);
+                let landing_pad = landing_pad.show_rru(mb_rru);
+                format!("b {} ; unwind to {}", dest, landing_pad)
+            }
             &Inst::CondBr {
                 ref taken,
                 ref not_taken,
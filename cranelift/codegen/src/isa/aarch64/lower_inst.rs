@@ -2046,6 +2046,9 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
                 _ => unreachable!(),
             };
 
+            if ctx.has_landing_pad(insn) {
+                abi.unwinds_to_landing_pad();
+            }
             abi.emit_stack_pre_adjust(ctx);
             assert!(inputs.len() == abi.num_args());
             for i in abi.get_copy_to_arg_order() {
@@ -2061,6 +2064,41 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
             abi.emit_stack_post_adjust(ctx);
         }
 
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            let caller_conv = ctx.abi().call_conv();
+            let (mut abi, inputs) = match op {
+                Opcode::ReturnCall => {
+                    let (extname, dist) = ctx.call_target(insn).unwrap();
+                    let extname = extname.clone();
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() == sig.params.len());
+                    (
+                        AArch64ABICaller::from_func(sig, &extname, dist, caller_conv, flags)?,
+                        &inputs[..],
+                    )
+                }
+                Opcode::ReturnCallIndirect => {
+                    let ptr = put_input_in_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend64);
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() - 1 == sig.params.len());
+                    (
+                        AArch64ABICaller::from_ptr(sig, ptr, op, caller_conv, flags)?,
+                        &inputs[1..],
+                    )
+                }
+                _ => unreachable!(),
+            };
+
+            abi.prepare_tail_call(ctx)?;
+            assert!(inputs.len() == abi.num_args());
+            for i in abi.get_copy_to_arg_order() {
+                let input = inputs[i];
+                let arg_regs = put_input_in_regs(ctx, input);
+                abi.emit_copy_regs_to_arg(ctx, i, arg_regs);
+            }
+            abi.emit_return_call(ctx);
+        }
+
         Opcode::GetPinnedReg => {
             let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
             ctx.emit(Inst::gen_move(rd, xreg(PINNED_REG), I64));
@@ -2091,6 +2129,7 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
         | Opcode::Fallthrough
         | Opcode::Brz
         | Opcode::Brnz
+        | Opcode::BrUnwind
         | Opcode::BrIcmp
         | Opcode::Brif
         | Opcode::Brff
@@ -3617,6 +3656,12 @@ pub(crate) fn lower_branch<C: LowerCtx<I = Inst>>(
         let not_taken = BranchTarget::Label(targets[1]);
 
         match op0 {
+            Opcode::BrUnwind => {
+                ctx.emit(Inst::JumpUnwind {
+                    dest: not_taken,
+                    landing_pad: taken,
+                });
+            }
             Opcode::Brz | Opcode::Brnz => {
                 let ty = ctx.input_ty(branches[0], 0);
                 let flag_input = InsnInput {
@@ -1,9 +1,49 @@
-//! Implementation of the 32-bit ARM ABI.
+//! Implementation of the 32-bit ARM ABI: AAPCS with the hard-float (VFP)
+//! variant of the procedure call standard.
+//!
+//! The frame layout is:
+//!
+//! ```plain
+//!   (high address)
+//!
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | stack args                |
+//!                              | (accessed via FP + 8)     |
+//! SP at function entry ----->  +---------------------------+
+//!                              | LR                        |
+//! FP after prologue -------->  | FP at function entry      |
+//!                              +---------------------------+
+//!                              | clobbered r4 - r10        |
+//!                              | (padded to 8 bytes)       |
+//!                              | clobbered d8 - d15        |
+//! unwind-frame base     ---->  | (pushed by prologue)      |
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | spill slots               |
+//!                              | (accessed via nominal SP) |
+//!                              |          ...              |
+//!                              | stack slots               |
+//!                              | (accessed via nominal SP) |
+//! nominal SP --------------->  | (alloc'd by prologue)     |
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | args for call             |
+//! SP ----------------------->  | (alloc'd by call seq)     |
+//!                              +---------------------------+
+//!
+//!   (low address)
+//! ```
+//!
+//! The frame is always set up, even in leaf functions, because function
+//! bodies use LR as a second scratch register.
 
 use crate::ir;
 use crate::ir::types::*;
+use crate::ir::{ExternalName, LibCall};
 use crate::isa;
 use crate::isa::arm32::inst::*;
+use crate::isa::unwind::UnwindInst;
 use crate::machinst::*;
 use crate::settings;
 use crate::{CodegenError, CodegenResult};
@@ -23,6 +63,12 @@ pub(crate) type Arm32ABICaller = ABICallerImpl<Arm32MachineDeps>;
 /// with 32-bit arithmetic: for now, 128 MB.
 static STACK_ARG_RET_SIZE_LIMIT: u64 = 128 * 1024 * 1024;
 
+/// The number of core argument registers, `r0`-`r3`.
+const NUM_CORE_ARG_REGS: u8 = 4;
+
+/// The number of single-precision argument registers, `s0`-`s15`.
+const NUM_VFP_ARG_SREGS: u8 = 16;
+
 /// ARM-specific ABI behavior. This struct just serves as an implementation
 /// point for the trait; it is never actually instantiated.
 pub(crate) struct Arm32MachineDeps;
@@ -37,6 +83,52 @@ impl Into<AMode> for StackAMode {
     }
 }
 
+/// Allocation state of the VFP argument registers: a bitmap of the
+/// single-precision registers in use. The hard-float variant of the AAPCS
+/// back-fills an `f32` into the first free single-precision register, which
+/// may be the odd half of a double-precision register left over by an
+/// earlier `f64`.
+struct VfpArgRegs {
+    used: u16,
+}
+
+impl VfpArgRegs {
+    fn new() -> Self {
+        Self { used: 0 }
+    }
+
+    /// Allocate a register for a value of type `ty`, if there is one left.
+    /// Return values are never back-filled, so that they never end up in an
+    /// odd single-precision register.
+    fn alloc(&mut self, ty: Type, back_fill: bool) -> Option<Reg> {
+        let (width, step) = match ty {
+            F32 if back_fill => (1, 1),
+            F32 => (1, 2),
+            F64 => (2, 2),
+            _ => panic!("Unexpected VFP argument type {:?}", ty),
+        };
+        let mask = (1u16 << width) - 1;
+        let num = (0..NUM_VFP_ARG_SREGS)
+            .step_by(step)
+            .find(|&n| n + width <= NUM_VFP_ARG_SREGS && (self.used >> n) & mask == 0);
+        match num {
+            Some(n) => {
+                self.used |= mask << n;
+                if n % 2 == 0 {
+                    Some(dreg(n / 2))
+                } else {
+                    Some(sreg_hi(n / 2))
+                }
+            }
+            None => {
+                // Once a VFP argument has gone on the stack, all later ones do too.
+                self.used = !0;
+                None
+            }
+        }
+    }
+}
+
 impl ABIMachineSpec for Arm32MachineDeps {
     type I = Inst;
 
@@ -50,58 +142,139 @@ impl ABIMachineSpec for Arm32MachineDeps {
     }
 
     fn compute_arg_locs(
-        _call_conv: isa::CallConv,
+        call_conv: isa::CallConv,
         _flags: &settings::Flags,
         params: &[ir::AbiParam],
         args_or_rets: ArgsOrRets,
         add_ret_area_ptr: bool,
     ) -> CodegenResult<(Vec<ABIArg>, i64, Option<usize>)> {
+        if call_conv.extends_baldrdash() {
+            return Err(CodegenError::Unsupported(format!(
+                "the {} calling convention on arm32",
+                call_conv
+            )));
+        }
+
         let mut next_rreg = 0;
+        let mut vfp_regs = VfpArgRegs::new();
         let mut next_stack: u64 = 0;
         let mut ret = vec![];
-        let mut stack_args = vec![];
 
-        let max_reg_val = 4; // r0-r3
-
-        for i in 0..params.len() {
-            let param = params[i];
+        // In the Wasmtime ABI only one return value can be in registers.
+        let is_wasmtime_ret = call_conv.extends_wasmtime() && args_or_rets == ArgsOrRets::Rets;
+        let mut remaining_reg_vals = if is_wasmtime_ret { 1 } else { params.len() };
+        let back_fill = args_or_rets == ArgsOrRets::Args;
 
+        for param in params {
             // Validate "purpose".
             match &param.purpose {
                 &ir::ArgumentPurpose::VMContext
                 | &ir::ArgumentPurpose::Normal
                 | &ir::ArgumentPurpose::StackLimit
-                | &ir::ArgumentPurpose::SignatureId => {}
+                | &ir::ArgumentPurpose::SignatureId
+                | &ir::ArgumentPurpose::StructReturn => {}
+                &ir::ArgumentPurpose::StructArgument(_) => {
+                    return Err(CodegenError::Unsupported(
+                        "struct arguments on arm32".into(),
+                    ));
+                }
                 _ => panic!(
                     "Unsupported argument purpose {:?} in signature: {:?}",
                     param.purpose, params
                 ),
             }
-            assert!(param.value_type.bits() <= 32);
 
-            if next_rreg < max_reg_val {
-                let reg = rreg(next_rreg);
+            let ty = param.value_type;
+            let (rcs, reg_types) = Inst::rc_for_type(ty)?;
+
+            if remaining_reg_vals > 0 {
+                remaining_reg_vals -= 1;
+                match rcs {
+                    &[RegClass::I32] if next_rreg < NUM_CORE_ARG_REGS => {
+                        ret.push(ABIArg::reg(
+                            rreg(next_rreg).to_real_reg(),
+                            ty,
+                            param.extension,
+                            param.purpose,
+                        ));
+                        next_rreg += 1;
+                        continue;
+                    }
+                    &[RegClass::I32, RegClass::I32] => {
+                        // 64-bit integers go in an even-numbered register
+                        // pair, low half first.
+                        next_rreg = align_to(next_rreg, 2);
+                        if next_rreg < NUM_CORE_ARG_REGS {
+                            let slots = (0..2)
+                                .map(|i| ABIArgSlot::Reg {
+                                    reg: rreg(next_rreg + i).to_real_reg(),
+                                    ty: I32,
+                                    extension: param.extension,
+                                })
+                                .collect();
+                            ret.push(ABIArg::Slots {
+                                slots,
+                                purpose: param.purpose,
+                            });
+                            next_rreg += 2;
+                            continue;
+                        }
+                    }
+                    &[RegClass::F64] => {
+                        if let Some(reg) = vfp_regs.alloc(ty, back_fill) {
+                            ret.push(ABIArg::reg(
+                                reg.to_real_reg(),
+                                ty,
+                                param.extension,
+                                param.purpose,
+                            ));
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+            }
 
-                ret.push(ABIArg::reg(
-                    reg.to_real_reg(),
-                    param.value_type,
-                    param.extension,
-                    param.purpose,
-                ));
-                next_rreg += 1;
+            // Once a core argument has gone on the stack, all later ones do too.
+            if rcs[0] == RegClass::I32 {
+                next_rreg = NUM_CORE_ARG_REGS;
+            }
+
+            // Spill to the stack. Every argument takes a slot of at least 4
+            // bytes, except for return values in the Wasmtime ABI.
+            let size = (ty_bits(ty) / 8) as u64;
+            let size = if is_wasmtime_ret {
+                size
             } else {
-                // Arguments are stored on stack in reversed order.
-                // https://static.docs.arm.com/ihi0042/g/aapcs32.pdf
+                std::cmp::max(size, 4)
+            };
+            debug_assert!(size.is_power_of_two());
+            next_stack = align_to(next_stack, size);
+
+            let slots = reg_types
+                .iter()
+                .copied()
+                .scan(next_stack, |next_stack, ty| {
+                    let offset = *next_stack as i64;
+                    *next_stack += (ty_bits(ty) / 8) as u64;
+                    Some(ABIArgSlot::Stack {
+                        offset,
+                        ty,
+                        extension: param.extension,
+                    })
+                })
+                .collect();
+            ret.push(ABIArg::Slots {
+                slots,
+                purpose: param.purpose,
+            });
 
-                // Stack offset is not known yet. Store param info for later.
-                stack_args.push((param.value_type, param.extension, param.purpose));
-                next_stack += 4;
-            }
+            next_stack += size;
         }
 
         let extra_arg = if add_ret_area_ptr {
             debug_assert!(args_or_rets == ArgsOrRets::Args);
-            if next_rreg < max_reg_val {
+            if next_rreg < NUM_CORE_ARG_REGS {
                 ret.push(ABIArg::reg(
                     rreg(next_rreg).to_real_reg(),
                     I32,
@@ -109,7 +282,8 @@ impl ABIMachineSpec for Arm32MachineDeps {
                     ir::ArgumentPurpose::Normal,
                 ));
             } else {
-                stack_args.push((
+                ret.push(ABIArg::stack(
+                    next_stack as i64,
                     I32,
                     ir::ArgumentExtension::None,
                     ir::ArgumentPurpose::Normal,
@@ -121,20 +295,7 @@ impl ABIMachineSpec for Arm32MachineDeps {
             None
         };
 
-        // Now we can assign proper stack offsets to params.
-        let max_stack = next_stack;
-        for (ty, ext, purpose) in stack_args.into_iter().rev() {
-            next_stack -= 4;
-            ret.push(ABIArg::stack(
-                (max_stack - next_stack) as i64,
-                ty,
-                ext,
-                purpose,
-            ));
-        }
-        assert_eq!(next_stack, 0);
-
-        next_stack = (next_stack + 7) & !7;
+        next_stack = align_to(next_stack, 8);
 
         // To avoid overflow issues, limit the arg/return size to something
         // reasonable -- here, 128 MB.
@@ -284,10 +445,17 @@ impl ABIMachineSpec for Arm32MachineDeps {
         Inst::VirtualSPOffsetAdj { offset }
     }
 
-    fn gen_prologue_frame_setup(_: &settings::Flags) -> SmallInstVec<Inst> {
+    fn gen_prologue_frame_setup(flags: &settings::Flags) -> SmallInstVec<Inst> {
         let mut ret = SmallVec::new();
         let reg_list = vec![fp_reg(), lr_reg()];
         ret.push(Inst::Push { reg_list });
+        if flags.unwind_info() {
+            ret.push(Inst::Unwind {
+                inst: UnwindInst::PushFrameRegs {
+                    offset_upward_to_caller_sp: 8, // FP, LR
+                },
+            });
+        }
         ret.push(Inst::Mov {
             rd: writable_fp_reg(),
             rm: sp_reg(),
@@ -306,6 +474,27 @@ impl ABIMachineSpec for Arm32MachineDeps {
         ret
     }
 
+    fn gen_grow_incoming_args(size: u32) -> SmallInstVec<Inst> {
+        let mut ret = SmallVec::new();
+        // Copy the frame record `size` bytes down through ip and point fp at
+        // the copy.
+        let tmp = writable_ip_reg();
+        ret.extend(Self::gen_sp_reg_adjust(-(size as i32)));
+        for &offset in &[0, 4] {
+            ret.push(Inst::gen_load(tmp, AMode::RegOffset(fp_reg(), offset), I32));
+            ret.push(Inst::gen_store(
+                tmp.to_reg(),
+                AMode::RegOffset(sp_reg(), offset),
+                I32,
+            ));
+        }
+        ret.push(Inst::Mov {
+            rd: writable_fp_reg(),
+            rm: sp_reg(),
+        });
+        ret
+    }
+
     fn gen_probestack(_: u32) -> SmallInstVec<Self::I> {
         // TODO: implement if we ever require stack probes on ARM32 (unlikely
         // unless Lucet is ported)
@@ -316,32 +505,70 @@ impl ABIMachineSpec for Arm32MachineDeps {
     /// nominal SP offset; caller will do that.
     fn gen_clobber_save(
         _call_conv: isa::CallConv,
-        _setup_frame: bool,
-        _flags: &settings::Flags,
+        setup_frame: bool,
+        flags: &settings::Flags,
         clobbered_callee_saves: &Vec<Writable<RealReg>>,
         fixed_frame_storage_size: u32,
         _outgoing_args_size: u32,
     ) -> (u64, SmallVec<[Inst; 16]>) {
+        let (core_regs, vfp_regs) = clobber_save_lists(clobbered_callee_saves);
+        let clobber_size = 4 * core_regs.len() as u32 + 8 * vfp_regs.len() as u32;
         let mut insts = SmallVec::new();
-        if fixed_frame_storage_size > 0 {
-            insts.extend(Self::gen_sp_reg_adjust(-(fixed_frame_storage_size as i32)).into_iter());
-        }
-        let mut clobbered_vec: Vec<_> = clobbered_callee_saves
-            .into_iter()
-            .map(|r| r.to_reg().to_reg())
-            .collect();
-        if clobbered_vec.len() % 2 == 1 {
-            // For alignment purposes.
-            clobbered_vec.push(ip_reg());
+
+        if flags.unwind_info() && setup_frame {
+            // The *unwind* frame (but not the actual frame) starts at the
+            // clobbers, just below the saved FP/LR pair.
+            insts.push(Inst::Unwind {
+                inst: UnwindInst::DefineNewFrame {
+                    offset_downward_to_clobbers: clobber_size,
+                    offset_upward_to_caller_sp: 8, // FP, LR
+                },
+            });
         }
-        let stack_used = clobbered_vec.len() * 4;
-        if !clobbered_vec.is_empty() {
+
+        // `push` stores the lowest-numbered register at the lowest address,
+        // and the core registers end up above the VFP registers.
+        if !core_regs.is_empty() {
             insts.push(Inst::Push {
-                reg_list: clobbered_vec,
+                reg_list: core_regs.clone(),
+            });
+        }
+        if !vfp_regs.is_empty() {
+            insts.push(Inst::FpuPush {
+                reg_list: vfp_regs.clone(),
             });
         }
+        if flags.unwind_info() {
+            let vfp_size = 8 * vfp_regs.len() as u32;
+            let saves = vfp_regs
+                .iter()
+                .enumerate()
+                .map(|(i, &reg)| (8 * i as u32, reg))
+                .chain(
+                    core_regs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &reg)| (vfp_size + 4 * i as u32, reg)),
+                );
+            for (clobber_offset, reg) in saves {
+                // The padding register needn't be restored by the unwinder.
+                if reg == lr_reg() {
+                    continue;
+                }
+                insts.push(Inst::Unwind {
+                    inst: UnwindInst::SaveReg {
+                        clobber_offset,
+                        reg: reg.to_real_reg(),
+                    },
+                });
+            }
+        }
 
-        (stack_used as u64, insts)
+        if fixed_frame_storage_size > 0 {
+            insts.extend(Self::gen_sp_reg_adjust(-(fixed_frame_storage_size as i32)));
+        }
+
+        (clobber_size.into(), insts)
     }
 
     fn gen_clobber_restore(
@@ -352,17 +579,34 @@ impl ABIMachineSpec for Arm32MachineDeps {
         _outgoing_args_size: u32,
     ) -> SmallVec<[Inst; 16]> {
         let mut insts = SmallVec::new();
-        let clobbered_vec = Self::get_clobbered_callee_saves(call_conv, clobbers);
-        let mut clobbered_vec: Vec<_> = clobbered_vec
-            .into_iter()
-            .map(|r| Writable::from_reg(r.to_reg().to_reg()))
-            .collect();
-        if clobbered_vec.len() % 2 == 1 {
-            clobbered_vec.push(writable_ip_reg());
+        let clobbered_callee_saves = Self::get_clobbered_callee_saves(call_conv, clobbers);
+        let (core_regs, vfp_regs) = clobber_save_lists(&clobbered_callee_saves);
+        let clobber_size = 4 * core_regs.len() as u32 + 8 * vfp_regs.len() as u32;
+        if clobber_size == 0 {
+            return insts;
         }
-        if !clobbered_vec.is_empty() {
+
+        // Point SP at the saved registers. This frees the fixed frame, and
+        // doesn't use ip, which holds the callee's address before a tail call.
+        // `sub sp, fp, #n` is unpredictable in Thumb-2, so go through SP.
+        insts.push(Inst::Mov {
+            rd: writable_sp_reg(),
+            rm: fp_reg(),
+        });
+        insts.push(Inst::AluRRImm12 {
+            alu_op: ALUOp::Sub,
+            rd: writable_sp_reg(),
+            rn: sp_reg(),
+            imm12: UImm12::maybe_from_i64(clobber_size.into()).unwrap(),
+        });
+        if !vfp_regs.is_empty() {
+            insts.push(Inst::FpuPop {
+                reg_list: vfp_regs.into_iter().map(Writable::from_reg).collect(),
+            });
+        }
+        if !core_regs.is_empty() {
             insts.push(Inst::Pop {
-                reg_list: clobbered_vec,
+                reg_list: core_regs.into_iter().map(Writable::from_reg).collect(),
             });
         }
         insts
@@ -377,6 +621,7 @@ impl ABIMachineSpec for Arm32MachineDeps {
         _callee_conv: isa::CallConv,
         _caller_conv: isa::CallConv,
     ) -> SmallVec<[(InstIsSafepoint, Inst); 2]> {
+        let uses = call_uses(uses);
         let mut insts = SmallVec::new();
         match &dest {
             &CallDest::ExtName(ref name, RelocDistance::Near) => insts.push((
@@ -427,18 +672,73 @@ impl ABIMachineSpec for Arm32MachineDeps {
         insts
     }
 
+    fn gen_return_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        _callee_conv: isa::CallConv,
+        sp_adjust: u32,
+    ) -> SmallInstVec<Inst> {
+        // The epilogue is emitted between these instructions and the jump,
+        // so the target goes in ip, which the epilogue leaves alone, rather
+        // than in an allocatable register which it might restore.
+        let uses = call_uses(uses);
+        let mut insts = SmallVec::new();
+        match &dest {
+            &CallDest::ExtName(ref name, _) => {
+                insts.push(Inst::ReturnCall {
+                    dest: Box::new(name.clone()),
+                    uses,
+                    sp_adjust,
+                });
+            }
+            &CallDest::Reg(reg) => {
+                insts.push(Inst::mov(writable_ip_reg(), *reg));
+                insts.push(Inst::ReturnCallInd {
+                    rm: ip_reg(),
+                    uses,
+                    sp_adjust,
+                });
+            }
+        }
+        insts
+    }
+
     fn gen_memcpy(
-        _call_conv: isa::CallConv,
-        _dst: Reg,
-        _src: Reg,
-        _size: usize,
+        call_conv: isa::CallConv,
+        dst: Reg,
+        src: Reg,
+        size: usize,
     ) -> SmallVec<[Self::I; 8]> {
-        unimplemented!("StructArgs not implemented for ARM32 yet");
+        let mut insts = SmallVec::new();
+        let arg0 = writable_rreg(0);
+        let arg1 = writable_rreg(1);
+        let arg2 = writable_rreg(2);
+        insts.push(Inst::gen_move(arg0, dst, I32));
+        insts.push(Inst::gen_move(arg1, src, I32));
+        insts.extend(Inst::load_constant(arg2, size as u32).into_iter());
+        // Calls to host code go through a register, so that `blx` switches
+        // to the callee's instruction set if necessary.
+        insts.push(Inst::LoadExtName {
+            rt: writable_ip_reg(),
+            name: Box::new(ExternalName::LibCall(LibCall::Memcpy)),
+            offset: 0,
+        });
+        insts.push(Inst::CallInd {
+            info: Box::new(CallIndInfo {
+                rm: ip_reg(),
+                uses: vec![arg0.to_reg(), arg1.to_reg(), arg2.to_reg()],
+                defs: Self::get_regs_clobbered_by_call(call_conv),
+                opcode: ir::Opcode::Call,
+            }),
+        });
+        insts
     }
 
     fn get_number_of_spillslots_for_value(rc: RegClass, _ty: Type) -> u32 {
+        // We allocate in terms of 4-byte slots.
         match rc {
             RegClass::I32 => 1,
+            RegClass::F64 => 2,
             _ => panic!("Unexpected register class!"),
         }
     }
@@ -459,9 +759,21 @@ impl ABIMachineSpec for Arm32MachineDeps {
                 caller_saved.push(r);
             }
         }
+        for i in 0..16 {
+            let d = writable_dreg(i);
+            if is_reg_clobbered_by_call(d.to_reg().to_real_reg()) {
+                caller_saved.push(d);
+            }
+        }
         caller_saved
     }
 
+    fn get_regs_clobbered_by_unwind(_: isa::CallConv) -> Vec<Writable<Reg>> {
+        // Lowering rejects `br_unwind` for ARM32, so no call here ever has a
+        // landing pad and nothing is clobbered by unwinding.
+        vec![]
+    }
+
     fn get_ext_mode(
         _call_conv: isa::CallConv,
         specified: ir::ArgumentExtension,
@@ -495,12 +807,64 @@ impl ABIMachineSpec for Arm32MachineDeps {
     }
 }
 
+/// Split the clobbered callee-saves into the lists of registers to push and
+/// `vpush`. The core registers are padded with lr to keep the stack 8-byte
+/// aligned; the padding can't be ip, which must survive the epilogue of a
+/// tail call. `vpush` takes a contiguous range of registers, so the VFP list
+/// covers every register between the lowest and the highest clobbered one.
+fn clobber_save_lists(clobbered_callee_saves: &[Writable<RealReg>]) -> (Vec<Reg>, Vec<Reg>) {
+    let mut core_regs = vec![];
+    let mut vfp_nums = vec![];
+    for reg in clobbered_callee_saves {
+        let reg = reg.to_reg();
+        match reg.get_class() {
+            RegClass::I32 => core_regs.push(reg.to_reg()),
+            RegClass::F64 => vfp_nums.push(reg.get_hw_encoding() as u8),
+            _ => panic!("Unexpected register class!"),
+        }
+    }
+    if core_regs.len() % 2 == 1 {
+        core_regs.push(lr_reg());
+    }
+    let vfp_regs = match (vfp_nums.iter().min(), vfp_nums.iter().max()) {
+        (Some(&first), Some(&last)) => (first..=last).map(dreg).collect(),
+        _ => vec![],
+    };
+    (core_regs, vfp_regs)
+}
+
+/// Call instructions use the double-precision registers containing odd
+/// single-precision argument registers instead of the placeholders standing
+/// for them; see `sreg_hi`.
+fn call_uses(uses: Vec<Reg>) -> Vec<Reg> {
+    let mut ret: Vec<Reg> = vec![];
+    for reg in uses {
+        let reg = if is_sreg_hi(reg) {
+            sreg_hi_to_dreg(reg)
+        } else {
+            reg
+        };
+        if !ret.contains(&reg) {
+            ret.push(reg);
+        }
+    }
+    ret
+}
+
 fn is_callee_save(r: RealReg) -> bool {
     let enc = r.get_hw_encoding();
-    4 <= enc && enc <= 10
+    match r.get_class() {
+        RegClass::I32 => 4 <= enc && enc <= 10,
+        RegClass::F64 => 8 <= enc && enc <= 15,
+        _ => panic!("Unexpected register class!"),
+    }
 }
 
 fn is_reg_clobbered_by_call(r: RealReg) -> bool {
     let enc = r.get_hw_encoding();
-    enc <= 3
+    match r.get_class() {
+        RegClass::I32 => enc <= 3,
+        RegClass::F64 => enc <= 7,
+        _ => panic!("Unexpected register class!"),
+    }
 }
@@ -3,14 +3,13 @@
 use crate::binemit::{Reloc, StackMap};
 use crate::ir::SourceLoc;
 use crate::isa::arm32::inst::*;
+use crate::machinst::StackMapExtent;
 
 use core::convert::TryFrom;
 
-/// Memory addressing mode finalization: convert "special" modes (e.g.,
-/// nominal stack offset) into real addressing modes, possibly by
-/// emitting some helper instructions that come immediately before the use
-/// of this amode.
-pub fn mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, AMode) {
+/// Resolve the "special" addressing modes (e.g., nominal stack offset) into
+/// a base register and an offset.
+fn mem_base_and_offset(mem: &AMode, state: &EmitState) -> Option<(Reg, i64)> {
     match mem {
         &AMode::RegOffset(_, off)
         | &AMode::SPOffset(off, _)
@@ -37,7 +36,19 @@ pub fn mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, AMo
             let off = off + adj;
 
             assert!(-(1 << 31) <= off && off <= (1 << 32));
+            Some((basereg, off))
+        }
+        _ => None,
+    }
+}
 
+/// Memory addressing mode finalization: convert "special" modes (e.g.,
+/// nominal stack offset) into real addressing modes, possibly by
+/// emitting some helper instructions that come immediately before the use
+/// of this amode.
+pub fn mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, AMode) {
+    match mem_base_and_offset(mem, state) {
+        Some((basereg, off)) => {
             if let Some(off) = UImm12::maybe_from_i64(off) {
                 let mem = AMode::RegOffset12(basereg, off);
                 (smallvec![], mem)
@@ -49,7 +60,45 @@ pub fn mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, AMo
             }
         }
         // Just assert immediate is valid here.
-        _ => (smallvec![], mem.clone()),
+        None => (smallvec![], mem.clone()),
+    }
+}
+
+/// Memory addressing mode finalization for VFP loads and stores, which only
+/// take a base register and a word-aligned offset in [-1020, 1020]. Other
+/// addresses are computed into ip first.
+pub fn fpu_mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, Reg, i32) {
+    let (basereg, off) = match mem_base_and_offset(mem, state) {
+        Some((basereg, off)) => (basereg, off),
+        None => match mem {
+            &AMode::RegOffset12(rn, off12) => (rn, i64::from(off12.bits())),
+            &AMode::RegReg(rn, rm, shift) => {
+                let shift = ShiftOpShiftImm::maybe_from_shift(u32::from(shift)).unwrap();
+                let inst = Inst::AluRRRShift {
+                    alu_op: ALUOp::Add,
+                    rd: writable_ip_reg(),
+                    rn,
+                    rm,
+                    shift: Some(ShiftOpAndAmt::new(ShiftOp::LSL, shift)),
+                };
+                return (smallvec![inst], ip_reg(), 0);
+            }
+            _ => panic!("Unsupported VFP addressing mode {:?}", mem),
+        },
+    };
+    if off % 4 == 0 && -1020 <= off && off <= 1020 {
+        (smallvec![], basereg, off as i32)
+    } else {
+        let tmp = writable_ip_reg();
+        let mut insts = Inst::load_constant(tmp, off as u32);
+        insts.push(Inst::AluRRRShift {
+            alu_op: ALUOp::Add,
+            rd: tmp,
+            rn: basereg,
+            rm: tmp.to_reg(),
+            shift: None,
+        });
+        (insts, tmp.to_reg(), 0)
     }
 }
 
@@ -71,6 +120,114 @@ fn machreg_is_lo(m: Reg) -> bool {
     machreg_to_gpr(m) < 8
 }
 
+/// Get the number of the double-precision register `m`.
+fn machreg_to_vfp(m: Reg) -> u32 {
+    assert_eq!(m.get_class(), RegClass::F64);
+    u32::try_from(m.to_real_reg().get_hw_encoding()).unwrap()
+}
+
+/// Split a VFP register number into the 4-bit field and the extra bit of its
+/// encoding: a single-precision register `s<n>` is encoded as `n >> 1` and
+/// `n & 1`, a double-precision register `d<n>` as `n & 15` and `n >> 4`.
+fn vfp_reg_fields(num: u32, single: bool) -> (u32, u32) {
+    if single {
+        (num >> 1, num & 1)
+    } else {
+        (num & 0xf, num >> 4)
+    }
+}
+
+/// Encode the `Vd` register field (and the `D` bit) of a VFP instruction.
+fn enc_vfp_d(inst: u32, num: u32, single: bool) -> u32 {
+    let (v, x) = vfp_reg_fields(num, single);
+    inst | (v << 12) | (x << 22)
+}
+
+/// Encode the `Vn` register field (and the `N` bit) of a VFP instruction.
+fn enc_vfp_n(inst: u32, num: u32, single: bool) -> u32 {
+    let (v, x) = vfp_reg_fields(num, single);
+    inst | (v << 16) | (x << 7)
+}
+
+/// Encode the `Vm` register field (and the `M` bit) of a VFP instruction.
+fn enc_vfp_m(inst: u32, num: u32, single: bool) -> u32 {
+    let (v, x) = vfp_reg_fields(num, single);
+    inst | v | (x << 5)
+}
+
+/// The VFP register number of the value in `m`: `s<2n>` for 32-bit values in
+/// `d<n>`, or `d<n>` itself.
+fn vfp_num(m: Reg, bits: u8) -> (u32, bool) {
+    match bits {
+        32 => (2 * machreg_to_vfp(m), true),
+        64 => (machreg_to_vfp(m), false),
+        _ => panic!("Invalid floating-point width {}", bits),
+    }
+}
+
+/// Encode a VFP data-processing instruction with the given operand width.
+fn enc_vfp_rrr(inst: u32, rd: Reg, rn: Option<Reg>, rm: Reg, bits: u8) -> u32 {
+    let (d, single) = vfp_num(rd, bits);
+    let (m, _) = vfp_num(rm, bits);
+    let sz = if single { 0 } else { 1 << 8 };
+    let inst = enc_vfp_m(enc_vfp_d(inst | sz, d, single), m, single);
+    match rn {
+        Some(rn) => enc_vfp_n(inst, vfp_num(rn, bits).0, single),
+        None => inst,
+    }
+}
+
+/// `vmov.f32 s<d>, s<m>`.
+fn enc_vmov_s(d: u32, m: u32) -> u32 {
+    enc_vfp_m(enc_vfp_d(0xeeb0_0a40, d, true), m, true)
+}
+
+/// `vmov s<n>, rt` or `vmov rt, s<n>`.
+fn enc_vmov_core_s(to_core: bool, rt: Reg, n: u32) -> u32 {
+    let inst = if to_core { 0xee10_0a10 } else { 0xee00_0a10 };
+    enc_vfp_n(inst | (u32::from(machreg_to_gpr(rt)) << 12), n, true)
+}
+
+/// `vmov.32 d<n>[x], rt` or `vmov.32 rt, d<n>[x]`.
+fn enc_vmov_core_scalar(to_core: bool, rt: Reg, n: Reg, x: u32) -> u32 {
+    let inst = if to_core { 0xee10_0b10 } else { 0xee00_0b10 };
+    let inst = inst | (x << 21) | (u32::from(machreg_to_gpr(rt)) << 12);
+    enc_vfp_n(inst, machreg_to_vfp(n), false)
+}
+
+/// `vcmp` followed by `vmrs APSR_nzcv, fpscr`, which copies the resulting
+/// flags to the integer flags.
+fn emit_vfp_cmp(rn: Reg, rm: Reg, bits: u8, sink: &mut MachBuffer<Inst>) {
+    emit_32(enc_vfp_rrr(0xeeb4_0a40, rn, None, rm, bits), sink);
+    emit_32(0xeef1_fa10, sink);
+}
+
+/// VLDR and VSTR.
+fn enc_vfp_mem(is_load: bool, rd: Reg, bits: u8, rn: Reg, off: i32) -> u32 {
+    debug_assert!(off % 4 == 0 && -1020 <= off && off <= 1020);
+    let (d, single) = vfp_num(rd, bits);
+    let inst = if is_load { 0xed10_0a00 } else { 0xed00_0a00 };
+    let sz = if single { 0 } else { 1 << 8 };
+    let (u, imm8) = if off >= 0 {
+        (1, (off >> 2) as u32)
+    } else {
+        (0, (-off >> 2) as u32)
+    };
+    let inst = inst | sz | (u << 23) | (u32::from(machreg_to_gpr(rn)) << 16) | imm8;
+    enc_vfp_d(inst, d, single)
+}
+
+/// VPUSH and VPOP of a contiguous range of double-precision registers.
+fn enc_vfp_push_pop(is_push: bool, regs: &[Reg]) -> u32 {
+    assert!(!regs.is_empty() && regs.len() <= 16);
+    let first = machreg_to_vfp(regs[0]);
+    for (i, reg) in regs.iter().enumerate() {
+        assert_eq!(machreg_to_vfp(*reg), first + i as u32);
+    }
+    let inst = if is_push { 0xed2d_0b00 } else { 0xecbd_0b00 };
+    enc_vfp_d(inst | (2 * regs.len() as u32), first, false)
+}
+
 fn enc_16_rr(bits_15_6: u16, rd: Reg, rm: Reg) -> u16 {
     (bits_15_6 << 6) | machreg_to_gpr_lo(rd) | (machreg_to_gpr_lo(rm) << 3)
 }
@@ -287,7 +444,12 @@ impl MachInstEmit for Inst {
     type State = EmitState;
 
     fn emit(&self, sink: &mut MachBuffer<Inst>, emit_info: &Self::Info, state: &mut EmitState) {
-        let start_off = sink.cur_offset();
+        // N.B.: we *must* not exceed the "worst-case size" used to compute
+        // where to insert islands, except when islands are explicitly triggered
+        // (with an `EmitIsland`). We check this in debug builds. This is `mut`
+        // to allow disabling the check for `JTSequence`, which is always
+        // emitted following an `EmitIsland`.
+        let mut start_off = sink.cur_offset();
 
         match self {
             &Inst::Nop0 | &Inst::EpiloguePlaceholder => {}
@@ -379,6 +541,22 @@ impl MachInstEmit for Inst {
                 );
                 emit_32(inst, sink);
             }
+            &Inst::AluRRRA {
+                alu_op,
+                rd,
+                rn,
+                rm,
+                ra,
+            } => {
+                let bits_7_4 = match alu_op {
+                    ALUOp::Mla => 0b0000,
+                    ALUOp::Mls => 0b0001,
+                    _ => panic!("Invalid ALUOp {:?} in RRRA form!", alu_op),
+                };
+                let inst = 0b111110110000_0000_0000_0000_0000_0000 | (bits_7_4 << 4);
+                let inst = enc_32_regs(inst, Some(rm), Some(rd.to_reg()), Some(ra), Some(rn));
+                emit_32(inst, sink);
+            }
             &Inst::AluRRImm12 {
                 alu_op,
                 rd,
@@ -648,7 +826,7 @@ impl MachInstEmit for Inst {
                         alu_op: ALUOp::Rsb,
                         rd,
                         rn: rd.to_reg(),
-                        imm8: UImm8::maybe_from_i64(1).unwrap(),
+                        imm8: UImm8::maybe_from_i64(0).unwrap(),
                     };
                     inst.emit(sink, emit_info, state);
                 }
@@ -702,19 +880,29 @@ impl MachInstEmit for Inst {
                 }
             },
             &Inst::Call { ref info } => {
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(4), s);
+                }
                 let srcloc = state.cur_srcloc();
+                // `bl .`: ELF on 32-bit ARM keeps the addend in the
+                // instruction, which is -4 to account for the PC bias.
                 sink.add_reloc(srcloc, Reloc::Arm32Call, &info.dest, 0);
-                emit_32(0b11110_0_0000000000_11_0_1_0_00000000000, sink);
+                emit_32(0b11110_1_1111111111_11_1_1_1_11111111110, sink);
                 if info.opcode.is_call() {
                     sink.add_call_site(srcloc, info.opcode);
                 }
+                emit_restore_sp_after_call(sink, emit_info, state);
             }
             &Inst::CallInd { ref info } => {
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(2), s);
+                }
                 let srcloc = state.cur_srcloc();
                 sink.put2(0b01000111_1_0000_000 | (machreg_to_gpr(info.rm) << 3));
                 if info.opcode.is_call() {
                     sink.add_call_site(srcloc, info.opcode);
                 }
+                emit_restore_sp_after_call(sink, emit_info, state);
             }
             &Inst::LoadExtName {
                 rt,
@@ -750,6 +938,30 @@ impl MachInstEmit for Inst {
                 sink.add_reloc(srcloc, Reloc::Abs4, name, offset.into());
                 sink.put4(0);
             }
+            &Inst::ReturnCall {
+                ref dest,
+                sp_adjust,
+                ..
+            } => {
+                for inst in Inst::gen_sp_add_in_steps(sp_adjust) {
+                    inst.emit(sink, emit_info, state);
+                }
+                // The callee's address is loaded into ip, which the epilogue leaves alone.
+                // `mov pc, ip` stays in Thumb state, so the address needn't have its low bit set.
+                let inst = Inst::LoadExtName {
+                    rt: writable_ip_reg(),
+                    name: dest.clone(),
+                    offset: 0,
+                };
+                inst.emit(sink, emit_info, state);
+                sink.put2(enc_16_mov(writable_pc_reg(), ip_reg()));
+            }
+            &Inst::ReturnCallInd { rm, sp_adjust, .. } => {
+                for inst in Inst::gen_sp_add_in_steps(sp_adjust) {
+                    inst.emit(sink, emit_info, state);
+                }
+                sink.put2(0b010001110_0000_000 | (machreg_to_gpr(rm) << 3)); // bx rm
+            }
             &Inst::Ret => {
                 sink.put2(0b010001110_1110_000); // bx lr
             }
@@ -794,6 +1006,9 @@ impl MachInstEmit for Inst {
                 let srcloc = state.cur_srcloc();
                 let code = trap_info;
                 sink.add_trap(srcloc, code);
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(2), s);
+                }
                 sink.put2(0b11011110_00000000);
             }
             &Inst::Bkpt => {
@@ -815,13 +1030,325 @@ impl MachInstEmit for Inst {
                 );
                 state.virtual_sp_offset += offset;
             }
+            &Inst::JTSequence { ridx, ref info } => {
+                // This sequence is *one* instruction in the vcode, and is expanded only here at
+                // emission time, because we cannot allow the regalloc to insert spills/reloads in
+                // the middle; we depend on hardcoded PC-rel addressing below.
+                //
+                //   maybe nop2                    (0|2) bytes (pc is now 4-aligned)
+                //   bhs default                   4 bytes
+                //   adr ip, table                 4 bytes
+                //   ldr lr, [ip, ridx, lsl #2]    4 bytes
+                //   add ip, lr                    2 bytes
+                //   mov pc, ip                    2 bytes
+                // table:
+                //   32-bit offsets from the start of the table
+                if sink.cur_offset() & 0x3 != 0 {
+                    Inst::Nop2.emit(sink, emit_info, state);
+                }
+
+                // Branch to default when condition code from prior comparison indicates.
+                // No need to inform the sink's branch folding logic about this branch, because it
+                // will not be merged with any other branch, flipped, or elided (it is not preceded
+                // or succeeded by any other branch). Just emit it with the label use.
+                let default_br_offset = sink.cur_offset();
+                if let BranchTarget::Label(l) = info.default_target {
+                    sink.use_label_at_offset(default_br_offset, l, LabelUse::Branch20);
+                }
+                emit_32(enc_32_cond_branch(Cond::Hs, info.default_target), sink);
+
+                // `adr` reads pc as the 4-aligned address of this instruction plus 4, so the
+                // table starts 8 bytes after that.
+                let inst = Inst::AluRRImm12 {
+                    alu_op: ALUOp::Add,
+                    rd: writable_ip_reg(),
+                    rn: pc_reg(),
+                    imm12: UImm12::maybe_from_i64(8).unwrap(),
+                };
+                inst.emit(sink, emit_info, state);
+                // Load the entry directly rather than with a `Load`, which would register a
+                // heap-access trap at this source location.
+                emit_32(enc_32_mem_r(0b00101, tmp2_reg(), ip_reg(), ridx, 2), sink);
+                sink.put2(enc_16_rr_any(0b01000100, ip_reg(), tmp2_reg())); // add ip, lr
+
+                // Unlike `bx`, `mov pc` stays in Thumb state regardless of the low address bit.
+                sink.put2(enc_16_mov(writable_pc_reg(), ip_reg()));
+
+                // Emit jump table (table of 32-bit offsets).
+                let jt_off = sink.cur_offset();
+                for &target in info.targets.iter() {
+                    let word_off = sink.cur_offset();
+                    // off_into_table is an addend here embedded in the label to be later patched
+                    // at the end of codegen. The offset is initially relative to this jump table
+                    // entry; with the extra addend, it'll be relative to the jump table's start,
+                    // after patching.
+                    let off_into_table = word_off - jt_off;
+                    sink.use_label_at_offset(
+                        word_off,
+                        target.as_label().unwrap(),
+                        LabelUse::PCRel32,
+                    );
+                    sink.put4(off_into_table);
+                }
+
+                // Lowering produces an EmitIsland before using a JTSequence, so we can safely
+                // disable the worst-case-size check in this case.
+                start_off = sink.cur_offset();
+            }
+            &Inst::FpuMove { rd, rn, bits } => {
+                emit_32(enc_vfp_rrr(0xeeb0_0a40, rd.to_reg(), None, rn, bits), sink);
+            }
+            &Inst::FpuMoveFromSHigh { rd, rn } => {
+                let d = 2 * machreg_to_vfp(rd.to_reg());
+                let m = 2 * machreg_to_vfp(rn) + 1;
+                emit_32(enc_vmov_s(d, m), sink);
+            }
+            &Inst::FpuMoveToSHigh { rd, rn } => {
+                let d = 2 * machreg_to_vfp(rd.to_reg()) + 1;
+                let m = 2 * machreg_to_vfp(rn);
+                emit_32(enc_vmov_s(d, m), sink);
+            }
+            &Inst::FpuRR {
+                fpu_op,
+                rd,
+                rn,
+                bits,
+            } => {
+                let d = machreg_to_vfp(rd.to_reg());
+                let m = machreg_to_vfp(rn);
+                let inst = match fpu_op {
+                    // The `sz` bit gives the size of the source.
+                    FPUOp1::Cvt32To64 => enc_vfp_m(enc_vfp_d(0xeeb7_0ac0, d, false), 2 * m, true),
+                    FPUOp1::Cvt64To32 => enc_vfp_m(enc_vfp_d(0xeeb7_0bc0, 2 * d, true), m, false),
+                    FPUOp1::Abs => enc_vfp_rrr(0xeeb0_0ac0, rd.to_reg(), None, rn, bits),
+                    FPUOp1::Neg => enc_vfp_rrr(0xeeb1_0a40, rd.to_reg(), None, rn, bits),
+                    FPUOp1::Sqrt => enc_vfp_rrr(0xeeb1_0ac0, rd.to_reg(), None, rn, bits),
+                };
+                emit_32(inst, sink);
+            }
+            &Inst::FpuRRR {
+                fpu_op,
+                rd,
+                rn,
+                rm,
+                bits,
+            } => {
+                let inst = match fpu_op {
+                    FPUOp2::Add => 0xee30_0a00,
+                    FPUOp2::Sub => 0xee30_0a40,
+                    FPUOp2::Mul => 0xee20_0a00,
+                    FPUOp2::Div => 0xee80_0a00,
+                };
+                emit_32(enc_vfp_rrr(inst, rd.to_reg(), Some(rn), rm, bits), sink);
+            }
+            &Inst::FpuCmp { rn, rm, bits } => {
+                emit_vfp_cmp(rn, rm, bits, sink);
+            }
+            &Inst::FpuLoad { rd, ref mem, bits } => {
+                let (mem_insts, rn, off) = fpu_mem_finalize(mem, state);
+                for inst in mem_insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() {
+                    // Register the offset at which the load instruction starts.
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+                emit_32(enc_vfp_mem(true, rd.to_reg(), bits, rn, off), sink);
+            }
+            &Inst::FpuStore { rd, ref mem, bits } => {
+                let (mem_insts, rn, off) = fpu_mem_finalize(mem, state);
+                for inst in mem_insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() {
+                    // Register the offset at which the store instruction starts.
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+                emit_32(enc_vfp_mem(false, rd, bits, rn, off), sink);
+            }
+            &Inst::MovToFpu { rd, rn } => {
+                let d = 2 * machreg_to_vfp(rd.to_reg());
+                emit_32(enc_vmov_core_s(false, rn, d), sink);
+            }
+            &Inst::MovFromFpu { rd, rn } => {
+                let n = 2 * machreg_to_vfp(rn);
+                emit_32(enc_vmov_core_s(true, rd.to_reg(), n), sink);
+            }
+            &Inst::MovToFpuPair { rd, rn_lo, rn_hi } => {
+                let inst = enc_32_regs(0xec40_0b10, None, None, Some(rn_lo), Some(rn_hi));
+                emit_32(enc_vfp_m(inst, machreg_to_vfp(rd.to_reg()), false), sink);
+            }
+            &Inst::MovFromFpuPair { rd_lo, rd_hi, rn } => {
+                let inst = enc_32_regs(
+                    0xec50_0b10,
+                    None,
+                    None,
+                    Some(rd_lo.to_reg()),
+                    Some(rd_hi.to_reg()),
+                );
+                emit_32(enc_vfp_m(inst, machreg_to_vfp(rn), false), sink);
+            }
+            &Inst::IntToFpu { op, rd, rn } => {
+                // vmov s<2d>, rn ; vcvt.<ty>.<s32|u32> <rd>, s<2d>
+                let d = machreg_to_vfp(rd.to_reg());
+                emit_32(enc_vmov_core_s(false, rn, 2 * d), sink);
+                let (signed, single) = match op {
+                    IntToFpuOp::I32ToF32 => (1, true),
+                    IntToFpuOp::U32ToF32 => (0, true),
+                    IntToFpuOp::I32ToF64 => (1, false),
+                    IntToFpuOp::U32ToF64 => (0, false),
+                };
+                let inst = 0xeeb8_0a40 | (signed << 7) | if single { 0 } else { 1 << 8 };
+                let inst = if single {
+                    enc_vfp_d(inst, 2 * d, true)
+                } else {
+                    enc_vfp_d(inst, d, false)
+                };
+                emit_32(enc_vfp_m(inst, 2 * d, true), sink);
+            }
+            &Inst::FpuToInt { op, rd, rn, tmp } => {
+                // vcvt.<s32|u32>.<ty> s<2tmp>, <rn> ; vmov rd, s<2tmp>
+                let t = 2 * machreg_to_vfp(tmp.to_reg());
+                let (inst, bits) = match op {
+                    FpuToIntOp::F32ToI32 => (0xeebd_0ac0, 32),
+                    FpuToIntOp::F32ToU32 => (0xeebc_0ac0, 32),
+                    FpuToIntOp::F64ToI32 => (0xeebd_0bc0, 64),
+                    FpuToIntOp::F64ToU32 => (0xeebc_0bc0, 64),
+                };
+                let (m, single) = vfp_num(rn, bits);
+                emit_32(enc_vfp_m(enc_vfp_d(inst, t, true), m, single), sink);
+                emit_32(enc_vmov_core_s(true, rd.to_reg(), t), sink);
+            }
+            &Inst::FpuMinMax {
+                is_min,
+                rd,
+                rn,
+                rm,
+                bits,
+            } => {
+                //   vcmp rn, rm ; vmrs APSR_nzcv, fpscr
+                //   bvs nan
+                //   beq eq
+                //   ite mi (min) / ite gt (max)
+                //   vmov rd, rn
+                //   vmov rd, rm
+                //   b end
+                // eq:
+                //   (combine the sign bits of rn and rm, which may be zeros of different signs)
+                //   b end
+                // nan:
+                //   vadd rd, rn, rm
+                // end:
+                //
+                // Every path reads all of its inputs before writing rd, so rd may be the same
+                // register as rn or rm.
+                let eq_len: i32 = if bits == 32 { 20 } else { 24 };
+                emit_vfp_cmp(rn, rm, bits, sink);
+                let nan = BranchTarget::ResolvedOffset(4 + 2 + 4 + 4 + 4 + eq_len);
+                emit_32(enc_32_cond_branch(Cond::Vs, nan), sink);
+                let eq = BranchTarget::ResolvedOffset(2 + 4 + 4 + 4);
+                emit_32(enc_32_cond_branch(Cond::Eq, eq), sink);
+                let cond = if is_min { Cond::Mi } else { Cond::Gt };
+                let it_insts = vec![
+                    CondInst::new(Inst::Nop2, true),
+                    CondInst::new(Inst::Nop2, false),
+                ];
+                sink.put2(enc_16_it(cond, &it_insts));
+                emit_32(enc_vfp_rrr(0xeeb0_0a40, rd.to_reg(), None, rn, bits), sink);
+                emit_32(enc_vfp_rrr(0xeeb0_0a40, rd.to_reg(), None, rm, bits), sink);
+                let end = BranchTarget::ResolvedOffset(eq_len + 4);
+                emit_32(enc_32_jump(end), sink);
+
+                // The sign bit is in the upper word of 64-bit values; the lower words are equal.
+                let rd_reg = rd.to_reg();
+                if bits == 32 {
+                    emit_32(
+                        enc_vmov_core_s(true, ip_reg(), 2 * machreg_to_vfp(rn)),
+                        sink,
+                    );
+                    emit_32(
+                        enc_vmov_core_s(true, tmp2_reg(), 2 * machreg_to_vfp(rm)),
+                        sink,
+                    );
+                } else {
+                    emit_32(enc_vmov_core_scalar(true, ip_reg(), rn, 1), sink);
+                    emit_32(enc_vmov_core_scalar(true, tmp2_reg(), rm, 1), sink);
+                }
+                // -0.0 has the sign bit set: min ORs the sign bits, max ANDs them.
+                let inst = Inst::AluRRRShift {
+                    alu_op: if is_min { ALUOp::Orr } else { ALUOp::And },
+                    rd: writable_ip_reg(),
+                    rn: ip_reg(),
+                    rm: tmp2_reg(),
+                    shift: None,
+                };
+                inst.emit(sink, emit_info, state);
+                if bits == 32 {
+                    emit_32(
+                        enc_vmov_core_s(false, ip_reg(), 2 * machreg_to_vfp(rd_reg)),
+                        sink,
+                    );
+                } else {
+                    emit_32(enc_vfp_rrr(0xeeb0_0a40, rd_reg, None, rn, bits), sink);
+                    emit_32(enc_vmov_core_scalar(false, ip_reg(), rd_reg, 1), sink);
+                }
+                emit_32(enc_32_jump(BranchTarget::ResolvedOffset(4)), sink);
+
+                emit_32(enc_vfp_rrr(0xee30_0a00, rd_reg, Some(rn), rm, bits), sink);
+            }
+            &Inst::FpuPush { ref reg_list } => {
+                emit_32(enc_vfp_push_pop(true, reg_list), sink);
+            }
+            &Inst::FpuPop { ref reg_list } => {
+                let reg_list = reg_list.iter().map(|r| r.to_reg()).collect::<Vec<_>>();
+                emit_32(enc_vfp_push_pop(false, &reg_list), sink);
+            }
+            &Inst::Fence => {
+                emit_32(0b11110011101111111000111101011011, sink); // dmb ish
+            }
+            &Inst::EmitIsland { needed_space } => {
+                if sink.island_needed(needed_space + 4) {
+                    let jump_around_label = sink.get_label();
+                    let jmp = Inst::Jump {
+                        dest: BranchTarget::Label(jump_around_label),
+                    };
+                    jmp.emit(sink, emit_info, state);
+                    sink.emit_island();
+                    sink.bind_label(jump_around_label);
+                }
+            }
+            &Inst::Unwind { ref inst } => {
+                sink.add_unwind(inst.clone());
+            }
         }
 
         let end_off = sink.cur_offset();
         debug_assert!((end_off - start_off) <= Inst::worst_case_size());
+
+        state.clear_post_insn();
     }
 
     fn pretty_print(&self, mb_rru: Option<&RealRegUniverse>, state: &mut EmitState) -> String {
         self.print_with_state(mb_rru, state)
     }
 }
+
+/// With growing tail calls, a callee may return with SP moved, so recompute it
+/// through ip from FP right after a call returns. This has to be part of the
+/// call itself, as the register allocator may reload spilled values right
+/// after it.
+fn emit_restore_sp_after_call(
+    sink: &mut MachBuffer<Inst>,
+    emit_info: &EmitInfo,
+    state: &mut EmitState,
+) {
+    if !emit_info.flags().enable_growing_tail_calls() {
+        return;
+    }
+    let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+    for inst in Inst::gen_sp_below_fp(fp_to_sp as u32) {
+        inst.emit(sink, emit_info, state);
+    }
+}
@@ -7,6 +7,7 @@ use alloc::vec::Vec;
 #[test]
 fn test_arm32_emit() {
     let flags = settings::Flags::new(settings::builder());
+    let emit_info = EmitInfo::new(flags);
     let mut insns = Vec::<(Inst, &str, &str)>::new();
 
     // litle endian order
@@ -1834,11 +1835,10 @@ fn test_arm32_emit() {
                 dest: ExternalName::testcase("test0"),
                 uses: Vec::new(),
                 defs: Vec::new(),
-                loc: SourceLoc::default(),
                 opcode: Opcode::Call,
             }),
         },
-        "00F000D0",
+        "FFF7FEFF",
         "bl 0",
     ));
     insns.push((
@@ -1847,7 +1847,6 @@ fn test_arm32_emit() {
                 rm: rreg(0),
                 uses: Vec::new(),
                 defs: Vec::new(),
-                loc: SourceLoc::default(),
                 opcode: Opcode::CallIndirect,
             }),
         },
@@ -1860,7 +1859,6 @@ fn test_arm32_emit() {
                 rm: rreg(8),
                 uses: Vec::new(),
                 defs: Vec::new(),
-                loc: SourceLoc::default(),
                 opcode: Opcode::CallIndirect,
             }),
         },
@@ -1868,6 +1866,15 @@ fn test_arm32_emit() {
         "blx r8",
     ));
     insns.push((Inst::Ret, "7047", "bx lr"));
+    insns.push((
+        Inst::ReturnCall {
+            dest: Box::new(ExternalName::testcase("test0")),
+            uses: vec![],
+            sp_adjust: 0,
+        },
+        "DFF804C000F002B800000000E746",
+        "ldr ip, [pc, #4] ; b 4 ; data TestCase { length: 5, ascii: [116, 101, 115, 116, 48, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] } ; mov pc, ip",
+    ));
     insns.push((
         Inst::Jump {
             dest: BranchTarget::ResolvedOffset(32),
@@ -1941,6 +1948,347 @@ fn test_arm32_emit() {
     ));
     insns.push((Inst::Bkpt, "00BE", "bkpt #0"));
 
+    insns.push((
+        Inst::AluRRRA {
+            alu_op: ALUOp::Mla,
+            rd: writable_rreg(0),
+            rn: rreg(1),
+            rm: rreg(2),
+            ra: rreg(3),
+        },
+        "01FB0230",
+        "mla r0, r1, r2, r3",
+    ));
+    insns.push((
+        Inst::AluRRRA {
+            alu_op: ALUOp::Mls,
+            rd: writable_rreg(8),
+            rn: rreg(9),
+            rm: rreg(10),
+            ra: ip_reg(),
+        },
+        "09FB1AC8",
+        "mls r8, r9, r10, ip",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rm: ip_reg(),
+            uses: vec![],
+            sp_adjust: 0,
+        },
+        "6047",
+        "bx ip",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rm: ip_reg(),
+            uses: vec![],
+            sp_adjust: 4096,
+        },
+        "0DF6F87D0DF2080D6047",
+        "add sp, sp, #4088 ; add sp, sp, #8 ; bx ip",
+    ));
+    insns.push((
+        Inst::FpuMove {
+            rd: writable_dreg(0),
+            rn: dreg(15),
+            bits: 64,
+        },
+        "B0EE4F0B",
+        "vmov.f64 d0, d15",
+    ));
+    insns.push((
+        Inst::FpuMove {
+            rd: writable_dreg(1),
+            rn: dreg(15),
+            bits: 32,
+        },
+        "B0EE4F1A",
+        "vmov.f32 s2, s30",
+    ));
+    insns.push((
+        Inst::FpuMoveFromSHigh {
+            rd: writable_dreg(2),
+            rn: dreg(3),
+        },
+        "B0EE632A",
+        "vmov.f32 s4, s7",
+    ));
+    insns.push((
+        Inst::FpuMoveToSHigh {
+            rd: writable_dreg(2),
+            rn: dreg(3),
+        },
+        "F0EE432A",
+        "vmov.f32 s5, s6",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            fpu_op: FPUOp1::Abs,
+            rd: writable_dreg(1),
+            rn: dreg(2),
+            bits: 64,
+        },
+        "B0EEC21B",
+        "vabs.f64 d1, d2",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            fpu_op: FPUOp1::Neg,
+            rd: writable_dreg(1),
+            rn: dreg(9),
+            bits: 32,
+        },
+        "B1EE491A",
+        "vneg.f32 s2, s18",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            fpu_op: FPUOp1::Sqrt,
+            rd: writable_dreg(8),
+            rn: dreg(0),
+            bits: 64,
+        },
+        "B1EEC08B",
+        "vsqrt.f64 d8, d0",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            fpu_op: FPUOp1::Cvt32To64,
+            rd: writable_dreg(3),
+            rn: dreg(9),
+            bits: 64,
+        },
+        "B7EEC93A",
+        "vcvt.f64.f32 d3, s18",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            fpu_op: FPUOp1::Cvt64To32,
+            rd: writable_dreg(9),
+            rn: dreg(3),
+            bits: 32,
+        },
+        "B7EEC39B",
+        "vcvt.f32.f64 s18, d3",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            fpu_op: FPUOp2::Add,
+            rd: writable_dreg(0),
+            rn: dreg(1),
+            rm: dreg(2),
+            bits: 64,
+        },
+        "31EE020B",
+        "vadd.f64 d0, d1, d2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            fpu_op: FPUOp2::Sub,
+            rd: writable_dreg(8),
+            rn: dreg(9),
+            rm: dreg(15),
+            bits: 32,
+        },
+        "39EE4F8A",
+        "vsub.f32 s16, s18, s30",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            fpu_op: FPUOp2::Mul,
+            rd: writable_dreg(10),
+            rn: dreg(11),
+            rm: dreg(12),
+            bits: 64,
+        },
+        "2BEE0CAB",
+        "vmul.f64 d10, d11, d12",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            fpu_op: FPUOp2::Div,
+            rd: writable_dreg(1),
+            rn: dreg(2),
+            rm: dreg(3),
+            bits: 32,
+        },
+        "82EE031A",
+        "vdiv.f32 s2, s4, s6",
+    ));
+    insns.push((
+        Inst::FpuCmp {
+            rn: dreg(1),
+            rm: dreg(14),
+            bits: 64,
+        },
+        "B4EE4E1BF1EE10FA",
+        "vcmp.f64 d1, d14 ; vmrs APSR_nzcv, fpscr",
+    ));
+    insns.push((
+        Inst::FpuCmp {
+            rn: dreg(14),
+            rm: dreg(1),
+            bits: 32,
+        },
+        "B4EE41EAF1EE10FA",
+        "vcmp.f32 s28, s2 ; vmrs APSR_nzcv, fpscr",
+    ));
+    insns.push((
+        Inst::FpuLoad {
+            rd: writable_dreg(0),
+            mem: AMode::reg_plus_reg(rreg(1), rreg(2), 3),
+            bits: 64,
+        },
+        "01EBC20C9CED000B",
+        "add ip, r1, r2, lsl #3 ; vldr d0, [ip, #0]",
+    ));
+    insns.push((
+        Inst::FpuLoad {
+            rd: writable_dreg(7),
+            mem: AMode::reg_plus_imm(rreg(8), 1020),
+            bits: 32,
+        },
+        "98EDFF7A",
+        "vldr s14, [r8, #1020]",
+    ));
+    insns.push((
+        Inst::FpuLoad {
+            rd: writable_dreg(7),
+            mem: AMode::reg_plus_imm(rreg(8), 1022),
+            bits: 64,
+        },
+        "40F2FE3C08EB0C0C9CED007B",
+        "mov ip, #1022 ; add ip, r8, ip ; vldr d7, [ip, #0]",
+    ));
+    insns.push((
+        Inst::FpuStore {
+            rd: dreg(15),
+            mem: AMode::reg_plus_imm(sp_reg(), 8),
+            bits: 64,
+        },
+        "8DED02FB",
+        "vstr d15, [sp, #8]",
+    ));
+    insns.push((
+        Inst::FpuStore {
+            rd: dreg(3),
+            mem: AMode::reg_plus_imm(fp_reg(), -12),
+            bits: 32,
+        },
+        "0BED033A",
+        "vstr s6, [fp, #-12]",
+    ));
+    insns.push((
+        Inst::MovToFpu {
+            rd: writable_dreg(1),
+            rn: rreg(2),
+        },
+        "01EE102A",
+        "vmov s2, r2",
+    ));
+    insns.push((
+        Inst::MovFromFpu {
+            rd: writable_rreg(8),
+            rn: dreg(15),
+        },
+        "1FEE108A",
+        "vmov r8, s30",
+    ));
+    insns.push((
+        Inst::MovToFpuPair {
+            rd: writable_dreg(9),
+            rn_lo: rreg(0),
+            rn_hi: rreg(10),
+        },
+        "4AEC190B",
+        "vmov d9, r0, r10",
+    ));
+    insns.push((
+        Inst::MovFromFpuPair {
+            rd_lo: writable_rreg(4),
+            rd_hi: writable_rreg(5),
+            rn: dreg(6),
+        },
+        "55EC164B",
+        "vmov r4, r5, d6",
+    ));
+    insns.push((
+        Inst::IntToFpu {
+            op: IntToFpuOp::I32ToF32,
+            rd: writable_dreg(1),
+            rn: rreg(2),
+        },
+        "01EE102AB8EEC11A",
+        "vmov s2, r2 ; vcvt.f32.s32 s2, s2",
+    ));
+    insns.push((
+        Inst::IntToFpu {
+            op: IntToFpuOp::U32ToF64,
+            rd: writable_dreg(9),
+            rn: rreg(8),
+        },
+        "09EE108AB8EE499B",
+        "vmov s18, r8 ; vcvt.f64.u32 d9, s18",
+    ));
+    insns.push((
+        Inst::FpuToInt {
+            op: FpuToIntOp::F32ToU32,
+            rd: writable_rreg(0),
+            rn: dreg(1),
+            tmp: writable_dreg(2),
+        },
+        "BCEEC12A12EE100A",
+        "vcvt.u32.f32 s4, s2 ; vmov r0, s4",
+    ));
+    insns.push((
+        Inst::FpuToInt {
+            op: FpuToIntOp::F64ToI32,
+            rd: writable_rreg(9),
+            rn: dreg(10),
+            tmp: writable_dreg(10),
+        },
+        "BDEECAAB1AEE109A",
+        "vcvt.s32.f64 s20, d10 ; vmov r9, s20",
+    ));
+    insns.push((
+        Inst::FpuMinMax {
+            is_min: true,
+            rd: writable_dreg(0),
+            rn: dreg(0),
+            rm: dreg(1),
+            bits: 32,
+        },
+        "B4EE410AF1EE10FA80F1138000F007804CBFB0EE400AB0EE410A00F00CB810EE10CA11EE10EA4CEA0E0C00EE10CA00F002B830EE010A",
+        "fmin.f32 s0, s0, s2",
+    ));
+    insns.push((
+        Inst::FpuMinMax {
+            is_min: false,
+            rd: writable_dreg(2),
+            rn: dreg(3),
+            rm: dreg(2),
+            bits: 64,
+        },
+        "B4EE423BF1EE10FA80F1158000F00780CCBFB0EE432BB0EE422B00F00EB833EE10CB32EE10EB0CEA0E0CB0EE432B22EE10CB00F002B833EE022B",
+        "fmax.f64 d2, d3, d2",
+    ));
+    insns.push((
+        Inst::FpuPush {
+            reg_list: vec![dreg(8), dreg(9), dreg(10)],
+        },
+        "2DED068B",
+        "vpush {d8, d9, d10}",
+    ));
+    insns.push((
+        Inst::FpuPop {
+            reg_list: vec![writable_dreg(8)],
+        },
+        "BDEC028B",
+        "vpop {d8}",
+    ));
+    insns.push((Inst::Fence, "BFF35B8F", "dmb ish"));
+
     // ========================================================
     // Run the tests
     let rru = regs::create_reg_universe();
@@ -1950,10 +2298,35 @@ fn test_arm32_emit() {
         assert_eq!(expected_printing, actual_printing);
         let mut sink = test_utils::TestCodeSink::new();
         let mut buffer = MachBuffer::new();
-        insn.emit(&mut buffer, &flags, &mut Default::default());
+        insn.emit(&mut buffer, &emit_info, &mut Default::default());
         let buffer = buffer.finish();
         buffer.emit(&mut sink);
         let actual_encoding = &sink.stringify();
         assert_eq!(expected_encoding, actual_encoding, "{}", expected_printing);
     }
 }
+
+#[test]
+fn test_arm32_emit_growing_tail_calls() {
+    // With growing tail calls, every call recomputes SP from FP once it
+    // returns, as part of the call instruction itself.
+    use crate::settings::Configurable;
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_growing_tail_calls").unwrap();
+    let emit_info = EmitInfo::new(settings::Flags::new(flag_builder));
+
+    let insn = Inst::CallInd {
+        info: Box::new(CallIndInfo {
+            rm: rreg(0),
+            uses: Vec::new(),
+            defs: Vec::new(),
+            opcode: Opcode::CallIndirect,
+        }),
+    };
+    let mut sink = test_utils::TestCodeSink::new();
+    let mut buffer = MachBuffer::new();
+    insn.emit(&mut buffer, &emit_info, &mut Default::default());
+    let buffer = buffer.finish();
+    buffer.emit(&mut sink);
+    assert_eq!("8047ABF2000CE546", sink.stringify());
+}
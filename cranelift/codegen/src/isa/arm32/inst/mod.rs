@@ -3,8 +3,9 @@
 #![allow(dead_code)]
 
 use crate::binemit::CodeOffset;
-use crate::ir::types::{B1, B16, B32, B8, I16, I32, I8, IFLAGS};
+use crate::ir::types::{B1, B16, B32, B8, F32, F64, FFLAGS, I16, I32, I64, I8, IFLAGS, R32};
 use crate::ir::{ExternalName, Opcode, TrapCode, Type};
+use crate::isa::unwind::UnwindInst;
 use crate::machinst::*;
 use crate::{settings, CodegenError, CodegenResult};
 
@@ -22,6 +23,7 @@ mod emit;
 pub use self::emit::*;
 mod regs;
 pub use self::regs::*;
+pub mod unwind;
 
 #[cfg(test)]
 mod emit_tests;
@@ -49,6 +51,8 @@ pub enum ALUOp {
     Umull,
     Udiv,
     Sdiv,
+    Mla,
+    Mls,
     And,
     Orr,
     Orn,
@@ -75,6 +79,46 @@ pub enum BitOp {
     Clz,
 }
 
+/// A floating-point operation with one argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FPUOp1 {
+    Abs,
+    Neg,
+    Sqrt,
+    /// Convert an `f32` to an `f64`.
+    Cvt32To64,
+    /// Convert an `f64` to an `f32`.
+    Cvt64To32,
+}
+
+/// A floating-point operation with two arguments.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FPUOp2 {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// A conversion from a 32-bit integer to a floating-point value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IntToFpuOp {
+    I32ToF32,
+    U32ToF32,
+    I32ToF64,
+    U32ToF64,
+}
+
+/// A conversion from a floating-point value to a 32-bit integer, rounding
+/// towards zero and saturating.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FpuToIntOp {
+    F32ToI32,
+    F32ToU32,
+    F64ToI32,
+    F64ToU32,
+}
+
 /// Additional information for (direct) Call instructions, left out of line to lower the size of
 /// the Inst enum.
 #[derive(Clone, Debug)]
@@ -95,6 +139,15 @@ pub struct CallIndInfo {
     pub opcode: Opcode,
 }
 
+/// Additional information for JTSequence instructions, left out of line to lower the size of the Inst
+/// enum.
+#[derive(Clone, Debug)]
+pub struct JTSequenceInfo {
+    pub targets: Vec<BranchTarget>,
+    pub default_target: BranchTarget,
+    pub targets_for_term: Vec<MachLabel>, // needed for MachTerminator.
+}
+
 /// Instruction formats.
 #[derive(Clone, Debug)]
 pub enum Inst {
@@ -140,6 +193,16 @@ pub enum Inst {
         rm: Reg,
     },
 
+    /// An ALU operation with three register sources and one register destination:
+    /// a multiply-accumulate, which computes `ra + rn * rm` or `ra - rn * rm`.
+    AluRRRA {
+        alu_op: ALUOp,
+        rd: Writable<Reg>,
+        rn: Reg,
+        rm: Reg,
+        ra: Reg,
+    },
+
     /// An ALU operation with a register source and a 12-bit immediate source,
     /// and a register destination.
     AluRRImm12 {
@@ -274,6 +337,22 @@ pub enum Inst {
         offset: i32,
     },
 
+    /// A tail call to a named function, following the epilogue of the current function. The
+    /// callee's address is loaded into ip. SP is first moved `sp_adjust` bytes up.
+    ReturnCall {
+        dest: Box<ExternalName>,
+        uses: Vec<Reg>,
+        sp_adjust: u32,
+    },
+
+    /// A tail call through a register, following the epilogue of the current function. SP is
+    /// first moved `sp_adjust` bytes up, without using any register besides SP.
+    ReturnCallInd {
+        rm: Reg,
+        uses: Vec<Reg>,
+        sp_adjust: u32,
+    },
+
     /// A return instruction, which is encoded as `bx lr`.
     Ret,
 
@@ -296,6 +375,14 @@ pub enum Inst {
         targets: Vec<MachLabel>,
     },
 
+    /// Jump-table sequence, as one compound instruction (see note in lower_inst.rs for
+    /// rationale). Jumps to the default target if the flags set by a comparison of `ridx`
+    /// against the table size indicate that it is out of range.
+    JTSequence {
+        ridx: Reg,
+        info: Box<JTSequenceInfo>,
+    },
+
     /// A conditional trap: execute a `udf` if the condition is true. This is
     /// one VCode instruction because it uses embedded control flow; it is
     /// logically a single-in, single-out region, but needs to appear as one
@@ -319,6 +406,145 @@ pub enum Inst {
         offset: i64,
     },
 
+    /// A floating-point register move. `bits` is the width of the value: 32-bit values
+    /// live in the low half of a double-precision register.
+    FpuMove {
+        rd: Writable<Reg>,
+        rn: Reg,
+        bits: u8,
+    },
+
+    /// Move the high half of the double-precision register `rn` to the low half of `rd`. Used to
+    /// read `f32` arguments that the ABI back-fills into odd single-precision registers.
+    FpuMoveFromSHigh {
+        rd: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// Move the low half of `rn` to the high half of the double-precision register `rd`. Used to
+    /// pass `f32` arguments in odd single-precision registers.
+    FpuMoveToSHigh {
+        rd: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// A floating-point operation with one register source and a register destination.
+    /// For conversions, `bits` is ignored.
+    FpuRR {
+        fpu_op: FPUOp1,
+        rd: Writable<Reg>,
+        rn: Reg,
+        bits: u8,
+    },
+
+    /// A floating-point operation with two register sources and a register destination.
+    FpuRRR {
+        fpu_op: FPUOp2,
+        rd: Writable<Reg>,
+        rn: Reg,
+        rm: Reg,
+        bits: u8,
+    },
+
+    /// A floating-point comparison, whose result is copied to the integer flags: `vcmp`
+    /// followed by `vmrs APSR_nzcv, fpscr`.
+    FpuCmp {
+        rn: Reg,
+        rm: Reg,
+        bits: u8,
+    },
+
+    /// A floating-point load.
+    FpuLoad {
+        rd: Writable<Reg>,
+        mem: AMode,
+        bits: u8,
+    },
+
+    /// A floating-point store.
+    FpuStore {
+        rd: Reg,
+        mem: AMode,
+        bits: u8,
+    },
+
+    /// Move a GPR to the low half of a floating-point register.
+    MovToFpu {
+        rd: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// Move two GPRs to a double-precision register.
+    MovToFpuPair {
+        rd: Writable<Reg>,
+        rn_lo: Reg,
+        rn_hi: Reg,
+    },
+
+    /// Move the low half of a floating-point register to a GPR.
+    MovFromFpu {
+        rd: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// Move a double-precision register to two GPRs.
+    MovFromFpuPair {
+        rd_lo: Writable<Reg>,
+        rd_hi: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// Convert a 32-bit integer in a GPR to a floating-point value.
+    IntToFpu {
+        op: IntToFpuOp,
+        rd: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// Convert a floating-point value to a 32-bit integer in a GPR, using `tmp` for the
+    /// intermediate result.
+    FpuToInt {
+        op: FpuToIntOp,
+        rd: Writable<Reg>,
+        rn: Reg,
+        tmp: Writable<Reg>,
+    },
+
+    /// A floating-point minimum or maximum with Wasm semantics: NaNs propagate, and -0.0 is
+    /// less than +0.0. This is a sequence with internal branches.
+    FpuMinMax {
+        is_min: bool,
+        rd: Writable<Reg>,
+        rn: Reg,
+        rm: Reg,
+        bits: u8,
+    },
+
+    /// A `vpush` of a contiguous range of double-precision registers.
+    FpuPush {
+        reg_list: Vec<Reg>,
+    },
+
+    /// A `vpop` of a contiguous range of double-precision registers.
+    FpuPop {
+        reg_list: Vec<Writable<Reg>>,
+    },
+
+    /// A memory fence, encoded as `dmb ish`.
+    Fence,
+
+    /// Emits an island of constants and veneers if needed, jumping over it. Used before
+    /// sequences that must not be interrupted, such as a `JTSequence`.
+    EmitIsland {
+        needed_space: CodeOffset,
+    },
+
+    /// An unwind pseudoinstruction describing the state of the
+    /// machine at this program point.
+    Unwind {
+        inst: UnwindInst,
+    },
+
     /// A placeholder instruction, generating no code, meaning that a function epilogue must be
     /// inserted there.
     EpiloguePlaceholder,
@@ -343,13 +569,103 @@ impl CondInst {
             | Inst::CondBr { .. }
             | Inst::TrapIf { .. }
             | Inst::EpiloguePlaceholder { .. }
-            | Inst::LoadExtName { .. } => panic!("Instruction {:?} cannot occur in it block", inst),
+            | Inst::LoadExtName { .. }
+            | Inst::ReturnCall { .. }
+            | Inst::ReturnCallInd { .. }
+            | Inst::JTSequence { .. }
+            | Inst::FpuCmp { .. }
+            | Inst::FpuLoad { .. }
+            | Inst::FpuStore { .. }
+            | Inst::IntToFpu { .. }
+            | Inst::FpuToInt { .. }
+            | Inst::FpuMinMax { .. }
+            | Inst::EmitIsland { .. }
+            | Inst::Unwind { .. } => panic!("Instruction {:?} cannot occur in it block", inst),
             _ => Self { inst, then },
         }
     }
+
+    /// The register written by the instruction, if any. Instructions with more than one
+    /// destination aren't allowed to be conditional.
+    fn def(&self) -> Option<Writable<Reg>> {
+        match self.inst {
+            Inst::Nop2
+            | Inst::Cmp { .. }
+            | Inst::CmpImm8 { .. }
+            | Inst::Store { .. }
+            | Inst::Udf { .. }
+            | Inst::Bkpt => None,
+            Inst::AluRRR { rd, .. }
+            | Inst::AluRRRShift { rd, .. }
+            | Inst::AluRRShift { rd, .. }
+            | Inst::AluRRRA { rd, .. }
+            | Inst::AluRRImm12 { rd, .. }
+            | Inst::AluRRImm8 { rd, .. }
+            | Inst::AluRImm8 { rd, .. }
+            | Inst::BitOpRR { rd, .. }
+            | Inst::Mov { rd, .. }
+            | Inst::MovImm16 { rd, .. }
+            | Inst::Movt { rd, .. }
+            | Inst::Load { rt: rd, .. }
+            | Inst::LoadAddr { rd, .. }
+            | Inst::Extend { rd, .. }
+            | Inst::FpuMove { rd, .. }
+            | Inst::FpuRR { rd, .. }
+            | Inst::FpuRRR { rd, .. }
+            | Inst::MovToFpu { rd, .. }
+            | Inst::MovFromFpu { rd, .. } => Some(rd),
+            _ => panic!("Instruction {:?} cannot occur in it block", self.inst),
+        }
+    }
 }
 
 impl Inst {
+    /// Create instructions that move SP `amount` bytes up, in as many immediate additions as
+    /// necessary so that no other register is needed.
+    pub fn gen_sp_add_in_steps(amount: u32) -> SmallVec<[Inst; 4]> {
+        // Keep SP 8-byte aligned after each step.
+        const MAX_STEP: u32 = 4088;
+        let mut insts = SmallVec::new();
+        let mut remaining = amount;
+        while remaining > 0 {
+            let step = std::cmp::min(remaining, MAX_STEP);
+            insts.push(Inst::AluRRImm12 {
+                alu_op: ALUOp::Add,
+                rd: writable_sp_reg(),
+                rn: sp_reg(),
+                imm12: UImm12::maybe_from_i64(step as i64).unwrap(),
+            });
+            remaining -= step;
+        }
+        insts
+    }
+
+    /// Create instructions that set SP to FP minus `offset`, through ip, so that SP never
+    /// points above data that is still live.
+    pub fn gen_sp_below_fp(offset: u32) -> SmallVec<[Inst; 4]> {
+        let tmp = writable_ip_reg();
+        let mut insts = SmallVec::new();
+        if let Some(imm12) = UImm12::maybe_from_i64(offset as i64) {
+            insts.push(Inst::AluRRImm12 {
+                alu_op: ALUOp::Sub,
+                rd: tmp,
+                rn: fp_reg(),
+                imm12,
+            });
+        } else {
+            insts.extend(Inst::load_constant(tmp, offset));
+            insts.push(Inst::AluRRRShift {
+                alu_op: ALUOp::Sub,
+                rd: tmp,
+                rn: fp_reg(),
+                rm: tmp.to_reg(),
+                shift: None,
+            });
+        }
+        insts.push(Inst::mov(writable_sp_reg(), tmp.to_reg()));
+        insts
+    }
+
     /// Create a move instruction.
     pub fn mov(to_reg: Writable<Reg>, from_reg: Reg) -> Inst {
         Inst::Mov {
@@ -364,10 +680,9 @@ impl Inst {
         let imm_lo = (value & 0xffff) as u16;
         let imm_hi = (value >> 16) as u16;
 
-        if imm_lo != 0 || imm_hi == 0 {
-            // imm_lo == 0 && imm_hi == 0 => we have to overwrite reg value with 0
-            insts.push(Inst::MovImm16 { rd, imm16: imm_lo });
-        }
+        // `movw` clears the top halfword, so it is always needed: `movt` alone would leave the
+        // previous value of the bottom halfword in place.
+        insts.push(Inst::MovImm16 { rd, imm16: imm_lo });
         if imm_hi != 0 {
             insts.push(Inst::Movt { rd, imm16: imm_hi });
         }
@@ -377,6 +692,14 @@ impl Inst {
 
     /// Generic constructor for a load (zero-extending where appropriate).
     pub fn gen_load(into_reg: Writable<Reg>, mem: AMode, ty: Type) -> Inst {
+        assert!(ty.bits() <= 64);
+        if ty.is_float() {
+            return Inst::FpuLoad {
+                rd: into_reg,
+                mem,
+                bits: ty.bits() as u8,
+            };
+        }
         assert!(ty.bits() <= 32);
         // Load 8 bits for B1.
         let bits = std::cmp::max(ty.bits(), 8) as u8;
@@ -391,6 +714,14 @@ impl Inst {
 
     /// Generic constructor for a store.
     pub fn gen_store(from_reg: Reg, mem: AMode, ty: Type) -> Inst {
+        assert!(ty.bits() <= 64);
+        if ty.is_float() {
+            return Inst::FpuStore {
+                rd: from_reg,
+                mem,
+                bits: ty.bits() as u8,
+            };
+        }
         assert!(ty.bits() <= 32);
         // Store 8 bits for B1.
         let bits = std::cmp::max(ty.bits(), 8) as u8;
@@ -401,6 +732,39 @@ impl Inst {
             bits,
         }
     }
+
+    /// Create instructions that load a 32- or 64-bit floating-point constant, given as its bit
+    /// pattern, through the GPRs.
+    pub fn load_fp_constant<F: FnMut(Type) -> Writable<Reg>>(
+        rd: Writable<Reg>,
+        value: u64,
+        ty: Type,
+        mut alloc_tmp: F,
+    ) -> SmallVec<[Inst; 4]> {
+        let mut insts = SmallVec::new();
+        let lo = alloc_tmp(I32);
+        insts.extend(Inst::load_constant(lo, value as u32));
+        if ty == F32 {
+            insts.push(Inst::MovToFpu {
+                rd,
+                rn: lo.to_reg(),
+            });
+        } else {
+            let hi = if (value >> 32) as u32 == value as u32 {
+                lo
+            } else {
+                let hi = alloc_tmp(I32);
+                insts.extend(Inst::load_constant(hi, (value >> 32) as u32));
+                hi
+            };
+            insts.push(Inst::MovToFpuPair {
+                rd,
+                rn_lo: lo.to_reg(),
+                rn_hi: hi.to_reg(),
+            });
+        }
+        insts
+    }
 }
 
 //=============================================================================
@@ -436,7 +800,10 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
         | &Inst::CondBr { .. }
         | &Inst::Bkpt
         | &Inst::Udf { .. }
-        | &Inst::TrapIf { .. } => {}
+        | &Inst::TrapIf { .. }
+        | &Inst::Fence
+        | &Inst::EmitIsland { .. }
+        | &Inst::Unwind { .. } => {}
         &Inst::AluRRR { rd, rn, rm, .. } => {
             collector.add_def(rd);
             collector.add_use(rn);
@@ -463,6 +830,12 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_use(rn);
             collector.add_use(rm);
         }
+        &Inst::AluRRRA { rd, rn, rm, ra, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rn);
+            collector.add_use(rm);
+            collector.add_use(ra);
+        }
         &Inst::AluRRImm12 { rd, rn, .. } => {
             collector.add_def(rd);
             collector.add_use(rn);
@@ -486,7 +859,7 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_def(rd);
         }
         &Inst::Movt { rd, .. } => {
-            collector.add_def(rd);
+            collector.add_mod(rd);
         }
         &Inst::Cmp { rn, rm } => {
             collector.add_use(rn);
@@ -503,8 +876,9 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_def(rt);
             memarg_regs(mem, collector);
         }
-        &Inst::LoadAddr { rd, mem: _ } => {
+        &Inst::LoadAddr { rd, ref mem } => {
             collector.add_def(rd);
+            memarg_regs(mem, collector);
         }
         &Inst::Extend { rd, rm, .. } => {
             collector.add_def(rd);
@@ -514,6 +888,18 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             for inst in insts.iter() {
                 arm32_get_regs(&inst.inst, collector);
             }
+            // A register that isn't written on both the "then" and the "else" path keeps its
+            // previous value on the other one, so it is modified rather than defined.
+            for inst in insts.iter() {
+                if let Some(rd) = inst.def() {
+                    let written_on_both_paths = insts
+                        .iter()
+                        .any(|other| other.then != inst.then && other.def() == Some(rd));
+                    if !written_on_both_paths {
+                        collector.add_mod(rd);
+                    }
+                }
+            }
         }
         &Inst::Push { ref reg_list } => {
             for reg in reg_list {
@@ -534,12 +920,73 @@ fn arm32_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             collector.add_defs(&*info.defs);
             collector.add_use(info.rm);
         }
+        &Inst::ReturnCall { ref uses, .. } => {
+            collector.add_uses(uses);
+        }
+        &Inst::ReturnCallInd { rm, ref uses, .. } => {
+            collector.add_use(rm);
+            collector.add_uses(uses);
+        }
         &Inst::LoadExtName { rt, .. } => {
             collector.add_def(rt);
         }
         &Inst::IndirectBr { rm, .. } => {
             collector.add_use(rm);
         }
+        &Inst::JTSequence { ridx, .. } => {
+            collector.add_use(ridx);
+        }
+        &Inst::FpuMove { rd, rn, .. }
+        | &Inst::FpuMoveFromSHigh { rd, rn }
+        | &Inst::FpuRR { rd, rn, .. }
+        | &Inst::MovToFpu { rd, rn }
+        | &Inst::MovFromFpu { rd, rn }
+        | &Inst::IntToFpu { rd, rn, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rn);
+        }
+        &Inst::FpuMoveToSHigh { rd, rn } => {
+            collector.add_mod(rd);
+            collector.add_use(rn);
+        }
+        &Inst::FpuRRR { rd, rn, rm, .. } | &Inst::FpuMinMax { rd, rn, rm, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rn);
+            collector.add_use(rm);
+        }
+        &Inst::FpuCmp { rn, rm, .. } => {
+            collector.add_use(rn);
+            collector.add_use(rm);
+        }
+        &Inst::FpuLoad { rd, ref mem, .. } => {
+            collector.add_def(rd);
+            memarg_regs(mem, collector);
+        }
+        &Inst::FpuStore { rd, ref mem, .. } => {
+            collector.add_use(rd);
+            memarg_regs(mem, collector);
+        }
+        &Inst::MovToFpuPair { rd, rn_lo, rn_hi } => {
+            collector.add_def(rd);
+            collector.add_use(rn_lo);
+            collector.add_use(rn_hi);
+        }
+        &Inst::MovFromFpuPair { rd_lo, rd_hi, rn } => {
+            collector.add_def(rd_lo);
+            collector.add_def(rd_hi);
+            collector.add_use(rn);
+        }
+        &Inst::FpuToInt { rd, rn, tmp, .. } => {
+            collector.add_def(rd);
+            collector.add_def(tmp);
+            collector.add_use(rn);
+        }
+        &Inst::FpuPush { ref reg_list } => {
+            collector.add_uses(reg_list);
+        }
+        &Inst::FpuPop { ref reg_list } => {
+            collector.add_defs(reg_list);
+        }
     }
 }
 
@@ -594,7 +1041,10 @@ fn arm32_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
         | &mut Inst::CondBr { .. }
         | &mut Inst::Bkpt
         | &mut Inst::Udf { .. }
-        | &mut Inst::TrapIf { .. } => {}
+        | &mut Inst::TrapIf { .. }
+        | &mut Inst::Fence
+        | &mut Inst::EmitIsland { .. }
+        | &mut Inst::Unwind { .. } => {}
         &mut Inst::AluRRR {
             ref mut rd,
             ref mut rn,
@@ -635,6 +1085,18 @@ fn arm32_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             map_use(mapper, rn);
             map_use(mapper, rm);
         }
+        &mut Inst::AluRRRA {
+            ref mut rd,
+            ref mut rn,
+            ref mut rm,
+            ref mut ra,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rn);
+            map_use(mapper, rm);
+            map_use(mapper, ra);
+        }
         &mut Inst::AluRRImm12 {
             ref mut rd,
             ref mut rn,
@@ -674,7 +1136,7 @@ fn arm32_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             map_def(mapper, rd);
         }
         &mut Inst::Movt { ref mut rd, .. } => {
-            map_def(mapper, rd);
+            map_mod(mapper, rd);
         }
         &mut Inst::Cmp {
             ref mut rn,
@@ -718,6 +1180,9 @@ fn arm32_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             map_use(mapper, rm);
         }
         &mut Inst::It { ref mut insts, .. } => {
+            // Registers that `arm32_get_regs` reports as modified rather than defined are mapped
+            // like definitions here: a modified register is assigned the same real register
+            // before and after the instruction.
             for inst in insts.iter_mut() {
                 arm32_map_regs(&mut inst.inst, mapper);
             }
@@ -749,12 +1214,145 @@ fn arm32_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             }
             map_use(mapper, &mut info.rm);
         }
+        &mut Inst::ReturnCall { ref mut uses, .. } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+        }
+        &mut Inst::ReturnCallInd {
+            ref mut rm,
+            ref mut uses,
+            ..
+        } => {
+            map_use(mapper, rm);
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+        }
         &mut Inst::LoadExtName { ref mut rt, .. } => {
             map_def(mapper, rt);
         }
         &mut Inst::IndirectBr { ref mut rm, .. } => {
             map_use(mapper, rm);
         }
+        &mut Inst::JTSequence { ref mut ridx, .. } => {
+            map_use(mapper, ridx);
+        }
+        &mut Inst::FpuMove {
+            ref mut rd,
+            ref mut rn,
+            ..
+        }
+        | &mut Inst::FpuMoveFromSHigh {
+            ref mut rd,
+            ref mut rn,
+        }
+        | &mut Inst::FpuRR {
+            ref mut rd,
+            ref mut rn,
+            ..
+        }
+        | &mut Inst::MovToFpu {
+            ref mut rd,
+            ref mut rn,
+        }
+        | &mut Inst::MovFromFpu {
+            ref mut rd,
+            ref mut rn,
+        }
+        | &mut Inst::IntToFpu {
+            ref mut rd,
+            ref mut rn,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rn);
+        }
+        &mut Inst::FpuMoveToSHigh {
+            ref mut rd,
+            ref mut rn,
+        } => {
+            map_mod(mapper, rd);
+            map_use(mapper, rn);
+        }
+        &mut Inst::FpuRRR {
+            ref mut rd,
+            ref mut rn,
+            ref mut rm,
+            ..
+        }
+        | &mut Inst::FpuMinMax {
+            ref mut rd,
+            ref mut rn,
+            ref mut rm,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rn);
+            map_use(mapper, rm);
+        }
+        &mut Inst::FpuCmp {
+            ref mut rn,
+            ref mut rm,
+            ..
+        } => {
+            map_use(mapper, rn);
+            map_use(mapper, rm);
+        }
+        &mut Inst::FpuLoad {
+            ref mut rd,
+            ref mut mem,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_mem(mapper, mem);
+        }
+        &mut Inst::FpuStore {
+            ref mut rd,
+            ref mut mem,
+            ..
+        } => {
+            map_use(mapper, rd);
+            map_mem(mapper, mem);
+        }
+        &mut Inst::MovToFpuPair {
+            ref mut rd,
+            ref mut rn_lo,
+            ref mut rn_hi,
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rn_lo);
+            map_use(mapper, rn_hi);
+        }
+        &mut Inst::MovFromFpuPair {
+            ref mut rd_lo,
+            ref mut rd_hi,
+            ref mut rn,
+        } => {
+            map_def(mapper, rd_lo);
+            map_def(mapper, rd_hi);
+            map_use(mapper, rn);
+        }
+        &mut Inst::FpuToInt {
+            ref mut rd,
+            ref mut rn,
+            ref mut tmp,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_def(mapper, tmp);
+            map_use(mapper, rn);
+        }
+        &mut Inst::FpuPush { ref mut reg_list } => {
+            for reg in reg_list {
+                map_use(mapper, reg);
+            }
+        }
+        &mut Inst::FpuPop { ref mut reg_list } => {
+            for reg in reg_list {
+                map_def(mapper, reg);
+            }
+        }
     }
 }
 
@@ -775,6 +1373,7 @@ impl MachInst for Inst {
     fn is_move(&self) -> Option<(Writable<Reg>, Reg)> {
         match self {
             &Inst::Mov { rd, rm } => Some((rd, rm)),
+            &Inst::FpuMove { rd, rn, .. } => Some((rd, rn)),
             _ => None,
         }
     }
@@ -794,31 +1393,62 @@ impl MachInst for Inst {
             &Inst::CondBr {
                 taken, not_taken, ..
             } => MachTerminator::Cond(taken.as_label().unwrap(), not_taken.as_label().unwrap()),
+            &Inst::ReturnCall { .. } | &Inst::ReturnCallInd { .. } => MachTerminator::TailCall,
             &Inst::IndirectBr { ref targets, .. } => MachTerminator::Indirect(&targets[..]),
+            &Inst::JTSequence { ref info, .. } => {
+                MachTerminator::Indirect(&info.targets_for_term[..])
+            }
             _ => MachTerminator::None,
         }
     }
 
-    fn gen_move(to_reg: Writable<Reg>, from_reg: Reg, _ty: Type) -> Inst {
-        assert_eq!(from_reg.get_class(), RegClass::I32);
+    fn gen_move(to_reg: Writable<Reg>, from_reg: Reg, ty: Type) -> Inst {
+        // Odd single-precision registers only occur as argument locations; see `sreg_hi`.
+        if is_sreg_hi(from_reg) {
+            return Inst::FpuMoveFromSHigh {
+                rd: to_reg,
+                rn: sreg_hi_to_dreg(from_reg),
+            };
+        }
+        if is_sreg_hi(to_reg.to_reg()) {
+            return Inst::FpuMoveToSHigh {
+                rd: Writable::from_reg(sreg_hi_to_dreg(to_reg.to_reg())),
+                rn: from_reg,
+            };
+        }
         assert_eq!(to_reg.to_reg().get_class(), from_reg.get_class());
-
-        Inst::mov(to_reg, from_reg)
+        match from_reg.get_class() {
+            RegClass::I32 => Inst::mov(to_reg, from_reg),
+            RegClass::F64 => Inst::FpuMove {
+                rd: to_reg,
+                rn: from_reg,
+                bits: if ty == F32 { 32 } else { 64 },
+            },
+            _ => panic!("Unexpected register class in gen_move: {:?}", from_reg),
+        }
     }
 
     fn gen_constant<F: FnMut(Type) -> Writable<Reg>>(
         to_regs: ValueRegs<Writable<Reg>>,
         value: u128,
         ty: Type,
-        _alloc_tmp: F,
+        alloc_tmp: F,
     ) -> SmallVec<[Inst; 4]> {
-        let to_reg = to_regs
-            .only_reg()
-            .expect("multi-reg values not supported yet");
         let value = value as u64;
 
         match ty {
-            B1 | I8 | B8 | I16 | B16 | I32 | B32 => {
+            I64 => {
+                let (lo, hi) = (to_regs.regs()[0], to_regs.regs()[1]);
+                let mut insts = Inst::load_constant(lo, value as u32);
+                insts.extend(Inst::load_constant(hi, (value >> 32) as u32));
+                insts
+            }
+            F32 | F64 => {
+                let to_reg = to_regs.only_reg().unwrap();
+                Inst::load_fp_constant(to_reg, value, ty, alloc_tmp)
+            }
+            B1 | I8 | B8 | I16 | B16 | I32 | B32 | R32 => {
+                let to_reg = to_regs.only_reg().unwrap();
                 let v: i64 = value as i64;
 
                 if v >= (1 << 32) || v < -(1 << 32) {
@@ -826,7 +1456,7 @@ impl MachInst for Inst {
                 }
                 Inst::load_constant(to_reg, value as u32)
             }
-            _ => unimplemented!(),
+            _ => panic!("Cannot load constant of type {}", ty),
         }
     }
 
@@ -845,7 +1475,11 @@ impl MachInst for Inst {
     fn rc_for_type(ty: Type) -> CodegenResult<(&'static [RegClass], &'static [Type])> {
         match ty {
             I8 | I16 | I32 | B1 | B8 | B16 | B32 => Ok((&[RegClass::I32], &[I32])),
-            IFLAGS => Ok((&[RegClass::I32], &[I32])),
+            I64 => Ok((&[RegClass::I32, RegClass::I32], &[I32, I32])),
+            R32 => Ok((&[RegClass::I32], &[R32])),
+            F32 => Ok((&[RegClass::F64], &[F32])),
+            F64 => Ok((&[RegClass::F64], &[F64])),
+            IFLAGS | FFLAGS => Ok((&[RegClass::I32], &[I32])),
             _ => Err(CodegenError::Unsupported(format!(
                 "Unexpected SSA-value type: {}",
                 ty
@@ -864,8 +1498,9 @@ impl MachInst for Inst {
     }
 
     fn worst_case_size() -> CodeOffset {
-        // It inst with four 32-bit instructions
-        2 + 4 * 4
+        // The longest sequence is `FpuMinMax` on 64-bit values. Other sequences that can be
+        // longer (`JTSequence`) are always preceded by an `EmitIsland`.
+        64
     }
 
     fn ref_type_regclass(_: &settings::Flags) -> RegClass {
@@ -894,6 +1529,59 @@ fn mem_finalize_for_show(
     (mem_str, mem)
 }
 
+fn show_fpu_mem(
+    mem: &AMode,
+    mb_rru: Option<&RealRegUniverse>,
+    state: &EmitState,
+) -> (String, String) {
+    let (mem_insts, rn, off) = fpu_mem_finalize(mem, state);
+    let mut mem_str = mem_insts
+        .into_iter()
+        .map(|inst| inst.show_rru(mb_rru))
+        .collect::<Vec<_>>()
+        .join(" ; ");
+    if !mem_str.is_empty() {
+        mem_str += " ; ";
+    }
+
+    (mem_str, format!("[{}, #{}]", rn.show_rru(mb_rru), off))
+}
+
+/// Show the single-precision register holding a 32-bit value in the low half of `reg`.
+fn show_sreg(reg: Reg, mb_rru: Option<&RealRegUniverse>) -> String {
+    if reg.is_real() {
+        format!("s{}", 2 * reg.get_hw_encoding())
+    } else {
+        reg.show_rru(mb_rru)
+    }
+}
+
+/// Show the single-precision register making up the high half of `reg`.
+fn show_sreg_hi(reg: Reg, mb_rru: Option<&RealRegUniverse>) -> String {
+    if reg.is_real() {
+        format!("s{}", 2 * reg.get_hw_encoding() + 1)
+    } else {
+        format!("{}.hi", reg.show_rru(mb_rru))
+    }
+}
+
+fn show_freg(reg: Reg, bits: u8, mb_rru: Option<&RealRegUniverse>) -> String {
+    match bits {
+        32 => show_sreg(reg, mb_rru),
+        64 => reg.show_rru(mb_rru),
+        _ => panic!("Invalid floating-point width {}", bits),
+    }
+}
+
+fn show_reg_list(reg_list: &[Reg], mb_rru: Option<&RealRegUniverse>) -> String {
+    assert!(!reg_list.is_empty());
+    reg_list
+        .iter()
+        .map(|r| r.show_rru(mb_rru))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl PrettyPrint for Inst {
     fn show_rru(&self, mb_rru: Option<&RealRegUniverse>) -> String {
         self.pretty_print(mb_rru, &mut EmitState::default())
@@ -920,6 +1608,8 @@ impl Inst {
                 ALUOp::Umull => "umull",
                 ALUOp::Udiv => "udiv",
                 ALUOp::Sdiv => "sdiv",
+                ALUOp::Mla => "mla",
+                ALUOp::Mls => "mls",
                 ALUOp::And => "and",
                 ALUOp::Orr => "orr",
                 ALUOp::Orn => "orn",
@@ -996,6 +1686,20 @@ impl Inst {
                 let rm = rm.show_rru(mb_rru);
                 format!("{} {}, {}, {}, {}", op, rd_lo, rd_hi, rn, rm)
             }
+            &Inst::AluRRRA {
+                alu_op,
+                rd,
+                rn,
+                rm,
+                ra,
+            } => {
+                let op = op_name(alu_op);
+                let rd = rd.show_rru(mb_rru);
+                let rn = rn.show_rru(mb_rru);
+                let rm = rm.show_rru(mb_rru);
+                let ra = ra.show_rru(mb_rru);
+                format!("{} {}, {}, {}, {}", op, rd, rn, rm, ra)
+            }
             &Inst::AluRRImm12 {
                 alu_op,
                 rd,
@@ -1189,6 +1893,32 @@ impl Inst {
                 let rt = rt.show_rru(mb_rru);
                 format!("ldr {}, [pc, #4] ; b 4 ; data {:?} + {}", rt, name, offset)
             }
+            &Inst::ReturnCall {
+                ref dest,
+                sp_adjust,
+                ..
+            } => {
+                let mut ret = String::new();
+                for inst in Inst::gen_sp_add_in_steps(sp_adjust) {
+                    ret.push_str(&inst.show_rru(mb_rru));
+                    ret.push_str(" ; ");
+                }
+                ret.push_str(&format!(
+                    "ldr ip, [pc, #4] ; b 4 ; data {:?} ; mov pc, ip",
+                    dest
+                ));
+                ret
+            }
+            &Inst::ReturnCallInd { rm, sp_adjust, .. } => {
+                let mut ret = String::new();
+                for inst in Inst::gen_sp_add_in_steps(sp_adjust) {
+                    ret.push_str(&inst.show_rru(mb_rru));
+                    ret.push_str(" ; ");
+                }
+                let rm = rm.show_rru(mb_rru);
+                ret.push_str(&format!("bx {}", rm));
+                ret
+            }
             &Inst::Ret => "bx lr".to_string(),
             &Inst::VirtualSPOffsetAdj { offset } => format!("virtual_sp_offset_adjust {}", offset),
             &Inst::EpiloguePlaceholder => "epilogue placeholder".to_string(),
@@ -1216,6 +1946,165 @@ impl Inst {
                 let c = cond.invert().show_rru(mb_rru);
                 format!("b{} 2 ; udf #0", c)
             }
+            &Inst::JTSequence { ridx, ref info } => {
+                let ridx = ridx.show_rru(mb_rru);
+                let default_target = info.default_target.show_rru(mb_rru);
+                let targets = info
+                    .targets
+                    .iter()
+                    .map(|t| t.show_rru(mb_rru))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "bhs {} ; adr ip, #8 ; ldr lr, [ip, {}, lsl #2] ; add ip, lr ; mov pc, ip ; jt_entries [{}]",
+                    default_target, ridx, targets
+                )
+            }
+            &Inst::FpuMove { rd, rn, bits } => {
+                let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                let rn = show_freg(rn, bits, mb_rru);
+                format!("vmov.f{} {}, {}", bits, rd, rn)
+            }
+            &Inst::FpuMoveFromSHigh { rd, rn } => {
+                let rd = show_sreg(rd.to_reg(), mb_rru);
+                let rn = show_sreg_hi(rn, mb_rru);
+                format!("vmov.f32 {}, {}", rd, rn)
+            }
+            &Inst::FpuMoveToSHigh { rd, rn } => {
+                let rd = show_sreg_hi(rd.to_reg(), mb_rru);
+                let rn = show_sreg(rn, mb_rru);
+                format!("vmov.f32 {}, {}", rd, rn)
+            }
+            &Inst::FpuRR {
+                fpu_op,
+                rd,
+                rn,
+                bits,
+            } => match fpu_op {
+                FPUOp1::Cvt32To64 => {
+                    let rd = show_freg(rd.to_reg(), 64, mb_rru);
+                    let rn = show_freg(rn, 32, mb_rru);
+                    format!("vcvt.f64.f32 {}, {}", rd, rn)
+                }
+                FPUOp1::Cvt64To32 => {
+                    let rd = show_freg(rd.to_reg(), 32, mb_rru);
+                    let rn = show_freg(rn, 64, mb_rru);
+                    format!("vcvt.f32.f64 {}, {}", rd, rn)
+                }
+                _ => {
+                    let op = match fpu_op {
+                        FPUOp1::Abs => "vabs",
+                        FPUOp1::Neg => "vneg",
+                        FPUOp1::Sqrt => "vsqrt",
+                        _ => unreachable!(),
+                    };
+                    let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                    let rn = show_freg(rn, bits, mb_rru);
+                    format!("{}.f{} {}, {}", op, bits, rd, rn)
+                }
+            },
+            &Inst::FpuRRR {
+                fpu_op,
+                rd,
+                rn,
+                rm,
+                bits,
+            } => {
+                let op = match fpu_op {
+                    FPUOp2::Add => "vadd",
+                    FPUOp2::Sub => "vsub",
+                    FPUOp2::Mul => "vmul",
+                    FPUOp2::Div => "vdiv",
+                };
+                let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                let rn = show_freg(rn, bits, mb_rru);
+                let rm = show_freg(rm, bits, mb_rru);
+                format!("{}.f{} {}, {}, {}", op, bits, rd, rn, rm)
+            }
+            &Inst::FpuCmp { rn, rm, bits } => {
+                let rn = show_freg(rn, bits, mb_rru);
+                let rm = show_freg(rm, bits, mb_rru);
+                format!("vcmp.f{} {}, {} ; vmrs APSR_nzcv, fpscr", bits, rn, rm)
+            }
+            &Inst::FpuLoad { rd, ref mem, bits } => {
+                let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                let (mem_str, mem) = show_fpu_mem(mem, mb_rru, state);
+                format!("{}vldr {}, {}", mem_str, rd, mem)
+            }
+            &Inst::FpuStore { rd, ref mem, bits } => {
+                let rd = show_freg(rd, bits, mb_rru);
+                let (mem_str, mem) = show_fpu_mem(mem, mb_rru, state);
+                format!("{}vstr {}, {}", mem_str, rd, mem)
+            }
+            &Inst::MovToFpu { rd, rn } => {
+                let rd = show_sreg(rd.to_reg(), mb_rru);
+                let rn = rn.show_rru(mb_rru);
+                format!("vmov {}, {}", rd, rn)
+            }
+            &Inst::MovToFpuPair { rd, rn_lo, rn_hi } => {
+                let rd = rd.show_rru(mb_rru);
+                let rn_lo = rn_lo.show_rru(mb_rru);
+                let rn_hi = rn_hi.show_rru(mb_rru);
+                format!("vmov {}, {}, {}", rd, rn_lo, rn_hi)
+            }
+            &Inst::MovFromFpu { rd, rn } => {
+                let rd = rd.show_rru(mb_rru);
+                let rn = show_sreg(rn, mb_rru);
+                format!("vmov {}, {}", rd, rn)
+            }
+            &Inst::MovFromFpuPair { rd_lo, rd_hi, rn } => {
+                let rd_lo = rd_lo.show_rru(mb_rru);
+                let rd_hi = rd_hi.show_rru(mb_rru);
+                let rn = rn.show_rru(mb_rru);
+                format!("vmov {}, {}, {}", rd_lo, rd_hi, rn)
+            }
+            &Inst::IntToFpu { op, rd, rn } => {
+                let (op, bits) = match op {
+                    IntToFpuOp::I32ToF32 => ("vcvt.f32.s32", 32),
+                    IntToFpuOp::U32ToF32 => ("vcvt.f32.u32", 32),
+                    IntToFpuOp::I32ToF64 => ("vcvt.f64.s32", 64),
+                    IntToFpuOp::U32ToF64 => ("vcvt.f64.u32", 64),
+                };
+                let rs = show_sreg(rd.to_reg(), mb_rru);
+                let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                let rn = rn.show_rru(mb_rru);
+                format!("vmov {}, {} ; {} {}, {}", rs, rn, op, rd, rs)
+            }
+            &Inst::FpuToInt { op, rd, rn, tmp } => {
+                let (op, bits) = match op {
+                    FpuToIntOp::F32ToI32 => ("vcvt.s32.f32", 32),
+                    FpuToIntOp::F32ToU32 => ("vcvt.u32.f32", 32),
+                    FpuToIntOp::F64ToI32 => ("vcvt.s32.f64", 64),
+                    FpuToIntOp::F64ToU32 => ("vcvt.u32.f64", 64),
+                };
+                let rd = rd.show_rru(mb_rru);
+                let rn = show_freg(rn, bits, mb_rru);
+                let tmp = show_sreg(tmp.to_reg(), mb_rru);
+                format!("{} {}, {} ; vmov {}, {}", op, tmp, rn, rd, tmp)
+            }
+            &Inst::FpuMinMax {
+                is_min,
+                rd,
+                rn,
+                rm,
+                bits,
+            } => {
+                let op = if is_min { "fmin" } else { "fmax" };
+                let rd = show_freg(rd.to_reg(), bits, mb_rru);
+                let rn = show_freg(rn, bits, mb_rru);
+                let rm = show_freg(rm, bits, mb_rru);
+                format!("{}.f{} {}, {}, {}", op, bits, rd, rn, rm)
+            }
+            &Inst::FpuPush { ref reg_list } => {
+                format!("vpush {{{}}}", show_reg_list(reg_list, mb_rru))
+            }
+            &Inst::FpuPop { ref reg_list } => {
+                let reg_list = reg_list.iter().map(|r| r.to_reg()).collect::<Vec<_>>();
+                format!("vpop {{{}}}", show_reg_list(&reg_list, mb_rru))
+            }
+            &Inst::Fence => "dmb ish".to_string(),
+            &Inst::EmitIsland { needed_space } => format!("emit_island {}", needed_space),
+            &Inst::Unwind { ref inst } => format!("unwind {:?}", inst),
         }
     }
 }
@@ -1231,10 +2120,14 @@ pub enum LabelUse {
 
     /// 24-bit branch offset used by 32-bit uncoditional jump instruction.
     Branch24,
+
+    /// 32-bit PC relative constant offset (from address of constant itself),
+    /// signed. Used in jump tables.
+    PCRel32,
 }
 
 impl MachInstLabelUse for LabelUse {
-    /// Alignment for veneer code. Every instruction must be 4-byte-aligned.
+    /// Alignment for veneer code. Every instruction must be 2-byte-aligned.
     const ALIGN: CodeOffset = 2;
 
     // Branches range:
@@ -1248,6 +2141,7 @@ impl MachInstLabelUse for LabelUse {
         match self {
             LabelUse::Branch20 => (1 << 20) + 2,
             LabelUse::Branch24 => (1 << 24) + 2,
+            LabelUse::PCRel32 => 0x7fffffff,
         }
     }
 
@@ -1256,6 +2150,7 @@ impl MachInstLabelUse for LabelUse {
         match self {
             LabelUse::Branch20 => (1 << 20) - 4,
             LabelUse::Branch24 => (1 << 24) - 4,
+            LabelUse::PCRel32 => 0x80000000,
         }
     }
 
@@ -1269,6 +2164,14 @@ impl MachInstLabelUse for LabelUse {
         let off = (label_offset as i64) - (use_offset as i64);
         debug_assert!(off <= self.max_pos_range() as i64);
         debug_assert!(off >= -(self.max_neg_range() as i64));
+        if self == LabelUse::PCRel32 {
+            // The word holds an addend, the offset of the entry into its jump table, so that
+            // the result is relative to the start of the table.
+            let word = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+            let word = word.wrapping_add(off as u32);
+            buffer[0..4].clone_from_slice(&u32::to_le_bytes(word));
+            return;
+        }
         let off = off - 4;
         match self {
             LabelUse::Branch20 => {
@@ -1299,23 +2202,35 @@ impl MachInstLabelUse for LabelUse {
                 buffer[0..2].clone_from_slice(&u16::to_le_bytes(insn_fst));
                 buffer[2..4].clone_from_slice(&u16::to_le_bytes(insn_snd));
             }
+            LabelUse::PCRel32 => unreachable!(),
         }
     }
 
     fn supports_veneer(self) -> bool {
-        false
+        match self {
+            LabelUse::Branch20 => true, // veneer is a Branch24
+            _ => false,
+        }
     }
 
     fn veneer_size(self) -> CodeOffset {
-        0
+        4
     }
 
     fn generate_veneer(
         self,
-        _buffer: &mut [u8],
-        _veneer_offset: CodeOffset,
+        buffer: &mut [u8],
+        veneer_offset: CodeOffset,
     ) -> (CodeOffset, LabelUse) {
-        panic!("Veneer not supported yet.")
+        match self {
+            LabelUse::Branch20 => {
+                // The veneer is an unconditional `b.w`, whose offset is patched in later.
+                buffer[0..2].clone_from_slice(&u16::to_le_bytes(0xf000));
+                buffer[2..4].clone_from_slice(&u16::to_le_bytes(0x9000));
+                (veneer_offset, LabelUse::Branch24)
+            }
+            _ => panic!("Unsupported label-reference type for veneer generation!"),
+        }
     }
 }
 
@@ -1358,4 +2273,26 @@ mod tests {
         assert_eq!(u16::from_le_bytes([buffer[0], buffer[1]]), 0xf400);
         assert_eq!(u16::from_le_bytes([buffer[2], buffer[3]]), 0x9000);
     }
+
+    #[test]
+    fn patch_pcrel32() {
+        let label_use = LabelUse::PCRel32;
+        let mut buffer = 8_u32.to_le_bytes(); // third jump table entry
+        label_use.patch(&mut buffer, 100, 40);
+        assert_eq!(u32::from_le_bytes(buffer), (-52_i32) as u32);
+    }
+
+    #[test]
+    fn branch20_veneer() {
+        let label_use = LabelUse::Branch20;
+        assert!(label_use.supports_veneer());
+        let mut buffer = [0_u8; 4];
+        let (use_offset, veneer_use) = label_use.generate_veneer(&mut buffer, 0x100);
+        assert_eq!(use_offset, 0x100);
+        assert_eq!(veneer_use, LabelUse::Branch24);
+        veneer_use.patch(&mut buffer, use_offset, 0x200_000);
+        // b.w 0x200000
+        assert_eq!(u16::from_le_bytes([buffer[0], buffer[1]]), 0xf1ff);
+        assert_eq!(u16::from_le_bytes([buffer[2], buffer[3]]), 0xbf7e);
+    }
 }
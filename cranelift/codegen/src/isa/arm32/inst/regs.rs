@@ -4,10 +4,27 @@ use regalloc::{RealRegUniverse, Reg, RegClass, RegClassInfo, Writable, NUM_REG_C
 
 use std::string::ToString;
 
+/// Universe indices of the GPRs: the allocatable `r0`-`r10` come first, then
+/// the double-precision registers, and the reserved GPRs come last.
+#[rustfmt::skip]
+const RREG_INDICES: [u8; 16] = [
+    // r0 - r10
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10,
+    // fp, ip, sp, lr, pc (reserved)
+    27, 28, 29, 30, 31,
+];
+
+/// Universe index of `d0`.
+const DREG_BASE_INDEX: u8 = 11;
+
 /// Get a reference to a GPR.
 pub fn rreg(num: u8) -> Reg {
     assert!(num < 16);
-    Reg::new_real(RegClass::I32, num, num)
+    Reg::new_real(
+        RegClass::I32,
+        /* enc = */ num,
+        /* index = */ RREG_INDICES[num as usize],
+    )
 }
 
 /// Get a writable reference to a GPR.
@@ -69,9 +86,10 @@ pub fn writable_fp_reg() -> Writable<Reg> {
 /// Get a reference to the second temp register. We need this in some edge cases
 /// where we need both the ip and another temporary.
 ///
-/// We use r10 for this role.
+/// We use lr for this role: the prologue always saves it, so the function
+/// body is free to clobber it.
 pub fn tmp2_reg() -> Reg {
-    rreg(10)
+    lr_reg()
 }
 
 /// Get a writable reference to the tmp2 reg.
@@ -79,35 +97,86 @@ pub fn writable_tmp2_reg() -> Writable<Reg> {
     Writable::from_reg(tmp2_reg())
 }
 
+/// Get a reference to a VFP double-precision register.
+///
+/// Both `f32` and `f64` values live in the `F64` register class: an `f32`
+/// occupies the low single-precision half of its register, i.e. `d<n>` holds
+/// an `f32` in `s<2n>`. Only `d0`-`d15` are used, which is what the VFPv3-D16
+/// baseline of the hard-float ABI guarantees.
+pub fn dreg(num: u8) -> Reg {
+    assert!(num < 16);
+    Reg::new_real(
+        RegClass::F64,
+        /* enc = */ num,
+        /* index = */ DREG_BASE_INDEX + num,
+    )
+}
+
+/// Get a writable reference to a VFP double-precision register.
+pub fn writable_dreg(num: u8) -> Writable<Reg> {
+    Writable::from_reg(dreg(num))
+}
+
+/// Get a reference to the odd single-precision register `s<2n+1>`, i.e. the
+/// high half of `d<n>`.
+///
+/// The hard-float ABI back-fills `f32` arguments into odd single-precision
+/// registers, which the register allocator can't model since they alias the
+/// double-precision registers. This returns a placeholder in the `F32` class
+/// that only ever appears in argument locations: `gen_move` turns moves from
+/// and to it into instructions that use or modify `d<n>` instead, and calls
+/// list `d<n>` in their uses.
+pub fn sreg_hi(num: u8) -> Reg {
+    assert!(num < 8);
+    Reg::new_real(
+        RegClass::F32,
+        /* enc = */ 2 * num + 1,
+        /* index = */ DREG_BASE_INDEX + num,
+    )
+}
+
+/// Is this the placeholder for an odd single-precision register (see
+/// `sreg_hi`)?
+pub fn is_sreg_hi(reg: Reg) -> bool {
+    reg.is_real() && reg.get_class() == RegClass::F32
+}
+
+/// Get the double-precision register containing the odd single-precision
+/// register `reg` (see `sreg_hi`).
+pub fn sreg_hi_to_dreg(reg: Reg) -> Reg {
+    assert!(is_sreg_hi(reg));
+    dreg(reg.get_hw_encoding() / 2)
+}
+
 /// Create the register universe.
-/// Use only GPR for now.
 pub fn create_reg_universe() -> RealRegUniverse {
     let mut regs = vec![];
     let mut allocable_by_class = [None; NUM_REG_CLASSES];
 
-    let r_reg_base = 0u8;
-    let r_reg_count = 10; // to exclude r10, fp, ip, sp, lr  and pc.
-    for i in 0..r_reg_count {
-        let reg = Reg::new_real(
-            RegClass::I32,
-            /* enc = */ i,
-            /* index = */ r_reg_base + i,
-        )
-        .to_real_reg();
-        let name = format!("r{}", i);
-        regs.push((reg, name));
+    // r0 - r10; fp, ip, sp, lr and pc are excluded.
+    let r_reg_base = regs.len();
+    for i in 0..11 {
+        regs.push((rreg(i).to_real_reg(), format!("r{}", i)));
     }
-    let r_reg_last = r_reg_base + r_reg_count - 1;
-
     allocable_by_class[RegClass::I32.rc_to_usize()] = Some(RegClassInfo {
-        first: r_reg_base as usize,
-        last: r_reg_last as usize,
+        first: r_reg_base,
+        last: regs.len() - 1,
+        suggested_scratch: None,
+    });
+
+    // d0 - d15, the call-clobbered d0 - d7 first.
+    let d_reg_base = regs.len();
+    for i in 0..16 {
+        regs.push((dreg(i).to_real_reg(), format!("d{}", i)));
+    }
+    allocable_by_class[RegClass::F64.rc_to_usize()] = Some(RegClassInfo {
+        first: d_reg_base,
+        last: regs.len() - 1,
         suggested_scratch: None,
     });
 
     // Other regs, not available to the allocator.
     let allocable = regs.len();
-    regs.push((tmp2_reg().to_real_reg(), "r10".to_string()));
     regs.push((fp_reg().to_real_reg(), "fp".to_string()));
     regs.push((ip_reg().to_real_reg(), "ip".to_string()));
     regs.push((sp_reg().to_real_reg(), "sp".to_string()));
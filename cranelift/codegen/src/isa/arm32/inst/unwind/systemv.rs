@@ -0,0 +1,176 @@
+//! Unwind information for System V ABI (32-bit ARM).
+
+use crate::isa::arm32::inst::regs;
+use crate::isa::unwind::systemv::RegisterMappingError;
+use gimli::{write::CommonInformationEntry, Encoding, Format, Register};
+use regalloc::{Reg, RegClass};
+
+/// Creates a new arm32 common information entry (CIE).
+pub fn create_cie() -> CommonInformationEntry {
+    use gimli::write::CallFrameInstruction;
+
+    let mut entry = CommonInformationEntry::new(
+        Encoding {
+            address_size: 4,
+            format: Format::Dwarf32,
+            version: 1,
+        },
+        2,  // Code alignment factor
+        -4, // Data alignment factor
+        Register(regs::lr_reg().get_hw_encoding().into()),
+    );
+
+    // Every frame will start with the call frame address (CFA) at SP
+    let sp = Register(regs::sp_reg().get_hw_encoding().into());
+    entry.add_instruction(CallFrameInstruction::Cfa(sp, 0));
+
+    entry
+}
+
+/// Map Cranelift registers to their corresponding Gimli registers.
+pub fn map_reg(reg: Reg) -> Result<Register, RegisterMappingError> {
+    // For ARM DWARF register mappings, see:
+    //
+    // https://github.com/ARM-software/abi-aa/blob/main/aadwarf32/aadwarf32.rst
+    //
+    // r0--r15 is 0--15; d0--d31 is 256--287.
+    match reg.get_class() {
+        RegClass::I32 => {
+            let reg = reg.get_hw_encoding() as u16;
+            Ok(Register(reg))
+        }
+        RegClass::F64 => {
+            let reg = reg.get_hw_encoding() as u16;
+            Ok(Register(256 + reg))
+        }
+        _ => Err(RegisterMappingError::UnsupportedRegisterBank("class?")),
+    }
+}
+
+pub(crate) struct RegisterMapper;
+
+impl crate::isa::unwind::systemv::RegisterMapper<Reg> for RegisterMapper {
+    fn map(&self, reg: Reg) -> Result<u16, RegisterMappingError> {
+        Ok(map_reg(reg)?.0)
+    }
+    fn sp(&self) -> u16 {
+        regs::sp_reg().get_hw_encoding().into()
+    }
+    fn fp(&self) -> Option<u16> {
+        Some(regs::fp_reg().get_hw_encoding().into())
+    }
+    fn lr(&self) -> Option<u16> {
+        Some(regs::lr_reg().get_hw_encoding().into())
+    }
+    fn lr_offset(&self) -> Option<u32> {
+        Some(4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{
+        types, AbiParam, ExternalName, Function, InstBuilder, Signature, StackSlotData,
+        StackSlotKind,
+    };
+    use crate::isa::{lookup, CallConv};
+    use crate::settings::{builder, Flags};
+    use crate::Context;
+    use gimli::write::Address;
+    use target_lexicon::triple;
+
+    #[test]
+    fn test_simple_func() {
+        let isa = lookup(triple!("armv7-unknown-linux-gnueabihf"))
+            .expect("expect arm32 ISA")
+            .finish(Flags::new(builder()));
+
+        let mut context = Context::for_function(create_function(
+            CallConv::SystemV,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 64)),
+        ));
+
+        context.compile(&*isa).expect("expected compilation");
+
+        let fde = match context
+            .create_unwind_info(isa.as_ref())
+            .expect("can create unwind info")
+        {
+            Some(crate::isa::unwind::UnwindInfo::SystemV(info)) => {
+                info.to_fde(Address::Constant(1234))
+            }
+            _ => panic!("expected unwind information"),
+        };
+
+        assert_eq!(format!("{:?}", fde), "FrameDescriptionEntry { address: Constant(1234), length: 18, lsda: None, instructions: [(4, CfaOffset(8)), (4, Offset(Register(11), -8)), (4, Offset(Register(14), -4)), (6, CfaRegister(Register(11)))] }");
+    }
+
+    fn create_function(call_conv: CallConv, stack_slot: Option<StackSlotData>) -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+
+        let block0 = func.dfg.make_block();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(block0);
+        pos.ins().return_(&[]);
+
+        if let Some(stack_slot) = stack_slot {
+            func.stack_slots.push(stack_slot);
+        }
+
+        func
+    }
+
+    #[test]
+    fn test_callee_saves() {
+        let isa = lookup(triple!("armv7-unknown-linux-gnueabihf"))
+            .expect("expect arm32 ISA")
+            .finish(Flags::new(builder()));
+
+        let mut context = Context::for_function(create_callee_save_function(CallConv::SystemV));
+
+        context.compile(&*isa).expect("expected compilation");
+
+        let fde = match context
+            .create_unwind_info(isa.as_ref())
+            .expect("can create unwind info")
+        {
+            Some(crate::isa::unwind::UnwindInfo::SystemV(info)) => {
+                info.to_fde(Address::Constant(4321))
+            }
+            _ => panic!("expected unwind information"),
+        };
+
+        assert_eq!(format!("{:?}", fde), "FrameDescriptionEntry { address: Constant(4321), length: 52, lsda: None, instructions: [(4, CfaOffset(8)), (4, Offset(Register(11), -8)), (4, Offset(Register(14), -4)), (6, CfaRegister(Register(11))), (14, Offset(Register(264), -24)), (14, Offset(Register(4), -16))] }");
+    }
+
+    /// A function that keeps an integer and a floating-point value alive
+    /// across a call, so that it has to save callee-saved registers.
+    fn create_callee_save_function(call_conv: CallConv) -> Function {
+        let mut sig = Signature::new(call_conv);
+        sig.params.push(AbiParam::new(types::I32));
+        sig.params.push(AbiParam::new(types::F64));
+        sig.returns.push(AbiParam::new(types::I32));
+        sig.returns.push(AbiParam::new(types::F64));
+        let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+
+        let callee = func.import_signature(Signature::new(call_conv));
+        let callee = func.import_function(crate::ir::ExtFuncData {
+            name: ExternalName::user(0, 1),
+            signature: callee,
+            colocated: true,
+        });
+
+        let block0 = func.dfg.make_block();
+        let v0 = func.dfg.append_block_param(block0, types::I32);
+        let v1 = func.dfg.append_block_param(block0, types::F64);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(block0);
+        pos.ins().call(callee, &[]);
+        pos.ins().return_(&[v0, v1]);
+
+        func
+    }
+}
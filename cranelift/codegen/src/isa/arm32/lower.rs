@@ -1,48 +1,37 @@
 //! Lowering rules for 32-bit ARM.
+//!
+//! An `i64` lives in a pair of registers, low half first, and `f32` and `f64` values live in the
+//! VFP registers. Integers narrower than 32 bits are not extended in registers; inputs are
+//! extended where an operation needs it. A `B1` is 0 or 1, and wider booleans are 0 or -1.
+//!
+//! `ifcmp`, `ffcmp` and `iadd_ifcout` are merged into their users: the users emit the comparison
+//! themselves, or in the case of `iadd_ifcout` test the flags set by its `adds`, which the CLIF
+//! verifier guarantees are still live.
 
-use crate::ir::condcodes::IntCC;
+use crate::ir::condcodes::{FloatCC, IntCC};
 use crate::ir::types::*;
 use crate::ir::Inst as IRInst;
-use crate::ir::{InstructionData, Opcode, TrapCode};
+use crate::ir::{
+    AbiParam, ArgumentPurpose, ExternalName, InstructionData, LibCall, Opcode, Signature, TrapCode,
+    Type,
+};
+use crate::isa::arm32::abi::*;
+use crate::isa::arm32::inst::*;
+use crate::isa::arm32::Arm32Backend;
+use crate::isa::CallConv;
 use crate::machinst::lower::*;
 use crate::machinst::*;
+use crate::settings::Flags;
 use crate::CodegenResult;
 
-use crate::isa::arm32::inst::*;
-use crate::isa::arm32::Arm32Backend;
-
 use super::lower_inst;
 
 use regalloc::{Reg, Writable};
+use target_lexicon::Triple;
 
 //============================================================================
 // Lowering: convert instruction outputs to result types.
 
-/// Lower an instruction output to a 32-bit constant, if possible.
-pub(crate) fn output_to_const<C: LowerCtx<I = Inst>>(ctx: &mut C, out: InsnOutput) -> Option<u64> {
-    if out.output > 0 {
-        None
-    } else {
-        let inst_data = ctx.data(out.insn);
-        if inst_data.opcode() == Opcode::Null {
-            Some(0)
-        } else {
-            match inst_data {
-                &InstructionData::UnaryImm { opcode: _, imm } => {
-                    // Only has Into for i64; we use u64 elsewhere, so we cast.
-                    let imm: i64 = imm.into();
-                    Some(imm as u64)
-                }
-                &InstructionData::UnaryBool { opcode: _, imm } => Some(u64::from(imm)),
-                &InstructionData::UnaryIeee32 { .. } | &InstructionData::UnaryIeee64 { .. } => {
-                    unimplemented!()
-                }
-                _ => None,
-            }
-        }
-    }
-}
-
 /// How to handle narrow values loaded into registers.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum NarrowValueMode {
@@ -58,6 +47,16 @@ pub(crate) fn output_to_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, out: InsnOutput)
     ctx.get_output(out.insn, out.output).only_reg().unwrap()
 }
 
+/// Lower an instruction output to the low and high halves of an `i64`.
+pub(crate) fn output_to_reg_pair<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    out: InsnOutput,
+) -> (Writable<Reg>, Writable<Reg>) {
+    let regs = ctx.get_output(out.insn, out.output);
+    assert_eq!(regs.len(), 2);
+    (regs.regs()[0], regs.regs()[1])
+}
+
 /// Lower an instruction input to a reg.
 ///
 /// The given register will be extended appropriately, according to `narrow_mode`.
@@ -125,23 +124,75 @@ pub(crate) fn input_to_reg<C: LowerCtx<I = Inst>>(
     }
 }
 
-pub(crate) fn lower_constant<C: LowerCtx<I = Inst>>(ctx: &mut C, rd: Writable<Reg>, value: u64) {
-    // We allow sign bits for high word.
-    assert!((value >> 32) == 0x0 || (value >> 32) == (1 << 32) - 1);
+/// Lower an `i64` instruction input to its low and high halves.
+pub(crate) fn input_to_reg_pair<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    input: InsnInput,
+) -> (Reg, Reg) {
+    let regs = ctx.put_input_in_regs(input.insn, input.input);
+    assert_eq!(regs.len(), 2);
+    (regs.regs()[0], regs.regs()[1])
+}
+
+/// Lower an instruction input to a 64-bit constant, if possible.
+pub(crate) fn input_to_const<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<u64> {
+    ctx.get_input_as_source_or_const(input.insn, input.input)
+        .constant
+}
+
+/// Checks for an instance of `op` feeding the given input.
+pub(crate) fn input_matches_insn<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    input: InsnInput,
+    op: Opcode,
+) -> Option<IRInst> {
+    let inputs = ctx.get_input_as_source_or_const(input.insn, input.input);
+    if let Some((src_inst, _)) = inputs.inst {
+        if ctx.data(src_inst).opcode() == op {
+            return Some(src_inst);
+        }
+    }
+    None
+}
+
+//============================================================================
+// Lowering: generate constants.
 
-    for inst in Inst::load_constant(rd, (value & ((1 << 32) - 1)) as u32) {
+/// Lower a constant of type `ty` into `rd`.
+pub(crate) fn lower_constant<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    rd: ValueRegs<Writable<Reg>>,
+    ty: Type,
+    value: u64,
+) {
+    for inst in Inst::gen_constant(rd, u128::from(value), ty, |ty| {
+        ctx.alloc_tmp(ty).only_reg().unwrap()
+    })
+    .into_iter()
+    {
         ctx.emit(inst);
     }
 }
 
-pub(crate) fn emit_cmp<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) {
-    let inputs = [InsnInput { insn, input: 0 }, InsnInput { insn, input: 1 }];
-    let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-    let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+/// Load a 32-bit constant into a new temporary.
+pub(crate) fn const_to_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, value: u32) -> Reg {
+    let rd = ctx.alloc_tmp(I32).only_reg().unwrap();
+    for inst in Inst::load_constant(rd, value) {
+        ctx.emit(inst);
+    }
+    rd.to_reg()
+}
 
-    ctx.emit(Inst::Cmp { rn, rm });
+/// Load a floating-point constant of type `ty`, given as its bit pattern, into a new temporary.
+pub(crate) fn fp_const_to_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, ty: Type, bits: u64) -> Reg {
+    let rd = ctx.alloc_tmp(ty).only_reg().unwrap();
+    lower_constant(ctx, ValueRegs::one(rd), ty, bits);
+    rd.to_reg()
 }
 
+//============================================================================
+// Lowering: condition codes and comparisons.
+
 pub(crate) fn lower_condcode(cc: IntCC) -> Cond {
     match cc {
         IntCC::Equal => Cond::Eq,
@@ -177,6 +228,199 @@ pub(crate) fn condcode_is_signed(cc: IntCC) -> bool {
     }
 }
 
+/// Lower a floating-point condition code to the condition which holds after a `vcmp` (and
+/// `vmrs`). Two conditions of which either may hold are needed for `one` and `ueq`.
+pub(crate) fn lower_fp_condcode(cc: FloatCC) -> (Cond, Option<Cond>) {
+    // `vcmp` sets NZCV to 0110 for equal, 1000 for less than, 0010 for greater than and 0011
+    // for unordered operands.
+    match cc {
+        FloatCC::Ordered => (Cond::Vc, None),
+        FloatCC::Unordered => (Cond::Vs, None),
+        FloatCC::Equal => (Cond::Eq, None),
+        FloatCC::NotEqual => (Cond::Ne, None),
+        FloatCC::OrderedNotEqual => (Cond::Mi, Some(Cond::Gt)),
+        FloatCC::UnorderedOrEqual => (Cond::Eq, Some(Cond::Vs)),
+        FloatCC::LessThan => (Cond::Mi, None),
+        FloatCC::LessThanOrEqual => (Cond::Ls, None),
+        FloatCC::GreaterThan => (Cond::Gt, None),
+        FloatCC::GreaterThanOrEqual => (Cond::Ge, None),
+        FloatCC::UnorderedOrLessThan => (Cond::Lt, None),
+        FloatCC::UnorderedOrLessThanOrEqual => (Cond::Le, None),
+        FloatCC::UnorderedOrGreaterThan => (Cond::Hi, None),
+        FloatCC::UnorderedOrGreaterThanOrEqual => (Cond::Hs, None),
+    }
+}
+
+/// Emit a comparison of the first two inputs of `insn` (an `icmp`, `ifcmp` or `br_icmp`), and
+/// return the condition which holds when `cc` holds for them.
+pub(crate) fn emit_cmp<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst, cc: IntCC) -> Cond {
+    let inputs = [InsnInput { insn, input: 0 }, InsnInput { insn, input: 1 }];
+    let ty = ctx.input_ty(insn, 0);
+    let cond = lower_condcode(cc);
+
+    if ty == I64 {
+        let (a_lo, a_hi) = input_to_reg_pair(ctx, inputs[0]);
+        let (b_lo, b_hi) = input_to_reg_pair(ctx, inputs[1]);
+        if condcode_is_signed(cc) {
+            // The flags of the full subtraction give the signed conditions, except that Z
+            // only reflects the high half. Swap the operands to test `gt` and `le` as `lt`
+            // and `ge`.
+            let (a_lo, a_hi, b_lo, b_hi, cond) = match cond {
+                Cond::Gt => (b_lo, b_hi, a_lo, a_hi, Cond::Lt),
+                Cond::Le => (b_lo, b_hi, a_lo, a_hi, Cond::Ge),
+                _ => (a_lo, a_hi, b_lo, b_hi, cond),
+            };
+            let tmp = ctx.alloc_tmp(I32).only_reg().unwrap();
+            ctx.emit(Inst::Cmp { rn: a_lo, rm: b_lo });
+            ctx.emit(Inst::AluRRRShift {
+                alu_op: ALUOp::Sbcs,
+                rd: tmp,
+                rn: a_hi,
+                rm: b_hi,
+                shift: None,
+            });
+            cond
+        } else {
+            // Compare the low halves only if the high halves are equal.
+            ctx.emit(Inst::Cmp { rn: a_hi, rm: b_hi });
+            ctx.emit(Inst::It {
+                cond: Cond::Eq,
+                insts: vec![CondInst::new(Inst::Cmp { rn: a_lo, rm: b_lo }, true)],
+            });
+            cond
+        }
+    } else {
+        let is_signed = condcode_is_signed(cc);
+        let narrow_mode = if is_signed {
+            NarrowValueMode::SignExtend
+        } else {
+            NarrowValueMode::ZeroExtend
+        };
+        let rn = input_to_reg(ctx, inputs[0], narrow_mode);
+        // A constant which is the same once extended can be an immediate.
+        let bits = ty.bits();
+        let imm8 = input_to_const(ctx, inputs[1])
+            .filter(|&c| c < 256 && (bits >= 32 || !is_signed || c < (1 << (bits - 1))));
+        match imm8 {
+            Some(c) => ctx.emit(Inst::CmpImm8 { rn, imm8: c as u8 }),
+            None => {
+                let rm = input_to_reg(ctx, inputs[1], narrow_mode);
+                ctx.emit(Inst::Cmp { rn, rm });
+            }
+        }
+        cond
+    }
+}
+
+/// Emit a comparison of the two inputs of `insn` (an `fcmp`, `ffcmp` or `fcmp`-fed branch), and
+/// return the conditions of which either holds when `cc` holds for them.
+pub(crate) fn emit_fcmp<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    cc: FloatCC,
+) -> (Cond, Option<Cond>) {
+    let bits = ctx.input_ty(insn, 0).bits() as u8;
+    let rn = input_to_reg(ctx, InsnInput { insn, input: 0 }, NarrowValueMode::None);
+    let rm = input_to_reg(ctx, InsnInput { insn, input: 1 }, NarrowValueMode::None);
+    ctx.emit(Inst::FpuCmp { rn, rm, bits });
+    lower_fp_condcode(cc)
+}
+
+/// Set the flags from the `iflags` input `input`, and return the condition which holds when `cc`
+/// holds.
+pub(crate) fn lower_iflags<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    input: InsnInput,
+    cc: IntCC,
+) -> Cond {
+    if let Some(cmp_insn) = input_matches_insn(ctx, input, Opcode::Ifcmp) {
+        emit_cmp(ctx, cmp_insn, cc)
+    } else if input_matches_insn(ctx, input, Opcode::IaddIfcout).is_some() {
+        // The flags set by the `adds` of the `iadd_ifcout` are still live: the CLIF verifier
+        // checks that no other instruction clobbers them in between.
+        lower_condcode(cc)
+    } else {
+        panic!("iflags input {:?} is not an ifcmp or iadd_ifcout", input);
+    }
+}
+
+/// Set the flags from the `fflags` input `input`, and return the conditions of which either
+/// holds when `cc` holds.
+pub(crate) fn lower_fflags<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    input: InsnInput,
+    cc: FloatCC,
+) -> (Cond, Option<Cond>) {
+    let cmp_insn = input_matches_insn(ctx, input, Opcode::Ffcmp)
+        .unwrap_or_else(|| panic!("fflags input {:?} is not an ffcmp", input));
+    emit_fcmp(ctx, cmp_insn, cc)
+}
+
+/// Materialize the condition `cond`, or `cond2` if given, from the flags into `rd` as a `B1`.
+pub(crate) fn materialize_bool<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    rd: Writable<Reg>,
+    cond: Cond,
+    cond2: Option<Cond>,
+) {
+    ctx.emit(Inst::It {
+        cond,
+        insts: vec![
+            CondInst::new(Inst::MovImm16 { rd, imm16: 1 }, true),
+            CondInst::new(Inst::MovImm16 { rd, imm16: 0 }, false),
+        ],
+    });
+    if let Some(cond2) = cond2 {
+        ctx.emit(Inst::It {
+            cond: cond2,
+            insts: vec![CondInst::new(Inst::MovImm16 { rd, imm16: 1 }, true)],
+        });
+    }
+}
+
+/// Set the flags from the boolean or integer input `input` (of `brz`, `brnz` or `select`), merging
+/// a feeding `icmp` or `fcmp`, and return the condition which holds when the input is true.
+pub(crate) fn lower_bool_to_flags<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Cond {
+    if let Some(icmp_insn) = input_matches_insn(ctx, input, Opcode::Icmp) {
+        let cc = inst_condcode(ctx.data(icmp_insn)).unwrap();
+        return emit_cmp(ctx, icmp_insn, cc);
+    }
+    if let Some(fcmp_insn) = input_matches_insn(ctx, input, Opcode::Fcmp) {
+        let cc = inst_fp_condcode(ctx.data(fcmp_insn)).unwrap();
+        let (cond, cond2) = emit_fcmp(ctx, fcmp_insn, cc);
+        match cond2 {
+            None => return cond,
+            Some(cond2) => {
+                let tmp = ctx.alloc_tmp(B1).only_reg().unwrap();
+                materialize_bool(ctx, tmp, cond, Some(cond2));
+                ctx.emit(Inst::CmpImm8 {
+                    rn: tmp.to_reg(),
+                    imm8: 0,
+                });
+                return Cond::Ne;
+            }
+        }
+    }
+
+    let ty = ctx.input_ty(input.insn, input.input);
+    let rn = if ty == I64 {
+        let (lo, hi) = input_to_reg_pair(ctx, input);
+        let tmp = ctx.alloc_tmp(I32).only_reg().unwrap();
+        ctx.emit(Inst::AluRRRShift {
+            alu_op: ALUOp::Orr,
+            rd: tmp,
+            rn: lo,
+            rm: hi,
+            shift: None,
+        });
+        tmp.to_reg()
+    } else {
+        input_to_reg(ctx, input, NarrowValueMode::ZeroExtend)
+    };
+    ctx.emit(Inst::CmpImm8 { rn, imm8: 0 });
+    Cond::Ne
+}
+
 //=============================================================================
 // Helpers for instruction lowering.
 
@@ -205,18 +449,128 @@ pub(crate) fn inst_condcode(data: &InstructionData) -> Option<IntCC> {
     }
 }
 
+pub(crate) fn inst_fp_condcode(data: &InstructionData) -> Option<FloatCC> {
+    match data {
+        &InstructionData::BranchFloat { cond, .. }
+        | &InstructionData::FloatCompare { cond, .. }
+        | &InstructionData::FloatCond { cond, .. }
+        | &InstructionData::FloatCondTrap { cond, .. } => Some(cond),
+        _ => None,
+    }
+}
+
 pub(crate) fn inst_trapcode(data: &InstructionData) -> Option<TrapCode> {
     match data {
         &InstructionData::Trap { code, .. }
         | &InstructionData::CondTrap { code, .. }
-        | &InstructionData::IntCondTrap { code, .. } => Some(code),
-        &InstructionData::FloatCondTrap { code, .. } => {
-            panic!("Unexpected float cond trap {:?}", code)
-        }
+        | &InstructionData::IntCondTrap { code, .. }
+        | &InstructionData::FloatCondTrap { code, .. } => Some(code),
         _ => None,
     }
 }
 
+/// Lower the address of a load or store with the given addends and offset.
+pub(crate) fn lower_address<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    addends: &[InsnInput],
+    offset: i32,
+) -> AMode {
+    let offset = i64::from(offset);
+    match addends.len() {
+        1 => AMode::RegOffset(input_to_reg(ctx, addends[0], NarrowValueMode::None), offset),
+        2 if offset == 0 => {
+            let rn = input_to_reg(ctx, addends[0], NarrowValueMode::None);
+            let rm = input_to_reg(ctx, addends[1], NarrowValueMode::None);
+            AMode::RegReg(rn, rm, 0)
+        }
+        _ => {
+            let tmp = ctx.alloc_tmp(I32).only_reg().unwrap();
+            let mut base = input_to_reg(ctx, addends[0], NarrowValueMode::None);
+            for &addend in &addends[1..] {
+                let rm = input_to_reg(ctx, addend, NarrowValueMode::None);
+                ctx.emit(Inst::AluRRRShift {
+                    alu_op: ALUOp::Add,
+                    rd: tmp,
+                    rn: base,
+                    rm,
+                    shift: None,
+                });
+                base = tmp.to_reg();
+            }
+            AMode::RegOffset(base, offset)
+        }
+    }
+}
+
+fn make_libcall_sig<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    call_conv: CallConv,
+) -> Signature {
+    let mut sig = Signature::new(call_conv);
+    for i in 0..ctx.num_inputs(insn) {
+        sig.params.push(AbiParam::new(ctx.input_ty(insn, i)));
+    }
+    for i in 0..ctx.num_outputs(insn) {
+        sig.returns.push(AbiParam::new(ctx.output_ty(insn, i)));
+    }
+    if call_conv.extends_baldrdash() {
+        // Adds the special VMContext parameter to the signature.
+        sig.params
+            .push(AbiParam::special(I32, ArgumentPurpose::VMContext));
+    }
+    sig
+}
+
+/// Call the library routine `libcall` to compute `insn`, with the given arguments, which have the
+/// types of the inputs of `insn`, and put its results into `outputs`.
+pub(crate) fn emit_vm_call<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    flags: &Flags,
+    triple: &Triple,
+    libcall: LibCall,
+    insn: IRInst,
+    args: &[ValueRegs<Reg>],
+    outputs: &[ValueRegs<Writable<Reg>>],
+) -> CodegenResult<()> {
+    let extname = ExternalName::LibCall(libcall);
+
+    let dist = if flags.use_colocated_libcalls() {
+        RelocDistance::Near
+    } else {
+        RelocDistance::Far
+    };
+
+    let call_conv = CallConv::for_libcall(flags, CallConv::triple_default(triple));
+    let sig = make_libcall_sig(ctx, insn, call_conv);
+    let caller_conv = ctx.abi().call_conv();
+
+    let mut abi = Arm32ABICaller::from_func(&sig, &extname, dist, caller_conv, flags)?;
+
+    abi.emit_stack_pre_adjust(ctx);
+
+    let vm_context = if call_conv.extends_baldrdash() { 1 } else { 0 };
+    assert_eq!(args.len() + vm_context, abi.num_args());
+
+    for (i, arg_regs) in args.iter().enumerate() {
+        abi.emit_copy_regs_to_arg(ctx, i, *arg_regs);
+    }
+    if call_conv.extends_baldrdash() {
+        let vm_context_vreg = ctx
+            .get_vm_context()
+            .expect("should have a VMContext to pass to libcall funcs");
+        abi.emit_copy_regs_to_arg(ctx, args.len(), ValueRegs::one(vm_context_vreg));
+    }
+
+    abi.emit_call(ctx);
+    for (i, retval_regs) in outputs.iter().enumerate() {
+        abi.emit_copy_retval_to_regs(ctx, i, *retval_regs);
+    }
+    abi.emit_stack_post_adjust(ctx);
+
+    Ok(())
+}
+
 //=============================================================================
 // Lowering-backend trait implementation.
 
@@ -224,7 +578,7 @@ impl LowerBackend for Arm32Backend {
     type MInst = Inst;
 
     fn lower<C: LowerCtx<I = Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
-        lower_inst::lower_insn_to_regs(ctx, ir_inst, &self.flags)
+        lower_inst::lower_insn_to_regs(ctx, ir_inst, &self.flags, &self.triple)
     }
 
     fn lower_branch_group<C: LowerCtx<I = Inst>>(
@@ -1,25 +1,1156 @@
 //! Lower a single Cranelift instruction into vcode.
 
+use crate::binemit::CodeOffset;
 use crate::ir::types::*;
 use crate::ir::Inst as IRInst;
-use crate::ir::Opcode;
+use crate::ir::{InstructionData, LibCall, Opcode, TrapCode, Type};
 use crate::machinst::lower::*;
 use crate::machinst::*;
 use crate::settings::Flags;
-use crate::CodegenResult;
+use crate::{CodegenError, CodegenResult};
 
 use crate::isa::arm32::abi::*;
 use crate::isa::arm32::inst::*;
 
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use regalloc::{Reg, Writable};
 use smallvec::SmallVec;
+use target_lexicon::Triple;
 
 use super::lower::*;
 
+//=============================================================================
+// Helpers for building instructions.
+
+fn alu_rrr(alu_op: ALUOp, rd: Writable<Reg>, rn: Reg, rm: Reg) -> Inst {
+    match alu_op {
+        ALUOp::Lsl
+        | ALUOp::Lsr
+        | ALUOp::Asr
+        | ALUOp::Ror
+        | ALUOp::Mul
+        | ALUOp::Udiv
+        | ALUOp::Sdiv => Inst::AluRRR { alu_op, rd, rn, rm },
+        _ => Inst::AluRRRShift {
+            alu_op,
+            rd,
+            rn,
+            rm,
+            shift: None,
+        },
+    }
+}
+
+fn alu_rr_imm8(alu_op: ALUOp, rd: Writable<Reg>, rn: Reg, imm: u8) -> Inst {
+    Inst::AluRRImm8 {
+        alu_op,
+        rd,
+        rn,
+        imm8: UImm8::maybe_from_i64(i64::from(imm)).unwrap(),
+    }
+}
+
+fn shift_op(op: ShiftOp, amt: u32) -> Option<ShiftOpAndAmt> {
+    Some(ShiftOpAndAmt::new(
+        op,
+        ShiftOpShiftImm::maybe_from_shift(amt).unwrap(),
+    ))
+}
+
+/// A shift of `rm` by the constant `amt`, which must be less than 32.
+fn shift_imm(op: ShiftOp, rd: Writable<Reg>, rm: Reg, amt: u32) -> Inst {
+    if amt == 0 {
+        Inst::mov(rd, rm)
+    } else {
+        Inst::AluRRShift {
+            alu_op: ALUOp1::Mov,
+            rd,
+            rm,
+            shift: shift_op(op, amt),
+        }
+    }
+}
+
+/// Set `rd` to `rn` if `cond` holds, and to `rm` otherwise.
+fn it_select(cond: Cond, rd: Writable<Reg>, rn: Reg, rm: Reg, ty: Type) -> Inst {
+    Inst::It {
+        cond,
+        insts: vec![
+            CondInst::new(Inst::gen_move(rd, rn, ty), true),
+            CondInst::new(Inst::gen_move(rd, rm, ty), false),
+        ],
+    }
+}
+
+fn tmp_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, ty: Type) -> Writable<Reg> {
+    ctx.alloc_tmp(ty).only_reg().unwrap()
+}
+
+/// The mask of the bits of a value of type `ty`.
+fn ty_mask(ty: Type) -> u64 {
+    match ty.bits() {
+        64 => u64::MAX,
+        bits => (1 << bits) - 1,
+    }
+}
+
+/// The bit pattern of `value` as a floating-point value of type `ty`.
+fn fp_bits(ty: Type, value: f64) -> u64 {
+    if ty == F32 {
+        u64::from((value as f32).to_bits())
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Split an addressing mode for a 64-bit access into the ones of its two words.
+fn split_amode<C: LowerCtx<I = Inst>>(ctx: &mut C, mem: AMode) -> (AMode, AMode) {
+    match mem {
+        AMode::RegOffset(base, off) => {
+            (AMode::RegOffset(base, off), AMode::RegOffset(base, off + 4))
+        }
+        AMode::RegReg(rn, rm, 0) => {
+            let base = tmp_reg(ctx, I32);
+            ctx.emit(alu_rrr(ALUOp::Add, base, rn, rm));
+            (
+                AMode::RegOffset(base.to_reg(), 0),
+                AMode::RegOffset(base.to_reg(), 4),
+            )
+        }
+        _ => panic!("Unexpected addressing mode {:?}", mem),
+    }
+}
+
+//=============================================================================
+// Lowering of groups of opcodes.
+
+/// Lower `udiv`, `sdiv`, `urem` and `srem`.
+fn lower_div_rem<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    flags: &Flags,
+    triple: &Triple,
+) -> CodegenResult<()> {
+    let op = ctx.data(insn).opcode();
+    let ty = ctx.output_ty(insn, 0);
+    let inputs = [InsnInput { insn, input: 0 }, InsnInput { insn, input: 1 }];
+    let is_signed = op == Opcode::Sdiv || op == Opcode::Srem;
+    let divisor_const = input_to_const(ctx, inputs[1]).map(|c| c & ty_mask(ty));
+    let may_be_zero = divisor_const.map_or(true, |c| c == 0);
+    let may_be_minus_one = is_signed && divisor_const.map_or(true, |c| c == ty_mask(ty));
+
+    if ty == I64 {
+        let (a_lo, a_hi) = input_to_reg_pair(ctx, inputs[0]);
+        let (b_lo, b_hi) = input_to_reg_pair(ctx, inputs[1]);
+
+        if may_be_zero {
+            let tmp = tmp_reg(ctx, I32);
+            ctx.emit(alu_rrr(ALUOp::Orr, tmp, b_lo, b_hi));
+            ctx.emit(Inst::CmpImm8 {
+                rn: tmp.to_reg(),
+                imm8: 0,
+            });
+            ctx.emit_safepoint(Inst::TrapIf {
+                cond: Cond::Eq,
+                trap_info: TrapCode::IntegerDivisionByZero,
+            });
+        }
+
+        let (b_lo, b_hi) = if may_be_minus_one {
+            // Set Z if the divisor is -1.
+            let tmp = tmp_reg(ctx, I32);
+            ctx.emit(alu_rrr(ALUOp::And, tmp, b_lo, b_hi));
+            ctx.emit(alu_rr_imm8(ALUOp::Adds, tmp, tmp.to_reg(), 1));
+            if op == Opcode::Sdiv {
+                // Trap if the dividend is also INT_MIN.
+                let int_min_hi = const_to_reg(ctx, 0x8000_0000);
+                ctx.emit(Inst::It {
+                    cond: Cond::Eq,
+                    insts: vec![
+                        CondInst::new(
+                            Inst::Cmp {
+                                rn: a_hi,
+                                rm: int_min_hi,
+                            },
+                            true,
+                        ),
+                        CondInst::new(Inst::CmpImm8 { rn: a_lo, imm8: 0 }, true),
+                    ],
+                });
+                ctx.emit_safepoint(Inst::TrapIf {
+                    cond: Cond::Eq,
+                    trap_info: TrapCode::IntegerOverflow,
+                });
+                (b_lo, b_hi)
+            } else {
+                // The remainder of a division by -1 is 0, as it is for a division by 1, which
+                // doesn't overflow in the library routine for INT_MIN.
+                let lo = tmp_reg(ctx, I32);
+                let hi = tmp_reg(ctx, I32);
+                ctx.emit(Inst::It {
+                    cond: Cond::Eq,
+                    insts: vec![
+                        CondInst::new(Inst::MovImm16 { rd: lo, imm16: 1 }, true),
+                        CondInst::new(Inst::mov(lo, b_lo), false),
+                    ],
+                });
+                ctx.emit(Inst::It {
+                    cond: Cond::Eq,
+                    insts: vec![
+                        CondInst::new(Inst::MovImm16 { rd: hi, imm16: 0 }, true),
+                        CondInst::new(Inst::mov(hi, b_hi), false),
+                    ],
+                });
+                (lo.to_reg(), hi.to_reg())
+            }
+        } else {
+            (b_lo, b_hi)
+        };
+
+        let libcall = LibCall::for_inst(op, I64).unwrap();
+        let rd = ctx.get_output(insn, 0);
+        return emit_vm_call(
+            ctx,
+            flags,
+            triple,
+            libcall,
+            insn,
+            &[ValueRegs::two(a_lo, a_hi), ValueRegs::two(b_lo, b_hi)],
+            &[rd],
+        );
+    }
+
+    let narrow_mode = if is_signed {
+        NarrowValueMode::SignExtend
+    } else {
+        NarrowValueMode::ZeroExtend
+    };
+    let rd = output_to_reg(ctx, InsnOutput { insn, output: 0 });
+    let rn = input_to_reg(ctx, inputs[0], narrow_mode);
+    let rm = input_to_reg(ctx, inputs[1], narrow_mode);
+
+    if may_be_zero {
+        ctx.emit(Inst::CmpImm8 { rn: rm, imm8: 0 });
+        ctx.emit_safepoint(Inst::TrapIf {
+            cond: Cond::Eq,
+            trap_info: TrapCode::IntegerDivisionByZero,
+        });
+    }
+
+    // `sdiv` yields INT_MIN for INT_MIN / -1 rather than trapping. The remainder is 0 then, as
+    // we need.
+    if op == Opcode::Sdiv && may_be_minus_one {
+        // The smallest value of the type, sign-extended.
+        let int_min = const_to_reg(ctx, (-1i32 << (ty.bits() - 1)) as u32);
+        let tmp = tmp_reg(ctx, I32);
+        ctx.emit(alu_rr_imm8(ALUOp::Adds, tmp, rm, 1));
+        ctx.emit(Inst::It {
+            cond: Cond::Eq,
+            insts: vec![CondInst::new(Inst::Cmp { rn, rm: int_min }, true)],
+        });
+        ctx.emit_safepoint(Inst::TrapIf {
+            cond: Cond::Eq,
+            trap_info: TrapCode::IntegerOverflow,
+        });
+    }
+
+    let div_op = if is_signed { ALUOp::Sdiv } else { ALUOp::Udiv };
+    match op {
+        Opcode::Udiv | Opcode::Sdiv => ctx.emit(alu_rrr(div_op, rd, rn, rm)),
+        Opcode::Urem | Opcode::Srem => {
+            let quotient = tmp_reg(ctx, I32);
+            ctx.emit(alu_rrr(div_op, quotient, rn, rm));
+            ctx.emit(Inst::AluRRRA {
+                alu_op: ALUOp::Mls,
+                rd,
+                rn: quotient.to_reg(),
+                rm,
+                ra: rn,
+            });
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Lower the shift amount input of a shift or rotate.
+fn input_to_shift_amt<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Reg {
+    if ctx.input_ty(input.insn, input.input) == I64 {
+        input_to_reg_pair(ctx, input).0
+    } else {
+        input_to_reg(ctx, input, NarrowValueMode::None)
+    }
+}
+
+/// Lower `ishl`, `ushr` and `sshr`.
+fn lower_shift<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) -> CodegenResult<()> {
+    let op = ctx.data(insn).opcode();
+    let ty = ctx.output_ty(insn, 0);
+    let inputs = [InsnInput { insn, input: 0 }, InsnInput { insn, input: 1 }];
+    let output = InsnOutput { insn, output: 0 };
+    let bits = ty.bits();
+    let amt_const = input_to_const(ctx, inputs[1]).map(|c| (c & u64::from(bits - 1)) as u32);
+
+    if ty != I64 {
+        let (shift, alu_op, narrow_mode) = match op {
+            Opcode::Ishl => (ShiftOp::LSL, ALUOp::Lsl, NarrowValueMode::None),
+            Opcode::Ushr => (ShiftOp::LSR, ALUOp::Lsr, NarrowValueMode::ZeroExtend),
+            Opcode::Sshr => (ShiftOp::ASR, ALUOp::Asr, NarrowValueMode::SignExtend),
+            _ => unreachable!(),
+        };
+        let rd = output_to_reg(ctx, output);
+        let rn = input_to_reg(ctx, inputs[0], narrow_mode);
+        match amt_const {
+            Some(amt) => ctx.emit(shift_imm(shift, rd, rn, amt)),
+            None => {
+                // Register-specified shifts use the bottom byte of the amount, which must be
+                // taken modulo the width of the type first.
+                let amt = input_to_shift_amt(ctx, inputs[1]);
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(alu_rr_imm8(ALUOp::And, tmp, amt, (bits - 1) as u8));
+                ctx.emit(alu_rrr(alu_op, rd, rn, tmp.to_reg()));
+            }
+        }
+        return Ok(());
+    }
+
+    let (lo, hi) = input_to_reg_pair(ctx, inputs[0]);
+    let (rd_lo, rd_hi) = output_to_reg_pair(ctx, output);
+
+    if let Some(amt) = amt_const {
+        match (op, amt) {
+            (Opcode::Ishl, 0..=31) => {
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(shift_imm(ShiftOp::LSL, tmp, hi, amt));
+                if amt == 0 {
+                    ctx.emit(Inst::mov(rd_hi, hi));
+                } else {
+                    ctx.emit(Inst::AluRRRShift {
+                        alu_op: ALUOp::Orr,
+                        rd: rd_hi,
+                        rn: tmp.to_reg(),
+                        rm: lo,
+                        shift: shift_op(ShiftOp::LSR, 32 - amt),
+                    });
+                }
+                ctx.emit(shift_imm(ShiftOp::LSL, rd_lo, lo, amt));
+            }
+            (Opcode::Ishl, _) => {
+                ctx.emit(shift_imm(ShiftOp::LSL, rd_hi, lo, amt - 32));
+                ctx.emit(Inst::MovImm16 {
+                    rd: rd_lo,
+                    imm16: 0,
+                });
+            }
+            (_, 0..=31) => {
+                let hi_shift = if op == Opcode::Ushr {
+                    ShiftOp::LSR
+                } else {
+                    ShiftOp::ASR
+                };
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(shift_imm(ShiftOp::LSR, tmp, lo, amt));
+                if amt == 0 {
+                    ctx.emit(Inst::mov(rd_lo, lo));
+                } else {
+                    ctx.emit(Inst::AluRRRShift {
+                        alu_op: ALUOp::Orr,
+                        rd: rd_lo,
+                        rn: tmp.to_reg(),
+                        rm: hi,
+                        shift: shift_op(ShiftOp::LSL, 32 - amt),
+                    });
+                }
+                ctx.emit(shift_imm(hi_shift, rd_hi, hi, amt));
+            }
+            (Opcode::Ushr, _) => {
+                ctx.emit(shift_imm(ShiftOp::LSR, rd_lo, hi, amt - 32));
+                ctx.emit(Inst::MovImm16 {
+                    rd: rd_hi,
+                    imm16: 0,
+                });
+            }
+            (_, _) => {
+                ctx.emit(shift_imm(ShiftOp::ASR, rd_lo, hi, amt - 32));
+                ctx.emit(shift_imm(ShiftOp::ASR, rd_hi, hi, 31));
+            }
+        }
+        return Ok(());
+    }
+
+    // Register-specified shifts by 32 to 255 yield 0 (or the sign for `asr`), which takes care
+    // of the bits moving from one half into the other:
+    //
+    //   ishl: hi = (hi << n) | (lo >> (32 - n)) | (lo << (n - 32)); lo = lo << n
+    //   ushr: lo = (lo >> n) | (hi << (32 - n)) | (hi >> (n - 32)); hi = hi >> n
+    //
+    // For `sshr`, `hi asr (n - 32)` replaces the low half if n >= 32 instead.
+    let amt = input_to_shift_amt(ctx, inputs[1]);
+    let n = tmp_reg(ctx, I32);
+    let n_rev = tmp_reg(ctx, I32);
+    let t1 = tmp_reg(ctx, I32);
+    let t2 = tmp_reg(ctx, I32);
+    ctx.emit(alu_rr_imm8(ALUOp::And, n, amt, 63));
+    ctx.emit(alu_rr_imm8(ALUOp::Rsb, n_rev, n.to_reg(), 32));
+    match op {
+        Opcode::Ishl => {
+            let n_sub = tmp_reg(ctx, I32);
+            let t3 = tmp_reg(ctx, I32);
+            ctx.emit(alu_rr_imm8(ALUOp::Sub, n_sub, n.to_reg(), 32));
+            ctx.emit(alu_rrr(ALUOp::Lsl, t1, hi, n.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsr, t2, lo, n_rev.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsl, t3, lo, n_sub.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Orr, t1, t1.to_reg(), t2.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Orr, rd_hi, t1.to_reg(), t3.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsl, rd_lo, lo, n.to_reg()));
+        }
+        Opcode::Ushr => {
+            let n_sub = tmp_reg(ctx, I32);
+            let t3 = tmp_reg(ctx, I32);
+            ctx.emit(alu_rr_imm8(ALUOp::Sub, n_sub, n.to_reg(), 32));
+            ctx.emit(alu_rrr(ALUOp::Lsr, t1, lo, n.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsl, t2, hi, n_rev.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsr, t3, hi, n_sub.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Orr, t1, t1.to_reg(), t2.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Orr, rd_lo, t1.to_reg(), t3.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsr, rd_hi, hi, n.to_reg()));
+        }
+        Opcode::Sshr => {
+            let n_sub = tmp_reg(ctx, I32);
+            ctx.emit(alu_rrr(ALUOp::Lsr, t1, lo, n.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Lsl, t2, hi, n_rev.to_reg()));
+            ctx.emit(alu_rrr(ALUOp::Orr, rd_lo, t1.to_reg(), t2.to_reg()));
+            ctx.emit(alu_rr_imm8(ALUOp::Subs, n_sub, n.to_reg(), 32));
+            ctx.emit(Inst::It {
+                cond: Cond::Pl,
+                insts: vec![CondInst::new(
+                    alu_rrr(ALUOp::Asr, rd_lo, hi, n_sub.to_reg()),
+                    true,
+                )],
+            });
+            ctx.emit(alu_rrr(ALUOp::Asr, rd_hi, hi, n.to_reg()));
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Lower `rotl` and `rotr`.
+fn lower_rotate<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) -> CodegenResult<()> {
+    let op = ctx.data(insn).opcode();
+    let ty = ctx.output_ty(insn, 0);
+    let inputs = [InsnInput { insn, input: 0 }, InsnInput { insn, input: 1 }];
+    let output = InsnOutput { insn, output: 0 };
+
+    match ty {
+        I32 => {
+            let rd = output_to_reg(ctx, output);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let rm = input_to_shift_amt(ctx, inputs[1]);
+            if op == Opcode::Rotr {
+                // `ror` takes the amount modulo 32.
+                ctx.emit(alu_rrr(ALUOp::Ror, rd, rn, rm));
+            } else {
+                // ror rd, rn, 32 - (rm & 31)
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(alu_rr_imm8(ALUOp::And, tmp, rm, 31));
+                ctx.emit(alu_rr_imm8(ALUOp::Rsb, tmp, tmp.to_reg(), 32));
+                ctx.emit(alu_rrr(ALUOp::Ror, rd, rn, tmp.to_reg()));
+            }
+        }
+        I64 => {
+            // Swap the halves if bit 5 of the amount is set, and then rotate by the amount
+            // modulo 32, where a shift by 32 yields 0:
+            //
+            //   rotl: hi = (hi << n) | (lo >> (32 - n)); lo = (lo << n) | (hi >> (32 - n))
+            //   rotr: lo = (lo >> n) | (hi << (32 - n)); hi = (hi >> n) | (lo << (32 - n))
+            let (lo, hi) = input_to_reg_pair(ctx, inputs[0]);
+            let amt = input_to_shift_amt(ctx, inputs[1]);
+            let (rd_lo, rd_hi) = output_to_reg_pair(ctx, output);
+            let swap = tmp_reg(ctx, I32);
+            let a = tmp_reg(ctx, I32);
+            let b = tmp_reg(ctx, I32);
+            ctx.emit(alu_rr_imm8(ALUOp::And, swap, amt, 32));
+            ctx.emit(Inst::CmpImm8 {
+                rn: swap.to_reg(),
+                imm8: 0,
+            });
+            ctx.emit(it_select(Cond::Ne, a, lo, hi, I32));
+            ctx.emit(it_select(Cond::Ne, b, hi, lo, I32));
+            let (a, b) = (a.to_reg(), b.to_reg());
+
+            let n = tmp_reg(ctx, I32);
+            let n_rev = tmp_reg(ctx, I32);
+            ctx.emit(alu_rr_imm8(ALUOp::And, n, amt, 31));
+            ctx.emit(alu_rr_imm8(ALUOp::Rsb, n_rev, n.to_reg(), 32));
+            let (main_op, rev_op) = if op == Opcode::Rotl {
+                (ALUOp::Lsl, ALUOp::Lsr)
+            } else {
+                (ALUOp::Lsr, ALUOp::Lsl)
+            };
+            for &(rd, x, y) in &[(rd_hi, a, b), (rd_lo, b, a)] {
+                let t1 = tmp_reg(ctx, I32);
+                let t2 = tmp_reg(ctx, I32);
+                ctx.emit(alu_rrr(main_op, t1, x, n.to_reg()));
+                ctx.emit(alu_rrr(rev_op, t2, y, n_rev.to_reg()));
+                ctx.emit(alu_rrr(ALUOp::Orr, rd, t1.to_reg(), t2.to_reg()));
+            }
+        }
+        _ => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} on type {} not supported",
+                op, ty
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Lower a 32-bit population count of `rn` into `rd`.
+fn lower_popcnt32<C: LowerCtx<I = Inst>>(ctx: &mut C, rd: Writable<Reg>, rn: Reg) {
+    // x = x - ((x >> 1) & 0x55555555)
+    // x = (x & 0x33333333) + ((x >> 2) & 0x33333333)
+    // x = (x + (x >> 4)) & 0x0f0f0f0f
+    // rd = (x * 0x01010101) >> 24
+    let m1 = const_to_reg(ctx, 0x5555_5555);
+    let m2 = const_to_reg(ctx, 0x3333_3333);
+    let m4 = const_to_reg(ctx, 0x0f0f_0f0f);
+    let h01 = const_to_reg(ctx, 0x0101_0101);
+    let t1 = tmp_reg(ctx, I32);
+    let t2 = tmp_reg(ctx, I32);
+    ctx.emit(Inst::AluRRRShift {
+        alu_op: ALUOp::And,
+        rd: t1,
+        rn: m1,
+        rm: rn,
+        shift: shift_op(ShiftOp::LSR, 1),
+    });
+    ctx.emit(alu_rrr(ALUOp::Sub, t1, rn, t1.to_reg()));
+    ctx.emit(alu_rrr(ALUOp::And, t2, t1.to_reg(), m2));
+    ctx.emit(Inst::AluRRRShift {
+        alu_op: ALUOp::And,
+        rd: t1,
+        rn: m2,
+        rm: t1.to_reg(),
+        shift: shift_op(ShiftOp::LSR, 2),
+    });
+    ctx.emit(alu_rrr(ALUOp::Add, t1, t1.to_reg(), t2.to_reg()));
+    ctx.emit(Inst::AluRRRShift {
+        alu_op: ALUOp::Add,
+        rd: t1,
+        rn: t1.to_reg(),
+        rm: t1.to_reg(),
+        shift: shift_op(ShiftOp::LSR, 4),
+    });
+    ctx.emit(alu_rrr(ALUOp::And, t1, t1.to_reg(), m4));
+    ctx.emit(alu_rrr(ALUOp::Mul, t1, t1.to_reg(), h01));
+    ctx.emit(shift_imm(ShiftOp::LSR, rd, t1.to_reg(), 24));
+}
+
+/// Lower `clz`, `ctz`, `popcnt` and `bitrev`.
+fn lower_bit_op<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst) -> CodegenResult<()> {
+    let op = ctx.data(insn).opcode();
+    let ty = ctx.output_ty(insn, 0);
+    let input = InsnInput { insn, input: 0 };
+    let output = InsnOutput { insn, output: 0 };
+
+    if ty == I64 {
+        let (lo, hi) = input_to_reg_pair(ctx, input);
+        let (rd_lo, rd_hi) = output_to_reg_pair(ctx, output);
+        match op {
+            Opcode::Clz | Opcode::Ctz => {
+                // Count in the high (for `clz`) or low (for `ctz`) half, and if it is zero, add
+                // 32 to the count in the other half.
+                let (first, second) = if op == Opcode::Clz {
+                    (hi, lo)
+                } else {
+                    (lo, hi)
+                };
+                let counts = [tmp_reg(ctx, I32), tmp_reg(ctx, I32)];
+                for (&count, &rm) in counts.iter().zip([first, second].iter()) {
+                    let rm = if op == Opcode::Ctz {
+                        ctx.emit(Inst::BitOpRR {
+                            bit_op: BitOp::Rbit,
+                            rd: count,
+                            rm,
+                        });
+                        count.to_reg()
+                    } else {
+                        rm
+                    };
+                    ctx.emit(Inst::BitOpRR {
+                        bit_op: BitOp::Clz,
+                        rd: count,
+                        rm,
+                    });
+                }
+                ctx.emit(alu_rr_imm8(ALUOp::Add, counts[1], counts[1].to_reg(), 32));
+                ctx.emit(Inst::CmpImm8 { rn: first, imm8: 0 });
+                ctx.emit(it_select(
+                    Cond::Eq,
+                    rd_lo,
+                    counts[1].to_reg(),
+                    counts[0].to_reg(),
+                    I32,
+                ));
+            }
+            Opcode::Popcnt => {
+                let count_lo = tmp_reg(ctx, I32);
+                let count_hi = tmp_reg(ctx, I32);
+                lower_popcnt32(ctx, count_lo, lo);
+                lower_popcnt32(ctx, count_hi, hi);
+                ctx.emit(alu_rrr(
+                    ALUOp::Add,
+                    rd_lo,
+                    count_lo.to_reg(),
+                    count_hi.to_reg(),
+                ));
+            }
+            Opcode::Bitrev => {
+                ctx.emit(Inst::BitOpRR {
+                    bit_op: BitOp::Rbit,
+                    rd: rd_lo,
+                    rm: hi,
+                });
+                ctx.emit(Inst::BitOpRR {
+                    bit_op: BitOp::Rbit,
+                    rd: rd_hi,
+                    rm: lo,
+                });
+                return Ok(());
+            }
+            _ => unreachable!(),
+        }
+        ctx.emit(Inst::MovImm16 {
+            rd: rd_hi,
+            imm16: 0,
+        });
+        return Ok(());
+    }
+
+    let rd = output_to_reg(ctx, output);
+    let bits = ty.bits();
+    match op {
+        Opcode::Clz | Opcode::Ctz => {
+            let rm = input_to_reg(ctx, input, NarrowValueMode::ZeroExtend);
+            let in_reg = if op == Opcode::Ctz {
+                // Set the bit above a narrow value, so that the count stops there.
+                let rm = if bits < 32 {
+                    let top = const_to_reg(ctx, 1 << bits);
+                    ctx.emit(alu_rrr(ALUOp::Orr, rd, rm, top));
+                    rd.to_reg()
+                } else {
+                    rm
+                };
+                ctx.emit(Inst::BitOpRR {
+                    bit_op: BitOp::Rbit,
+                    rd,
+                    rm,
+                });
+                rd.to_reg()
+            } else {
+                rm
+            };
+            ctx.emit(Inst::BitOpRR {
+                bit_op: BitOp::Clz,
+                rd,
+                rm: in_reg,
+            });
+
+            if op == Opcode::Clz && bits < 32 {
+                let imm12 = UImm12::maybe_from_i64(32 - i64::from(bits)).unwrap();
+                ctx.emit(Inst::AluRRImm12 {
+                    alu_op: ALUOp::Sub,
+                    rd,
+                    rn: rd.to_reg(),
+                    imm12,
+                });
+            }
+        }
+        Opcode::Popcnt => {
+            let rn = input_to_reg(ctx, input, NarrowValueMode::ZeroExtend);
+            lower_popcnt32(ctx, rd, rn);
+        }
+        Opcode::Bitrev => {
+            let rm = input_to_reg(ctx, input, NarrowValueMode::None);
+            let bit_op = BitOp::Rbit;
+
+            match bits {
+                32 => ctx.emit(Inst::BitOpRR { bit_op, rd, rm }),
+                n if n < 32 => {
+                    ctx.emit(shift_imm(ShiftOp::LSL, rd, rm, u32::from(32 - n)));
+                    ctx.emit(Inst::BitOpRR {
+                        bit_op,
+                        rd,
+                        rm: rd.to_reg(),
+                    });
+                }
+                _ => panic!("Unexpected output type {}", ty),
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Lower the loads, with the given address inputs.
+fn lower_load<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst, addends: &[InsnInput]) {
+    let op = ctx.data(insn).opcode();
+    let off = ldst_offset(ctx.data(insn)).unwrap();
+    let flags = ctx.memflags(insn).unwrap();
+    let out_ty = ctx.output_ty(insn, 0);
+    let output = InsnOutput { insn, output: 0 };
+    let (elem_ty, sign_extend) = match op {
+        Opcode::Load | Opcode::LoadComplex => (out_ty, false),
+        Opcode::Uload8 | Opcode::Uload8Complex => (I8, false),
+        Opcode::Sload8 | Opcode::Sload8Complex => (I8, true),
+        Opcode::Uload16 | Opcode::Uload16Complex => (I16, false),
+        Opcode::Sload16 | Opcode::Sload16Complex => (I16, true),
+        Opcode::Uload32 | Opcode::Uload32Complex => (I32, false),
+        Opcode::Sload32 | Opcode::Sload32Complex => (I32, true),
+        _ => unreachable!(),
+    };
+    let mem = lower_address(ctx, addends, off);
+
+    match elem_ty {
+        I64 => {
+            let (rd_lo, rd_hi) = output_to_reg_pair(ctx, output);
+            let (mem_lo, mem_hi) = split_amode(ctx, mem);
+            ctx.emit(Inst::gen_load(rd_lo, mem_lo, I32));
+            ctx.emit(Inst::gen_load(rd_hi, mem_hi, I32));
+        }
+        F32 | F64 if flags.aligned() => {
+            let rd = output_to_reg(ctx, output);
+            ctx.emit(Inst::gen_load(rd, mem, elem_ty));
+        }
+        F32 => {
+            // `vldr` faults on unaligned addresses, unlike `ldr`.
+            let rd = output_to_reg(ctx, output);
+            let tmp = tmp_reg(ctx, I32);
+            ctx.emit(Inst::gen_load(tmp, mem, I32));
+            ctx.emit(Inst::MovToFpu {
+                rd,
+                rn: tmp.to_reg(),
+            });
+        }
+        F64 => {
+            let rd = output_to_reg(ctx, output);
+            let lo = tmp_reg(ctx, I32);
+            let hi = tmp_reg(ctx, I32);
+            let (mem_lo, mem_hi) = split_amode(ctx, mem);
+            ctx.emit(Inst::gen_load(lo, mem_lo, I32));
+            ctx.emit(Inst::gen_load(hi, mem_hi, I32));
+            ctx.emit(Inst::MovToFpuPair {
+                rd,
+                rn_lo: lo.to_reg(),
+                rn_hi: hi.to_reg(),
+            });
+        }
+        _ if out_ty == I64 => {
+            let (rd_lo, rd_hi) = output_to_reg_pair(ctx, output);
+            ctx.emit(Inst::Load {
+                rt: rd_lo,
+                mem,
+                bits: elem_ty.bits() as u8,
+                sign_extend,
+            });
+            if sign_extend {
+                ctx.emit(shift_imm(ShiftOp::ASR, rd_hi, rd_lo.to_reg(), 31));
+            } else {
+                ctx.emit(Inst::MovImm16 {
+                    rd: rd_hi,
+                    imm16: 0,
+                });
+            }
+        }
+        _ => {
+            let rd = output_to_reg(ctx, output);
+            // Load 8 bits for B1.
+            let bits = std::cmp::max(elem_ty.bits(), 8) as u8;
+            ctx.emit(Inst::Load {
+                rt: rd,
+                mem,
+                bits,
+                sign_extend,
+            });
+        }
+    }
+}
+
+/// Lower the stores, with the given address inputs.
+fn lower_store<C: LowerCtx<I = Inst>>(ctx: &mut C, insn: IRInst, addends: &[InsnInput]) {
+    let op = ctx.data(insn).opcode();
+    let off = ldst_offset(ctx.data(insn)).unwrap();
+    let flags = ctx.memflags(insn).unwrap();
+    let input = InsnInput { insn, input: 0 };
+    let val_ty = ctx.input_ty(insn, 0);
+    let elem_ty = match op {
+        Opcode::Istore8 | Opcode::Istore8Complex => I8,
+        Opcode::Istore16 | Opcode::Istore16Complex => I16,
+        Opcode::Istore32 | Opcode::Istore32Complex => I32,
+        Opcode::Store | Opcode::StoreComplex => val_ty,
+        _ => unreachable!(),
+    };
+
+    match elem_ty {
+        I64 => {
+            let (lo, hi) = input_to_reg_pair(ctx, input);
+            let mem = lower_address(ctx, addends, off);
+            let (mem_lo, mem_hi) = split_amode(ctx, mem);
+            ctx.emit(Inst::gen_store(lo, mem_lo, I32));
+            ctx.emit(Inst::gen_store(hi, mem_hi, I32));
+        }
+        F32 | F64 if flags.aligned() => {
+            let rt = input_to_reg(ctx, input, NarrowValueMode::None);
+            let mem = lower_address(ctx, addends, off);
+            ctx.emit(Inst::gen_store(rt, mem, elem_ty));
+        }
+        F32 => {
+            // `vstr` faults on unaligned addresses, unlike `str`.
+            let rn = input_to_reg(ctx, input, NarrowValueMode::None);
+            let tmp = tmp_reg(ctx, I32);
+            ctx.emit(Inst::MovFromFpu { rd: tmp, rn });
+            let mem = lower_address(ctx, addends, off);
+            ctx.emit(Inst::gen_store(tmp.to_reg(), mem, I32));
+        }
+        F64 => {
+            let rn = input_to_reg(ctx, input, NarrowValueMode::None);
+            let lo = tmp_reg(ctx, I32);
+            let hi = tmp_reg(ctx, I32);
+            ctx.emit(Inst::MovFromFpuPair {
+                rd_lo: lo,
+                rd_hi: hi,
+                rn,
+            });
+            let mem = lower_address(ctx, addends, off);
+            let (mem_lo, mem_hi) = split_amode(ctx, mem);
+            ctx.emit(Inst::gen_store(lo.to_reg(), mem_lo, I32));
+            ctx.emit(Inst::gen_store(hi.to_reg(), mem_hi, I32));
+        }
+        _ => {
+            // Narrow stores of an `i64` store (part of) its low half.
+            let rt = if val_ty == I64 {
+                input_to_reg_pair(ctx, input).0
+            } else {
+                input_to_reg(ctx, input, NarrowValueMode::None)
+            };
+            let mem = lower_address(ctx, addends, off);
+            ctx.emit(Inst::gen_store(rt, mem, elem_ty));
+        }
+    }
+}
+
+/// Trap unless `rn`, of type `in_ty`, is a number which truncates to a value of type `out_ty`.
+fn emit_fcvt_to_int_checks<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    rn: Reg,
+    in_ty: Type,
+    out_ty: Type,
+    signed: bool,
+) {
+    let bits = in_ty.bits() as u8;
+    ctx.emit(Inst::FpuCmp { rn, rm: rn, bits });
+    ctx.emit_safepoint(Inst::TrapIf {
+        cond: Cond::Vs,
+        trap_info: TrapCode::BadConversionToInteger,
+    });
+
+    // The bounds of the valid range, and whether the lower one is in it. An `f32` can't
+    // represent the largest invalid value below the signed 32-bit range, but it can represent
+    // the smallest valid one.
+    let (low, low_valid, high) = match (signed, out_ty.bits()) {
+        (true, 32) if in_ty == F32 => (-2147483648.0, true, 2147483648.0),
+        (true, 32) => (-2147483649.0, false, 2147483648.0),
+        (true, _) => (-9223372036854775808.0, true, 9223372036854775808.0),
+        (false, 32) => (-1.0, false, 4294967296.0),
+        (false, _) => (-1.0, false, 18446744073709551616.0),
+    };
+    let low = fp_const_to_reg(ctx, in_ty, fp_bits(in_ty, low));
+    ctx.emit(Inst::FpuCmp { rn, rm: low, bits });
+    ctx.emit_safepoint(Inst::TrapIf {
+        cond: if low_valid { Cond::Mi } else { Cond::Ls },
+        trap_info: TrapCode::IntegerOverflow,
+    });
+    let high = fp_const_to_reg(ctx, in_ty, fp_bits(in_ty, high));
+    ctx.emit(Inst::FpuCmp { rn, rm: high, bits });
+    ctx.emit_safepoint(Inst::TrapIf {
+        cond: Cond::Ge,
+        trap_info: TrapCode::IntegerOverflow,
+    });
+}
+
+/// Truncate `rn`, of type `in_ty`, to a 64-bit integer in `rd_lo` and `rd_hi`, saturating.
+fn lower_fcvt_to_i64<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    rn: Reg,
+    in_ty: Type,
+    signed: bool,
+    rd_lo: Writable<Reg>,
+    rd_hi: Writable<Reg>,
+) {
+    let x = if in_ty == F32 {
+        let tmp = tmp_reg(ctx, F64);
+        ctx.emit(Inst::FpuRR {
+            fpu_op: FPUOp1::Cvt32To64,
+            rd: tmp,
+            rn,
+            bits: 32,
+        });
+        tmp.to_reg()
+    } else {
+        rn
+    };
+    // Signed conversions convert the magnitude, and negate the result for negative inputs.
+    let a = if signed {
+        let tmp = tmp_reg(ctx, F64);
+        ctx.emit(Inst::FpuRR {
+            fpu_op: FPUOp1::Abs,
+            rd: tmp,
+            rn: x,
+            bits: 64,
+        });
+        tmp.to_reg()
+    } else {
+        x
+    };
+
+    // hi = trunc(a / 2^32) and lo = trunc(a - hi * 2^32), where the subtraction is exact. Out of
+    // range and NaN inputs saturate the halves as needed.
+    let (lo, hi) = if signed {
+        (tmp_reg(ctx, I32), tmp_reg(ctx, I32))
+    } else {
+        (rd_lo, rd_hi)
+    };
+    let scale_down = fp_const_to_reg(ctx, F64, fp_bits(F64, 1.0 / 4294967296.0));
+    let scale_up = fp_const_to_reg(ctx, F64, fp_bits(F64, 4294967296.0));
+    let t1 = tmp_reg(ctx, F64);
+    let t2 = tmp_reg(ctx, F64);
+    ctx.emit(Inst::FpuRRR {
+        fpu_op: FPUOp2::Mul,
+        rd: t1,
+        rn: a,
+        rm: scale_down,
+        bits: 64,
+    });
+    let tmp = tmp_reg(ctx, F64);
+    ctx.emit(Inst::FpuToInt {
+        op: FpuToIntOp::F64ToU32,
+        rd: hi,
+        rn: t1.to_reg(),
+        tmp,
+    });
+    ctx.emit(Inst::IntToFpu {
+        op: IntToFpuOp::U32ToF64,
+        rd: t1,
+        rn: hi.to_reg(),
+    });
+    ctx.emit(Inst::FpuRRR {
+        fpu_op: FPUOp2::Mul,
+        rd: t2,
+        rn: t1.to_reg(),
+        rm: scale_up,
+        bits: 64,
+    });
+    ctx.emit(Inst::FpuRRR {
+        fpu_op: FPUOp2::Sub,
+        rd: t1,
+        rn: a,
+        rm: t2.to_reg(),
+        bits: 64,
+    });
+    let tmp = tmp_reg(ctx, F64);
+    ctx.emit(Inst::FpuToInt {
+        op: FpuToIntOp::F64ToU32,
+        rd: lo,
+        rn: t1.to_reg(),
+        tmp,
+    });
+    if !signed {
+        return;
+    }
+
+    let zero = const_to_reg(ctx, 0);
+    let neg_lo = tmp_reg(ctx, I32);
+    let neg_hi = tmp_reg(ctx, I32);
+    ctx.emit(alu_rrr(ALUOp::Subs, neg_lo, zero, lo.to_reg()));
+    ctx.emit(alu_rrr(ALUOp::Sbc, neg_hi, zero, hi.to_reg()));
+
+    let x_lo = tmp_reg(ctx, I32);
+    let x_hi = tmp_reg(ctx, I32);
+    ctx.emit(Inst::MovFromFpuPair {
+        rd_lo: x_lo,
+        rd_hi: x_hi,
+        rn: x,
+    });
+    // The saturated values: INT_MIN for negative inputs, and INT_MAX otherwise.
+    let sign = tmp_reg(ctx, I32);
+    let sat_lo = tmp_reg(ctx, I32);
+    let sat_hi = tmp_reg(ctx, I32);
+    let int_max_hi = const_to_reg(ctx, 0x7fff_ffff);
+    ctx.emit(shift_imm(ShiftOp::ASR, sign, x_hi.to_reg(), 31));
+    ctx.emit(Inst::AluRRShift {
+        alu_op: ALUOp1::Mvn,
+        rd: sat_lo,
+        rm: sign.to_reg(),
+        shift: None,
+    });
+    ctx.emit(alu_rrr(ALUOp::Eor, sat_hi, sign.to_reg(), int_max_hi));
+
+    let sel_lo = tmp_reg(ctx, I32);
+    let sel_hi = tmp_reg(ctx, I32);
+    ctx.emit(Inst::CmpImm8 {
+        rn: x_hi.to_reg(),
+        imm8: 0,
+    });
+    ctx.emit(it_select(
+        Cond::Lt,
+        sel_lo,
+        neg_lo.to_reg(),
+        lo.to_reg(),
+        I32,
+    ));
+    ctx.emit(it_select(
+        Cond::Lt,
+        sel_hi,
+        neg_hi.to_reg(),
+        hi.to_reg(),
+        I32,
+    ));
+
+    let limit = fp_const_to_reg(ctx, F64, fp_bits(F64, 9223372036854775808.0));
+    ctx.emit(Inst::FpuCmp {
+        rn: a,
+        rm: limit,
+        bits: 64,
+    });
+    ctx.emit(it_select(
+        Cond::Ge,
+        rd_lo,
+        sat_lo.to_reg(),
+        sel_lo.to_reg(),
+        I32,
+    ));
+    ctx.emit(it_select(
+        Cond::Ge,
+        rd_hi,
+        sat_hi.to_reg(),
+        sel_hi.to_reg(),
+        I32,
+    ));
+}
+
+/// Convert the 64-bit integer in `lo` and `hi` to an `f64` in `rd`.
+fn lower_i64_to_f64<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    lo: Reg,
+    hi: Reg,
+    signed: bool,
+    rd: Writable<Reg>,
+) {
+    // rd = hi * 2^32 + lo, where only the addition rounds.
+    let hi_f = tmp_reg(ctx, F64);
+    let lo_f = tmp_reg(ctx, F64);
+    ctx.emit(Inst::IntToFpu {
+        op: if signed {
+            IntToFpuOp::I32ToF64
+        } else {
+            IntToFpuOp::U32ToF64
+        },
+        rd: hi_f,
+        rn: hi,
+    });
+    ctx.emit(Inst::IntToFpu {
+        op: IntToFpuOp::U32ToF64,
+        rd: lo_f,
+        rn: lo,
+    });
+    let scale = fp_const_to_reg(ctx, F64, fp_bits(F64, 4294967296.0));
+    ctx.emit(Inst::FpuRRR {
+        fpu_op: FPUOp2::Mul,
+        rd: hi_f,
+        rn: hi_f.to_reg(),
+        rm: scale,
+        bits: 64,
+    });
+    ctx.emit(Inst::FpuRRR {
+        fpu_op: FPUOp2::Add,
+        rd,
+        rn: hi_f.to_reg(),
+        rm: lo_f.to_reg(),
+        bits: 64,
+    });
+}
+
+/// Convert the 64-bit integer in `lo` and `hi` to an `f32` in `rd`.
+fn lower_i64_to_f32<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    lo: Reg,
+    hi: Reg,
+    signed: bool,
+    rd: Writable<Reg>,
+) {
+    // Going through an `f64` could round twice. If the value doesn't fit in 53 bits, collapse
+    // its low 11 bits into a sticky bit 11 first: the `f64` conversion is exact then, and the
+    // sticky bit still makes the `f32` rounding go the right way.
+    let mask = const_to_reg(ctx, 0x7ff);
+    let t = tmp_reg(ctx, I32);
+    let sticky_lo = tmp_reg(ctx, I32);
+    ctx.emit(alu_rrr(ALUOp::And, t, lo, mask));
+    ctx.emit(alu_rrr(ALUOp::Add, t, t.to_reg(), mask));
+    ctx.emit(alu_rrr(ALUOp::Orr, t, lo, t.to_reg()));
+    ctx.emit(alu_rrr(ALUOp::Bic, sticky_lo, t.to_reg(), mask));
+
+    // The value needs more than 53 bits if the top 11 (unsigned) or 12 (signed) bits aren't all
+    // the same.
+    let wide = tmp_reg(ctx, I32);
+    if signed {
+        let bias = const_to_reg(ctx, 1 << 21);
+        ctx.emit(alu_rrr(ALUOp::Add, wide, hi, bias));
+        ctx.emit(shift_imm(ShiftOp::LSR, wide, wide.to_reg(), 22));
+    } else {
+        ctx.emit(shift_imm(ShiftOp::LSR, wide, hi, 21));
+    }
+    ctx.emit(Inst::CmpImm8 {
+        rn: wide.to_reg(),
+        imm8: 0,
+    });
+    let sel_lo = tmp_reg(ctx, I32);
+    ctx.emit(it_select(Cond::Ne, sel_lo, sticky_lo.to_reg(), lo, I32));
+
+    let tmp = tmp_reg(ctx, F64);
+    lower_i64_to_f64(ctx, sel_lo.to_reg(), hi, signed, tmp);
+    ctx.emit(Inst::FpuRR {
+        fpu_op: FPUOp1::Cvt64To32,
+        rd,
+        rn: tmp.to_reg(),
+        bits: 64,
+    });
+}
+
+/// Copy the sign bit of `b` into the word `a`, which holds the sign of a float.
+fn lower_copysign_word<C: LowerCtx<I = Inst>>(ctx: &mut C, rd: Writable<Reg>, a: Reg, b: Reg) {
+    let magnitude = tmp_reg(ctx, I32);
+    let sign = tmp_reg(ctx, I32);
+    ctx.emit(shift_imm(ShiftOp::LSL, magnitude, a, 1));
+    ctx.emit(shift_imm(ShiftOp::LSR, magnitude, magnitude.to_reg(), 1));
+    ctx.emit(shift_imm(ShiftOp::LSR, sign, b, 31));
+    ctx.emit(Inst::AluRRRShift {
+        alu_op: ALUOp::Orr,
+        rd,
+        rn: magnitude.to_reg(),
+        rm: sign.to_reg(),
+        shift: shift_op(ShiftOp::LSL, 31),
+    });
+}
+
 /// Actually codegen an instruction's results into registers.
 pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
     ctx: &mut C,
     insn: IRInst,
     flags: &Flags,
+    triple: &Triple,
 ) -> CodegenResult<()> {
     let op = ctx.data(insn).opcode();
     let inputs: SmallVec<[InsnInput; 4]> = (0..ctx.num_inputs(insn))
@@ -29,26 +1160,85 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
         .map(|i| InsnOutput { insn, output: i })
         .collect();
     let ty = if outputs.len() > 0 {
-        let ty = ctx.output_ty(insn, 0);
-        if ty.bits() > 32 || ty.is_float() {
-            panic!("Cannot lower inst with type {}!", ty);
-        }
-        Some(ty)
+        Some(ctx.output_ty(insn, 0))
     } else {
         None
     };
 
     match op {
-        Opcode::Iconst | Opcode::Bconst | Opcode::Null => {
-            let value = output_to_const(ctx, outputs[0]).unwrap();
-            let rd = output_to_reg(ctx, outputs[0]);
-            lower_constant(ctx, rd, value);
+        Opcode::Nop => {
+            // Nothing.
+        }
+        Opcode::Iconst | Opcode::Bconst | Opcode::Null | Opcode::F32const | Opcode::F64const => {
+            let value = ctx.get_constant(insn).unwrap();
+            let rd = ctx.get_output(insn, 0);
+            lower_constant(ctx, rd, ty.unwrap(), value);
         }
         Opcode::Iadd
-        | Opcode::IaddIfcin
+        | Opcode::Isub
+        | Opcode::IaddIfcout
+        | Opcode::Band
+        | Opcode::Bor
+        | Opcode::Bxor
+        | Opcode::BandNot
+        | Opcode::BorNot
+            if ty.unwrap() == I64 =>
+        {
+            let (lo_op, hi_op) = match op {
+                Opcode::Iadd => (ALUOp::Adds, ALUOp::Adc),
+                Opcode::IaddIfcout => (ALUOp::Adds, ALUOp::Adcs),
+                Opcode::Isub => (ALUOp::Subs, ALUOp::Sbc),
+                Opcode::Band => (ALUOp::And, ALUOp::And),
+                Opcode::Bor => (ALUOp::Orr, ALUOp::Orr),
+                Opcode::Bxor => (ALUOp::Eor, ALUOp::Eor),
+                Opcode::BandNot => (ALUOp::Bic, ALUOp::Bic),
+                Opcode::BorNot => (ALUOp::Orn, ALUOp::Orn),
+                _ => unreachable!(),
+            };
+            let (a_lo, a_hi) = input_to_reg_pair(ctx, inputs[0]);
+            let (b_lo, b_hi) = input_to_reg_pair(ctx, inputs[1]);
+            let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+            ctx.emit(alu_rrr(lo_op, rd_lo, a_lo, b_lo));
+            ctx.emit(alu_rrr(hi_op, rd_hi, a_hi, b_hi));
+        }
+        Opcode::Iadd | Opcode::Isub => {
+            let rd = output_to_reg(ctx, outputs[0]);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            // Use an immediate for small constants, negating it if that helps.
+            let imm = input_to_const(ctx, inputs[1]).and_then(|c| {
+                let c = c as u32 as i32;
+                let c = if op == Opcode::Isub {
+                    c.wrapping_neg()
+                } else {
+                    c
+                };
+                if let Some(imm12) = UImm12::maybe_from_i64(i64::from(c)) {
+                    Some((ALUOp::Add, imm12))
+                } else {
+                    UImm12::maybe_from_i64(-i64::from(c)).map(|imm12| (ALUOp::Sub, imm12))
+                }
+            });
+            match imm {
+                Some((alu_op, imm12)) => ctx.emit(Inst::AluRRImm12 {
+                    alu_op,
+                    rd,
+                    rn,
+                    imm12,
+                }),
+                None => {
+                    let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+                    let alu_op = if op == Opcode::Iadd {
+                        ALUOp::Add
+                    } else {
+                        ALUOp::Sub
+                    };
+                    ctx.emit(alu_rrr(alu_op, rd, rn, rm));
+                }
+            }
+        }
+        Opcode::IaddIfcin
         | Opcode::IaddIfcout
         | Opcode::IaddIfcarry
-        | Opcode::Isub
         | Opcode::IsubIfbin
         | Opcode::IsubIfbout
         | Opcode::IsubIfborrow
@@ -57,16 +1247,21 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
         | Opcode::Bxor
         | Opcode::BandNot
         | Opcode::BorNot => {
+            let ty = ty.unwrap();
+            if ty.bits() > 32 {
+                return Err(CodegenError::Unsupported(format!(
+                    "arm32: {} on type {} not supported",
+                    op, ty
+                )));
+            }
             let rd = output_to_reg(ctx, outputs[0]);
             let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
             let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
 
             let alu_op = match op {
-                Opcode::Iadd => ALUOp::Add,
                 Opcode::IaddIfcin => ALUOp::Adc,
                 Opcode::IaddIfcout => ALUOp::Adds,
                 Opcode::IaddIfcarry => ALUOp::Adcs,
-                Opcode::Isub => ALUOp::Sub,
                 Opcode::IsubIfbin => ALUOp::Sbc,
                 Opcode::IsubIfbout => ALUOp::Subs,
                 Opcode::IsubIfborrow => ALUOp::Sbcs,
@@ -77,92 +1272,136 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
                 Opcode::BorNot => ALUOp::Orn,
                 _ => unreachable!(),
             };
-            ctx.emit(Inst::AluRRRShift {
-                alu_op,
-                rd,
-                rn,
-                rm,
-                shift: None,
-            });
+            ctx.emit(alu_rrr(alu_op, rd, rn, rm));
         }
-        Opcode::Imul | Opcode::Udiv | Opcode::Sdiv => {
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-
-            let alu_op = match op {
-                Opcode::Imul => ALUOp::Mul,
-                Opcode::Udiv => ALUOp::Udiv,
-                Opcode::Sdiv => ALUOp::Sdiv,
-                _ => unreachable!(),
-            };
-            ctx.emit(Inst::AluRRR { alu_op, rd, rn, rm });
+        Opcode::BxorNot => {
+            let ty = ty.unwrap();
+            let rn = ctx.put_input_in_regs(insn, 0);
+            let rm = ctx.put_input_in_regs(insn, 1);
+            let rd = ctx.get_output(insn, 0);
+            for i in 0..rd.len() {
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(Inst::AluRRShift {
+                    alu_op: ALUOp1::Mvn,
+                    rd: tmp,
+                    rm: rm.regs()[i],
+                    shift: None,
+                });
+                ctx.emit(alu_rrr(
+                    ALUOp::Eor,
+                    rd.regs()[i],
+                    rn.regs()[i],
+                    tmp.to_reg(),
+                ));
+            }
+            debug_assert!(ty.is_int() || ty.is_bool());
         }
-        Opcode::Ineg => {
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-
-            ctx.emit(Inst::AluRRImm8 {
-                alu_op: ALUOp::Rsb,
-                rd,
-                rn,
-                imm8: UImm8::maybe_from_i64(0).unwrap(),
-            });
+        Opcode::Bnot => {
+            let rm = ctx.put_input_in_regs(insn, 0);
+            let rd = ctx.get_output(insn, 0);
+            for i in 0..rd.len() {
+                ctx.emit(Inst::AluRRShift {
+                    alu_op: ALUOp1::Mvn,
+                    rd: rd.regs()[i],
+                    rm: rm.regs()[i],
+                    shift: None,
+                });
+            }
         }
-        Opcode::Ishl | Opcode::Ushr | Opcode::Sshr => {
-            let (alu_op, ext) = match op {
-                Opcode::Ishl => (ALUOp::Lsl, NarrowValueMode::None),
-                Opcode::Ushr => (ALUOp::Lsr, NarrowValueMode::ZeroExtend),
-                Opcode::Sshr => (ALUOp::Asr, NarrowValueMode::SignExtend),
-                _ => unreachable!(),
-            };
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], ext);
-            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::ZeroExtend);
-            ctx.emit(Inst::AluRRR { alu_op, rd, rn, rm });
+        Opcode::Bitselect => {
+            let ty = ty.unwrap();
+            if ty.is_float() {
+                return Err(CodegenError::Unsupported(format!(
+                    "arm32: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            // rd = (c & x) | (y & ~c)
+            let c = ctx.put_input_in_regs(insn, 0);
+            let x = ctx.put_input_in_regs(insn, 1);
+            let y = ctx.put_input_in_regs(insn, 2);
+            let rd = ctx.get_output(insn, 0);
+            for i in 0..rd.len() {
+                let t1 = tmp_reg(ctx, I32);
+                let t2 = tmp_reg(ctx, I32);
+                ctx.emit(alu_rrr(ALUOp::And, t1, x.regs()[i], c.regs()[i]));
+                ctx.emit(alu_rrr(ALUOp::Bic, t2, y.regs()[i], c.regs()[i]));
+                ctx.emit(alu_rrr(ALUOp::Orr, rd.regs()[i], t1.to_reg(), t2.to_reg()));
+            }
         }
-        Opcode::Rotr => {
-            if ty.unwrap().bits() != 32 {
-                unimplemented!()
+        Opcode::Imul => {
+            if ty.unwrap() == I64 {
+                // The low half of the product is the full product of the low halves plus the
+                // cross products shifted up.
+                let (a_lo, a_hi) = input_to_reg_pair(ctx, inputs[0]);
+                let (b_lo, b_hi) = input_to_reg_pair(ctx, inputs[1]);
+                let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                let t1 = tmp_reg(ctx, I32);
+                let t2 = tmp_reg(ctx, I32);
+                ctx.emit(Inst::AluRRRR {
+                    alu_op: ALUOp::Umull,
+                    rd_hi: t1,
+                    rd_lo,
+                    rn: a_lo,
+                    rm: b_lo,
+                });
+                ctx.emit(Inst::AluRRRA {
+                    alu_op: ALUOp::Mla,
+                    rd: t2,
+                    rn: a_lo,
+                    rm: b_hi,
+                    ra: t1.to_reg(),
+                });
+                ctx.emit(Inst::AluRRRA {
+                    alu_op: ALUOp::Mla,
+                    rd: rd_hi,
+                    rn: a_hi,
+                    rm: b_lo,
+                    ra: t2.to_reg(),
+                });
+            } else {
+                let rd = output_to_reg(ctx, outputs[0]);
+                let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+                ctx.emit(alu_rrr(ALUOp::Mul, rd, rn, rm));
             }
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-            ctx.emit(Inst::AluRRR {
-                alu_op: ALUOp::Ror,
-                rd,
-                rn,
-                rm,
-            });
         }
-        Opcode::Rotl => {
-            if ty.unwrap().bits() != 32 {
-                unimplemented!()
+        Opcode::Udiv | Opcode::Sdiv | Opcode::Urem | Opcode::Srem => {
+            lower_div_rem(ctx, insn, flags, triple)?;
+        }
+        Opcode::Ineg => {
+            if ty.unwrap() == I64 {
+                let (lo, hi) = input_to_reg_pair(ctx, inputs[0]);
+                let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                let zero = const_to_reg(ctx, 0);
+                ctx.emit(alu_rrr(ALUOp::Subs, rd_lo, zero, lo));
+                ctx.emit(alu_rrr(ALUOp::Sbc, rd_hi, zero, hi));
+            } else {
+                let rd = output_to_reg(ctx, outputs[0]);
+                let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                ctx.emit(alu_rr_imm8(ALUOp::Rsb, rd, rn, 0));
+            }
+        }
+        Opcode::Iabs => {
+            let ty = ty.unwrap();
+            if ty == I64 {
+                return Err(CodegenError::Unsupported(format!(
+                    "arm32: {} on type {} not supported",
+                    op, ty
+                )));
             }
             let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-            let tmp = ctx.alloc_tmp(I32).only_reg().unwrap();
-
-            // ror rd, rn, 32 - (rm & 31)
-            ctx.emit(Inst::AluRRImm8 {
-                alu_op: ALUOp::And,
-                rd: tmp,
-                rn: rm,
-                imm8: UImm8::maybe_from_i64(31).unwrap(),
-            });
-            ctx.emit(Inst::AluRRImm8 {
-                alu_op: ALUOp::Rsb,
-                rd: tmp,
-                rn: tmp.to_reg(),
-                imm8: UImm8::maybe_from_i64(32).unwrap(),
-            });
-            ctx.emit(Inst::AluRRR {
-                alu_op: ALUOp::Ror,
-                rd,
-                rn,
-                rm: tmp.to_reg(),
-            });
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::SignExtend);
+            let neg = tmp_reg(ctx, I32);
+            ctx.emit(alu_rr_imm8(ALUOp::Rsb, neg, rn, 0));
+            ctx.emit(Inst::CmpImm8 { rn, imm8: 0 });
+            ctx.emit(it_select(Cond::Lt, rd, neg.to_reg(), rn, I32));
+        }
+        Opcode::Ishl | Opcode::Ushr | Opcode::Sshr => {
+            lower_shift(ctx, insn)?;
+        }
+        Opcode::Rotr | Opcode::Rotl => {
+            lower_rotate(ctx, insn)?;
         }
         Opcode::Smulhi | Opcode::Umulhi => {
             let ty = ty.unwrap();
@@ -214,179 +1453,86 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
                         imm8,
                     });
                 }
-                _ => panic!("Unexpected type {} in lower {}!", ty, op),
+                _ => {
+                    return Err(CodegenError::Unsupported(format!(
+                        "arm32: {} on type {} not supported",
+                        op, ty
+                    )));
+                }
             }
         }
-        Opcode::Bnot => {
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rm = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-
-            ctx.emit(Inst::AluRRShift {
-                alu_op: ALUOp1::Mvn,
-                rd,
-                rm,
-                shift: None,
-            });
-        }
-        Opcode::Clz | Opcode::Ctz => {
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rm = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend);
-            let ty = ctx.output_ty(insn, 0);
-
-            let in_reg = if op == Opcode::Ctz {
-                ctx.emit(Inst::BitOpRR {
-                    bit_op: BitOp::Rbit,
-                    rd,
-                    rm,
-                });
-                rd.to_reg()
-            } else {
-                rm
-            };
-            ctx.emit(Inst::BitOpRR {
-                bit_op: BitOp::Clz,
-                rd,
-                rm: in_reg,
-            });
-
-            if ty.bits() < 32 {
-                let imm12 = UImm12::maybe_from_i64(32 - ty.bits() as i64).unwrap();
-                ctx.emit(Inst::AluRRImm12 {
-                    alu_op: ALUOp::Sub,
-                    rd,
-                    rn: rd.to_reg(),
-                    imm12,
-                });
-            }
+        Opcode::Clz | Opcode::Ctz | Opcode::Popcnt | Opcode::Bitrev => {
+            lower_bit_op(ctx, insn)?;
         }
-        Opcode::Bitrev => {
-            let rd = output_to_reg(ctx, outputs[0]);
-            let rm = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let ty = ctx.output_ty(insn, 0);
-            let bit_op = BitOp::Rbit;
-
-            match ty.bits() {
-                32 => ctx.emit(Inst::BitOpRR { bit_op, rd, rm }),
-                n if n < 32 => {
-                    let shift = ShiftOpAndAmt::new(
-                        ShiftOp::LSL,
-                        ShiftOpShiftImm::maybe_from_shift(32 - n as u32).unwrap(),
-                    );
-                    ctx.emit(Inst::AluRRShift {
-                        alu_op: ALUOp1::Mov,
-                        rd,
-                        rm,
-                        shift: Some(shift),
-                    });
-                    ctx.emit(Inst::BitOpRR {
-                        bit_op,
-                        rd,
-                        rm: rd.to_reg(),
-                    });
-                }
-                _ => panic!("Unexpected output type {}", ty),
-            }
+        Opcode::Cls => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} not supported",
+                op
+            )));
         }
-        Opcode::Icmp | Opcode::Ifcmp => {
-            let condcode = inst_condcode(ctx.data(insn)).unwrap();
-            let cond = lower_condcode(condcode);
-            let is_signed = condcode_is_signed(condcode);
-
-            let narrow_mode = if is_signed {
-                NarrowValueMode::SignExtend
-            } else {
-                NarrowValueMode::ZeroExtend
-            };
+        Opcode::Icmp => {
+            let cc = inst_condcode(ctx.data(insn)).unwrap();
             let rd = output_to_reg(ctx, outputs[0]);
-            let rn = input_to_reg(ctx, inputs[0], narrow_mode);
-            let rm = input_to_reg(ctx, inputs[1], narrow_mode);
-
-            ctx.emit(Inst::Cmp { rn, rm });
-
-            if op == Opcode::Icmp {
-                let mut it_insts = vec![];
-                it_insts.push(CondInst::new(Inst::MovImm16 { rd, imm16: 1 }, true));
-                it_insts.push(CondInst::new(Inst::MovImm16 { rd, imm16: 0 }, false));
-                ctx.emit(Inst::It {
-                    cond,
-                    insts: it_insts,
-                });
-            }
+            let cond = emit_cmp(ctx, insn, cc);
+            materialize_bool(ctx, rd, cond, None);
+        }
+        Opcode::Fcmp => {
+            let cc = inst_fp_condcode(ctx.data(insn)).unwrap();
+            let rd = output_to_reg(ctx, outputs[0]);
+            let (cond, cond2) = emit_fcmp(ctx, insn, cc);
+            materialize_bool(ctx, rd, cond, cond2);
+        }
+        Opcode::Ifcmp | Opcode::Ffcmp => {
+            // An Ifcmp/Ffcmp must always be seen as a use of a brif/brff, trapif/trapff,
+            // trueif/trueff or selectif instruction, which performs the comparison.
+            panic!("Should never reach ifcmp/ffcmp as isel root!");
         }
         Opcode::Trueif => {
-            let cmp_insn = ctx
-                .get_input_as_source_or_const(inputs[0].insn, inputs[0].input)
-                .inst
-                .unwrap()
-                .0;
-            debug_assert_eq!(ctx.data(cmp_insn).opcode(), Opcode::Ifcmp);
-            emit_cmp(ctx, cmp_insn);
-
-            let condcode = inst_condcode(ctx.data(insn)).unwrap();
-            let cond = lower_condcode(condcode);
+            let cc = inst_condcode(ctx.data(insn)).unwrap();
             let rd = output_to_reg(ctx, outputs[0]);
-
-            let mut it_insts = vec![];
-            it_insts.push(CondInst::new(Inst::MovImm16 { rd, imm16: 1 }, true));
-            it_insts.push(CondInst::new(Inst::MovImm16 { rd, imm16: 0 }, false));
-
-            ctx.emit(Inst::It {
-                cond,
-                insts: it_insts,
-            });
+            let cond = lower_iflags(ctx, inputs[0], cc);
+            materialize_bool(ctx, rd, cond, None);
         }
-        Opcode::Select | Opcode::Selectif => {
-            let cond = if op == Opcode::Select {
-                let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend);
+        Opcode::Trueff => {
+            let cc = inst_fp_condcode(ctx.data(insn)).unwrap();
+            let rd = output_to_reg(ctx, outputs[0]);
+            let (cond, cond2) = lower_fflags(ctx, inputs[0], cc);
+            materialize_bool(ctx, rd, cond, cond2);
+        }
+        Opcode::IsNull | Opcode::IsInvalid => {
+            // Null references are 0 and invalid ones are -1.
+            let rd = output_to_reg(ctx, outputs[0]);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            if op == Opcode::IsNull {
                 ctx.emit(Inst::CmpImm8 { rn, imm8: 0 });
-                Cond::Ne
             } else {
-                // Verification ensures that the input is always a single-def ifcmp.
-                let cmp_insn = ctx
-                    .get_input_as_source_or_const(inputs[0].insn, inputs[0].input)
-                    .inst
-                    .unwrap()
-                    .0;
-                debug_assert_eq!(ctx.data(cmp_insn).opcode(), Opcode::Ifcmp);
-                emit_cmp(ctx, cmp_insn);
-
-                let condcode = inst_condcode(ctx.data(insn)).unwrap();
-                lower_condcode(condcode)
-            };
-            let r1 = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-            let r2 = input_to_reg(ctx, inputs[2], NarrowValueMode::None);
-            let out_reg = output_to_reg(ctx, outputs[0]);
-
-            let mut it_insts = vec![];
-            it_insts.push(CondInst::new(Inst::mov(out_reg, r1), true));
-            it_insts.push(CondInst::new(Inst::mov(out_reg, r2), false));
-
-            ctx.emit(Inst::It {
-                cond,
-                insts: it_insts,
-            });
+                let tmp = tmp_reg(ctx, I32);
+                ctx.emit(alu_rr_imm8(ALUOp::Adds, tmp, rn, 1));
+            }
+            materialize_bool(ctx, rd, Cond::Eq, None);
         }
-        Opcode::Store | Opcode::Istore8 | Opcode::Istore16 | Opcode::Istore32 => {
-            let off = ldst_offset(ctx.data(insn)).unwrap();
-            let elem_ty = match op {
-                Opcode::Istore8 => I8,
-                Opcode::Istore16 => I16,
-                Opcode::Istore32 => I32,
-                Opcode::Store => ctx.input_ty(insn, 0),
-                _ => unreachable!(),
+        Opcode::Select | Opcode::Selectif | Opcode::SelectifSpectreGuard => {
+            let ty = ty.unwrap();
+            let (_, tys) = Inst::rc_for_type(ty)?;
+            // Get the values before setting the flags, which their lowering may not preserve.
+            let rn = ctx.put_input_in_regs(insn, 1);
+            let rm = ctx.put_input_in_regs(insn, 2);
+            let rd = ctx.get_output(insn, 0);
+            let cond = if op == Opcode::Select {
+                lower_bool_to_flags(ctx, inputs[0])
+            } else {
+                let cc = inst_condcode(ctx.data(insn)).unwrap();
+                lower_iflags(ctx, inputs[0], cc)
             };
-            if elem_ty.bits() > 32 {
-                unimplemented!()
+            for i in 0..rd.len() {
+                ctx.emit(it_select(
+                    cond,
+                    rd.regs()[i],
+                    rn.regs()[i],
+                    rm.regs()[i],
+                    tys[i],
+                ));
             }
-            let bits = elem_ty.bits() as u8;
-
-            assert_eq!(inputs.len(), 2, "only one input for store memory operands");
-            let rt = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let base = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-
-            let mem = AMode::RegOffset(base, i64::from(off));
-
-            ctx.emit(Inst::Store { rt, mem, bits });
         }
         Opcode::Load
         | Opcode::Uload8
@@ -394,102 +1540,393 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
         | Opcode::Uload16
         | Opcode::Sload16
         | Opcode::Uload32
-        | Opcode::Sload32 => {
-            let off = ldst_offset(ctx.data(insn)).unwrap();
-            let elem_ty = match op {
-                Opcode::Sload8 | Opcode::Uload8 => I8,
-                Opcode::Sload16 | Opcode::Uload16 => I16,
-                Opcode::Sload32 | Opcode::Uload32 => I32,
-                Opcode::Load => ctx.output_ty(insn, 0),
+        | Opcode::Sload32
+        | Opcode::LoadComplex
+        | Opcode::Uload8Complex
+        | Opcode::Sload8Complex
+        | Opcode::Uload16Complex
+        | Opcode::Sload16Complex
+        | Opcode::Uload32Complex
+        | Opcode::Sload32Complex => {
+            lower_load(ctx, insn, &inputs[..]);
+        }
+        Opcode::Store
+        | Opcode::Istore8
+        | Opcode::Istore16
+        | Opcode::Istore32
+        | Opcode::StoreComplex
+        | Opcode::Istore8Complex
+        | Opcode::Istore16Complex
+        | Opcode::Istore32Complex => {
+            lower_store(ctx, insn, &inputs[1..]);
+        }
+        Opcode::StackLoad | Opcode::StackStore => {
+            panic!("Direct stack memory access not supported; should not be used by Wasm");
+        }
+        Opcode::StackAddr => {
+            let (stack_slot, offset) = match *ctx.data(insn) {
+                InstructionData::StackLoad {
+                    opcode: Opcode::StackAddr,
+                    stack_slot,
+                    offset,
+                } => (stack_slot, offset),
                 _ => unreachable!(),
             };
-            if elem_ty.bits() > 32 {
-                unimplemented!()
-            }
-            let bits = elem_ty.bits() as u8;
-
-            let sign_extend = match op {
-                Opcode::Sload8 | Opcode::Sload16 | Opcode::Sload32 => true,
-                _ => false,
-            };
-            let out_reg = output_to_reg(ctx, outputs[0]);
-
-            assert_eq!(inputs.len(), 2, "only one input for store memory operands");
-            let base = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
-            let mem = AMode::RegOffset(base, i64::from(off));
-
-            ctx.emit(Inst::Load {
-                rt: out_reg,
-                mem,
-                bits,
-                sign_extend,
-            });
+            let rd = output_to_reg(ctx, outputs[0]);
+            let offset: i32 = offset.into();
+            let inst = ctx
+                .abi()
+                .stackslot_addr(stack_slot, u32::try_from(offset).unwrap(), rd);
+            ctx.emit(inst);
+        }
+        Opcode::AtomicRmw | Opcode::AtomicCas | Opcode::AtomicLoad | Opcode::AtomicStore => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: atomic op {} not supported",
+                op
+            )));
+        }
+        Opcode::Fence => {
+            ctx.emit(Inst::Fence);
         }
         Opcode::Uextend | Opcode::Sextend => {
             let output_ty = ty.unwrap();
             let input_ty = ctx.input_ty(insn, 0);
             let from_bits = input_ty.bits() as u8;
-            let to_bits = 32;
             let signed = op == Opcode::Sextend;
 
             let rm = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let rd = output_to_reg(ctx, outputs[0]);
+            let rd = if output_ty == I64 {
+                let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                if signed {
+                    // Only the low half may need extending before the high half copies its
+                    // sign.
+                    if from_bits < 32 {
+                        ctx.emit(Inst::Extend {
+                            rd: rd_lo,
+                            rm,
+                            from_bits,
+                            signed,
+                        });
+                    } else {
+                        ctx.emit(Inst::mov(rd_lo, rm));
+                    }
+                    ctx.emit(shift_imm(ShiftOp::ASR, rd_hi, rd_lo.to_reg(), 31));
+                    return Ok(());
+                }
+                ctx.emit(Inst::MovImm16 {
+                    rd: rd_hi,
+                    imm16: 0,
+                });
+                rd_lo
+            } else {
+                output_to_reg(ctx, outputs[0])
+            };
 
-            if output_ty.bits() > 32 {
-                panic!("Unexpected output type {}", output_ty);
-            }
-            if from_bits < to_bits {
+            if from_bits < 32 {
                 ctx.emit(Inst::Extend {
                     rd,
                     rm,
                     from_bits,
                     signed,
                 });
+            } else {
+                ctx.emit(Inst::mov(rd, rm));
             }
         }
-        Opcode::Bint | Opcode::Breduce | Opcode::Bextend | Opcode::Ireduce => {
-            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend);
+        Opcode::Ireduce => {
             let rd = output_to_reg(ctx, outputs[0]);
-            let ty = ctx.input_ty(insn, 0);
-
-            ctx.emit(Inst::gen_move(rd, rn, ty));
+            let rn = if ctx.input_ty(insn, 0) == I64 {
+                input_to_reg_pair(ctx, inputs[0]).0
+            } else {
+                input_to_reg(ctx, inputs[0], NarrowValueMode::None)
+            };
+            ctx.emit(Inst::mov(rd, rn));
+        }
+        Opcode::Bint | Opcode::Breduce | Opcode::Bextend | Opcode::Bmask => {
+            // Wider booleans are 0 or -1, so only the conversions to and from the 0 or 1 of a
+            // `B1` (or an integer, for `bint`) need work.
+            let from_ty = ctx.input_ty(insn, 0);
+            let to_ty = ty.unwrap();
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let (rd, rd_hi) = if to_ty == I64 {
+                let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                (rd_lo, Some(rd_hi))
+            } else {
+                (output_to_reg(ctx, outputs[0]), None)
+            };
+            if op == Opcode::Bint || (to_ty == B1 && from_ty != B1) {
+                ctx.emit(alu_rr_imm8(ALUOp::And, rd, rn, 1));
+            } else if from_ty == B1 && to_ty != B1 {
+                ctx.emit(Inst::Extend {
+                    rd,
+                    rm: rn,
+                    from_bits: 1,
+                    signed: true,
+                });
+            } else {
+                ctx.emit(Inst::mov(rd, rn));
+            }
+            if let Some(rd_hi) = rd_hi {
+                if op == Opcode::Bint {
+                    ctx.emit(Inst::MovImm16 {
+                        rd: rd_hi,
+                        imm16: 0,
+                    });
+                } else {
+                    ctx.emit(Inst::mov(rd_hi, rd.to_reg()));
+                }
+            }
         }
         Opcode::Copy => {
+            let ty = ty.unwrap();
+            let (_, tys) = Inst::rc_for_type(ty)?;
+            let rn = ctx.put_input_in_regs(insn, 0);
+            let rd = ctx.get_output(insn, 0);
+            for i in 0..rd.len() {
+                ctx.emit(Inst::gen_move(rd.regs()[i], rn.regs()[i], tys[i]));
+            }
+        }
+        Opcode::Isplit => {
+            let (lo, hi) = input_to_reg_pair(ctx, inputs[0]);
+            let rd_lo = output_to_reg(ctx, outputs[0]);
+            let rd_hi = output_to_reg(ctx, outputs[1]);
+            ctx.emit(Inst::mov(rd_lo, lo));
+            ctx.emit(Inst::mov(rd_hi, hi));
+        }
+        Opcode::Iconcat => {
+            let lo = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let hi = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+            let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+            ctx.emit(Inst::mov(rd_lo, lo));
+            ctx.emit(Inst::mov(rd_hi, hi));
+        }
+        Opcode::FuncAddr => {
+            let rt = output_to_reg(ctx, outputs[0]);
+            let (extname, _) = ctx.call_target(insn).unwrap();
+            let extname = extname.clone();
+            ctx.emit(Inst::LoadExtName {
+                rt,
+                name: Box::new(extname),
+                offset: 0,
+            });
+        }
+        Opcode::SymbolValue => {
+            let rt = output_to_reg(ctx, outputs[0]);
+            let (extname, _, offset) = ctx.symbol_value(insn).unwrap();
+            let extname = extname.clone();
+            ctx.emit(Inst::LoadExtName {
+                rt,
+                name: Box::new(extname),
+                offset: i32::try_from(offset).unwrap(),
+            });
+        }
+        Opcode::ConstAddr | Opcode::TlsValue | Opcode::GetPinnedReg | Opcode::SetPinnedReg => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} not supported",
+                op
+            )));
+        }
+        Opcode::HeapAddr => {
+            panic!("heap_addr should have been removed by legalization!");
+        }
+        Opcode::TableAddr => {
+            panic!("table_addr should have been removed by legalization!");
+        }
+        Opcode::GlobalValue => {
+            panic!("global_value should have been removed by legalization!");
+        }
+        Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv => {
+            let bits = ty.unwrap().bits() as u8;
             let rd = output_to_reg(ctx, outputs[0]);
             let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
-            let ty = ctx.input_ty(insn, 0);
-
-            ctx.emit(Inst::gen_move(rd, rn, ty));
+            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+            let fpu_op = match op {
+                Opcode::Fadd => FPUOp2::Add,
+                Opcode::Fsub => FPUOp2::Sub,
+                Opcode::Fmul => FPUOp2::Mul,
+                Opcode::Fdiv => FPUOp2::Div,
+                _ => unreachable!(),
+            };
+            ctx.emit(Inst::FpuRRR {
+                fpu_op,
+                rd,
+                rn,
+                rm,
+                bits,
+            });
         }
-        Opcode::Debugtrap => {
-            ctx.emit(Inst::Bkpt);
+        Opcode::Fmin | Opcode::Fmax => {
+            let bits = ty.unwrap().bits() as u8;
+            let rd = output_to_reg(ctx, outputs[0]);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+            ctx.emit(Inst::FpuMinMax {
+                is_min: op == Opcode::Fmin,
+                rd,
+                rn,
+                rm,
+                bits,
+            });
         }
-        Opcode::Trap => {
-            let trap_info = inst_trapcode(ctx.data(insn)).unwrap();
-            ctx.emit(Inst::Udf { trap_info })
+        Opcode::Sqrt | Opcode::Fneg | Opcode::Fabs | Opcode::Fpromote | Opcode::Fdemote => {
+            let in_bits = ctx.input_ty(insn, 0).bits() as u8;
+            let rd = output_to_reg(ctx, outputs[0]);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let fpu_op = match op {
+                Opcode::Sqrt => FPUOp1::Sqrt,
+                Opcode::Fneg => FPUOp1::Neg,
+                Opcode::Fabs => FPUOp1::Abs,
+                Opcode::Fpromote => FPUOp1::Cvt32To64,
+                Opcode::Fdemote => FPUOp1::Cvt64To32,
+                _ => unreachable!(),
+            };
+            ctx.emit(Inst::FpuRR {
+                fpu_op,
+                rd,
+                rn,
+                bits: in_bits,
+            });
         }
-        Opcode::Trapif => {
-            let cmp_insn = ctx
-                .get_input_as_source_or_const(inputs[0].insn, inputs[0].input)
-                .inst
-                .unwrap()
-                .0;
-            debug_assert_eq!(ctx.data(cmp_insn).opcode(), Opcode::Ifcmp);
-            emit_cmp(ctx, cmp_insn);
-
-            let trap_info = inst_trapcode(ctx.data(insn)).unwrap();
-            let condcode = inst_condcode(ctx.data(insn)).unwrap();
-            let cond = lower_condcode(condcode);
-
-            ctx.emit(Inst::TrapIf { cond, trap_info });
+        Opcode::Ceil | Opcode::Floor | Opcode::Trunc | Opcode::Nearest => {
+            // VFPv3 has no rounding instructions.
+            let ty = ty.unwrap();
+            let libcall = LibCall::for_inst(op, ty).unwrap();
+            let rn = ctx.put_input_in_regs(insn, 0);
+            let rd = ctx.get_output(insn, 0);
+            emit_vm_call(ctx, flags, triple, libcall, insn, &[rn], &[rd])?;
         }
-        Opcode::FallthroughReturn | Opcode::Return => {
-            for (i, input) in inputs.iter().enumerate() {
-                let reg = input_to_reg(ctx, *input, NarrowValueMode::None);
-                let retval_reg = ctx.retval(i).only_reg().unwrap();
-                let ty = ctx.input_ty(insn, i);
-
-                ctx.emit(Inst::gen_move(retval_reg, reg, ty));
+        Opcode::Fma => {
+            // VFPv3 has no fused multiply-add.
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} not supported",
+                op
+            )));
+        }
+        Opcode::Fcopysign => {
+            let ty = ty.unwrap();
+            let rd = output_to_reg(ctx, outputs[0]);
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let rm = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+            let word = tmp_reg(ctx, I32);
+            if ty == F32 {
+                let a = tmp_reg(ctx, I32);
+                let b = tmp_reg(ctx, I32);
+                ctx.emit(Inst::MovFromFpu { rd: a, rn });
+                ctx.emit(Inst::MovFromFpu { rd: b, rn: rm });
+                lower_copysign_word(ctx, word, a.to_reg(), b.to_reg());
+                ctx.emit(Inst::MovToFpu {
+                    rd,
+                    rn: word.to_reg(),
+                });
+            } else {
+                let a_lo = tmp_reg(ctx, I32);
+                let a_hi = tmp_reg(ctx, I32);
+                let b_lo = tmp_reg(ctx, I32);
+                let b_hi = tmp_reg(ctx, I32);
+                ctx.emit(Inst::MovFromFpuPair {
+                    rd_lo: a_lo,
+                    rd_hi: a_hi,
+                    rn,
+                });
+                ctx.emit(Inst::MovFromFpuPair {
+                    rd_lo: b_lo,
+                    rd_hi: b_hi,
+                    rn: rm,
+                });
+                lower_copysign_word(ctx, word, a_hi.to_reg(), b_hi.to_reg());
+                ctx.emit(Inst::MovToFpuPair {
+                    rd,
+                    rn_lo: a_lo.to_reg(),
+                    rn_hi: word.to_reg(),
+                });
+            }
+        }
+        Opcode::FcvtToSint | Opcode::FcvtToUint | Opcode::FcvtToSintSat | Opcode::FcvtToUintSat => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            let signed = op == Opcode::FcvtToSint || op == Opcode::FcvtToSintSat;
+            let saturating = op == Opcode::FcvtToSintSat || op == Opcode::FcvtToUintSat;
+            if out_ty != I32 && out_ty != I64 {
+                return Err(CodegenError::Unsupported(format!(
+                    "arm32: {} to type {} not supported",
+                    op, out_ty
+                )));
+            }
+            let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            if !saturating {
+                emit_fcvt_to_int_checks(ctx, rn, in_ty, out_ty, signed);
+            }
+            if out_ty == I64 {
+                let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                lower_fcvt_to_i64(ctx, rn, in_ty, signed, rd_lo, rd_hi);
+            } else {
+                // `vcvt` saturates, and converts NaN to 0.
+                let rd = output_to_reg(ctx, outputs[0]);
+                let op = match (in_ty, signed) {
+                    (F32, true) => FpuToIntOp::F32ToI32,
+                    (F32, false) => FpuToIntOp::F32ToU32,
+                    (F64, true) => FpuToIntOp::F64ToI32,
+                    (F64, false) => FpuToIntOp::F64ToU32,
+                    _ => panic!("Unexpected input type {}", in_ty),
+                };
+                let tmp = tmp_reg(ctx, F64);
+                ctx.emit(Inst::FpuToInt { op, rd, rn, tmp });
+            }
+        }
+        Opcode::FcvtFromSint | Opcode::FcvtFromUint => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            let signed = op == Opcode::FcvtFromSint;
+            let rd = output_to_reg(ctx, outputs[0]);
+            if in_ty == I64 {
+                let (lo, hi) = input_to_reg_pair(ctx, inputs[0]);
+                if out_ty == F64 {
+                    lower_i64_to_f64(ctx, lo, hi, signed, rd);
+                } else {
+                    lower_i64_to_f32(ctx, lo, hi, signed, rd);
+                }
+            } else {
+                let narrow_mode = if signed {
+                    NarrowValueMode::SignExtend
+                } else {
+                    NarrowValueMode::ZeroExtend
+                };
+                let rn = input_to_reg(ctx, inputs[0], narrow_mode);
+                let op = match (out_ty, signed) {
+                    (F32, true) => IntToFpuOp::I32ToF32,
+                    (F32, false) => IntToFpuOp::U32ToF32,
+                    (F64, true) => IntToFpuOp::I32ToF64,
+                    (F64, false) => IntToFpuOp::U32ToF64,
+                    _ => panic!("Unexpected output type {}", out_ty),
+                };
+                ctx.emit(Inst::IntToFpu { op, rd, rn });
+            }
+        }
+        Opcode::Bitcast => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            match (in_ty, out_ty) {
+                (I32, F32) => {
+                    let rd = output_to_reg(ctx, outputs[0]);
+                    let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                    ctx.emit(Inst::MovToFpu { rd, rn });
+                }
+                (F32, I32) => {
+                    let rd = output_to_reg(ctx, outputs[0]);
+                    let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                    ctx.emit(Inst::MovFromFpu { rd, rn });
+                }
+                (I64, F64) => {
+                    let rd = output_to_reg(ctx, outputs[0]);
+                    let (rn_lo, rn_hi) = input_to_reg_pair(ctx, inputs[0]);
+                    ctx.emit(Inst::MovToFpuPair { rd, rn_lo, rn_hi });
+                }
+                (F64, I64) => {
+                    let (rd_lo, rd_hi) = output_to_reg_pair(ctx, outputs[0]);
+                    let rn = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                    ctx.emit(Inst::MovFromFpuPair { rd_lo, rd_hi, rn });
+                }
+                _ => panic!("Unexpected bitcast from {} to {}", in_ty, out_ty),
             }
         }
         Opcode::Call | Opcode::CallIndirect => {
@@ -518,18 +1955,267 @@ pub(crate) fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
                 }
                 _ => unreachable!(),
             };
+
+            abi.emit_stack_pre_adjust(ctx);
             assert_eq!(inputs.len(), abi.num_args());
-            for (i, input) in inputs.iter().enumerate().filter(|(i, _)| *i <= 3) {
-                let arg_reg = input_to_reg(ctx, *input, NarrowValueMode::None);
-                abi.emit_copy_regs_to_arg(ctx, i, ValueRegs::one(arg_reg));
+            for i in abi.get_copy_to_arg_order() {
+                let input = inputs[i];
+                let arg_regs = ctx.put_input_in_regs(input.insn, input.input);
+                abi.emit_copy_regs_to_arg(ctx, i, arg_regs);
             }
             abi.emit_call(ctx);
             for (i, output) in outputs.iter().enumerate() {
-                let retval_reg = output_to_reg(ctx, *output);
-                abi.emit_copy_retval_to_regs(ctx, i, ValueRegs::one(retval_reg));
+                let retval_regs = ctx.get_output(output.insn, output.output);
+                abi.emit_copy_retval_to_regs(ctx, i, retval_regs);
+            }
+            abi.emit_stack_post_adjust(ctx);
+        }
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            let caller_conv = ctx.abi().call_conv();
+            let (mut abi, inputs) = match op {
+                Opcode::ReturnCall => {
+                    let (extname, dist) = ctx.call_target(insn).unwrap();
+                    let extname = extname.clone();
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert_eq!(inputs.len(), sig.params.len());
+                    (
+                        Arm32ABICaller::from_func(sig, &extname, dist, caller_conv, flags)?,
+                        &inputs[..],
+                    )
+                }
+                Opcode::ReturnCallIndirect => {
+                    let ptr = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend);
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert_eq!(inputs.len() - 1, sig.params.len());
+                    (
+                        Arm32ABICaller::from_ptr(sig, ptr, op, caller_conv, flags)?,
+                        &inputs[1..],
+                    )
+                }
+                _ => unreachable!(),
+            };
+
+            abi.prepare_tail_call(ctx)?;
+            assert_eq!(inputs.len(), abi.num_args());
+            for i in abi.get_copy_to_arg_order() {
+                let input = inputs[i];
+                let arg_regs = ctx.put_input_in_regs(input.insn, input.input);
+                abi.emit_copy_regs_to_arg(ctx, i, arg_regs);
+            }
+            abi.emit_return_call(ctx);
+        }
+        Opcode::FallthroughReturn | Opcode::Return => {
+            for (i, input) in inputs.iter().enumerate() {
+                let src_regs = ctx.put_input_in_regs(input.insn, input.input);
+                let retval_regs = ctx.retval(i);
+
+                assert_eq!(src_regs.len(), retval_regs.len());
+                let ty = ctx.input_ty(insn, i);
+                let (_, tys) = Inst::rc_for_type(ty)?;
+
+                src_regs
+                    .regs()
+                    .iter()
+                    .zip(retval_regs.regs().iter())
+                    .zip(tys.iter())
+                    .for_each(|((&src, &dst), &ty)| {
+                        ctx.emit(Inst::gen_move(dst, src, ty));
+                    });
+            }
+            // N.B.: the Ret itself is generated by the ABI.
+        }
+        Opcode::Debugtrap => {
+            ctx.emit(Inst::Bkpt);
+        }
+        Opcode::Trap | Opcode::ResumableTrap => {
+            let trap_info = inst_trapcode(ctx.data(insn)).unwrap();
+            ctx.emit_safepoint(Inst::Udf { trap_info });
+        }
+        Opcode::Trapif => {
+            let trap_info = inst_trapcode(ctx.data(insn)).unwrap();
+            let cc = inst_condcode(ctx.data(insn)).unwrap();
+            let cond = lower_iflags(ctx, inputs[0], cc);
+            ctx.emit_safepoint(Inst::TrapIf { cond, trap_info });
+        }
+        Opcode::Trapff => {
+            let trap_info = inst_trapcode(ctx.data(insn)).unwrap();
+            let cc = inst_fp_condcode(ctx.data(insn)).unwrap();
+            let (cond, cond2) = lower_fflags(ctx, inputs[0], cc);
+            ctx.emit_safepoint(Inst::TrapIf { cond, trap_info });
+            if let Some(cond) = cond2 {
+                ctx.emit_safepoint(Inst::TrapIf { cond, trap_info });
             }
         }
-        _ => panic!("lowering {} unimplemented!", op),
+        Opcode::Trapz | Opcode::Trapnz | Opcode::ResumableTrapnz => {
+            panic!("trapz / trapnz / resumable_trapnz should have been removed by legalization!");
+        }
+
+        Opcode::UaddSat
+        | Opcode::SaddSat
+        | Opcode::UsubSat
+        | Opcode::SsubSat
+        | Opcode::RawBitcast
+        | Opcode::Splat
+        | Opcode::Swizzle
+        | Opcode::Insertlane
+        | Opcode::Extractlane
+        | Opcode::Imin
+        | Opcode::Umin
+        | Opcode::Imax
+        | Opcode::Umax
+        | Opcode::AvgRound
+        | Opcode::FminPseudo
+        | Opcode::FmaxPseudo
+        | Opcode::Uload8x8
+        | Opcode::Uload8x8Complex
+        | Opcode::Sload8x8
+        | Opcode::Sload8x8Complex
+        | Opcode::Uload16x4
+        | Opcode::Uload16x4Complex
+        | Opcode::Sload16x4
+        | Opcode::Sload16x4Complex
+        | Opcode::Uload32x2
+        | Opcode::Uload32x2Complex
+        | Opcode::Sload32x2
+        | Opcode::Sload32x2Complex
+        | Opcode::Vconst
+        | Opcode::Shuffle
+        | Opcode::Vsplit
+        | Opcode::Vconcat
+        | Opcode::Vselect
+        | Opcode::VanyTrue
+        | Opcode::VallTrue
+        | Opcode::VhighBits
+        | Opcode::ScalarToVector
+        | Opcode::Snarrow
+        | Opcode::Unarrow
+        | Opcode::Uunarrow
+        | Opcode::SwidenLow
+        | Opcode::SwidenHigh
+        | Opcode::UwidenLow
+        | Opcode::UwidenHigh
+        | Opcode::WideningPairwiseDotProductS
+        | Opcode::SqmulRoundSat
+        | Opcode::FvpromoteLow
+        | Opcode::Fvdemote
+        | Opcode::FcvtLowFromSint
+        | Opcode::IaddPairwise => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: vector op {} not supported",
+                op
+            )));
+        }
+
+        Opcode::Spill
+        | Opcode::Fill
+        | Opcode::FillNop
+        | Opcode::Regmove
+        | Opcode::CopySpecial
+        | Opcode::CopyToSsa
+        | Opcode::CopyNop
+        | Opcode::AdjustSpDown
+        | Opcode::AdjustSpUpImm
+        | Opcode::AdjustSpDownImm
+        | Opcode::DummySargT
+        | Opcode::IfcmpSp
+        | Opcode::Regspill
+        | Opcode::Regfill => {
+            panic!("Unused opcode should not be encountered.");
+        }
+
+        Opcode::Jump
+        | Opcode::Fallthrough
+        | Opcode::Brz
+        | Opcode::Brnz
+        | Opcode::BrUnwind
+        | Opcode::BrIcmp
+        | Opcode::Brif
+        | Opcode::Brff
+        | Opcode::IndirectJumpTableBr
+        | Opcode::BrTable => {
+            panic!("Branch opcode reached non-branch lowering logic!");
+        }
+
+        Opcode::JumpTableEntry | Opcode::JumpTableBase => {
+            panic!("Should not appear: we handle BrTable directly");
+        }
+
+        Opcode::Safepoint => {
+            panic!("safepoint instructions not used by new backend's safepoints!");
+        }
+
+        Opcode::IaddCin | Opcode::IaddCout | Opcode::IaddCarry => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} not supported",
+                op
+            )));
+        }
+
+        Opcode::IsubBin | Opcode::IsubBout | Opcode::IsubBorrow => {
+            return Err(CodegenError::Unsupported(format!(
+                "arm32: {} not supported",
+                op
+            )));
+        }
+
+        Opcode::IaddImm
+        | Opcode::ImulImm
+        | Opcode::UdivImm
+        | Opcode::SdivImm
+        | Opcode::UremImm
+        | Opcode::SremImm
+        | Opcode::IrsubImm
+        | Opcode::BandImm
+        | Opcode::BorImm
+        | Opcode::BxorImm
+        | Opcode::RotlImm
+        | Opcode::RotrImm
+        | Opcode::IshlImm
+        | Opcode::UshrImm
+        | Opcode::SshrImm
+        | Opcode::IcmpImm
+        | Opcode::IfcmpImm => {
+            panic!("ALU+imm ops should not appear here!");
+        }
+
+        #[cfg(feature = "x86")]
+        Opcode::X86Udivmodx
+        | Opcode::X86Sdivmodx
+        | Opcode::X86Umulx
+        | Opcode::X86Smulx
+        | Opcode::X86Cvtt2si
+        | Opcode::X86Fmin
+        | Opcode::X86Fmax
+        | Opcode::X86Push
+        | Opcode::X86Pop
+        | Opcode::X86Bsr
+        | Opcode::X86Bsf
+        | Opcode::X86Pblendw
+        | Opcode::X86Pshufd
+        | Opcode::X86Pshufb
+        | Opcode::X86Pextr
+        | Opcode::X86Pinsr
+        | Opcode::X86Insertps
+        | Opcode::X86Movsd
+        | Opcode::X86Movlhps
+        | Opcode::X86Psll
+        | Opcode::X86Psrl
+        | Opcode::X86Psra
+        | Opcode::X86Ptest
+        | Opcode::X86Pmaxs
+        | Opcode::X86Pmaxu
+        | Opcode::X86Pmins
+        | Opcode::X86Pminu
+        | Opcode::X86Pmullq
+        | Opcode::X86Pmuludq
+        | Opcode::X86Punpckh
+        | Opcode::X86Punpckl
+        | Opcode::X86Vcvtudq2ps
+        | Opcode::X86Palignr
+        | Opcode::X86ElfTlsGetAddr
+        | Opcode::X86MachoTlsGetAddr => {
+            panic!("x86-specific opcode in supposedly arch-neutral IR!");
+        }
     }
 
     Ok(())
@@ -556,32 +2242,62 @@ pub(crate) fn lower_branch<C: LowerCtx<I = Inst>>(
         assert!(op1 == Opcode::Jump || op1 == Opcode::Fallthrough);
         let taken = BranchTarget::Label(targets[0]);
         let not_taken = BranchTarget::Label(targets[1]);
+        let flag_input = InsnInput {
+            insn: branches[0],
+            input: 0,
+        };
 
-        match op0 {
+        let cond = match op0 {
+            Opcode::BrUnwind => {
+                return Err(CodegenError::Unsupported(format!(
+                    "{}: exception handling is not implemented for ARM32",
+                    op0
+                )));
+            }
             Opcode::Brz | Opcode::Brnz => {
-                let rn = input_to_reg(
-                    ctx,
-                    InsnInput {
-                        insn: branches[0],
-                        input: 0,
-                    },
-                    NarrowValueMode::ZeroExtend,
-                );
-                let cond = if op0 == Opcode::Brz {
-                    Cond::Eq
+                let cond = lower_bool_to_flags(ctx, flag_input);
+                if op0 == Opcode::Brz {
+                    cond.invert()
                 } else {
-                    Cond::Ne
-                };
-
-                ctx.emit(Inst::CmpImm8 { rn, imm8: 0 });
-                ctx.emit(Inst::CondBr {
-                    taken,
-                    not_taken,
-                    cond,
-                });
+                    cond
+                }
             }
-            _ => unimplemented!(),
-        }
+            Opcode::BrIcmp => {
+                let cc = inst_condcode(ctx.data(branches[0])).unwrap();
+                emit_cmp(ctx, branches[0], cc)
+            }
+            Opcode::Brif => {
+                let cc = inst_condcode(ctx.data(branches[0])).unwrap();
+                lower_iflags(ctx, flag_input, cc)
+            }
+            Opcode::Brff => {
+                let cc = inst_fp_condcode(ctx.data(branches[0])).unwrap();
+                match lower_fflags(ctx, flag_input, cc) {
+                    (cond, None) => cond,
+                    (cond, cond2) => {
+                        let tmp = ctx.alloc_tmp(B1).only_reg().unwrap();
+                        materialize_bool(ctx, tmp, cond, cond2);
+                        ctx.emit(Inst::CmpImm8 {
+                            rn: tmp.to_reg(),
+                            imm8: 0,
+                        });
+                        Cond::Ne
+                    }
+                }
+            }
+            _ => {
+                return Err(CodegenError::Unsupported(format!(
+                    "arm32: {} not supported",
+                    op0
+                )));
+            }
+        };
+
+        ctx.emit(Inst::CondBr {
+            taken,
+            not_taken,
+            cond,
+        });
     } else {
         // Must be an unconditional branch or an indirect branch.
         let op = ctx.data(branches[0]).opcode();
@@ -595,7 +2311,58 @@ pub(crate) fn lower_branch<C: LowerCtx<I = Inst>>(
                     dest: BranchTarget::Label(targets[0]),
                 });
             }
-            _ => unimplemented!(),
+            Opcode::BrTable => {
+                // Expand `br_table index, default, JT` to:
+                //
+                //   emit_island  // this forces an island at this point
+                //                // if the jumptable would push us past
+                //                // the deadline
+                //   cmp idx, #jt_size
+                //   [JTSequence, which branches to default if hs]
+                let jt_size = targets.len() - 1;
+                assert!(jt_size <= std::u32::MAX as usize);
+
+                // The comparison takes up to 10 bytes, and the sequence up to 18 before the
+                // table itself.
+                ctx.emit(Inst::EmitIsland {
+                    needed_space: 4 * (8 + jt_size) as CodeOffset,
+                });
+
+                let ridx = input_to_reg(
+                    ctx,
+                    InsnInput {
+                        insn: branches[0],
+                        input: 0,
+                    },
+                    NarrowValueMode::ZeroExtend,
+                );
+                if jt_size < 256 {
+                    ctx.emit(Inst::CmpImm8 {
+                        rn: ridx,
+                        imm8: jt_size as u8,
+                    });
+                } else {
+                    let rm = const_to_reg(ctx, jt_size as u32);
+                    ctx.emit(Inst::Cmp { rn: ridx, rm });
+                }
+
+                let jt_targets: Vec<BranchTarget> = targets
+                    .iter()
+                    .skip(1)
+                    .map(|bix| BranchTarget::Label(*bix))
+                    .collect();
+                let default_target = BranchTarget::Label(targets[0]);
+                let targets_for_term: Vec<MachLabel> = targets.to_vec();
+                ctx.emit(Inst::JTSequence {
+                    ridx,
+                    info: Box::new(JTSequenceInfo {
+                        targets: jt_targets,
+                        default_target,
+                        targets_for_term,
+                    }),
+                });
+            }
+            _ => panic!("Unknown branch type!"),
         }
     }
 
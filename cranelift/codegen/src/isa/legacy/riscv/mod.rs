@@ -167,7 +167,7 @@ mod tests {
     fn test_64bitenc() {
         let shared_builder = settings::builder();
         let shared_flags = settings::Flags::new(shared_builder);
-        let isa = isa::lookup(triple!("riscv64"))
+        let isa = isa::lookup_variant(triple!("riscv64"), isa::BackendVariant::Legacy)
             .unwrap()
             .finish(shared_flags);
 
@@ -84,6 +84,9 @@ pub(crate) mod aarch64;
 #[cfg(feature = "s390x")]
 mod s390x;
 
+#[cfg(feature = "riscv64")]
+mod riscv64;
+
 #[cfg(any(feature = "x86", feature = "riscv"))]
 mod legacy;
 
@@ -143,9 +146,11 @@ impl Default for BackendVariant {
 /// by `variant` if available.
 pub fn lookup_variant(triple: Triple, variant: BackendVariant) -> Result<Builder, LookupError> {
     match (triple.architecture, variant) {
-        (Architecture::Riscv32 { .. }, _) | (Architecture::Riscv64 { .. }, _) => {
+        (Architecture::Riscv32 { .. }, _)
+        | (Architecture::Riscv64 { .. }, BackendVariant::Legacy) => {
             isa_builder!(riscv, (feature = "riscv"), triple)
         }
+        (Architecture::Riscv64 { .. }, _) => isa_builder!(riscv64, (feature = "riscv64"), triple),
         (Architecture::X86_64, BackendVariant::Legacy) => {
             isa_builder!(x86, (feature = "x86"), triple)
         }
@@ -0,0 +1,856 @@
+//! Implementation of the standard RISC-V 64 (LP64D) ABI.
+//!
+//! The frame layout follows AArch64's: the prologue pushes the return
+//! address and the caller's frame pointer, points the frame pointer at them,
+//! and then allocates the clobbered callee-saves and the fixed frame below.
+//!
+//! ```plain
+//!   (high address)
+//!
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | stack args                |
+//!                              | (accessed via FP + 16)    |
+//! SP at function entry ----->  +---------------------------+
+//!                              | return address            |
+//! FP after prologue -------->  | FP at function entry      |
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | clobbered callee-saves    |
+//! unwind-frame base     ---->  | (pushed by prologue)      |
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | spill slots               |
+//!                              | (accessed via nominal SP) |
+//!                              |          ...              |
+//!                              | stack slots               |
+//!                              | (accessed via nominal SP) |
+//! nominal SP --------------->  | (alloc'd by prologue)     |
+//!                              +---------------------------+
+//!                              |          ...              |
+//!                              | args for call             |
+//! SP ----------------------->  | (alloc'd by call seq)     |
+//!                              +---------------------------+
+//!
+//!   (low address)
+//! ```
+//!
+//! Integers narrower than 64 bits are kept sign-extended in registers, which
+//! is what the psABI requires of 32-bit integers, signed or not, so no
+//! extension is done at calls and returns. Narrower integers are only
+//! passed correctly between functions compiled by Cranelift.
+//!
+//! Floats beyond the eight floating-point argument registers go on the stack
+//! rather than in the remaining integer registers, and 128-bit integers are
+//! never split between the last argument register and the stack. Both only
+//! matter for calls to foreign code with many arguments.
+
+use crate::ir;
+use crate::ir::condcodes::IntCC;
+use crate::ir::types::*;
+use crate::ir::MemFlags;
+use crate::ir::Opcode;
+use crate::ir::{ExternalName, LibCall};
+use crate::isa;
+use crate::isa::riscv64::inst::*;
+use crate::isa::unwind::UnwindInst;
+use crate::machinst::*;
+use crate::settings;
+use crate::{CodegenError, CodegenResult};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use regalloc::{RealReg, Reg, RegClass, Set, Writable};
+use smallvec::{smallvec, SmallVec};
+
+// We use a generic implementation that factors out ABI commonalities.
+
+/// Support for the RISC-V 64 ABI from the callee side (within a function body).
+pub(crate) type Riscv64ABICallee = ABICalleeImpl<Riscv64MachineDeps>;
+
+/// Support for the RISC-V 64 ABI from the caller side (at a callsite).
+pub(crate) type Riscv64ABICaller = ABICallerImpl<Riscv64MachineDeps>;
+
+/// This is the limit for the size of argument and return-value areas on the
+/// stack. We place a reasonable limit here to avoid integer overflow issues
+/// with 32-bit arithmetic: for now, 128 MB.
+static STACK_ARG_RET_SIZE_LIMIT: u64 = 128 * 1024 * 1024;
+
+/// The first argument register of each class: `a0` and `fa0`.
+const FIRST_ARG_REG: u8 = 10;
+
+/// The number of argument registers of each class: `a0`-`a7` and `fa0`-`fa7`.
+const NUM_ARG_REGS: u8 = 8;
+
+impl Into<AMode> for StackAMode {
+    fn into(self) -> AMode {
+        match self {
+            StackAMode::FPOffset(off, _ty) => AMode::FPOffset(off),
+            StackAMode::NominalSPOffset(off, _ty) => AMode::NominalSPOffset(off),
+            StackAMode::SPOffset(off, _ty) => AMode::SPOffset(off),
+        }
+    }
+}
+
+/// RISC-V 64-specific ABI behavior. This struct just serves as an
+/// implementation point for the trait; it is never actually instantiated.
+pub(crate) struct Riscv64MachineDeps;
+
+impl ABIMachineSpec for Riscv64MachineDeps {
+    type I = Inst;
+
+    fn word_bits() -> u32 {
+        64
+    }
+
+    /// Return required stack alignment in bytes.
+    fn stack_align(_call_conv: isa::CallConv) -> u32 {
+        16
+    }
+
+    fn compute_arg_locs(
+        call_conv: isa::CallConv,
+        _flags: &settings::Flags,
+        params: &[ir::AbiParam],
+        args_or_rets: ArgsOrRets,
+        add_ret_area_ptr: bool,
+    ) -> CodegenResult<(Vec<ABIArg>, i64, Option<usize>)> {
+        if call_conv.extends_baldrdash() {
+            return Err(CodegenError::Unsupported(format!(
+                "the {} calling convention on riscv64",
+                call_conv
+            )));
+        }
+
+        let mut next_xreg = 0;
+        let mut next_freg = 0;
+        let mut next_stack: u64 = 0;
+        let mut ret = vec![];
+
+        // In the Wasmtime ABI only one return value can be in a register.
+        let (max_per_class_reg_vals, mut remaining_reg_vals) =
+            if call_conv.extends_wasmtime() && args_or_rets == ArgsOrRets::Rets {
+                (1, 1)
+            } else {
+                (NUM_ARG_REGS, 2 * NUM_ARG_REGS)
+            };
+
+        for param in params {
+            // Validate "purpose".
+            match &param.purpose {
+                &ir::ArgumentPurpose::VMContext
+                | &ir::ArgumentPurpose::Normal
+                | &ir::ArgumentPurpose::StackLimit
+                | &ir::ArgumentPurpose::SignatureId
+                | &ir::ArgumentPurpose::StructReturn
+                | &ir::ArgumentPurpose::StructArgument(_) => {}
+                _ => panic!(
+                    "Unsupported argument purpose {:?} in signature: {:?}",
+                    param.purpose, params
+                ),
+            }
+
+            assert!(
+                legal_type_for_machine(param.value_type),
+                "Invalid type for riscv64: {:?}",
+                param.value_type
+            );
+
+            let (rcs, reg_types) = Inst::rc_for_type(param.value_type)?;
+
+            if let ir::ArgumentPurpose::StructArgument(size) = param.purpose {
+                let offset = next_stack as i64;
+                let size = size as u64;
+                assert!(size % 8 == 0, "StructArgument size is not properly aligned");
+                next_stack += size;
+                ret.push(ABIArg::StructArg {
+                    offset,
+                    size,
+                    purpose: param.purpose,
+                });
+                continue;
+            }
+
+            if rcs.len() == 2 {
+                // 128-bit integers go in two consecutive integer registers,
+                // low half first.
+                assert!(
+                    rcs == &[RegClass::I64, RegClass::I64],
+                    "Unable to handle non i64 regs"
+                );
+                if max_per_class_reg_vals - next_xreg >= 2 && remaining_reg_vals >= 2 {
+                    let slots = (0..2)
+                        .map(|i| ABIArgSlot::Reg {
+                            reg: xreg(FIRST_ARG_REG + next_xreg + i).to_real_reg(),
+                            ty: I64,
+                            extension: param.extension,
+                        })
+                        .collect();
+                    ret.push(ABIArg::Slots {
+                        slots,
+                        purpose: param.purpose,
+                    });
+                    next_xreg += 2;
+                    remaining_reg_vals -= 2;
+                    continue;
+                }
+            } else {
+                let rc = rcs[0];
+                let next_reg = match rc {
+                    RegClass::I64 => &mut next_xreg,
+                    RegClass::F64 => &mut next_freg,
+                    _ => panic!("Invalid register class: {:?}", rc),
+                };
+
+                if *next_reg < max_per_class_reg_vals && remaining_reg_vals > 0 {
+                    let reg = match rc {
+                        RegClass::I64 => xreg(FIRST_ARG_REG + *next_reg),
+                        RegClass::F64 => freg(FIRST_ARG_REG + *next_reg),
+                        _ => unreachable!(),
+                    };
+                    ret.push(ABIArg::reg(
+                        reg.to_real_reg(),
+                        param.value_type,
+                        param.extension,
+                        param.purpose,
+                    ));
+                    *next_reg += 1;
+                    remaining_reg_vals -= 1;
+                    continue;
+                }
+            }
+
+            // Spill to the stack. Every argument takes a slot of at least 8
+            // bytes, except for return values in the Wasmtime ABI.
+            let size = (ty_bits(param.value_type) / 8) as u64;
+            let size = if call_conv.extends_wasmtime() && args_or_rets == ArgsOrRets::Rets {
+                size
+            } else {
+                std::cmp::max(size, 8)
+            };
+
+            // Align the stack slot.
+            debug_assert!(size.is_power_of_two());
+            next_stack = align_to(next_stack, size);
+
+            let slots = reg_types
+                .iter()
+                .copied()
+                // Build the stack locations from each slot
+                .scan(next_stack, |next_stack, ty| {
+                    let slot_offset = *next_stack as i64;
+                    *next_stack += (ty_bits(ty) / 8) as u64;
+
+                    Some((ty, slot_offset))
+                })
+                .map(|(ty, offset)| ABIArgSlot::Stack {
+                    offset,
+                    ty,
+                    extension: param.extension,
+                })
+                .collect();
+
+            ret.push(ABIArg::Slots {
+                slots,
+                purpose: param.purpose,
+            });
+
+            next_stack += size;
+        }
+
+        let extra_arg = if add_ret_area_ptr {
+            debug_assert!(args_or_rets == ArgsOrRets::Args);
+            if next_xreg < max_per_class_reg_vals && remaining_reg_vals > 0 {
+                ret.push(ABIArg::reg(
+                    xreg(FIRST_ARG_REG + next_xreg).to_real_reg(),
+                    I64,
+                    ir::ArgumentExtension::None,
+                    ir::ArgumentPurpose::Normal,
+                ));
+            } else {
+                ret.push(ABIArg::stack(
+                    next_stack as i64,
+                    I64,
+                    ir::ArgumentExtension::None,
+                    ir::ArgumentPurpose::Normal,
+                ));
+                next_stack += 8;
+            }
+            Some(ret.len() - 1)
+        } else {
+            None
+        };
+
+        next_stack = align_to(next_stack, 16);
+
+        // To avoid overflow issues, limit the arg/return size to something
+        // reasonable -- here, 128 MB.
+        if next_stack > STACK_ARG_RET_SIZE_LIMIT {
+            return Err(CodegenError::ImplLimitExceeded);
+        }
+
+        Ok((ret, next_stack as i64, extra_arg))
+    }
+
+    fn fp_to_arg_offset(_call_conv: isa::CallConv, _flags: &settings::Flags) -> i64 {
+        16 // frame pointer + return address.
+    }
+
+    fn gen_load_stack(mem: StackAMode, into_reg: Writable<Reg>, ty: Type) -> Inst {
+        Inst::gen_load(into_reg, mem.into(), ty, MemFlags::trusted())
+    }
+
+    fn gen_store_stack(mem: StackAMode, from_reg: Reg, ty: Type) -> Inst {
+        Inst::gen_store(mem.into(), from_reg, ty, MemFlags::trusted())
+    }
+
+    fn gen_move(to_reg: Writable<Reg>, from_reg: Reg, ty: Type) -> Inst {
+        Inst::gen_move(to_reg, from_reg, ty)
+    }
+
+    fn gen_extend(
+        to_reg: Writable<Reg>,
+        from_reg: Reg,
+        signed: bool,
+        from_bits: u8,
+        to_bits: u8,
+    ) -> Inst {
+        assert!(from_bits < to_bits);
+        Inst::Extend {
+            rd: to_reg,
+            rn: from_reg,
+            signed,
+            from_bits,
+            to_bits,
+        }
+    }
+
+    fn gen_ret() -> Inst {
+        Inst::Ret
+    }
+
+    fn gen_add_imm(into_reg: Writable<Reg>, from_reg: Reg, imm: u32) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+        if let Some(imm12) = Imm12::maybe_from_i64(imm.into()) {
+            insts.push(Inst::addi(into_reg, from_reg, imm12));
+        } else {
+            let scratch2 = writable_tmp2_reg();
+            assert_ne!(scratch2.to_reg(), from_reg);
+            insts.extend(Inst::load_constant(scratch2, imm.into()));
+            insts.push(Inst::AluRRR {
+                alu_op: AluOPRRR::Add,
+                rd: into_reg,
+                rs1: from_reg,
+                rs2: scratch2.to_reg(),
+            });
+        }
+        insts
+    }
+
+    fn gen_stack_lower_bound_trap(limit_reg: Reg) -> SmallInstVec<Inst> {
+        smallvec![Inst::TrapIf {
+            cc: IntegerCompare {
+                kind: IntCC::UnsignedLessThan,
+                rs1: stack_reg(),
+                rs2: limit_reg,
+            },
+            trap_code: ir::TrapCode::StackOverflow,
+        }]
+    }
+
+    fn gen_epilogue_placeholder() -> Inst {
+        Inst::EpiloguePlaceholder
+    }
+
+    fn gen_get_stack_addr(mem: StackAMode, into_reg: Writable<Reg>, _ty: Type) -> Inst {
+        let mem = mem.into();
+        Inst::LoadAddr { rd: into_reg, mem }
+    }
+
+    fn get_stacklimit_reg() -> Reg {
+        spilltmp_reg()
+    }
+
+    fn gen_load_base_offset(into_reg: Writable<Reg>, base: Reg, offset: i32, ty: Type) -> Inst {
+        let mem = AMode::RegOffset(base, offset as i64);
+        Inst::gen_load(into_reg, mem, ty, MemFlags::trusted())
+    }
+
+    fn gen_store_base_offset(base: Reg, offset: i32, from_reg: Reg, ty: Type) -> Inst {
+        let mem = AMode::RegOffset(base, offset as i64);
+        Inst::gen_store(mem, from_reg, ty, MemFlags::trusted())
+    }
+
+    fn gen_sp_reg_adjust(amount: i32) -> SmallInstVec<Inst> {
+        if amount == 0 {
+            return SmallVec::new();
+        }
+
+        let mut ret = SmallVec::new();
+        if let Some(imm12) = Imm12::maybe_from_i64(amount.into()) {
+            ret.push(Inst::addi(writable_stack_reg(), stack_reg(), imm12));
+        } else {
+            let tmp = writable_spilltmp_reg();
+            ret.extend(Inst::load_constant(tmp, i64::from(amount) as u64));
+            ret.push(Inst::AluRRR {
+                alu_op: AluOPRRR::Add,
+                rd: writable_stack_reg(),
+                rs1: stack_reg(),
+                rs2: tmp.to_reg(),
+            });
+        }
+        ret
+    }
+
+    fn gen_nominal_sp_adj(offset: i32) -> Inst {
+        Inst::VirtualSPOffsetAdj {
+            offset: offset as i64,
+        }
+    }
+
+    fn gen_prologue_frame_setup(flags: &settings::Flags) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+
+        // addi sp, sp, -16 ; sd ra, 8(sp) ; sd fp, 0(sp)
+        insts.extend(Self::gen_sp_reg_adjust(-16));
+        insts.push(Inst::gen_store(
+            AMode::SPOffset(8),
+            link_reg(),
+            I64,
+            MemFlags::trusted(),
+        ));
+        insts.push(Inst::gen_store(
+            AMode::SPOffset(0),
+            fp_reg(),
+            I64,
+            MemFlags::trusted(),
+        ));
+
+        if flags.unwind_info() {
+            insts.push(Inst::Unwind {
+                inst: UnwindInst::PushFrameRegs {
+                    offset_upward_to_caller_sp: 16, // FP, RA
+                },
+            });
+        }
+
+        // mv fp, sp
+        insts.push(Inst::gen_move(writable_fp_reg(), stack_reg(), I64));
+        insts
+    }
+
+    fn gen_epilogue_frame_restore(_: &settings::Flags) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+
+        // N.B.: sp is already adjusted to the appropriate place by the
+        // clobber-restore code (which also frees the fixed frame). Hence, there
+        // is no need for the usual `mv sp, fp` here.
+
+        // ld ra, 8(sp) ; ld fp, 0(sp) ; addi sp, sp, 16
+        insts.push(Inst::gen_load(
+            writable_link_reg(),
+            AMode::SPOffset(8),
+            I64,
+            MemFlags::trusted(),
+        ));
+        insts.push(Inst::gen_load(
+            writable_fp_reg(),
+            AMode::SPOffset(0),
+            I64,
+            MemFlags::trusted(),
+        ));
+        insts.extend(Self::gen_sp_reg_adjust(16));
+        insts
+    }
+
+    fn gen_grow_incoming_args(size: u32) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+
+        // Copy the frame record `size` bytes down through t0 and point fp at
+        // the copy.
+        let tmp = writable_spilltmp_reg();
+        insts.extend(Inst::gen_set_sp(stack_reg(), -(size as i64)));
+        for &offset in &[0, 8] {
+            insts.push(Inst::gen_load(
+                tmp,
+                AMode::RegOffset(fp_reg(), offset),
+                I64,
+                MemFlags::trusted(),
+            ));
+            insts.push(Inst::gen_store(
+                AMode::SPOffset(offset),
+                tmp.to_reg(),
+                I64,
+                MemFlags::trusted(),
+            ));
+        }
+        insts.push(Inst::gen_move(writable_fp_reg(), stack_reg(), I64));
+        insts
+    }
+
+    fn gen_probestack(_: u32) -> SmallInstVec<Self::I> {
+        // TODO: implement if we ever require stack probes on riscv64 (e.g. on
+        // Windows-like platforms).
+        smallvec![]
+    }
+
+    fn gen_clobber_save(
+        _call_conv: isa::CallConv,
+        setup_frame: bool,
+        flags: &settings::Flags,
+        clobbered_callee_saves: &Vec<Writable<RealReg>>,
+        fixed_frame_storage_size: u32,
+        _outgoing_args_size: u32,
+    ) -> (u64, SmallVec<[Inst; 16]>) {
+        let clobber_size = clobber_save_size(clobbered_callee_saves);
+        let mut insts = SmallVec::new();
+
+        if flags.unwind_info() && setup_frame {
+            // The *unwind* frame (but not the actual frame) starts at the
+            // clobbers, just below the saved FP/RA pair.
+            insts.push(Inst::Unwind {
+                inst: UnwindInst::DefineNewFrame {
+                    offset_downward_to_clobbers: clobber_size,
+                    offset_upward_to_caller_sp: 16, // FP, RA
+                },
+            });
+        }
+
+        // Allocate the whole save area at once, and store the registers from
+        // its top down.
+        insts.extend(Self::gen_sp_reg_adjust(-(clobber_size as i32)));
+        for (i, reg) in clobbered_callee_saves.iter().enumerate() {
+            let reg = reg.to_reg();
+            let ty = match reg.get_class() {
+                RegClass::I64 => I64,
+                _ => F64,
+            };
+            let clobber_offset = clobber_size - 8 * (i as u32 + 1);
+            insts.push(Inst::gen_store(
+                AMode::SPOffset(clobber_offset.into()),
+                reg.to_reg(),
+                ty,
+                MemFlags::trusted(),
+            ));
+            if flags.unwind_info() {
+                insts.push(Inst::Unwind {
+                    inst: UnwindInst::SaveReg {
+                        clobber_offset,
+                        reg,
+                    },
+                });
+            }
+        }
+
+        if fixed_frame_storage_size > 0 {
+            insts.extend(Self::gen_sp_reg_adjust(-(fixed_frame_storage_size as i32)));
+        }
+
+        (clobber_size.into(), insts)
+    }
+
+    fn gen_clobber_restore(
+        call_conv: isa::CallConv,
+        _flags: &settings::Flags,
+        clobbers: &Set<Writable<RealReg>>,
+        fixed_frame_storage_size: u32,
+        _outgoing_args_size: u32,
+    ) -> SmallVec<[Inst; 16]> {
+        let mut insts = SmallVec::new();
+        let clobbered_callee_saves = Self::get_clobbered_callee_saves(call_conv, clobbers);
+        let clobber_size = clobber_save_size(&clobbered_callee_saves);
+
+        if fixed_frame_storage_size > 0 {
+            insts.extend(Self::gen_sp_reg_adjust(fixed_frame_storage_size as i32));
+        }
+
+        for (i, reg) in clobbered_callee_saves.iter().enumerate() {
+            let reg = reg.map(|r| r.to_reg());
+            let ty = match reg.to_reg().get_class() {
+                RegClass::I64 => I64,
+                _ => F64,
+            };
+            let clobber_offset = clobber_size - 8 * (i as u32 + 1);
+            insts.push(Inst::gen_load(
+                reg,
+                AMode::SPOffset(clobber_offset.into()),
+                ty,
+                MemFlags::trusted(),
+            ));
+        }
+        insts.extend(Self::gen_sp_reg_adjust(clobber_size as i32));
+
+        insts
+    }
+
+    fn gen_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        defs: Vec<Writable<Reg>>,
+        opcode: ir::Opcode,
+        tmp: Writable<Reg>,
+        callee_conv: isa::CallConv,
+        caller_conv: isa::CallConv,
+    ) -> SmallVec<[(InstIsSafepoint, Inst); 2]> {
+        let mut insts = SmallVec::new();
+        match &dest {
+            &CallDest::ExtName(ref name, RelocDistance::Near) => insts.push((
+                InstIsSafepoint::Yes,
+                Inst::Call {
+                    info: Box::new(CallInfo {
+                        dest: name.clone(),
+                        uses,
+                        defs,
+                        opcode,
+                        caller_callconv: caller_conv,
+                        callee_callconv: callee_conv,
+                    }),
+                },
+            )),
+            &CallDest::ExtName(ref name, RelocDistance::Far) => {
+                insts.push((
+                    InstIsSafepoint::No,
+                    Inst::LoadExtName {
+                        rd: tmp,
+                        name: Box::new(name.clone()),
+                        offset: 0,
+                    },
+                ));
+                insts.push((
+                    InstIsSafepoint::Yes,
+                    Inst::CallInd {
+                        info: Box::new(CallIndInfo {
+                            rn: tmp.to_reg(),
+                            uses,
+                            defs,
+                            opcode,
+                            caller_callconv: caller_conv,
+                            callee_callconv: callee_conv,
+                        }),
+                    },
+                ));
+            }
+            &CallDest::Reg(reg) => insts.push((
+                InstIsSafepoint::Yes,
+                Inst::CallInd {
+                    info: Box::new(CallIndInfo {
+                        rn: *reg,
+                        uses,
+                        defs,
+                        opcode,
+                        caller_callconv: caller_conv,
+                        callee_callconv: callee_conv,
+                    }),
+                },
+            )),
+        }
+
+        insts
+    }
+
+    fn gen_return_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        _callee_conv: isa::CallConv,
+        sp_adjust: u32,
+    ) -> SmallInstVec<Inst> {
+        // The epilogue is emitted between these instructions and the jump,
+        // so the target goes in `t1`, which the epilogue leaves alone, rather
+        // than in an allocatable register which it might restore.
+        let tmp = writable_tmp2_reg();
+        let uses = uses.into_boxed_slice();
+        let mut insts = SmallVec::new();
+        match &dest {
+            &CallDest::ExtName(ref name, RelocDistance::Near) => {
+                insts.push(Inst::ReturnCall {
+                    dest: Box::new(name.clone()),
+                    uses,
+                    sp_adjust,
+                });
+            }
+            &CallDest::ExtName(ref name, RelocDistance::Far) => {
+                insts.push(Inst::LoadExtName {
+                    rd: tmp,
+                    name: Box::new(name.clone()),
+                    offset: 0,
+                });
+                insts.push(Inst::ReturnCallInd {
+                    rn: tmp.to_reg(),
+                    uses,
+                    sp_adjust,
+                });
+            }
+            &CallDest::Reg(reg) => {
+                insts.push(Inst::gen_move(tmp, *reg, I64));
+                insts.push(Inst::ReturnCallInd {
+                    rn: tmp.to_reg(),
+                    uses,
+                    sp_adjust,
+                });
+            }
+        }
+        insts
+    }
+
+    fn gen_memcpy(
+        call_conv: isa::CallConv,
+        dst: Reg,
+        src: Reg,
+        size: usize,
+    ) -> SmallVec<[Self::I; 8]> {
+        let mut insts = SmallVec::new();
+        let arg0 = writable_xreg(FIRST_ARG_REG);
+        let arg1 = writable_xreg(FIRST_ARG_REG + 1);
+        let arg2 = writable_xreg(FIRST_ARG_REG + 2);
+        insts.push(Inst::gen_move(arg0, dst, I64));
+        insts.push(Inst::gen_move(arg1, src, I64));
+        insts.extend(Inst::load_constant(arg2, size as u64).into_iter());
+        insts.push(Inst::Call {
+            info: Box::new(CallInfo {
+                dest: ExternalName::LibCall(LibCall::Memcpy),
+                uses: vec![arg0.to_reg(), arg1.to_reg(), arg2.to_reg()],
+                defs: Self::get_regs_clobbered_by_call(call_conv),
+                opcode: Opcode::Call,
+                caller_callconv: call_conv,
+                callee_callconv: call_conv,
+            }),
+        });
+        insts
+    }
+
+    fn get_number_of_spillslots_for_value(rc: RegClass, _ty: Type) -> u32 {
+        // We allocate in terms of 8-byte slots.
+        match rc {
+            RegClass::I64 | RegClass::F64 => 1,
+            _ => panic!("Unexpected register class!"),
+        }
+    }
+
+    /// Get the current virtual-SP offset from an instruction-emission state.
+    fn get_virtual_sp_offset_from_state(s: &EmitState) -> i64 {
+        s.virtual_sp_offset
+    }
+
+    /// Get the nominal-SP-to-FP offset from an instruction-emission state.
+    fn get_nominal_sp_to_fp(s: &EmitState) -> i64 {
+        s.nominal_sp_to_fp
+    }
+
+    fn get_regs_clobbered_by_call(_call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>> {
+        let mut caller_saved = Vec::new();
+        for i in 0..32 {
+            let x = writable_xreg(i);
+            if is_reg_clobbered_by_call(x.to_reg().to_real_reg()) {
+                caller_saved.push(x);
+            }
+        }
+        for i in 0..32 {
+            let f = writable_freg(i);
+            if is_reg_clobbered_by_call(f.to_reg().to_real_reg()) {
+                caller_saved.push(f);
+            }
+        }
+        caller_saved
+    }
+
+    fn get_regs_clobbered_by_unwind(_call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>> {
+        let mut callee_saved = Vec::new();
+        for i in 0..32 {
+            let x = writable_xreg(i);
+            if is_reg_saved_in_prologue(x.to_reg().to_real_reg()) {
+                callee_saved.push(x);
+            }
+        }
+        for i in 0..32 {
+            let f = writable_freg(i);
+            if is_reg_saved_in_prologue(f.to_reg().to_real_reg()) {
+                callee_saved.push(f);
+            }
+        }
+        callee_saved
+    }
+
+    fn get_ext_mode(
+        _call_conv: isa::CallConv,
+        _specified: ir::ArgumentExtension,
+    ) -> ir::ArgumentExtension {
+        // Narrow integers are always kept sign-extended; see the module
+        // documentation.
+        ir::ArgumentExtension::None
+    }
+
+    fn get_clobbered_callee_saves(
+        _call_conv: isa::CallConv,
+        regs: &Set<Writable<RealReg>>,
+    ) -> Vec<Writable<RealReg>> {
+        let mut regs: Vec<Writable<RealReg>> = regs
+            .iter()
+            .cloned()
+            .filter(|r| is_reg_saved_in_prologue(r.to_reg()))
+            .collect();
+
+        // Sort registers for deterministic code output. We can do an unstable
+        // sort because the registers will be unique (there are no dups).
+        regs.sort_unstable_by_key(|r| r.to_reg().get_index());
+        regs
+    }
+
+    fn is_frame_setup_needed(
+        is_leaf: bool,
+        stack_args_size: u32,
+        num_clobbered_callee_saves: usize,
+        fixed_frame_storage_size: u32,
+    ) -> bool {
+        !is_leaf
+            // The function arguments that are passed on the stack are addressed
+            // relative to the Frame Pointer.
+            || stack_args_size > 0
+            || num_clobbered_callee_saves > 0
+            || fixed_frame_storage_size > 0
+    }
+}
+
+/// Is this type supposed to be seen on this machine? E.g. references of the
+/// wrong width are invalid.
+fn legal_type_for_machine(ty: Type) -> bool {
+    match ty {
+        R32 => false,
+        _ => true,
+    }
+}
+
+/// The size of the save area for `regs`, keeping the stack 16-byte aligned.
+fn clobber_save_size(regs: &[Writable<RealReg>]) -> u32 {
+    align_to(8 * regs.len() as u32, 16)
+}
+
+/// Is the given register saved in the prologue if clobbered, i.e., is it a
+/// callee-save?
+fn is_reg_saved_in_prologue(r: RealReg) -> bool {
+    let enc = r.get_hw_encoding();
+    match r.get_class() {
+        // s1 and s2 - s11. s0 is the frame pointer, which the prologue saves
+        // separately.
+        RegClass::I64 => enc == 9 || (18..=27).contains(&enc),
+        // fs0 - fs1 and fs2 - fs11.
+        RegClass::F64 => enc == 8 || enc == 9 || (18..=27).contains(&enc),
+        _ => panic!("Unexpected RegClass"),
+    }
+}
+
+/// Does a call with the given defs clobber registers that a function has to
+/// save in its prologue?
+pub(crate) fn clobbers_callee_saves(defs: &[Writable<Reg>]) -> bool {
+    defs.iter()
+        .any(|r| is_reg_saved_in_prologue(r.to_reg().to_real_reg()))
+}
+
+fn is_reg_clobbered_by_call(r: RealReg) -> bool {
+    let enc = r.get_hw_encoding();
+    match r.get_class() {
+        // t2 (t0 and t1 aren't allocatable), a0 - a7 and t3 - t6.
+        RegClass::I64 => enc == 7 || (10..=17).contains(&enc) || enc >= 28,
+        // ft0 - ft7, fa0 - fa7 and ft8 - ft11.
+        RegClass::F64 => enc <= 7 || (10..=17).contains(&enc) || enc >= 28,
+        _ => panic!("Unexpected RegClass"),
+    }
+}
@@ -0,0 +1,203 @@
+//! RISC-V 64 ISA definitions: instruction arguments.
+
+// Some variants are never constructed, but we still want them as options in the future.
+#![allow(dead_code)]
+
+use crate::ir::condcodes::{CondCode, IntCC};
+use crate::isa::riscv64::inst::*;
+use crate::machinst::MachLabel;
+
+use regalloc::{PrettyPrint, RealRegUniverse, Reg};
+
+use std::string::String;
+
+//=============================================================================
+// Instruction sub-components (memory addresses): definitions
+
+/// A memory argument to load/store, encapsulating the possible addressing modes.
+///
+/// RISC-V has a single real addressing mode, a base register plus a signed
+/// 12-bit offset; every other mode is converted into it at emission time by
+/// `mem_finalize()`, possibly with helper instructions computing the address
+/// into the spilltmp register.
+#[derive(Clone, Debug)]
+pub enum AMode {
+    /// Arbitrary offset from a register. Offsets outside the signed 12-bit
+    /// range are materialized with extra instructions during code emission.
+    RegOffset(Reg, i64),
+
+    /// Offset from the stack pointer.
+    SPOffset(i64),
+
+    /// Offset from the frame pointer.
+    FPOffset(i64),
+
+    /// Offset from the "nominal stack pointer", which is where the real SP is
+    /// just after stack and spill slots are allocated in the function prologue.
+    /// At emission time, this is converted to `SPOffset` with a fixup added to
+    /// the offset constant. The fixup is a running value that is tracked as
+    /// emission iterates through instructions in linear order, and can be
+    /// adjusted up and down with [Inst::VirtualSPOffsetAdj].
+    ///
+    /// The standard ABI is in charge of handling this (by emitting the
+    /// adjustment meta-instructions). It maintains the invariant that "nominal
+    /// SP" is where the actual SP is after the function prologue and before
+    /// clobber pushes. See the diagram in the documentation for
+    /// [crate::isa::riscv64::abi](the ABI module) for more details.
+    NominalSPOffset(i64),
+}
+
+impl AMode {
+    /// Memory reference using an address in a register.
+    pub fn reg(reg: Reg) -> AMode {
+        AMode::RegOffset(reg, 0)
+    }
+
+    /// Memory reference using the sum of a register and an offset as address.
+    pub fn reg_plus_off(reg: Reg, off: i64) -> AMode {
+        AMode::RegOffset(reg, off)
+    }
+}
+
+//=============================================================================
+// Instruction sub-components (conditions, branches and branch targets):
+// definitions
+
+/// A comparison of two integer registers, as performed by the conditional
+/// branch instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntegerCompare {
+    pub kind: IntCC,
+    pub rs1: Reg,
+    pub rs2: Reg,
+}
+
+impl IntegerCompare {
+    /// Return the comparison which is true exactly when this one is false.
+    pub fn inverse(self) -> IntegerCompare {
+        IntegerCompare {
+            kind: self.kind.inverse(),
+            ..self
+        }
+    }
+
+    /// Return the `funct3` field of the branch instruction implementing this
+    /// comparison, and its two source registers. Only `<`, `>=` and equality
+    /// tests exist, so the others are implemented by swapping the operands.
+    pub fn branch_args(self) -> (u32, Reg, Reg) {
+        let (rs1, rs2) = (self.rs1, self.rs2);
+        match self.kind {
+            IntCC::Equal => (0b000, rs1, rs2),
+            IntCC::NotEqual => (0b001, rs1, rs2),
+            IntCC::SignedLessThan => (0b100, rs1, rs2),
+            IntCC::SignedGreaterThanOrEqual => (0b101, rs1, rs2),
+            IntCC::SignedGreaterThan => (0b100, rs2, rs1),
+            IntCC::SignedLessThanOrEqual => (0b101, rs2, rs1),
+            IntCC::UnsignedLessThan => (0b110, rs1, rs2),
+            IntCC::UnsignedGreaterThanOrEqual => (0b111, rs1, rs2),
+            IntCC::UnsignedGreaterThan => (0b110, rs2, rs1),
+            IntCC::UnsignedLessThanOrEqual => (0b111, rs2, rs1),
+            IntCC::Overflow | IntCC::NotOverflow => {
+                panic!("overflow conditions can't be tested by a branch")
+            }
+        }
+    }
+}
+
+/// A branch target. Either unresolved (basic-block index) or resolved (offset
+/// from start of current instruction).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BranchTarget {
+    /// An unresolved reference to a Label, as passed into
+    /// `lower_branch_group()`.
+    Label(MachLabel),
+    /// A fixed PC offset.
+    ResolvedOffset(i32),
+}
+
+impl BranchTarget {
+    /// Return the target's label, if it is a label-based target.
+    pub fn as_label(self) -> Option<MachLabel> {
+        match self {
+            BranchTarget::Label(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Return the target's offset, if specified, or zero if label-based.
+    pub fn as_offset_or_zero(self) -> i32 {
+        match self {
+            BranchTarget::ResolvedOffset(off) => off,
+            _ => 0,
+        }
+    }
+}
+
+/// A floating-point rounding mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FRM {
+    /// Round to nearest, ties to even.
+    RNE,
+    /// Round towards zero.
+    RTZ,
+    /// Round down, towards negative infinity.
+    RDN,
+    /// Round up, towards positive infinity.
+    RUP,
+    /// Round to nearest, ties to max magnitude.
+    RMM,
+    /// The dynamic rounding mode, from the `frm` CSR.
+    Dyn,
+}
+
+impl FRM {
+    /// Bits for the `rm` field of the encoding.
+    pub fn bits(self) -> u32 {
+        match self {
+            FRM::RNE => 0b000,
+            FRM::RTZ => 0b001,
+            FRM::RDN => 0b010,
+            FRM::RUP => 0b011,
+            FRM::RMM => 0b100,
+            FRM::Dyn => 0b111,
+        }
+    }
+}
+
+//=============================================================================
+// Instruction sub-components: pretty-printing
+
+impl PrettyPrint for AMode {
+    fn show_rru(&self, mb_rru: Option<&RealRegUniverse>) -> String {
+        match self {
+            &AMode::RegOffset(reg, off) => format!("{}({})", off, reg.show_rru(mb_rru)),
+            // Eliminated by `mem_finalize()`.
+            &AMode::SPOffset(..) | &AMode::FPOffset(..) | &AMode::NominalSPOffset(..) => {
+                panic!("Unexpected stack-offset mem-arg mode!")
+            }
+        }
+    }
+}
+
+impl PrettyPrint for BranchTarget {
+    fn show_rru(&self, _mb_rru: Option<&RealRegUniverse>) -> String {
+        match self {
+            &BranchTarget::Label(label) => format!("label{:?}", label.get()),
+            &BranchTarget::ResolvedOffset(off) => format!("{}", off),
+        }
+    }
+}
+
+impl PrettyPrint for FRM {
+    fn show_rru(&self, _mb_rru: Option<&RealRegUniverse>) -> String {
+        match self {
+            FRM::RNE => "rne",
+            FRM::RTZ => "rtz",
+            FRM::RDN => "rdn",
+            FRM::RUP => "rup",
+            FRM::RMM => "rmm",
+            FRM::Dyn => "dyn",
+        }
+        .to_string()
+    }
+}
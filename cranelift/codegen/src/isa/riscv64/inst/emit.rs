@@ -0,0 +1,1321 @@
+//! RISC-V 64 ISA: binary code emission.
+
+use crate::binemit::{Reloc, StackMap};
+use crate::ir::{MemFlags, SourceLoc, TrapCode};
+use crate::isa::riscv64::inst::*;
+use crate::isa::riscv64::settings as riscv64_settings;
+use core::convert::TryFrom;
+use regalloc::{Reg, RegClass};
+
+/// Memory addressing mode finalization: convert "special" modes (e.g.,
+/// generic arbitrary stack offset) into a base register plus a 12-bit offset,
+/// possibly with instructions computing the base into the spilltmp register
+/// (or tmp2, if spilltmp is the original base).
+pub fn mem_finalize(mem: &AMode, state: &EmitState) -> (SmallVec<[Inst; 4]>, AMode) {
+    let (base, off) = match mem {
+        &AMode::RegOffset(base, off) => (base, off),
+        &AMode::SPOffset(off) => (stack_reg(), off),
+        &AMode::FPOffset(off) => (fp_reg(), off),
+        &AMode::NominalSPOffset(off) => (stack_reg(), off + state.virtual_sp_offset),
+    };
+    if Imm12::maybe_from_i64(off).is_some() {
+        return (smallvec![], AMode::RegOffset(base, off));
+    }
+    let tmp = if base == spilltmp_reg() {
+        writable_tmp2_reg()
+    } else {
+        writable_spilltmp_reg()
+    };
+    let mut insts = Inst::load_constant(tmp, off as u64);
+    insts.push(Inst::AluRRR {
+        alu_op: AluOPRRR::Add,
+        rd: tmp,
+        rs1: tmp.to_reg(),
+        rs2: base,
+    });
+    (insts, AMode::RegOffset(tmp.to_reg(), 0))
+}
+
+//=============================================================================
+// Instructions and subcomponents: emission
+
+fn machreg_to_gpr(m: Reg) -> u32 {
+    assert_eq!(m.get_class(), RegClass::I64);
+    u32::try_from(m.to_real_reg().get_hw_encoding()).unwrap()
+}
+
+fn machreg_to_fpr(m: Reg) -> u32 {
+    assert_eq!(m.get_class(), RegClass::F64);
+    u32::try_from(m.to_real_reg().get_hw_encoding()).unwrap()
+}
+
+fn machreg_to_gpr_or_fpr(m: Reg) -> u32 {
+    u32::try_from(m.to_real_reg().get_hw_encoding()).unwrap()
+}
+
+/// R-type instructions.
+///
+///   31     25 24  20 19  15 14    12 11 7 6      0
+///   | funct7 | rs2  | rs1  | funct3 | rd  | opcode |
+fn enc_r(opcode: u32, rd: u32, funct3: u32, rs1: u32, rs2: u32, funct7: u32) -> u32 {
+    opcode | rd << 7 | funct3 << 12 | rs1 << 15 | rs2 << 20 | funct7 << 25
+}
+
+/// I-type instructions, with a 12-bit immediate.
+///
+///   31        20 19  15 14    12 11 7 6      0
+///   | imm[11:0] | rs1  | funct3 | rd  | opcode |
+fn enc_i(opcode: u32, rd: u32, funct3: u32, rs1: u32, imm12: u32) -> u32 {
+    debug_assert!(imm12 < (1 << 12));
+    opcode | rd << 7 | funct3 << 12 | rs1 << 15 | imm12 << 20
+}
+
+/// S-type instructions (stores).
+///
+///   31        25 24  20 19  15 14    12 11       7 6      0
+///   | imm[11:5] | rs2  | rs1  | funct3 | imm[4:0] | opcode |
+fn enc_s(opcode: u32, funct3: u32, rs1: u32, rs2: u32, imm12: u32) -> u32 {
+    debug_assert!(imm12 < (1 << 12));
+    opcode | (imm12 & 0x1f) << 7 | funct3 << 12 | rs1 << 15 | rs2 << 20 | (imm12 >> 5) << 25
+}
+
+/// The bits of a B-type (conditional branch) instruction holding `off`.
+pub(crate) fn enc_b_offset(off: u32) -> u32 {
+    ((off >> 12) & 1) << 31
+        | ((off >> 5) & 0x3f) << 25
+        | ((off >> 1) & 0xf) << 8
+        | ((off >> 11) & 1) << 7
+}
+
+/// The bits of a J-type (`jal`) instruction holding `off`.
+pub(crate) fn enc_jal_offset(off: u32) -> u32 {
+    ((off >> 20) & 1) << 31
+        | ((off >> 1) & 0x3ff) << 21
+        | ((off >> 11) & 1) << 20
+        | ((off >> 12) & 0xff) << 12
+}
+
+/// Split a PC-relative offset between an `auipc` and a following instruction
+/// with a signed 12-bit immediate, returning their immediate fields.
+pub(crate) fn split_pcrel(off: i32) -> (u32, u32) {
+    let hi20 = (off.wrapping_add(0x800) as u32) >> 12;
+    let lo12 = (off as u32) & 0xfff;
+    (hi20, lo12)
+}
+
+/// A conditional branch to `off`, given the `funct3` field selecting the
+/// comparison.
+fn enc_branch(funct3: u32, rs1: Reg, rs2: Reg, off: i32) -> u32 {
+    0b1100011
+        | funct3 << 12
+        | machreg_to_gpr(rs1) << 15
+        | machreg_to_gpr(rs2) << 20
+        | enc_b_offset(off as u32)
+}
+
+fn enc_cond_branch(cmp: IntegerCompare, off: i32) -> u32 {
+    let (funct3, rs1, rs2) = cmp.branch_args();
+    enc_branch(funct3, rs1, rs2, off)
+}
+
+pub(crate) fn enc_jal(rd: Writable<Reg>, off: i32) -> u32 {
+    0b1101111 | machreg_to_gpr(rd.to_reg()) << 7 | enc_jal_offset(off as u32)
+}
+
+pub(crate) fn enc_jalr(rd: Writable<Reg>, rs1: Reg, off: i32) -> u32 {
+    let imm12 = Imm12::maybe_from_i64(i64::from(off)).unwrap();
+    enc_i(
+        0b1100111,
+        machreg_to_gpr(rd.to_reg()),
+        0b000,
+        machreg_to_gpr(rs1),
+        imm12.bits(),
+    )
+}
+
+pub(crate) fn enc_auipc(rd: Writable<Reg>, imm20: u32) -> u32 {
+    0b0010111 | machreg_to_gpr(rd.to_reg()) << 7 | imm20 << 12
+}
+
+/// The `unimp` instruction (`csrrw zero, cycle, zero`), which always raises an
+/// illegal-instruction exception.
+const UNIMP: u32 = 0xc0001073;
+
+/// `csrrw zero, fflags, zero`: clear the accrued floating-point exceptions.
+const CLEAR_FFLAGS: u32 = 0x00101073;
+
+/// `csrrs rd, fflags, zero`: read the accrued floating-point exceptions.
+fn enc_read_fflags(rd: Writable<Reg>) -> u32 {
+    0x00102073 | machreg_to_gpr(rd.to_reg()) << 7
+}
+
+/// The invalid-operation flag of `fflags`.
+const FFLAGS_NV: i64 = 0x10;
+
+impl AluOPRRR {
+    /// The opcode, funct3 and funct7 fields.
+    fn encoding(self) -> (u32, u32, u32) {
+        match self {
+            AluOPRRR::Add => (0b0110011, 0b000, 0b0000000),
+            AluOPRRR::Sub => (0b0110011, 0b000, 0b0100000),
+            AluOPRRR::Sll => (0b0110011, 0b001, 0b0000000),
+            AluOPRRR::Slt => (0b0110011, 0b010, 0b0000000),
+            AluOPRRR::Sltu => (0b0110011, 0b011, 0b0000000),
+            AluOPRRR::Xor => (0b0110011, 0b100, 0b0000000),
+            AluOPRRR::Srl => (0b0110011, 0b101, 0b0000000),
+            AluOPRRR::Sra => (0b0110011, 0b101, 0b0100000),
+            AluOPRRR::Or => (0b0110011, 0b110, 0b0000000),
+            AluOPRRR::And => (0b0110011, 0b111, 0b0000000),
+            AluOPRRR::Addw => (0b0111011, 0b000, 0b0000000),
+            AluOPRRR::Subw => (0b0111011, 0b000, 0b0100000),
+            AluOPRRR::Sllw => (0b0111011, 0b001, 0b0000000),
+            AluOPRRR::Srlw => (0b0111011, 0b101, 0b0000000),
+            AluOPRRR::Sraw => (0b0111011, 0b101, 0b0100000),
+            AluOPRRR::Mul => (0b0110011, 0b000, 0b0000001),
+            AluOPRRR::Mulh => (0b0110011, 0b001, 0b0000001),
+            AluOPRRR::Mulhsu => (0b0110011, 0b010, 0b0000001),
+            AluOPRRR::Mulhu => (0b0110011, 0b011, 0b0000001),
+            AluOPRRR::Div => (0b0110011, 0b100, 0b0000001),
+            AluOPRRR::Divu => (0b0110011, 0b101, 0b0000001),
+            AluOPRRR::Rem => (0b0110011, 0b110, 0b0000001),
+            AluOPRRR::Remu => (0b0110011, 0b111, 0b0000001),
+            AluOPRRR::Mulw => (0b0111011, 0b000, 0b0000001),
+            AluOPRRR::Divw => (0b0111011, 0b100, 0b0000001),
+            AluOPRRR::Divuw => (0b0111011, 0b101, 0b0000001),
+            AluOPRRR::Remw => (0b0111011, 0b110, 0b0000001),
+            AluOPRRR::Remuw => (0b0111011, 0b111, 0b0000001),
+            AluOPRRR::Andn => (0b0110011, 0b111, 0b0100000),
+            AluOPRRR::Orn => (0b0110011, 0b110, 0b0100000),
+            AluOPRRR::Xnor => (0b0110011, 0b100, 0b0100000),
+            AluOPRRR::Rol => (0b0110011, 0b001, 0b0110000),
+            AluOPRRR::Ror => (0b0110011, 0b101, 0b0110000),
+            AluOPRRR::Rolw => (0b0111011, 0b001, 0b0110000),
+            AluOPRRR::Rorw => (0b0111011, 0b101, 0b0110000),
+        }
+    }
+}
+
+impl AluOPRRI {
+    /// The opcode and funct3 fields, and the immediate field for `imm`: the
+    /// shifts only take a shift amount, with some bits of funct7 above it.
+    fn encoding(self, imm: Imm12) -> (u32, u32, u32) {
+        let bits = imm.bits();
+        match self {
+            AluOPRRI::Addi => (0b0010011, 0b000, bits),
+            AluOPRRI::Slti => (0b0010011, 0b010, bits),
+            AluOPRRI::Sltiu => (0b0010011, 0b011, bits),
+            AluOPRRI::Xori => (0b0010011, 0b100, bits),
+            AluOPRRI::Ori => (0b0010011, 0b110, bits),
+            AluOPRRI::Andi => (0b0010011, 0b111, bits),
+            AluOPRRI::Slli => (0b0010011, 0b001, bits & 0x3f),
+            AluOPRRI::Srli => (0b0010011, 0b101, bits & 0x3f),
+            AluOPRRI::Srai => (0b0010011, 0b101, 0x400 | (bits & 0x3f)),
+            AluOPRRI::Addiw => (0b0011011, 0b000, bits),
+            AluOPRRI::Slliw => (0b0011011, 0b001, bits & 0x1f),
+            AluOPRRI::Srliw => (0b0011011, 0b101, bits & 0x1f),
+            AluOPRRI::Sraiw => (0b0011011, 0b101, 0x400 | (bits & 0x1f)),
+            AluOPRRI::Rori => (0b0010011, 0b101, 0x600 | (bits & 0x3f)),
+            AluOPRRI::Roriw => (0b0011011, 0b101, 0x600 | (bits & 0x1f)),
+        }
+    }
+}
+
+impl AluOPRR {
+    /// The opcode, funct3 and immediate fields: these are I-type instructions
+    /// with a fixed immediate.
+    fn encoding(self) -> (u32, u32, u32) {
+        match self {
+            AluOPRR::Clz => (0b0010011, 0b001, 0x600),
+            AluOPRR::Ctz => (0b0010011, 0b001, 0x601),
+            AluOPRR::Cpop => (0b0010011, 0b001, 0x602),
+            AluOPRR::Clzw => (0b0011011, 0b001, 0x600),
+            AluOPRR::Ctzw => (0b0011011, 0b001, 0x601),
+            AluOPRR::Cpopw => (0b0011011, 0b001, 0x602),
+            AluOPRR::SextB => (0b0010011, 0b001, 0x604),
+            AluOPRR::SextH => (0b0010011, 0b001, 0x605),
+            AluOPRR::ZextH => (0b0111011, 0b100, 0x080),
+        }
+    }
+}
+
+impl LoadOP {
+    /// The opcode and funct3 fields.
+    fn encoding(self) -> (u32, u32) {
+        match self {
+            LoadOP::Lb => (0b0000011, 0b000),
+            LoadOP::Lh => (0b0000011, 0b001),
+            LoadOP::Lw => (0b0000011, 0b010),
+            LoadOP::Ld => (0b0000011, 0b011),
+            LoadOP::Lbu => (0b0000011, 0b100),
+            LoadOP::Lhu => (0b0000011, 0b101),
+            LoadOP::Lwu => (0b0000011, 0b110),
+            LoadOP::Flw => (0b0000111, 0b010),
+            LoadOP::Fld => (0b0000111, 0b011),
+        }
+    }
+}
+
+impl StoreOP {
+    /// The opcode and funct3 fields.
+    fn encoding(self) -> (u32, u32) {
+        match self {
+            StoreOP::Sb => (0b0100011, 0b000),
+            StoreOP::Sh => (0b0100011, 0b001),
+            StoreOP::Sw => (0b0100011, 0b010),
+            StoreOP::Sd => (0b0100011, 0b011),
+            StoreOP::Fsw => (0b0100111, 0b010),
+            StoreOP::Fsd => (0b0100111, 0b011),
+        }
+    }
+}
+
+impl FpuOPRR {
+    /// The funct7 and rs2 fields, and whether the funct3 field is the rounding
+    /// mode (it's zero otherwise).
+    fn encoding(self) -> (u32, u32, bool) {
+        match self {
+            FpuOPRR::FsqrtS => (0b0101100, 0b00000, true),
+            FpuOPRR::FsqrtD => (0b0101101, 0b00000, true),
+            FpuOPRR::FcvtSD => (0b0100000, 0b00001, true),
+            FpuOPRR::FcvtDS => (0b0100001, 0b00000, true),
+            FpuOPRR::FmvXW => (0b1110000, 0b00000, false),
+            FpuOPRR::FmvXD => (0b1110001, 0b00000, false),
+            FpuOPRR::FmvWX => (0b1111000, 0b00000, false),
+            FpuOPRR::FmvDX => (0b1111001, 0b00000, false),
+            FpuOPRR::FcvtWS => (0b1100000, 0b00000, true),
+            FpuOPRR::FcvtWuS => (0b1100000, 0b00001, true),
+            FpuOPRR::FcvtLS => (0b1100000, 0b00010, true),
+            FpuOPRR::FcvtLuS => (0b1100000, 0b00011, true),
+            FpuOPRR::FcvtWD => (0b1100001, 0b00000, true),
+            FpuOPRR::FcvtWuD => (0b1100001, 0b00001, true),
+            FpuOPRR::FcvtLD => (0b1100001, 0b00010, true),
+            FpuOPRR::FcvtLuD => (0b1100001, 0b00011, true),
+            FpuOPRR::FcvtSW => (0b1101000, 0b00000, true),
+            FpuOPRR::FcvtSWu => (0b1101000, 0b00001, true),
+            FpuOPRR::FcvtSL => (0b1101000, 0b00010, true),
+            FpuOPRR::FcvtSLu => (0b1101000, 0b00011, true),
+            FpuOPRR::FcvtDW => (0b1101001, 0b00000, true),
+            FpuOPRR::FcvtDWu => (0b1101001, 0b00001, true),
+            FpuOPRR::FcvtDL => (0b1101001, 0b00010, true),
+            FpuOPRR::FcvtDLu => (0b1101001, 0b00011, true),
+        }
+    }
+}
+
+impl FpuOPRRR {
+    /// The funct7 field, and the funct3 field if it isn't the rounding mode.
+    fn encoding(self) -> (u32, Option<u32>) {
+        match self {
+            FpuOPRRR::FaddS => (0b0000000, None),
+            FpuOPRRR::FsubS => (0b0000100, None),
+            FpuOPRRR::FmulS => (0b0001000, None),
+            FpuOPRRR::FdivS => (0b0001100, None),
+            FpuOPRRR::FsgnjS => (0b0010000, Some(0b000)),
+            FpuOPRRR::FsgnjnS => (0b0010000, Some(0b001)),
+            FpuOPRRR::FsgnjxS => (0b0010000, Some(0b010)),
+            FpuOPRRR::FminS => (0b0010100, Some(0b000)),
+            FpuOPRRR::FmaxS => (0b0010100, Some(0b001)),
+            FpuOPRRR::FeqS => (0b1010000, Some(0b010)),
+            FpuOPRRR::FltS => (0b1010000, Some(0b001)),
+            FpuOPRRR::FleS => (0b1010000, Some(0b000)),
+            FpuOPRRR::FaddD => (0b0000001, None),
+            FpuOPRRR::FsubD => (0b0000101, None),
+            FpuOPRRR::FmulD => (0b0001001, None),
+            FpuOPRRR::FdivD => (0b0001101, None),
+            FpuOPRRR::FsgnjD => (0b0010001, Some(0b000)),
+            FpuOPRRR::FsgnjnD => (0b0010001, Some(0b001)),
+            FpuOPRRR::FsgnjxD => (0b0010001, Some(0b010)),
+            FpuOPRRR::FminD => (0b0010101, Some(0b000)),
+            FpuOPRRR::FmaxD => (0b0010101, Some(0b001)),
+            FpuOPRRR::FeqD => (0b1010001, Some(0b010)),
+            FpuOPRRR::FltD => (0b1010001, Some(0b001)),
+            FpuOPRRR::FleD => (0b1010001, Some(0b000)),
+        }
+    }
+}
+
+impl AtomicOP {
+    /// The funct5 field.
+    fn funct5(self) -> u32 {
+        match self {
+            AtomicOP::Add => 0b00000,
+            AtomicOP::Swap => 0b00001,
+            AtomicOP::Lr => 0b00010,
+            AtomicOP::Sc => 0b00011,
+            AtomicOP::Xor => 0b00100,
+            AtomicOP::Or => 0b01000,
+            AtomicOP::And => 0b01100,
+            AtomicOP::Min => 0b10000,
+            AtomicOP::Max => 0b10100,
+            AtomicOP::Minu => 0b11000,
+            AtomicOP::Maxu => 0b11100,
+        }
+    }
+}
+
+/// An atomic memory operation. All of them are sequentially consistent:
+/// `lr` and the AMOs have both the acquire and release bits set, and `sc` the
+/// release bit.
+fn enc_atomic(op: AtomicOP, ty: Type, rd: Writable<Reg>, addr: Reg, src: Reg) -> u32 {
+    let funct3 = match ty {
+        I32 => 0b010,
+        I64 => 0b011,
+        _ => panic!("unsupported atomic type {}", ty),
+    };
+    let aqrl = match op {
+        AtomicOP::Sc => 0b01,
+        _ => 0b11,
+    };
+    enc_r(
+        0b0101111,
+        machreg_to_gpr(rd.to_reg()),
+        funct3,
+        machreg_to_gpr(addr),
+        machreg_to_gpr(src),
+        op.funct5() << 2 | aqrl,
+    )
+}
+
+/// The conversion of a float of type `in_ty` to an integer of type `out_ty`.
+fn fcvt_to_int_op(is_signed: bool, in_ty: Type, out_ty: Type) -> FpuOPRR {
+    match (is_signed, in_ty, out_ty) {
+        (true, F32, I32) => FpuOPRR::FcvtWS,
+        (false, F32, I32) => FpuOPRR::FcvtWuS,
+        (true, F32, I64) => FpuOPRR::FcvtLS,
+        (false, F32, I64) => FpuOPRR::FcvtLuS,
+        (true, F64, I32) => FpuOPRR::FcvtWD,
+        (false, F64, I32) => FpuOPRR::FcvtWuD,
+        (true, F64, I64) => FpuOPRR::FcvtLD,
+        (false, F64, I64) => FpuOPRR::FcvtLuD,
+        _ => panic!("unsupported conversion from {} to {}", in_ty, out_ty),
+    }
+}
+
+/// State carried between emissions of a sequence of instructions.
+#[derive(Default, Clone, Debug)]
+pub struct EmitState {
+    /// Addend to convert nominal-SP offsets to real-SP offsets at the current
+    /// program point.
+    pub(crate) virtual_sp_offset: i64,
+    /// Offset of FP from nominal-SP.
+    pub(crate) nominal_sp_to_fp: i64,
+    /// Safepoint stack map for upcoming instruction, as provided to `pre_safepoint()`.
+    stack_map: Option<StackMap>,
+    /// Current source-code location corresponding to instruction to be emitted.
+    cur_srcloc: SourceLoc,
+}
+
+impl MachInstEmitState<Inst> for EmitState {
+    fn new(abi: &dyn ABICallee<I = Inst>) -> Self {
+        EmitState {
+            virtual_sp_offset: 0,
+            nominal_sp_to_fp: abi.frame_size() as i64,
+            stack_map: None,
+            cur_srcloc: SourceLoc::default(),
+        }
+    }
+
+    fn pre_safepoint(&mut self, stack_map: StackMap) {
+        self.stack_map = Some(stack_map);
+    }
+
+    fn pre_sourceloc(&mut self, srcloc: SourceLoc) {
+        self.cur_srcloc = srcloc;
+    }
+}
+
+impl EmitState {
+    fn take_stack_map(&mut self) -> Option<StackMap> {
+        self.stack_map.take()
+    }
+
+    fn clear_post_insn(&mut self) {
+        self.stack_map = None;
+    }
+
+    fn cur_srcloc(&self) -> SourceLoc {
+        self.cur_srcloc
+    }
+}
+
+/// Constant state used during function compilation.
+pub struct EmitInfo {
+    flags: settings::Flags,
+    isa_flags: riscv64_settings::Flags,
+}
+
+impl EmitInfo {
+    pub(crate) fn new(flags: settings::Flags, isa_flags: riscv64_settings::Flags) -> Self {
+        Self { flags, isa_flags }
+    }
+}
+
+impl MachInstEmitInfo for EmitInfo {
+    fn flags(&self) -> &settings::Flags {
+        &self.flags
+    }
+}
+
+impl MachInstEmit for Inst {
+    type State = EmitState;
+    type Info = EmitInfo;
+
+    fn emit(&self, sink: &mut MachBuffer<Inst>, emit_info: &Self::Info, state: &mut EmitState) {
+        // Verify that we can emit this Inst in the current ISA
+        let isa_requirements = self.available_in_isa();
+        let matches_isa_flags = match isa_requirements {
+            InstructionSet::Base => true,
+            InstructionSet::Zbb => emit_info.isa_flags.has_zbb(),
+        };
+        if !matches_isa_flags {
+            panic!(
+                "Cannot emit inst '{:?}' for target; failed to match ISA requirements: {:?}",
+                self, isa_requirements
+            )
+        }
+
+        // N.B.: we *must* not exceed the "worst-case size" used to compute
+        // where to insert islands, except when islands are explicitly triggered
+        // (by `JTSequence`). We check this in debug builds. This is `mut` to
+        // allow disabling the check for `JTSequence`.
+        let mut start_off = sink.cur_offset();
+
+        match self {
+            &Inst::Nop0 => {}
+            &Inst::Nop4 => {
+                // addi zero, zero, 0
+                sink.put4(0x00000013);
+            }
+            &Inst::Lui { rd, imm } => {
+                sink.put4(0b0110111 | machreg_to_gpr(rd.to_reg()) << 7 | imm.bits() << 12);
+            }
+            &Inst::Auipc { rd, imm } => {
+                sink.put4(enc_auipc(rd, imm.bits()));
+            }
+            &Inst::AluRRR {
+                alu_op,
+                rd,
+                rs1,
+                rs2,
+            } => {
+                let (opcode, funct3, funct7) = alu_op.encoding();
+                sink.put4(enc_r(
+                    opcode,
+                    machreg_to_gpr(rd.to_reg()),
+                    funct3,
+                    machreg_to_gpr(rs1),
+                    machreg_to_gpr(rs2),
+                    funct7,
+                ));
+            }
+            &Inst::AluRRImm12 {
+                alu_op,
+                rd,
+                rs,
+                imm12,
+            } => {
+                let (opcode, funct3, imm) = alu_op.encoding(imm12);
+                sink.put4(enc_i(
+                    opcode,
+                    machreg_to_gpr(rd.to_reg()),
+                    funct3,
+                    machreg_to_gpr(rs),
+                    imm,
+                ));
+            }
+            &Inst::AluRR { alu_op, rd, rs } => {
+                let (opcode, funct3, imm) = alu_op.encoding();
+                sink.put4(enc_i(
+                    opcode,
+                    machreg_to_gpr(rd.to_reg()),
+                    funct3,
+                    machreg_to_gpr(rs),
+                    imm,
+                ));
+            }
+            &Inst::Load {
+                op,
+                rd,
+                ref mem,
+                flags,
+            } => {
+                let (mem_insts, mem) = mem_finalize(mem, state);
+                for inst in mem_insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                let (base, off) = match mem {
+                    AMode::RegOffset(base, off) => (base, off),
+                    _ => unreachable!(),
+                };
+
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() && !flags.notrap() {
+                    // Register the offset at which the actual load instruction starts.
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+
+                let (opcode, funct3) = op.encoding();
+                sink.put4(enc_i(
+                    opcode,
+                    machreg_to_gpr_or_fpr(rd.to_reg()),
+                    funct3,
+                    machreg_to_gpr(base),
+                    Imm12::maybe_from_i64(off).unwrap().bits(),
+                ));
+            }
+            &Inst::Store {
+                op,
+                src,
+                ref mem,
+                flags,
+            } => {
+                let (mem_insts, mem) = mem_finalize(mem, state);
+                for inst in mem_insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                let (base, off) = match mem {
+                    AMode::RegOffset(base, off) => (base, off),
+                    _ => unreachable!(),
+                };
+
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() && !flags.notrap() {
+                    // Register the offset at which the actual store instruction starts.
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+
+                let (opcode, funct3) = op.encoding();
+                sink.put4(enc_s(
+                    opcode,
+                    funct3,
+                    machreg_to_gpr(base),
+                    machreg_to_gpr_or_fpr(src),
+                    Imm12::maybe_from_i64(off).unwrap().bits(),
+                ));
+            }
+            &Inst::FpuRR {
+                alu_op,
+                frm,
+                rd,
+                rs,
+            } => {
+                let (funct7, rs2, has_frm) = alu_op.encoding();
+                let funct3 = if has_frm { frm.bits() } else { 0b000 };
+                sink.put4(enc_r(
+                    0b1010011,
+                    machreg_to_gpr_or_fpr(rd.to_reg()),
+                    funct3,
+                    machreg_to_gpr_or_fpr(rs),
+                    rs2,
+                    funct7,
+                ));
+            }
+            &Inst::FpuRRR {
+                alu_op,
+                frm,
+                rd,
+                rs1,
+                rs2,
+            } => {
+                let (funct7, funct3) = alu_op.encoding();
+                sink.put4(enc_r(
+                    0b1010011,
+                    machreg_to_gpr_or_fpr(rd.to_reg()),
+                    funct3.unwrap_or(frm.bits()),
+                    machreg_to_fpr(rs1),
+                    machreg_to_fpr(rs2),
+                    funct7,
+                ));
+            }
+            &Inst::FpuRRRR {
+                alu_op,
+                frm,
+                rd,
+                rs1,
+                rs2,
+                rs3,
+            } => {
+                let fmt = match alu_op {
+                    FpuOPRRRR::FmaddS => 0b00,
+                    FpuOPRRRR::FmaddD => 0b01,
+                };
+                sink.put4(enc_r(
+                    0b1000011,
+                    machreg_to_fpr(rd.to_reg()),
+                    frm.bits(),
+                    machreg_to_fpr(rs1),
+                    machreg_to_fpr(rs2),
+                    machreg_to_fpr(rs3) << 2 | fmt,
+                ));
+            }
+            &Inst::Mov { rd, rm, ty } => {
+                if ty.is_float() {
+                    // fsgnj.d rd, rm, rm. This copies the whole register, so also
+                    // moves NaN-boxed `F32` values.
+                    sink.put4(enc_r(
+                        0b1010011,
+                        machreg_to_fpr(rd.to_reg()),
+                        0b000,
+                        machreg_to_fpr(rm),
+                        machreg_to_fpr(rm),
+                        0b0010001,
+                    ));
+                } else {
+                    Inst::addi(rd, rm, Imm12::zero()).emit(sink, emit_info, state);
+                }
+            }
+            &Inst::Extend {
+                rd,
+                rn,
+                signed,
+                from_bits,
+                ..
+            } => {
+                let shift = Imm12::maybe_from_i64(64 - i64::from(from_bits)).unwrap();
+                let insts: SmallVec<[Inst; 2]> = match (signed, from_bits) {
+                    (_, 64) => smallvec![Inst::addi(rd, rn, Imm12::zero())],
+                    (true, 32) => smallvec![Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Addiw,
+                        rd,
+                        rs: rn,
+                        imm12: Imm12::zero(),
+                    }],
+                    (false, 1) | (false, 8) => smallvec![Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Andi,
+                        rd,
+                        rs: rn,
+                        imm12: Imm12::maybe_from_i64((1 << from_bits) - 1).unwrap(),
+                    }],
+                    (_, _) => smallvec![
+                        Inst::AluRRImm12 {
+                            alu_op: AluOPRRI::Slli,
+                            rd,
+                            rs: rn,
+                            imm12: shift,
+                        },
+                        Inst::AluRRImm12 {
+                            alu_op: if signed {
+                                AluOPRRI::Srai
+                            } else {
+                                AluOPRRI::Srli
+                            },
+                            rd,
+                            rs: rd.to_reg(),
+                            imm12: shift,
+                        },
+                    ],
+                };
+                for inst in insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+            }
+            &Inst::Select { rd, ty, cond, x, y } => {
+                // b<cond> 12 ; mv rd, y ; j 8 ; mv rd, x
+                sink.put4(enc_cond_branch(cond, 12));
+                Inst::gen_move(rd, y, ty).emit(sink, emit_info, state);
+                sink.put4(enc_jal(writable_zero_reg(), 8));
+                Inst::gen_move(rd, x, ty).emit(sink, emit_info, state);
+            }
+            &Inst::BitCount { op, ty, rd, rs } => {
+                // Shift the value to the top of the spilltmp register, and count
+                // the shifts it takes to clear it, one bit at a time. For `clz`,
+                // the value is zero-extended instead, and shifted right.
+                let tmp = writable_spilltmp_reg();
+                let bits = i64::from(ty.bits());
+                let mut insts: SmallVec<[Inst; 4]> = smallvec![Inst::AluRRImm12 {
+                    alu_op: AluOPRRI::Slli,
+                    rd: tmp,
+                    rs,
+                    imm12: Imm12::maybe_from_i64(64 - bits).unwrap(),
+                }];
+                if op == BitCountOp::Clz && bits < 64 {
+                    insts.push(Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Srli,
+                        rd: tmp,
+                        rs: tmp.to_reg(),
+                        imm12: Imm12::maybe_from_i64(64 - bits).unwrap(),
+                    });
+                }
+                let initial = if op == BitCountOp::Popcnt { 0 } else { bits };
+                insts.push(Inst::addi(
+                    rd,
+                    zero_reg(),
+                    Imm12::maybe_from_i64(initial).unwrap(),
+                ));
+                for inst in insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+
+                let shift_op = if op == BitCountOp::Clz {
+                    AluOPRRI::Srli
+                } else {
+                    AluOPRRI::Slli
+                };
+                let shift = Inst::AluRRImm12 {
+                    alu_op: shift_op,
+                    rd: tmp,
+                    rs: tmp.to_reg(),
+                    imm12: Imm12::maybe_from_i64(1).unwrap(),
+                };
+                if op == BitCountOp::Popcnt {
+                    // loop: beqz t0, done ; bgez t0, 8 ; addi rd, rd, 1 ;
+                    //       slli t0, t0, 1 ; j loop ; done:
+                    sink.put4(enc_branch(0b000, tmp.to_reg(), zero_reg(), 20));
+                    sink.put4(enc_branch(0b101, tmp.to_reg(), zero_reg(), 8));
+                    Inst::addi(rd, rd.to_reg(), Imm12::maybe_from_i64(1).unwrap())
+                        .emit(sink, emit_info, state);
+                    shift.emit(sink, emit_info, state);
+                    sink.put4(enc_jal(writable_zero_reg(), -16));
+                } else {
+                    // loop: beqz t0, done ; s{l,r}li t0, t0, 1 ; addi rd, rd, -1 ;
+                    //       j loop ; done:
+                    sink.put4(enc_branch(0b000, tmp.to_reg(), zero_reg(), 16));
+                    shift.emit(sink, emit_info, state);
+                    Inst::addi(rd, rd.to_reg(), Imm12::maybe_from_i64(-1).unwrap())
+                        .emit(sink, emit_info, state);
+                    sink.put4(enc_jal(writable_zero_reg(), -12));
+                }
+            }
+            &Inst::FpuRound { op, ty, rd, rs } => {
+                // Values whose exponent shows that they have no fractional bits
+                // (including infinities and NaNs) are only canonicalized. The
+                // others are converted to a 64-bit integer and back, and get
+                // their sign restored, for the zeros.
+                let (to_int, from_int, to_x, from_x, min, exp_shift, exp_bias) = match ty {
+                    F32 => (
+                        FpuOPRR::FcvtLS,
+                        FpuOPRR::FcvtSL,
+                        FpuOPRR::FmvXW,
+                        FpuOPRR::FmvWX,
+                        FpuOPRRR::FminS,
+                        (33, 56, 31),
+                        -150,
+                    ),
+                    F64 => (
+                        FpuOPRR::FcvtLD,
+                        FpuOPRR::FcvtDL,
+                        FpuOPRR::FmvXD,
+                        FpuOPRR::FmvDX,
+                        FpuOPRRR::FminD,
+                        (1, 53, 63),
+                        -1075,
+                    ),
+                    _ => panic!("unsupported rounding type {}", ty),
+                };
+                let (tmp, bits) = (writable_spilltmp_reg(), writable_tmp2_reg());
+                let alu = |alu_op, rd: Writable<Reg>, rs: Reg, imm: i64| Inst::AluRRImm12 {
+                    alu_op,
+                    rd,
+                    rs,
+                    imm12: Imm12::maybe_from_i64(imm).unwrap(),
+                };
+                let fpu = |alu_op, rd, rs| Inst::FpuRR {
+                    alu_op,
+                    frm: op.frm(),
+                    rd,
+                    rs,
+                };
+                for inst in &[
+                    fpu(to_x, bits, rs),
+                    alu(AluOPRRI::Slli, tmp, bits.to_reg(), exp_shift.0),
+                    alu(AluOPRRI::Srli, tmp, tmp.to_reg(), exp_shift.1),
+                    alu(AluOPRRI::Addi, tmp, tmp.to_reg(), exp_bias),
+                    fpu(from_x, rd, bits.to_reg()),
+                ] {
+                    inst.emit(sink, emit_info, state);
+                }
+                // bgez t0, big
+                sink.put4(enc_branch(0b101, tmp.to_reg(), zero_reg(), 36));
+                for inst in &[
+                    fpu(to_int, tmp, rd.to_reg()),
+                    fpu(from_int, rd, tmp.to_reg()),
+                    fpu(to_x, tmp, rd.to_reg()),
+                    alu(AluOPRRI::Srli, bits, bits.to_reg(), exp_shift.2),
+                    alu(AluOPRRI::Slli, bits, bits.to_reg(), exp_shift.2),
+                    Inst::AluRRR {
+                        alu_op: AluOPRRR::Or,
+                        rd: tmp,
+                        rs1: tmp.to_reg(),
+                        rs2: bits.to_reg(),
+                    },
+                    fpu(from_x, rd, tmp.to_reg()),
+                ] {
+                    inst.emit(sink, emit_info, state);
+                }
+                // j done ; big: fmin rd, rd, rd ; done:
+                sink.put4(enc_jal(writable_zero_reg(), 8));
+                Inst::FpuRRR {
+                    alu_op: min,
+                    frm: FRM::RNE,
+                    rd,
+                    rs1: rd.to_reg(),
+                    rs2: rd.to_reg(),
+                }
+                .emit(sink, emit_info, state);
+            }
+            &Inst::FcvtToInt {
+                is_sat,
+                is_signed,
+                in_ty,
+                out_ty,
+                rd,
+                rs,
+            } => {
+                let tmp = writable_spilltmp_reg();
+                let feq = Inst::FpuRRR {
+                    alu_op: if in_ty == F32 {
+                        FpuOPRRR::FeqS
+                    } else {
+                        FpuOPRRR::FeqD
+                    },
+                    frm: FRM::RNE,
+                    rd: tmp,
+                    rs1: rs,
+                    rs2: rs,
+                };
+                let cvt = Inst::FpuRR {
+                    alu_op: fcvt_to_int_op(is_signed, in_ty, out_ty),
+                    frm: FRM::RTZ,
+                    rd,
+                    rs,
+                };
+                if is_sat {
+                    // The conversion saturates, but converts NaNs to the maximum
+                    // value, so mask them to zero:
+                    // fcvt rd, rs, rtz ; feq t0, rs, rs ; neg t0, t0 ; and rd, rd, t0
+                    cvt.emit(sink, emit_info, state);
+                    feq.emit(sink, emit_info, state);
+                    for inst in &[
+                        Inst::AluRRR {
+                            alu_op: AluOPRRR::Sub,
+                            rd: tmp,
+                            rs1: zero_reg(),
+                            rs2: tmp.to_reg(),
+                        },
+                        Inst::AluRRR {
+                            alu_op: AluOPRRR::And,
+                            rd,
+                            rs1: rd.to_reg(),
+                            rs2: tmp.to_reg(),
+                        },
+                    ] {
+                        inst.emit(sink, emit_info, state);
+                    }
+                } else {
+                    // Trap on NaN, and on the invalid-operation flag raised by
+                    // the conversion of values out of range:
+                    // feq t0, rs, rs ; bnez t0, 8 ; unimp ;
+                    // fsflags zero ; fcvt rd, rs, rtz ; frflags t0 ;
+                    // andi t0, t0, NV ; beqz t0, 8 ; unimp
+                    feq.emit(sink, emit_info, state);
+                    sink.put4(enc_branch(0b001, tmp.to_reg(), zero_reg(), 8));
+                    Inst::Udf {
+                        trap_code: TrapCode::BadConversionToInteger,
+                    }
+                    .emit(sink, emit_info, state);
+                    sink.put4(CLEAR_FFLAGS);
+                    cvt.emit(sink, emit_info, state);
+                    sink.put4(enc_read_fflags(tmp));
+                    Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Andi,
+                        rd: tmp,
+                        rs: tmp.to_reg(),
+                        imm12: Imm12::maybe_from_i64(FFLAGS_NV).unwrap(),
+                    }
+                    .emit(sink, emit_info, state);
+                    sink.put4(enc_branch(0b000, tmp.to_reg(), zero_reg(), 8));
+                    Inst::Udf {
+                        trap_code: TrapCode::IntegerOverflow,
+                    }
+                    .emit(sink, emit_info, state);
+                }
+            }
+            &Inst::FloatMinMax {
+                is_min,
+                ty,
+                rd,
+                rs1,
+                rs2,
+            } => {
+                // feq t0, rs1, rs1 ; beqz t0, nan ; feq t0, rs2, rs2 ; beqz t0, nan ;
+                // f{min,max} rd, rs1, rs2 ; j done ; nan: fadd rd, rs1, rs2 ; done:
+                let tmp = writable_spilltmp_reg();
+                let (feq, op, add) = match (ty, is_min) {
+                    (F32, true) => (FpuOPRRR::FeqS, FpuOPRRR::FminS, FpuOPRRR::FaddS),
+                    (F32, false) => (FpuOPRRR::FeqS, FpuOPRRR::FmaxS, FpuOPRRR::FaddS),
+                    (F64, true) => (FpuOPRRR::FeqD, FpuOPRRR::FminD, FpuOPRRR::FaddD),
+                    (F64, false) => (FpuOPRRR::FeqD, FpuOPRRR::FmaxD, FpuOPRRR::FaddD),
+                    _ => panic!("unsupported min/max type {}", ty),
+                };
+                let fpu = |alu_op, rd, rs1, rs2| Inst::FpuRRR {
+                    alu_op,
+                    frm: FRM::RNE,
+                    rd,
+                    rs1,
+                    rs2,
+                };
+                fpu(feq, tmp, rs1, rs1).emit(sink, emit_info, state);
+                sink.put4(enc_branch(0b000, tmp.to_reg(), zero_reg(), 20));
+                fpu(feq, tmp, rs2, rs2).emit(sink, emit_info, state);
+                sink.put4(enc_branch(0b000, tmp.to_reg(), zero_reg(), 12));
+                fpu(op, rd, rs1, rs2).emit(sink, emit_info, state);
+                sink.put4(enc_jal(writable_zero_reg(), 8));
+                fpu(add, rd, rs1, rs2).emit(sink, emit_info, state);
+            }
+            &Inst::Atomic {
+                op,
+                ty,
+                rd,
+                addr,
+                src,
+            } => {
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() {
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+                sink.put4(enc_atomic(op, ty, rd, addr, src));
+            }
+            &Inst::AtomicCas {
+                ty,
+                rd,
+                addr,
+                expected,
+                replacement,
+            } => {
+                // loop: lr t0, (addr) ; bne t0, expected, done ;
+                //       sc t1, replacement, (addr) ; bnez t1, loop ;
+                // done: mv rd, t0
+                let (tmp, status) = (writable_spilltmp_reg(), writable_tmp2_reg());
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() {
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+                sink.put4(enc_atomic(AtomicOP::Lr, ty, tmp, addr, zero_reg()));
+                sink.put4(enc_branch(0b001, tmp.to_reg(), expected, 12));
+                sink.put4(enc_atomic(AtomicOP::Sc, ty, status, addr, replacement));
+                sink.put4(enc_branch(0b001, status.to_reg(), zero_reg(), -12));
+                Inst::gen_move(rd, tmp.to_reg(), I64).emit(sink, emit_info, state);
+            }
+            &Inst::AtomicNand { ty, rd, addr, src } => {
+                // loop: lr t0, (addr) ; and t1, t0, src ; not t1, t1 ;
+                //       sc t1, t1, (addr) ; bnez t1, loop ; mv rd, t0
+                let (tmp, new) = (writable_spilltmp_reg(), writable_tmp2_reg());
+                let srcloc = state.cur_srcloc();
+                if srcloc != SourceLoc::default() {
+                    sink.add_trap(srcloc, TrapCode::HeapOutOfBounds);
+                }
+                sink.put4(enc_atomic(AtomicOP::Lr, ty, tmp, addr, zero_reg()));
+                for inst in &[
+                    Inst::AluRRR {
+                        alu_op: AluOPRRR::And,
+                        rd: new,
+                        rs1: tmp.to_reg(),
+                        rs2: src,
+                    },
+                    Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Xori,
+                        rd: new,
+                        rs: new.to_reg(),
+                        imm12: Imm12::maybe_from_i64(-1).unwrap(),
+                    },
+                ] {
+                    inst.emit(sink, emit_info, state);
+                }
+                sink.put4(enc_atomic(AtomicOP::Sc, ty, new, addr, new.to_reg()));
+                sink.put4(enc_branch(0b001, new.to_reg(), zero_reg(), -16));
+                Inst::gen_move(rd, tmp.to_reg(), I64).emit(sink, emit_info, state);
+            }
+            &Inst::Fence => {
+                // fence rw, rw
+                sink.put4(0x0330000f);
+            }
+            &Inst::Call { ref info } => {
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(8), s);
+                }
+                let loc = state.cur_srcloc();
+                // auipc ra, 0 ; jalr ra, 0(ra)
+                sink.add_reloc(loc, Reloc::RiscvCallPlt, &info.dest, 0);
+                sink.put4(enc_auipc(writable_link_reg(), 0));
+                sink.put4(enc_jalr(writable_link_reg(), link_reg(), 0));
+                if info.opcode.is_call() {
+                    sink.add_call_site(loc, info.opcode);
+                }
+                emit_restore_sp_after_call(sink, emit_info, state);
+            }
+            &Inst::CallInd { ref info } => {
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(4), s);
+                }
+                sink.put4(enc_jalr(writable_link_reg(), info.rn, 0));
+                let loc = state.cur_srcloc();
+                if info.opcode.is_call() {
+                    sink.add_call_site(loc, info.opcode);
+                }
+                emit_restore_sp_after_call(sink, emit_info, state);
+            }
+            &Inst::ReturnCall {
+                ref dest,
+                sp_adjust,
+                ..
+            } => {
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        inst.emit(sink, emit_info, state);
+                    }
+                }
+                // auipc t1, 0 ; jalr zero, 0(t1)
+                let loc = state.cur_srcloc();
+                sink.add_reloc(loc, Reloc::RiscvCallPlt, dest, 0);
+                sink.put4(enc_auipc(writable_tmp2_reg(), 0));
+                sink.put4(enc_jalr(writable_zero_reg(), tmp2_reg(), 0));
+            }
+            &Inst::ReturnCallInd { rn, sp_adjust, .. } => {
+                if sp_adjust > 0 {
+                    debug_assert_ne!(rn, spilltmp_reg());
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        inst.emit(sink, emit_info, state);
+                    }
+                }
+                sink.put4(enc_jalr(writable_zero_reg(), rn, 0));
+            }
+            &Inst::Ret => {
+                sink.put4(enc_jalr(writable_zero_reg(), link_reg(), 0));
+            }
+            &Inst::EpiloguePlaceholder => {
+                // Noop; this is just a placeholder for epilogues.
+            }
+            &Inst::Jump { ref dest } => {
+                let off = sink.cur_offset();
+                // Indicate that the jump uses a label, if so, so that a fixup can occur later.
+                if let Some(l) = dest.as_label() {
+                    sink.use_label_at_offset(off, l, LabelUse::Jal20);
+                    sink.add_uncond_branch(off, off + 4, l);
+                }
+                // Emit the jump itself.
+                sink.put4(enc_jal(writable_zero_reg(), dest.as_offset_or_zero()));
+            }
+            &Inst::JumpUnwind { dest, landing_pad } => {
+                // The landing pad expects SP where it is between calls.
+                let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+                sink.add_exception_handler(landing_pad.as_label().unwrap(), fp_to_sp as u32);
+                Inst::Jump { dest }.emit(sink, emit_info, state);
+            }
+            &Inst::CondBr {
+                taken,
+                not_taken,
+                kind,
+            } => {
+                // Conditional part first.
+                let cond_off = sink.cur_offset();
+                if let Some(l) = taken.as_label() {
+                    sink.use_label_at_offset(cond_off, l, LabelUse::B12);
+                    let inverted = enc_cond_branch(kind.inverse(), 0).to_le_bytes();
+                    sink.add_cond_branch(cond_off, cond_off + 4, l, &inverted[..]);
+                }
+                sink.put4(enc_cond_branch(kind, taken.as_offset_or_zero()));
+
+                // Unconditional part next.
+                let uncond_off = sink.cur_offset();
+                if let Some(l) = not_taken.as_label() {
+                    sink.use_label_at_offset(uncond_off, l, LabelUse::Jal20);
+                    sink.add_uncond_branch(uncond_off, uncond_off + 4, l);
+                }
+                sink.put4(enc_jal(writable_zero_reg(), not_taken.as_offset_or_zero()));
+            }
+            &Inst::TrapIf { cc, trap_code } => {
+                // b<!cc> 8 ; unimp
+                sink.put4(enc_cond_branch(cc.inverse(), 8));
+                Inst::Udf { trap_code }.emit(sink, emit_info, state);
+            }
+            &Inst::Udf { trap_code } => {
+                let srcloc = state.cur_srcloc();
+                sink.add_trap(srcloc, trap_code);
+                if let Some(s) = state.take_stack_map() {
+                    sink.add_stack_map(StackMapExtent::UpcomingBytes(4), s);
+                }
+                sink.put4(UNIMP);
+            }
+            &Inst::Debugtrap => {
+                // ebreak
+                sink.put4(0x00100073);
+            }
+            &Inst::IndirectBr { rn, .. } => {
+                sink.put4(enc_jalr(writable_zero_reg(), rn, 0));
+            }
+            &Inst::JTSequence { ridx, ref info } => {
+                // This sequence is *one* instruction in the vcode, and is expanded only here at
+                // emission time, because we cannot allow the regalloc to insert spills/reloads in
+                // the middle; we depend on hardcoded PC-rel addressing below.
+                let (tmp1, tmp2) = (writable_spilltmp_reg(), writable_tmp2_reg());
+                let bound = Inst::load_constant(tmp1, info.targets.len() as u64);
+
+                // The table can be large, so emit an island first if it would
+                // push any pending branch out of range.
+                let size = 4 * (8 + bound.len() + info.targets.len()) as CodeOffset;
+                if sink.island_needed(size + 4) {
+                    let jump_around_label = sink.get_label();
+                    let jmp = Inst::Jump {
+                        dest: BranchTarget::Label(jump_around_label),
+                    };
+                    jmp.emit(sink, emit_info, state);
+                    sink.emit_island();
+                    sink.bind_label(jump_around_label);
+                }
+
+                // Branch to default when the index is out of range:
+                // li t0, len ; bltu ridx, t0, 8 ; j default
+                for inst in bound.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                sink.put4(enc_branch(0b110, ridx, tmp1.to_reg(), 8));
+                // No need to inform the sink's branch folding logic about this branch, because it
+                // will not be merged with any other branch, flipped, or elided (it is not preceded
+                // or succeeded by any other branch). Just emit it with the label use.
+                let default_br_offset = sink.cur_offset();
+                if let BranchTarget::Label(l) = info.default_target {
+                    sink.use_label_at_offset(default_br_offset, l, LabelUse::Jal20);
+                }
+                sink.put4(enc_jal(writable_zero_reg(), 0));
+
+                // Load the offset of the target from the table, which starts
+                // 20 bytes after the `auipc`, and add it to the table's address:
+                // slli t0, ridx, 2 ; auipc t1, 0 ; add t0, t0, t1 ; lw t0, 20(t0) ;
+                // add t1, t1, t0 ; jr 20(t1)
+                for inst in &[
+                    Inst::AluRRImm12 {
+                        alu_op: AluOPRRI::Slli,
+                        rd: tmp1,
+                        rs: ridx,
+                        imm12: Imm12::maybe_from_i64(2).unwrap(),
+                    },
+                    Inst::Auipc {
+                        rd: tmp2,
+                        imm: Imm20::maybe_from_i64(0).unwrap(),
+                    },
+                    Inst::AluRRR {
+                        alu_op: AluOPRRR::Add,
+                        rd: tmp1,
+                        rs1: tmp1.to_reg(),
+                        rs2: tmp2.to_reg(),
+                    },
+                    Inst::Load {
+                        op: LoadOP::Lw,
+                        rd: tmp1,
+                        mem: AMode::reg_plus_off(tmp1.to_reg(), 20),
+                        flags: MemFlags::trusted(),
+                    },
+                    Inst::AluRRR {
+                        alu_op: AluOPRRR::Add,
+                        rd: tmp2,
+                        rs1: tmp2.to_reg(),
+                        rs2: tmp1.to_reg(),
+                    },
+                ] {
+                    inst.emit(sink, emit_info, state);
+                }
+                sink.put4(enc_jalr(writable_zero_reg(), tmp2.to_reg(), 20));
+
+                // Emit jump table (table of 32-bit offsets).
+                let jt_off = sink.cur_offset();
+                for &target in info.targets.iter() {
+                    let word_off = sink.cur_offset();
+                    // off_into_table is an addend here embedded in the label to be later patched
+                    // at the end of codegen. The offset is initially relative to this jump table
+                    // entry; with the extra addend, it'll be relative to the jump table's start,
+                    // after patching.
+                    let off_into_table = word_off - jt_off;
+                    sink.use_label_at_offset(
+                        word_off,
+                        target.as_label().unwrap(),
+                        LabelUse::PCRel32,
+                    );
+                    sink.put4(off_into_table);
+                }
+
+                // The island check above covers the table, so we can safely
+                // disable the worst-case-size check in this case.
+                start_off = sink.cur_offset();
+            }
+            &Inst::LoadExtName {
+                rd,
+                ref name,
+                offset,
+            } => {
+                // Align the constant to 8 bytes:
+                // auipc rd, 0 ; ld rd, 12(rd) ; j 12 ; .8byte name + offset
+                if (sink.cur_offset() + 12) % 8 != 0 {
+                    Inst::Nop4.emit(sink, emit_info, state);
+                }
+                sink.put4(enc_auipc(rd, 0));
+                Inst::Load {
+                    op: LoadOP::Ld,
+                    rd,
+                    mem: AMode::reg_plus_off(rd.to_reg(), 12),
+                    flags: MemFlags::trusted(),
+                }
+                .emit(sink, emit_info, state);
+                sink.put4(enc_jal(writable_zero_reg(), 12));
+                let srcloc = state.cur_srcloc();
+                sink.add_reloc(srcloc, Reloc::Abs8, name, offset);
+                if emit_info.flags().emit_all_ones_funcaddrs() {
+                    sink.put8(u64::max_value());
+                } else {
+                    sink.put8(0);
+                }
+            }
+            &Inst::LoadAddr { rd, ref mem } => {
+                let (mem_insts, mem) = mem_finalize(mem, state);
+                for inst in mem_insts.into_iter() {
+                    inst.emit(sink, emit_info, state);
+                }
+                let (base, off) = match mem {
+                    AMode::RegOffset(base, off) => (base, off),
+                    _ => unreachable!(),
+                };
+                Inst::addi(rd, base, Imm12::maybe_from_i64(off).unwrap())
+                    .emit(sink, emit_info, state);
+            }
+            &Inst::VirtualSPOffsetAdj { offset } => {
+                log::trace!(
+                    "virtual sp offset adjusted by {} -> {}",
+                    offset,
+                    state.virtual_sp_offset + offset,
+                );
+                state.virtual_sp_offset += offset;
+            }
+            &Inst::ValueLabelMarker { .. } => {
+                // Nothing; this is only used to compute debug info.
+            }
+            &Inst::Unwind { ref inst } => {
+                sink.add_unwind(inst.clone());
+            }
+        }
+
+        let end_off = sink.cur_offset();
+        debug_assert!((end_off - start_off) <= Inst::worst_case_size());
+
+        state.clear_post_insn();
+    }
+
+    fn pretty_print(&self, mb_rru: Option<&RealRegUniverse>, state: &mut EmitState) -> String {
+        self.print_with_state(mb_rru, state)
+    }
+}
+
+/// With growing tail calls, a callee may return with SP moved, so recompute it
+/// from FP right after a call returns. This has to be part of the call itself,
+/// as the register allocator may reload spilled values right after it.
+fn emit_restore_sp_after_call(
+    sink: &mut MachBuffer<Inst>,
+    emit_info: &EmitInfo,
+    state: &mut EmitState,
+) {
+    if !emit_info.flags().enable_growing_tail_calls() {
+        return;
+    }
+    let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+    for inst in Inst::gen_set_sp(fp_reg(), -fp_to_sp) {
+        inst.emit(sink, emit_info, state);
+    }
+}
@@ -0,0 +1,1985 @@
+use crate::ir::condcodes::IntCC;
+use crate::ir::types::*;
+use crate::ir::{ExternalName, MemFlags, Opcode, TrapCode};
+use crate::isa::riscv64::inst::*;
+use crate::isa::riscv64::settings as riscv64_settings;
+use crate::isa::test_utils;
+use crate::isa::CallConv;
+use crate::settings;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[test]
+fn test_riscv64_binemit() {
+    let mut insns = Vec::<(Inst, &str, &str)>::new();
+
+    // N.B.: the architecture is little-endian, so when transcribing the 32-bit
+    // hex instructions from e.g. llvm-mc, be sure to reverse the byte order.
+    insns.push((Inst::Nop0, "", "nop-zero-len"));
+    insns.push((Inst::Nop4, "13000000", "nop"));
+    insns.push((
+        Inst::Lui {
+            rd: writable_xreg(10),
+            imm: Imm20::maybe_from_i64(0x12345).unwrap(),
+        },
+        "37553412",
+        "lui a0, 74565",
+    ));
+    insns.push((
+        Inst::Lui {
+            rd: writable_xreg(5),
+            imm: Imm20::maybe_from_i64(-1).unwrap(),
+        },
+        "B7F2FFFF",
+        "lui t0, 1048575",
+    ));
+    insns.push((
+        Inst::Auipc {
+            rd: writable_xreg(6),
+            imm: Imm20::maybe_from_i64(1).unwrap(),
+        },
+        "17130000",
+        "auipc t1, 1",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Add,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3385C500",
+        "add a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sub,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3385C540",
+        "sub a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sll,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3395C500",
+        "sll a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Slt,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33A5C500",
+        "slt a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sltu,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33B5C500",
+        "sltu a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Xor,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33C5C500",
+        "xor a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Srl,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33D5C500",
+        "srl a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sra,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33D5C540",
+        "sra a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Or,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33E5C500",
+        "or a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::And,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33F5C500",
+        "and a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Addw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3B85C500",
+        "addw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Subw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3B85C540",
+        "subw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sllw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3B95C500",
+        "sllw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Srlw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BD5C500",
+        "srlw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Sraw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BD5C540",
+        "sraw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Mul,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3385C502",
+        "mul a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Mulh,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3395C502",
+        "mulh a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Mulhsu,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33A5C502",
+        "mulhsu a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Mulhu,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33B5C502",
+        "mulhu a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Div,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33C5C502",
+        "div a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Divu,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33D5C502",
+        "divu a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Rem,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33E5C502",
+        "rem a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Remu,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33F5C502",
+        "remu a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Mulw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3B85C502",
+        "mulw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Divw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BC5C502",
+        "divw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Divuw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BD5C502",
+        "divuw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Remw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BE5C502",
+        "remw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Remuw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BF5C502",
+        "remuw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Andn,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33F5C540",
+        "andn a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Orn,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33E5C540",
+        "orn a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Xnor,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33C5C540",
+        "xnor a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Rol,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3395C560",
+        "rol a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Ror,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "33D5C560",
+        "ror a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Rolw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3B95C560",
+        "rolw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRR {
+            alu_op: AluOPRRR::Rorw,
+            rd: writable_xreg(10),
+            rs1: xreg(11),
+            rs2: xreg(12),
+        },
+        "3BD5C560",
+        "rorw a0, a1, a2",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Addi,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(-2048).unwrap(),
+        },
+        "93060780",
+        "addi a3, a4, -2048",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Addi,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(2047).unwrap(),
+        },
+        "9306F77F",
+        "addi a3, a4, 2047",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Slti,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(-1).unwrap(),
+        },
+        "9326F7FF",
+        "slti a3, a4, -1",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Sltiu,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(1).unwrap(),
+        },
+        "93361700",
+        "sltiu a3, a4, 1",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Xori,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(-1).unwrap(),
+        },
+        "9346F7FF",
+        "xori a3, a4, -1",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Ori,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(127).unwrap(),
+        },
+        "9366F707",
+        "ori a3, a4, 127",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Andi,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(255).unwrap(),
+        },
+        "9376F70F",
+        "andi a3, a4, 255",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Slli,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(63).unwrap(),
+        },
+        "9316F703",
+        "slli a3, a4, 63",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Srli,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(1).unwrap(),
+        },
+        "93561700",
+        "srli a3, a4, 1",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Srai,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(32).unwrap(),
+        },
+        "93560742",
+        "srai a3, a4, 32",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Addiw,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(-5).unwrap(),
+        },
+        "9B06B7FF",
+        "addiw a3, a4, -5",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Slliw,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(31).unwrap(),
+        },
+        "9B16F701",
+        "slliw a3, a4, 31",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Srliw,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(7).unwrap(),
+        },
+        "9B567700",
+        "srliw a3, a4, 7",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Sraiw,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(16).unwrap(),
+        },
+        "9B560741",
+        "sraiw a3, a4, 16",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Rori,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(13).unwrap(),
+        },
+        "9356D760",
+        "rori a3, a4, 13",
+    ));
+    insns.push((
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Roriw,
+            rd: writable_xreg(13),
+            rs: xreg(14),
+            imm12: Imm12::maybe_from_i64(31).unwrap(),
+        },
+        "9B56F761",
+        "roriw a3, a4, 31",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Clz,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "13950560",
+        "clz a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Ctz,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "13951560",
+        "ctz a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Cpop,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "13952560",
+        "cpop a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Clzw,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "1B950560",
+        "clzw a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Ctzw,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "1B951560",
+        "ctzw a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::Cpopw,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "1B952560",
+        "cpopw a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::SextB,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "13954560",
+        "sext.b a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::SextH,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "13955560",
+        "sext.h a0, a1",
+    ));
+    insns.push((
+        Inst::AluRR {
+            alu_op: AluOPRR::ZextH,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "3BC50508",
+        "zext.h a0, a1",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lb,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), 0),
+            flags: MemFlags::trusted(),
+        },
+        "03850500",
+        "lb a0, 0(a1)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lh,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), -2),
+            flags: MemFlags::trusted(),
+        },
+        "0395E5FF",
+        "lh a0, -2(a1)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lw,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), 2047),
+            flags: MemFlags::trusted(),
+        },
+        "03A5F57F",
+        "lw a0, 2047(a1)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Ld,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), -2048),
+            flags: MemFlags::trusted(),
+        },
+        "03B50580",
+        "ld a0, -2048(a1)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lbu,
+            rd: writable_xreg(10),
+            mem: AMode::SPOffset(16),
+            flags: MemFlags::trusted(),
+        },
+        "03450101",
+        "lbu a0, 16(sp)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lhu,
+            rd: writable_xreg(10),
+            mem: AMode::FPOffset(-8),
+            flags: MemFlags::trusted(),
+        },
+        "035584FF",
+        "lhu a0, -8(fp)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Lwu,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), 4096),
+            flags: MemFlags::trusted(),
+        },
+        "B7120000B382B20003E50200",
+        "lui t0, 1 ; add t0, t0, a1 ; lwu a0, 0(t0)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Flw,
+            rd: writable_freg(10),
+            mem: AMode::RegOffset(xreg(11), 4),
+            flags: MemFlags::trusted(),
+        },
+        "07A54500",
+        "flw fa0, 4(a1)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Fld,
+            rd: writable_freg(10),
+            mem: AMode::RegOffset(xreg(11), 0x12345678),
+            flags: MemFlags::trusted(),
+        },
+        "B75234129B828267B382B20007B50200",
+        "lui t0, 74565 ; addiw t0, t0, 1656 ; add t0, t0, a1 ; fld fa0, 0(t0)",
+    ));
+    insns.push((
+        Inst::Load {
+            op: LoadOP::Ld,
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), 8),
+            flags: MemFlags::new(),
+        },
+        "03B58500",
+        "ld a0, 8(a1)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Sb,
+            src: xreg(12),
+            mem: AMode::RegOffset(xreg(11), 0),
+            flags: MemFlags::trusted(),
+        },
+        "2380C500",
+        "sb a2, 0(a1)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Sh,
+            src: xreg(12),
+            mem: AMode::RegOffset(xreg(11), -2),
+            flags: MemFlags::trusted(),
+        },
+        "239FC5FE",
+        "sh a2, -2(a1)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Sw,
+            src: xreg(12),
+            mem: AMode::SPOffset(2047),
+            flags: MemFlags::trusted(),
+        },
+        "A32FC17E",
+        "sw a2, 2047(sp)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Sd,
+            src: xreg(12),
+            mem: AMode::RegOffset(xreg(11), 8192),
+            flags: MemFlags::trusted(),
+        },
+        "B7220000B382B20023B0C200",
+        "lui t0, 2 ; add t0, t0, a1 ; sd a2, 0(t0)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Fsw,
+            src: freg(12),
+            mem: AMode::RegOffset(xreg(11), 4),
+            flags: MemFlags::trusted(),
+        },
+        "27A2C500",
+        "fsw fa2, 4(a1)",
+    ));
+    insns.push((
+        Inst::Store {
+            op: StoreOP::Fsd,
+            src: freg(12),
+            mem: AMode::FPOffset(-16),
+            flags: MemFlags::trusted(),
+        },
+        "2738C4FE",
+        "fsd fa2, -16(fp)",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FsqrtS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "53850558",
+        "fsqrt.s fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FsqrtD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "5385055A",
+        "fsqrt.d fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtSD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "53851540",
+        "fcvt.s.d fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtDS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "53850542",
+        "fcvt.d.s fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FmvXW,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "538505E0",
+        "fmv.x.w a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FmvXD,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "538505E2",
+        "fmv.x.d a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FmvWX,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538505F0",
+        "fmv.w.x fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FmvDX,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538505F2",
+        "fmv.d.x fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtWS,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539505C0",
+        "fcvt.w.s a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtWuS,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539515C0",
+        "fcvt.wu.s a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtLS,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539525C0",
+        "fcvt.l.s a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtLuS,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539535C0",
+        "fcvt.lu.s a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtWD,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539505C2",
+        "fcvt.w.d a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtWuD,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539515C2",
+        "fcvt.wu.d a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtLD,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539525C2",
+        "fcvt.l.d a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtLuD,
+            frm: FRM::RTZ,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539535C2",
+        "fcvt.lu.d a0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtSW,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538505D0",
+        "fcvt.s.w fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtSWu,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538515D0",
+        "fcvt.s.wu fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtSL,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538525D0",
+        "fcvt.s.l fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtSLu,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538535D0",
+        "fcvt.s.lu fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtDW,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538505D2",
+        "fcvt.d.w fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtDWu,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538515D2",
+        "fcvt.d.wu fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtDL,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538525D2",
+        "fcvt.d.l fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRR {
+            alu_op: FpuOPRR::FcvtDLu,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs: xreg(11),
+        },
+        "538535D2",
+        "fcvt.d.lu fa0, a1",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FaddS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C500",
+        "fadd.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsubS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C508",
+        "fsub.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FmulS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C510",
+        "fmul.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FdivS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C518",
+        "fdiv.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C520",
+        "fsgnj.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjnS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C520",
+        "fsgnjn.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjxS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "53A5C520",
+        "fsgnjx.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FminS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C528",
+        "fmin.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FmaxS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C528",
+        "fmax.s fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FaddD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C502",
+        "fadd.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsubD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C50A",
+        "fsub.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FmulD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C512",
+        "fmul.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FdivD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C51A",
+        "fdiv.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C522",
+        "fsgnj.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjnD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C522",
+        "fsgnjn.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FsgnjxD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "53A5C522",
+        "fsgnjx.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FminD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C52A",
+        "fmin.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FmaxD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C52A",
+        "fmax.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FaddD,
+            frm: FRM::Dyn,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "53F5C502",
+        "fadd.d fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FeqS,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "53A5C5A0",
+        "feq.s a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FltS,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C5A0",
+        "flt.s a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FleS,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C5A0",
+        "fle.s a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FeqD,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "53A5C5A2",
+        "feq.d a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FltD,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5395C5A2",
+        "flt.d a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRR {
+            alu_op: FpuOPRRR::FleD,
+            frm: FRM::RNE,
+            rd: writable_xreg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "5385C5A2",
+        "fle.d a0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FpuRRRR {
+            alu_op: FpuOPRRRR::FmaddS,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+            rs3: freg(13),
+        },
+        "4385C568",
+        "fmadd.s fa0, fa1, fa2, fa3",
+    ));
+    insns.push((
+        Inst::FpuRRRR {
+            alu_op: FpuOPRRRR::FmaddD,
+            frm: FRM::RNE,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+            rs3: freg(13),
+        },
+        "4385C56A",
+        "fmadd.d fa0, fa1, fa2, fa3",
+    ));
+    insns.push((
+        Inst::Mov {
+            rd: writable_xreg(10),
+            rm: xreg(11),
+            ty: I64,
+        },
+        "13850500",
+        "mv a0, a1",
+    ));
+    insns.push((
+        Inst::Mov {
+            rd: writable_freg(10),
+            rm: freg(11),
+            ty: F64,
+        },
+        "5385B522",
+        "fmv.d fa0, fa1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: true,
+            from_bits: 8,
+            to_bits: 64,
+        },
+        "1395850313558543",
+        "sext8_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: true,
+            from_bits: 16,
+            to_bits: 64,
+        },
+        "1395050313550543",
+        "sext16_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: true,
+            from_bits: 32,
+            to_bits: 64,
+        },
+        "1B850500",
+        "sext32_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: false,
+            from_bits: 8,
+            to_bits: 64,
+        },
+        "13F5F50F",
+        "uext8_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: false,
+            from_bits: 16,
+            to_bits: 64,
+        },
+        "1395050313550503",
+        "uext16_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Extend {
+            rd: writable_xreg(10),
+            rn: xreg(11),
+            signed: false,
+            from_bits: 32,
+            to_bits: 64,
+        },
+        "1395050213550502",
+        "uext32_64 a0, a1",
+    ));
+    insns.push((
+        Inst::Select {
+            rd: writable_xreg(10),
+            ty: I64,
+            cond: IntegerCompare {
+                kind: IntCC::SignedLessThan,
+                rs1: xreg(11),
+                rs2: xreg(12),
+            },
+            x: xreg(13),
+            y: xreg(14),
+        },
+        "63C6C500130507006F00800013850600",
+        "select.i64 a0, a3, a4 ## blt a1, a2",
+    ));
+    insns.push((
+        Inst::Select {
+            rd: writable_freg(10),
+            ty: F64,
+            cond: IntegerCompare {
+                kind: IntCC::NotEqual,
+                rs1: xreg(11),
+                rs2: zero_reg(),
+            },
+            x: freg(13),
+            y: freg(14),
+        },
+        "639605005305E7226F0080005385D622",
+        "select.f64 fa0, fa3, fa4 ## bne a1, zero",
+    ));
+    insns.push((
+        Inst::BitCount {
+            op: BitCountOp::Clz,
+            ty: I64,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "93920500130500046388020093D212001305F5FF6FF05FFF",
+        "clz.i64 a0, a1",
+    ));
+    insns.push((
+        Inst::BitCount {
+            op: BitCountOp::Ctz,
+            ty: I32,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "939205021305000263880200939212001305F5FF6FF05FFF",
+        "ctz.i32 a0, a1",
+    ));
+    insns.push((
+        Inst::BitCount {
+            op: BitCountOp::Popcnt,
+            ty: I16,
+            rd: writable_xreg(10),
+            rs: xreg(11),
+        },
+        "9392050313050000638A020063D4020013051500939212006FF01FFF",
+        "popcnt.i16 a0, a1",
+    ));
+    insns.push((
+        Inst::FpuRound {
+            op: FpuRoundOp::Floor,
+            ty: F32,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "538305E09312130293D282039382A2F6530503F063D20202D32225C053A522D0D30205E01353F3011313F301B3E26200538502F06F0080005305A528",
+        "floor.f32 fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRound {
+            op: FpuRoundOp::Ceil,
+            ty: F64,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "538305E29312130093D252039382D2BC530503F263D20202D33225C253B522D2D30205E21353F3031313F303B3E26200538502F26F0080005305A52A",
+        "ceil.f64 fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRound {
+            op: FpuRoundOp::Trunc,
+            ty: F64,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "538305E29312130093D252039382D2BC530503F263D20202D31225C2539522D2D30205E21353F3031313F303B3E26200538502F26F0080005305A52A",
+        "trunc.f64 fa0, fa1",
+    ));
+    insns.push((
+        Inst::FpuRound {
+            op: FpuRoundOp::Nearest,
+            ty: F32,
+            rd: writable_freg(10),
+            rs: freg(11),
+        },
+        "538305E09312130293D282039382A2F6530503F063D20202D30225C0538522D0D30205E01353F3011313F301B3E26200538502F06F0080005305A528",
+        "nearest.f32 fa0, fa1",
+    ));
+    insns.push((
+        Inst::FcvtToInt {
+            is_sat: false,
+            is_signed: true,
+            in_ty: F64,
+            out_ty: I32,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "D3A2B5A263940200731000C073101000539505C2F322100093F2020163840200731000C0",
+        "fcvt_to_sint.i32.f64 a0, fa1",
+    ));
+    insns.push((
+        Inst::FcvtToInt {
+            is_sat: false,
+            is_signed: false,
+            in_ty: F32,
+            out_ty: I64,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "D3A2B5A063940200731000C073101000539535C0F322100093F2020163840200731000C0",
+        "fcvt_to_uint.i64.f32 a0, fa1",
+    ));
+    insns.push((
+        Inst::FcvtToInt {
+            is_sat: true,
+            is_signed: true,
+            in_ty: F64,
+            out_ty: I32,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539505C2D3A2B5A2B302504033755500",
+        "fcvt_to_sint_sat.i32.f64 a0, fa1",
+    ));
+    insns.push((
+        Inst::FcvtToInt {
+            is_sat: true,
+            is_signed: false,
+            in_ty: F32,
+            out_ty: I64,
+            rd: writable_xreg(10),
+            rs: freg(11),
+        },
+        "539535C0D3A2B5A0B302504033755500",
+        "fcvt_to_uint_sat.i64.f32 a0, fa1",
+    ));
+    insns.push((
+        Inst::FloatMinMax {
+            is_min: true,
+            ty: F32,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "D3A2B5A0638A0200D322C6A0638602005385C5286F0080005385C500",
+        "fmin.f32 fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::FloatMinMax {
+            is_min: false,
+            ty: F64,
+            rd: writable_freg(10),
+            rs1: freg(11),
+            rs2: freg(12),
+        },
+        "D3A2B5A2638A0200D322C6A2638602005395C52A6F0080005385C502",
+        "fmax.f64 fa0, fa1, fa2",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Add,
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FB5C506",
+        "amoadd.d.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Swap,
+            ty: I32,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FA5C50E",
+        "amoswap.w.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Xor,
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FB5C526",
+        "amoxor.d.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Or,
+            ty: I32,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FA5C546",
+        "amoor.w.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::And,
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FB5C566",
+        "amoand.d.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Min,
+            ty: I32,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FA5C586",
+        "amomin.w.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Max,
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FB5C5A6",
+        "amomax.d.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Minu,
+            ty: I32,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FA5C5C6",
+        "amominu.w.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::Atomic {
+            op: AtomicOP::Maxu,
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "2FB5C5E6",
+        "amomaxu.d.aqrl a0, a2, (a1)",
+    ));
+    insns.push((
+        Inst::AtomicCas {
+            ty: I64,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            expected: xreg(12),
+            replacement: xreg(13),
+        },
+        "AFB205166396C2002FB3D51AE31A03FE13850200",
+        "atomic_cas.i64 a0, a2, a3, (a1)",
+    ));
+    insns.push((
+        Inst::AtomicNand {
+            ty: I32,
+            rd: writable_xreg(10),
+            addr: xreg(11),
+            src: xreg(12),
+        },
+        "AFA2051633F3C2001343F3FF2FA3651AE31803FE13850200",
+        "atomic_nand.i32 a0, a2, (a1)",
+    ));
+    insns.push((Inst::Fence, "0F003003", "fence rw, rw"));
+    insns.push((
+        Inst::Call {
+            info: Box::new(CallInfo {
+                dest: ExternalName::testcase("test0"),
+                uses: Vec::new(),
+                defs: Vec::new(),
+                opcode: Opcode::Call,
+                caller_callconv: CallConv::SystemV,
+                callee_callconv: CallConv::SystemV,
+            }),
+        },
+        "97000000E7800000",
+        "call %test0",
+    ));
+    insns.push((
+        Inst::CallInd {
+            info: Box::new(CallIndInfo {
+                rn: xreg(11),
+                uses: Vec::new(),
+                defs: Vec::new(),
+                opcode: Opcode::CallIndirect,
+                caller_callconv: CallConv::SystemV,
+                callee_callconv: CallConv::SystemV,
+            }),
+        },
+        "E7800500",
+        "jalr a1",
+    ));
+    insns.push((
+        Inst::ReturnCall {
+            dest: Box::new(ExternalName::testcase("test0")),
+            uses: Box::new([]),
+            sp_adjust: 0,
+        },
+        "1703000067000300",
+        "tail %test0",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rn: xreg(11),
+            uses: Box::new([]),
+            sp_adjust: 0,
+        },
+        "67800500",
+        "jr a1",
+    ));
+    insns.push((
+        Inst::ReturnCallInd {
+            rn: xreg(11),
+            uses: Box::new([]),
+            sp_adjust: 32,
+        },
+        "1301010267800500",
+        "addi sp, sp, 32 ; jr a1",
+    ));
+    insns.push((Inst::Ret, "67800000", "ret"));
+    insns.push((
+        Inst::Jump {
+            dest: BranchTarget::ResolvedOffset(64),
+        },
+        "6F000004",
+        "j 64",
+    ));
+    insns.push((
+        Inst::Jump {
+            dest: BranchTarget::ResolvedOffset(-8),
+        },
+        "6FF09FFF",
+        "j -8",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::Equal,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6300B5026FF0DFFF",
+        "beq a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::NotEqual,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6310B5026FF0DFFF",
+        "bne a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::SignedLessThan,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6340B5026FF0DFFF",
+        "blt a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::SignedGreaterThanOrEqual,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6350B5026FF0DFFF",
+        "bge a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::SignedGreaterThan,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "63C0A5026FF0DFFF",
+        "blt a1, a0, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::UnsignedLessThanOrEqual,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "63F0A5026FF0DFFF",
+        "bgeu a1, a0, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::UnsignedLessThan,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6360B5026FF0DFFF",
+        "bltu a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::CondBr {
+            taken: BranchTarget::ResolvedOffset(32),
+            not_taken: BranchTarget::ResolvedOffset(-4),
+            kind: IntegerCompare {
+                kind: IntCC::UnsignedGreaterThanOrEqual,
+                rs1: xreg(10),
+                rs2: xreg(11),
+            },
+        },
+        "6370B5026FF0DFFF",
+        "bgeu a0, a1, 32 ; j -4",
+    ));
+    insns.push((
+        Inst::TrapIf {
+            cc: IntegerCompare {
+                kind: IntCC::Equal,
+                rs1: xreg(10),
+                rs2: zero_reg(),
+            },
+            trap_code: TrapCode::IntegerDivisionByZero,
+        },
+        "63140500731000C0",
+        "bne a0, zero, 8 ; unimp",
+    ));
+    insns.push((
+        Inst::Udf {
+            trap_code: TrapCode::Interrupt,
+        },
+        "731000C0",
+        "unimp",
+    ));
+    insns.push((Inst::Debugtrap, "73001000", "ebreak"));
+    insns.push((
+        Inst::LoadExtName {
+            rd: writable_xreg(10),
+            name: Box::new(ExternalName::testcase("test0")),
+            offset: 8,
+        },
+        "13000000170500000335C5006F00C0000000000000000000",
+        "ld a0, 8 ; j 12 ; data %test0 + 8",
+    ));
+    insns.push((
+        Inst::LoadAddr {
+            rd: writable_xreg(10),
+            mem: AMode::RegOffset(xreg(11), 100),
+        },
+        "13854506",
+        "addi a0, a1, 100",
+    ));
+    insns.push((
+        Inst::LoadAddr {
+            rd: writable_xreg(10),
+            mem: AMode::SPOffset(0x10000),
+        },
+        "B7020100B382220013850200",
+        "lui t0, 16 ; add t0, t0, sp ; addi a0, t0, 0",
+    ));
+
+    let flags = settings::Flags::new(settings::builder());
+
+    use crate::settings::Configurable;
+    let mut isa_flag_builder = riscv64_settings::builder();
+    isa_flag_builder.enable("has_zbb").unwrap();
+    let isa_flags = riscv64_settings::Flags::new(&flags, isa_flag_builder);
+
+    let rru = create_reg_universe(&flags);
+    let emit_info = EmitInfo::new(flags, isa_flags);
+    for (insn, expected_encoding, expected_printing) in insns {
+        println!(
+            "RISC-V 64: {:?}, {}, {}",
+            insn, expected_encoding, expected_printing
+        );
+
+        // Check the printed text is as expected.
+        let actual_printing = insn.show_rru(Some(&rru));
+        assert_eq!(expected_printing, actual_printing);
+
+        let mut sink = test_utils::TestCodeSink::new();
+        let mut buffer = MachBuffer::new();
+        insn.emit(&mut buffer, &emit_info, &mut Default::default());
+        let buffer = buffer.finish();
+        buffer.emit(&mut sink);
+        let actual_encoding = &sink.stringify();
+        assert_eq!(expected_encoding, actual_encoding);
+    }
+}
+
+#[test]
+fn test_riscv64_binemit_growing_tail_calls() {
+    // With growing tail calls, every call recomputes SP from FP once it
+    // returns, as part of the call instruction itself.
+    use crate::settings::Configurable;
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_growing_tail_calls").unwrap();
+    let flags = settings::Flags::new(flag_builder);
+    let isa_flags = riscv64_settings::Flags::new(&flags, riscv64_settings::builder());
+    let emit_info = EmitInfo::new(flags, isa_flags);
+
+    let insn = Inst::CallInd {
+        info: Box::new(CallIndInfo {
+            rn: xreg(11),
+            uses: Vec::new(),
+            defs: Vec::new(),
+            opcode: Opcode::CallIndirect,
+            caller_callconv: CallConv::SystemV,
+            callee_callconv: CallConv::SystemV,
+        }),
+    };
+    let mut sink = test_utils::TestCodeSink::new();
+    let mut buffer = MachBuffer::new();
+    insn.emit(&mut buffer, &emit_info, &mut Default::default());
+    let buffer = buffer.finish();
+    buffer.emit(&mut sink);
+    assert_eq!("E780050013010400", sink.stringify());
+}
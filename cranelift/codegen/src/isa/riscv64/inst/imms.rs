@@ -0,0 +1,83 @@
+//! RISC-V 64 ISA definitions: immediate constants.
+
+use regalloc::{PrettyPrint, RealRegUniverse};
+use std::string::String;
+
+/// A signed 12-bit immediate, as used by I-type and S-type instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Imm12 {
+    /// The value.
+    value: i16,
+}
+
+impl Imm12 {
+    /// Create an immediate if `value` fits in 12 signed bits.
+    pub fn maybe_from_i64(value: i64) -> Option<Imm12> {
+        if value >= -2048 && value <= 2047 {
+            Some(Imm12 {
+                value: value as i16,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Create a zero immediate of this format.
+    pub fn zero() -> Imm12 {
+        Imm12 { value: 0 }
+    }
+
+    /// The value of the immediate.
+    pub fn as_i16(&self) -> i16 {
+        self.value
+    }
+
+    /// Bits for encoding.
+    pub fn bits(&self) -> u32 {
+        (self.value as u32) & 0xfff
+    }
+}
+
+/// A 20-bit immediate, as used by `lui` and `auipc` for bits 31:12 of a
+/// 32-bit value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Imm20 {
+    /// The value, sign-extended from 20 bits.
+    value: i32,
+}
+
+impl Imm20 {
+    /// Create an immediate if `value` fits in 20 signed bits.
+    pub fn maybe_from_i64(value: i64) -> Option<Imm20> {
+        if value >= -(1 << 19) && value < (1 << 19) {
+            Some(Imm20 {
+                value: value as i32,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The value of the immediate.
+    pub fn as_i32(&self) -> i32 {
+        self.value
+    }
+
+    /// Bits for encoding.
+    pub fn bits(&self) -> u32 {
+        (self.value as u32) & 0xfffff
+    }
+}
+
+impl PrettyPrint for Imm12 {
+    fn show_rru(&self, _mb_rru: Option<&RealRegUniverse>) -> String {
+        format!("{}", self.value)
+    }
+}
+
+impl PrettyPrint for Imm20 {
+    fn show_rru(&self, _mb_rru: Option<&RealRegUniverse>) -> String {
+        // Assemblers take the unsigned form.
+        format!("{}", self.bits())
+    }
+}
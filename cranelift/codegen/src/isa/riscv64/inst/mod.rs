@@ -0,0 +1,2110 @@
+//! This module defines riscv64-specific machine instruction types.
+
+// Some variants are not constructed, but we still want them as options in the future.
+#![allow(dead_code)]
+
+use crate::binemit::CodeOffset;
+use crate::ir::types::{B1, B128, B16, B32, B64, B8, F32, F64, I128, I16, I32, I64, I8, R32, R64};
+use crate::ir::{types, ExternalName, MemFlags, Opcode, TrapCode, Type, ValueLabel};
+use crate::isa::riscv64::abi::clobbers_callee_saves;
+use crate::isa::unwind::UnwindInst;
+use crate::isa::CallConv;
+use crate::machinst::*;
+use crate::{settings, CodegenError, CodegenResult};
+
+use regalloc::{PrettyPrint, RegUsageCollector, RegUsageMapper};
+use regalloc::{RealRegUniverse, Reg, RegClass, SpillSlot, VirtualReg, Writable};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use smallvec::{smallvec, SmallVec};
+use std::string::{String, ToString};
+
+pub mod regs;
+pub use self::regs::*;
+pub mod imms;
+pub use self::imms::*;
+pub mod args;
+pub use self::args::*;
+pub mod emit;
+pub use self::emit::*;
+pub mod unwind;
+
+#[cfg(test)]
+mod emit_tests;
+
+//=============================================================================
+// Instructions (top level): definition
+
+/// An ALU operation with two register sources.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AluOPRRR {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+    Addw,
+    Subw,
+    Sllw,
+    Srlw,
+    Sraw,
+    // M extension.
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+    Mulw,
+    Divw,
+    Divuw,
+    Remw,
+    Remuw,
+    // Zbb extension.
+    Andn,
+    Orn,
+    Xnor,
+    Rol,
+    Ror,
+    Rolw,
+    Rorw,
+}
+
+/// An ALU operation with a register and a 12-bit immediate source. The shifts
+/// only use the low 6 (or 5 for the `w` forms) bits of the immediate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AluOPRRI {
+    Addi,
+    Slti,
+    Sltiu,
+    Xori,
+    Ori,
+    Andi,
+    Slli,
+    Srli,
+    Srai,
+    Addiw,
+    Slliw,
+    Srliw,
+    Sraiw,
+    // Zbb extension.
+    Rori,
+    Roriw,
+}
+
+/// A unary ALU operation, all from the Zbb extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AluOPRR {
+    Clz,
+    Ctz,
+    Cpop,
+    Clzw,
+    Ctzw,
+    Cpopw,
+    SextB,
+    SextH,
+    ZextH,
+}
+
+/// A load. Loads of integers narrower than 64 bits extend the value to 64
+/// bits, as given by the operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LoadOP {
+    Lb,
+    Lh,
+    Lw,
+    Ld,
+    Lbu,
+    Lhu,
+    Lwu,
+    Flw,
+    Fld,
+}
+
+impl LoadOP {
+    /// The load of a value of type `ty`, keeping narrow integers sign-extended.
+    pub fn from_type(ty: Type) -> LoadOP {
+        match ty {
+            B1 | B8 | I8 => LoadOP::Lb,
+            B16 | I16 => LoadOP::Lh,
+            B32 | I32 | R32 => LoadOP::Lw,
+            B64 | I64 | R64 => LoadOP::Ld,
+            F32 => LoadOP::Flw,
+            F64 => LoadOP::Fld,
+            _ => panic!("unsupported load type {}", ty),
+        }
+    }
+}
+
+/// A store of the low bits of a register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StoreOP {
+    Sb,
+    Sh,
+    Sw,
+    Sd,
+    Fsw,
+    Fsd,
+}
+
+impl StoreOP {
+    /// The store of a value of type `ty`.
+    pub fn from_type(ty: Type) -> StoreOP {
+        match ty {
+            B1 | B8 | I8 => StoreOP::Sb,
+            B16 | I16 => StoreOP::Sh,
+            B32 | I32 | R32 => StoreOP::Sw,
+            B64 | I64 | R64 => StoreOP::Sd,
+            F32 => StoreOP::Fsw,
+            F64 => StoreOP::Fsd,
+            _ => panic!("unsupported store type {}", ty),
+        }
+    }
+}
+
+/// A floating-point operation with one register source. The conversions are
+/// named after the instructions: `FcvtWS` converts an `F32` (`s`) to a signed
+/// 32-bit integer (`w`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FpuOPRR {
+    FsqrtS,
+    FsqrtD,
+    FcvtSD,
+    FcvtDS,
+    FmvXW,
+    FmvXD,
+    FmvWX,
+    FmvDX,
+    FcvtWS,
+    FcvtWuS,
+    FcvtLS,
+    FcvtLuS,
+    FcvtWD,
+    FcvtWuD,
+    FcvtLD,
+    FcvtLuD,
+    FcvtSW,
+    FcvtSWu,
+    FcvtSL,
+    FcvtSLu,
+    FcvtDW,
+    FcvtDWu,
+    FcvtDL,
+    FcvtDLu,
+}
+
+/// A floating-point operation with two register sources. The comparisons
+/// write 0 or 1 to an integer register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FpuOPRRR {
+    FaddS,
+    FsubS,
+    FmulS,
+    FdivS,
+    FsgnjS,
+    FsgnjnS,
+    FsgnjxS,
+    FminS,
+    FmaxS,
+    FeqS,
+    FltS,
+    FleS,
+    FaddD,
+    FsubD,
+    FmulD,
+    FdivD,
+    FsgnjD,
+    FsgnjnD,
+    FsgnjxD,
+    FminD,
+    FmaxD,
+    FeqD,
+    FltD,
+    FleD,
+}
+
+/// A floating-point operation with three register sources.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FpuOPRRRR {
+    FmaddS,
+    FmaddD,
+}
+
+/// An atomic memory operation (AMO), or the load-reserved and
+/// store-conditional instructions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AtomicOP {
+    Add,
+    Swap,
+    Lr,
+    Sc,
+    Xor,
+    Or,
+    And,
+    Min,
+    Max,
+    Minu,
+    Maxu,
+}
+
+/// A bit-counting operation, implemented as a loop for targets without the
+/// Zbb extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BitCountOp {
+    Clz,
+    Ctz,
+    Popcnt,
+}
+
+/// A rounding of a float to an integral float.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FpuRoundOp {
+    Floor,
+    Ceil,
+    Trunc,
+    Nearest,
+}
+
+impl FpuRoundOp {
+    /// The rounding mode of the conversions implementing this rounding.
+    pub fn frm(self) -> FRM {
+        match self {
+            FpuRoundOp::Floor => FRM::RDN,
+            FpuRoundOp::Ceil => FRM::RUP,
+            FpuRoundOp::Trunc => FRM::RTZ,
+            FpuRoundOp::Nearest => FRM::RNE,
+        }
+    }
+}
+
+/// Additional information for (direct) Call instructions, left out of line to lower the size of
+/// the Inst enum.
+#[derive(Clone, Debug)]
+pub struct CallInfo {
+    pub dest: ExternalName,
+    pub uses: Vec<Reg>,
+    pub defs: Vec<Writable<Reg>>,
+    pub opcode: Opcode,
+    pub caller_callconv: CallConv,
+    pub callee_callconv: CallConv,
+}
+
+/// Additional information for CallInd instructions, left out of line to lower the size of the Inst
+/// enum.
+#[derive(Clone, Debug)]
+pub struct CallIndInfo {
+    pub rn: Reg,
+    pub uses: Vec<Reg>,
+    pub defs: Vec<Writable<Reg>>,
+    pub opcode: Opcode,
+    pub caller_callconv: CallConv,
+    pub callee_callconv: CallConv,
+}
+
+/// Additional information for JTSequence instructions, left out of line to lower the size of the Inst
+/// enum.
+#[derive(Clone, Debug)]
+pub struct JTSequenceInfo {
+    pub default_target: BranchTarget,
+    pub targets: Vec<BranchTarget>,
+    pub targets_for_term: Vec<MachLabel>, // needed for MachTerminator.
+}
+
+/// Instruction formats.
+///
+/// Integer values narrower than 64 bits are always kept sign-extended to 64
+/// bits in registers, as the `w` instructions and the loads produce them, so
+/// that comparisons and 64-bit operations can consume them directly.
+///
+/// The multi-instruction sequences below may use the spilltmp and tmp2
+/// registers (`t0` and `t1`) as scratch registers, and write their
+/// destination only after their last read of a source, as the register
+/// allocator may assign both the same register.
+#[derive(Clone, Debug)]
+pub enum Inst {
+    /// A no-op of zero size.
+    Nop0,
+
+    /// A no-op of size four bytes (`addi zero, zero, 0`).
+    Nop4,
+
+    /// Load an upper immediate: `rd = sext(imm << 12)`.
+    Lui { rd: Writable<Reg>, imm: Imm20 },
+
+    /// Add an upper immediate to the PC: `rd = pc + sext(imm << 12)`.
+    Auipc { rd: Writable<Reg>, imm: Imm20 },
+
+    /// An ALU operation with two register sources and a register destination.
+    AluRRR {
+        alu_op: AluOPRRR,
+        rd: Writable<Reg>,
+        rs1: Reg,
+        rs2: Reg,
+    },
+
+    /// An ALU operation with a register source and an immediate source, and a
+    /// register destination.
+    AluRRImm12 {
+        alu_op: AluOPRRI,
+        rd: Writable<Reg>,
+        rs: Reg,
+        imm12: Imm12,
+    },
+
+    /// A unary ALU operation.
+    AluRR {
+        alu_op: AluOPRR,
+        rd: Writable<Reg>,
+        rs: Reg,
+    },
+
+    /// A load.
+    Load {
+        op: LoadOP,
+        rd: Writable<Reg>,
+        mem: AMode,
+        flags: MemFlags,
+    },
+
+    /// A store.
+    Store {
+        op: StoreOP,
+        src: Reg,
+        mem: AMode,
+        flags: MemFlags,
+    },
+
+    /// A floating-point operation with one source.
+    FpuRR {
+        alu_op: FpuOPRR,
+        frm: FRM,
+        rd: Writable<Reg>,
+        rs: Reg,
+    },
+
+    /// A floating-point operation with two sources.
+    FpuRRR {
+        alu_op: FpuOPRRR,
+        frm: FRM,
+        rd: Writable<Reg>,
+        rs1: Reg,
+        rs2: Reg,
+    },
+
+    /// A floating-point operation with three sources: `rd = rs1 * rs2 + rs3`.
+    FpuRRRR {
+        alu_op: FpuOPRRRR,
+        frm: FRM,
+        rd: Writable<Reg>,
+        rs1: Reg,
+        rs2: Reg,
+        rs3: Reg,
+    },
+
+    /// A register-to-register move of an integer or a float.
+    Mov {
+        rd: Writable<Reg>,
+        rm: Reg,
+        ty: Type,
+    },
+
+    /// A sign- or zero-extension of the low `from_bits` of `rn` to 64 bits,
+    /// which is a valid `to_bits` value as well.
+    Extend {
+        rd: Writable<Reg>,
+        rn: Reg,
+        signed: bool,
+        from_bits: u8,
+        to_bits: u8,
+    },
+
+    /// A conditional move: `rd = if cond { x } else { y }`.
+    Select {
+        rd: Writable<Reg>,
+        ty: Type,
+        cond: IntegerCompare,
+        x: Reg,
+        y: Reg,
+    },
+
+    /// A bit count of the `ty`-sized value in `rs`, implemented as a loop.
+    BitCount {
+        op: BitCountOp,
+        ty: Type,
+        rd: Writable<Reg>,
+        rs: Reg,
+    },
+
+    /// A rounding of the float in `rs` to an integral float.
+    FpuRound {
+        op: FpuRoundOp,
+        ty: Type,
+        rd: Writable<Reg>,
+        rs: Reg,
+    },
+
+    /// A conversion of a float to an integer, rounding towards zero. If not
+    /// saturating, this traps on NaN and on values out of range of `out_ty`.
+    FcvtToInt {
+        is_sat: bool,
+        is_signed: bool,
+        in_ty: Type,
+        out_ty: Type,
+        rd: Writable<Reg>,
+        rs: Reg,
+    },
+
+    /// The Wasm float minimum or maximum: NaN if either source is NaN, and
+    /// -0.0 is less than +0.0.
+    FloatMinMax {
+        is_min: bool,
+        ty: Type,
+        rd: Writable<Reg>,
+        rs1: Reg,
+        rs2: Reg,
+    },
+
+    /// An atomic memory operation on the `ty`-sized (`I32` or `I64`) value at
+    /// the address in `addr`, returning the old value.
+    Atomic {
+        op: AtomicOP,
+        ty: Type,
+        rd: Writable<Reg>,
+        addr: Reg,
+        src: Reg,
+    },
+
+    /// An atomic compare-and-swap loop, returning the old value.
+    AtomicCas {
+        ty: Type,
+        rd: Writable<Reg>,
+        addr: Reg,
+        expected: Reg,
+        replacement: Reg,
+    },
+
+    /// An atomic NAND loop, returning the old value.
+    AtomicNand {
+        ty: Type,
+        rd: Writable<Reg>,
+        addr: Reg,
+        src: Reg,
+    },
+
+    /// A full memory barrier (`fence rw, rw`).
+    Fence,
+
+    /// A machine call instruction: an `auipc`/`jalr` pair with a
+    /// `Reloc::RiscvCallPlt` relocation, allowing a +/- 2GB offset.
+    Call { info: Box<CallInfo> },
+    /// A machine indirect-call instruction.
+    CallInd { info: Box<CallIndInfo> },
+    /// A tail call, following the epilogue: like `Call`, but jumping through
+    /// the tmp2 register instead of linking. SP is first moved `sp_adjust`
+    /// bytes up, using the spilltmp register if that doesn't fit an immediate.
+    ReturnCall {
+        dest: Box<ExternalName>,
+        uses: Box<[Reg]>,
+        sp_adjust: u32,
+    },
+    /// An indirect tail call, following the epilogue. `rn` must not be restored by the
+    /// epilogue, and must not be the spilltmp register.
+    ReturnCallInd {
+        rn: Reg,
+        uses: Box<[Reg]>,
+        sp_adjust: u32,
+    },
+
+    // ---- branches (exactly one must appear at end of BB) ----
+    /// A machine return instruction.
+    Ret,
+
+    /// A placeholder instruction, generating no code, meaning that a function epilogue must be
+    /// inserted there.
+    EpiloguePlaceholder,
+
+    /// An unconditional branch.
+    Jump { dest: BranchTarget },
+
+    /// An unconditional branch after a call, recording `landing_pad` as where
+    /// execution resumes instead if the stack unwinds through that call.
+    JumpUnwind {
+        dest: BranchTarget,
+        landing_pad: BranchTarget,
+    },
+
+    /// A conditional branch. Contains two targets; at emission time, both are emitted, but
+    /// the MachBuffer knows to truncate the trailing branch if fallthrough. We optimize the
+    /// choice of taken/not_taken (inverting the branch polarity as needed) based on the
+    /// fallthrough at the time of lowering.
+    CondBr {
+        taken: BranchTarget,
+        not_taken: BranchTarget,
+        kind: IntegerCompare,
+    },
+
+    /// A conditional trap: execute an `unimp` if the condition is true. This is
+    /// one VCode instruction because it uses embedded control flow; it is
+    /// logically a single-in, single-out region, but needs to appear as one
+    /// unit to the register allocator.
+    TrapIf {
+        cc: IntegerCompare,
+        trap_code: TrapCode,
+    },
+
+    /// An instruction guaranteed to always be undefined and to trigger an illegal instruction at
+    /// runtime.
+    Udf { trap_code: TrapCode },
+
+    /// A breakpoint (`ebreak`).
+    Debugtrap,
+
+    /// An indirect branch through a register, augmented with set of all
+    /// possible successors.
+    IndirectBr { rn: Reg, targets: Vec<MachLabel> },
+
+    /// Jump-table sequence, as one compound instruction (see note in lower.rs
+    /// for rationale). Jumps to the default target if `ridx` is out of range.
+    JTSequence {
+        info: Box<JTSequenceInfo>,
+        ridx: Reg,
+    },
+
+    /// Load an inline symbol reference with relocation.
+    LoadExtName {
+        rd: Writable<Reg>,
+        name: Box<ExternalName>,
+        offset: i64,
+    },
+
+    /// Load address referenced by `mem` into `rd`.
+    LoadAddr { rd: Writable<Reg>, mem: AMode },
+
+    /// Marker, no-op in generated code: SP "virtual offset" is adjusted. This
+    /// controls how AMode::NominalSPOffset args are lowered.
+    VirtualSPOffsetAdj { offset: i64 },
+
+    /// A definition of a value label.
+    ValueLabelMarker { reg: Reg, label: ValueLabel },
+
+    /// An unwind pseudoinstruction describing the state of the
+    /// machine at this program point.
+    Unwind { inst: UnwindInst },
+}
+
+/// The ISA extension an instruction requires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InstructionSet {
+    /// The base ISA with the G extensions, which is always available.
+    Base,
+    /// The Zbb basic bit-manipulation extension.
+    Zbb,
+}
+
+#[test]
+fn inst_size_test() {
+    // This test will help with unintentionally growing the size
+    // of the Inst enum.
+    assert_eq!(32, std::mem::size_of::<Inst>());
+}
+
+impl Inst {
+    /// The ISA extension this instruction requires.
+    pub(crate) fn available_in_isa(&self) -> InstructionSet {
+        match self {
+            &Inst::AluRRR { alu_op, .. } => match alu_op {
+                AluOPRRR::Andn
+                | AluOPRRR::Orn
+                | AluOPRRR::Xnor
+                | AluOPRRR::Rol
+                | AluOPRRR::Ror
+                | AluOPRRR::Rolw
+                | AluOPRRR::Rorw => InstructionSet::Zbb,
+                _ => InstructionSet::Base,
+            },
+            &Inst::AluRRImm12 { alu_op, .. } => match alu_op {
+                AluOPRRI::Rori | AluOPRRI::Roriw => InstructionSet::Zbb,
+                _ => InstructionSet::Base,
+            },
+            &Inst::AluRR { .. } => InstructionSet::Zbb,
+            _ => InstructionSet::Base,
+        }
+    }
+
+    /// Create an instruction that loads a 64-bit integer constant.
+    pub fn load_constant(rd: Writable<Reg>, value: u64) -> SmallVec<[Inst; 4]> {
+        let value = value as i64;
+        if let Some(imm12) = Imm12::maybe_from_i64(value) {
+            // addi rd, zero, imm
+            return smallvec![Inst::AluRRImm12 {
+                alu_op: AluOPRRI::Addi,
+                rd,
+                rs: zero_reg(),
+                imm12,
+            }];
+        }
+        if value == i64::from(value as i32) {
+            // lui rd, hi; addiw rd, rd, lo
+            let lo = (value << 52) >> 52;
+            let hi = (value - lo) >> 12;
+            let mut insts = smallvec![Inst::Lui {
+                rd,
+                imm: Imm20::maybe_from_i64((hi << 44) >> 44).unwrap(),
+            }];
+            if lo != 0 {
+                insts.push(Inst::AluRRImm12 {
+                    alu_op: AluOPRRI::Addiw,
+                    rd,
+                    rs: rd.to_reg(),
+                    imm12: Imm12::maybe_from_i64(lo).unwrap(),
+                });
+            }
+            return insts;
+        }
+        // Load the value without its low 12 bits, shifted right by as much as
+        // possible, then shift it into place and add the low bits.
+        let lo = (value << 52) >> 52;
+        let hi = value.wrapping_sub(lo) >> 12;
+        let shift = 12 + hi.trailing_zeros();
+        let hi = hi >> (shift - 12);
+        let mut insts = Inst::load_constant(rd, hi as u64);
+        insts.push(Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Slli,
+            rd,
+            rs: rd.to_reg(),
+            imm12: Imm12::maybe_from_i64(i64::from(shift)).unwrap(),
+        });
+        if lo != 0 {
+            insts.push(Inst::AluRRImm12 {
+                alu_op: AluOPRRI::Addi,
+                rd,
+                rs: rd.to_reg(),
+                imm12: Imm12::maybe_from_i64(lo).unwrap(),
+            });
+        }
+        insts
+    }
+
+    /// Create an instruction that loads a value of type `ty` from memory,
+    /// sign-extending narrow integers.
+    pub fn gen_load(rd: Writable<Reg>, mem: AMode, ty: Type, flags: MemFlags) -> Inst {
+        Inst::Load {
+            op: LoadOP::from_type(ty),
+            rd,
+            mem,
+            flags,
+        }
+    }
+
+    /// Create an instruction that stores a value of type `ty` to memory.
+    pub fn gen_store(mem: AMode, src: Reg, ty: Type, flags: MemFlags) -> Inst {
+        Inst::Store {
+            op: StoreOP::from_type(ty),
+            src,
+            mem,
+            flags,
+        }
+    }
+
+    /// Create instructions that set SP to `rs` plus `offset`, using the
+    /// spilltmp register if the offset doesn't fit an immediate.
+    pub fn gen_set_sp(rs: Reg, offset: i64) -> SmallVec<[Inst; 4]> {
+        if let Some(imm12) = Imm12::maybe_from_i64(offset) {
+            smallvec![Inst::addi(writable_stack_reg(), rs, imm12)]
+        } else {
+            let tmp = writable_spilltmp_reg();
+            let mut insts = Inst::load_constant(tmp, offset as u64);
+            insts.push(Inst::AluRRR {
+                alu_op: AluOPRRR::Add,
+                rd: writable_stack_reg(),
+                rs1: rs,
+                rs2: tmp.to_reg(),
+            });
+            insts
+        }
+    }
+
+    /// Create an instruction adding a 12-bit immediate to a register.
+    pub fn addi(rd: Writable<Reg>, rs: Reg, imm12: Imm12) -> Inst {
+        Inst::AluRRImm12 {
+            alu_op: AluOPRRI::Addi,
+            rd,
+            rs,
+            imm12,
+        }
+    }
+}
+
+//=============================================================================
+// Instructions: get_regs
+
+fn memarg_regs(memarg: &AMode, collector: &mut RegUsageCollector) {
+    match memarg {
+        &AMode::RegOffset(reg, ..) => collector.add_use(reg),
+        &AMode::SPOffset(..) | &AMode::FPOffset(..) | &AMode::NominalSPOffset(..) => {}
+    }
+}
+
+fn riscv64_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
+    match inst {
+        &Inst::Nop0 | &Inst::Nop4 => {}
+        &Inst::Lui { rd, .. } | &Inst::Auipc { rd, .. } => {
+            collector.add_def(rd);
+        }
+        &Inst::AluRRR { rd, rs1, rs2, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs1);
+            collector.add_use(rs2);
+        }
+        &Inst::AluRRImm12 { rd, rs, .. } | &Inst::AluRR { rd, rs, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs);
+        }
+        &Inst::Load { rd, ref mem, .. } => {
+            collector.add_def(rd);
+            memarg_regs(mem, collector);
+        }
+        &Inst::Store { src, ref mem, .. } => {
+            collector.add_use(src);
+            memarg_regs(mem, collector);
+        }
+        &Inst::FpuRR { rd, rs, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs);
+        }
+        &Inst::FpuRRR { rd, rs1, rs2, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs1);
+            collector.add_use(rs2);
+        }
+        &Inst::FpuRRRR {
+            rd, rs1, rs2, rs3, ..
+        } => {
+            collector.add_def(rd);
+            collector.add_use(rs1);
+            collector.add_use(rs2);
+            collector.add_use(rs3);
+        }
+        &Inst::Mov { rd, rm, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rm);
+        }
+        &Inst::Extend { rd, rn, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rn);
+        }
+        &Inst::Select { rd, cond, x, y, .. } => {
+            collector.add_def(rd);
+            collector.add_use(cond.rs1);
+            collector.add_use(cond.rs2);
+            collector.add_use(x);
+            collector.add_use(y);
+        }
+        &Inst::BitCount { rd, rs, .. }
+        | &Inst::FpuRound { rd, rs, .. }
+        | &Inst::FcvtToInt { rd, rs, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs);
+        }
+        &Inst::FloatMinMax { rd, rs1, rs2, .. } => {
+            collector.add_def(rd);
+            collector.add_use(rs1);
+            collector.add_use(rs2);
+        }
+        &Inst::Atomic { rd, addr, src, .. } | &Inst::AtomicNand { rd, addr, src, .. } => {
+            collector.add_def(rd);
+            collector.add_use(addr);
+            collector.add_use(src);
+        }
+        &Inst::AtomicCas {
+            rd,
+            addr,
+            expected,
+            replacement,
+            ..
+        } => {
+            collector.add_def(rd);
+            collector.add_use(addr);
+            collector.add_use(expected);
+            collector.add_use(replacement);
+        }
+        &Inst::Fence => {}
+        &Inst::Call { ref info } => {
+            collector.add_uses(&*info.uses);
+            collector.add_defs(&*info.defs);
+        }
+        &Inst::CallInd { ref info } => {
+            collector.add_uses(&*info.uses);
+            collector.add_defs(&*info.defs);
+            collector.add_use(info.rn);
+        }
+        &Inst::ReturnCall { ref uses, .. } => {
+            collector.add_uses(uses);
+        }
+        &Inst::ReturnCallInd { rn, ref uses, .. } => {
+            collector.add_uses(uses);
+            collector.add_use(rn);
+        }
+        &Inst::Ret | &Inst::EpiloguePlaceholder => {}
+        &Inst::Jump { .. } | &Inst::JumpUnwind { .. } => {}
+        &Inst::CondBr { kind, .. } => {
+            collector.add_use(kind.rs1);
+            collector.add_use(kind.rs2);
+        }
+        &Inst::TrapIf { cc, .. } => {
+            collector.add_use(cc.rs1);
+            collector.add_use(cc.rs2);
+        }
+        &Inst::Udf { .. } | &Inst::Debugtrap => {}
+        &Inst::IndirectBr { rn, .. } => {
+            collector.add_use(rn);
+        }
+        &Inst::JTSequence { ridx, .. } => {
+            collector.add_use(ridx);
+        }
+        &Inst::LoadExtName { rd, .. } => {
+            collector.add_def(rd);
+        }
+        &Inst::LoadAddr { rd, ref mem } => {
+            collector.add_def(rd);
+            memarg_regs(mem, collector);
+        }
+        &Inst::VirtualSPOffsetAdj { .. } => {}
+        &Inst::ValueLabelMarker { reg, .. } => {
+            collector.add_use(reg);
+        }
+        &Inst::Unwind { .. } => {}
+    }
+}
+
+//=============================================================================
+// Instructions: map_regs
+
+fn riscv64_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
+    fn map_use<RUM: RegUsageMapper>(m: &RUM, r: &mut Reg) {
+        if r.is_virtual() {
+            let new = m.get_use(r.to_virtual_reg()).unwrap().to_reg();
+            *r = new;
+        }
+    }
+
+    fn map_def<RUM: RegUsageMapper>(m: &RUM, r: &mut Writable<Reg>) {
+        if r.to_reg().is_virtual() {
+            let new = m.get_def(r.to_reg().to_virtual_reg()).unwrap().to_reg();
+            *r = Writable::from_reg(new);
+        }
+    }
+
+    fn map_mem<RUM: RegUsageMapper>(m: &RUM, mem: &mut AMode) {
+        match mem {
+            &mut AMode::RegOffset(ref mut reg, ..) => map_use(m, reg),
+            &mut AMode::SPOffset(..)
+            | &mut AMode::FPOffset(..)
+            | &mut AMode::NominalSPOffset(..) => {}
+        }
+    }
+
+    fn map_cmp<RUM: RegUsageMapper>(m: &RUM, cmp: &mut IntegerCompare) {
+        map_use(m, &mut cmp.rs1);
+        map_use(m, &mut cmp.rs2);
+    }
+
+    match inst {
+        &mut Inst::Nop0 | &mut Inst::Nop4 => {}
+        &mut Inst::Lui { ref mut rd, .. } | &mut Inst::Auipc { ref mut rd, .. } => {
+            map_def(mapper, rd);
+        }
+        &mut Inst::AluRRR {
+            ref mut rd,
+            ref mut rs1,
+            ref mut rs2,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs1);
+            map_use(mapper, rs2);
+        }
+        &mut Inst::AluRRImm12 {
+            ref mut rd,
+            ref mut rs,
+            ..
+        }
+        | &mut Inst::AluRR {
+            ref mut rd,
+            ref mut rs,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs);
+        }
+        &mut Inst::Load {
+            ref mut rd,
+            ref mut mem,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_mem(mapper, mem);
+        }
+        &mut Inst::Store {
+            ref mut src,
+            ref mut mem,
+            ..
+        } => {
+            map_use(mapper, src);
+            map_mem(mapper, mem);
+        }
+        &mut Inst::FpuRR {
+            ref mut rd,
+            ref mut rs,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs);
+        }
+        &mut Inst::FpuRRR {
+            ref mut rd,
+            ref mut rs1,
+            ref mut rs2,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs1);
+            map_use(mapper, rs2);
+        }
+        &mut Inst::FpuRRRR {
+            ref mut rd,
+            ref mut rs1,
+            ref mut rs2,
+            ref mut rs3,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs1);
+            map_use(mapper, rs2);
+            map_use(mapper, rs3);
+        }
+        &mut Inst::Mov {
+            ref mut rd,
+            ref mut rm,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rm);
+        }
+        &mut Inst::Extend {
+            ref mut rd,
+            ref mut rn,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rn);
+        }
+        &mut Inst::Select {
+            ref mut rd,
+            ref mut cond,
+            ref mut x,
+            ref mut y,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_cmp(mapper, cond);
+            map_use(mapper, x);
+            map_use(mapper, y);
+        }
+        &mut Inst::BitCount {
+            ref mut rd,
+            ref mut rs,
+            ..
+        }
+        | &mut Inst::FpuRound {
+            ref mut rd,
+            ref mut rs,
+            ..
+        }
+        | &mut Inst::FcvtToInt {
+            ref mut rd,
+            ref mut rs,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs);
+        }
+        &mut Inst::FloatMinMax {
+            ref mut rd,
+            ref mut rs1,
+            ref mut rs2,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, rs1);
+            map_use(mapper, rs2);
+        }
+        &mut Inst::Atomic {
+            ref mut rd,
+            ref mut addr,
+            ref mut src,
+            ..
+        }
+        | &mut Inst::AtomicNand {
+            ref mut rd,
+            ref mut addr,
+            ref mut src,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, addr);
+            map_use(mapper, src);
+        }
+        &mut Inst::AtomicCas {
+            ref mut rd,
+            ref mut addr,
+            ref mut expected,
+            ref mut replacement,
+            ..
+        } => {
+            map_def(mapper, rd);
+            map_use(mapper, addr);
+            map_use(mapper, expected);
+            map_use(mapper, replacement);
+        }
+        &mut Inst::Fence => {}
+        &mut Inst::Call { ref mut info } => {
+            for r in info.uses.iter_mut() {
+                map_use(mapper, r);
+            }
+            for r in info.defs.iter_mut() {
+                map_def(mapper, r);
+            }
+        }
+        &mut Inst::CallInd { ref mut info, .. } => {
+            for r in info.uses.iter_mut() {
+                map_use(mapper, r);
+            }
+            for r in info.defs.iter_mut() {
+                map_def(mapper, r);
+            }
+            map_use(mapper, &mut info.rn);
+        }
+        &mut Inst::ReturnCall { ref mut uses, .. } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+        }
+        &mut Inst::ReturnCallInd {
+            ref mut rn,
+            ref mut uses,
+            ..
+        } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+            map_use(mapper, rn);
+        }
+        &mut Inst::Ret | &mut Inst::EpiloguePlaceholder => {}
+        &mut Inst::Jump { .. } | &mut Inst::JumpUnwind { .. } => {}
+        &mut Inst::CondBr { ref mut kind, .. } => {
+            map_cmp(mapper, kind);
+        }
+        &mut Inst::TrapIf { ref mut cc, .. } => {
+            map_cmp(mapper, cc);
+        }
+        &mut Inst::Udf { .. } | &mut Inst::Debugtrap => {}
+        &mut Inst::IndirectBr { ref mut rn, .. } => {
+            map_use(mapper, rn);
+        }
+        &mut Inst::JTSequence { ref mut ridx, .. } => {
+            map_use(mapper, ridx);
+        }
+        &mut Inst::LoadExtName { ref mut rd, .. } => {
+            map_def(mapper, rd);
+        }
+        &mut Inst::LoadAddr {
+            ref mut rd,
+            ref mut mem,
+        } => {
+            map_def(mapper, rd);
+            map_mem(mapper, mem);
+        }
+        &mut Inst::VirtualSPOffsetAdj { .. } => {}
+        &mut Inst::ValueLabelMarker { ref mut reg, .. } => {
+            map_use(mapper, reg);
+        }
+        &mut Inst::Unwind { .. } => {}
+    }
+}
+
+//=============================================================================
+// Instructions: misc functions and external interface
+
+impl MachInst for Inst {
+    type LabelUse = LabelUse;
+
+    fn get_regs(&self, collector: &mut RegUsageCollector) {
+        riscv64_get_regs(self, collector)
+    }
+
+    fn map_regs<RUM: RegUsageMapper>(&mut self, mapper: &RUM) {
+        riscv64_map_regs(self, mapper);
+    }
+
+    fn is_move(&self) -> Option<(Writable<Reg>, Reg)> {
+        match self {
+            &Inst::Mov { rd, rm, .. } => Some((rd, rm)),
+            _ => None,
+        }
+    }
+
+    fn is_included_in_clobbers(&self) -> bool {
+        // We exclude call instructions from the clobber-set when they are calls
+        // from caller to callee with the same ABI. Such calls cannot possibly
+        // force any new registers to be saved in the prologue, because anything
+        // that the callee clobbers, the caller is also allowed to clobber. That
+        // doesn't hold for a call that an exception can unwind through to a
+        // landing pad, which also clobbers the callee-saves.
+        match self {
+            &Inst::Call { ref info } => {
+                info.caller_callconv != info.callee_callconv || clobbers_callee_saves(&info.defs)
+            }
+            &Inst::CallInd { ref info } => {
+                info.caller_callconv != info.callee_callconv || clobbers_callee_saves(&info.defs)
+            }
+            _ => true,
+        }
+    }
+
+    fn is_epilogue_placeholder(&self) -> bool {
+        if let Inst::EpiloguePlaceholder = self {
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_term<'a>(&'a self) -> MachTerminator<'a> {
+        match self {
+            &Inst::Ret | &Inst::EpiloguePlaceholder => MachTerminator::Ret,
+            &Inst::ReturnCall { .. } | &Inst::ReturnCallInd { .. } => MachTerminator::TailCall,
+            &Inst::Jump { dest } => MachTerminator::Uncond(dest.as_label().unwrap()),
+            &Inst::JumpUnwind { dest, landing_pad } => {
+                MachTerminator::Cond(dest.as_label().unwrap(), landing_pad.as_label().unwrap())
+            }
+            &Inst::CondBr {
+                taken, not_taken, ..
+            } => MachTerminator::Cond(taken.as_label().unwrap(), not_taken.as_label().unwrap()),
+            &Inst::IndirectBr { ref targets, .. } => MachTerminator::Indirect(&targets[..]),
+            &Inst::JTSequence { ref info, .. } => {
+                MachTerminator::Indirect(&info.targets_for_term[..])
+            }
+            _ => MachTerminator::None,
+        }
+    }
+
+    fn stack_op_info(&self) -> Option<MachInstStackOpInfo> {
+        match self {
+            &Inst::VirtualSPOffsetAdj { offset } => Some(MachInstStackOpInfo::NomSPAdj(offset)),
+            &Inst::Store {
+                op: StoreOP::Sd,
+                src,
+                mem: AMode::NominalSPOffset(off),
+                ..
+            } => Some(MachInstStackOpInfo::StoreNomSPOff(src, off)),
+            &Inst::Load {
+                op: LoadOP::Ld,
+                rd,
+                mem: AMode::NominalSPOffset(off),
+                ..
+            } => Some(MachInstStackOpInfo::LoadNomSPOff(rd.to_reg(), off)),
+            _ => None,
+        }
+    }
+
+    fn gen_move(to_reg: Writable<Reg>, from_reg: Reg, ty: Type) -> Inst {
+        assert!(ty.bits() <= 64);
+        Inst::Mov {
+            rd: to_reg,
+            rm: from_reg,
+            ty,
+        }
+    }
+
+    fn gen_constant<F: FnMut(Type) -> Writable<Reg>>(
+        to_regs: ValueRegs<Writable<Reg>>,
+        value: u128,
+        ty: Type,
+        mut alloc_tmp: F,
+    ) -> SmallVec<[Inst; 4]> {
+        let to_reg = to_regs
+            .only_reg()
+            .expect("multi-reg values not supported yet");
+        let value = value as u64;
+        match ty {
+            F32 | F64 => {
+                let alu_op = if ty == F32 {
+                    FpuOPRR::FmvWX
+                } else {
+                    FpuOPRR::FmvDX
+                };
+                let (mut insts, rs) = if value == 0 {
+                    (smallvec![], zero_reg())
+                } else {
+                    let tmp = alloc_tmp(I64);
+                    (Inst::load_constant(tmp, value), tmp.to_reg())
+                };
+                insts.push(Inst::FpuRR {
+                    alu_op,
+                    frm: FRM::RNE,
+                    rd: to_reg,
+                    rs,
+                });
+                insts
+            }
+            // A `B1` is 0 or 1, like the result of the comparisons.
+            B1 => Inst::load_constant(to_reg, value & 1),
+            I8 | B8 | I16 | B16 | I32 | B32 | I64 | B64 | R32 | R64 => {
+                // Keep the value sign-extended from its type's width. Wider
+                // booleans are thus 0 or -1.
+                let shift = 64 - ty.bits();
+                let value = ((value << shift) as i64 >> shift) as u64;
+                Inst::load_constant(to_reg, value)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn gen_nop(preferred_size: usize) -> Inst {
+        if preferred_size == 0 {
+            Inst::Nop0
+        } else {
+            // We can't give a NOP (or any insn) < 4 bytes.
+            assert!(preferred_size >= 4);
+            Inst::Nop4
+        }
+    }
+
+    fn maybe_direct_reload(&self, _reg: VirtualReg, _slot: SpillSlot) -> Option<Inst> {
+        None
+    }
+
+    fn rc_for_type(ty: Type) -> CodegenResult<(&'static [RegClass], &'static [Type])> {
+        match ty {
+            I8 => Ok((&[RegClass::I64], &[I8])),
+            I16 => Ok((&[RegClass::I64], &[I16])),
+            I32 => Ok((&[RegClass::I64], &[I32])),
+            I64 => Ok((&[RegClass::I64], &[I64])),
+            B1 => Ok((&[RegClass::I64], &[B1])),
+            B8 => Ok((&[RegClass::I64], &[B8])),
+            B16 => Ok((&[RegClass::I64], &[B16])),
+            B32 => Ok((&[RegClass::I64], &[B32])),
+            B64 => Ok((&[RegClass::I64], &[B64])),
+            R32 => panic!("32-bit reftype pointer should never be seen on riscv64"),
+            R64 => Ok((&[RegClass::I64], &[R64])),
+            F32 => Ok((&[RegClass::F64], &[F32])),
+            F64 => Ok((&[RegClass::F64], &[F64])),
+            I128 => Ok((&[RegClass::I64, RegClass::I64], &[I64, I64])),
+            B128 => Ok((&[RegClass::I64, RegClass::I64], &[B64, B64])),
+            // Flags are never materialized: their producers are merged into
+            // their users (see lower.rs), but the values still need a class.
+            types::IFLAGS | types::FFLAGS => Ok((&[RegClass::I64], &[I64])),
+            _ => Err(CodegenError::Unsupported(format!(
+                "Unexpected SSA-value type: {}",
+                ty
+            ))),
+        }
+    }
+
+    fn gen_jump(target: MachLabel) -> Inst {
+        Inst::Jump {
+            dest: BranchTarget::Label(target),
+        }
+    }
+
+    fn reg_universe(flags: &settings::Flags) -> RealRegUniverse {
+        create_reg_universe(flags)
+    }
+
+    fn worst_case_size() -> CodeOffset {
+        // The maximum size, in bytes, of any `Inst`'s emitted code. The longest
+        // sequence is the 15-instruction float rounding of `FpuRound`.
+        //
+        // Note that inline jump-tables handle island/pool insertion separately, so we do not need
+        // to account for them here (otherwise the worst case would be 2^31 * 4, clearly not
+        // feasible for other reasons).
+        60
+    }
+
+    fn ref_type_regclass(_: &settings::Flags) -> RegClass {
+        RegClass::I64
+    }
+
+    fn gen_value_label_marker(label: ValueLabel, reg: Reg) -> Self {
+        Inst::ValueLabelMarker { label, reg }
+    }
+
+    fn defines_value_label(&self) -> Option<(ValueLabel, Reg)> {
+        match self {
+            Inst::ValueLabelMarker { label, reg } => Some((*label, *reg)),
+            _ => None,
+        }
+    }
+}
+
+//=============================================================================
+// Pretty-printing of instructions.
+
+impl AluOPRRR {
+    fn op_name(self) -> &'static str {
+        match self {
+            AluOPRRR::Add => "add",
+            AluOPRRR::Sub => "sub",
+            AluOPRRR::Sll => "sll",
+            AluOPRRR::Slt => "slt",
+            AluOPRRR::Sltu => "sltu",
+            AluOPRRR::Xor => "xor",
+            AluOPRRR::Srl => "srl",
+            AluOPRRR::Sra => "sra",
+            AluOPRRR::Or => "or",
+            AluOPRRR::And => "and",
+            AluOPRRR::Addw => "addw",
+            AluOPRRR::Subw => "subw",
+            AluOPRRR::Sllw => "sllw",
+            AluOPRRR::Srlw => "srlw",
+            AluOPRRR::Sraw => "sraw",
+            AluOPRRR::Mul => "mul",
+            AluOPRRR::Mulh => "mulh",
+            AluOPRRR::Mulhsu => "mulhsu",
+            AluOPRRR::Mulhu => "mulhu",
+            AluOPRRR::Div => "div",
+            AluOPRRR::Divu => "divu",
+            AluOPRRR::Rem => "rem",
+            AluOPRRR::Remu => "remu",
+            AluOPRRR::Mulw => "mulw",
+            AluOPRRR::Divw => "divw",
+            AluOPRRR::Divuw => "divuw",
+            AluOPRRR::Remw => "remw",
+            AluOPRRR::Remuw => "remuw",
+            AluOPRRR::Andn => "andn",
+            AluOPRRR::Orn => "orn",
+            AluOPRRR::Xnor => "xnor",
+            AluOPRRR::Rol => "rol",
+            AluOPRRR::Ror => "ror",
+            AluOPRRR::Rolw => "rolw",
+            AluOPRRR::Rorw => "rorw",
+        }
+    }
+}
+
+impl AluOPRRI {
+    fn op_name(self) -> &'static str {
+        match self {
+            AluOPRRI::Addi => "addi",
+            AluOPRRI::Slti => "slti",
+            AluOPRRI::Sltiu => "sltiu",
+            AluOPRRI::Xori => "xori",
+            AluOPRRI::Ori => "ori",
+            AluOPRRI::Andi => "andi",
+            AluOPRRI::Slli => "slli",
+            AluOPRRI::Srli => "srli",
+            AluOPRRI::Srai => "srai",
+            AluOPRRI::Addiw => "addiw",
+            AluOPRRI::Slliw => "slliw",
+            AluOPRRI::Srliw => "srliw",
+            AluOPRRI::Sraiw => "sraiw",
+            AluOPRRI::Rori => "rori",
+            AluOPRRI::Roriw => "roriw",
+        }
+    }
+}
+
+impl AluOPRR {
+    fn op_name(self) -> &'static str {
+        match self {
+            AluOPRR::Clz => "clz",
+            AluOPRR::Ctz => "ctz",
+            AluOPRR::Cpop => "cpop",
+            AluOPRR::Clzw => "clzw",
+            AluOPRR::Ctzw => "ctzw",
+            AluOPRR::Cpopw => "cpopw",
+            AluOPRR::SextB => "sext.b",
+            AluOPRR::SextH => "sext.h",
+            AluOPRR::ZextH => "zext.h",
+        }
+    }
+}
+
+impl LoadOP {
+    fn op_name(self) -> &'static str {
+        match self {
+            LoadOP::Lb => "lb",
+            LoadOP::Lh => "lh",
+            LoadOP::Lw => "lw",
+            LoadOP::Ld => "ld",
+            LoadOP::Lbu => "lbu",
+            LoadOP::Lhu => "lhu",
+            LoadOP::Lwu => "lwu",
+            LoadOP::Flw => "flw",
+            LoadOP::Fld => "fld",
+        }
+    }
+}
+
+impl StoreOP {
+    fn op_name(self) -> &'static str {
+        match self {
+            StoreOP::Sb => "sb",
+            StoreOP::Sh => "sh",
+            StoreOP::Sw => "sw",
+            StoreOP::Sd => "sd",
+            StoreOP::Fsw => "fsw",
+            StoreOP::Fsd => "fsd",
+        }
+    }
+}
+
+impl FpuOPRR {
+    fn op_name(self) -> &'static str {
+        match self {
+            FpuOPRR::FsqrtS => "fsqrt.s",
+            FpuOPRR::FsqrtD => "fsqrt.d",
+            FpuOPRR::FcvtSD => "fcvt.s.d",
+            FpuOPRR::FcvtDS => "fcvt.d.s",
+            FpuOPRR::FmvXW => "fmv.x.w",
+            FpuOPRR::FmvXD => "fmv.x.d",
+            FpuOPRR::FmvWX => "fmv.w.x",
+            FpuOPRR::FmvDX => "fmv.d.x",
+            FpuOPRR::FcvtWS => "fcvt.w.s",
+            FpuOPRR::FcvtWuS => "fcvt.wu.s",
+            FpuOPRR::FcvtLS => "fcvt.l.s",
+            FpuOPRR::FcvtLuS => "fcvt.lu.s",
+            FpuOPRR::FcvtWD => "fcvt.w.d",
+            FpuOPRR::FcvtWuD => "fcvt.wu.d",
+            FpuOPRR::FcvtLD => "fcvt.l.d",
+            FpuOPRR::FcvtLuD => "fcvt.lu.d",
+            FpuOPRR::FcvtSW => "fcvt.s.w",
+            FpuOPRR::FcvtSWu => "fcvt.s.wu",
+            FpuOPRR::FcvtSL => "fcvt.s.l",
+            FpuOPRR::FcvtSLu => "fcvt.s.lu",
+            FpuOPRR::FcvtDW => "fcvt.d.w",
+            FpuOPRR::FcvtDWu => "fcvt.d.wu",
+            FpuOPRR::FcvtDL => "fcvt.d.l",
+            FpuOPRR::FcvtDLu => "fcvt.d.lu",
+        }
+    }
+}
+
+impl FpuOPRRR {
+    fn op_name(self) -> &'static str {
+        match self {
+            FpuOPRRR::FaddS => "fadd.s",
+            FpuOPRRR::FsubS => "fsub.s",
+            FpuOPRRR::FmulS => "fmul.s",
+            FpuOPRRR::FdivS => "fdiv.s",
+            FpuOPRRR::FsgnjS => "fsgnj.s",
+            FpuOPRRR::FsgnjnS => "fsgnjn.s",
+            FpuOPRRR::FsgnjxS => "fsgnjx.s",
+            FpuOPRRR::FminS => "fmin.s",
+            FpuOPRRR::FmaxS => "fmax.s",
+            FpuOPRRR::FeqS => "feq.s",
+            FpuOPRRR::FltS => "flt.s",
+            FpuOPRRR::FleS => "fle.s",
+            FpuOPRRR::FaddD => "fadd.d",
+            FpuOPRRR::FsubD => "fsub.d",
+            FpuOPRRR::FmulD => "fmul.d",
+            FpuOPRRR::FdivD => "fdiv.d",
+            FpuOPRRR::FsgnjD => "fsgnj.d",
+            FpuOPRRR::FsgnjnD => "fsgnjn.d",
+            FpuOPRRR::FsgnjxD => "fsgnjx.d",
+            FpuOPRRR::FminD => "fmin.d",
+            FpuOPRRR::FmaxD => "fmax.d",
+            FpuOPRRR::FeqD => "feq.d",
+            FpuOPRRR::FltD => "flt.d",
+            FpuOPRRR::FleD => "fle.d",
+        }
+    }
+
+    /// Does the operation depend on the rounding mode?
+    pub(crate) fn has_frm(self) -> bool {
+        match self {
+            FpuOPRRR::FaddS
+            | FpuOPRRR::FsubS
+            | FpuOPRRR::FmulS
+            | FpuOPRRR::FdivS
+            | FpuOPRRR::FaddD
+            | FpuOPRRR::FsubD
+            | FpuOPRRR::FmulD
+            | FpuOPRRR::FdivD => true,
+            _ => false,
+        }
+    }
+}
+
+impl FpuOPRRRR {
+    fn op_name(self) -> &'static str {
+        match self {
+            FpuOPRRRR::FmaddS => "fmadd.s",
+            FpuOPRRRR::FmaddD => "fmadd.d",
+        }
+    }
+}
+
+impl AtomicOP {
+    fn op_name(self) -> &'static str {
+        match self {
+            AtomicOP::Add => "amoadd",
+            AtomicOP::Swap => "amoswap",
+            AtomicOP::Lr => "lr",
+            AtomicOP::Sc => "sc",
+            AtomicOP::Xor => "amoxor",
+            AtomicOP::Or => "amoor",
+            AtomicOP::And => "amoand",
+            AtomicOP::Min => "amomin",
+            AtomicOP::Max => "amomax",
+            AtomicOP::Minu => "amominu",
+            AtomicOP::Maxu => "amomaxu",
+        }
+    }
+}
+
+/// The mnemonic of the branch instruction testing `cmp`, and its operands.
+fn show_branch(cmp: IntegerCompare, mb_rru: Option<&RealRegUniverse>) -> String {
+    let (funct3, rs1, rs2) = cmp.branch_args();
+    let op = match funct3 {
+        0b000 => "beq",
+        0b001 => "bne",
+        0b100 => "blt",
+        0b101 => "bge",
+        0b110 => "bltu",
+        0b111 => "bgeu",
+        _ => unreachable!(),
+    };
+    format!("{} {}, {}", op, rs1.show_rru(mb_rru), rs2.show_rru(mb_rru))
+}
+
+/// The suffix of the `lr`, `sc` and AMO instructions for a `ty`-sized access.
+fn amo_width(ty: Type) -> &'static str {
+    match ty {
+        I32 => "w",
+        I64 => "d",
+        _ => panic!("unsupported atomic type {}", ty),
+    }
+}
+
+fn mem_finalize_for_show(
+    mem: &AMode,
+    mb_rru: Option<&RealRegUniverse>,
+    state: &EmitState,
+) -> (String, AMode) {
+    let (mem_insts, mem) = mem_finalize(mem, state);
+    let mut mem_str = mem_insts
+        .into_iter()
+        .map(|inst| inst.show_rru(mb_rru))
+        .collect::<Vec<_>>()
+        .join(" ; ");
+    if !mem_str.is_empty() {
+        mem_str += " ; ";
+    }
+
+    (mem_str, mem)
+}
+
+impl PrettyPrint for Inst {
+    fn show_rru(&self, mb_rru: Option<&RealRegUniverse>) -> String {
+        self.pretty_print(mb_rru, &mut EmitState::default())
+    }
+}
+
+impl Inst {
+    fn print_with_state(&self, mb_rru: Option<&RealRegUniverse>, state: &mut EmitState) -> String {
+        match self {
+            &Inst::Nop0 => "nop-zero-len".to_string(),
+            &Inst::Nop4 => "nop".to_string(),
+            &Inst::Lui { rd, imm } => {
+                format!(
+                    "lui {}, {}",
+                    rd.to_reg().show_rru(mb_rru),
+                    imm.show_rru(mb_rru)
+                )
+            }
+            &Inst::Auipc { rd, imm } => {
+                format!(
+                    "auipc {}, {}",
+                    rd.to_reg().show_rru(mb_rru),
+                    imm.show_rru(mb_rru)
+                )
+            }
+            &Inst::AluRRR {
+                alu_op,
+                rd,
+                rs1,
+                rs2,
+            } => format!(
+                "{} {}, {}, {}",
+                alu_op.op_name(),
+                rd.to_reg().show_rru(mb_rru),
+                rs1.show_rru(mb_rru),
+                rs2.show_rru(mb_rru)
+            ),
+            &Inst::AluRRImm12 {
+                alu_op,
+                rd,
+                rs,
+                imm12,
+            } => {
+                let rd = rd.to_reg().show_rru(mb_rru);
+                if alu_op == AluOPRRI::Addi && rs == zero_reg() {
+                    format!("li {}, {}", rd, imm12.show_rru(mb_rru))
+                } else {
+                    format!(
+                        "{} {}, {}, {}",
+                        alu_op.op_name(),
+                        rd,
+                        rs.show_rru(mb_rru),
+                        imm12.show_rru(mb_rru)
+                    )
+                }
+            }
+            &Inst::AluRR { alu_op, rd, rs } => format!(
+                "{} {}, {}",
+                alu_op.op_name(),
+                rd.to_reg().show_rru(mb_rru),
+                rs.show_rru(mb_rru)
+            ),
+            &Inst::Load {
+                op, rd, ref mem, ..
+            } => {
+                let (mem_str, mem) = mem_finalize_for_show(mem, mb_rru, state);
+                format!(
+                    "{}{} {}, {}",
+                    mem_str,
+                    op.op_name(),
+                    rd.to_reg().show_rru(mb_rru),
+                    mem.show_rru(mb_rru)
+                )
+            }
+            &Inst::Store {
+                op, src, ref mem, ..
+            } => {
+                let (mem_str, mem) = mem_finalize_for_show(mem, mb_rru, state);
+                format!(
+                    "{}{} {}, {}",
+                    mem_str,
+                    op.op_name(),
+                    src.show_rru(mb_rru),
+                    mem.show_rru(mb_rru)
+                )
+            }
+            &Inst::FpuRR { alu_op, rd, rs, .. } => format!(
+                "{} {}, {}",
+                alu_op.op_name(),
+                rd.to_reg().show_rru(mb_rru),
+                rs.show_rru(mb_rru)
+            ),
+            &Inst::FpuRRR {
+                alu_op,
+                rd,
+                rs1,
+                rs2,
+                ..
+            } => format!(
+                "{} {}, {}, {}",
+                alu_op.op_name(),
+                rd.to_reg().show_rru(mb_rru),
+                rs1.show_rru(mb_rru),
+                rs2.show_rru(mb_rru)
+            ),
+            &Inst::FpuRRRR {
+                alu_op,
+                rd,
+                rs1,
+                rs2,
+                rs3,
+                ..
+            } => format!(
+                "{} {}, {}, {}, {}",
+                alu_op.op_name(),
+                rd.to_reg().show_rru(mb_rru),
+                rs1.show_rru(mb_rru),
+                rs2.show_rru(mb_rru),
+                rs3.show_rru(mb_rru)
+            ),
+            &Inst::Mov { rd, rm, ty } => {
+                let op = if ty.is_float() { "fmv.d" } else { "mv" };
+                format!(
+                    "{} {}, {}",
+                    op,
+                    rd.to_reg().show_rru(mb_rru),
+                    rm.show_rru(mb_rru)
+                )
+            }
+            &Inst::Extend {
+                rd,
+                rn,
+                signed,
+                from_bits,
+                to_bits,
+            } => format!(
+                "{}ext{}_{} {}, {}",
+                if signed { "s" } else { "u" },
+                from_bits,
+                to_bits,
+                rd.to_reg().show_rru(mb_rru),
+                rn.show_rru(mb_rru)
+            ),
+            &Inst::Select { rd, ty, cond, x, y } => format!(
+                "select.{} {}, {}, {} ## {}",
+                ty,
+                rd.to_reg().show_rru(mb_rru),
+                x.show_rru(mb_rru),
+                y.show_rru(mb_rru),
+                show_branch(cond, mb_rru)
+            ),
+            &Inst::BitCount { op, ty, rd, rs } => {
+                let op = match op {
+                    BitCountOp::Clz => "clz",
+                    BitCountOp::Ctz => "ctz",
+                    BitCountOp::Popcnt => "popcnt",
+                };
+                format!(
+                    "{}.{} {}, {}",
+                    op,
+                    ty,
+                    rd.to_reg().show_rru(mb_rru),
+                    rs.show_rru(mb_rru)
+                )
+            }
+            &Inst::FpuRound { op, ty, rd, rs } => {
+                let op = match op {
+                    FpuRoundOp::Floor => "floor",
+                    FpuRoundOp::Ceil => "ceil",
+                    FpuRoundOp::Trunc => "trunc",
+                    FpuRoundOp::Nearest => "nearest",
+                };
+                format!(
+                    "{}.{} {}, {}",
+                    op,
+                    ty,
+                    rd.to_reg().show_rru(mb_rru),
+                    rs.show_rru(mb_rru)
+                )
+            }
+            &Inst::FcvtToInt {
+                is_sat,
+                is_signed,
+                in_ty,
+                out_ty,
+                rd,
+                rs,
+            } => format!(
+                "fcvt_to_{}int{}.{}.{} {}, {}",
+                if is_signed { "s" } else { "u" },
+                if is_sat { "_sat" } else { "" },
+                out_ty,
+                in_ty,
+                rd.to_reg().show_rru(mb_rru),
+                rs.show_rru(mb_rru)
+            ),
+            &Inst::FloatMinMax {
+                is_min,
+                ty,
+                rd,
+                rs1,
+                rs2,
+            } => format!(
+                "{}.{} {}, {}, {}",
+                if is_min { "fmin" } else { "fmax" },
+                ty,
+                rd.to_reg().show_rru(mb_rru),
+                rs1.show_rru(mb_rru),
+                rs2.show_rru(mb_rru)
+            ),
+            &Inst::Atomic {
+                op,
+                ty,
+                rd,
+                addr,
+                src,
+            } => format!(
+                "{}.{}.aqrl {}, {}, ({})",
+                op.op_name(),
+                amo_width(ty),
+                rd.to_reg().show_rru(mb_rru),
+                src.show_rru(mb_rru),
+                addr.show_rru(mb_rru)
+            ),
+            &Inst::AtomicCas {
+                ty,
+                rd,
+                addr,
+                expected,
+                replacement,
+            } => format!(
+                "atomic_cas.{} {}, {}, {}, ({})",
+                ty,
+                rd.to_reg().show_rru(mb_rru),
+                expected.show_rru(mb_rru),
+                replacement.show_rru(mb_rru),
+                addr.show_rru(mb_rru)
+            ),
+            &Inst::AtomicNand { ty, rd, addr, src } => format!(
+                "atomic_nand.{} {}, {}, ({})",
+                ty,
+                rd.to_reg().show_rru(mb_rru),
+                src.show_rru(mb_rru),
+                addr.show_rru(mb_rru)
+            ),
+            &Inst::Fence => "fence rw, rw".to_string(),
+            &Inst::Call { ref info } => format!("call {}", info.dest),
+            &Inst::CallInd { ref info } => {
+                let rn = info.rn.show_rru(mb_rru);
+                format!("jalr {}", rn)
+            }
+            &Inst::ReturnCall {
+                ref dest,
+                sp_adjust,
+                ..
+            } => {
+                let mut ret = String::new();
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        ret.push_str(&inst.show_rru(mb_rru));
+                        ret.push_str(" ; ");
+                    }
+                }
+                ret.push_str(&format!("tail {}", dest));
+                ret
+            }
+            &Inst::ReturnCallInd { rn, sp_adjust, .. } => {
+                let mut ret = String::new();
+                if sp_adjust > 0 {
+                    for inst in Inst::gen_set_sp(stack_reg(), sp_adjust as i64) {
+                        ret.push_str(&inst.show_rru(mb_rru));
+                        ret.push_str(" ; ");
+                    }
+                }
+                ret.push_str(&format!("jr {}", rn.show_rru(mb_rru)));
+                ret
+            }
+            &Inst::Ret => "ret".to_string(),
+            &Inst::EpiloguePlaceholder => "epilogue placeholder".to_string(),
+            &Inst::Jump { ref dest } => format!("j {}", dest.show_rru(mb_rru)),
+            &Inst::JumpUnwind {
+                ref dest,
+                ref landing_pad,
+            } => format!(
+                "j {} ; unwind to {}",
+                dest.show_rru(mb_rru),
+                landing_pad.show_rru(mb_rru)
+            ),
+            &Inst::CondBr {
+                ref taken,
+                ref not_taken,
+                kind,
+            } => format!(
+                "{}, {} ; j {}",
+                show_branch(kind, mb_rru),
+                taken.show_rru(mb_rru),
+                not_taken.show_rru(mb_rru)
+            ),
+            &Inst::TrapIf { cc, .. } => {
+                format!("{}, 8 ; unimp", show_branch(cc.inverse(), mb_rru))
+            }
+            &Inst::Udf { .. } => "unimp".to_string(),
+            &Inst::Debugtrap => "ebreak".to_string(),
+            &Inst::IndirectBr { rn, .. } => format!("jr {}", rn.show_rru(mb_rru)),
+            &Inst::JTSequence { ref info, ridx } => {
+                let default_target = info.default_target.show_rru(mb_rru);
+                format!(
+                    concat!(
+                        "li t0, {} ; ",
+                        "bltu {}, t0, 8 ; ",
+                        "j {} ; ",
+                        "slli t0, {}, 2 ; ",
+                        "auipc t1, 0 ; ",
+                        "add t0, t0, t1 ; ",
+                        "lw t0, 20(t0) ; ",
+                        "add t1, t1, t0 ; ",
+                        "jr 20(t1) ; ",
+                        "jt_entries {:?}"
+                    ),
+                    info.targets.len(),
+                    ridx.show_rru(mb_rru),
+                    default_target,
+                    ridx.show_rru(mb_rru),
+                    info.targets
+                )
+            }
+            &Inst::LoadExtName {
+                rd,
+                ref name,
+                offset,
+            } => {
+                let rd = rd.to_reg().show_rru(mb_rru);
+                format!("ld {}, 8 ; j 12 ; data {} + {}", rd, name, offset)
+            }
+            &Inst::LoadAddr { rd, ref mem } => {
+                let (mem_str, mem) = mem_finalize_for_show(mem, mb_rru, state);
+                let (base, off) = match mem {
+                    AMode::RegOffset(base, off) => (base, off),
+                    _ => unreachable!(),
+                };
+                format!(
+                    "{}addi {}, {}, {}",
+                    mem_str,
+                    rd.to_reg().show_rru(mb_rru),
+                    base.show_rru(mb_rru),
+                    off
+                )
+            }
+            &Inst::VirtualSPOffsetAdj { offset } => {
+                state.virtual_sp_offset += offset;
+                format!("virtual_sp_offset_adjust {}", offset)
+            }
+            &Inst::ValueLabelMarker { label, reg } => {
+                format!("value_label {:?}, {}", label, reg.show_rru(mb_rru))
+            }
+            &Inst::Unwind { ref inst } => {
+                format!("unwind {:?}", inst)
+            }
+        }
+    }
+}
+
+//=============================================================================
+// Label fixups and jump veneers.
+
+/// Different forms of label references for different instruction formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelUse {
+    /// 12-bit branch offset (conditional branches). PC-relative, offset is
+    /// imm << 1. Immediate is 12 signed bits, in bits 31:25 and 11:7 of the
+    /// instruction.
+    B12,
+    /// 20-bit branch offset (unconditional `jal`). PC-relative, offset is
+    /// imm << 1. Immediate is 20 signed bits, in bits 31:12 of the
+    /// instruction.
+    Jal20,
+    /// A PC-relative `auipc`/`jalr` pair: the high 20 bits of the offset go in
+    /// the `auipc`, and the low 12 bits, which are sign-extended, in the
+    /// `jalr`. Offset is relative to the `auipc`.
+    PCRelHiLo,
+    /// 32-bit PC relative constant offset (from address of constant itself),
+    /// signed. Used in jump tables.
+    PCRel32,
+}
+
+impl MachInstLabelUse for LabelUse {
+    /// Alignment for veneer code. Every RISC-V instruction must be 4-byte-aligned.
+    const ALIGN: CodeOffset = 4;
+
+    /// Maximum PC-relative range (positive), inclusive.
+    fn max_pos_range(self) -> CodeOffset {
+        match self {
+            // 12-bit signed immediate, left-shifted by 1.
+            LabelUse::B12 => (1 << 12) - 1,
+            // 20-bit signed immediate, left-shifted by 1.
+            LabelUse::Jal20 => (1 << 20) - 1,
+            // The high part is rounded up when the low part is negative.
+            LabelUse::PCRelHiLo => 0x7fff_f7ff,
+            LabelUse::PCRel32 => 0x7fff_ffff,
+        }
+    }
+
+    /// Maximum PC-relative range (negative).
+    fn max_neg_range(self) -> CodeOffset {
+        match self {
+            LabelUse::B12 => 1 << 12,
+            LabelUse::Jal20 => 1 << 20,
+            LabelUse::PCRelHiLo => 0x8000_0800,
+            LabelUse::PCRel32 => 0x8000_0000,
+        }
+    }
+
+    /// Size of window into code needed to do the patch.
+    fn patch_size(self) -> CodeOffset {
+        match self {
+            LabelUse::B12 | LabelUse::Jal20 | LabelUse::PCRel32 => 4,
+            LabelUse::PCRelHiLo => 8,
+        }
+    }
+
+    /// Perform the patch.
+    fn patch(self, buffer: &mut [u8], use_offset: CodeOffset, label_offset: CodeOffset) {
+        let pc_rel = (label_offset as i64) - (use_offset as i64);
+        debug_assert!(pc_rel <= self.max_pos_range() as i64);
+        debug_assert!(pc_rel >= -(self.max_neg_range() as i64));
+        let pc_rel = pc_rel as u32;
+        let insn_word = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        match self {
+            LabelUse::B12 => {
+                let insn_word = insn_word | enc_b_offset(pc_rel);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(insn_word));
+            }
+            LabelUse::Jal20 => {
+                let insn_word = insn_word | enc_jal_offset(pc_rel);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(insn_word));
+            }
+            LabelUse::PCRelHiLo => {
+                let (hi20, lo12) = split_pcrel(pc_rel as i32);
+                let auipc = insn_word | (hi20 << 12);
+                let jalr = u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+                let jalr = jalr | (lo12 << 20);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(auipc));
+                buffer[4..8].clone_from_slice(&u32::to_le_bytes(jalr));
+            }
+            LabelUse::PCRel32 => {
+                let insn_word = insn_word.wrapping_add(pc_rel);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(insn_word));
+            }
+        }
+    }
+
+    /// Is a veneer supported for this label reference type?
+    fn supports_veneer(self) -> bool {
+        match self {
+            LabelUse::B12 | LabelUse::Jal20 => true,
+            _ => false,
+        }
+    }
+
+    /// How large is the veneer, if supported?
+    fn veneer_size(self) -> CodeOffset {
+        match self {
+            LabelUse::B12 => 4,
+            LabelUse::Jal20 => 8,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Generate a veneer into the buffer, given that this veneer is at `veneer_offset`, and return
+    /// an offset and label-use for the veneer's use of the original label.
+    fn generate_veneer(
+        self,
+        buffer: &mut [u8],
+        veneer_offset: CodeOffset,
+    ) -> (CodeOffset, LabelUse) {
+        match self {
+            LabelUse::B12 => {
+                // jal zero, 0
+                let insn_word = enc_jal(writable_zero_reg(), 0);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(insn_word));
+                (veneer_offset, LabelUse::Jal20)
+            }
+            LabelUse::Jal20 => {
+                // auipc t0, 0; jalr zero, 0(t0)
+                let auipc = enc_auipc(writable_spilltmp_reg(), 0);
+                let jalr = enc_jalr(writable_zero_reg(), spilltmp_reg(), 0);
+                buffer[0..4].clone_from_slice(&u32::to_le_bytes(auipc));
+                buffer[4..8].clone_from_slice(&u32::to_le_bytes(jalr));
+                (veneer_offset, LabelUse::PCRelHiLo)
+            }
+            _ => panic!("Unsupported label-reference type for veneer generation!"),
+        }
+    }
+}
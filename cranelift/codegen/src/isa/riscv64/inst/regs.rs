@@ -0,0 +1,218 @@
+//! RISC-V 64-bit ISA definitions: registers.
+
+use crate::settings;
+use regalloc::{RealRegUniverse, Reg, RegClass, RegClassInfo, Writable, NUM_REG_CLASSES};
+
+//=============================================================================
+// Registers, the Universe thereof, and printing
+
+/// Universe indices of the integer registers: the allocatable ones follow
+/// the floating-point registers, and the reserved ones come last.
+#[rustfmt::skip]
+const XREG_INDICES: [u8; 32] = [
+    // zero, ra, sp, gp, tp, t0, t1 (reserved)
+    56, 57, 58, 59, 60, 61, 62,
+    // t2
+    40,
+    // fp (reserved)
+    63,
+    // s1
+    45,
+    // a0 - a7
+    32, 33, 34, 35, 36, 37, 38, 39,
+    // s2 - s11
+    46, 47, 48, 49, 50, 51, 52, 53, 54, 55,
+    // t3 - t6
+    41, 42, 43, 44,
+];
+
+/// Universe indices of the floating-point registers: the call-clobbered ones
+/// first.
+#[rustfmt::skip]
+const FREG_INDICES: [u8; 32] = [
+    // ft0 - ft7
+    0, 1, 2, 3, 4, 5, 6, 7,
+    // fs0 - fs1
+    20, 21,
+    // fa0 - fa7
+    8, 9, 10, 11, 12, 13, 14, 15,
+    // fs2 - fs11
+    22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+    // ft8 - ft11
+    16, 17, 18, 19,
+];
+
+#[rustfmt::skip]
+const XREG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "fp", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+#[rustfmt::skip]
+const FREG_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+/// Get a reference to an X-register (integer register).
+pub fn xreg(num: u8) -> Reg {
+    assert!(num < 32);
+    Reg::new_real(
+        RegClass::I64,
+        /* enc = */ num,
+        /* index = */ XREG_INDICES[num as usize],
+    )
+}
+
+/// Get a writable reference to an X-register.
+pub fn writable_xreg(num: u8) -> Writable<Reg> {
+    Writable::from_reg(xreg(num))
+}
+
+/// Get a reference to an F-register (floating-point register).
+pub fn freg(num: u8) -> Reg {
+    assert!(num < 32);
+    Reg::new_real(
+        RegClass::F64,
+        /* enc = */ num,
+        /* index = */ FREG_INDICES[num as usize],
+    )
+}
+
+/// Get a writable reference to an F-register.
+pub fn writable_freg(num: u8) -> Writable<Reg> {
+    Writable::from_reg(freg(num))
+}
+
+/// Get a reference to the hard-wired zero register.
+pub fn zero_reg() -> Reg {
+    xreg(0)
+}
+
+/// Get a writable reference to the zero register, to discard a result.
+pub fn writable_zero_reg() -> Writable<Reg> {
+    Writable::from_reg(zero_reg())
+}
+
+/// Get a reference to the link register (ra).
+pub fn link_reg() -> Reg {
+    xreg(1)
+}
+
+/// Get a writable reference to the link register.
+pub fn writable_link_reg() -> Writable<Reg> {
+    Writable::from_reg(link_reg())
+}
+
+/// Get a reference to the stack-pointer register.
+pub fn stack_reg() -> Reg {
+    xreg(2)
+}
+
+/// Get a writable reference to the stack-pointer register.
+pub fn writable_stack_reg() -> Writable<Reg> {
+    Writable::from_reg(stack_reg())
+}
+
+/// Get a reference to the frame-pointer register (s0).
+pub fn fp_reg() -> Reg {
+    xreg(8)
+}
+
+/// Get a writable reference to the frame-pointer register.
+pub fn writable_fp_reg() -> Writable<Reg> {
+    Writable::from_reg(fp_reg())
+}
+
+/// Get a reference to the first temporary, sometimes "spill temporary", register. This register is
+/// used to compute the address of a spill slot when a direct offset addressing mode from FP is not
+/// sufficient (+/- 2^11 bytes). We exclude this register from regalloc and reserve it for this
+/// purpose for simplicity; otherwise we need a multi-stage analysis where we first determine how
+/// many spill slots we have, then perhaps remove the reg from the pool and recompute regalloc.
+///
+/// We use t0 (x5) for this; it's also the register the prologue's stack limit check loads into.
+pub fn spilltmp_reg() -> Reg {
+    xreg(5)
+}
+
+/// Get a writable reference to the spilltmp reg.
+pub fn writable_spilltmp_reg() -> Writable<Reg> {
+    Writable::from_reg(spilltmp_reg())
+}
+
+/// Get a reference to the second temp register. We need this in some edge cases
+/// where we need both the spilltmp and another temporary, e.g. an address
+/// computation whose base is the spilltmp, or a tail call's target.
+///
+/// We use t1 (x6) for this.
+pub fn tmp2_reg() -> Reg {
+    xreg(6)
+}
+
+/// Get a writable reference to the tmp2 reg.
+pub fn writable_tmp2_reg() -> Writable<Reg> {
+    Writable::from_reg(tmp2_reg())
+}
+
+/// Create the register universe for RISC-V 64.
+pub fn create_reg_universe(_flags: &settings::Flags) -> RealRegUniverse {
+    let mut regs = vec![];
+    let mut allocable_by_class = [None; NUM_REG_CLASSES];
+
+    // Numbering Scheme: we put F-regs first, then X-regs. The X-regs exclude several registers:
+    // zero, ra, sp, gp, tp (ABI-reserved), t0 and t1 (spilltmp and tmp2) and fp.
+
+    // F-regs, the call-clobbered ones first.
+    let mut base = regs.len();
+    for &num in [
+        0, 1, 2, 3, 4, 5, 6, 7, 10, 11, 12, 13, 14, 15, 16, 17, 28, 29, 30, 31,
+    ]
+    .iter()
+    .chain([8, 9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27].iter())
+    {
+        regs.push((freg(num).to_real_reg(), FREG_NAMES[num as usize].into()));
+    }
+
+    allocable_by_class[RegClass::F64.rc_to_usize()] = Some(RegClassInfo {
+        first: base,
+        last: regs.len() - 1,
+        suggested_scratch: Some(freg(31).get_index()),
+    });
+
+    // Caller-saved X-regs in the RISC-V psABI, then the callee-saved ones.
+    base = regs.len();
+    for &num in [10, 11, 12, 13, 14, 15, 16, 17, 7, 28, 29, 30, 31]
+        .iter()
+        .chain([9, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27].iter())
+    {
+        regs.push((xreg(num).to_real_reg(), XREG_NAMES[num as usize].into()));
+    }
+
+    allocable_by_class[RegClass::I64.rc_to_usize()] = Some(RegClassInfo {
+        first: base,
+        last: regs.len() - 1,
+        suggested_scratch: Some(xreg(27).get_index()),
+    });
+
+    // Other regs, not available to the allocator.
+    let allocable = regs.len();
+    for &num in &[0, 1, 2, 3, 4, 5, 6, 8] {
+        regs.push((xreg(num).to_real_reg(), XREG_NAMES[num as usize].into()));
+    }
+
+    // Assert sanity: the indices in the register structs must match their
+    // actual indices in the array.
+    for (i, reg) in regs.iter().enumerate() {
+        assert_eq!(i, reg.0.get_index());
+    }
+
+    RealRegUniverse {
+        regs,
+        allocable,
+        allocable_by_class,
+    }
+}
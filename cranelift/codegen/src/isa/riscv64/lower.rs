@@ -0,0 +1,2020 @@
+//! Lowering rules for RISC-V 64.
+//!
+//! Integers narrower than 64 bits are kept sign-extended in registers (see
+//! the `Inst` documentation), so results which may overflow their width are
+//! extended again, and inputs are zero-extended where an operation needs it.
+//! A `B1` is 0 or 1, and wider booleans are 0 or -1.
+//!
+//! There are no condition flags: `ifcmp` and `iadd_ifcout` are merged into
+//! their users, which perform the comparison themselves.
+
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::types::*;
+use crate::ir::Inst as IRInst;
+use crate::ir::{AtomicRmwOp, InstructionData, Opcode, TrapCode, Type};
+use crate::isa::riscv64::abi::*;
+use crate::isa::riscv64::inst::*;
+use crate::isa::riscv64::settings as riscv64_settings;
+use crate::isa::riscv64::Riscv64Backend;
+use crate::machinst::lower::*;
+use crate::machinst::*;
+use crate::settings::Flags;
+use crate::{CodegenError, CodegenResult};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use regalloc::{Reg, Writable};
+use smallvec::SmallVec;
+
+//=============================================================================
+// Helpers for instruction lowering.
+
+fn ty_is_int(ty: Type) -> bool {
+    match ty {
+        B1 | B8 | B16 | B32 | B64 => true,
+        I8 | I16 | I32 | I64 | R64 => true,
+        F32 | F64 => false,
+        IFLAGS | FFLAGS => panic!("Unexpected flags type"),
+        _ => panic!("ty_is_int() on unknown type: {:?}", ty),
+    }
+}
+
+fn ty_is_float(ty: Type) -> bool {
+    !ty_is_int(ty)
+}
+
+/// Whether values of type `ty` don't fit in one register.
+fn ty_is_wide(ty: Type) -> bool {
+    ty.is_vector() || ty_bits(ty) > 64
+}
+
+fn choose_32_64<T: Copy>(ty: Type, op32: T, op64: T) -> T {
+    let bits = ty_bits(ty);
+    if bits <= 32 {
+        op32
+    } else if bits == 64 {
+        op64
+    } else {
+        panic!("choose_32_64 on > 64 bits!")
+    }
+}
+
+fn alu_rrr(alu_op: AluOPRRR, rd: Writable<Reg>, rs1: Reg, rs2: Reg) -> Inst {
+    Inst::AluRRR {
+        alu_op,
+        rd,
+        rs1,
+        rs2,
+    }
+}
+
+fn alu_rr_imm12(alu_op: AluOPRRI, rd: Writable<Reg>, rs: Reg, imm: i64) -> Inst {
+    Inst::AluRRImm12 {
+        alu_op,
+        rd,
+        rs,
+        imm12: Imm12::maybe_from_i64(imm).unwrap(),
+    }
+}
+
+fn fpu_rr(alu_op: FpuOPRR, rd: Writable<Reg>, rs: Reg) -> Inst {
+    Inst::FpuRR {
+        alu_op,
+        frm: FRM::RNE,
+        rd,
+        rs,
+    }
+}
+
+fn fpu_rrr(alu_op: FpuOPRRR, rd: Writable<Reg>, rs1: Reg, rs2: Reg) -> Inst {
+    Inst::FpuRRR {
+        alu_op,
+        frm: FRM::RNE,
+        rd,
+        rs1,
+        rs2,
+    }
+}
+
+//============================================================================
+// Lowering: convert instruction inputs to forms that we can use.
+
+/// Lower an instruction input to a 64-bit constant, if possible.
+fn input_matches_const<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<u64> {
+    let input = ctx.get_input_as_source_or_const(input.insn, input.input);
+    input.constant
+}
+
+/// Lower an instruction input to a constant, as it would be held in a
+/// register, if possible.
+fn input_matches_sext_const<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<i64> {
+    let value = input_matches_const(ctx, input)?;
+    let ty = ctx.input_ty(input.insn, input.input);
+    if ty == B1 {
+        return Some((value & 1) as i64);
+    }
+    let shift = 64 - ty_bits(ty);
+    Some(((value << shift) as i64) >> shift)
+}
+
+/// Lower an instruction input to a 12-bit signed immediate, if possible.
+fn input_matches_imm12<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<Imm12> {
+    Imm12::maybe_from_i64(input_matches_sext_const(ctx, input)?)
+}
+
+/// Checks for an instance of `op` feeding the given input.
+fn input_matches_insn<C: LowerCtx<I = Inst>>(
+    c: &mut C,
+    input: InsnInput,
+    op: Opcode,
+) -> Option<IRInst> {
+    let inputs = c.get_input_as_source_or_const(input.insn, input.input);
+    if let Some((src_inst, _)) = inputs.inst {
+        let data = c.data(src_inst);
+        if data.opcode() == op {
+            return Some(src_inst);
+        }
+    }
+    None
+}
+
+//============================================================================
+// Lowering: force instruction input into a register
+
+/// Put the given input into a register, and return the assigned register.
+fn put_input_in_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Reg {
+    let ty = ctx.input_ty(input.insn, input.input);
+    if let Some(value) = input_matches_const(ctx, input) {
+        let to_reg = ctx.alloc_tmp(ty).only_reg().unwrap();
+        lower_constant(ctx, to_reg, ty, value);
+        to_reg.to_reg()
+    } else {
+        ctx.put_input_in_regs(input.insn, input.input)
+            .only_reg()
+            .unwrap()
+    }
+}
+
+/// Put the given input into a register, or use the zero register for an
+/// integer zero.
+fn put_input_in_reg_or_zero<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Reg {
+    let ty = ctx.input_ty(input.insn, input.input);
+    if ty_is_int(ty) && input_matches_const(ctx, input) == Some(0) {
+        zero_reg()
+    } else {
+        put_input_in_reg(ctx, input)
+    }
+}
+
+/// Put the given input into a register, zero-extended to 64 bits.
+fn put_input_in_reg_zext<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Reg {
+    let ty = ctx.input_ty(input.insn, input.input);
+    let reg = put_input_in_reg(ctx, input);
+    let from_bits = ty_bits(ty);
+    if from_bits >= 64 {
+        return reg;
+    }
+    let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+    ctx.emit(Inst::Extend {
+        rd: tmp,
+        rn: reg,
+        signed: false,
+        from_bits: from_bits as u8,
+        to_bits: 64,
+    });
+    tmp.to_reg()
+}
+
+/// Put the given inputs into a register, and return an address for the
+/// memory at their sum plus `offset`.
+fn lower_address<C: LowerCtx<I = Inst>>(ctx: &mut C, addends: &[InsnInput], offset: i32) -> AMode {
+    let offset = i64::from(offset);
+    match addends.len() {
+        0 => AMode::RegOffset(zero_reg(), offset),
+        1 => AMode::RegOffset(put_input_in_reg(ctx, addends[0]), offset),
+        _ => {
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            let mut base = put_input_in_reg(ctx, addends[0]);
+            for &addend in &addends[1..] {
+                let reg = put_input_in_reg(ctx, addend);
+                ctx.emit(alu_rrr(AluOPRRR::Add, tmp, base, reg));
+                base = tmp.to_reg();
+            }
+            AMode::RegOffset(base, offset)
+        }
+    }
+}
+
+//============================================================================
+// Lowering: generate constants and extensions
+
+fn lower_constant<C: LowerCtx<I = Inst>>(ctx: &mut C, rd: Writable<Reg>, ty: Type, value: u64) {
+    for inst in Inst::gen_constant(ValueRegs::one(rd), value as u128, ty, |ty| {
+        ctx.alloc_tmp(ty).only_reg().unwrap()
+    })
+    .into_iter()
+    {
+        ctx.emit(inst);
+    }
+}
+
+/// Sign-extend the result in `rd` of an operation on `ty` that may have
+/// overflowed its width. The `w` instructions already sign-extend their
+/// 32-bit results.
+fn sext_narrow_result<C: LowerCtx<I = Inst>>(ctx: &mut C, ty: Type, rd: Writable<Reg>) {
+    let bits = ty_bits(ty);
+    if bits < 32 {
+        ctx.emit(Inst::Extend {
+            rd,
+            rn: rd.to_reg(),
+            signed: true,
+            from_bits: bits as u8,
+            to_bits: 64,
+        });
+    }
+}
+
+//============================================================================
+// Lowering: comparisons
+
+/// Lower the comparison of the two inputs of `insn` with `cc` to an
+/// `IntegerCompare`.
+fn lower_int_compare<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    cc: IntCC,
+) -> CodegenResult<IntegerCompare> {
+    let ty = ctx.input_ty(insn, 0);
+    if ty_is_wide(ty) {
+        return Err(CodegenError::Unsupported(format!(
+            "riscv64: comparison of type {} not supported",
+            ty
+        )));
+    }
+    if cc == IntCC::Overflow || cc == IntCC::NotOverflow {
+        return Err(CodegenError::Unsupported(format!(
+            "riscv64: comparison with condition {} not supported",
+            cc
+        )));
+    }
+    // Narrow values are sign-extended, which preserves both their signed and
+    // their unsigned order.
+    let rs1 = put_input_in_reg(ctx, InsnInput { insn, input: 0 });
+    let rs2 = put_input_in_reg_or_zero(ctx, InsnInput { insn, input: 1 });
+    Ok(IntegerCompare { kind: cc, rs1, rs2 })
+}
+
+/// Materialize the result of `cmp`, 0 or 1, in `rd`.
+fn lower_icmp_to_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, cmp: IntegerCompare, rd: Writable<Reg>) {
+    let IntegerCompare { kind, rs1, rs2 } = cmp;
+    match kind {
+        IntCC::Equal | IntCC::NotEqual => {
+            let diff = if rs2 == zero_reg() {
+                rs1
+            } else {
+                ctx.emit(alu_rrr(AluOPRRR::Xor, rd, rs1, rs2));
+                rd.to_reg()
+            };
+            if kind == IntCC::Equal {
+                // seqz rd, diff
+                ctx.emit(alu_rr_imm12(AluOPRRI::Sltiu, rd, diff, 1));
+            } else {
+                // snez rd, diff
+                ctx.emit(alu_rrr(AluOPRRR::Sltu, rd, zero_reg(), diff));
+            }
+        }
+        _ => {
+            let (op, swap, invert) = match kind {
+                IntCC::SignedLessThan => (AluOPRRR::Slt, false, false),
+                IntCC::SignedGreaterThan => (AluOPRRR::Slt, true, false),
+                IntCC::SignedLessThanOrEqual => (AluOPRRR::Slt, true, true),
+                IntCC::SignedGreaterThanOrEqual => (AluOPRRR::Slt, false, true),
+                IntCC::UnsignedLessThan => (AluOPRRR::Sltu, false, false),
+                IntCC::UnsignedGreaterThan => (AluOPRRR::Sltu, true, false),
+                IntCC::UnsignedLessThanOrEqual => (AluOPRRR::Sltu, true, true),
+                IntCC::UnsignedGreaterThanOrEqual => (AluOPRRR::Sltu, false, true),
+                _ => unreachable!(),
+            };
+            let (rs1, rs2) = if swap { (rs2, rs1) } else { (rs1, rs2) };
+            ctx.emit(alu_rrr(op, rd, rs1, rs2));
+            if invert {
+                ctx.emit(alu_rr_imm12(AluOPRRI::Xori, rd, rd.to_reg(), 1));
+            }
+        }
+    }
+}
+
+/// Materialize the result of the float comparison `fcmp`, 0 or 1, in `rd`.
+fn lower_fcmp_to_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, fcmp: IRInst, rd: Writable<Reg>) {
+    let cc = ctx.data(fcmp).fp_cond_code().unwrap();
+    let ty = ctx.input_ty(fcmp, 0);
+    let a = put_input_in_reg(
+        ctx,
+        InsnInput {
+            insn: fcmp,
+            input: 0,
+        },
+    );
+    let b = put_input_in_reg(
+        ctx,
+        InsnInput {
+            insn: fcmp,
+            input: 1,
+        },
+    );
+    let (feq, flt, fle) = match ty {
+        F32 => (FpuOPRRR::FeqS, FpuOPRRR::FltS, FpuOPRRR::FleS),
+        F64 => (FpuOPRRR::FeqD, FpuOPRRR::FltD, FpuOPRRR::FleD),
+        _ => panic!("Unknown fcmp type: {}", ty),
+    };
+    // The comparisons are false when either operand is NaN, so the unordered
+    // conditions are computed as the inverse of the ordered ones.
+    let invert = match cc {
+        FloatCC::Ordered | FloatCC::Unordered => {
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(fpu_rrr(feq, tmp, a, a));
+            ctx.emit(fpu_rrr(feq, rd, b, b));
+            ctx.emit(alu_rrr(AluOPRRR::And, rd, rd.to_reg(), tmp.to_reg()));
+            cc == FloatCC::Unordered
+        }
+        FloatCC::OrderedNotEqual | FloatCC::UnorderedOrEqual => {
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(fpu_rrr(flt, tmp, a, b));
+            ctx.emit(fpu_rrr(flt, rd, b, a));
+            ctx.emit(alu_rrr(AluOPRRR::Or, rd, rd.to_reg(), tmp.to_reg()));
+            cc == FloatCC::UnorderedOrEqual
+        }
+        _ => {
+            let (op, rs1, rs2, invert) = match cc {
+                FloatCC::Equal => (feq, a, b, false),
+                FloatCC::NotEqual => (feq, a, b, true),
+                FloatCC::LessThan => (flt, a, b, false),
+                FloatCC::LessThanOrEqual => (fle, a, b, false),
+                FloatCC::GreaterThan => (flt, b, a, false),
+                FloatCC::GreaterThanOrEqual => (fle, b, a, false),
+                FloatCC::UnorderedOrLessThan => (fle, b, a, true),
+                FloatCC::UnorderedOrLessThanOrEqual => (flt, b, a, true),
+                FloatCC::UnorderedOrGreaterThan => (fle, a, b, true),
+                FloatCC::UnorderedOrGreaterThanOrEqual => (flt, a, b, true),
+                _ => unreachable!(),
+            };
+            ctx.emit(fpu_rrr(op, rd, rs1, rs2));
+            invert
+        }
+    };
+    if invert {
+        ctx.emit(alu_rr_imm12(AluOPRRI::Xori, rd, rd.to_reg(), 1));
+    }
+}
+
+/// Lower the condition of a `trapif`, `trueif`, `selectif` or `brif`, whose
+/// flags input must come from an `ifcmp`.
+fn lower_flags_condition<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+) -> CodegenResult<IntegerCompare> {
+    let cc = ctx.data(insn).cond_code().unwrap();
+    let flags_input = InsnInput { insn, input: 0 };
+    if let Some(ifcmp_insn) = input_matches_insn(ctx, flags_input, Opcode::Ifcmp) {
+        lower_int_compare(ctx, ifcmp_insn, cc)
+    } else {
+        Err(CodegenError::Unsupported(
+            "riscv64: flags not produced by ifcmp".into(),
+        ))
+    }
+}
+
+//============================================================================
+// Lowering: shifts and rotates
+
+fn lower_shift<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    op: Opcode,
+    ty: Type,
+    rd: Writable<Reg>,
+    inputs: &[InsnInput],
+) {
+    let bits = ty_bits(ty) as i64;
+    let (op32, op64, opi32, opi64) = match op {
+        Opcode::Ishl => (
+            AluOPRRR::Sllw,
+            AluOPRRR::Sll,
+            AluOPRRI::Slliw,
+            AluOPRRI::Slli,
+        ),
+        Opcode::Ushr => (
+            AluOPRRR::Srlw,
+            AluOPRRR::Srl,
+            AluOPRRI::Srliw,
+            AluOPRRI::Srli,
+        ),
+        Opcode::Sshr => (
+            AluOPRRR::Sraw,
+            AluOPRRR::Sra,
+            AluOPRRI::Sraiw,
+            AluOPRRI::Srai,
+        ),
+        _ => unreachable!(),
+    };
+    // The 32- and 64-bit shifts only use the low 5 or 6 bits of the amount,
+    // as we need. Narrower values are shifted with the 64-bit instructions,
+    // with a masked amount, after a zero-extension for logical right shifts.
+    let rs = if op == Opcode::Ushr && bits < 32 {
+        put_input_in_reg_zext(ctx, inputs[0])
+    } else {
+        put_input_in_reg(ctx, inputs[0])
+    };
+    if let Some(amt) = input_matches_const(ctx, inputs[1]) {
+        let amt = (amt as i64) & (bits - 1);
+        let alu_op = if bits == 32 { opi32 } else { opi64 };
+        ctx.emit(alu_rr_imm12(alu_op, rd, rs, amt));
+    } else {
+        let amt = put_input_in_reg(ctx, inputs[1]);
+        let amt = if bits < 32 {
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(alu_rr_imm12(AluOPRRI::Andi, tmp, amt, bits - 1));
+            tmp.to_reg()
+        } else {
+            amt
+        };
+        let alu_op = if bits == 32 { op32 } else { op64 };
+        ctx.emit(alu_rrr(alu_op, rd, rs, amt));
+    }
+    if op != Opcode::Sshr {
+        sext_narrow_result(ctx, ty, rd);
+    }
+}
+
+fn lower_rotate<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    op: Opcode,
+    ty: Type,
+    rd: Writable<Reg>,
+    inputs: &[InsnInput],
+    isa_flags: &riscv64_settings::Flags,
+) {
+    let bits = ty_bits(ty) as i64;
+    let is_left = op == Opcode::Rotl;
+    let amt_const = input_matches_const(ctx, inputs[1]).map(|amt| (amt as i64) & (bits - 1));
+
+    if isa_flags.has_zbb() && bits >= 32 {
+        let rs = put_input_in_reg(ctx, inputs[0]);
+        if let Some(amt) = amt_const {
+            // There is only an immediate rotate right.
+            let amt = if is_left {
+                (bits - amt) & (bits - 1)
+            } else {
+                amt
+            };
+            let alu_op = choose_32_64(ty, AluOPRRI::Roriw, AluOPRRI::Rori);
+            ctx.emit(alu_rr_imm12(alu_op, rd, rs, amt));
+        } else {
+            let amt = put_input_in_reg(ctx, inputs[1]);
+            let alu_op = match (is_left, bits) {
+                (true, 32) => AluOPRRR::Rolw,
+                (true, _) => AluOPRRR::Rol,
+                (false, 32) => AluOPRRR::Rorw,
+                (false, _) => AluOPRRR::Ror,
+            };
+            ctx.emit(alu_rrr(alu_op, rd, rs, amt));
+        }
+        return;
+    }
+
+    // rd = (x << left) | (x >> right), on the zero-extended value, with
+    // `left` and `right` the amount and its negation, modulo the width.
+    let rs = put_input_in_reg_zext(ctx, inputs[0]);
+    let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+    if let Some(amt) = amt_const {
+        let neg_amt = (bits - amt) & (bits - 1);
+        let (left, right) = if is_left {
+            (amt, neg_amt)
+        } else {
+            (neg_amt, amt)
+        };
+        ctx.emit(alu_rr_imm12(AluOPRRI::Slli, tmp, rs, left));
+        ctx.emit(alu_rr_imm12(AluOPRRI::Srli, rd, rs, right));
+    } else {
+        let amt = put_input_in_reg(ctx, inputs[1]);
+        let neg_amt = ctx.alloc_tmp(I64).only_reg().unwrap();
+        ctx.emit(alu_rrr(AluOPRRR::Sub, neg_amt, zero_reg(), amt));
+        let amt = if bits < 64 {
+            let masked = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(alu_rr_imm12(AluOPRRI::Andi, masked, amt, bits - 1));
+            ctx.emit(alu_rr_imm12(
+                AluOPRRI::Andi,
+                neg_amt,
+                neg_amt.to_reg(),
+                bits - 1,
+            ));
+            masked.to_reg()
+        } else {
+            amt
+        };
+        let (left, right) = if is_left {
+            (amt, neg_amt.to_reg())
+        } else {
+            (neg_amt.to_reg(), amt)
+        };
+        ctx.emit(alu_rrr(AluOPRRR::Sll, tmp, rs, left));
+        ctx.emit(alu_rrr(AluOPRRR::Srl, rd, rs, right));
+    }
+    ctx.emit(alu_rrr(AluOPRRR::Or, rd, rd.to_reg(), tmp.to_reg()));
+    if bits < 64 {
+        ctx.emit(Inst::Extend {
+            rd,
+            rn: rd.to_reg(),
+            signed: true,
+            from_bits: bits as u8,
+            to_bits: 64,
+        });
+    }
+}
+
+//============================================================================
+// Lowering: bit counts
+
+fn lower_bit_count<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    op: Opcode,
+    ty: Type,
+    rd: Writable<Reg>,
+    input: InsnInput,
+    isa_flags: &riscv64_settings::Flags,
+) {
+    let bits = ty_bits(ty) as i64;
+    if !isa_flags.has_zbb() {
+        let op = match op {
+            Opcode::Clz => BitCountOp::Clz,
+            Opcode::Ctz => BitCountOp::Ctz,
+            Opcode::Popcnt => BitCountOp::Popcnt,
+            _ => unreachable!(),
+        };
+        let rs = put_input_in_reg(ctx, input);
+        ctx.emit(Inst::BitCount { op, ty, rd, rs });
+        return;
+    }
+
+    let alu_rr = |alu_op, rd, rs| Inst::AluRR { alu_op, rd, rs };
+    match (op, bits) {
+        (Opcode::Clz, 32) | (Opcode::Ctz, 32) | (Opcode::Popcnt, 32) => {
+            let alu_op = match op {
+                Opcode::Clz => AluOPRR::Clzw,
+                Opcode::Ctz => AluOPRR::Ctzw,
+                _ => AluOPRR::Cpopw,
+            };
+            let rs = put_input_in_reg(ctx, input);
+            ctx.emit(alu_rr(alu_op, rd, rs));
+        }
+        (Opcode::Clz, _) => {
+            let rs = put_input_in_reg_zext(ctx, input);
+            ctx.emit(alu_rr(AluOPRR::Clz, rd, rs));
+            if bits < 64 {
+                ctx.emit(alu_rr_imm12(AluOPRRI::Addi, rd, rd.to_reg(), bits - 64));
+            }
+        }
+        (Opcode::Ctz, _) => {
+            let rs = put_input_in_reg(ctx, input);
+            let rs = if bits < 64 {
+                // Set the bit above the value, to count up to its width.
+                let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+                lower_constant(ctx, tmp, I64, 1 << bits);
+                ctx.emit(alu_rrr(AluOPRRR::Or, tmp, tmp.to_reg(), rs));
+                tmp.to_reg()
+            } else {
+                rs
+            };
+            ctx.emit(alu_rr(AluOPRR::Ctz, rd, rs));
+        }
+        (Opcode::Popcnt, _) => {
+            let rs = put_input_in_reg_zext(ctx, input);
+            ctx.emit(alu_rr(AluOPRR::Cpop, rd, rs));
+        }
+        _ => unreachable!(),
+    }
+}
+
+//============================================================================
+// Lowering: division
+
+fn lower_div_rem<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    op: Opcode,
+    ty: Type,
+    rd: Writable<Reg>,
+    inputs: &[InsnInput],
+) {
+    let bits = ty_bits(ty) as i64;
+    let is_signed = op == Opcode::Sdiv || op == Opcode::Srem;
+    let divisor_const = input_matches_sext_const(ctx, inputs[1]);
+
+    // The instructions don't trap, so check for a zero divisor.
+    let divisor = put_input_in_reg(ctx, inputs[1]);
+    if divisor_const.map_or(true, |c| c == 0) {
+        ctx.emit_safepoint(Inst::TrapIf {
+            cc: IntegerCompare {
+                kind: IntCC::Equal,
+                rs1: divisor,
+                rs2: zero_reg(),
+            },
+            trap_code: TrapCode::IntegerDivisionByZero,
+        });
+    }
+
+    // Narrow values are divided by the 64-bit instructions; the unsigned
+    // operations need them zero-extended. The 32-bit instructions only use
+    // the low halves of the registers.
+    let (dividend, divisor) = if !is_signed && bits < 32 {
+        let dividend = put_input_in_reg_zext(ctx, inputs[0]);
+        let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+        ctx.emit(Inst::Extend {
+            rd: tmp,
+            rn: divisor,
+            signed: false,
+            from_bits: bits as u8,
+            to_bits: 64,
+        });
+        (dividend, tmp.to_reg())
+    } else {
+        (put_input_in_reg(ctx, inputs[0]), divisor)
+    };
+
+    // The signed division overflows for INT_MIN / -1, which yields INT_MIN
+    // rather than trapping. The remainder is 0 then, as we need.
+    if op == Opcode::Sdiv && divisor_const.map_or(true, |c| c == -1) {
+        // Trap if ((divisor + 1) | (dividend ^ INT_MIN)) == 0.
+        let tmp1 = ctx.alloc_tmp(I64).only_reg().unwrap();
+        let tmp2 = ctx.alloc_tmp(I64).only_reg().unwrap();
+        ctx.emit(alu_rr_imm12(AluOPRRI::Addi, tmp1, divisor, 1));
+        lower_constant(ctx, tmp2, I64, (-1i64 << (bits - 1)) as u64);
+        ctx.emit(alu_rrr(AluOPRRR::Xor, tmp2, dividend, tmp2.to_reg()));
+        ctx.emit(alu_rrr(AluOPRRR::Or, tmp1, tmp1.to_reg(), tmp2.to_reg()));
+        ctx.emit_safepoint(Inst::TrapIf {
+            cc: IntegerCompare {
+                kind: IntCC::Equal,
+                rs1: tmp1.to_reg(),
+                rs2: zero_reg(),
+            },
+            trap_code: TrapCode::IntegerOverflow,
+        });
+    }
+
+    let alu_op = match (op, bits == 32) {
+        (Opcode::Sdiv, true) => AluOPRRR::Divw,
+        (Opcode::Sdiv, false) => AluOPRRR::Div,
+        (Opcode::Udiv, true) => AluOPRRR::Divuw,
+        (Opcode::Udiv, false) => AluOPRRR::Divu,
+        (Opcode::Srem, true) => AluOPRRR::Remw,
+        (Opcode::Srem, false) => AluOPRRR::Rem,
+        (Opcode::Urem, true) => AluOPRRR::Remuw,
+        (Opcode::Urem, false) => AluOPRRR::Remu,
+        _ => unreachable!(),
+    };
+    ctx.emit(alu_rrr(alu_op, rd, dividend, divisor));
+    if !is_signed {
+        sext_narrow_result(ctx, ty, rd);
+    }
+}
+
+//=============================================================================
+// Lowering-backend trait implementation.
+
+/// Actually codegen an instruction's results into registers.
+fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    flags: &Flags,
+    isa_flags: &riscv64_settings::Flags,
+) -> CodegenResult<()> {
+    let op = ctx.data(insn).opcode();
+    let inputs: SmallVec<[InsnInput; 4]> = (0..ctx.num_inputs(insn))
+        .map(|i| InsnInput { insn, input: i })
+        .collect();
+    let outputs: SmallVec<[InsnOutput; 2]> = (0..ctx.num_outputs(insn))
+        .map(|i| InsnOutput { insn, output: i })
+        .collect();
+    let ty = if outputs.len() > 0 {
+        Some(ctx.output_ty(insn, 0))
+    } else {
+        None
+    };
+
+    // Only the instructions which just move values around support 128-bit
+    // and vector values.
+    match op {
+        Opcode::Isplit
+        | Opcode::Iconcat
+        | Opcode::Call
+        | Opcode::CallIndirect
+        | Opcode::ReturnCall
+        | Opcode::ReturnCallIndirect
+        | Opcode::Return
+        | Opcode::FallthroughReturn => {}
+        _ => {
+            let input_tys = inputs.iter().map(|i| ctx.input_ty(i.insn, i.input));
+            let output_tys = outputs.iter().map(|o| ctx.output_ty(o.insn, o.output));
+            if let Some(ty) = input_tys.chain(output_tys).find(|&ty| ty_is_wide(ty)) {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+        }
+    }
+
+    match op {
+        Opcode::Nop => {
+            // Nothing.
+        }
+
+        Opcode::Copy => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rn = put_input_in_reg(ctx, inputs[0]);
+            ctx.emit(Inst::gen_move(rd, rn, ty));
+        }
+
+        Opcode::Iconst | Opcode::Bconst | Opcode::Null | Opcode::F32const | Opcode::F64const => {
+            let value = ctx.get_constant(insn).unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_constant(ctx, rd, ty.unwrap(), value);
+        }
+
+        Opcode::Iadd | Opcode::IaddIfcout => {
+            // The carry of `iadd_ifcout` is computed by its user.
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let (x, y) = if input_matches_imm12(ctx, inputs[0]).is_some() {
+                (inputs[1], inputs[0])
+            } else {
+                (inputs[0], inputs[1])
+            };
+            let rs1 = put_input_in_reg(ctx, x);
+            if let Some(imm12) = input_matches_imm12(ctx, y) {
+                ctx.emit(Inst::AluRRImm12 {
+                    alu_op: choose_32_64(ty, AluOPRRI::Addiw, AluOPRRI::Addi),
+                    rd,
+                    rs: rs1,
+                    imm12,
+                });
+            } else {
+                let rs2 = put_input_in_reg(ctx, y);
+                let alu_op = choose_32_64(ty, AluOPRRR::Addw, AluOPRRR::Add);
+                ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            }
+            sext_narrow_result(ctx, ty, rd);
+        }
+
+        Opcode::Isub => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg_or_zero(ctx, inputs[0]);
+            let neg_imm = input_matches_sext_const(ctx, inputs[1])
+                .and_then(|c| Imm12::maybe_from_i64(c.wrapping_neg()));
+            if let Some(imm12) = neg_imm {
+                ctx.emit(Inst::AluRRImm12 {
+                    alu_op: choose_32_64(ty, AluOPRRI::Addiw, AluOPRRI::Addi),
+                    rd,
+                    rs: rs1,
+                    imm12,
+                });
+            } else {
+                let rs2 = put_input_in_reg(ctx, inputs[1]);
+                let alu_op = choose_32_64(ty, AluOPRRR::Subw, AluOPRRR::Sub);
+                ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            }
+            sext_narrow_result(ctx, ty, rd);
+        }
+
+        Opcode::Ineg => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let alu_op = choose_32_64(ty, AluOPRRR::Subw, AluOPRRR::Sub);
+            ctx.emit(alu_rrr(alu_op, rd, zero_reg(), rs));
+            sext_narrow_result(ctx, ty, rd);
+        }
+
+        Opcode::Iabs => {
+            // t = x >> 63 (arithmetic) ; rd = (x ^ t) - t
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(alu_rr_imm12(AluOPRRI::Srai, tmp, rs, 63));
+            ctx.emit(alu_rrr(AluOPRRR::Xor, rd, rs, tmp.to_reg()));
+            let alu_op = choose_32_64(ty, AluOPRRR::Subw, AluOPRRR::Sub);
+            ctx.emit(alu_rrr(alu_op, rd, rd.to_reg(), tmp.to_reg()));
+            sext_narrow_result(ctx, ty, rd);
+        }
+
+        Opcode::Imul => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg(ctx, inputs[0]);
+            let rs2 = put_input_in_reg(ctx, inputs[1]);
+            let alu_op = choose_32_64(ty, AluOPRRR::Mulw, AluOPRRR::Mul);
+            ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            sext_narrow_result(ctx, ty, rd);
+        }
+
+        Opcode::Umulhi | Opcode::Smulhi => {
+            let ty = ty.unwrap();
+            let bits = ty_bits(ty) as i64;
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let is_signed = op == Opcode::Smulhi;
+            if bits == 64 {
+                let rs1 = put_input_in_reg(ctx, inputs[0]);
+                let rs2 = put_input_in_reg(ctx, inputs[1]);
+                let alu_op = if is_signed {
+                    AluOPRRR::Mulh
+                } else {
+                    AluOPRRR::Mulhu
+                };
+                ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            } else {
+                // The full product fits in 64 bits: shift its high half down.
+                let (rs1, rs2) = if is_signed {
+                    (
+                        put_input_in_reg(ctx, inputs[0]),
+                        put_input_in_reg(ctx, inputs[1]),
+                    )
+                } else {
+                    (
+                        put_input_in_reg_zext(ctx, inputs[0]),
+                        put_input_in_reg_zext(ctx, inputs[1]),
+                    )
+                };
+                ctx.emit(alu_rrr(AluOPRRR::Mul, rd, rs1, rs2));
+                ctx.emit(alu_rr_imm12(AluOPRRI::Srai, rd, rd.to_reg(), bits));
+                if !is_signed {
+                    ctx.emit(Inst::Extend {
+                        rd,
+                        rn: rd.to_reg(),
+                        signed: true,
+                        from_bits: bits as u8,
+                        to_bits: 64,
+                    });
+                }
+            }
+        }
+
+        Opcode::Udiv | Opcode::Urem | Opcode::Sdiv | Opcode::Srem => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_div_rem(ctx, op, ty, rd, &inputs[..]);
+        }
+
+        Opcode::Band | Opcode::Bor | Opcode::Bxor => {
+            let ty = ty.unwrap();
+            if ty_is_float(ty) {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let (alu_op, alu_op_imm) = match op {
+                Opcode::Band => (AluOPRRR::And, AluOPRRI::Andi),
+                Opcode::Bor => (AluOPRRR::Or, AluOPRRI::Ori),
+                Opcode::Bxor => (AluOPRRR::Xor, AluOPRRI::Xori),
+                _ => unreachable!(),
+            };
+            let (x, y) = if input_matches_imm12(ctx, inputs[0]).is_some() {
+                (inputs[1], inputs[0])
+            } else {
+                (inputs[0], inputs[1])
+            };
+            let rs1 = put_input_in_reg(ctx, x);
+            if let Some(imm12) = input_matches_imm12(ctx, y) {
+                ctx.emit(Inst::AluRRImm12 {
+                    alu_op: alu_op_imm,
+                    rd,
+                    rs: rs1,
+                    imm12,
+                });
+            } else {
+                let rs2 = put_input_in_reg(ctx, y);
+                ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            }
+        }
+
+        Opcode::BandNot | Opcode::BorNot | Opcode::BxorNot => {
+            let ty = ty.unwrap();
+            if ty_is_float(ty) {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg(ctx, inputs[0]);
+            let rs2 = put_input_in_reg(ctx, inputs[1]);
+            if isa_flags.has_zbb() {
+                let alu_op = match op {
+                    Opcode::BandNot => AluOPRRR::Andn,
+                    Opcode::BorNot => AluOPRRR::Orn,
+                    Opcode::BxorNot => AluOPRRR::Xnor,
+                    _ => unreachable!(),
+                };
+                ctx.emit(alu_rrr(alu_op, rd, rs1, rs2));
+            } else {
+                let alu_op = match op {
+                    Opcode::BandNot => AluOPRRR::And,
+                    Opcode::BorNot => AluOPRRR::Or,
+                    Opcode::BxorNot => AluOPRRR::Xor,
+                    _ => unreachable!(),
+                };
+                let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+                ctx.emit(alu_rr_imm12(AluOPRRI::Xori, tmp, rs2, -1));
+                ctx.emit(alu_rrr(alu_op, rd, rs1, tmp.to_reg()));
+            }
+        }
+
+        Opcode::Bnot => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let mask = if ty == B1 { 1 } else { -1 };
+            ctx.emit(alu_rr_imm12(AluOPRRI::Xori, rd, rs, mask));
+        }
+
+        Opcode::Bitselect => {
+            // rd = (x & c) | (y & ~c)
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rc = put_input_in_reg(ctx, inputs[0]);
+            let rx = put_input_in_reg(ctx, inputs[1]);
+            let ry = put_input_in_reg(ctx, inputs[2]);
+            let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+            ctx.emit(alu_rrr(AluOPRRR::And, tmp, rx, rc));
+            if isa_flags.has_zbb() {
+                ctx.emit(alu_rrr(AluOPRRR::Andn, rd, ry, rc));
+            } else {
+                ctx.emit(alu_rr_imm12(AluOPRRI::Xori, rd, rc, -1));
+                ctx.emit(alu_rrr(AluOPRRR::And, rd, rd.to_reg(), ry));
+            }
+            ctx.emit(alu_rrr(AluOPRRR::Or, rd, rd.to_reg(), tmp.to_reg()));
+        }
+
+        Opcode::Ishl | Opcode::Ushr | Opcode::Sshr => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_shift(ctx, op, ty, rd, &inputs[..]);
+        }
+
+        Opcode::Rotl | Opcode::Rotr => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_rotate(ctx, op, ty, rd, &inputs[..], isa_flags);
+        }
+
+        Opcode::Clz | Opcode::Ctz | Opcode::Popcnt => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_bit_count(ctx, op, ty, rd, inputs[0], isa_flags);
+        }
+
+        Opcode::Cls | Opcode::Bitrev => {
+            return Err(CodegenError::Unsupported(format!(
+                "riscv64: {} not supported",
+                op
+            )));
+        }
+
+        Opcode::Uextend | Opcode::Sextend => {
+            let from_bits = ty_bits(ctx.input_ty(insn, 0));
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rn = put_input_in_reg(ctx, inputs[0]);
+            if op == Opcode::Sextend || from_bits == 64 {
+                // The value is already sign-extended.
+                ctx.emit(Inst::gen_move(rd, rn, I64));
+            } else {
+                ctx.emit(Inst::Extend {
+                    rd,
+                    rn,
+                    signed: false,
+                    from_bits: from_bits as u8,
+                    to_bits: 64,
+                });
+            }
+        }
+
+        Opcode::Ireduce => {
+            let ty = ty.unwrap();
+            let to_bits = ty_bits(ty);
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rn = put_input_in_reg(ctx, inputs[0]);
+            if to_bits < ty_bits(ctx.input_ty(insn, 0)) {
+                ctx.emit(Inst::Extend {
+                    rd,
+                    rn,
+                    signed: true,
+                    from_bits: to_bits as u8,
+                    to_bits: 64,
+                });
+            } else {
+                ctx.emit(Inst::gen_move(rd, rn, I64));
+            }
+        }
+
+        Opcode::Bextend | Opcode::Bmask | Opcode::Breduce | Opcode::Bint => {
+            // Wider booleans are 0 or -1, so only the conversions to and from
+            // the 0 or 1 of a `B1` (or an integer, for `bint`) need work.
+            let from_ty = ctx.input_ty(insn, 0);
+            let to_ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rn = put_input_in_reg(ctx, inputs[0]);
+            if op == Opcode::Bint || (to_ty == B1 && from_ty != B1) {
+                ctx.emit(alu_rr_imm12(AluOPRRI::Andi, rd, rn, 1));
+            } else if from_ty == B1 && to_ty != B1 {
+                ctx.emit(alu_rrr(AluOPRRR::Sub, rd, zero_reg(), rn));
+            } else {
+                ctx.emit(Inst::gen_move(rd, rn, I64));
+            }
+        }
+
+        Opcode::Icmp => {
+            let cc = ctx.data(insn).cond_code().unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let cmp = lower_int_compare(ctx, insn, cc)?;
+            lower_icmp_to_reg(ctx, cmp, rd);
+        }
+
+        Opcode::Trueif => {
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let cmp = lower_flags_condition(ctx, insn)?;
+            lower_icmp_to_reg(ctx, cmp, rd);
+        }
+
+        Opcode::Fcmp => {
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            lower_fcmp_to_reg(ctx, insn, rd);
+        }
+
+        Opcode::IsNull | Opcode::IsInvalid => {
+            // Null references are 0 and invalid ones are -1.
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rn = put_input_in_reg(ctx, inputs[0]);
+            let rn = if op == Opcode::IsInvalid {
+                let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+                ctx.emit(alu_rr_imm12(AluOPRRI::Addi, tmp, rn, 1));
+                tmp.to_reg()
+            } else {
+                rn
+            };
+            ctx.emit(alu_rr_imm12(AluOPRRI::Sltiu, rd, rn, 1));
+        }
+
+        Opcode::Select => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let cond = if let Some(icmp_insn) = input_matches_insn(ctx, inputs[0], Opcode::Icmp) {
+                let cc = ctx.data(icmp_insn).cond_code().unwrap();
+                lower_int_compare(ctx, icmp_insn, cc)?
+            } else {
+                IntegerCompare {
+                    kind: IntCC::NotEqual,
+                    rs1: put_input_in_reg(ctx, inputs[0]),
+                    rs2: zero_reg(),
+                }
+            };
+            let x = put_input_in_reg(ctx, inputs[1]);
+            let y = put_input_in_reg(ctx, inputs[2]);
+            ctx.emit(Inst::Select { rd, ty, cond, x, y });
+        }
+
+        Opcode::Selectif => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let cond = lower_flags_condition(ctx, insn)?;
+            let x = put_input_in_reg(ctx, inputs[1]);
+            let y = put_input_in_reg(ctx, inputs[2]);
+            ctx.emit(Inst::Select { rd, ty, cond, x, y });
+        }
+
+        Opcode::SelectifSpectreGuard => {
+            // This must not be a branch, which could be mispredicted:
+            // mask = -cond ; rd = y ^ ((x ^ y) & mask)
+            let ty = ty.unwrap();
+            if ty_is_float(ty) {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let cond = lower_flags_condition(ctx, insn)?;
+            let x = put_input_in_reg(ctx, inputs[1]);
+            let y = put_input_in_reg(ctx, inputs[2]);
+            let mask = ctx.alloc_tmp(I64).only_reg().unwrap();
+            lower_icmp_to_reg(ctx, cond, mask);
+            ctx.emit(alu_rrr(AluOPRRR::Sub, mask, zero_reg(), mask.to_reg()));
+            ctx.emit(alu_rrr(AluOPRRR::Xor, rd, x, y));
+            ctx.emit(alu_rrr(AluOPRRR::And, rd, rd.to_reg(), mask.to_reg()));
+            ctx.emit(alu_rrr(AluOPRRR::Xor, rd, rd.to_reg(), y));
+        }
+
+        Opcode::Load
+        | Opcode::Uload8
+        | Opcode::Sload8
+        | Opcode::Uload16
+        | Opcode::Sload16
+        | Opcode::Uload32
+        | Opcode::Sload32
+        | Opcode::LoadComplex
+        | Opcode::Uload8Complex
+        | Opcode::Sload8Complex
+        | Opcode::Uload16Complex
+        | Opcode::Sload16Complex
+        | Opcode::Uload32Complex
+        | Opcode::Sload32Complex => {
+            let off = ctx.data(insn).load_store_offset().unwrap();
+            let flags = ctx.memflags(insn).unwrap();
+            let load_op = match op {
+                Opcode::Load | Opcode::LoadComplex => LoadOP::from_type(ty.unwrap()),
+                Opcode::Uload8 | Opcode::Uload8Complex => LoadOP::Lbu,
+                Opcode::Sload8 | Opcode::Sload8Complex => LoadOP::Lb,
+                Opcode::Uload16 | Opcode::Uload16Complex => LoadOP::Lhu,
+                Opcode::Sload16 | Opcode::Sload16Complex => LoadOP::Lh,
+                Opcode::Uload32 | Opcode::Uload32Complex => LoadOP::Lwu,
+                Opcode::Sload32 | Opcode::Sload32Complex => LoadOP::Lw,
+                _ => unreachable!(),
+            };
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let mem = lower_address(ctx, &inputs[..], off);
+            ctx.emit(Inst::Load {
+                op: load_op,
+                rd,
+                mem,
+                flags,
+            });
+        }
+
+        Opcode::Store
+        | Opcode::Istore8
+        | Opcode::Istore16
+        | Opcode::Istore32
+        | Opcode::StoreComplex
+        | Opcode::Istore8Complex
+        | Opcode::Istore16Complex
+        | Opcode::Istore32Complex => {
+            let off = ctx.data(insn).load_store_offset().unwrap();
+            let flags = ctx.memflags(insn).unwrap();
+            let elem_ty = match op {
+                Opcode::Istore8 | Opcode::Istore8Complex => I8,
+                Opcode::Istore16 | Opcode::Istore16Complex => I16,
+                Opcode::Istore32 | Opcode::Istore32Complex => I32,
+                Opcode::Store | Opcode::StoreComplex => ctx.input_ty(insn, 0),
+                _ => unreachable!(),
+            };
+            let src = put_input_in_reg_or_zero(ctx, inputs[0]);
+            let mem = lower_address(ctx, &inputs[1..], off);
+            ctx.emit(Inst::gen_store(mem, src, elem_ty, flags));
+        }
+
+        Opcode::StackLoad | Opcode::StackStore => {
+            panic!("Direct stack memory access not supported; should not be used by Wasm");
+        }
+
+        Opcode::StackAddr => {
+            let (stack_slot, offset) = match *ctx.data(insn) {
+                InstructionData::StackLoad {
+                    opcode: Opcode::StackAddr,
+                    stack_slot,
+                    offset,
+                } => (stack_slot, offset),
+                _ => unreachable!(),
+            };
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let offset: i32 = offset.into();
+            let inst = ctx
+                .abi()
+                .stackslot_addr(stack_slot, u32::try_from(offset).unwrap(), rd);
+            ctx.emit(inst);
+        }
+
+        Opcode::AtomicRmw => {
+            let ty = ty.unwrap();
+            if ty != I32 && ty != I64 {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let addr = put_input_in_reg(ctx, inputs[0]);
+            let src = put_input_in_reg(ctx, inputs[1]);
+            let atomic_op = match ctx.data(insn).atomic_rmw_op().unwrap() {
+                AtomicRmwOp::Nand => {
+                    ctx.emit(Inst::AtomicNand { ty, rd, addr, src });
+                    return Ok(());
+                }
+                AtomicRmwOp::Sub => {
+                    // Add the negated value.
+                    let tmp = ctx.alloc_tmp(I64).only_reg().unwrap();
+                    ctx.emit(alu_rrr(AluOPRRR::Sub, tmp, zero_reg(), src));
+                    ctx.emit(Inst::Atomic {
+                        op: AtomicOP::Add,
+                        ty,
+                        rd,
+                        addr,
+                        src: tmp.to_reg(),
+                    });
+                    return Ok(());
+                }
+                AtomicRmwOp::Add => AtomicOP::Add,
+                AtomicRmwOp::And => AtomicOP::And,
+                AtomicRmwOp::Or => AtomicOP::Or,
+                AtomicRmwOp::Xor => AtomicOP::Xor,
+                AtomicRmwOp::Xchg => AtomicOP::Swap,
+                AtomicRmwOp::Umin => AtomicOP::Minu,
+                AtomicRmwOp::Umax => AtomicOP::Maxu,
+                AtomicRmwOp::Smin => AtomicOP::Min,
+                AtomicRmwOp::Smax => AtomicOP::Max,
+            };
+            ctx.emit(Inst::Atomic {
+                op: atomic_op,
+                ty,
+                rd,
+                addr,
+                src,
+            });
+        }
+
+        Opcode::AtomicCas => {
+            let ty = ty.unwrap();
+            if ty != I32 && ty != I64 {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} on type {} not supported",
+                    op, ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let addr = put_input_in_reg(ctx, inputs[0]);
+            let expected = put_input_in_reg(ctx, inputs[1]);
+            let replacement = put_input_in_reg(ctx, inputs[2]);
+            ctx.emit(Inst::AtomicCas {
+                ty,
+                rd,
+                addr,
+                expected,
+                replacement,
+            });
+        }
+
+        Opcode::AtomicLoad => {
+            let ty = ty.unwrap();
+            let flags = ctx.memflags(insn).unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let addr = put_input_in_reg(ctx, inputs[0]);
+            ctx.emit(Inst::Fence);
+            ctx.emit(Inst::gen_load(rd, AMode::reg(addr), ty, flags));
+            ctx.emit(Inst::Fence);
+        }
+
+        Opcode::AtomicStore => {
+            let ty = ctx.input_ty(insn, 0);
+            let flags = ctx.memflags(insn).unwrap();
+            let src = put_input_in_reg_or_zero(ctx, inputs[0]);
+            let addr = put_input_in_reg(ctx, inputs[1]);
+            ctx.emit(Inst::Fence);
+            ctx.emit(Inst::gen_store(AMode::reg(addr), src, ty, flags));
+            ctx.emit(Inst::Fence);
+        }
+
+        Opcode::Fence => {
+            ctx.emit(Inst::Fence);
+        }
+
+        Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv | Opcode::Fcopysign => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg(ctx, inputs[0]);
+            let rs2 = put_input_in_reg(ctx, inputs[1]);
+            let alu_op = match (op, ty) {
+                (Opcode::Fadd, F32) => FpuOPRRR::FaddS,
+                (Opcode::Fadd, F64) => FpuOPRRR::FaddD,
+                (Opcode::Fsub, F32) => FpuOPRRR::FsubS,
+                (Opcode::Fsub, F64) => FpuOPRRR::FsubD,
+                (Opcode::Fmul, F32) => FpuOPRRR::FmulS,
+                (Opcode::Fmul, F64) => FpuOPRRR::FmulD,
+                (Opcode::Fdiv, F32) => FpuOPRRR::FdivS,
+                (Opcode::Fdiv, F64) => FpuOPRRR::FdivD,
+                (Opcode::Fcopysign, F32) => FpuOPRRR::FsgnjS,
+                (Opcode::Fcopysign, F64) => FpuOPRRR::FsgnjD,
+                _ => panic!("Unknown op/type combination"),
+            };
+            ctx.emit(fpu_rrr(alu_op, rd, rs1, rs2));
+        }
+
+        Opcode::Fmin | Opcode::Fmax => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg(ctx, inputs[0]);
+            let rs2 = put_input_in_reg(ctx, inputs[1]);
+            ctx.emit(Inst::FloatMinMax {
+                is_min: op == Opcode::Fmin,
+                ty,
+                rd,
+                rs1,
+                rs2,
+            });
+        }
+
+        Opcode::Fneg | Opcode::Fabs => {
+            // These only change the sign bit: fsgnj{n,x} rd, rs, rs
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let alu_op = match (op, ty) {
+                (Opcode::Fneg, F32) => FpuOPRRR::FsgnjnS,
+                (Opcode::Fneg, F64) => FpuOPRRR::FsgnjnD,
+                (Opcode::Fabs, F32) => FpuOPRRR::FsgnjxS,
+                (Opcode::Fabs, F64) => FpuOPRRR::FsgnjxD,
+                _ => panic!("Unknown op/type combination"),
+            };
+            ctx.emit(fpu_rrr(alu_op, rd, rs, rs));
+        }
+
+        Opcode::Sqrt | Opcode::Fpromote | Opcode::Fdemote => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let alu_op = match (op, ty) {
+                (Opcode::Sqrt, F32) => FpuOPRR::FsqrtS,
+                (Opcode::Sqrt, F64) => FpuOPRR::FsqrtD,
+                (Opcode::Fpromote, F64) => FpuOPRR::FcvtDS,
+                (Opcode::Fdemote, F32) => FpuOPRR::FcvtSD,
+                _ => panic!("Unknown op/type combination"),
+            };
+            ctx.emit(fpu_rr(alu_op, rd, rs));
+        }
+
+        Opcode::Ceil | Opcode::Floor | Opcode::Trunc | Opcode::Nearest => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            let round_op = match op {
+                Opcode::Ceil => FpuRoundOp::Ceil,
+                Opcode::Floor => FpuRoundOp::Floor,
+                Opcode::Trunc => FpuRoundOp::Trunc,
+                Opcode::Nearest => FpuRoundOp::Nearest,
+                _ => unreachable!(),
+            };
+            ctx.emit(Inst::FpuRound {
+                op: round_op,
+                ty,
+                rd,
+                rs,
+            });
+        }
+
+        Opcode::Fma => {
+            let ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs1 = put_input_in_reg(ctx, inputs[0]);
+            let rs2 = put_input_in_reg(ctx, inputs[1]);
+            let rs3 = put_input_in_reg(ctx, inputs[2]);
+            let alu_op = match ty {
+                F32 => FpuOPRRRR::FmaddS,
+                F64 => FpuOPRRRR::FmaddD,
+                _ => panic!("Unknown fma type: {}", ty),
+            };
+            ctx.emit(Inst::FpuRRRR {
+                alu_op,
+                frm: FRM::RNE,
+                rd,
+                rs1,
+                rs2,
+                rs3,
+            });
+        }
+
+        Opcode::FcvtFromSint | Opcode::FcvtFromUint => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            let is_signed = op == Opcode::FcvtFromSint;
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            // The 32-bit conversions only use the low half of the register;
+            // narrower values are converted from 32 bits.
+            let rs = if !is_signed && ty_bits(in_ty) < 32 {
+                put_input_in_reg_zext(ctx, inputs[0])
+            } else {
+                put_input_in_reg(ctx, inputs[0])
+            };
+            let alu_op = match (is_signed, ty_bits(in_ty) == 64, out_ty) {
+                (true, false, F32) => FpuOPRR::FcvtSW,
+                (true, true, F32) => FpuOPRR::FcvtSL,
+                (false, false, F32) => FpuOPRR::FcvtSWu,
+                (false, true, F32) => FpuOPRR::FcvtSLu,
+                (true, false, F64) => FpuOPRR::FcvtDW,
+                (true, true, F64) => FpuOPRR::FcvtDL,
+                (false, false, F64) => FpuOPRR::FcvtDWu,
+                (false, true, F64) => FpuOPRR::FcvtDLu,
+                _ => panic!("Unknown input/output-bits combination"),
+            };
+            ctx.emit(fpu_rr(alu_op, rd, rs));
+        }
+
+        Opcode::FcvtToSint | Opcode::FcvtToUint | Opcode::FcvtToSintSat | Opcode::FcvtToUintSat => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            if out_ty != I32 && out_ty != I64 {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} to type {} not supported",
+                    op, out_ty
+                )));
+            }
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            ctx.emit(Inst::FcvtToInt {
+                is_sat: op == Opcode::FcvtToSintSat || op == Opcode::FcvtToUintSat,
+                is_signed: op == Opcode::FcvtToSint || op == Opcode::FcvtToSintSat,
+                in_ty,
+                out_ty,
+                rd,
+                rs,
+            });
+        }
+
+        Opcode::Bitcast => {
+            let in_ty = ctx.input_ty(insn, 0);
+            let out_ty = ty.unwrap();
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let rs = put_input_in_reg(ctx, inputs[0]);
+            match (in_ty, out_ty) {
+                (F32, I32) => ctx.emit(fpu_rr(FpuOPRR::FmvXW, rd, rs)),
+                (I32, F32) => ctx.emit(fpu_rr(FpuOPRR::FmvWX, rd, rs)),
+                (F64, I64) => ctx.emit(fpu_rr(FpuOPRR::FmvXD, rd, rs)),
+                (I64, F64) => ctx.emit(fpu_rr(FpuOPRR::FmvDX, rd, rs)),
+                _ if ty_is_float(in_ty) == ty_is_float(out_ty) => {
+                    ctx.emit(Inst::gen_move(rd, rs, out_ty))
+                }
+                _ => panic!("Unknown input/output-type combination"),
+            }
+        }
+
+        Opcode::Isplit => {
+            let input = ctx.put_input_in_regs(insn, 0);
+            let lo = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let hi = get_output_reg(ctx, outputs[1]).only_reg().unwrap();
+            ctx.emit(Inst::gen_move(lo, input.regs()[0], I64));
+            ctx.emit(Inst::gen_move(hi, input.regs()[1], I64));
+        }
+
+        Opcode::Iconcat => {
+            let lo = put_input_in_reg(ctx, inputs[0]);
+            let hi = put_input_in_reg(ctx, inputs[1]);
+            let output = get_output_reg(ctx, outputs[0]);
+            ctx.emit(Inst::gen_move(output.regs()[0], lo, I64));
+            ctx.emit(Inst::gen_move(output.regs()[1], hi, I64));
+        }
+
+        Opcode::FuncAddr => {
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let (extname, _) = ctx.call_target(insn).unwrap();
+            let extname = extname.clone();
+            ctx.emit(Inst::LoadExtName {
+                rd,
+                name: Box::new(extname),
+                offset: 0,
+            });
+        }
+
+        Opcode::SymbolValue => {
+            let rd = get_output_reg(ctx, outputs[0]).only_reg().unwrap();
+            let (extname, _, offset) = ctx.symbol_value(insn).unwrap();
+            let extname = extname.clone();
+            ctx.emit(Inst::LoadExtName {
+                rd,
+                name: Box::new(extname),
+                offset,
+            });
+        }
+
+        Opcode::ConstAddr | Opcode::TlsValue | Opcode::GetPinnedReg | Opcode::SetPinnedReg => {
+            return Err(CodegenError::Unsupported(format!(
+                "riscv64: {} not supported",
+                op
+            )));
+        }
+
+        Opcode::HeapAddr => {
+            panic!("heap_addr should have been removed by legalization!");
+        }
+
+        Opcode::TableAddr => {
+            panic!("table_addr should have been removed by legalization!");
+        }
+
+        Opcode::GlobalValue => {
+            panic!("global_value should have been removed by legalization!");
+        }
+
+        Opcode::Call | Opcode::CallIndirect => {
+            let caller_conv = ctx.abi().call_conv();
+            let (mut abi, inputs) = match op {
+                Opcode::Call => {
+                    let (extname, dist) = ctx.call_target(insn).unwrap();
+                    let extname = extname.clone();
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() == sig.params.len());
+                    assert!(outputs.len() == sig.returns.len());
+                    (
+                        Riscv64ABICaller::from_func(sig, &extname, dist, caller_conv, flags)?,
+                        &inputs[..],
+                    )
+                }
+                Opcode::CallIndirect => {
+                    let ptr = put_input_in_reg(ctx, inputs[0]);
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() - 1 == sig.params.len());
+                    assert!(outputs.len() == sig.returns.len());
+                    (
+                        Riscv64ABICaller::from_ptr(sig, ptr, op, caller_conv, flags)?,
+                        &inputs[1..],
+                    )
+                }
+                _ => unreachable!(),
+            };
+
+            if ctx.has_landing_pad(insn) {
+                abi.unwinds_to_landing_pad();
+            }
+            abi.emit_stack_pre_adjust(ctx);
+            assert!(inputs.len() == abi.num_args());
+            for i in abi.get_copy_to_arg_order() {
+                let input = inputs[i];
+                let arg_regs = ctx.put_input_in_regs(input.insn, input.input);
+                abi.emit_copy_regs_to_arg(ctx, i, arg_regs);
+            }
+            abi.emit_call(ctx);
+            for (i, output) in outputs.iter().enumerate() {
+                let retval_regs = get_output_reg(ctx, *output);
+                abi.emit_copy_retval_to_regs(ctx, i, retval_regs);
+            }
+            abi.emit_stack_post_adjust(ctx);
+        }
+
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            let caller_conv = ctx.abi().call_conv();
+            let (mut abi, inputs) = match op {
+                Opcode::ReturnCall => {
+                    let (extname, dist) = ctx.call_target(insn).unwrap();
+                    let extname = extname.clone();
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() == sig.params.len());
+                    (
+                        Riscv64ABICaller::from_func(sig, &extname, dist, caller_conv, flags)?,
+                        &inputs[..],
+                    )
+                }
+                Opcode::ReturnCallIndirect => {
+                    let ptr = put_input_in_reg(ctx, inputs[0]);
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() - 1 == sig.params.len());
+                    (
+                        Riscv64ABICaller::from_ptr(sig, ptr, op, caller_conv, flags)?,
+                        &inputs[1..],
+                    )
+                }
+                _ => unreachable!(),
+            };
+
+            abi.prepare_tail_call(ctx)?;
+            assert!(inputs.len() == abi.num_args());
+            for i in abi.get_copy_to_arg_order() {
+                let input = inputs[i];
+                let arg_regs = ctx.put_input_in_regs(input.insn, input.input);
+                abi.emit_copy_regs_to_arg(ctx, i, arg_regs);
+            }
+            abi.emit_return_call(ctx);
+        }
+
+        Opcode::FallthroughReturn | Opcode::Return => {
+            for (i, input) in inputs.iter().enumerate() {
+                // Narrow values are already extended as the ABI requires.
+                let src_regs = ctx.put_input_in_regs(input.insn, input.input);
+                let retval_regs = ctx.retval(i);
+
+                assert_eq!(src_regs.len(), retval_regs.len());
+                let ty = ctx.input_ty(insn, i);
+                let (_, tys) = Inst::rc_for_type(ty)?;
+
+                src_regs
+                    .regs()
+                    .iter()
+                    .zip(retval_regs.regs().iter())
+                    .zip(tys.iter())
+                    .for_each(|((&src, &dst), &ty)| {
+                        ctx.emit(Inst::gen_move(dst, src, ty));
+                    });
+            }
+            // N.B.: the Ret itself is generated by the ABI.
+        }
+
+        Opcode::Debugtrap => {
+            ctx.emit(Inst::Debugtrap);
+        }
+
+        Opcode::Trap | Opcode::ResumableTrap => {
+            let trap_code = ctx.data(insn).trap_code().unwrap();
+            ctx.emit_safepoint(Inst::Udf { trap_code });
+        }
+
+        Opcode::Trapif => {
+            let trap_code = ctx.data(insn).trap_code().unwrap();
+            let flags_input = InsnInput { insn, input: 0 };
+            let cc =
+                if let Some(add_insn) = input_matches_insn(ctx, flags_input, Opcode::IaddIfcout) {
+                    // Compute the sum again, zero-extended, and compare it to the
+                    // first operand: the sum is smaller iff it carried.
+                    let kind = ctx.data(insn).cond_code().unwrap();
+                    let ty = ctx.input_ty(add_insn, 0);
+                    let rs1 = put_input_in_reg_zext(
+                        ctx,
+                        InsnInput {
+                            insn: add_insn,
+                            input: 0,
+                        },
+                    );
+                    let rs2 = put_input_in_reg_zext(
+                        ctx,
+                        InsnInput {
+                            insn: add_insn,
+                            input: 1,
+                        },
+                    );
+                    let sum = ctx.alloc_tmp(I64).only_reg().unwrap();
+                    ctx.emit(alu_rrr(AluOPRRR::Add, sum, rs1, rs2));
+                    let bits = ty_bits(ty);
+                    if bits < 64 {
+                        ctx.emit(Inst::Extend {
+                            rd: sum,
+                            rn: sum.to_reg(),
+                            signed: false,
+                            from_bits: bits as u8,
+                            to_bits: 64,
+                        });
+                    }
+                    IntegerCompare {
+                        kind,
+                        rs1: sum.to_reg(),
+                        rs2: rs1,
+                    }
+                } else {
+                    lower_flags_condition(ctx, insn)?
+                };
+            ctx.emit_safepoint(Inst::TrapIf { cc, trap_code });
+        }
+
+        Opcode::Trapz | Opcode::Trapnz | Opcode::ResumableTrapnz => {
+            panic!("trapz / trapnz / resumable_trapnz should have been removed by legalization!");
+        }
+
+        Opcode::Brff | Opcode::Trapff | Opcode::Trueff | Opcode::Ffcmp => {
+            return Err(CodegenError::Unsupported(format!(
+                "riscv64: {} not supported",
+                op
+            )));
+        }
+
+        Opcode::Ifcmp => {
+            // An Ifcmp must always be seen as a use of a brif, trapif, trueif
+            // or selectif instruction, which performs the comparison.
+            panic!("Should never reach ifcmp as isel root!");
+        }
+
+        Opcode::UaddSat
+        | Opcode::SaddSat
+        | Opcode::UsubSat
+        | Opcode::SsubSat
+        | Opcode::RawBitcast
+        | Opcode::Splat
+        | Opcode::Swizzle
+        | Opcode::Insertlane
+        | Opcode::Extractlane
+        | Opcode::Imin
+        | Opcode::Umin
+        | Opcode::Imax
+        | Opcode::Umax
+        | Opcode::AvgRound
+        | Opcode::FminPseudo
+        | Opcode::FmaxPseudo
+        | Opcode::Uload8x8
+        | Opcode::Uload8x8Complex
+        | Opcode::Sload8x8
+        | Opcode::Sload8x8Complex
+        | Opcode::Uload16x4
+        | Opcode::Uload16x4Complex
+        | Opcode::Sload16x4
+        | Opcode::Sload16x4Complex
+        | Opcode::Uload32x2
+        | Opcode::Uload32x2Complex
+        | Opcode::Sload32x2
+        | Opcode::Sload32x2Complex
+        | Opcode::Vconst
+        | Opcode::Shuffle
+        | Opcode::Vsplit
+        | Opcode::Vconcat
+        | Opcode::Vselect
+        | Opcode::VanyTrue
+        | Opcode::VallTrue
+        | Opcode::VhighBits
+        | Opcode::ScalarToVector
+        | Opcode::Snarrow
+        | Opcode::Unarrow
+        | Opcode::Uunarrow
+        | Opcode::SwidenLow
+        | Opcode::SwidenHigh
+        | Opcode::UwidenLow
+        | Opcode::UwidenHigh
+        | Opcode::WideningPairwiseDotProductS
+        | Opcode::SqmulRoundSat
+        | Opcode::FvpromoteLow
+        | Opcode::Fvdemote
+        | Opcode::FcvtLowFromSint
+        | Opcode::IaddPairwise => {
+            return Err(CodegenError::Unsupported(format!(
+                "riscv64: vector op {} not supported",
+                op
+            )));
+        }
+
+        Opcode::Spill
+        | Opcode::Fill
+        | Opcode::FillNop
+        | Opcode::Regmove
+        | Opcode::CopySpecial
+        | Opcode::CopyToSsa
+        | Opcode::CopyNop
+        | Opcode::AdjustSpDown
+        | Opcode::AdjustSpUpImm
+        | Opcode::AdjustSpDownImm
+        | Opcode::DummySargT
+        | Opcode::IfcmpSp
+        | Opcode::Regspill
+        | Opcode::Regfill => {
+            panic!("Unused opcode should not be encountered.");
+        }
+
+        Opcode::Jump
+        | Opcode::Fallthrough
+        | Opcode::Brz
+        | Opcode::Brnz
+        | Opcode::BrUnwind
+        | Opcode::BrIcmp
+        | Opcode::Brif
+        | Opcode::IndirectJumpTableBr
+        | Opcode::BrTable => {
+            panic!("Branch opcode reached non-branch lowering logic!");
+        }
+
+        Opcode::JumpTableEntry | Opcode::JumpTableBase => {
+            panic!("Should not appear: we handle BrTable directly");
+        }
+
+        Opcode::Safepoint => {
+            panic!("safepoint instructions not used by new backend's safepoints!");
+        }
+
+        Opcode::IaddImm
+        | Opcode::ImulImm
+        | Opcode::UdivImm
+        | Opcode::SdivImm
+        | Opcode::UremImm
+        | Opcode::SremImm
+        | Opcode::IrsubImm
+        | Opcode::IaddCin
+        | Opcode::IaddIfcin
+        | Opcode::IaddCout
+        | Opcode::IaddCarry
+        | Opcode::IaddIfcarry
+        | Opcode::IsubBin
+        | Opcode::IsubIfbin
+        | Opcode::IsubBout
+        | Opcode::IsubIfbout
+        | Opcode::IsubBorrow
+        | Opcode::IsubIfborrow
+        | Opcode::BandImm
+        | Opcode::BorImm
+        | Opcode::BxorImm
+        | Opcode::RotlImm
+        | Opcode::RotrImm
+        | Opcode::IshlImm
+        | Opcode::UshrImm
+        | Opcode::SshrImm
+        | Opcode::IcmpImm
+        | Opcode::IfcmpImm => {
+            panic!("ALU+imm and ALU+carry ops should not appear here!");
+        }
+
+        #[cfg(feature = "x86")]
+        Opcode::X86Udivmodx
+        | Opcode::X86Sdivmodx
+        | Opcode::X86Umulx
+        | Opcode::X86Smulx
+        | Opcode::X86Cvtt2si
+        | Opcode::X86Fmin
+        | Opcode::X86Fmax
+        | Opcode::X86Push
+        | Opcode::X86Pop
+        | Opcode::X86Bsr
+        | Opcode::X86Bsf
+        | Opcode::X86Pblendw
+        | Opcode::X86Pshufd
+        | Opcode::X86Pshufb
+        | Opcode::X86Pextr
+        | Opcode::X86Pinsr
+        | Opcode::X86Insertps
+        | Opcode::X86Movsd
+        | Opcode::X86Movlhps
+        | Opcode::X86Psll
+        | Opcode::X86Psrl
+        | Opcode::X86Psra
+        | Opcode::X86Ptest
+        | Opcode::X86Pmaxs
+        | Opcode::X86Pmaxu
+        | Opcode::X86Pmins
+        | Opcode::X86Pminu
+        | Opcode::X86Pmullq
+        | Opcode::X86Pmuludq
+        | Opcode::X86Punpckh
+        | Opcode::X86Punpckl
+        | Opcode::X86Vcvtudq2ps
+        | Opcode::X86Palignr
+        | Opcode::X86ElfTlsGetAddr
+        | Opcode::X86MachoTlsGetAddr => {
+            panic!("x86-specific opcode in supposedly arch-neutral IR!");
+        }
+    }
+
+    Ok(())
+}
+
+//============================================================================
+// Lowering: main entry point for lowering a branch group
+
+fn lower_branch<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    branches: &[IRInst],
+    targets: &[MachLabel],
+) -> CodegenResult<()> {
+    // A block should end with at most two branches. The first may be a
+    // conditional branch; a conditional branch can be followed only by an
+    // unconditional branch or fallthrough. Otherwise, if only one branch,
+    // it may be an unconditional branch, a fallthrough, a return, or a
+    // trap. These conditions are verified by `is_ebb_basic()` during the
+    // verifier pass.
+    assert!(branches.len() <= 2);
+
+    if branches.len() == 2 {
+        // Must be a conditional branch followed by an unconditional branch.
+        let op0 = ctx.data(branches[0]).opcode();
+        let op1 = ctx.data(branches[1]).opcode();
+
+        assert!(op1 == Opcode::Jump || op1 == Opcode::Fallthrough);
+        let taken = BranchTarget::Label(targets[0]);
+        // not_taken target is the target of the second branch, even if it is a Fallthrough
+        // instruction: because we reorder blocks while we lower, the fallthrough in the new
+        // order is not (necessarily) the same as the fallthrough in CLIF. So we use the
+        // explicitly-provided target.
+        let not_taken = BranchTarget::Label(targets[1]);
+
+        let kind = match op0 {
+            Opcode::BrUnwind => {
+                ctx.emit(Inst::JumpUnwind {
+                    dest: not_taken,
+                    landing_pad: taken,
+                });
+                return Ok(());
+            }
+
+            Opcode::Brz | Opcode::Brnz => {
+                let flag_input = InsnInput {
+                    insn: branches[0],
+                    input: 0,
+                };
+                let kind = if let Some(icmp_insn) =
+                    input_matches_insn(ctx, flag_input, Opcode::Icmp)
+                {
+                    let cc = ctx.data(icmp_insn).cond_code().unwrap();
+                    lower_int_compare(ctx, icmp_insn, cc)?
+                } else if let Some(fcmp_insn) = input_matches_insn(ctx, flag_input, Opcode::Fcmp) {
+                    let tmp = ctx.alloc_tmp(B1).only_reg().unwrap();
+                    lower_fcmp_to_reg(ctx, fcmp_insn, tmp);
+                    IntegerCompare {
+                        kind: IntCC::NotEqual,
+                        rs1: tmp.to_reg(),
+                        rs2: zero_reg(),
+                    }
+                } else {
+                    let ty = ctx.input_ty(branches[0], 0);
+                    if ty_is_wide(ty) {
+                        return Err(CodegenError::Unsupported(format!(
+                            "riscv64: branch on type {} not supported",
+                            ty
+                        )));
+                    }
+                    IntegerCompare {
+                        kind: IntCC::NotEqual,
+                        rs1: put_input_in_reg(ctx, flag_input),
+                        rs2: zero_reg(),
+                    }
+                };
+                if op0 == Opcode::Brz {
+                    kind.inverse()
+                } else {
+                    kind
+                }
+            }
+
+            Opcode::BrIcmp => {
+                let cc = ctx.data(branches[0]).cond_code().unwrap();
+                lower_int_compare(ctx, branches[0], cc)?
+            }
+
+            Opcode::Brif => lower_flags_condition(ctx, branches[0])?,
+
+            _ => {
+                return Err(CodegenError::Unsupported(format!(
+                    "riscv64: {} not supported",
+                    op0
+                )));
+            }
+        };
+
+        ctx.emit(Inst::CondBr {
+            taken,
+            not_taken,
+            kind,
+        });
+    } else {
+        // Must be an unconditional branch or an indirect branch.
+        let op = ctx.data(branches[0]).opcode();
+        match op {
+            Opcode::Jump | Opcode::Fallthrough => {
+                assert!(branches.len() == 1);
+                // In the Fallthrough case, the machine-independent driver
+                // fills in `targets[0]` with our fallthrough block, so this
+                // is valid for both Jump and Fallthrough.
+                ctx.emit(Inst::Jump {
+                    dest: BranchTarget::Label(targets[0]),
+                });
+            }
+
+            Opcode::BrTable => {
+                // The index is sign-extended, which moves indices of 2^31 or
+                // more out of the table's range when compared unsigned, so
+                // `JTSequence` can check it as is.
+                let ridx = put_input_in_reg(
+                    ctx,
+                    InsnInput {
+                        insn: branches[0],
+                        input: 0,
+                    },
+                );
+
+                let jt_targets: Vec<BranchTarget> = targets
+                    .iter()
+                    .skip(1)
+                    .map(|bix| BranchTarget::Label(*bix))
+                    .collect();
+                let default_target = BranchTarget::Label(targets[0]);
+                let targets_for_term: Vec<MachLabel> = targets.to_vec();
+                ctx.emit(Inst::JTSequence {
+                    ridx,
+                    info: Box::new(JTSequenceInfo {
+                        targets: jt_targets,
+                        default_target,
+                        targets_for_term,
+                    }),
+                });
+            }
+
+            _ => panic!("Unknown branch type!"),
+        }
+    }
+
+    Ok(())
+}
+
+//=============================================================================
+// Lowering-backend trait implementation.
+
+impl LowerBackend for Riscv64Backend {
+    type MInst = Inst;
+
+    fn lower<C: LowerCtx<I = Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
+        lower_insn_to_regs(ctx, ir_inst, &self.flags, &self.isa_flags)
+    }
+
+    fn lower_branch_group<C: LowerCtx<I = Inst>>(
+        &self,
+        ctx: &mut C,
+        branches: &[IRInst],
+        targets: &[MachLabel],
+    ) -> CodegenResult<()> {
+        lower_branch(ctx, branches, targets)
+    }
+}
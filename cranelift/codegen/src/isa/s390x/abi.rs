@@ -450,6 +450,10 @@ impl ABIMachineSpec for S390xMachineDeps {
         SmallVec::new()
     }
 
+    fn gen_grow_incoming_args(_size: u32) -> SmallInstVec<Inst> {
+        unreachable!("tail calls are rejected when lowering for S390X");
+    }
+
     fn gen_probestack(_: u32) -> SmallInstVec<Self::I> {
         // TODO: implement if we ever require stack probes on an s390x host
         // (unlikely unless Lucet is ported)
@@ -676,6 +680,15 @@ impl ABIMachineSpec for S390xMachineDeps {
         insts
     }
 
+    fn gen_return_call(
+        _dest: &CallDest,
+        _uses: Vec<Reg>,
+        _callee_conv: isa::CallConv,
+        _sp_adjust: u32,
+    ) -> SmallInstVec<Inst> {
+        unreachable!("tail calls are rejected when lowering for S390X");
+    }
+
     fn gen_memcpy(
         _call_conv: isa::CallConv,
         _dst: Reg,
@@ -721,6 +734,12 @@ impl ABIMachineSpec for S390xMachineDeps {
         caller_saved
     }
 
+    fn get_regs_clobbered_by_unwind(_call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>> {
+        // Lowering rejects `br_unwind` for S390X, so no call here ever has a
+        // landing pad and nothing is clobbered by unwinding.
+        vec![]
+    }
+
     fn get_ext_mode(
         _call_conv: isa::CallConv,
         specified: ir::ArgumentExtension,
@@ -10,7 +10,7 @@ use crate::isa::s390x::S390xBackend;
 use crate::machinst::lower::*;
 use crate::machinst::*;
 use crate::settings::Flags;
-use crate::CodegenResult;
+use crate::{CodegenError, CodegenResult};
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::convert::TryFrom;
@@ -2876,6 +2876,13 @@ fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
 
         Opcode::Isplit | Opcode::Iconcat => unimplemented!("Wide integer ops not implemented."),
 
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            return Err(CodegenError::Unsupported(format!(
+                "{}: tail calls are not implemented for S390X",
+                op
+            )));
+        }
+
         Opcode::Spill
         | Opcode::Fill
         | Opcode::FillNop
@@ -2906,6 +2913,7 @@ fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
         | Opcode::Fallthrough
         | Opcode::Brz
         | Opcode::Brnz
+        | Opcode::BrUnwind
         | Opcode::BrIcmp
         | Opcode::Brif
         | Opcode::Brff
@@ -3022,6 +3030,13 @@ fn lower_branch<C: LowerCtx<I = Inst>>(
         let not_taken = BranchTarget::Label(targets[1]);
 
         match op0 {
+            Opcode::BrUnwind => {
+                return Err(CodegenError::Unsupported(format!(
+                    "{}: exception handling is not implemented for S390X",
+                    op0
+                )));
+            }
+
             Opcode::Brz | Opcode::Brnz => {
                 let flag_input = InsnInput {
                     insn: branches[0],
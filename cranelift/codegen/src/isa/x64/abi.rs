@@ -479,6 +479,34 @@ impl ABIMachineSpec for X64ABIMachineSpec {
         insts
     }
 
+    fn gen_grow_incoming_args(size: u32) -> SmallInstVec<Self::I> {
+        // Copy the frame record (the saved %rbp and the return address)
+        // `size` bytes down and point %rbp at the copy. %r11 is caller-saved
+        // and never holds an argument.
+        let tmp = Writable::from_reg(regs::r11());
+        let mut insts = SmallVec::new();
+        insts.push(Inst::alu_rmi_r(
+            OperandSize::Size64,
+            AluRmiROpcode::Sub,
+            RegMemImm::imm(size),
+            Writable::from_reg(regs::rsp()),
+        ));
+        for offset in &[0, 8] {
+            insts.push(Inst::mov64_m_r(Amode::imm_reg(*offset, regs::rbp()), tmp));
+            insts.push(Inst::mov_r_m(
+                OperandSize::Size64,
+                tmp.to_reg(),
+                Amode::imm_reg(*offset, regs::rsp()),
+            ));
+        }
+        insts.push(Inst::mov_r_r(
+            OperandSize::Size64,
+            regs::rsp(),
+            Writable::from_reg(regs::rbp()),
+        ));
+        insts
+    }
+
     fn gen_probestack(frame_size: u32) -> SmallInstVec<Self::I> {
         let mut insts = SmallVec::new();
         insts.push(Inst::imm(
@@ -674,6 +702,44 @@ impl ABIMachineSpec for X64ABIMachineSpec {
         insts
     }
 
+    fn gen_return_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        _callee_conv: isa::CallConv,
+        sp_adjust: u32,
+    ) -> SmallInstVec<Self::I> {
+        // %r11 is caller-saved and never holds an argument, so the epilogue
+        // leaves it alone.
+        let tmp = Writable::from_reg(regs::r11());
+        let mut insts = SmallVec::new();
+        match dest {
+            &CallDest::ExtName(ref name, RelocDistance::Near) => {
+                insts.push(Inst::ReturnCallKnown {
+                    dest: name.clone(),
+                    uses,
+                    sp_adjust,
+                });
+                return insts;
+            }
+            &CallDest::ExtName(ref name, RelocDistance::Far) => {
+                insts.push(Inst::LoadExtName {
+                    dst: tmp,
+                    name: Box::new(name.clone()),
+                    offset: 0,
+                });
+            }
+            &CallDest::Reg(reg) => {
+                insts.push(Inst::mov_r_r(OperandSize::Size64, reg, tmp));
+            }
+        }
+        insts.push(Inst::ReturnCallUnknown {
+            dest: tmp.to_reg(),
+            uses,
+            sp_adjust,
+        });
+        insts
+    }
+
     fn gen_memcpy(
         call_conv: isa::CallConv,
         dst: Reg,
@@ -784,6 +850,34 @@ impl ABIMachineSpec for X64ABIMachineSpec {
         caller_saved
     }
 
+    fn get_regs_clobbered_by_unwind(call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>> {
+        // RBP is the frame pointer, which the unwinder restores.
+        let mut callee_saved = vec![
+            Writable::from_reg(regs::rbx()),
+            Writable::from_reg(regs::r12()),
+            Writable::from_reg(regs::r13()),
+            Writable::from_reg(regs::r14()),
+            Writable::from_reg(regs::r15()),
+        ];
+
+        if call_conv_of_callee.extends_windows_fastcall() {
+            callee_saved.push(Writable::from_reg(regs::rsi()));
+            callee_saved.push(Writable::from_reg(regs::rdi()));
+            callee_saved.push(Writable::from_reg(regs::xmm6()));
+            callee_saved.push(Writable::from_reg(regs::xmm7()));
+            callee_saved.push(Writable::from_reg(regs::xmm8()));
+            callee_saved.push(Writable::from_reg(regs::xmm9()));
+            callee_saved.push(Writable::from_reg(regs::xmm10()));
+            callee_saved.push(Writable::from_reg(regs::xmm11()));
+            callee_saved.push(Writable::from_reg(regs::xmm12()));
+            callee_saved.push(Writable::from_reg(regs::xmm13()));
+            callee_saved.push(Writable::from_reg(regs::xmm14()));
+            callee_saved.push(Writable::from_reg(regs::xmm15()));
+        }
+
+        callee_saved
+    }
+
     fn get_ext_mode(
         call_conv: isa::CallConv,
         specified: ir::ArgumentExtension,
@@ -1111,6 +1111,7 @@ pub(crate) fn emit(
                 let loc = state.cur_srcloc();
                 sink.add_call_site(loc, *opcode);
             }
+            emit_restore_sp_after_call(sink, info, state);
         }
 
         Inst::CallUnknown { dest, opcode, .. } => {
@@ -1154,6 +1155,35 @@ pub(crate) fn emit(
                 let loc = state.cur_srcloc();
                 sink.add_call_site(loc, *opcode);
             }
+            emit_restore_sp_after_call(sink, info, state);
+        }
+
+        Inst::ReturnCallKnown {
+            dest, sp_adjust, ..
+        } => {
+            emit_return_call_sp_adjust(*sp_adjust, sink, info, state);
+            sink.put1(0xE9);
+            // The addend adjusts for the difference between the end of the instruction and the
+            // beginning of the immediate field.
+            emit_reloc(sink, state, Reloc::X86CallPCRel4, &dest, -4);
+            sink.put4(0);
+        }
+
+        Inst::ReturnCallUnknown {
+            dest, sp_adjust, ..
+        } => {
+            debug_assert_ne!(*dest, regs::r10());
+            emit_return_call_sp_adjust(*sp_adjust, sink, info, state);
+            let reg_enc = int_reg_enc(*dest);
+            emit_std_enc_enc(
+                sink,
+                LegacyPrefixes::None,
+                0xFF,
+                1,
+                4, /*subopcode*/
+                reg_enc,
+                RexFlags::clear_w(),
+            );
         }
 
         Inst::Ret {} => sink.put1(0xC3),
@@ -1171,6 +1201,13 @@ pub(crate) fn emit(
             sink.put4(0x0);
         }
 
+        Inst::JmpUnwind { dst, landing_pad } => {
+            // The landing pad expects SP where it is between calls.
+            let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+            sink.add_exception_handler(*landing_pad, fp_to_sp as u32);
+            Inst::jmp_known(*dst).emit(sink, info, state);
+        }
+
         Inst::JmpIf { cc, taken } => {
             let cond_start = sink.cur_offset();
             let cond_disp_off = cond_start + 2;
@@ -2696,3 +2733,47 @@ pub(crate) fn emit(
 
     state.clear_post_insn();
 }
+
+/// Moves the return address, and with it SP, `sp_adjust` bytes up the stack
+/// before a tail call jumps, so that it lies just below the callee's stack
+/// arguments. %r10 is caller-saved and never holds an argument.
+fn emit_return_call_sp_adjust(
+    sp_adjust: u32,
+    sink: &mut MachBuffer<Inst>,
+    info: &EmitInfo,
+    state: &mut EmitState,
+) {
+    if sp_adjust == 0 {
+        return;
+    }
+    let r10 = Writable::from_reg(regs::r10());
+    let insts = [
+        Inst::mov64_m_r(Amode::imm_reg(0, regs::rsp()), r10),
+        Inst::alu_rmi_r(
+            OperandSize::Size64,
+            AluRmiROpcode::Add,
+            RegMemImm::imm(sp_adjust),
+            Writable::from_reg(regs::rsp()),
+        ),
+        Inst::mov_r_m(
+            OperandSize::Size64,
+            r10.to_reg(),
+            Amode::imm_reg(0, regs::rsp()),
+        ),
+    ];
+    for inst in insts.iter() {
+        inst.emit(sink, info, state);
+    }
+}
+
+/// With growing tail calls, a callee may return with SP moved, so recompute it
+/// from FP right after a call returns. This has to be part of the call itself,
+/// as the register allocator may reload spilled values right after it.
+fn emit_restore_sp_after_call(sink: &mut MachBuffer<Inst>, info: &EmitInfo, state: &mut EmitState) {
+    if !info.flags().enable_growing_tail_calls() {
+        return;
+    }
+    let fp_to_sp = state.nominal_sp_to_fp + state.virtual_sp_offset;
+    let addr = Amode::imm_reg(-(fp_to_sp as i32) as u32, regs::rbp());
+    Inst::lea(addr, Writable::from_reg(regs::rsp())).emit(sink, info, state);
+}
@@ -3226,6 +3226,27 @@ fn test_x64_emit() {
         "call    *321(%r10,%rdx,4)",
     ));
 
+    // ========================================================
+    // ReturnCallUnknown
+    insns.push((
+        Inst::ReturnCallUnknown {
+            dest: r11,
+            uses: Vec::new(),
+            sp_adjust: 0,
+        },
+        "41FFE3",
+        "jmp     *%r11",
+    ));
+    insns.push((
+        Inst::ReturnCallUnknown {
+            dest: r11,
+            uses: Vec::new(),
+            sp_adjust: 32,
+        },
+        "4C8B14244883C4204C89142441FFE3",
+        "movq    0(%rsp), %r10 ; addq    $32, %rsp ; movq    %r10, 0(%rsp) ; jmp     *%r11",
+    ));
+
     // ========================================================
     // LoadExtName
     // N.B.: test harness below sets is_pic.
@@ -4418,3 +4439,47 @@ fn test_x64_emit() {
         assert_eq!(expected_encoding, actual_encoding, "{}", expected_printing);
     }
 }
+
+#[test]
+fn test_x64_emit_growing_tail_calls() {
+    // With growing tail calls, every call recomputes %rsp from %rbp once it
+    // returns, as part of the call instruction itself.
+    use crate::settings::Configurable;
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_growing_tail_calls").unwrap();
+    let flags = settings::Flags::new(flag_builder);
+    let isa_flags = x64::settings::Flags::new(&flags, x64::settings::builder());
+    let emit_info = EmitInfo::new(flags, isa_flags);
+
+    let insns = [
+        (
+            Inst::call_unknown(
+                RegMem::reg(regs::r11()),
+                Vec::new(),
+                Vec::new(),
+                Opcode::CallIndirect,
+            ),
+            "41FFD3488D6500",
+        ),
+        (
+            Inst::call_known(
+                ExternalName::User {
+                    namespace: 0,
+                    index: 0,
+                },
+                Vec::new(),
+                Vec::new(),
+                Opcode::Call,
+            ),
+            "E800000000488D6500",
+        ),
+    ];
+    for (insn, expected_encoding) in insns.iter() {
+        let mut sink = test_utils::TestCodeSink::new();
+        let mut buffer = MachBuffer::new();
+        insn.emit(&mut buffer, &emit_info, &mut Default::default());
+        let buffer = buffer.finish();
+        buffer.emit(&mut sink);
+        assert_eq!(*expected_encoding, sink.stringify());
+    }
+}
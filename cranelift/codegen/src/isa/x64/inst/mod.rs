@@ -361,6 +361,24 @@ pub enum Inst {
         opcode: Opcode,
     },
 
+    /// Direct tail call, following the epilogue: jmp simm32. If `sp_adjust`
+    /// is non-zero, the return address first moves that many bytes up the
+    /// stack, using %r10.
+    ReturnCallKnown {
+        dest: ExternalName,
+        uses: Vec<Reg>,
+        sp_adjust: u32,
+    },
+
+    /// Indirect tail call, following the epilogue: jmpq *reg. The register
+    /// must not be restored by the epilogue, nor be %r10, which moves the
+    /// return address `sp_adjust` bytes up the stack like for a direct call.
+    ReturnCallUnknown {
+        dest: Reg,
+        uses: Vec<Reg>,
+        sp_adjust: u32,
+    },
+
     /// Return.
     Ret,
 
@@ -371,6 +389,13 @@ pub enum Inst {
     /// Jump to a known target: jmp simm32.
     JmpKnown { dst: MachLabel },
 
+    /// Jump to a known target after a call, recording `landing_pad` as where
+    /// execution resumes instead if the stack unwinds through that call.
+    JmpUnwind {
+        dst: MachLabel,
+        landing_pad: MachLabel,
+    },
+
     /// One-way conditional branch: jcond cond target.
     ///
     /// This instruction is useful when we have conditional jumps depending on more than two
@@ -526,6 +551,8 @@ impl Inst {
             | Inst::AtomicRmwSeq { .. }
             | Inst::CallKnown { .. }
             | Inst::CallUnknown { .. }
+            | Inst::ReturnCallKnown { .. }
+            | Inst::ReturnCallUnknown { .. }
             | Inst::CheckedDivOrRemSeq { .. }
             | Inst::Cmove { .. }
             | Inst::CmpRmiR { .. }
@@ -540,6 +567,7 @@ impl Inst {
             | Inst::JmpCond { .. }
             | Inst::JmpIf { .. }
             | Inst::JmpKnown { .. }
+            | Inst::JmpUnwind { .. }
             | Inst::JmpTableSeq { .. }
             | Inst::JmpUnknown { .. }
             | Inst::LoadEffectiveAddress { .. }
@@ -1085,6 +1113,10 @@ impl Inst {
         Inst::JmpKnown { dst }
     }
 
+    pub(crate) fn jmp_unwind(dst: MachLabel, landing_pad: MachLabel) -> Inst {
+        Inst::JmpUnwind { dst, landing_pad }
+    }
+
     pub(crate) fn jmp_if(cc: CC, taken: MachLabel) -> Inst {
         Inst::JmpIf { cc, taken }
     }
@@ -1306,6 +1338,19 @@ impl PrettyPrint for Inst {
             ljustify(s1 + &s2)
         }
 
+        fn show_return_call_sp_adjust(sp_adjust: u32) -> String {
+            if sp_adjust == 0 {
+                return String::new();
+            }
+            format!(
+                "{} 0(%rsp), %r10 ; {} ${}, %rsp ; {} %r10, 0(%rsp) ; ",
+                ljustify("movq".to_string()),
+                ljustify("addq".to_string()),
+                sp_adjust,
+                ljustify("movq".to_string())
+            )
+        }
+
         fn suffix_lq(size: OperandSize) -> String {
             match size {
                 OperandSize::Size32 => "l",
@@ -1764,6 +1809,24 @@ impl PrettyPrint for Inst {
                 dest.show_rru(mb_rru)
             ),
 
+            Inst::ReturnCallKnown {
+                dest, sp_adjust, ..
+            } => format!(
+                "{}{} {:?}",
+                show_return_call_sp_adjust(*sp_adjust),
+                ljustify("jmp".to_string()),
+                dest
+            ),
+
+            Inst::ReturnCallUnknown {
+                dest, sp_adjust, ..
+            } => format!(
+                "{}{} *{}",
+                show_return_call_sp_adjust(*sp_adjust),
+                ljustify("jmp".to_string()),
+                show_ireg_sized(*dest, mb_rru, 8)
+            ),
+
             Inst::Ret => "ret".to_string(),
 
             Inst::EpiloguePlaceholder => "epilogue placeholder".to_string(),
@@ -1772,6 +1835,13 @@ impl PrettyPrint for Inst {
                 format!("{} {}", ljustify("jmp".to_string()), dst.to_string())
             }
 
+            Inst::JmpUnwind { dst, landing_pad } => format!(
+                "{} {}; unwind to {}",
+                ljustify("jmp".to_string()),
+                dst.to_string(),
+                landing_pad.to_string()
+            ),
+
             Inst::JmpIf { cc, taken } => format!(
                 "{} {}",
                 ljustify2("j".to_string(), cc.to_string()),
@@ -2096,6 +2166,15 @@ fn x64_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
             dest.get_regs_as_uses(collector);
         }
 
+        Inst::ReturnCallKnown { ref uses, .. } => {
+            collector.add_uses(uses);
+        }
+
+        Inst::ReturnCallUnknown { ref uses, dest, .. } => {
+            collector.add_uses(uses);
+            collector.add_use(*dest);
+        }
+
         Inst::JmpTableSeq {
             ref idx,
             ref tmp1,
@@ -2131,6 +2210,7 @@ fn x64_get_regs(inst: &Inst, collector: &mut RegUsageCollector) {
         Inst::Ret
         | Inst::EpiloguePlaceholder
         | Inst::JmpKnown { .. }
+        | Inst::JmpUnwind { .. }
         | Inst::JmpIf { .. }
         | Inst::JmpCond { .. }
         | Inst::Nop { .. }
@@ -2533,6 +2613,23 @@ fn x64_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
             dest.map_uses(mapper);
         }
 
+        Inst::ReturnCallKnown { ref mut uses, .. } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+        }
+
+        Inst::ReturnCallUnknown {
+            ref mut uses,
+            ref mut dest,
+            ..
+        } => {
+            for r in uses.iter_mut() {
+                map_use(mapper, r);
+            }
+            map_use(mapper, dest);
+        }
+
         Inst::JmpTableSeq {
             ref mut idx,
             ref mut tmp1,
@@ -2562,6 +2659,7 @@ fn x64_map_regs<RUM: RegUsageMapper>(inst: &mut Inst, mapper: &RUM) {
         Inst::Ret
         | Inst::EpiloguePlaceholder
         | Inst::JmpKnown { .. }
+        | Inst::JmpUnwind { .. }
         | Inst::JmpCond { .. }
         | Inst::JmpIf { .. }
         | Inst::Nop { .. }
@@ -2637,7 +2735,11 @@ impl MachInst for Inst {
         match self {
             // Interesting cases.
             &Self::Ret | &Self::EpiloguePlaceholder => MachTerminator::Ret,
+            &Self::ReturnCallKnown { .. } | &Self::ReturnCallUnknown { .. } => {
+                MachTerminator::TailCall
+            }
             &Self::JmpKnown { dst } => MachTerminator::Uncond(dst),
+            &Self::JmpUnwind { dst, landing_pad } => MachTerminator::Cond(dst, landing_pad),
             &Self::JmpCond {
                 taken, not_taken, ..
             } => MachTerminator::Cond(taken, not_taken),
@@ -0,0 +1,133 @@
+//! Lowering of the instructions covered by `lower.rules`.
+//!
+//! The `lower` function is generated from the rules at build time; this module
+//! provides the extractors and constructors they use. A rules `Value` is an
+//! `InsnInput` here, `WritableRegs` is a `ValueRegs<Writable<Reg>>` and `Unit`
+//! is `()`.
+
+use super::*;
+
+include!(concat!(env!("OUT_DIR"), "/lower_rules-x64.rs"));
+
+//=============================================================================
+// Extractors.
+
+fn simm32<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<u32> {
+    let source = ctx.get_input_as_source_or_const(input.insn, input.input);
+    let ty = ctx.input_ty(input.insn, input.input);
+    non_reg_input_to_sext_imm(source, ty)
+}
+
+/// A load which can be merged into the instruction using its result.
+#[derive(Clone, Copy)]
+struct SinkableLoad {
+    insn: IRInst,
+    addr: InsnInput,
+    offset: i32,
+}
+
+fn sinkable_load<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Option<SinkableLoad> {
+    let source = ctx.get_input_as_source_or_const(input.insn, input.input);
+    if source.constant.is_some() {
+        return None;
+    }
+    match source.inst {
+        Some((insn, 0)) => {
+            let (addr, offset) = is_mergeable_load(ctx, insn)?;
+            Some(SinkableLoad { insn, addr, offset })
+        }
+        _ => None,
+    }
+}
+
+//=============================================================================
+// Constructors.
+
+fn put_in_reg<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> Reg {
+    put_input_in_reg(ctx, input)
+}
+
+fn put_in_regs<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> ValueRegs<Reg> {
+    put_input_in_regs(ctx, input)
+}
+
+fn put_in_reg_mem<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> RegMem {
+    input_to_reg_mem(ctx, input)
+}
+
+fn put_in_reg_mem_imm<C: LowerCtx<I = Inst>>(ctx: &mut C, input: InsnInput) -> RegMemImm {
+    input_to_reg_mem_imm(ctx, input)
+}
+
+fn sink_load<C: LowerCtx<I = Inst>>(ctx: &mut C, load: SinkableLoad) -> Amode {
+    ctx.sink_inst(load.insn);
+    lower_to_amode(ctx, load.addr, load.offset)
+}
+
+fn rmi_imm<C: LowerCtx<I = Inst>>(_ctx: &mut C, imm: u32) -> RegMemImm {
+    RegMemImm::imm(imm)
+}
+
+fn rmi_mem<C: LowerCtx<I = Inst>>(_ctx: &mut C, addr: Amode) -> RegMemImm {
+    RegMemImm::mem(addr)
+}
+
+fn alu_rmi_r<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    ty: Type,
+    op: AluRmiROpcode,
+    lhs: Reg,
+    rhs: RegMemImm,
+    dst: ValueRegs<Writable<Reg>>,
+) {
+    let size = if ty == types::I64 {
+        OperandSize::Size64
+    } else {
+        OperandSize::Size32
+    };
+    let dst = dst.only_reg().unwrap();
+    ctx.emit(Inst::mov_r_r(OperandSize::Size64, lhs, dst));
+    ctx.emit(Inst::alu_rmi_r(size, op, rhs, dst));
+}
+
+fn alu_rmi_r_i128<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    lo_op: AluRmiROpcode,
+    hi_op: AluRmiROpcode,
+    lhs: ValueRegs<Reg>,
+    rhs: ValueRegs<Reg>,
+    dst: ValueRegs<Writable<Reg>>,
+) {
+    assert_eq!(lhs.len(), 2);
+    assert_eq!(rhs.len(), 2);
+    assert_eq!(dst.len(), 2);
+
+    ctx.emit(Inst::gen_move(dst.regs()[0], lhs.regs()[0], types::I64));
+    ctx.emit(Inst::gen_move(dst.regs()[1], lhs.regs()[1], types::I64));
+    ctx.emit(Inst::alu_rmi_r(
+        OperandSize::Size64,
+        lo_op,
+        RegMemImm::reg(rhs.regs()[0]),
+        dst.regs()[0],
+    ));
+    ctx.emit(Inst::alu_rmi_r(
+        OperandSize::Size64,
+        hi_op,
+        RegMemImm::reg(rhs.regs()[1]),
+        dst.regs()[1],
+    ));
+}
+
+fn xmm_rm_r<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    ty: Type,
+    op: SseOpcode,
+    lhs: Reg,
+    rhs: RegMem,
+    dst: ValueRegs<Writable<Reg>>,
+) {
+    let dst = dst.only_reg().unwrap();
+    // Move the `lhs` to the same register as `dst`.
+    ctx.emit(Inst::gen_move(dst, lhs, ty));
+    ctx.emit(Inst::xmm_rm_r(op, rhs, dst));
+}
@@ -5,6 +5,7 @@ use crate::ir::{Signature, StackSlot};
 use crate::isa::CallConv;
 use crate::machinst::*;
 use crate::settings;
+use crate::CodegenResult;
 use regalloc::{Reg, Set, SpillSlot, Writable};
 use smallvec::SmallVec;
 
@@ -149,6 +150,16 @@ pub trait ABICallee {
     /// likely closely related.
     fn gen_epilogue(&self) -> SmallInstVec<Self::I>;
 
+    /// Generate the epilogue which precedes a tail call, post-regalloc. This
+    /// restores the caller's frame like `gen_epilogue`, but leaves the
+    /// argument registers alone and doesn't return; the tail call's jump
+    /// follows it.
+    fn gen_tail_call_epilogue(&self) -> SmallInstVec<Self::I>;
+
+    /// The register holding the return-area pointer, if this function returns
+    /// values on the stack. A tail call passes it on to its callee.
+    fn ret_area_ptr(&self) -> Option<Reg>;
+
     /// Returns the full frame size for the given function, after prologue
     /// emission has run. This comprises the spill slots and stack-storage slots
     /// (but not storage for clobbered callee-save registers, arguments pushed
@@ -160,6 +171,11 @@ pub trait ABICallee {
     /// Returns the size of arguments expected on the stack.
     fn stack_args_size(&self) -> u32;
 
+    /// Returns the size of the incoming argument area, which tail calls pass
+    /// their own stack arguments in. This is `stack_args_size` plus however
+    /// much the prologue grew the area by to fit every tail call's arguments.
+    fn tail_call_args_area_size(&self) -> u32;
+
     /// Get the spill-slot size.
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32;
 
@@ -251,4 +267,22 @@ pub trait ABICaller {
     /// This function should only be called once, as it is allowed to re-use
     /// parts of the ABICaller object in emitting instructions.
     fn emit_call<C: LowerCtx<I = Self::I>>(&mut self, ctx: &mut C);
+
+    /// Declare that an exception may unwind the stack through this callsite
+    /// to a landing pad in the function being lowered. That skips the
+    /// epilogues that would restore callee-saved registers, so the call
+    /// clobbers those too. This must be done before `emit_call`.
+    fn unwinds_to_landing_pad(&mut self);
+
+    /// Turn this callsite into a tail call from the function being lowered,
+    /// which must be done before any argument is copied. The callee reuses
+    /// the current function's incoming argument area for its stack arguments,
+    /// so this fails if they don't fit there, which only happens without
+    /// `enable_growing_tail_calls`.
+    fn prepare_tail_call<C: LowerCtx<I = Self::I>>(&mut self, ctx: &mut C) -> CodegenResult<()>;
+
+    /// Emit the tail call itself, after the arguments are copied. The
+    /// returned instruction is a terminator; no stack adjustment is needed
+    /// around it.
+    fn emit_return_call<C: LowerCtx<I = Self::I>>(&mut self, ctx: &mut C);
 }
@@ -129,8 +129,8 @@ use crate::ir::types::*;
 use crate::ir::{ArgumentExtension, ArgumentPurpose, StackSlot};
 use crate::machinst::*;
 use crate::settings;
-use crate::CodegenResult;
 use crate::{ir, isa};
+use crate::{CodegenError, CodegenResult};
 use alloc::vec::Vec;
 use regalloc::{RealReg, Reg, RegClass, Set, SpillSlot, Writable};
 use smallvec::{smallvec, SmallVec};
@@ -425,6 +425,13 @@ pub trait ABIMachineSpec {
     /// Generate the usual frame-restore sequence for this architecture.
     fn gen_epilogue_frame_restore(flags: &settings::Flags) -> SmallInstVec<Self::I>;
 
+    /// Generate a sequence, following the frame setup and stack check, that
+    /// copies the saved FP and return address `size` bytes further down the
+    /// stack and points FP at the copy. This leaves a gap of `size` bytes
+    /// above the frame, which enlarges the incoming argument area for tail
+    /// calls. Like the stack check, it may only use fixed scratch registers.
+    fn gen_grow_incoming_args(size: u32) -> SmallInstVec<Self::I>;
+
     /// Generate a probestack call.
     fn gen_probestack(_frame_size: u32) -> SmallInstVec<Self::I>;
 
@@ -485,6 +492,18 @@ pub trait ABIMachineSpec {
         callee_conv: isa::CallConv,
     ) -> SmallVec<[(InstIsSafepoint, Self::I); 2]>;
 
+    /// Generate a tail call to `dest`, which follows the epilogue of the
+    /// current function, so it must not keep the callee's address in a
+    /// callee-saved register. Before jumping, the call moves SP (and on
+    /// x86-64, the return address) `sp_adjust` bytes up, to just below the
+    /// callee's stack arguments.
+    fn gen_return_call(
+        dest: &CallDest,
+        uses: Vec<Reg>,
+        callee_conv: isa::CallConv,
+        sp_adjust: u32,
+    ) -> SmallInstVec<Self::I>;
+
     /// Generate a memcpy invocation. Used to set up struct args. May clobber
     /// caller-save registers; we only memcpy before we start to set up args for
     /// a call.
@@ -509,6 +528,10 @@ pub trait ABIMachineSpec {
     /// not to be saved across a call to a callee with the given ABI.
     fn get_regs_clobbered_by_call(call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>>;
 
+    /// Get the callee-save registers of the given ABI, which a call no longer
+    /// preserves when the stack unwinds through it to a landing pad.
+    fn get_regs_clobbered_by_unwind(call_conv_of_callee: isa::CallConv) -> Vec<Writable<Reg>>;
+
     /// Get the needed extension mode, given the mode attached to the argument
     /// in the signature and the calling convention. The input (the attribute in
     /// the signature) specifies what extension type should be done *if* the ABI
@@ -632,6 +655,10 @@ pub struct ABICalleeImpl<M: ABIMachineSpec> {
     probestack_min_frame: Option<u32>,
     /// Whether it is necessary to generate the usual frame-setup sequence.
     setup_frame: bool,
+    /// How many bytes the prologue adds to the incoming argument area, so
+    /// that the stack arguments of every tail call fit in it. This is only
+    /// non-zero with `enable_growing_tail_calls`.
+    tail_args_growth: u32,
 
     _mach: PhantomData<M>,
 }
@@ -707,6 +734,34 @@ impl<M: ABIMachineSpec> ABICalleeImpl<M> {
             None
         };
 
+        // With growing tail calls, the incoming argument area must be able to
+        // hold the stack arguments of every tail call made from this function.
+        let mut tail_args_growth = 0;
+        if flags.enable_growing_tail_calls() {
+            let mut tail_args_size = 0;
+            for block in f.layout.blocks() {
+                let inst = match f.layout.last_inst(block) {
+                    Some(inst) => inst,
+                    None => continue,
+                };
+                match f.dfg[inst].opcode() {
+                    ir::Opcode::ReturnCall | ir::Opcode::ReturnCallIndirect => {
+                        let sig_ref = f.dfg.call_signature(inst).unwrap();
+                        let callee_sig =
+                            ensure_struct_return_ptr_is_returned(&f.dfg.signatures[sig_ref]);
+                        let callee_sig = ABISig::from_func_sig::<M>(&callee_sig, &flags)?;
+                        tail_args_size = std::cmp::max(tail_args_size, callee_sig.stack_arg_space);
+                    }
+                    _ => {}
+                }
+            }
+            if tail_args_size > sig.stack_arg_space {
+                let mask = M::stack_align(call_conv) - 1;
+                let growth = (tail_args_size - sig.stack_arg_space) as u32;
+                tail_args_growth = (growth + mask) & !mask;
+            }
+        }
+
         Ok(Self {
             ir_sig,
             sig,
@@ -724,10 +779,17 @@ impl<M: ABIMachineSpec> ABICalleeImpl<M> {
             stack_limit,
             probestack_min_frame,
             setup_frame: true,
+            tail_args_growth,
             _mach: PhantomData,
         })
     }
 
+    /// The offset from FP to the incoming stack arguments, which the prologue
+    /// moves FP away from when it grows the incoming argument area.
+    fn fp_to_arg_offset(&self) -> i64 {
+        M::fp_to_arg_offset(self.call_conv, &self.flags) + i64::from(self.tail_args_growth)
+    }
+
     /// Inserts instructions necessary for checking the stack limit into the
     /// prologue.
     ///
@@ -1016,10 +1078,7 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
                         }
                         &ABIArgSlot::Stack { offset, ty, .. } => {
                             insts.push(M::gen_load_stack(
-                                StackAMode::FPOffset(
-                                    M::fp_to_arg_offset(self.call_conv, &self.flags) + offset,
-                                    ty,
-                                ),
+                                StackAMode::FPOffset(self.fp_to_arg_offset() + offset, ty),
                                 *into_reg,
                                 ty,
                             ));
@@ -1030,10 +1089,7 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
             &ABIArg::StructArg { offset, .. } => {
                 let into_reg = into_regs.only_reg().unwrap();
                 insts.push(M::gen_get_stack_addr(
-                    StackAMode::FPOffset(
-                        M::fp_to_arg_offset(self.call_conv, &self.flags) + offset,
-                        I8,
-                    ),
+                    StackAMode::FPOffset(self.fp_to_arg_offset() + offset, I8),
                     into_reg,
                     I8,
                 ));
@@ -1284,7 +1340,7 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
             self.fixed_frame_storage_size += total_stacksize;
             self.setup_frame = M::is_frame_setup_needed(
                 self.is_leaf,
-                self.stack_args_size(),
+                self.stack_args_size() + self.tail_args_growth,
                 clobbered_callee_saves.len(),
                 self.fixed_frame_storage_size,
             );
@@ -1296,17 +1352,23 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
 
             // Leaf functions with zero stack don't need a stack check if one's
             // specified, otherwise always insert the stack check.
-            if total_stacksize > 0 || !self.is_leaf {
+            let checked_stacksize = total_stacksize + self.tail_args_growth;
+            if checked_stacksize > 0 || !self.is_leaf {
                 if let Some((reg, stack_limit_load)) = &self.stack_limit {
                     insts.extend(stack_limit_load.clone());
-                    self.insert_stack_check(*reg, total_stacksize, &mut insts);
+                    self.insert_stack_check(*reg, checked_stacksize, &mut insts);
                 }
                 if let Some(min_frame) = &self.probestack_min_frame {
-                    if total_stacksize >= *min_frame {
-                        insts.extend(M::gen_probestack(total_stacksize));
+                    if checked_stacksize >= *min_frame {
+                        insts.extend(M::gen_probestack(checked_stacksize));
                     }
                 }
             }
+
+            if self.tail_args_growth > 0 {
+                debug_assert!(self.setup_frame);
+                insts.extend(M::gen_grow_incoming_args(self.tail_args_growth));
+            }
         }
 
         // Save clobbered registers.
@@ -1336,6 +1398,22 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
     }
 
     fn gen_epilogue(&self) -> SmallInstVec<M::I> {
+        let mut insts = self.gen_tail_call_epilogue();
+        if !self.call_conv.extends_baldrdash() {
+            // The frame record's original copy is still intact above the
+            // incoming argument area's growth, so return from there, handing
+            // our caller back the SP it called us with.
+            if self.tail_args_growth > 0 {
+                insts.extend(M::gen_sp_reg_adjust(self.tail_args_growth as i32));
+            }
+            insts.push(M::gen_ret());
+        }
+
+        log::trace!("Epilogue: {:?}", insts);
+        insts
+    }
+
+    fn gen_tail_call_epilogue(&self) -> SmallInstVec<M::I> {
         let mut insts = smallvec![];
 
         // Restore clobbered registers.
@@ -1353,18 +1431,16 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
         // the CFG, so early returns in the middle of function bodies would cause an incorrect
         // offset for the rest of the body.
 
-        if !self.call_conv.extends_baldrdash() {
-            if self.setup_frame {
-                insts.extend(M::gen_epilogue_frame_restore(&self.flags));
-            }
-
-            insts.push(M::gen_ret());
+        if !self.call_conv.extends_baldrdash() && self.setup_frame {
+            insts.extend(M::gen_epilogue_frame_restore(&self.flags));
         }
-
-        log::trace!("Epilogue: {:?}", insts);
         insts
     }
 
+    fn ret_area_ptr(&self) -> Option<Reg> {
+        self.ret_area_ptr.map(|r| r.to_reg())
+    }
+
     fn frame_size(&self) -> u32 {
         self.total_frame_size
             .expect("frame size not computed before prologue generation")
@@ -1374,6 +1450,10 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
         self.sig.stack_arg_space as u32
     }
 
+    fn tail_call_args_area_size(&self) -> u32 {
+        self.stack_args_size() + self.tail_args_growth
+    }
+
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32 {
         M::get_number_of_spillslots_for_value(rc, ty)
     }
@@ -1456,6 +1536,10 @@ pub struct ABICallerImpl<M: ABIMachineSpec> {
     caller_conv: isa::CallConv,
     /// The settings controlling this compilation.
     flags: settings::Flags,
+    /// For a tail call, whose stack arguments go in the caller's incoming
+    /// argument area, how far SP moves up once the caller's frame is gone,
+    /// to just below the callee's stack arguments.
+    tail_call_sp_adjust: Option<u32>,
 
     _mach: PhantomData<M>,
 }
@@ -1490,6 +1574,7 @@ impl<M: ABIMachineSpec> ABICallerImpl<M> {
             opcode: ir::Opcode::Call,
             caller_conv,
             flags: flags.clone(),
+            tail_call_sp_adjust: None,
             _mach: PhantomData,
         })
     }
@@ -1515,11 +1600,27 @@ impl<M: ABIMachineSpec> ABICallerImpl<M> {
             opcode,
             caller_conv,
             flags: flags.clone(),
+            tail_call_sp_adjust: None,
             _mach: PhantomData,
         })
     }
 }
 
+impl<M: ABIMachineSpec> ABICallerImpl<M> {
+    /// The address of an argument passed on the stack: the outgoing argument
+    /// area at the bottom of the caller's frame, or for a tail call, the
+    /// caller's own incoming argument area.
+    fn arg_stack_amode(&self, offset: i64, ty: Type) -> StackAMode {
+        match self.tail_call_sp_adjust {
+            Some(sp_adjust) => {
+                let fp_to_arg = M::fp_to_arg_offset(self.caller_conv, &self.flags);
+                StackAMode::FPOffset(fp_to_arg + i64::from(sp_adjust) + offset, ty)
+            }
+            None => StackAMode::SPOffset(offset, ty),
+        }
+    }
+}
+
 fn adjust_stack_and_nominal_sp<M: ABIMachineSpec, C: LowerCtx<I = M::I>>(
     ctx: &mut C,
     off: i32,
@@ -1633,7 +1734,7 @@ impl<M: ABIMachineSpec> ABICaller for ABICallerImpl<M> {
                                 ty = M::word_type();
                             }
                             ctx.emit(M::gen_store_stack(
-                                StackAMode::SPOffset(offset, ty),
+                                self.arg_stack_amode(offset, ty),
                                 *from_reg,
                                 ty,
                             ));
@@ -1749,4 +1850,64 @@ impl<M: ABIMachineSpec> ABICaller for ABICallerImpl<M> {
             }
         }
     }
+
+    fn unwinds_to_landing_pad(&mut self) {
+        for reg in M::get_regs_clobbered_by_unwind(self.sig.call_conv) {
+            if !self.defs.contains(&reg) {
+                self.defs.push(reg);
+            }
+        }
+    }
+
+    fn prepare_tail_call<C: LowerCtx<I = Self::I>>(&mut self, ctx: &mut C) -> CodegenResult<()> {
+        if self.caller_conv.extends_baldrdash() {
+            return Err(CodegenError::Unsupported(format!(
+                "tail calls from {} functions",
+                self.caller_conv
+            )));
+        }
+        if self.sig.args.iter().any(|arg| arg.is_struct_arg()) {
+            return Err(CodegenError::Unsupported(format!(
+                "tail calls with struct arguments"
+            )));
+        }
+        let area = ctx.abi().tail_call_args_area_size();
+        if self.sig.stack_arg_space > i64::from(area) {
+            debug_assert!(!self.flags.enable_growing_tail_calls());
+            return Err(CodegenError::Unsupported(format!(
+                "tail call with {} bytes of stack arguments from a function with {}",
+                self.sig.stack_arg_space, area
+            )));
+        }
+        // With growing tail calls, the callee's stack arguments end where the
+        // incoming argument area does, so that its own tail calls can reuse
+        // the whole area however their sizes differ, and a chain of them
+        // doesn't creep up or down the stack. Otherwise they start where the
+        // area does, so that the callee returns with SP where our caller
+        // expects it.
+        let sp_adjust = if self.flags.enable_growing_tail_calls() {
+            area - self.sig.stack_arg_space as u32
+        } else {
+            0
+        };
+        self.tail_call_sp_adjust = Some(sp_adjust);
+        Ok(())
+    }
+
+    fn emit_return_call<C: LowerCtx<I = Self::I>>(&mut self, ctx: &mut C) {
+        let sp_adjust = self.tail_call_sp_adjust.unwrap();
+        let uses = mem::replace(&mut self.uses, Default::default());
+        // The callee returns to our caller, so it uses our return area, whose
+        // layout matches since the return types and calling conventions do.
+        if let Some(i) = self.sig.stack_ret_arg {
+            let ret_area_ptr = ctx
+                .abi()
+                .ret_area_ptr()
+                .expect("tail call to a function with a return area from one without");
+            self.emit_copy_regs_to_arg(ctx, i, ValueRegs::one(ret_area_ptr));
+        }
+        for inst in M::gen_return_call(&self.dest, uses, self.sig.call_conv, sp_adjust) {
+            ctx.emit(inst);
+        }
+    }
 }
@@ -170,6 +170,9 @@ pub struct MachBuffer<I: VCodeInst> {
     traps: SmallVec<[MachTrap; 16]>,
     /// Any call site records referring to this code.
     call_sites: SmallVec<[MachCallSite; 16]>,
+    /// Any exception handlers for the calls in this code, as the call's return
+    /// address, the landing pad, and the landing pad's stack pointer offset.
+    exception_handlers: SmallVec<[(CodeOffset, MachLabel, u32); 4]>,
     /// Any source location mappings referring to this code.
     srclocs: SmallVec<[MachSrcLoc; 64]>,
     /// Any stack maps referring to this code.
@@ -239,6 +242,8 @@ pub struct MachBufferFinalized {
     traps: SmallVec<[MachTrap; 16]>,
     /// Any call site records referring to this code.
     call_sites: SmallVec<[MachCallSite; 16]>,
+    /// Any exception handlers for the calls in this code.
+    exception_handlers: SmallVec<[MachExceptionHandler; 4]>,
     /// Any source location mappings referring to this code.
     srclocs: SmallVec<[MachSrcLoc; 64]>,
     /// Any stack maps referring to this code.
@@ -302,6 +307,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             relocs: SmallVec::new(),
             traps: SmallVec::new(),
             call_sites: SmallVec::new(),
+            exception_handlers: SmallVec::new(),
             srclocs: SmallVec::new(),
             stack_maps: SmallVec::new(),
             unwind_info: SmallVec::new(),
@@ -1202,6 +1208,16 @@ impl<I: VCodeInst> MachBuffer<I> {
         // incorrect.
         assert!(self.fixup_records.is_empty());
 
+        let exception_handlers = self
+            .exception_handlers
+            .iter()
+            .map(|&(ret_addr, landing_pad, sp_offset)| MachExceptionHandler {
+                ret_addr,
+                landing_pad: self.resolve_label_offset(landing_pad),
+                sp_offset,
+            })
+            .collect();
+
         let mut srclocs = self.srclocs;
         srclocs.sort_by_key(|entry| entry.start);
 
@@ -1210,6 +1226,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             relocs: self.relocs,
             traps: self.traps,
             call_sites: self.call_sites,
+            exception_handlers,
             srclocs,
             stack_maps: self.stack_maps,
             unwind_info: self.unwind_info,
@@ -1252,6 +1269,18 @@ impl<I: VCodeInst> MachBuffer<I> {
         });
     }
 
+    /// Add an exception handler for the most recently emitted call: if the stack
+    /// unwinds through that call, execution resumes at `landing_pad`, with the
+    /// stack pointer `sp_offset` bytes below the frame pointer.
+    pub fn add_exception_handler(&mut self, landing_pad: MachLabel, sp_offset: u32) {
+        let call_site = self
+            .call_sites
+            .last()
+            .expect("exception handler without a call");
+        self.exception_handlers
+            .push((call_site.ret_addr, landing_pad, sp_offset));
+    }
+
     /// Add an unwind record at the current offset.
     pub fn add_unwind(&mut self, unwind: UnwindInst) {
         self.unwind_info.push((self.cur_offset(), unwind));
@@ -1364,6 +1393,11 @@ impl MachBufferFinalized {
     pub fn stack_maps(&self) -> &[MachStackMap] {
         &self.stack_maps[..]
     }
+
+    /// Get the exception handlers for this code, sorted by return address.
+    pub fn exception_handlers(&self) -> &[MachExceptionHandler] {
+        &self.exception_handlers[..]
+    }
 }
 
 /// A constant that is deferred to the next constant-pool opportunity.
@@ -1456,6 +1490,18 @@ pub struct MachStackMap {
     pub stack_map: StackMap,
 }
 
+/// Record of an exception handler: where to resume when the stack unwinds
+/// through a call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachExceptionHandler {
+    /// The code offset of the call's return address.
+    pub ret_addr: CodeOffset,
+    /// The code offset of the landing pad.
+    pub landing_pad: CodeOffset,
+    /// How far below the frame pointer the stack pointer is at the landing pad.
+    pub sp_offset: u32,
+}
+
 /// Record of branch instruction in the buffer, to facilitate editing.
 #[derive(Clone, Debug)]
 struct MachBranch {
@@ -1840,6 +1886,57 @@ mod test {
         assert_eq!(&golden_data[..], &buf.data[..]);
     }
 
+    #[test]
+    fn test_exception_handler() {
+        // label0:
+        //   bl <callee>
+        //   b label1 ; unwind to label2
+        // label1:
+        //   ret
+        // label2:
+        //   b label3
+        // label3:
+        //   ret
+        //
+        // -- the landing pad resolves through label2's trivial jump block to
+        // label3.
+        let info = EmitInfo::new(settings::Flags::new(settings::builder()));
+        let mut buf = MachBuffer::new();
+        let mut state = Default::default();
+
+        buf.reserve_labels_for_blocks(4);
+
+        buf.bind_label(label(0));
+        buf.put4(0x94000000);
+        buf.add_call_site(SourceLoc::default(), Opcode::Call);
+        let inst = Inst::JumpUnwind {
+            dest: target(1),
+            landing_pad: target(2),
+        };
+        inst.emit(&mut buf, &info, &mut state);
+
+        buf.bind_label(label(1));
+        Inst::Ret.emit(&mut buf, &info, &mut state);
+
+        buf.bind_label(label(2));
+        let inst = Inst::Jump { dest: target(3) };
+        inst.emit(&mut buf, &info, &mut state);
+
+        buf.bind_label(label(3));
+        Inst::Ret.emit(&mut buf, &info, &mut state);
+
+        let buf = buf.finish();
+        assert_eq!(12, buf.total_size());
+        assert_eq!(
+            buf.exception_handlers(),
+            &[MachExceptionHandler {
+                ret_addr: 4,
+                landing_pad: 8,
+                sp_offset: 0,
+            }]
+        );
+    }
+
     #[test]
     fn metadata_records() {
         let mut buf = MachBuffer::<Inst>::new();
@@ -86,6 +86,9 @@ pub trait LowerCtx {
     fn call_target<'b>(&'b self, ir_inst: Inst) -> Option<(&'b ExternalName, RelocDistance)>;
     /// Get the signature for a call or call-indirect instruction.
     fn call_sig<'b>(&'b self, ir_inst: Inst) -> Option<&'b Signature>;
+    /// Is a call or call-indirect instruction followed by a `br_unwind`, so
+    /// that an exception unwinding through it resumes in this function?
+    fn has_landing_pad(&self, ir_inst: Inst) -> bool;
     /// Get the symbol name, relocation distance estimate, and offset for a
     /// symbol_value instruction.
     fn symbol_value<'b>(&'b self, ir_inst: Inst) -> Option<(&'b ExternalName, RelocDistance, i64)>;
@@ -1140,6 +1143,13 @@ impl<'func, I: VCodeInst> LowerCtx for Lower<'func, I> {
         }
     }
 
+    fn has_landing_pad(&self, ir_inst: Inst) -> bool {
+        self.f
+            .layout
+            .next_inst(ir_inst)
+            .map_or(false, |next| self.f.dfg[next].opcode() == Opcode::BrUnwind)
+    }
+
     fn symbol_value<'b>(&'b self, ir_inst: Inst) -> Option<(&'b ExternalName, RelocDistance, i64)> {
         match &self.f.dfg[ir_inst] {
             &InstructionData::UnaryGlobalValue { global_value, .. } => {
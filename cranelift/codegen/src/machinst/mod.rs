@@ -258,6 +258,9 @@ pub enum MachTerminator<'a> {
     None,
     /// A return instruction.
     Ret,
+    /// A tail call, which leaves the function like a return, but jumps to
+    /// the callee instead of returning to the caller.
+    TailCall,
     /// An unconditional branch to another block.
     Uncond(MachLabel),
     /// A conditional branch to one of two other blocks.
@@ -229,7 +229,7 @@ impl<I: VCodeInst> VCodeBuilder<I> {
     /// Push an instruction for the current BB and current IR inst within the BB.
     pub fn push(&mut self, insn: I, is_safepoint: bool) {
         match insn.is_term() {
-            MachTerminator::None | MachTerminator::Ret => {}
+            MachTerminator::None | MachTerminator::Ret | MachTerminator::TailCall => {}
             MachTerminator::Uncond(target) => {
                 self.vcode.block_succs.push(BlockIx::new(target.get()));
             }
@@ -404,16 +404,26 @@ impl<I: VCodeInst> VCode<I> {
                 };
 
                 // Whenever encountering a return instruction, replace it
-                // with the epilogue.
-                let is_ret = insn.is_term() == MachTerminator::Ret;
-                if is_ret {
-                    let epilogue = self.abi.gen_epilogue();
-                    let len = epilogue.len();
-                    final_insns.extend(epilogue.into_iter());
-                    final_srclocs.extend(iter::repeat(srcloc).take(len));
-                } else {
-                    final_insns.push(insn.clone());
-                    final_srclocs.push(srcloc);
+                // with the epilogue. A tail call keeps its jump, but tears
+                // down the frame first.
+                match insn.is_term() {
+                    MachTerminator::Ret => {
+                        let epilogue = self.abi.gen_epilogue();
+                        let len = epilogue.len();
+                        final_insns.extend(epilogue.into_iter());
+                        final_srclocs.extend(iter::repeat(srcloc).take(len));
+                    }
+                    MachTerminator::TailCall => {
+                        let epilogue = self.abi.gen_tail_call_epilogue();
+                        let len = epilogue.len() + 1;
+                        final_insns.extend(epilogue.into_iter());
+                        final_insns.push(insn.clone());
+                        final_srclocs.extend(iter::repeat(srcloc).take(len));
+                    }
+                    _ => {
+                        final_insns.push(insn.clone());
+                        final_srclocs.push(srcloc);
+                    }
                 }
 
                 // Was this instruction a safepoint instruction? Add its final
@@ -527,6 +527,7 @@ use_pinned_reg_as_heap_base = false
 enable_simd = false
 enable_atomics = true
 enable_safepoints = false
+enable_growing_tail_calls = false
 enable_llvm_abi_extensions = false
 unwind_info = true
 machine_code_cfg_info = false
@@ -588,7 +588,7 @@ impl<'a> Verifier<'a> {
         let num_fixed_results = inst_data.opcode().constraints().num_fixed_results();
         // var_results is 0 if we aren't a call instruction
         let var_results = dfg
-            .call_signature(inst)
+            .non_tail_call_signature(inst)
             .map_or(0, |sig| dfg.signatures[sig].returns.len());
         let total_results = num_fixed_results + var_results;
 
@@ -1236,6 +1236,7 @@ impl<'a> Verifier<'a> {
         let _ = self.typecheck_fixed_args(inst, ctrl_type, errors);
         let _ = self.typecheck_variable_args(inst, errors);
         let _ = self.typecheck_return(inst, errors);
+        let _ = self.typecheck_tail_call(inst, errors);
         let _ = self.typecheck_special(inst, ctrl_type, errors);
 
         // Misuses of copy_nop instructions are fatal
@@ -1538,6 +1539,50 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// A tail call returns the callee's results to the current function's caller, so the callee
+    /// must return the same types using the same calling convention.
+    fn typecheck_tail_call(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let sig_ref = match self.func.dfg[inst] {
+            ir::InstructionData::Call {
+                opcode: Opcode::ReturnCall,
+                func_ref,
+                ..
+            } => self.func.dfg.ext_funcs[func_ref].signature,
+            ir::InstructionData::CallIndirect {
+                opcode: Opcode::ReturnCallIndirect,
+                sig_ref,
+                ..
+            } => sig_ref,
+            _ => return Ok(()),
+        };
+        let callee = &self.func.dfg.signatures[sig_ref];
+        if callee.call_conv != self.func.signature.call_conv {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                format!(
+                    "tail call to a {} function from a {} function",
+                    callee.call_conv, self.func.signature.call_conv
+                ),
+            ));
+        }
+        let returns = |sig: &ir::Signature| -> Vec<Type> {
+            sig.returns.iter().map(|r| r.value_type).collect()
+        };
+        if returns(callee) != returns(&self.func.signature) {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                "return types of tail call must match function signature",
+            ));
+        }
+        Ok(())
+    }
+
     // Check special-purpose type constraints that can't be expressed in the normal opcode
     // constraints.
     fn typecheck_special(
@@ -1969,6 +2014,25 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// The landing pad given by a `br_unwind` belongs to the call before it, so there must be one.
+    fn verify_landing_pad(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        if self.func.dfg[inst].opcode() != Opcode::BrUnwind {
+            return Ok(());
+        }
+        match self.func.layout.prev_inst(inst) {
+            Some(call) if self.func.dfg.non_tail_call_signature(call).is_some() => Ok(()),
+            _ => errors.fatal((
+                inst,
+                self.context(inst),
+                "br_unwind must directly follow a call",
+            )),
+        }
+    }
+
     fn typecheck_function_signature(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         self.func
             .signature
@@ -2033,6 +2097,7 @@ impl<'a> Verifier<'a> {
                 self.block_integrity(block, inst, errors)?;
                 self.instruction_integrity(inst, errors)?;
                 self.verify_safepoint_unused(inst, errors)?;
+                self.verify_landing_pad(inst, errors)?;
                 self.typecheck(inst, errors)?;
                 self.verify_encoding(inst, errors)?;
                 self.immediate_constraints(inst, errors)?;
@@ -580,6 +580,22 @@ impl<'a> FunctionBuilder<'a> {
         self.func_ctx.blocks[self.position.unwrap()].filled
     }
 
+    /// Declares that the current block ends with a terminator inserted through
+    /// [`cursor`](Self::cursor), like a tail call built by an environment, so that no more
+    /// instructions can be added to it.
+    pub fn fill_current_block(&mut self) {
+        let block = self.position.unwrap();
+        debug_assert!(
+            self.func
+                .layout
+                .last_inst(block)
+                .map_or(false, |inst| self.func.dfg[inst].opcode().is_terminator()),
+            "block {} doesn't end with a terminator",
+            block
+        );
+        self.func_ctx.blocks[block].filled = true;
+    }
+
     /// Returns a displayable object for the function as it is.
     ///
     /// Useful for debug purposes. Use it with `None` for standard printing.
@@ -822,11 +838,6 @@ fn greatest_divisible_power_of_two(size: u64) -> u64 {
 
 // Helper functions
 impl<'a> FunctionBuilder<'a> {
-    /// A Block is 'filled' when a terminator instruction is present.
-    fn fill_current_block(&mut self) {
-        self.func_ctx.blocks[self.position.unwrap()].filled = true;
-    }
-
     fn declare_successor(&mut self, dest_block: Block, jump_inst: Inst) {
         self.func_ctx
             .ssa
@@ -122,6 +122,11 @@ impl<'a> Interpreter<'a> {
                         .set_all(function.dfg.inst_results(inst), returned_arguments);
                     maybe_inst = layout.next_inst(inst)
                 }
+                ControlFlow::ReturnCall(called_function, arguments) => {
+                    // The callee's frame replaces this one and its results are ours.
+                    self.state.pop_frame();
+                    return self.call(called_function, &arguments);
+                }
                 ControlFlow::Return(returned_values) => {
                     self.state.pop_frame();
                     return Ok(ControlFlow::Return(returned_values));
@@ -463,6 +468,37 @@ mod tests {
         assert_eq!(result, vec![DataValue::I32(0)])
     }
 
+    #[test]
+    fn tail_call() {
+        let code = "
+        function %child(i32) -> i32 {
+        block0(v0: i32):
+            v1 = iadd_imm v0, -1
+            return v1
+        }
+
+        function %parent(i32) -> i32 {
+            fn42 = %child(i32) -> i32
+        block0(v0: i32):
+            v1 = iadd_imm v0, 1
+            return_call fn42(v1)
+        }";
+
+        let mut env = FunctionStore::default();
+        let funcs = parse_functions(code).unwrap().to_vec();
+        funcs.iter().for_each(|f| env.add(f.name.to_string(), f));
+
+        let state = InterpreterState::default().with_function_store(env);
+        let mut interpreter = Interpreter::new(state);
+        let result = interpreter
+            .call_by_name("%parent", &[DataValue::I32(0)])
+            .unwrap()
+            .unwrap_return();
+
+        assert_eq!(result, vec![DataValue::I32(0)]);
+        assert!(interpreter.state.frame_stack.is_empty());
+    }
+
     #[test]
     fn state_flags() {
         let mut state = InterpreterState::default();
@@ -17,7 +17,7 @@ impl CompiledBlob {
         get_got_entry: impl Fn(&ExternalName) -> *const u8,
         get_plt_entry: impl Fn(&ExternalName) -> *const u8,
     ) {
-        use std::ptr::write_unaligned;
+        use std::ptr::{read_unaligned, write_unaligned};
 
         for &RelocRecord {
             reloc,
@@ -81,6 +81,40 @@ impl CompiledBlob {
                         write_unaligned(at as *mut i32, pcrel)
                     };
                 }
+                Reloc::RiscvCallPlt => {
+                    let base = get_address(name);
+                    let what = unsafe { base.offset(isize::try_from(addend).unwrap()) };
+                    let pcrel = i32::try_from((what as isize) - (at as isize)).unwrap() as u32;
+                    // Split the offset between the `auipc` and the `jalr`,
+                    // compensating for the sign extension of the latter.
+                    let hi20 = pcrel.wrapping_add(0x800) & 0xffff_f000;
+                    let lo12 = pcrel.wrapping_sub(hi20) & 0xfff;
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    unsafe {
+                        let auipc = at as *mut u32;
+                        let jalr = at.offset(4) as *mut u32;
+                        write_unaligned(auipc, (read_unaligned(auipc) & 0xfff) | hi20);
+                        write_unaligned(jalr, (read_unaligned(jalr) & 0x000f_ffff) | (lo12 << 20));
+                    };
+                }
+                Reloc::Arm32Call => {
+                    let base = get_address(name);
+                    let what = unsafe { base.offset(isize::try_from(addend).unwrap()) };
+                    // A Thumb-2 `bl`, relative to the address after it.
+                    let pcrel = i32::try_from((what as isize) - (at as isize) - 4).unwrap();
+                    assert!(pcrel >= -(1 << 24) && pcrel < (1 << 24));
+                    let pcrel = pcrel as u32;
+                    let s = (pcrel >> 24) & 1;
+                    let j1 = !((pcrel >> 23) ^ s) & 1;
+                    let j2 = !((pcrel >> 22) ^ s) & 1;
+                    let hi = 0xf000 | (s << 10) | ((pcrel >> 12) & 0x3ff);
+                    let lo = 0xd000 | (j1 << 13) | (j2 << 11) | ((pcrel >> 1) & 0x7ff);
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    unsafe {
+                        write_unaligned(at as *mut u16, hi as u16);
+                        write_unaligned(at.offset(2) as *mut u16, lo as u16);
+                    };
+                }
                 _ => unimplemented!(),
             }
         }
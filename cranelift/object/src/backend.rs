@@ -73,6 +73,7 @@ impl ObjectBuilder {
             target_lexicon::Architecture::X86_64 => object::Architecture::X86_64,
             target_lexicon::Architecture::Arm(_) => object::Architecture::Arm,
             target_lexicon::Architecture::Aarch64(_) => object::Architecture::Aarch64,
+            target_lexicon::Architecture::Riscv64(_) => object::Architecture::Riscv64,
             architecture => {
                 return Err(ModuleError::Backend(anyhow!(
                     "target architecture {:?} is unsupported",
@@ -642,6 +643,30 @@ impl ObjectModule {
                     12,
                 )
             }
+            Reloc::RiscvCallPlt => {
+                assert_eq!(
+                    self.object.format(),
+                    object::BinaryFormat::Elf,
+                    "RiscvCallPlt is not supported for this file format"
+                );
+                (
+                    RelocationKind::Elf(object::elf::R_RISCV_CALL_PLT),
+                    RelocationEncoding::Generic,
+                    64,
+                )
+            }
+            Reloc::Arm32Call => {
+                assert_eq!(
+                    self.object.format(),
+                    object::BinaryFormat::Elf,
+                    "Arm32Call is not supported for this file format"
+                );
+                (
+                    RelocationKind::Elf(object::elf::R_ARM_THM_PC22),
+                    RelocationEncoding::Generic,
+                    32,
+                )
+            }
             // FIXME
             reloc => unimplemented!("{:?}", reloc),
         };
@@ -73,12 +73,12 @@
 
 use super::{hash_map, HashMap};
 use crate::environ::{FuncEnvironment, GlobalVariable, ReturnMode};
-use crate::state::{ControlStackFrame, ElseData, FuncTranslationState};
+use crate::state::{ControlStackFrame, ElseData, FuncTranslationState, TryData};
 use crate::translation_utils::{
     block_with_params, blocktype_params_results, f32_translation, f64_translation,
 };
 use crate::wasm_unsupported;
-use crate::{FuncIndex, GlobalIndex, MemoryIndex, TableIndex, TypeIndex, WasmResult};
+use crate::{FuncIndex, GlobalIndex, MemoryIndex, TableIndex, TagIndex, TypeIndex, WasmResult};
 use core::convert::TryInto;
 use core::{i32, u32};
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
@@ -388,6 +388,12 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 // below.
             }
 
+            if let ControlStackFrame::Try { .. } = frame {
+                // Exceptions that the `try` doesn't catch go to the next handler out.
+                finish_try(frame, 0, true, builder, state, environ)?;
+                return Ok(());
+            }
+
             builder.switch_to_block(next_block);
             builder.seal_block(next_block);
 
@@ -548,17 +554,57 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             state.popn(return_count);
             state.reachable = false;
         }
-        /********************************** Exception handing **********************************/
-        Operator::Try { .. }
-        | Operator::Catch { .. }
-        | Operator::Throw { .. }
-        | Operator::Rethrow { .. }
-        | Operator::Delegate { .. }
-        | Operator::CatchAll => {
-            return Err(wasm_unsupported!(
-                "proposed exception handling operator {:?}",
-                op
-            ));
+        /********************************** Exception handing **********************************
+         * Exceptions unwind the native stack. Every call inside the body of a `try` is followed
+         * by a `br_unwind` to the landing pad of the innermost such body, and the unwinder
+         * resumes execution there when the call throws. The landing pad dispatches to the
+         * `catch` clauses in order by comparing tags, and sends exceptions that aren't caught
+         * on to the next handler out, or continues unwinding into the caller if there is none.
+         ***********************************************************************************/
+        Operator::Try { ty } => {
+            let (params, results) = blocktype_params_results(validator, *ty)?;
+            let destination = block_with_params(builder, results.clone(), environ)?;
+            let landing_pad = builder.create_block();
+            state.push_try(destination, landing_pad, params.len(), results.len());
+        }
+        Operator::Catch { index } => {
+            translate_catch_clause(validator, Some(*index), builder, state, environ)?;
+        }
+        Operator::CatchAll => {
+            translate_catch_clause(validator, None, builder, state, environ)?;
+        }
+        Operator::Throw { index } => {
+            let tag_index = TagIndex::from_u32(*index);
+            let num_args = tag_param_count(validator, *index);
+            environ.translate_throw(builder.cursor(), tag_index, state.peekn(num_args))?;
+            state.popn(num_args);
+            translate_unwind_edge(0, builder, state);
+            builder.ins().trap(ir::TrapCode::UnreachableCodeReached);
+            state.reachable = false;
+        }
+        Operator::Rethrow { relative_depth } => {
+            let i = state.control_stack.len() - 1 - (*relative_depth as usize);
+            let exception = match state.control_stack[i] {
+                ControlStackFrame::Try {
+                    try_data: TryData::Catch { exception, .. },
+                    ..
+                } => exception,
+                _ => unreachable!("rethrow must target a catch clause"),
+            };
+            environ.translate_rethrow(builder.cursor(), exception)?;
+            translate_unwind_edge(0, builder, state);
+            builder.ins().trap(ir::TrapCode::UnreachableCodeReached);
+            state.reachable = false;
+        }
+        Operator::Delegate { relative_depth } => {
+            let frame = state.control_stack.pop().unwrap();
+            if !builder.is_unreachable() || !builder.is_pristine() {
+                let return_count = frame.num_return_values();
+                let return_args = state.peekn_mut(return_count);
+                canonicalise_then_jump(builder, frame.following_code(), return_args);
+            }
+            // Exceptions thrown in the body go to the handler of the frame `relative_depth` out.
+            finish_try(frame, *relative_depth, true, builder, state, environ)?;
         }
         /************************************ Calls ****************************************
          * The call instructions pop off their arguments from the stack and append their
@@ -593,6 +639,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             );
             state.popn(num_args);
             state.pushn(inst_results);
+            translate_unwind_edge(0, builder, state);
         }
         Operator::CallIndirect { index, table_index } => {
             // `index` is the index of the function's signature and `table_index` is the index of
@@ -626,6 +673,61 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             );
             state.popn(num_args);
             state.pushn(inst_results);
+            translate_unwind_edge(0, builder, state);
+        }
+        Operator::ReturnCall { function_index } => {
+            if environ.return_mode() == ReturnMode::FallthroughReturn {
+                return Err(wasm_unsupported!("tail calls with fallthrough returns"));
+            }
+            let (fref, num_args) = state.get_direct_func(builder.func, *function_index, environ)?;
+
+            // Bitcast any vector arguments to their default type, I8X16, before calling.
+            let callee_signature =
+                &builder.func.dfg.signatures[builder.func.dfg.ext_funcs[fref].signature];
+            let args = state.peekn_mut(num_args);
+            let types = wasm_param_types(&callee_signature.params, |i| {
+                environ.is_wasm_parameter(&callee_signature, i)
+            });
+            bitcast_arguments(args, &types, builder);
+
+            environ.translate_return_call(
+                builder.cursor(),
+                FuncIndex::from_u32(*function_index),
+                fref,
+                args,
+            )?;
+            builder.fill_current_block();
+            state.popn(num_args);
+            state.reachable = false;
+        }
+        Operator::ReturnCallIndirect { index, table_index } => {
+            if environ.return_mode() == ReturnMode::FallthroughReturn {
+                return Err(wasm_unsupported!("tail calls with fallthrough returns"));
+            }
+            let (sigref, num_args) = state.get_indirect_sig(builder.func, *index, environ)?;
+            let table = state.get_or_create_table(builder.func, *table_index, environ)?;
+            let callee = state.pop1();
+
+            // Bitcast any vector arguments to their default type, I8X16, before calling.
+            let callee_signature = &builder.func.dfg.signatures[sigref];
+            let args = state.peekn_mut(num_args);
+            let types = wasm_param_types(&callee_signature.params, |i| {
+                environ.is_wasm_parameter(&callee_signature, i)
+            });
+            bitcast_arguments(args, &types, builder);
+
+            environ.translate_return_call_indirect(
+                builder.cursor(),
+                TableIndex::from_u32(*table_index),
+                table,
+                TypeIndex::from_u32(*index),
+                sigref,
+                callee,
+                state.peekn(num_args),
+            )?;
+            builder.fill_current_block();
+            state.popn(num_args);
+            state.reachable = false;
         }
         /******************************* Memory management ***********************************
          * Memory management is handled by environment. It is usually translated into calls to
@@ -2008,9 +2110,6 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let b_high = builder.ins().uwiden_high(b);
             state.push1(builder.ins().imul(a_high, b_high));
         }
-        Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
-            return Err(wasm_unsupported!("proposed tail-call operator {:?}", op));
-        }
     };
     Ok(())
 }
@@ -2045,6 +2144,35 @@ fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
         Operator::Loop { ty: _ } | Operator::Block { ty: _ } => {
             state.push_block(ir::Block::reserved_value(), 0, 0);
         }
+        Operator::Try { ty: _ } => {
+            // Push a placeholder control stack entry. Nothing in the `try` is
+            // reachable, including its handlers.
+            state.push_try(
+                ir::Block::reserved_value(),
+                ir::Block::reserved_value(),
+                0,
+                0,
+            );
+        }
+        Operator::Catch { index } => {
+            translate_catch_clause(validator, Some(index), builder, state, environ)?;
+        }
+        Operator::CatchAll => {
+            translate_catch_clause(validator, None, builder, state, environ)?;
+        }
+        Operator::Delegate { relative_depth } => {
+            let frame = state.control_stack.pop().unwrap();
+            frame.truncate_value_stack_to_original_size(&mut state.stack);
+            let exit_is_branched_to = frame.exit_is_branched_to();
+            finish_try(
+                frame,
+                relative_depth,
+                exit_is_branched_to,
+                builder,
+                state,
+                environ,
+            )?;
+        }
         Operator::Else => {
             let i = state.control_stack.len() - 1;
             match state.control_stack[i] {
@@ -2101,6 +2229,14 @@ fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
             // Pop unused parameters from stack.
             frame.truncate_value_stack_to_original_size(stack);
 
+            if let ControlStackFrame::Try { .. } = frame {
+                // The `try` has no fallthrough into the following code, but
+                // earlier clauses may have branched there.
+                let exit_is_branched_to = frame.exit_is_branched_to();
+                finish_try(frame, 0, exit_is_branched_to, builder, state, environ)?;
+                return Ok(());
+            }
+
             let reachable_anyway = match frame {
                 // If it is a loop we also have to seal the body loop block
                 ControlStackFrame::Loop { header, .. } => {
@@ -2148,6 +2284,180 @@ fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Get the number of values in the payload of exceptions with the tag `index`.
+fn tag_param_count(validator: &FuncValidator<impl WasmModuleResources>, index: u32) -> usize {
+    let ty = validator
+        .resources()
+        .tag_at(index)
+        .expect("should be valid");
+    wasmparser::WasmFuncType::len_inputs(ty)
+}
+
+/// Make the call that was just translated unwind to the innermost exception handler of the frame
+/// `relative_depth` frames below the top of the control stack, if there is one in this function.
+/// Otherwise exceptions thrown by the call unwind straight through to the caller.
+fn translate_unwind_edge(
+    relative_depth: usize,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+) {
+    if let Some(handler) = state.exception_handler(relative_depth) {
+        builder.ins().br_unwind(handler, &[]);
+        let next_block = builder.create_block();
+        canonicalise_then_jump(builder, next_block, &[]);
+        builder.seal_block(next_block); // Only predecessor is the current block.
+        builder.switch_to_block(next_block);
+    }
+}
+
+/// Translate the start of a `catch` clause for the tag `index`, or of a `catch_all` clause if
+/// `index` is `None`, which also ends the body or clause before it.
+///
+/// The clause is entered from the landing pad of the `try` body if it is the first clause, or
+/// from the dispatch block of the clause before it otherwise. Either way, an exception that
+/// doesn't match the tag moves on to a new dispatch block for the next clause.
+fn translate_catch_clause<FE: FuncEnvironment + ?Sized>(
+    validator: &FuncValidator<impl WasmModuleResources>,
+    index: Option<u32>,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let i = state.control_stack.len() - 1;
+    let (destination, num_return_values, head_is_reachable, entry) = match state.control_stack[i] {
+        ControlStackFrame::Try {
+            destination,
+            num_return_values,
+            head_is_reachable,
+            ref try_data,
+            ..
+        } => {
+            let entry = match *try_data {
+                TryData::Body { landing_pad } => Some((landing_pad, true)),
+                TryData::Catch { dispatch, .. } => dispatch.map(|dispatch| (dispatch, false)),
+            };
+            (destination, num_return_values, head_is_reachable, entry)
+        }
+        _ => unreachable!(),
+    };
+
+    // The body or clause we just finished continues after the `try`.
+    if state.reachable {
+        let return_args = state.peekn_mut(num_return_values);
+        canonicalise_then_jump(builder, destination, return_args);
+        state.control_stack[i].set_branched_to_exit();
+    }
+    state.control_stack[i].truncate_value_stack_to_original_size(&mut state.stack);
+    state.reachable = false;
+    if !head_is_reachable {
+        return Ok(());
+    }
+
+    let (entry, is_landing_pad) = entry.expect("no clause can follow a `catch_all`");
+    builder.switch_to_block(entry);
+    builder.seal_block(entry);
+    if is_landing_pad {
+        environ.translate_landing_pad(builder)?;
+    }
+
+    let tag_index = index.map(TagIndex::from_u32);
+    let dispatch = match tag_index {
+        Some(tag_index) => {
+            let matches = environ.translate_exception_matches(builder.cursor(), tag_index)?;
+            let catch_block = builder.create_block();
+            let dispatch = builder.create_block();
+            canonicalise_then_brnz(builder, matches, catch_block, &[]);
+            canonicalise_then_jump(builder, dispatch, &[]);
+            builder.seal_block(catch_block);
+            builder.switch_to_block(catch_block);
+            Some(dispatch)
+        }
+        None => None,
+    };
+
+    let (exception, payload) = environ.translate_catch(builder.cursor(), tag_index)?;
+    debug_assert_eq!(
+        payload.len(),
+        index.map_or(0, |index| tag_param_count(validator, index)),
+        "translate_catch payload should match the tag signature"
+    );
+    state.pushn(&payload);
+    state.reachable = true;
+
+    if let ControlStackFrame::Try {
+        ref mut try_data, ..
+    } = state.control_stack[i]
+    {
+        *try_data = TryData::Catch {
+            dispatch,
+            exception,
+        };
+    }
+    Ok(())
+}
+
+/// Finish translating a `try` at its `end` or `delegate`, after its frame has been popped off
+/// the control stack and the value stack has been truncated to its original size.
+///
+/// Exceptions that the `try` doesn't catch are sent to the handler of the frame
+/// `relative_depth` out, or unwind into the caller if there is no such handler in this
+/// function. If `exit_is_reachable`, translation continues after the `try`.
+fn finish_try<FE: FuncEnvironment + ?Sized>(
+    frame: ControlStackFrame,
+    relative_depth: u32,
+    exit_is_reachable: bool,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (destination, head_is_reachable, uncaught) = match frame {
+        ControlStackFrame::Try {
+            destination,
+            head_is_reachable,
+            ref try_data,
+            ..
+        } => {
+            let uncaught = match *try_data {
+                TryData::Body { landing_pad } => Some((landing_pad, true)),
+                TryData::Catch { dispatch, .. } => dispatch.map(|dispatch| (dispatch, false)),
+            };
+            (destination, head_is_reachable, uncaught)
+        }
+        _ => unreachable!(),
+    };
+    if !head_is_reachable {
+        return Ok(());
+    }
+
+    if let Some((uncaught, is_landing_pad)) = uncaught {
+        builder.switch_to_block(uncaught);
+        builder.seal_block(uncaught);
+        if is_landing_pad {
+            environ.translate_landing_pad(builder)?;
+        }
+        match state.exception_handler(relative_depth as usize) {
+            Some(handler) => canonicalise_then_jump(builder, handler, &[]),
+            None => {
+                environ.translate_resume_unwind(builder.cursor())?;
+                builder.ins().trap(ir::TrapCode::UnreachableCodeReached)
+            }
+        };
+    }
+
+    if exit_is_reachable {
+        builder.switch_to_block(destination);
+        builder.seal_block(destination);
+        frame.truncate_value_stack_to_original_size(&mut state.stack);
+        state
+            .stack
+            .extend_from_slice(builder.block_params(destination));
+        state.reachable = true;
+    } else {
+        state.reachable = false;
+    }
+    Ok(())
+}
+
 /// This function is a generalized helper for validating that a wasm-supplied
 /// heap address is in-bounds.
 ///
@@ -13,7 +13,7 @@ use crate::state::FuncTranslationState;
 use crate::WasmType;
 use crate::{
     DataIndex, DefinedFuncIndex, ElemIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex,
-    Table, TableIndex, TypeIndex, WasmFuncType, WasmResult,
+    SignatureIndex, Table, TableIndex, Tag, TagIndex, TypeIndex, WasmFuncType, WasmResult,
 };
 use core::convert::TryFrom;
 use cranelift_codegen::cursor::FuncCursor;
@@ -73,6 +73,9 @@ pub struct DummyModuleInfo {
     /// Module and field names of imported memories as provided by `declare_memory_import`.
     pub imported_memories: Vec<(String, String)>,
 
+    /// Module and field names of imported tags as provided by `declare_tag_import`.
+    pub imported_tags: Vec<(String, String)>,
+
     /// Functions, imported and local.
     pub functions: PrimaryMap<FuncIndex, Exportable<TypeIndex>>,
 
@@ -88,6 +91,9 @@ pub struct DummyModuleInfo {
     /// Globals as provided by `declare_global`.
     pub globals: PrimaryMap<GlobalIndex, Exportable<Global>>,
 
+    /// Tags as provided by `declare_tag`.
+    pub tags: PrimaryMap<TagIndex, Exportable<Tag>>,
+
     /// The start function.
     pub start_func: Option<FuncIndex>,
 }
@@ -102,11 +108,13 @@ impl DummyModuleInfo {
             imported_globals: Vec::new(),
             imported_tables: Vec::new(),
             imported_memories: Vec::new(),
+            imported_tags: Vec::new(),
             functions: PrimaryMap::new(),
             function_bodies: PrimaryMap::new(),
             tables: PrimaryMap::new(),
             memories: PrimaryMap::new(),
             globals: PrimaryMap::new(),
+            tags: PrimaryMap::new(),
             start_func: None,
         }
     }
@@ -259,6 +267,85 @@ impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
             _ => panic!("unsupported pointer type"),
         }
     }
+
+    /// Builds an indirect call, or tail call depending on `opcode`, through the dummy table of
+    /// function pointers.
+    fn call_indirect_with(
+        &self,
+        mut pos: FuncCursor,
+        opcode: ir::Opcode,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> ir::Inst {
+        // Pass the current function's vmctx parameter on to the callee.
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+
+        // The `callee` value is an index into a table of function pointers.
+        // Apparently, that table is stored at absolute address 0 in this dummy environment.
+        // TODO: Generate bounds checking code.
+        let ptr = self.pointer_type();
+        let callee_offset = if ptr == I32 {
+            pos.ins().imul_imm(callee, 4)
+        } else {
+            let ext = pos.ins().uextend(I64, callee);
+            pos.ins().imul_imm(ext, 4)
+        };
+        let mflags = ir::MemFlags::trusted();
+        let func_ptr = pos.ins().load(ptr, mflags, callee_offset, 0);
+
+        // Build a value list for the indirect call instruction containing the callee, call_args,
+        // and the vmctx parameter.
+        let mut args = ir::ValueList::default();
+        args.push(func_ptr, &mut pos.func.dfg.value_lists);
+        args.extend(call_args.iter().cloned(), &mut pos.func.dfg.value_lists);
+        args.push(vmctx, &mut pos.func.dfg.value_lists);
+
+        pos.ins().CallIndirect(opcode, INVALID, sig_ref, args).0
+    }
+
+    /// Builds a direct call, or tail call depending on `opcode`.
+    fn call_with(
+        &self,
+        mut pos: FuncCursor,
+        opcode: ir::Opcode,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> ir::Inst {
+        // Pass the current function's vmctx parameter on to the callee.
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+
+        // Build a value list for the call instruction containing the call_args and the vmctx
+        // parameter.
+        let mut args = ir::ValueList::default();
+        args.extend(call_args.iter().cloned(), &mut pos.func.dfg.value_lists);
+        args.push(vmctx, &mut pos.func.dfg.value_lists);
+
+        pos.ins().Call(opcode, INVALID, callee, args).0
+    }
+
+    /// Builds a call to a runtime function taking only the vmctx parameter, which this dummy
+    /// environment keeps at absolute address 0. Stands in for the calls that throw exceptions.
+    fn call_runtime(&self, mut pos: FuncCursor) -> ir::Inst {
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter");
+        let mut sig = ir::Signature::new(CallConv::Fast);
+        sig.params.push(ir::AbiParam::special(
+            self.pointer_type(),
+            ir::ArgumentPurpose::VMContext,
+        ));
+        let sig_ref = pos.func.import_signature(sig);
+        let func_ptr = pos.ins().iconst(self.pointer_type(), 0);
+        pos.ins().call_indirect(sig_ref, func_ptr, &[vmctx])
+    }
 }
 
 impl<'dummy_environment> TargetEnvironment for DummyFuncEnvironment<'dummy_environment> {
@@ -404,7 +491,7 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
 
     fn translate_call_indirect(
         &mut self,
-        mut pos: FuncCursor,
+        pos: FuncCursor,
         _table_index: TableIndex,
         _table: ir::Table,
         _sig_index: TypeIndex,
@@ -412,58 +499,48 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         callee: ir::Value,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        // Pass the current function's vmctx parameter on to the callee.
-        let vmctx = pos
-            .func
-            .special_param(ir::ArgumentPurpose::VMContext)
-            .expect("Missing vmctx parameter");
-
-        // The `callee` value is an index into a table of function pointers.
-        // Apparently, that table is stored at absolute address 0 in this dummy environment.
-        // TODO: Generate bounds checking code.
-        let ptr = self.pointer_type();
-        let callee_offset = if ptr == I32 {
-            pos.ins().imul_imm(callee, 4)
-        } else {
-            let ext = pos.ins().uextend(I64, callee);
-            pos.ins().imul_imm(ext, 4)
-        };
-        let mflags = ir::MemFlags::trusted();
-        let func_ptr = pos.ins().load(ptr, mflags, callee_offset, 0);
-
-        // Build a value list for the indirect call instruction containing the callee, call_args,
-        // and the vmctx parameter.
-        let mut args = ir::ValueList::default();
-        args.push(func_ptr, &mut pos.func.dfg.value_lists);
-        args.extend(call_args.iter().cloned(), &mut pos.func.dfg.value_lists);
-        args.push(vmctx, &mut pos.func.dfg.value_lists);
-
-        Ok(pos
-            .ins()
-            .CallIndirect(ir::Opcode::CallIndirect, INVALID, sig_ref, args)
-            .0)
+        Ok(self.call_indirect_with(pos, ir::Opcode::CallIndirect, sig_ref, callee, call_args))
     }
 
     fn translate_call(
         &mut self,
-        mut pos: FuncCursor,
+        pos: FuncCursor,
         _callee_index: FuncIndex,
         callee: ir::FuncRef,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        // Pass the current function's vmctx parameter on to the callee.
-        let vmctx = pos
-            .func
-            .special_param(ir::ArgumentPurpose::VMContext)
-            .expect("Missing vmctx parameter");
+        Ok(self.call_with(pos, ir::Opcode::Call, callee, call_args))
+    }
 
-        // Build a value list for the call instruction containing the call_args and the vmctx
-        // parameter.
-        let mut args = ir::ValueList::default();
-        args.extend(call_args.iter().cloned(), &mut pos.func.dfg.value_lists);
-        args.push(vmctx, &mut pos.func.dfg.value_lists);
+    fn translate_return_call_indirect(
+        &mut self,
+        pos: FuncCursor,
+        _table_index: TableIndex,
+        _table: ir::Table,
+        _sig_index: TypeIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()> {
+        self.call_indirect_with(
+            pos,
+            ir::Opcode::ReturnCallIndirect,
+            sig_ref,
+            callee,
+            call_args,
+        );
+        Ok(())
+    }
 
-        Ok(pos.ins().Call(ir::Opcode::Call, INVALID, callee, args).0)
+    fn translate_return_call(
+        &mut self,
+        pos: FuncCursor,
+        _callee_index: FuncIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()> {
+        self.call_with(pos, ir::Opcode::ReturnCall, callee, call_args);
+        Ok(())
     }
 
     fn translate_memory_grow(
@@ -659,6 +736,65 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         Ok(pos.ins().iconst(I32, 0))
     }
 
+    fn translate_throw(
+        &mut self,
+        pos: FuncCursor,
+        _tag_index: TagIndex,
+        _args: &[ir::Value],
+    ) -> WasmResult<()> {
+        self.call_runtime(pos);
+        Ok(())
+    }
+
+    fn translate_exception_matches(
+        &mut self,
+        mut pos: FuncCursor,
+        _tag_index: TagIndex,
+    ) -> WasmResult<ir::Value> {
+        Ok(pos.ins().iconst(I32, 0))
+    }
+
+    fn translate_catch(
+        &mut self,
+        mut pos: FuncCursor,
+        tag_index: Option<TagIndex>,
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)> {
+        let exception = pos.ins().iconst(self.pointer_type(), 0);
+        let payload = match tag_index {
+            Some(tag_index) => {
+                // Signature indices are type indices in this environment, see
+                // `type_to_signature`.
+                let ty = self.mod_info.tags[tag_index].entity.ty;
+                let sig = &self.mod_info.signatures[TypeIndex::from_u32(ty.as_u32())];
+                sig.params
+                    .iter()
+                    .map(|param| match param.value_type {
+                        F32 => pos.ins().f32const(0.0),
+                        F64 => pos.ins().f64const(0.0),
+                        ty if ty.is_vector() => {
+                            let zero = pos.func.dfg.constants.insert(vec![0u8; 16].into());
+                            pos.ins().vconst(ty, zero)
+                        }
+                        ty if ty.is_ref() => pos.ins().null(ty),
+                        ty => pos.ins().iconst(ty, 0),
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        Ok((exception, payload))
+    }
+
+    fn translate_rethrow(&mut self, pos: FuncCursor, _exception: ir::Value) -> WasmResult<()> {
+        self.call_runtime(pos);
+        Ok(())
+    }
+
+    fn translate_resume_unwind(&mut self, pos: FuncCursor) -> WasmResult<()> {
+        self.call_runtime(pos);
+        Ok(())
+    }
+
     fn unsigned_add_overflow_condition(&self) -> ir::condcodes::IntCC {
         unimplemented!()
     }
@@ -694,6 +830,10 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         Ok(())
     }
 
+    fn type_to_signature(&self, index: TypeIndex) -> WasmResult<SignatureIndex> {
+        Ok(SignatureIndex::from_u32(index.as_u32()))
+    }
+
     fn declare_func_import(
         &mut self,
         index: TypeIndex,
@@ -798,6 +938,24 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         Ok(())
     }
 
+    fn declare_tag_import(
+        &mut self,
+        tag: Tag,
+        module: &'data str,
+        field: Option<&'data str>,
+    ) -> WasmResult<()> {
+        self.info.tags.push(Exportable::new(tag));
+        self.info
+            .imported_tags
+            .push((String::from(module), String::from(field.unwrap())));
+        Ok(())
+    }
+
+    fn declare_tag(&mut self, tag: Tag) -> WasmResult<()> {
+        self.info.tags.push(Exportable::new(tag));
+        Ok(())
+    }
+
     fn declare_data_initialization(
         &mut self,
         _memory_index: MemoryIndex,
@@ -849,6 +1007,13 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         Ok(())
     }
 
+    fn declare_tag_export(&mut self, tag_index: TagIndex, name: &'data str) -> WasmResult<()> {
+        self.info.tags[tag_index]
+            .export_names
+            .push(String::from(name));
+        Ok(())
+    }
+
     fn declare_start_func(&mut self, func_index: FuncIndex) -> WasmResult<()> {
         debug_assert!(self.info.start_func.is_none());
         self.info.start_func = Some(func_index);
@@ -898,6 +1063,8 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
             simd: true,
             reference_types: true,
             bulk_memory: true,
+            tail_call: true,
+            exceptions: true,
             ..WasmFeatures::default()
         }
     }
@@ -245,6 +245,38 @@ pub trait FuncEnvironment: TargetEnvironment {
         Ok(pos.ins().call(callee, call_args))
     }
 
+    /// Translate a `return_call_indirect` WebAssembly instruction at `pos`.
+    ///
+    /// Insert instructions at `pos` for an indirect tail call to the function `callee` in the
+    /// table `table_index` with WebAssembly signature `sig_index`, like
+    /// `translate_call_indirect`. The tail call must be the last instruction inserted.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_return_call_indirect(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        sig_index: TypeIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()>;
+
+    /// Translate a `return_call` WebAssembly instruction at `pos`.
+    ///
+    /// Insert instructions at `pos` for a direct tail call to the function `callee_index`, like
+    /// `translate_call`. The tail call must be the last instruction inserted.
+    fn translate_return_call(
+        &mut self,
+        mut pos: FuncCursor,
+        _callee_index: FuncIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()> {
+        pos.ins().return_call(callee, call_args);
+        Ok(())
+    }
+
     /// Translate a `memory.grow` WebAssembly instruction.
     ///
     /// The `index` provided identifies the linear memory to grow, and `heap` is the heap reference
@@ -496,6 +528,76 @@ pub trait FuncEnvironment: TargetEnvironment {
         count: ir::Value,
     ) -> WasmResult<ir::Value>;
 
+    /// Translate a `throw` WebAssembly instruction at `pos`.
+    ///
+    /// Insert instructions at `pos` that throw an exception with the tag `tag_index` and the
+    /// payload `args`. These must end with a call that unwinds the stack rather than returning,
+    /// which the translator follows with a `br_unwind` to the innermost handler, if there is one.
+    fn translate_throw(
+        &mut self,
+        pos: FuncCursor,
+        tag_index: TagIndex,
+        args: &[ir::Value],
+    ) -> WasmResult<()> {
+        drop((pos, tag_index, args));
+        Err(WasmError::Unsupported("wasm exceptions".to_string()))
+    }
+
+    /// Emit code at the start of the landing pad of a `try` body, where execution resumes when a
+    /// call in the body throws, before the exception is dispatched to the `catch` clauses.
+    ///
+    /// The unwinder clobbers all registers, and the callees may have changed any state that the
+    /// environment caches in variables, so this is where to reload such state.
+    fn translate_landing_pad(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+        // By default, don't emit anything.
+        Ok(())
+    }
+
+    /// Insert instructions at `pos` that test whether the pending exception has the tag
+    /// `tag_index`. This is only called while an exception is pending.
+    ///
+    /// Returns a value that is nonzero if and only if the tag matches.
+    fn translate_exception_matches(
+        &mut self,
+        pos: FuncCursor,
+        tag_index: TagIndex,
+    ) -> WasmResult<ir::Value> {
+        drop((pos, tag_index));
+        Err(WasmError::Unsupported("wasm exceptions".to_string()))
+    }
+
+    /// Translate the start of a `catch` (if `tag_index` is `Some`) or `catch_all` clause at `pos`.
+    ///
+    /// Insert instructions at `pos` that take the pending exception so that it is no longer
+    /// pending. Returns a value identifying the caught exception, which is passed back to
+    /// `translate_rethrow`, along with the exception's payload for a `catch` clause. A
+    /// `catch_all` clause has no payload.
+    fn translate_catch(
+        &mut self,
+        pos: FuncCursor,
+        tag_index: Option<TagIndex>,
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)> {
+        drop((pos, tag_index));
+        Err(WasmError::Unsupported("wasm exceptions".to_string()))
+    }
+
+    /// Translate a `rethrow` WebAssembly instruction at `pos`.
+    ///
+    /// `exception` is the value returned by `translate_catch` for the targeted clause. As with
+    /// `translate_throw`, the inserted instructions must end with a call that unwinds the stack.
+    fn translate_rethrow(&mut self, pos: FuncCursor, exception: ir::Value) -> WasmResult<()> {
+        drop((pos, exception));
+        Err(WasmError::Unsupported("wasm exceptions".to_string()))
+    }
+
+    /// Insert instructions at `pos` that continue unwinding the pending exception into the
+    /// caller, when no handler in this function catches it. As with `translate_throw`, these must
+    /// end with a call that unwinds the stack.
+    fn translate_resume_unwind(&mut self, pos: FuncCursor) -> WasmResult<()> {
+        drop(pos);
+        Err(WasmError::Unsupported("wasm exceptions".to_string()))
+    }
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
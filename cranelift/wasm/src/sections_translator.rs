@@ -44,7 +44,7 @@ fn entity_type(
             EntityType::Instance(environ.type_to_instance_type(TypeIndex::from_u32(sig))?)
         }
         ImportSectionEntryType::Memory(ty) => EntityType::Memory(memory(ty)),
-        ImportSectionEntryType::Tag(t) => EntityType::Tag(tag(t)),
+        ImportSectionEntryType::Tag(t) => EntityType::Tag(tag(t, environ)?),
         ImportSectionEntryType::Global(ty) => EntityType::Global(global(ty, GlobalInit::Import)?),
         ImportSectionEntryType::Table(ty) => EntityType::Table(table(ty)?),
     })
@@ -59,10 +59,10 @@ fn memory(ty: MemoryType) -> Memory {
     }
 }
 
-fn tag(e: TagType) -> Tag {
-    Tag {
-        ty: TypeIndex::from_u32(e.type_index),
-    }
+fn tag(e: TagType, environ: &dyn ModuleEnvironment<'_>) -> WasmResult<Tag> {
+    Ok(Tag {
+        ty: environ.type_to_signature(TypeIndex::from_u32(e.type_index))?,
+    })
 }
 
 fn table(ty: TableType) -> WasmResult<Table> {
@@ -160,7 +160,8 @@ pub fn parse_import_section<'data>(
                 environ.declare_memory_import(memory(ty), import.module, import.field)?;
             }
             ImportSectionEntryType::Tag(e) => {
-                environ.declare_tag_import(tag(e), import.module, import.field)?;
+                let tag = tag(e, environ)?;
+                environ.declare_tag_import(tag, import.module, import.field)?;
             }
             ImportSectionEntryType::Global(ty) => {
                 let ty = global(ty, GlobalInit::Import)?;
@@ -236,7 +237,7 @@ pub fn parse_tag_section(
     environ.reserve_tags(tags.get_count())?;
 
     for entry in tags {
-        let tag = tag(entry?);
+        let tag = tag(entry?, environ)?;
         environ.declare_tag(tag)?;
     }
 
@@ -513,7 +514,7 @@ pub fn parse_instance_section<'data>(
                     ExternalKind::Instance => {
                         EntityIndex::Instance(InstanceIndex::from_u32(arg.index))
                     }
-                    ExternalKind::Tag => unimplemented!(),
+                    ExternalKind::Tag => EntityIndex::Tag(TagIndex::from_u32(arg.index)),
 
                     // this won't pass validation
                     ExternalKind::Type => unreachable!(),
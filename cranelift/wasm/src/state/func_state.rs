@@ -39,7 +39,28 @@ pub enum ElseData {
     },
 }
 
-/// A control stack frame can be an `if`, a `block` or a `loop`, each one having the following
+/// The exception handlers of a `try` block, which depend on how far into the `try` we are.
+#[derive(Debug)]
+pub enum TryData {
+    /// We are translating the body of the `try`.
+    Body {
+        /// Exceptions thrown in the body unwind to here. What this block does with them is only
+        /// known once we see the first `catch`, `catch_all`, `delegate` or `end`.
+        landing_pad: Block,
+    },
+
+    /// We are translating one of the `catch` or `catch_all` clauses of the `try`.
+    Catch {
+        /// The block that dispatches the pending exception to the remaining clauses if it didn't
+        /// match any of the clauses so far, or `None` after a `catch_all`.
+        dispatch: Option<Block>,
+        /// The exception caught by this clause, as returned by
+        /// `FuncEnvironment::translate_catch`, for `rethrow`.
+        exception: Value,
+    },
+}
+
+/// A control stack frame can be an `if`, a `block`, a `loop` or a `try`, each one having the following
 /// fields:
 ///
 /// - `destination`: reference to the `Block` that will hold the code after the control block;
@@ -48,7 +69,8 @@ pub enum ElseData {
 ///
 /// Moreover, the `if` frame has the `branch_inst` field that points to the `brz` instruction
 /// separating the `true` and `false` branch. The `loop` frame has a `header` field that references
-/// the `Block` that contains the beginning of the body of the loop.
+/// the `Block` that contains the beginning of the body of the loop. The `try` frame has a `try_data`
+/// field that tracks where exceptions thrown inside of it go.
 #[derive(Debug)]
 pub enum ControlStackFrame {
     If {
@@ -85,6 +107,16 @@ pub enum ControlStackFrame {
         num_return_values: usize,
         original_stack_size: usize,
     },
+    Try {
+        destination: Block,
+        try_data: TryData,
+        num_param_values: usize,
+        num_return_values: usize,
+        original_stack_size: usize,
+        exit_is_branched_to: bool,
+        /// Was the head of the `try` reachable? If not, neither are its handlers.
+        head_is_reachable: bool,
+    },
 }
 
 /// Helper methods for the control stack objects.
@@ -99,6 +131,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_return_values, ..
+            }
+            | Self::Try {
+                num_return_values, ..
             } => num_return_values,
         }
     }
@@ -112,6 +147,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_param_values, ..
+            }
+            | Self::Try {
+                num_param_values, ..
             } => num_param_values,
         }
     }
@@ -119,12 +157,15 @@ impl ControlStackFrame {
         match *self {
             Self::If { destination, .. }
             | Self::Block { destination, .. }
-            | Self::Loop { destination, .. } => destination,
+            | Self::Loop { destination, .. }
+            | Self::Try { destination, .. } => destination,
         }
     }
     pub fn br_destination(&self) -> Block {
         match *self {
-            Self::If { destination, .. } | Self::Block { destination, .. } => destination,
+            Self::If { destination, .. }
+            | Self::Block { destination, .. }
+            | Self::Try { destination, .. } => destination,
             Self::Loop { header, .. } => header,
         }
     }
@@ -143,12 +184,16 @@ impl ControlStackFrame {
             | Self::Loop {
                 original_stack_size,
                 ..
+            }
+            | Self::Try {
+                original_stack_size,
+                ..
             } => original_stack_size,
         }
     }
     pub fn is_loop(&self) -> bool {
         match *self {
-            Self::If { .. } | Self::Block { .. } => false,
+            Self::If { .. } | Self::Block { .. } | Self::Try { .. } => false,
             Self::Loop { .. } => true,
         }
     }
@@ -162,6 +207,10 @@ impl ControlStackFrame {
             | Self::Block {
                 exit_is_branched_to,
                 ..
+            }
+            | Self::Try {
+                exit_is_branched_to,
+                ..
             } => exit_is_branched_to,
             Self::Loop { .. } => false,
         }
@@ -176,6 +225,10 @@ impl ControlStackFrame {
             | Self::Block {
                 ref mut exit_is_branched_to,
                 ..
+            }
+            | Self::Try {
+                ref mut exit_is_branched_to,
+                ..
             } => *exit_is_branched_to = true,
             Self::Loop { .. } => {}
         }
@@ -251,6 +304,13 @@ impl FuncTranslationState {
     pub fn reachable(&self) -> bool {
         self.reachable
     }
+
+    /// The values currently on the wasm operand stack, with the top of the
+    /// stack last.
+    #[inline]
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
 }
 
 impl FuncTranslationState {
@@ -438,6 +498,43 @@ impl FuncTranslationState {
             blocktype,
         });
     }
+
+    /// Push a try on the control stack.
+    pub(crate) fn push_try(
+        &mut self,
+        destination: Block,
+        landing_pad: Block,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
+        self.control_stack.push(ControlStackFrame::Try {
+            destination,
+            try_data: TryData::Body { landing_pad },
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
+            num_return_values: num_result_types,
+            exit_is_branched_to: false,
+            head_is_reachable: self.reachable,
+        });
+    }
+
+    /// Get the block that handles an exception thrown `relative_depth` frames below the top of
+    /// the control stack: the landing pad of the innermost `try` body enclosing that frame, if
+    /// there is one.
+    pub(crate) fn exception_handler(&self, relative_depth: usize) -> Option<Block> {
+        let i = self.control_stack.len() - 1 - relative_depth;
+        self.control_stack[..=i]
+            .iter()
+            .rev()
+            .find_map(|frame| match *frame {
+                ControlStackFrame::Try {
+                    try_data: TryData::Body { landing_pad },
+                    ..
+                } => Some(landing_pad),
+                _ => None,
+            })
+    }
 }
 
 /// Methods for handling entity references.
@@ -0,0 +1,79 @@
+(module
+  (import "env" "tag" (tag $imported (param f64)))
+  (import "env" "f" (func $f (param i32) (result i32)))
+  (tag $e0 (param i32))
+  (tag $e1 (param i64 f32))
+  (tag $e2)
+  (export "e0" (tag $e0))
+
+  (func $catch (export "catch") (param i32) (result i32)
+    try (result i32)
+      local.get 0
+      call $f
+    catch $e0
+    catch $e1
+      drop
+      i32.wrap_i64
+    catch_all
+      i32.const -1
+    end)
+
+  (func $rethrow (param i32) (result i32)
+    try (result i32)
+      local.get 0
+      throw $e0
+    catch $e0
+      i32.eqz
+      if
+        rethrow 1
+      end
+      i32.const 1
+    end)
+
+  (func $delegate (param i32)
+    try
+      try
+        local.get 0
+        throw $e0
+      delegate 0
+    catch $e0
+      drop
+    end)
+
+  (func $nested (param i32) (result i32)
+    block $outer (result i32)
+      try (result i32)
+        try (result i32)
+          local.get 0
+          call $f
+        catch $e2
+          f64.const 1
+          throw $imported
+        end
+      catch $imported
+        drop
+        i32.const 2
+        br $outer
+      catch_all
+        i32.const 3
+      end
+    end)
+
+  (func $unreachable_try (result i32)
+    unreachable
+    try (result i32)
+      i32.const 0
+    catch $e0
+    catch_all
+      i32.const 1
+    end)
+
+  (func $uncaught (param i32) (result i32 f64)
+    try
+      throw $e2
+    catch $e0
+      drop
+    end
+    local.get 0
+    f64.const 0)
+)
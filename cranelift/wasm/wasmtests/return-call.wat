@@ -0,0 +1,20 @@
+(module
+  (type $ft (func (param i64 i64) (result i64)))
+  (func $fac (export "fac") (param i64) (result i64)
+    (return_call $fac-acc (local.get 0) (i64.const 1))
+  )
+  (func $fac-acc (param i64 i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 1))
+      (else
+        (return_call_indirect (type $ft)
+          (i64.sub (local.get 0) (i64.const 1))
+          (i64.mul (local.get 0) (local.get 1))
+          (i32.const 0)
+        )
+      )
+    )
+  )
+  (table 1 1 funcref)
+  (elem (i32.const 0) $fac-acc)
+)
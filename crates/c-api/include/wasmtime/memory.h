@@ -49,6 +49,11 @@ WASM_API_EXTERN bool wasmtime_memorytype_maximum(const wasm_memorytype_t *ty, ui
  */
 WASM_API_EXTERN bool wasmtime_memorytype_is64(const wasm_memorytype_t *ty);
 
+/**
+ * \brief Returns whether this type of memory represents a shared memory.
+ */
+WASM_API_EXTERN bool wasmtime_memorytype_isshared(const wasm_memorytype_t *ty);
+
 /**
  * \brief Creates a new WebAssembly linear memory
  *
@@ -116,6 +121,96 @@ WASM_API_EXTERN wasmtime_error_t *wasmtime_memory_grow(
     uint64_t *prev_size
 );
 
+/**
+ * \typedef wasmtime_sharedmemory_t
+ * \brief Convenience alias for #wasmtime_sharedmemory
+ *
+ * \struct wasmtime_sharedmemory
+ * \brief A WebAssembly linear memory which may be shared between threads.
+ *
+ * Unlike #wasmtime_memory_t a shared memory isn't owned by a store. It's a
+ * reference-counted handle which can be used from any thread and imported into
+ * instances of any store which uses the same engine it was created with.
+ */
+typedef struct wasmtime_sharedmemory wasmtime_sharedmemory_t;
+
+/**
+ * \brief Creates a new WebAssembly shared linear memory
+ *
+ * \param engine the engine the memory can be used with
+ * \param ty the type of the memory to create, which must be shared
+ * \param ret where to store the returned memory
+ *
+ * If an error happens when creating the memory it's returned and owned by the
+ * caller. If an error happens then `ret` is not filled in, otherwise `ret` is
+ * owned by the caller and must be deleted with #wasmtime_sharedmemory_delete.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_sharedmemory_new(
+    const wasm_engine_t *engine,
+    const wasm_memorytype_t *ty,
+    wasmtime_sharedmemory_t **ret
+);
+
+/**
+ * \brief Deletes a shared memory handle.
+ */
+WASM_API_EXTERN void wasmtime_sharedmemory_delete(wasmtime_sharedmemory_t *memory);
+
+/**
+ * \brief Creates a new handle to the same shared memory.
+ */
+WASM_API_EXTERN wasmtime_sharedmemory_t *wasmtime_sharedmemory_clone(
+    const wasmtime_sharedmemory_t *memory
+);
+
+/**
+ * \brief Returns the type of the shared memory specified
+ */
+WASM_API_EXTERN wasm_memorytype_t *wasmtime_sharedmemory_type(
+    const wasmtime_sharedmemory_t *memory
+);
+
+/**
+ * \brief Returns the base pointer in memory where the shared memory starts.
+ *
+ * The base pointer of a shared memory never changes, but its contents may be
+ * concurrently modified by other threads.
+ */
+WASM_API_EXTERN uint8_t *wasmtime_sharedmemory_data(
+    const wasmtime_sharedmemory_t *memory
+);
+
+/**
+ * \brief Returns the byte length of this shared memory.
+ */
+WASM_API_EXTERN size_t wasmtime_sharedmemory_data_size(
+    const wasmtime_sharedmemory_t *memory
+);
+
+/**
+ * \brief Returns the length, in WebAssembly pages, of this shared memory
+ */
+WASM_API_EXTERN uint64_t wasmtime_sharedmemory_size(
+    const wasmtime_sharedmemory_t *memory
+);
+
+/**
+ * \brief Attempts to grow the specified shared memory by `delta` pages.
+ *
+ * \param memory the memory to grow
+ * \param delta the number of pages to grow by
+ * \param prev_size where to store the previous size of memory
+ *
+ * If memory cannot be grown then `prev_size` is left unchanged and an error is
+ * returned. Otherwise `prev_size` is set to the previous size of the memory, in
+ * WebAssembly pages, and `NULL` is returned.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_sharedmemory_grow(
+    const wasmtime_sharedmemory_t *memory,
+    uint64_t delta,
+    uint64_t *prev_size
+);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
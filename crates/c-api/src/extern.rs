@@ -1,9 +1,10 @@
 use crate::{
     wasm_externkind_t, wasm_externtype_t, wasm_func_t, wasm_global_t, wasm_instance_t,
-    wasm_memory_t, wasm_module_t, wasm_table_t, wasmtime_module_t, CStoreContext, StoreRef,
+    wasm_memory_t, wasm_module_t, wasm_table_t, wasmtime_module_t, wasmtime_sharedmemory_t,
+    CStoreContext, StoreRef,
 };
 use std::mem::ManuallyDrop;
-use wasmtime::{Extern, Func, Global, Instance, Memory, Table};
+use wasmtime::{Extern, Func, Global, Instance, Memory, Table, Tag};
 
 #[derive(Clone)]
 pub struct wasm_extern_t {
@@ -20,8 +21,13 @@ pub extern "C" fn wasm_extern_kind(e: &wasm_extern_t) -> wasm_externkind_t {
         Extern::Global(_) => crate::WASM_EXTERN_GLOBAL,
         Extern::Table(_) => crate::WASM_EXTERN_TABLE,
         Extern::Memory(_) => crate::WASM_EXTERN_MEMORY,
+        // The standard C API has no notion of shared memories, so they're
+        // reported as memories even though `wasm_extern_as_memory` doesn't
+        // accept them.
+        Extern::SharedMemory(_) => crate::WASM_EXTERN_MEMORY,
         Extern::Instance(_) => crate::WASM_EXTERN_INSTANCE,
         Extern::Module(_) => crate::WASM_EXTERN_MODULE,
+        Extern::Tag(_) => crate::WASM_EXTERN_TAG,
     }
 }
 
@@ -103,6 +109,8 @@ pub const WASMTIME_EXTERN_TABLE: wasmtime_extern_kind_t = 2;
 pub const WASMTIME_EXTERN_MEMORY: wasmtime_extern_kind_t = 3;
 pub const WASMTIME_EXTERN_INSTANCE: wasmtime_extern_kind_t = 4;
 pub const WASMTIME_EXTERN_MODULE: wasmtime_extern_kind_t = 5;
+pub const WASMTIME_EXTERN_SHAREDMEMORY: wasmtime_extern_kind_t = 6;
+pub const WASMTIME_EXTERN_TAG: wasmtime_extern_kind_t = 7;
 
 #[repr(C)]
 pub union wasmtime_extern_union {
@@ -112,6 +120,8 @@ pub union wasmtime_extern_union {
     pub instance: Instance,
     pub memory: Memory,
     pub module: ManuallyDrop<Box<wasmtime_module_t>>,
+    pub sharedmemory: ManuallyDrop<Box<wasmtime_sharedmemory_t>>,
+    pub tag: Tag,
 }
 
 impl wasmtime_extern_t {
@@ -123,6 +133,10 @@ impl wasmtime_extern_t {
             WASMTIME_EXTERN_MEMORY => Extern::Memory(self.of.memory),
             WASMTIME_EXTERN_INSTANCE => Extern::Instance(self.of.instance),
             WASMTIME_EXTERN_MODULE => Extern::Module(self.of.module.module.clone()),
+            WASMTIME_EXTERN_SHAREDMEMORY => {
+                Extern::SharedMemory(self.of.sharedmemory.memory.clone())
+            }
+            WASMTIME_EXTERN_TAG => Extern::Tag(self.of.tag),
             other => panic!("unknown wasm_extern_kind_t: {}", other),
         }
     }
@@ -157,16 +171,30 @@ impl From<Extern> for wasmtime_extern_t {
                     module: ManuallyDrop::new(Box::new(wasmtime_module_t { module })),
                 },
             },
+            Extern::SharedMemory(memory) => wasmtime_extern_t {
+                kind: WASMTIME_EXTERN_SHAREDMEMORY,
+                of: wasmtime_extern_union {
+                    sharedmemory: ManuallyDrop::new(Box::new(wasmtime_sharedmemory_t { memory })),
+                },
+            },
+            Extern::Tag(tag) => wasmtime_extern_t {
+                kind: WASMTIME_EXTERN_TAG,
+                of: wasmtime_extern_union { tag },
+            },
         }
     }
 }
 
 impl Drop for wasmtime_extern_t {
     fn drop(&mut self) {
-        if self.kind == WASMTIME_EXTERN_MODULE {
-            unsafe {
+        match self.kind {
+            WASMTIME_EXTERN_MODULE => unsafe {
                 ManuallyDrop::drop(&mut self.of.module);
-            }
+            },
+            WASMTIME_EXTERN_SHAREDMEMORY => unsafe {
+                ManuallyDrop::drop(&mut self.of.sharedmemory);
+            },
+            _ => {}
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::{wasm_functype_t, wasm_globaltype_t, wasm_memorytype_t, wasm_tabletype_t};
 use crate::{wasmtime_instancetype_t, wasmtime_moduletype_t};
 use crate::{CFuncType, CGlobalType, CInstanceType, CMemoryType, CModuleType, CTableType};
-use wasmtime::ExternType;
+use wasmtime::{ExternType, TagType};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -19,6 +19,9 @@ pub(crate) enum CExternType {
     Table(CTableType),
     Instance(CInstanceType),
     Module(CModuleType),
+    // The C API doesn't expose the types of exception tags yet, but they're
+    // retained here so that they round-trip through `wasm_externtype_t`.
+    Tag(TagType),
 }
 
 pub type wasm_externkind_t = u8;
@@ -29,6 +32,7 @@ pub const WASM_EXTERN_TABLE: wasm_externkind_t = 2;
 pub const WASM_EXTERN_MEMORY: wasm_externkind_t = 3;
 pub const WASM_EXTERN_MODULE: wasm_externkind_t = 4;
 pub const WASM_EXTERN_INSTANCE: wasm_externkind_t = 5;
+pub const WASM_EXTERN_TAG: wasm_externkind_t = 6;
 
 impl wasm_externtype_t {
     pub(crate) fn new(ty: ExternType) -> wasm_externtype_t {
@@ -40,6 +44,7 @@ impl wasm_externtype_t {
                 ExternType::Table(f) => CExternType::Table(CTableType::new(f)),
                 ExternType::Instance(f) => CExternType::Instance(CInstanceType::new(f)),
                 ExternType::Module(f) => CExternType::Module(CModuleType::new(f)),
+                ExternType::Tag(f) => CExternType::Tag(f),
             },
         }
     }
@@ -52,6 +57,7 @@ impl wasm_externtype_t {
             CExternType::Memory(f) => ExternType::Memory(f.ty.clone()),
             CExternType::Instance(f) => ExternType::Instance(f.ty.clone()),
             CExternType::Module(f) => ExternType::Module(f.ty.clone()),
+            CExternType::Tag(f) => ExternType::Tag(f.clone()),
         }
     }
 }
@@ -65,6 +71,7 @@ pub extern "C" fn wasm_externtype_kind(et: &wasm_externtype_t) -> wasm_externkin
         CExternType::Memory(_) => WASM_EXTERN_MEMORY,
         CExternType::Instance(_) => WASM_EXTERN_INSTANCE,
         CExternType::Module(_) => WASM_EXTERN_MODULE,
+        CExternType::Tag(_) => WASM_EXTERN_TAG,
     }
 }
 
@@ -0,0 +1,358 @@
+//! Derive macros for the `wasmtime::component` traits.
+//!
+//! Structs are derived as canonical ABI records whose fields are laid out in
+//! declaration order, and enums are derived as variants whose cases are the
+//! enum's variants in declaration order. Each enum variant may either have no
+//! fields or a single unnamed field holding its payload.
+//!
+//! This crate is re-exported by `wasmtime::component` and isn't intended to be
+//! used directly.
+
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Ident, Member, Type};
+
+/// Derives `wasmtime::component::ComponentType` for a struct or enum.
+#[proc_macro_derive(ComponentType)]
+pub fn component_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(
+        parse_macro_input!(input as DeriveInput),
+        Trait::ComponentType,
+    )
+}
+
+/// Derives `wasmtime::component::Lift` for a struct or enum.
+#[proc_macro_derive(Lift)]
+pub fn lift(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(parse_macro_input!(input as DeriveInput), Trait::Lift)
+}
+
+/// Derives `wasmtime::component::Lower` for a struct or enum.
+#[proc_macro_derive(Lower)]
+pub fn lower(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(parse_macro_input!(input as DeriveInput), Trait::Lower)
+}
+
+#[derive(Copy, Clone)]
+enum Trait {
+    ComponentType,
+    Lift,
+    Lower,
+}
+
+enum Shape {
+    Record(Vec<(Member, Type)>),
+    Variant(Vec<(Ident, Option<Type>)>),
+}
+
+fn expand(input: DeriveInput, which: Trait) -> proc_macro::TokenStream {
+    let result = shape(&input).map(|shape| match which {
+        Trait::ComponentType => expand_component_type(&input, &shape),
+        Trait::Lift => expand_lift(&input, &shape),
+        Trait::Lower => expand_lower(&input, &shape),
+    });
+    match result {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn shape(input: &DeriveInput) -> syn::Result<Shape> {
+    match &input.data {
+        Data::Struct(data) => Ok(Shape::Record(
+            data.fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let member = match &field.ident {
+                        Some(ident) => Member::Named(ident.clone()),
+                        None => Member::Unnamed(i.into()),
+                    };
+                    (member, field.ty.clone())
+                })
+                .collect(),
+        )),
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "enums without any cases are not supported",
+                ));
+            }
+            let mut cases = Vec::new();
+            for variant in data.variants.iter() {
+                if let Some((_, discriminant)) = &variant.discriminant {
+                    return Err(Error::new_spanned(
+                        discriminant,
+                        "explicit discriminants are not supported",
+                    ));
+                }
+                let payload = match &variant.fields {
+                    Fields::Unit => None,
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        Some(fields.unnamed[0].ty.clone())
+                    }
+                    fields => {
+                        return Err(Error::new_spanned(
+                            fields,
+                            "enum variants must have no fields or a single unnamed field",
+                        ))
+                    }
+                };
+                cases.push((variant.ident.clone(), payload));
+            }
+            Ok(Shape::Variant(cases))
+        }
+        Data::Union(_) => Err(Error::new_spanned(&input.ident, "unions are not supported")),
+    }
+}
+
+/// Generates the `impl` header for `trait_` on `input`, requiring every type
+/// parameter to implement `trait_` as well.
+fn impl_header(input: &DeriveInput, trait_: TokenStream) -> TokenStream {
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#trait_));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+    quote!(impl #impl_generics #trait_ for #name #ty_generics #where_clause)
+}
+
+/// Returns the payload type of each case, with `()` for cases without one.
+fn payloads(cases: &[(Ident, Option<Type>)]) -> Vec<Type> {
+    cases
+        .iter()
+        .map(|(_, payload)| match payload {
+            Some(ty) => ty.clone(),
+            None => parse_quote!(()),
+        })
+        .collect()
+}
+
+fn expand_component_type(input: &DeriveInput, shape: &Shape) -> TokenStream {
+    let header = impl_header(input, quote!(wasmtime::component::ComponentType));
+    let internal = quote!(wasmtime::component::__internal);
+    let (types, size, align, flatten) = match shape {
+        Shape::Record(fields) => {
+            let types = fields.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>();
+            let flatten = quote! {
+                #(<#types as wasmtime::component::ComponentType>::flatten(dst);)*
+            };
+            (types, quote!(record_size), quote!(record_align), flatten)
+        }
+        Shape::Variant(cases) => {
+            let types = payloads(cases);
+            let flatten = quote! {
+                let cases: &[fn(&mut std::vec::Vec<#internal::ValType>)] = &[
+                    #(<#types as wasmtime::component::ComponentType>::flatten,)*
+                ];
+                #internal::variant_flatten(cases, dst)
+            };
+            (types, quote!(variant_size), quote!(variant_align), flatten)
+        }
+    };
+    quote! {
+        #header {
+            const SIZE32: usize = #internal::#size(&[#((
+                <#types as wasmtime::component::ComponentType>::SIZE32,
+                <#types as wasmtime::component::ComponentType>::ALIGN32,
+            ),)*]);
+            const ALIGN32: u32 = #internal::#align(&[#((
+                <#types as wasmtime::component::ComponentType>::SIZE32,
+                <#types as wasmtime::component::ComponentType>::ALIGN32,
+            ),)*]);
+
+            fn flatten(dst: &mut std::vec::Vec<#internal::ValType>) {
+                #flatten
+            }
+        }
+    }
+}
+
+fn expand_lift(input: &DeriveInput, shape: &Shape) -> TokenStream {
+    let header = impl_header(input, quote!(wasmtime::component::Lift));
+    let internal = quote!(wasmtime::component::__internal);
+    let (lift, load) = match shape {
+        Shape::Record(fields) => {
+            let members = fields.iter().map(|(member, _)| member).collect::<Vec<_>>();
+            let types = fields.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+            let lift = quote! {
+                Ok(Self {
+                    #(#members: <#types as wasmtime::component::Lift>::lift(cx, src)?,)*
+                })
+            };
+            let load = quote! {
+                let mut offset = 0;
+                Ok(Self {
+                    #(#members: <#types as wasmtime::component::Lift>::load(
+                        cx,
+                        &bytes[#internal::next_field::<#types>(&mut offset)..]
+                            [..<#types as wasmtime::component::ComponentType>::SIZE32],
+                    )?,)*
+                })
+            };
+            (lift, load)
+        }
+        Shape::Variant(cases) => {
+            let types = payloads(cases);
+            let discriminants = (0..cases.len() as u32)
+                .map(Literal::u32_unsuffixed)
+                .collect::<Vec<_>>();
+            let lifts = cases.iter().map(|(ident, payload)| match payload {
+                Some(ty) => quote!(Self::#ident(#internal::lift_payload::<#ty>(cx, payload)?)),
+                None => quote!(Self::#ident),
+            });
+            let loads = cases.iter().map(|(ident, payload)| match payload {
+                Some(ty) => {
+                    quote!(Self::#ident(<#ty as wasmtime::component::Lift>::load(cx, payload)?))
+                }
+                None => quote!(Self::#ident),
+            });
+            let name = &input.ident;
+            let lift = quote! {
+                let cases: &[fn(&mut std::vec::Vec<#internal::ValType>)] = &[
+                    #(<#types as wasmtime::component::ComponentType>::flatten,)*
+                ];
+                match #internal::lift_variant(src, cases)? {
+                    #((#discriminants, payload) => Ok(#lifts),)*
+                    (n, _) => #internal::bail!(
+                        "invalid discriminant {} for `{}`",
+                        n,
+                        stringify!(#name),
+                    ),
+                }
+            };
+            let load = quote! {
+                let cases = &[#((
+                    <#types as wasmtime::component::ComponentType>::SIZE32,
+                    <#types as wasmtime::component::ComponentType>::ALIGN32,
+                ),)*];
+                match #internal::load_variant(bytes, cases)? {
+                    #((#discriminants, payload) => Ok(#loads),)*
+                    (n, _) => #internal::bail!(
+                        "invalid discriminant {} for `{}`",
+                        n,
+                        stringify!(#name),
+                    ),
+                }
+            };
+            (lift, load)
+        }
+    };
+    quote! {
+        #header {
+            #[allow(unused_variables)]
+            fn lift(
+                cx: &wasmtime::component::LiftContext<'_>,
+                src: &mut std::slice::Iter<'_, wasmtime::Val>,
+            ) -> #internal::Result<Self> {
+                #lift
+            }
+
+            #[allow(unused_variables)]
+            fn load(
+                cx: &wasmtime::component::LiftContext<'_>,
+                bytes: &[u8],
+            ) -> #internal::Result<Self> {
+                #load
+            }
+        }
+    }
+}
+
+fn expand_lower(input: &DeriveInput, shape: &Shape) -> TokenStream {
+    let header = impl_header(input, quote!(wasmtime::component::Lower));
+    let internal = quote!(wasmtime::component::__internal);
+    let (lower, store) = match shape {
+        Shape::Record(fields) => {
+            let members = fields.iter().map(|(member, _)| member).collect::<Vec<_>>();
+            let types = fields.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+            let lower = quote! {
+                #(wasmtime::component::Lower::lower(&self.#members, cx, dst)?;)*
+                Ok(())
+            };
+            let store = quote! {
+                let mut field = 0;
+                #(wasmtime::component::Lower::store(
+                    &self.#members,
+                    cx,
+                    offset + #internal::next_field::<#types>(&mut field),
+                )?;)*
+                Ok(())
+            };
+            (lower, store)
+        }
+        Shape::Variant(cases) => {
+            let types = payloads(cases);
+            let discriminants = (0..cases.len() as u32)
+                .map(Literal::u32_unsuffixed)
+                .collect::<Vec<_>>();
+            let patterns = cases
+                .iter()
+                .map(|(ident, payload)| match payload {
+                    Some(_) => quote!(Self::#ident(payload)),
+                    None => quote!(Self::#ident),
+                })
+                .collect::<Vec<_>>();
+            let payloads = cases
+                .iter()
+                .map(|(_, payload)| match payload {
+                    Some(_) => quote!(payload),
+                    None => quote!(&()),
+                })
+                .collect::<Vec<_>>();
+            let lower = quote! {
+                let cases: &[fn(&mut std::vec::Vec<#internal::ValType>)] = &[
+                    #(<#types as wasmtime::component::ComponentType>::flatten,)*
+                ];
+                match self {
+                    #(#patterns => #internal::lower_variant(
+                        cx,
+                        #discriminants,
+                        #payloads,
+                        cases,
+                        dst,
+                    ),)*
+                }
+            };
+            let store = quote! {
+                let cases = &[#((
+                    <#types as wasmtime::component::ComponentType>::SIZE32,
+                    <#types as wasmtime::component::ComponentType>::ALIGN32,
+                ),)*];
+                match self {
+                    #(#patterns => #internal::store_variant(
+                        cx,
+                        offset,
+                        #discriminants,
+                        #payloads,
+                        cases,
+                    ),)*
+                }
+            };
+            (lower, store)
+        }
+    };
+    quote! {
+        #header {
+            #[allow(unused_variables)]
+            fn lower<__T>(
+                &self,
+                cx: &mut wasmtime::component::LowerContext<'_, __T>,
+                dst: &mut std::vec::Vec<wasmtime::Val>,
+            ) -> #internal::Result<()> {
+                #lower
+            }
+
+            #[allow(unused_variables)]
+            fn store<__T>(
+                &self,
+                cx: &mut wasmtime::component::LowerContext<'_, __T>,
+                offset: usize,
+            ) -> #internal::Result<()> {
+                #store
+            }
+        }
+    }
+}
@@ -16,7 +16,7 @@ use cranelift_entity::{EntityRef, PrimaryMap};
 use cranelift_frontend::FunctionBuilder;
 use cranelift_wasm::{
     DefinedFuncIndex, DefinedMemoryIndex, FuncIndex, FuncTranslator, MemoryIndex, SignatureIndex,
-    WasmFuncType,
+    WasmError, WasmFuncType, WasmType,
 };
 use object::write::Object;
 use std::any::Any;
@@ -26,9 +26,10 @@ use std::convert::TryFrom;
 use std::mem;
 use std::sync::Mutex;
 use wasmtime_environ::{
-    AddressMapSection, CompileError, FilePos, FlagValue, FunctionBodyData, FunctionInfo,
-    InstructionAddressMap, Module, ModuleTranslation, StackMapInformation, TrapCode,
-    TrapEncodingBuilder, TrapInformation, Tunables, TypeTables, VMOffsets,
+    AddressMapSection, CompileError, ExceptionHandlerInformation, ExceptionTableSection, FilePos,
+    FlagValue, FunctionBodyData, FunctionInfo, InstructionAddressMap, Module, ModuleTranslation,
+    StackMapInformation, StackMapSection, TrapCode, TrapEncodingBuilder, TrapInformation, Tunables,
+    TypeTables, VMOffsets,
 };
 
 /// A compiler that compiles a WebAssembly module with Compiler, translating
@@ -126,6 +127,17 @@ impl wasmtime_environ::Compiler for Compiler {
         }
 
         let mut func_env = FuncEnvironment::new(isa, module, types, tunables);
+        if tunables.guest_debug {
+            let body_offset = input.body.get_binary_reader().original_position();
+            let mut locals = Vec::new();
+            let reader = input.body.get_locals_reader().map_err(WasmError::from)?;
+            for local in reader {
+                let (count, ty) = local.map_err(WasmError::from)?;
+                let ty = WasmType::try_from(ty)?;
+                locals.extend((0..count).map(|_| ty));
+            }
+            func_env.enable_guest_debug(func_index, body_offset as u32, locals);
+        }
 
         // We use these as constant offsets below in
         // `stack_limit_from_arguments`, so assert their values here. This
@@ -192,6 +204,20 @@ impl wasmtime_environ::Compiler for Compiler {
         let address_transform =
             self.get_function_address_map(&context, &input, code_buf.len() as u32);
 
+        let exception_handlers = match &context.mach_compile_result {
+            Some(result) => result
+                .buffer
+                .exception_handlers()
+                .iter()
+                .map(|handler| ExceptionHandlerInformation {
+                    ret_offset: handler.ret_addr,
+                    landing_pad_offset: handler.landing_pad,
+                    sp_offset: handler.sp_offset,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
         let ranges = if tunables.generate_native_debuginfo {
             let ranges = context.build_value_labels_ranges(isa).map_err(|error| {
                 CompileError::Codegen(pretty_error(&context.func, Some(isa), error))
@@ -209,9 +235,10 @@ impl wasmtime_environ::Compiler for Compiler {
             stack_slots: context.func.stack_slots,
             unwind_info,
             traps: trap_sink.traps,
+            stack_maps: stack_map_sink.finish(),
+            exception_handlers,
             info: FunctionInfo {
                 start_srcloc: address_transform.start_srcloc,
-                stack_maps: stack_map_sink.finish(),
             },
             address_map: address_transform,
         }))
@@ -250,10 +277,14 @@ impl wasmtime_environ::Compiler for Compiler {
         let mut builder = ObjectBuilder::new(obj, &translation.module);
         let mut addrs = AddressMapSection::default();
         let mut traps = TrapEncodingBuilder::default();
+        let mut stack_maps = StackMapSection::default();
+        let mut exception_handlers = ExceptionTableSection::default();
 
         for (i, func) in funcs.iter() {
             let range = builder.func(i, func);
             addrs.push(range.clone(), &func.address_map.instructions);
+            stack_maps.push(range.clone(), &func.stack_maps);
+            exception_handlers.push(range.clone(), &func.exception_handlers);
             traps.push(range, &func.traps);
         }
         for (i, func) in trampolines.iter() {
@@ -261,6 +292,12 @@ impl wasmtime_environ::Compiler for Compiler {
         }
         builder.align_text_to(CODE_SECTION_ALIGNMENT);
 
+        // Finish the text section, which also appends unwind information,
+        // before any DWARF sections are added. This places the unwind
+        // information immediately after the text section in the image, which
+        // is required to load the text section in place.
+        builder.finish(&*self.isa)?;
+
         if emit_dwarf && funcs.len() > 0 {
             let ofs = VMOffsets::new(
                 self.isa
@@ -291,9 +328,10 @@ impl wasmtime_environ::Compiler for Compiler {
             builder.dwarf_sections(&dwarf_sections)?;
         }
 
-        builder.finish(&*self.isa)?;
         addrs.append_to(obj);
         traps.append_to(obj);
+        stack_maps.append_to(obj);
+        exception_handlers.append_to(obj);
 
         Ok(funcs.into_iter().map(|(_, f)| f.info).collect())
     }
@@ -537,6 +575,8 @@ impl Compiler {
             info: Default::default(),
             address_map: Default::default(),
             traps: Vec::new(),
+            stack_maps: Vec::new(),
+            exception_handlers: Vec::new(),
         })
     }
 }
@@ -10,7 +10,7 @@ use cranelift_frontend::FunctionBuilder;
 use cranelift_frontend::Variable;
 use cranelift_wasm::{
     self, FuncIndex, FuncTranslationState, GlobalIndex, GlobalVariable, MemoryIndex, TableIndex,
-    TargetEnvironment, TypeIndex, WasmError, WasmResult, WasmType,
+    TagIndex, TargetEnvironment, TypeIndex, WasmError, WasmResult, WasmType,
 };
 use std::convert::TryFrom;
 use std::mem;
@@ -137,6 +137,38 @@ pub struct FuncEnvironment<'module_environment> {
     vminterrupts_ptr: cranelift_frontend::Variable,
 
     fuel_consumed: i64,
+
+    /// A function-local variable which caches the value of the epoch
+    /// deadline from `VMInterrupts`. This is only a cache: when the current
+    /// epoch is seen to be at or beyond it the real deadline is reloaded
+    /// before calling into the runtime.
+    epoch_deadline_var: cranelift_frontend::Variable,
+
+    /// A function-local variable which caches the value of `*const
+    /// AtomicU64` for the engine's epoch counter, loaded from the vmctx.
+    epoch_ptr_var: cranelift_frontend::Variable,
+
+    /// The function being translated, used to describe the location of each
+    /// guest debugging hook. Only present if `Tunables::guest_debug` is
+    /// enabled.
+    guest_debug: Option<GuestDebugFunc>,
+
+    /// The stack slot that locals and operand stack values are written into
+    /// for guest debugging hooks, sized for the hook with the most values.
+    debug_values_slot: Option<ir::StackSlot>,
+
+    /// The stack slot that the payload of thrown exceptions is written into,
+    /// sized for the tag with the largest payload.
+    throw_payload_slot: Option<ir::StackSlot>,
+}
+
+/// The function-specific information needed to emit guest debugging hooks.
+struct GuestDebugFunc {
+    func_index: FuncIndex,
+    /// The offset of the function's body within the wasm module.
+    body_offset: u32,
+    /// The types of the function's parameters followed by its locals.
+    locals: Vec<WasmType>,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -169,9 +201,31 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // Start with at least one fuel being consumed because even empty
             // functions should consume at least some fuel.
             fuel_consumed: 1,
+            epoch_deadline_var: Variable::new(0),
+            epoch_ptr_var: Variable::new(0),
+            guest_debug: None,
+            debug_values_slot: None,
+            throw_payload_slot: None,
         }
     }
 
+    /// Enables guest debugging hooks for the function `func_index`, whose body
+    /// starts at `body_offset` within the wasm module and which declares the
+    /// `locals` given in addition to its parameters.
+    pub fn enable_guest_debug(
+        &mut self,
+        func_index: FuncIndex,
+        body_offset: u32,
+        locals: impl IntoIterator<Item = WasmType>,
+    ) {
+        let sig = &self.types.wasm_signatures[self.module.functions[func_index]];
+        self.guest_debug = Some(GuestDebugFunc {
+            func_index,
+            body_offset,
+            locals: sig.params.iter().cloned().chain(locals).collect(),
+        });
+    }
+
     fn pointer_type(&self) -> ir::Type {
         self.isa.pointer_type()
     }
@@ -377,9 +431,13 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // cost is incurred with the conditional check.
             Operator::Block { .. }
             | Operator::Loop { .. }
+            | Operator::Try { .. }
             | Operator::Unreachable
             | Operator::Return
             | Operator::Else
+            | Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Delegate { .. }
             | Operator::End => 0,
 
             // everything else, just call it one operation.
@@ -401,7 +459,9 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             | Operator::CallIndirect { .. }
             | Operator::Call { .. }
             | Operator::ReturnCall { .. }
-            | Operator::ReturnCallIndirect { .. } => {
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. } => {
                 self.fuel_increment_var(builder);
                 self.fuel_save_from_var(builder);
             }
@@ -431,8 +491,12 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // This is similar to `end`, except that it's only the terminator
             // for an `if` block. The same reasoning applies though in that we
             // are terminating a basic block and need to update the fuel
-            // variable.
-            | Operator::Else => self.fuel_increment_var(builder),
+            // variable. The clauses of a `try` block, and `delegate`, are
+            // similar.
+            | Operator::Else
+            | Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Delegate { .. } => self.fuel_increment_var(builder),
 
             // This is a normal instruction where the fuel is buffered to later
             // get added to `self.fuel_var`.
@@ -556,6 +620,231 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder.switch_to_block(continuation_block);
     }
 
+    fn epoch_function_entry(&mut self, builder: &mut FunctionBuilder<'_>) {
+        builder.declare_var(self.epoch_deadline_var, ir::types::I64);
+        self.epoch_load_deadline_into_var(builder);
+        builder.declare_var(self.epoch_ptr_var, self.pointer_type());
+        let epoch_ptr = self.epoch_ptr(builder);
+        builder.def_var(self.epoch_ptr_var, epoch_ptr);
+
+        // We must check for an epoch change when entering a function body.
+        // This ensures that if there's an infinite recursion, and the deadline
+        // has already been reached, we don't loop forever.
+        self.epoch_check(builder);
+    }
+
+    /// Loads the `*const AtomicU64` for the engine's epoch counter stored in
+    /// the vmctx.
+    fn epoch_ptr(&mut self, builder: &mut FunctionBuilder<'_>) -> ir::Value {
+        let vmctx = self.vmctx(builder.func);
+        let pointer_type = self.pointer_type();
+        let base = builder.ins().global_value(pointer_type, vmctx);
+        let offset = i32::try_from(self.offsets.vmctx_epoch_ptr()).unwrap();
+        builder
+            .ins()
+            .load(pointer_type, ir::MemFlags::trusted(), base, offset)
+    }
+
+    /// Loads the current value of the engine's epoch counter.
+    fn epoch_load_current(&mut self, builder: &mut FunctionBuilder<'_>) -> ir::Value {
+        let addr = builder.use_var(self.epoch_ptr_var);
+        builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            addr,
+            ir::immediates::Offset32::new(0),
+        )
+    }
+
+    /// Loads the epoch deadline from `VMInterrupts` into
+    /// `self.epoch_deadline_var`.
+    fn epoch_load_deadline_into_var(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let interrupts = builder.use_var(self.vminterrupts_ptr);
+        let deadline = builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            interrupts,
+            ir::immediates::Offset32::new(i32::from(self.offsets.vminterrupts_epoch_deadline())),
+        );
+        builder.def_var(self.epoch_deadline_var, deadline);
+    }
+
+    /// Checks whether the current epoch has reached the deadline, and if so
+    /// calls the new-epoch function which will either trap or return a new
+    /// deadline.
+    fn epoch_check(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let new_epoch_block = builder.create_block();
+        let new_epoch_doublecheck_block = builder.create_block();
+        let continuation_block = builder.create_block();
+
+        // Compare against the cached deadline first. The cache may be stale
+        // (for example a callee may have moved the deadline) so if it looks
+        // like the deadline has been reached we reload the real deadline and
+        // only call into the runtime if it has really been reached.
+        let epoch_deadline = builder.use_var(self.epoch_deadline_var);
+        let cur_epoch_value = self.epoch_load_current(builder);
+        let cmp = builder.ins().ifcmp(cur_epoch_value, epoch_deadline);
+        builder.ins().brif(
+            IntCC::UnsignedGreaterThanOrEqual,
+            cmp,
+            new_epoch_doublecheck_block,
+            &[],
+        );
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(new_epoch_doublecheck_block);
+
+        builder.switch_to_block(new_epoch_doublecheck_block);
+        self.epoch_load_deadline_into_var(builder);
+        let fresh_epoch_deadline = builder.use_var(self.epoch_deadline_var);
+        let fresh_cmp = builder.ins().ifcmp(cur_epoch_value, fresh_epoch_deadline);
+        builder.ins().brif(
+            IntCC::UnsignedGreaterThanOrEqual,
+            fresh_cmp,
+            new_epoch_block,
+            &[],
+        );
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(new_epoch_block);
+
+        // If the deadline has been reached then we call our new-epoch
+        // intrinsic which either raises a trap or yields and returns the new
+        // deadline to use from here on.
+        builder.switch_to_block(new_epoch_block);
+        let new_epoch_sig = self.builtin_function_signatures.new_epoch(builder.func);
+        let (vmctx, new_epoch) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::new_epoch(),
+        );
+        let call = builder
+            .ins()
+            .call_indirect(new_epoch_sig, new_epoch, &[vmctx]);
+        let new_deadline = *builder.func.dfg.inst_results(call).first().unwrap();
+        builder.def_var(self.epoch_deadline_var, new_deadline);
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+    }
+
+    /// Calls into the runtime's debug hook, if the store has requested it,
+    /// with the values of all locals and of the operand stack.
+    ///
+    /// The values are written to a stack slot as an array of `VMDebugValue`,
+    /// each tagged with its type's encoding in the wasm binary format. Values
+    /// on the operand stack are tagged based on their Cranelift type, so
+    /// `funcref`s there are indistinguishable from pointer-sized integers.
+    fn debug_hook(&mut self, builder: &mut FunctionBuilder<'_>, state: &FuncTranslationState) {
+        let hook_block = builder.create_block();
+        let continuation_block = builder.create_block();
+
+        let interrupts = builder.use_var(self.vminterrupts_ptr);
+        let enabled = builder.ins().load(
+            ir::types::I32,
+            ir::MemFlags::trusted(),
+            interrupts,
+            i32::from(self.offsets.vminterrupts_debug_hooks()),
+        );
+        // The srcloc of any instruction emitted here is the offset of the
+        // wasm instruction about to be translated.
+        let load = builder.func.dfg.value_def(enabled).unwrap_inst();
+        let offset = builder.func.srclocs[load].bits();
+        builder.ins().brnz(enabled, hook_block, &[]);
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(hook_block);
+
+        builder.switch_to_block(hook_block);
+        let debug = self.guest_debug.as_ref().unwrap();
+        let func_index = debug.func_index;
+        let func_offset = offset - debug.body_offset;
+        let mut values = Vec::new();
+        for (i, ty) in debug.locals.iter().enumerate() {
+            let val = builder.use_var(Variable::new(i));
+            values.push((debug_value_ty(*ty), val));
+        }
+        let num_locals = values.len();
+        for val in state.stack() {
+            let ty = match builder.func.dfg.value_type(*val) {
+                I32 => WasmType::I32,
+                I64 => WasmType::I64,
+                F32 => WasmType::F32,
+                F64 => WasmType::F64,
+                R32 | R64 => WasmType::ExternRef,
+                ty if ty.is_vector() => WasmType::V128,
+                ty => panic!("unexpected type on the operand stack: {}", ty),
+            };
+            values.push((debug_value_ty(ty), *val));
+        }
+
+        let value_size = u32::from(self.offsets.size_of_vmdebug_value());
+        let size = value_size * values.len() as u32;
+        let slot = match self.debug_values_slot {
+            Some(slot) => {
+                let data = &mut builder.func.stack_slots[slot];
+                data.size = data.size.max(size);
+                slot
+            }
+            None => {
+                let slot = builder.create_stack_slot(ir::StackSlotData::new(
+                    ir::StackSlotKind::ExplicitSlot,
+                    size,
+                ));
+                self.debug_values_slot = Some(slot);
+                slot
+            }
+        };
+        let pointer_type = self.pointer_type();
+        let base = builder.ins().stack_addr(pointer_type, slot, 0);
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        for (i, (ty, val)) in values.iter().enumerate() {
+            let offset = (value_size * i as u32) as i32;
+            let ty = builder.ins().iconst(I32, i64::from(*ty));
+            builder.ins().store(
+                flags,
+                ty,
+                base,
+                offset + i32::from(self.offsets.vmdebug_value_ty()),
+            );
+            builder.ins().store(
+                flags,
+                *val,
+                base,
+                offset + i32::from(self.offsets.vmdebug_value_value()),
+            );
+        }
+
+        let debug_hook_sig = self.builtin_function_signatures.debug_hook(builder.func);
+        let (vmctx, debug_hook) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::debug_hook(),
+        );
+        let func_index = builder.ins().iconst(I32, i64::from(func_index.as_u32()));
+        let func_offset = builder.ins().iconst(I32, i64::from(func_offset));
+        let num_locals = builder.ins().iconst(I32, num_locals as i64);
+        let num_stack = builder.ins().iconst(I32, state.stack().len() as i64);
+        builder.ins().call_indirect(
+            debug_hook_sig,
+            debug_hook,
+            &[vmctx, func_index, func_offset, base, num_locals, num_stack],
+        );
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+    }
+
+    /// Returns the types of the payload of exceptions with the tag `index`.
+    fn tag_payload_types(&self, index: TagIndex) -> &[WasmType] {
+        &self.types.wasm_signatures[self.module.tags[index].ty].params
+    }
+
+    /// Returns the size of a stack slot that holds `len` payload values, each
+    /// of which takes up 16 bytes like a `VMInvokeArgument`. Slots are never
+    /// empty, since their address identifies a caught exception.
+    fn payload_slot_size(len: usize) -> u32 {
+        16 * u32::try_from(len.max(1)).unwrap()
+    }
+
     fn memory_index_type(&self, index: MemoryIndex) -> ir::Type {
         if self.module.memory_plans[index].memory.memory64 {
             I64
@@ -609,6 +898,142 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             pos.ins().uextend(I64, val)
         }
     }
+
+    /// Checks and loads the function at index `callee` in the table for an
+    /// indirect call, returning its address and the arguments to pass it.
+    fn prepare_call_indirect(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        table_index: TableIndex,
+        table: ir::Table,
+        ty_index: TypeIndex,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> (ir::Value, Vec<ir::Value>) {
+        let pointer_type = self.pointer_type();
+
+        let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
+
+        // Dereference the table entry to get the pointer to the
+        // `VMCallerCheckedAnyfunc`.
+        let anyfunc_ptr =
+            pos.ins()
+                .load(pointer_type, ir::MemFlags::trusted(), table_entry_addr, 0);
+
+        // Check for whether the table element is null, and trap if so.
+        pos.ins()
+            .trapz(anyfunc_ptr, ir::TrapCode::IndirectCallToNull);
+
+        // Dereference anyfunc pointer to get the function address.
+        let mem_flags = ir::MemFlags::trusted();
+        let func_addr = pos.ins().load(
+            pointer_type,
+            mem_flags,
+            anyfunc_ptr,
+            i32::from(self.offsets.vmcaller_checked_anyfunc_func_ptr()),
+        );
+
+        // If necessary, check the signature.
+        match self.module.table_plans[table_index].style {
+            TableStyle::CallerChecksSignature => {
+                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
+                let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
+                let vmctx = self.vmctx(pos.func);
+                let base = pos.ins().global_value(pointer_type, vmctx);
+                let offset =
+                    i32::try_from(self.offsets.vmctx_vmshared_signature_id(ty_index)).unwrap();
+
+                // Load the caller ID.
+                let mut mem_flags = ir::MemFlags::trusted();
+                mem_flags.set_readonly();
+                let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
+
+                // Load the callee ID.
+                let mem_flags = ir::MemFlags::trusted();
+                let callee_sig_id = pos.ins().load(
+                    sig_id_type,
+                    mem_flags,
+                    anyfunc_ptr,
+                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
+                );
+
+                // Check that they match.
+                let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
+                pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
+            }
+        }
+
+        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
+        let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
+
+        // First append the callee vmctx address.
+        let vmctx = pos.ins().load(
+            pointer_type,
+            mem_flags,
+            anyfunc_ptr,
+            i32::from(self.offsets.vmcaller_checked_anyfunc_vmctx()),
+        );
+        real_call_args.push(vmctx);
+        real_call_args.push(caller_vmctx);
+
+        // Then append the regular call arguments.
+        real_call_args.extend_from_slice(call_args);
+
+        (func_addr, real_call_args)
+    }
+
+    /// Returns the arguments to pass to the function `callee_index` for a
+    /// direct call, along with the address to call it through if it's
+    /// imported.
+    fn prepare_call(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        callee_index: FuncIndex,
+        call_args: &[ir::Value],
+    ) -> (Option<ir::Value>, Vec<ir::Value>) {
+        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
+        let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
+
+        // Handle direct calls to locally-defined functions.
+        if !self.module.is_imported_function(callee_index) {
+            // First append the callee vmctx address, which is the same as the caller vmctx in
+            // this case.
+            real_call_args.push(caller_vmctx);
+
+            // Then append the caller vmctx address.
+            real_call_args.push(caller_vmctx);
+
+            // Then append the regular call arguments.
+            real_call_args.extend_from_slice(call_args);
+
+            return (None, real_call_args);
+        }
+
+        // Handle direct calls to imported functions. We use an indirect call
+        // so that we don't have to patch the code at runtime.
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(pos.func);
+        let base = pos.ins().global_value(pointer_type, vmctx);
+
+        let mem_flags = ir::MemFlags::trusted();
+
+        // Load the callee address.
+        let body_offset =
+            i32::try_from(self.offsets.vmctx_vmfunction_import_body(callee_index)).unwrap();
+        let func_addr = pos.ins().load(pointer_type, mem_flags, base, body_offset);
+
+        // First append the callee vmctx address.
+        let vmctx_offset =
+            i32::try_from(self.offsets.vmctx_vmfunction_import_vmctx(callee_index)).unwrap();
+        let vmctx = pos.ins().load(pointer_type, mem_flags, base, vmctx_offset);
+        real_call_args.push(vmctx);
+        real_call_args.push(caller_vmctx);
+
+        // Then append the regular call arguments.
+        real_call_args.extend_from_slice(call_args);
+
+        (Some(func_addr), real_call_args)
+    }
 }
 
 impl<'module_environment> TargetEnvironment for FuncEnvironment<'module_environment> {
@@ -631,6 +1056,8 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     fn after_locals(&mut self, num_locals: usize) {
         self.vminterrupts_ptr = Variable::new(num_locals);
         self.fuel_var = Variable::new(num_locals + 1);
+        self.epoch_deadline_var = Variable::new(num_locals + 2);
+        self.epoch_ptr_var = Variable::new(num_locals + 3);
     }
 
     fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> WasmResult<ir::Table> {
@@ -1173,29 +1600,50 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
         let pointer_type = self.pointer_type();
 
+        let is_shared = self.module.memory_plans[index].memory.shared;
         let (ptr, base_offset, current_length_offset) = {
             let vmctx = self.vmctx(func);
-            if let Some(def_index) = self.module.defined_memory_index(index) {
-                let base_offset =
-                    i32::try_from(self.offsets.vmctx_vmmemory_definition_base(def_index)).unwrap();
-                let current_length_offset = i32::try_from(
-                    self.offsets
-                        .vmctx_vmmemory_definition_current_length(def_index),
-                )
-                .unwrap();
-                (vmctx, base_offset, current_length_offset)
-            } else {
-                let from_offset = self.offsets.vmctx_vmmemory_import_from(index);
-                let memory = func.create_global_value(ir::GlobalValueData::Load {
-                    base: vmctx,
-                    offset: Offset32::new(i32::try_from(from_offset).unwrap()),
-                    global_type: pointer_type,
-                    readonly: true,
-                });
-                let base_offset = i32::from(self.offsets.vmmemory_definition_base());
-                let current_length_offset =
-                    i32::from(self.offsets.vmmemory_definition_current_length());
-                (memory, base_offset, current_length_offset)
+            match self.module.defined_memory_index(index) {
+                // Shared memories are defined outside of the instance so they
+                // can outlive it, so their definition is always reached
+                // through the pointer stored in the `VMContext`.
+                Some(def_index) if is_shared => {
+                    let from_offset = self.offsets.vmctx_vmmemory_pointer(def_index);
+                    let memory = func.create_global_value(ir::GlobalValueData::Load {
+                        base: vmctx,
+                        offset: Offset32::new(i32::try_from(from_offset).unwrap()),
+                        global_type: pointer_type,
+                        readonly: true,
+                    });
+                    let base_offset = i32::from(self.offsets.vmmemory_definition_base());
+                    let current_length_offset =
+                        i32::from(self.offsets.vmmemory_definition_current_length());
+                    (memory, base_offset, current_length_offset)
+                }
+                Some(def_index) => {
+                    let base_offset =
+                        i32::try_from(self.offsets.vmctx_vmmemory_definition_base(def_index))
+                            .unwrap();
+                    let current_length_offset = i32::try_from(
+                        self.offsets
+                            .vmctx_vmmemory_definition_current_length(def_index),
+                    )
+                    .unwrap();
+                    (vmctx, base_offset, current_length_offset)
+                }
+                None => {
+                    let from_offset = self.offsets.vmctx_vmmemory_import_from(index);
+                    let memory = func.create_global_value(ir::GlobalValueData::Load {
+                        base: vmctx,
+                        offset: Offset32::new(i32::try_from(from_offset).unwrap()),
+                        global_type: pointer_type,
+                        readonly: true,
+                    });
+                    let base_offset = i32::from(self.offsets.vmmemory_definition_base());
+                    let current_length_offset =
+                        i32::from(self.offsets.vmmemory_definition_current_length());
+                    (memory, base_offset, current_length_offset)
+                }
             }
         };
 
@@ -1311,75 +1759,8 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         callee: ir::Value,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        let pointer_type = self.pointer_type();
-
-        let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
-
-        // Dereference the table entry to get the pointer to the
-        // `VMCallerCheckedAnyfunc`.
-        let anyfunc_ptr =
-            pos.ins()
-                .load(pointer_type, ir::MemFlags::trusted(), table_entry_addr, 0);
-
-        // Check for whether the table element is null, and trap if so.
-        pos.ins()
-            .trapz(anyfunc_ptr, ir::TrapCode::IndirectCallToNull);
-
-        // Dereference anyfunc pointer to get the function address.
-        let mem_flags = ir::MemFlags::trusted();
-        let func_addr = pos.ins().load(
-            pointer_type,
-            mem_flags,
-            anyfunc_ptr,
-            i32::from(self.offsets.vmcaller_checked_anyfunc_func_ptr()),
-        );
-
-        // If necessary, check the signature.
-        match self.module.table_plans[table_index].style {
-            TableStyle::CallerChecksSignature => {
-                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
-                let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
-                let vmctx = self.vmctx(pos.func);
-                let base = pos.ins().global_value(pointer_type, vmctx);
-                let offset =
-                    i32::try_from(self.offsets.vmctx_vmshared_signature_id(ty_index)).unwrap();
-
-                // Load the caller ID.
-                let mut mem_flags = ir::MemFlags::trusted();
-                mem_flags.set_readonly();
-                let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
-
-                // Load the callee ID.
-                let mem_flags = ir::MemFlags::trusted();
-                let callee_sig_id = pos.ins().load(
-                    sig_id_type,
-                    mem_flags,
-                    anyfunc_ptr,
-                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
-                );
-
-                // Check that they match.
-                let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
-                pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
-            }
-        }
-
-        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
-        let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
-
-        // First append the callee vmctx address.
-        let vmctx = pos.ins().load(
-            pointer_type,
-            mem_flags,
-            anyfunc_ptr,
-            i32::from(self.offsets.vmcaller_checked_anyfunc_vmctx()),
-        );
-        real_call_args.push(vmctx);
-        real_call_args.push(caller_vmctx);
-
-        // Then append the regular call arguments.
-        real_call_args.extend_from_slice(call_args);
-
+        let (func_addr, real_call_args) =
+            self.prepare_call_indirect(&mut pos, table_index, table, ty_index, callee, call_args);
         Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args))
     }
 
@@ -1390,49 +1771,50 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         callee: ir::FuncRef,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
-        let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
-
-        // Handle direct calls to locally-defined functions.
-        if !self.module.is_imported_function(callee_index) {
-            // First append the callee vmctx address, which is the same as the caller vmctx in
-            // this case.
-            real_call_args.push(caller_vmctx);
-
-            // Then append the caller vmctx address.
-            real_call_args.push(caller_vmctx);
-
-            // Then append the regular call arguments.
-            real_call_args.extend_from_slice(call_args);
-
-            return Ok(pos.ins().call(callee, &real_call_args));
+        match self.prepare_call(&mut pos, callee_index, call_args) {
+            (None, real_call_args) => Ok(pos.ins().call(callee, &real_call_args)),
+            (Some(func_addr), real_call_args) => {
+                let sig_ref = pos.func.dfg.ext_funcs[callee].signature;
+                Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args))
+            }
         }
+    }
 
-        // Handle direct calls to imported functions. We use an indirect call
-        // so that we don't have to patch the code at runtime.
-        let pointer_type = self.pointer_type();
-        let sig_ref = pos.func.dfg.ext_funcs[callee].signature;
-        let vmctx = self.vmctx(&mut pos.func);
-        let base = pos.ins().global_value(pointer_type, vmctx);
-
-        let mem_flags = ir::MemFlags::trusted();
-
-        // Load the callee address.
-        let body_offset =
-            i32::try_from(self.offsets.vmctx_vmfunction_import_body(callee_index)).unwrap();
-        let func_addr = pos.ins().load(pointer_type, mem_flags, base, body_offset);
-
-        // First append the callee vmctx address.
-        let vmctx_offset =
-            i32::try_from(self.offsets.vmctx_vmfunction_import_vmctx(callee_index)).unwrap();
-        let vmctx = pos.ins().load(pointer_type, mem_flags, base, vmctx_offset);
-        real_call_args.push(vmctx);
-        real_call_args.push(caller_vmctx);
-
-        // Then append the regular call arguments.
-        real_call_args.extend_from_slice(call_args);
+    fn translate_return_call_indirect(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        table_index: TableIndex,
+        table: ir::Table,
+        ty_index: TypeIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()> {
+        let (func_addr, real_call_args) =
+            self.prepare_call_indirect(&mut pos, table_index, table, ty_index, callee, call_args);
+        pos.ins()
+            .return_call_indirect(sig_ref, func_addr, &real_call_args);
+        Ok(())
+    }
 
-        Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args))
+    fn translate_return_call(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        callee_index: FuncIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<()> {
+        match self.prepare_call(&mut pos, callee_index, call_args) {
+            (None, real_call_args) => {
+                pos.ins().return_call(callee, &real_call_args);
+            }
+            (Some(func_addr), real_call_args) => {
+                let sig_ref = pos.func.dfg.ext_funcs[callee].signature;
+                pos.ins()
+                    .return_call_indirect(sig_ref, func_addr, &real_call_args);
+            }
+        }
+        Ok(())
     }
 
     fn translate_memory_grow(
@@ -1470,8 +1852,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         let pointer_type = self.pointer_type();
         let vmctx = self.vmctx(&mut pos.func);
         let base = pos.ins().global_value(pointer_type, vmctx);
+        let is_shared = self.module.memory_plans[index].memory.shared;
         let current_length_in_bytes = match self.module.defined_memory_index(index) {
-            Some(def_index) => {
+            Some(def_index) if !is_shared => {
                 let offset = i32::try_from(
                     self.offsets
                         .vmctx_vmmemory_definition_current_length(def_index),
@@ -1480,8 +1863,12 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 pos.ins()
                     .load(pointer_type, ir::MemFlags::trusted(), base, offset)
             }
-            None => {
-                let offset = i32::try_from(self.offsets.vmctx_vmmemory_import_from(index)).unwrap();
+            def_index => {
+                let offset = match def_index {
+                    Some(def_index) => self.offsets.vmctx_vmmemory_pointer(def_index),
+                    None => self.offsets.vmctx_vmmemory_import_from(index),
+                };
+                let offset = i32::try_from(offset).unwrap();
                 let vmmemory_ptr =
                     pos.ins()
                         .load(pointer_type, ir::MemFlags::trusted(), base, offset);
@@ -1740,6 +2127,146 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
+    fn translate_throw(
+        &mut self,
+        mut pos: FuncCursor,
+        tag_index: TagIndex,
+        args: &[ir::Value],
+    ) -> WasmResult<()> {
+        let size = Self::payload_slot_size(args.len());
+        let slot = match self.throw_payload_slot {
+            Some(slot) => {
+                let data = &mut pos.func.stack_slots[slot];
+                data.size = data.size.max(size);
+                slot
+            }
+            None => {
+                let slot = pos.func.create_stack_slot(ir::StackSlotData::new(
+                    ir::StackSlotKind::ExplicitSlot,
+                    size,
+                ));
+                self.throw_payload_slot = Some(slot);
+                slot
+            }
+        };
+        let pointer_type = self.pointer_type();
+        let payload = pos.ins().stack_addr(pointer_type, slot, 0);
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        for (i, arg) in args.iter().enumerate() {
+            pos.ins().store(flags, *arg, payload, (i * 16) as i32);
+        }
+
+        let func_sig = self
+            .builtin_function_signatures
+            .throw_exception(&mut pos.func);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            BuiltinFunctionIndex::throw_exception(),
+        );
+        let tag_index = pos.ins().iconst(I32, i64::from(tag_index.as_u32()));
+        let len = pos.ins().iconst(I32, args.len() as i64);
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, tag_index, payload, len]);
+        Ok(())
+    }
+
+    fn translate_landing_pad(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
+        // The fuel variable is reloaded after a call, but only once the call
+        // has returned normally, so reload it here for the handlers too.
+        if self.tunables.consume_fuel {
+            self.fuel_load_into_var(builder);
+        }
+        Ok(())
+    }
+
+    fn translate_exception_matches(
+        &mut self,
+        mut pos: FuncCursor,
+        tag_index: TagIndex,
+    ) -> WasmResult<ir::Value> {
+        let func_sig = self
+            .builtin_function_signatures
+            .exception_matches(&mut pos.func);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            BuiltinFunctionIndex::exception_matches(),
+        );
+        let tag_index = pos.ins().iconst(I32, i64::from(tag_index.as_u32()));
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, tag_index]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn translate_catch(
+        &mut self,
+        mut pos: FuncCursor,
+        tag_index: Option<TagIndex>,
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)> {
+        let types = match tag_index {
+            Some(index) => self.tag_payload_types(index).to_vec(),
+            None => Vec::new(),
+        };
+
+        // Each clause gets a slot of its own since its address identifies the
+        // caught exception for as long as the clause may rethrow it.
+        let slot = pos.func.create_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            Self::payload_slot_size(types.len()),
+        ));
+        let pointer_type = self.pointer_type();
+        let payload = pos.ins().stack_addr(pointer_type, slot, 0);
+
+        let func_sig = self
+            .builtin_function_signatures
+            .catch_exception(&mut pos.func);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            BuiltinFunctionIndex::catch_exception(),
+        );
+        let len = pos.ins().iconst(I32, types.len() as i64);
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, payload, len]);
+
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        let values = types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let ty = super::value_type(self.isa, *ty);
+                pos.ins().load(ty, flags, payload, (i * 16) as i32)
+            })
+            .collect();
+        Ok((payload, values))
+    }
+
+    fn translate_rethrow(&mut self, mut pos: FuncCursor, exception: ir::Value) -> WasmResult<()> {
+        let func_sig = self
+            .builtin_function_signatures
+            .rethrow_exception(&mut pos.func);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            BuiltinFunctionIndex::rethrow_exception(),
+        );
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, exception]);
+        Ok(())
+    }
+
+    fn translate_resume_unwind(&mut self, mut pos: FuncCursor) -> WasmResult<()> {
+        let func_sig = self
+            .builtin_function_signatures
+            .resume_exception(&mut pos.func);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            BuiltinFunctionIndex::resume_exception(),
+        );
+        pos.ins().call_indirect(func_sig, func_addr, &[vmctx]);
+        Ok(())
+    }
+
     fn translate_loop_header(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
         // If enabled check the interrupt flag to prevent long or infinite
         // loops.
@@ -1772,6 +2299,12 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             self.fuel_check(builder);
         }
 
+        // If we are performing epoch-based interruption, check to see if the
+        // epoch counter has changed.
+        if self.tunables.epoch_interruption {
+            self.epoch_check(builder);
+        }
+
         Ok(())
     }
 
@@ -1784,6 +2317,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         if self.tunables.consume_fuel {
             self.fuel_before_op(op, builder, state.reachable());
         }
+        if self.guest_debug.is_some() && state.reachable() {
+            self.debug_hook(builder, state);
+        }
         Ok(())
     }
 
@@ -1806,13 +2342,22 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     ) -> WasmResult<()> {
         // If the `vminterrupts_ptr` variable will get used then we initialize
         // it here.
-        if self.tunables.consume_fuel || self.tunables.interruptable {
+        if self.tunables.consume_fuel
+            || self.tunables.interruptable
+            || self.tunables.epoch_interruption
+            || self.guest_debug.is_some()
+        {
             self.declare_vminterrupts_ptr(builder);
         }
         // Additionally we initialize `fuel_var` if it will get used.
         if self.tunables.consume_fuel {
             self.fuel_function_entry(builder);
         }
+        // Initialize `epoch_deadline_var` and `epoch_ptr_var` if they will get
+        // used.
+        if self.tunables.epoch_interruption {
+            self.epoch_function_entry(builder);
+        }
         Ok(())
     }
 
@@ -1831,3 +2376,18 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         self.isa.unsigned_add_overflow_condition()
     }
 }
+
+/// Returns the tag identifying values of type `ty` passed to the debug hook,
+/// which is the type's encoding in the wasm binary format.
+fn debug_value_ty(ty: WasmType) -> u32 {
+    match ty {
+        WasmType::I32 => 0x7f,
+        WasmType::I64 => 0x7e,
+        WasmType::F32 => 0x7d,
+        WasmType::F64 => 0x7c,
+        WasmType::V128 => 0x7b,
+        WasmType::FuncRef => 0x70,
+        WasmType::ExternRef => 0x6f,
+        WasmType::ExnRef => 0x68,
+    }
+}
@@ -95,7 +95,8 @@ use cranelift_entity::PrimaryMap;
 use cranelift_wasm::{DefinedFuncIndex, FuncIndex, WasmFuncType, WasmType};
 use target_lexicon::CallingConvention;
 use wasmtime_environ::{
-    FilePos, FunctionInfo, InstructionAddressMap, Module, TrapInformation, TypeTables,
+    ExceptionHandlerInformation, FilePos, FunctionInfo, InstructionAddressMap, Module,
+    StackMapInformation, TrapInformation, TypeTables,
 };
 
 pub use builder::builder;
@@ -128,6 +129,14 @@ pub struct CompiledFunction {
     /// that they may cause.
     traps: Vec<TrapInformation>,
 
+    /// The stack maps of all GC safepoints in this function, sorted by their
+    /// offset.
+    stack_maps: Vec<StackMapInformation>,
+
+    /// The landing pads of the calls in this function that have one, sorted
+    /// by the offsets of the calls' return addresses.
+    exception_handlers: Vec<ExceptionHandlerInformation>,
+
     relocations: Vec<Relocation>,
     value_labels_ranges: cranelift_codegen::ValueLabelsRanges,
     stack_slots: ir::StackSlots,
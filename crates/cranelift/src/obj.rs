@@ -341,9 +341,19 @@ impl<'a> ObjectBuilder<'a> {
                         RelocationEncoding::Generic,
                         32,
                     ),
+                    Reloc::Arm32Call => (
+                        RelocationKind::Elf(elf::R_ARM_THM_PC22),
+                        RelocationEncoding::Generic,
+                        32,
+                    ),
                     Reloc::S390xPCRel32Dbl => {
                         (RelocationKind::Relative, RelocationEncoding::S390xDbl, 32)
                     }
+                    Reloc::RiscvCallPlt => (
+                        RelocationKind::Elf(elf::R_RISCV_CALL_PLT),
+                        RelocationEncoding::Generic,
+                        64,
+                    ),
                     other => unimplemented!("Unimplemented relocation {:?}", other),
                 };
                 self.obj.add_relocation(
@@ -516,6 +526,13 @@ impl<'a> ObjectBuilder<'a> {
                 assert_eq!(eh_pe.application(), constants::DW_EH_PE_pcrel);
                 let offset = self.len() as u64;
                 let val = val.wrapping_sub(offset);
+                // The offset is usually negative, so it has to be truncated
+                // to fit in 32-bit pointers.
+                let val = if size < 8 {
+                    val & ((1 << (8 * size)) - 1)
+                } else {
+                    val
+                };
                 self.write_eh_pointer_data(val, eh_pe.format(), size)
             }
         }
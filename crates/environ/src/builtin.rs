@@ -45,6 +45,21 @@ macro_rules! foreach_builtin_function {
             memory_atomic_wait64(vmctx, i32, pointer, i64, i64) -> (i32);
             /// Invoked when fuel has run out while executing a function.
             out_of_gas(vmctx) -> ();
+            /// Invoked when we reach a new epoch.
+            new_epoch(vmctx) -> (i64);
+            /// Invoked before a wasm instruction when guest debugging has
+            /// requested it.
+            debug_hook(vmctx, i32, i32, pointer, i32, i32) -> ();
+            /// Invoked for wasm's `throw` instruction.
+            throw_exception(vmctx, i32, pointer, i32) -> ();
+            /// Returns whether the pending exception has the given tag.
+            exception_matches(vmctx, i32) -> (i32);
+            /// Invoked on entry to a `catch` or `catch_all` clause.
+            catch_exception(vmctx, pointer, i32) -> ();
+            /// Invoked for wasm's `rethrow` instruction.
+            rethrow_exception(vmctx, pointer) -> ();
+            /// Invoked to continue unwinding an exception that wasn't caught.
+            resume_exception(vmctx) -> ();
         }
     };
 }
@@ -15,13 +15,15 @@ use std::collections::BTreeMap;
 use std::fmt;
 use thiserror::Error;
 
-/// Information about a function, such as trap information, address map,
-/// and stack maps.
+/// Information about a function which is deserialized along with the rest of
+/// a compiled module's metadata.
+///
+/// Note that trap information, address maps, and stack maps are instead
+/// encoded in custom sections of the compilation image which are read lazily.
 #[derive(Serialize, Deserialize, Clone, Default)]
 #[allow(missing_docs)]
 pub struct FunctionInfo {
     pub start_srcloc: FilePos,
-    pub stack_maps: Vec<StackMapInformation>,
 }
 
 /// The offset within a function of a GC safepoint, and its associated stack
@@ -186,6 +188,7 @@ pub trait Compiler: Send + Sync {
                 Arm(_) => Architecture::Arm,
                 Aarch64(_) => Architecture::Aarch64,
                 S390x => Architecture::S390x,
+                Riscv64(_) => Architecture::Riscv64,
                 architecture => {
                     anyhow::bail!("target architecture {:?} is unsupported", architecture,);
                 }
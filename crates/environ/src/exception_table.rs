@@ -0,0 +1,135 @@
+use object::write::{Object, StandardSegment};
+use object::{Bytes, LittleEndian, SectionKind, U32Bytes};
+use std::convert::TryFrom;
+use std::ops::Range;
+
+/// A custom binary-encoded section of wasmtime compilation artifacts which
+/// encodes the landing pads that calls throwing an exception unwind to.
+///
+/// This section is read at runtime by the unwinder when an exception is thrown,
+/// to find the innermost wasm frame with a handler for it. The encoding of this
+/// section is custom to Wasmtime and is managed with helpers in the `object`
+/// crate:
+///
+/// * First the section has a 32-bit little endian integer indicating how many
+///   calls with landing pads are in the section.
+/// * Next is an array, of the same length as read before, of 32-bit
+///   little-endian integers. These integers are the offsets of the return
+///   addresses of the calls in the text section of the compilation image, in
+///   sorted order.
+/// * Finally is an array of twice that many 32-bit little-endian integers. For
+///   each call this holds the offset of its landing pad in the text section,
+///   followed by the distance from the frame pointer down to the stack pointer
+///   that the landing pad expects.
+///
+/// Note that at this time this section has an alignment of 1. Additionally due
+/// to the 32-bit encodings for offsets this doesn't support images >=4gb.
+pub const ELF_WASMTIME_EXCEPTIONS: &str = ".wasmtime.exceptions";
+
+/// Information about a call that unwinds to a landing pad when it throws.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExceptionHandlerInformation {
+    /// The offset of the return address of the call in native code.
+    ///
+    /// This is relative to the beginning of the function.
+    pub ret_offset: u32,
+
+    /// The offset of the landing pad in native code.
+    ///
+    /// This is relative to the beginning of the function.
+    pub landing_pad_offset: u32,
+
+    /// The distance from the frame pointer down to the stack pointer at the
+    /// landing pad.
+    pub sp_offset: u32,
+}
+
+/// A helper structure to build the `ELF_WASMTIME_EXCEPTIONS` section of a
+/// wasmtime compilation image.
+///
+/// This structure is incrementally fed the exception handlers of individual
+/// compiled functions and handles all the encoding internally, allowing usage
+/// of `lookup_exception_handler` below with the resulting section.
+#[derive(Default)]
+pub struct ExceptionTableSection {
+    offsets: Vec<U32Bytes<LittleEndian>>,
+    handlers: Vec<U32Bytes<LittleEndian>>,
+    last_offset: u32,
+}
+
+impl ExceptionTableSection {
+    /// Appends the exception handlers of a function into this section.
+    ///
+    /// The `func` offsets are specified relative to the text section itself,
+    /// and the `handlers` offsets are specified relative to the start of
+    /// `func`.
+    ///
+    /// This is required to be called in-order for increasing ranges of `func`
+    /// to ensure the final array is properly sorted. Additionally `handlers`
+    /// must be sorted.
+    pub fn push(&mut self, func: Range<u64>, handlers: &[ExceptionHandlerInformation]) {
+        let func_start = u32::try_from(func.start).unwrap();
+        let func_end = u32::try_from(func.end).unwrap();
+
+        assert!(func_start >= self.last_offset);
+
+        for info in handlers {
+            let pos = func_start + info.ret_offset;
+            assert!(pos >= self.last_offset);
+            self.offsets.push(U32Bytes::new(LittleEndian, pos));
+            self.handlers.push(U32Bytes::new(
+                LittleEndian,
+                func_start + info.landing_pad_offset,
+            ));
+            self.handlers
+                .push(U32Bytes::new(LittleEndian, info.sp_offset));
+            self.last_offset = pos;
+        }
+
+        self.last_offset = func_end;
+    }
+
+    /// Encodes this section into the object provided.
+    pub fn append_to(self, obj: &mut Object) {
+        let section = obj.add_section(
+            obj.segment_name(StandardSegment::Data).to_vec(),
+            ELF_WASMTIME_EXCEPTIONS.as_bytes().to_vec(),
+            SectionKind::ReadOnlyData,
+        );
+
+        // NB: this matches the encoding expected by `lookup_exception_handler`
+        // below.
+        let amt = u32::try_from(self.offsets.len()).unwrap();
+        obj.append_section_data(section, &amt.to_le_bytes(), 1);
+        obj.append_section_data(section, object::bytes_of_slice(&self.offsets), 1);
+        obj.append_section_data(section, object::bytes_of_slice(&self.handlers), 1);
+    }
+}
+
+/// Decodes the provided exception table section and attempts to find the
+/// landing pad of the call whose return address is at `offset`.
+///
+/// The `section` provided is expected to have been built by
+/// `ExceptionTableSection` above, and `offset` is a relative offset within the
+/// text section of the compilation image. Returns the offset of the landing pad
+/// within the text section, along with the distance from the frame pointer
+/// down to the stack pointer that it expects.
+pub fn lookup_exception_handler(section: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let mut section = Bytes(section);
+    // NB: this matches the encoding written by `append_to` above.
+    let count = section.read::<U32Bytes<LittleEndian>>().ok()?;
+    let count = usize::try_from(count.get(LittleEndian)).ok()?;
+    let (offsets, rest) =
+        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section.0, count).ok()?;
+    let (handlers, _) = object::slice_from_bytes::<U32Bytes<LittleEndian>>(rest, 2 * count).ok()?;
+
+    // Return addresses are precise, unlike the pcs that stack maps are looked
+    // up with, so only an exact match has a landing pad.
+    let offset = u32::try_from(offset).ok()?;
+    let index = offsets
+        .binary_search_by_key(&offset, |val| val.get(LittleEndian))
+        .ok()?;
+    let landing_pad = usize::try_from(handlers.get(2 * index)?.get(LittleEndian)).ok()?;
+    let sp_offset = usize::try_from(handlers.get(2 * index + 1)?.get(LittleEndian)).ok()?;
+    Some((landing_pad, sp_offset))
+}
@@ -26,6 +26,7 @@
 mod address_map;
 mod builtin;
 mod compilation;
+mod exception_table;
 mod module;
 mod module_environ;
 pub mod obj;
@@ -37,9 +38,10 @@ mod vmoffsets;
 pub use crate::address_map::*;
 pub use crate::builtin::*;
 pub use crate::compilation::*;
+pub use crate::exception_table::*;
 pub use crate::module::*;
 pub use crate::module_environ::*;
-pub use crate::stack_map::StackMap;
+pub use crate::stack_map::*;
 pub use crate::trap_encoding::*;
 pub use crate::tunables::Tunables;
 pub use crate::vmoffsets::*;
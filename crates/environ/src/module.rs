@@ -405,6 +405,9 @@ pub struct Module {
     /// Number of imported or aliased globals in the module.
     pub num_imported_globals: usize,
 
+    /// Number of imported or aliased exception tags in the module.
+    pub num_imported_tags: usize,
+
     /// Types of functions, imported and local.
     pub functions: PrimaryMap<FuncIndex, SignatureIndex>,
 
@@ -417,6 +420,9 @@ pub struct Module {
     /// WebAssembly global variables.
     pub globals: PrimaryMap<GlobalIndex, Global>,
 
+    /// WebAssembly exception tags, imported and local.
+    pub tags: PrimaryMap<TagIndex, Tag>,
+
     /// The type of each wasm instance this module defines.
     pub instances: PrimaryMap<InstanceIndex, InstanceTypeIndex>,
 
@@ -603,6 +609,12 @@ impl Module {
         index.index() < self.num_imported_globals
     }
 
+    /// Test whether the given tag index is for an imported tag.
+    #[inline]
+    pub fn is_imported_tag(&self, index: TagIndex) -> bool {
+        index.index() < self.num_imported_tags
+    }
+
     /// Returns an iterator of all the imports in this module, along with their
     /// module name, field name, and type that's being imported.
     pub fn imports(&self) -> impl Iterator<Item = (&str, Option<&str>, EntityType)> {
@@ -623,6 +635,7 @@ impl Module {
             EntityIndex::Function(i) => EntityType::Function(self.functions[i]),
             EntityIndex::Instance(i) => EntityType::Instance(self.instances[i]),
             EntityIndex::Module(i) => EntityType::Module(self.modules[i]),
+            EntityIndex::Tag(i) => EntityType::Tag(self.tags[i]),
         }
     }
 }
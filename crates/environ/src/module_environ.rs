@@ -5,8 +5,8 @@ use crate::module::{
 use crate::{
     DataIndex, DefinedFuncIndex, ElemIndex, EntityIndex, EntityType, FuncIndex, Global,
     GlobalIndex, GlobalInit, InstanceIndex, InstanceTypeIndex, MemoryIndex, ModuleIndex,
-    ModuleTypeIndex, PrimaryMap, SignatureIndex, TableIndex, Tunables, TypeIndex, WasmError,
-    WasmFuncType, WasmResult,
+    ModuleTypeIndex, PrimaryMap, SignatureIndex, TableIndex, Tag, TagIndex, Tunables, TypeIndex,
+    WasmError, WasmFuncType, WasmResult,
 };
 use cranelift_entity::packed_option::ReservedValue;
 use std::borrow::Cow;
@@ -18,7 +18,7 @@ use std::sync::Arc;
 use wasmparser::Type as WasmType;
 use wasmparser::{
     Alias, DataKind, ElementItem, ElementKind, ExternalKind, FuncValidator, FunctionBody,
-    ImportSectionEntryType, NameSectionReader, Naming, Operator, Parser, Payload, TypeDef,
+    ImportSectionEntryType, NameSectionReader, Naming, Operator, Parser, Payload, TagType, TypeDef,
     Validator, ValidatorResources, WasmFeatures,
 };
 
@@ -325,9 +325,6 @@ impl<'data> ModuleEnvironment<'data> {
                             EntityType::Instance(signature)
                         }
                         ImportSectionEntryType::Memory(ty) => {
-                            if ty.shared {
-                                return Err(WasmError::Unsupported("shared memories".to_owned()));
-                            }
                             self.result.module.num_imported_memories += 1;
                             EntityType::Memory(ty.into())
                         }
@@ -339,9 +336,10 @@ impl<'data> ModuleEnvironment<'data> {
                             self.result.module.num_imported_tables += 1;
                             EntityType::Table(ty.try_into()?)
                         }
-
-                        // doesn't get past validation
-                        ImportSectionEntryType::Tag(_) => unreachable!(),
+                        ImportSectionEntryType::Tag(ty) => {
+                            self.result.module.num_imported_tags += 1;
+                            EntityType::Tag(self.tag(ty)?)
+                        }
                     };
                     self.declare_import(import.module, import.field, ty);
                 }
@@ -357,7 +355,14 @@ impl<'data> ModuleEnvironment<'data> {
                     let sigindex = entry?;
                     let ty = TypeIndex::from_u32(sigindex);
                     let sig_index = self.result.module.types[ty].unwrap_function();
-                    self.result.module.functions.push(sig_index);
+                    let func_index = self.result.module.functions.push(sig_index);
+
+                    // A tail call requires the caller and callee to use
+                    // the same calling convention, so with tail calls every
+                    // function uses the one exported functions use.
+                    if self.features.tail_call {
+                        self.flag_func_possibly_exported(func_index);
+                    }
                 }
             }
 
@@ -381,9 +386,6 @@ impl<'data> ModuleEnvironment<'data> {
 
                 for entry in memories {
                     let memory = entry?;
-                    if memory.shared {
-                        return Err(WasmError::Unsupported("shared memories".to_owned()));
-                    }
                     let plan = MemoryPlan::for_memory(memory.into(), &self.tunables);
                     self.result.module.memory_plans.push(plan);
                 }
@@ -392,9 +394,13 @@ impl<'data> ModuleEnvironment<'data> {
             Payload::TagSection(tags) => {
                 validator.tag_section(&tags)?;
 
-                // This feature isn't enabled at this time, so we should
-                // never get here.
-                unreachable!();
+                let cnt = usize::try_from(tags.get_count()).unwrap();
+                self.result.module.tags.reserve_exact(cnt);
+
+                for entry in tags {
+                    let tag = self.tag(entry?)?;
+                    self.result.module.tags.push(tag);
+                }
             }
 
             Payload::GlobalSection(globals) => {
@@ -456,9 +462,10 @@ impl<'data> ModuleEnvironment<'data> {
                         ExternalKind::Instance => {
                             EntityIndex::Instance(InstanceIndex::from_u32(index))
                         }
+                        ExternalKind::Tag => EntityIndex::Tag(TagIndex::from_u32(index)),
 
                         // this never gets past validation
-                        ExternalKind::Tag | ExternalKind::Type => unreachable!(),
+                        ExternalKind::Type => unreachable!(),
                     };
                     self.result
                         .module
@@ -702,9 +709,12 @@ impl<'data> ModuleEnvironment<'data> {
                                 ExternalKind::Instance => {
                                     EntityIndex::Instance(InstanceIndex::from_u32(arg.index))
                                 }
+                                ExternalKind::Tag => {
+                                    EntityIndex::Tag(TagIndex::from_u32(arg.index))
+                                }
 
                                 // this won't pass validation
-                                ExternalKind::Tag | ExternalKind::Type => unreachable!(),
+                                ExternalKind::Type => unreachable!(),
                             };
                             Ok((arg.name.to_string(), index))
                         })
@@ -827,7 +837,10 @@ impl<'data> ModuleEnvironment<'data> {
                                 EntityType::Module(sig) => {
                                     self.result.module.modules.push(*sig);
                                 }
-                                EntityType::Tag(_) => unimplemented!(),
+                                EntityType::Tag(tag) => {
+                                    self.result.module.tags.push(*tag);
+                                    self.result.module.num_imported_tags += 1;
+                                }
                             }
                             self.result
                                 .module
@@ -1058,7 +1071,7 @@ and for re-adding support for interface types you can see this issue:
                 EntityType::Instance(self.type_to_instance_type(TypeIndex::from_u32(sig))?)
             }
             ImportSectionEntryType::Memory(ty) => EntityType::Memory(ty.into()),
-            ImportSectionEntryType::Tag(t) => EntityType::Tag(t.into()),
+            ImportSectionEntryType::Tag(t) => EntityType::Tag(self.tag(t)?),
             ImportSectionEntryType::Global(ty) => {
                 EntityType::Global(Global::new(ty, GlobalInit::Import)?)
             }
@@ -1084,7 +1097,7 @@ and for re-adding support for interface types you can see this issue:
                 EntityIndex::Instance(self.result.module.instances.push(ty))
             }
             EntityType::Module(ty) => EntityIndex::Module(self.result.module.modules.push(ty)),
-            EntityType::Tag(_) => unimplemented!(),
+            EntityType::Tag(ty) => EntityIndex::Tag(self.result.module.tags.push(ty)),
         }
     }
 
@@ -1221,6 +1234,20 @@ and for re-adding support for interface types you can see this issue:
         }
     }
 
+    fn tag(&self, ty: TagType) -> WasmResult<Tag> {
+        let sig = self.type_to_signature(TypeIndex::from_u32(ty.type_index))?;
+        if self.types.wasm_signatures[sig]
+            .params
+            .iter()
+            .any(|ty| *ty == crate::WasmType::ExternRef)
+        {
+            return Err(WasmError::Unsupported(
+                "exception tags with externref payloads".to_string(),
+            ));
+        }
+        Ok(Tag { ty: sig })
+    }
+
     /// Parses the Name section of the wasm module.
     fn name_section(&mut self, names: NameSectionReader<'data>) -> WasmResult<()> {
         for subsection in names {
@@ -1,4 +1,9 @@
+use crate::StackMapInformation;
+use object::write::{Object, StandardSegment};
+use object::{Bytes, LittleEndian, SectionKind, U32Bytes};
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::ops::Range;
 
 /// A map for determining where live GC references live in a stack frame.
 ///
@@ -34,3 +39,137 @@ impl StackMap {
         self.mapped_words
     }
 }
+
+/// A custom binary-encoded section of wasmtime compilation artifacts which
+/// encodes the stack maps of all GC safepoints in the text section.
+///
+/// This section is read lazily at runtime when tracing the stack for GC roots,
+/// rather than being deserialized when a module is loaded. The encoding of
+/// this section is custom to Wasmtime and is managed with helpers in the
+/// `object` crate:
+///
+/// * First the section has a 32-bit little endian integer indicating how many
+///   stack maps are in the section.
+/// * Next is an array, of the same length as read before, of 32-bit
+///   little-endian integers. These integers are the offsets of GC safepoints
+///   in the text section of the compilation image, in sorted order.
+/// * Next is another array of the same length of 32-bit little-endian
+///   integers. Each of these is the index, in 32-bit words, into the remainder
+///   of the section where the stack map for the corresponding offset starts.
+/// * Finally the rest of the section is an array of 32-bit little-endian
+///   words. Each stack map is encoded as its number of mapped words, followed
+///   by the number of words in its bitmap, followed by the bitmap itself.
+///
+/// Note that at this time this section has an alignment of 1. Additionally due
+/// to the 32-bit encodings for offsets this doesn't support images >=4gb.
+pub const ELF_WASMTIME_STACK_MAP: &str = ".wasmtime.stackmap";
+
+/// A helper structure to build the `ELF_WASMTIME_STACK_MAP` section of a
+/// wasmtime compilation image.
+///
+/// This structure is incrementally fed the stack maps of individual compiled
+/// functions and handles all the encoding internally, allowing usage of
+/// `lookup_stack_map` below with the resulting section.
+#[derive(Default)]
+pub struct StackMapSection {
+    offsets: Vec<U32Bytes<LittleEndian>>,
+    indices: Vec<U32Bytes<LittleEndian>>,
+    data: Vec<U32Bytes<LittleEndian>>,
+    last_offset: u32,
+}
+
+impl StackMapSection {
+    /// Appends the stack maps of a function into this section.
+    ///
+    /// The `func` offsets are specified relative to the text section itself,
+    /// and the `stack_maps` offsets are specified relative to the start of
+    /// `func`.
+    ///
+    /// This is required to be called in-order for increasing ranges of `func`
+    /// to ensure the final array is properly sorted. Additionally `stack_maps`
+    /// must be sorted.
+    pub fn push(&mut self, func: Range<u64>, stack_maps: &[StackMapInformation]) {
+        let func_start = u32::try_from(func.start).unwrap();
+        let func_end = u32::try_from(func.end).unwrap();
+
+        assert!(func_start >= self.last_offset);
+
+        for info in stack_maps {
+            let pos = func_start + info.code_offset;
+            assert!(pos >= self.last_offset);
+            let index = u32::try_from(self.data.len()).unwrap();
+            self.offsets.push(U32Bytes::new(LittleEndian, pos));
+            self.indices.push(U32Bytes::new(LittleEndian, index));
+
+            let map = &info.stack_map;
+            let bits_len = u32::try_from(map.bits.len()).unwrap();
+            self.data
+                .push(U32Bytes::new(LittleEndian, map.mapped_words));
+            self.data.push(U32Bytes::new(LittleEndian, bits_len));
+            self.data
+                .extend(map.bits.iter().map(|b| U32Bytes::new(LittleEndian, *b)));
+            self.last_offset = pos;
+        }
+
+        self.last_offset = func_end;
+    }
+
+    /// Encodes this section into the object provided.
+    pub fn append_to(self, obj: &mut Object) {
+        let section = obj.add_section(
+            obj.segment_name(StandardSegment::Data).to_vec(),
+            ELF_WASMTIME_STACK_MAP.as_bytes().to_vec(),
+            SectionKind::ReadOnlyData,
+        );
+
+        // NB: this matches the encoding expected by `lookup_stack_map` below.
+        let amt = u32::try_from(self.offsets.len()).unwrap();
+        obj.append_section_data(section, &amt.to_le_bytes(), 1);
+        obj.append_section_data(section, object::bytes_of_slice(&self.offsets), 1);
+        obj.append_section_data(section, object::bytes_of_slice(&self.indices), 1);
+        obj.append_section_data(section, object::bytes_of_slice(&self.data), 1);
+    }
+}
+
+/// Decodes the provided stack map section and attempts to find the stack map
+/// for the GC safepoint at, or most closely preceding, `offset`.
+///
+/// The `section` provided is expected to have been built by `StackMapSection`
+/// above. Both `func_start` and `offset` are relative offsets within the text
+/// section of the compilation image, where `func_start` is the start of the
+/// function containing `offset`. Safepoints before `func_start` are never
+/// returned.
+pub fn lookup_stack_map(section: &[u8], func_start: usize, offset: usize) -> Option<StackMap> {
+    let mut section = Bytes(section);
+    // NB: this matches the encoding written by `append_to` above.
+    let count = section.read::<U32Bytes<LittleEndian>>().ok()?;
+    let count = usize::try_from(count.get(LittleEndian)).ok()?;
+    let (offsets, rest) =
+        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section.0, count).ok()?;
+    let (indices, rest) = object::slice_from_bytes::<U32Bytes<LittleEndian>>(rest, count).ok()?;
+    let (data, _) =
+        object::slice_from_bytes::<U32Bytes<LittleEndian>>(rest, rest.len() / 4).ok()?;
+
+    // Safepoints are only precisely associated with a single pc, but the pc
+    // being queried here may have drifted past it (see the comments in
+    // `lookup_stack_map` in the `wasmtime` crate), so the closest preceding
+    // safepoint within the same function is used.
+    let offset = u32::try_from(offset).ok()?;
+    let index = match offsets.binary_search_by_key(&offset, |val| val.get(LittleEndian)) {
+        Ok(i) => i,
+        Err(0) => return None,
+        Err(i) => i - 1,
+    };
+    if (offsets[index].get(LittleEndian) as usize) < func_start {
+        return None;
+    }
+
+    let start = usize::try_from(indices.get(index)?.get(LittleEndian)).ok()?;
+    let mapped_words = data.get(start)?.get(LittleEndian);
+    let bits_len = usize::try_from(data.get(start + 1)?.get(LittleEndian)).ok()?;
+    let bits = data.get(start + 2..start + 2 + bits_len)?;
+    Some(StackMap::new(
+        mapped_words,
+        bits.iter().map(|b| b.get(LittleEndian)),
+    ))
+}
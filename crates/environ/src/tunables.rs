@@ -36,6 +36,13 @@ pub struct Tunables {
     /// will be consumed every time a wasm instruction is executed.
     pub consume_fuel: bool,
 
+    /// Whether or not we use epoch-based interruption.
+    pub epoch_interruption: bool,
+
+    /// Whether or not generated code calls into the runtime before each wasm
+    /// instruction, when requested, to support breakpoints and single-stepping.
+    pub guest_debug: bool,
+
     /// Whether or not to treat the static memory bound as the maximum for unbounded heaps.
     pub static_memory_bound_is_maximum: bool,
 
@@ -44,8 +51,23 @@ pub struct Tunables {
     pub guard_before_linear_memory: bool,
 }
 
-impl Default for Tunables {
-    fn default() -> Self {
+impl Tunables {
+    /// Returns the default set of tunables for a target with 32-bit pointers.
+    pub fn default_u32() -> Self {
+        Self {
+            // For 32-bit we scale way down to 10MB of reserved memory. This
+            // impacts performance severely but allows us to have more than a
+            // few instances running around.
+            static_memory_bound: (10 * (1 << 20)) / u64::from(crate::WASM_PAGE_SIZE),
+            static_memory_offset_guard_size: 0x1_0000,
+            // On 32-bit we aren't as lucky with the grow-into-this area.
+            dynamic_memory_growth_reserve: 1 << 20, // 1MB
+            ..Self::default_u64()
+        }
+    }
+
+    /// Returns the default set of tunables for a target with 64-bit pointers.
+    pub fn default_u64() -> Self {
         Self {
             // 64-bit has tons of address space to static memories can have 4gb
             // address space reservations liberally by default, allowing us to
@@ -53,19 +75,9 @@ impl Default for Tunables {
             //
             // Coupled with a 2 GiB address space guard it lets us translate
             // wasm offsets into x86 offsets as aggressively as we can.
-            #[cfg(target_pointer_width = "64")]
             static_memory_bound: 0x1_0000,
-            #[cfg(target_pointer_width = "64")]
             static_memory_offset_guard_size: 0x8000_0000,
 
-            // For 32-bit we scale way down to 10MB of reserved memory. This
-            // impacts performance severely but allows us to have more than a
-            // few instances running around.
-            #[cfg(target_pointer_width = "32")]
-            static_memory_bound: (10 * (1 << 20)) / crate::WASM_PAGE_SIZE,
-            #[cfg(target_pointer_width = "32")]
-            static_memory_offset_guard_size: 0x1_0000,
-
             // Size in bytes of the offset guard for dynamic memories.
             //
             // Allocate a small guard to optimize common cases but without
@@ -73,18 +85,27 @@ impl Default for Tunables {
             dynamic_memory_offset_guard_size: 0x1_0000,
 
             // We've got lots of address space on 64-bit so use a larger
-            // grow-into-this area, but on 32-bit we aren't as lucky.
-            #[cfg(target_pointer_width = "64")]
+            // grow-into-this area.
             dynamic_memory_growth_reserve: 2 << 30, // 2GB
-            #[cfg(target_pointer_width = "32")]
-            dynamic_memory_growth_reserve: 1 << 20, // 1MB
 
             generate_native_debuginfo: false,
             parse_wasm_debuginfo: true,
             interruptable: false,
             consume_fuel: false,
+            epoch_interruption: false,
+            guest_debug: false,
             static_memory_bound_is_maximum: false,
             guard_before_linear_memory: true,
         }
     }
 }
+
+impl Default for Tunables {
+    fn default() -> Self {
+        if cfg!(target_pointer_width = "32") {
+            Self::default_u32()
+        } else {
+            Self::default_u64()
+        }
+    }
+}
@@ -7,6 +7,7 @@
 //      interrupts: *const VMInterrupts,
 //      externref_activations_table: *mut VMExternRefActivationsTable,
 //      store: *mut dyn Store,
+//      epoch_ptr: *const AtomicU64,
 //      signature_ids: [VMSharedSignatureIndex; module.num_signature_ids],
 //      imported_functions: [VMFunctionImport; module.num_imported_functions],
 //      imported_tables: [VMTableImport; module.num_imported_tables],
@@ -14,6 +15,7 @@
 //      imported_globals: [VMGlobalImport; module.num_imported_globals],
 //      tables: [VMTableDefinition; module.num_defined_tables],
 //      memories: [VMMemoryDefinition; module.num_defined_memories],
+//      memory_pointers: [*mut VMMemoryDefinition; module.num_defined_memories],
 //      globals: [VMGlobalDefinition; module.num_defined_globals],
 //      anyfuncs: [VMCallerCheckedAnyfunc; module.num_imported_functions + module.num_defined_functions],
 //      builtins: VMBuiltinFunctionsArray,
@@ -75,6 +77,7 @@ pub struct VMOffsets<P> {
     interrupts: u32,
     externref_activations_table: u32,
     store: u32,
+    epoch_ptr: u32,
     signature_ids: u32,
     imported_functions: u32,
     imported_tables: u32,
@@ -82,6 +85,7 @@ pub struct VMOffsets<P> {
     imported_globals: u32,
     defined_tables: u32,
     defined_memories: u32,
+    defined_memory_pointers: u32,
     defined_globals: u32,
     defined_anyfuncs: u32,
     builtin_functions: u32,
@@ -176,6 +180,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             interrupts: 0,
             externref_activations_table: 0,
             store: 0,
+            epoch_ptr: 0,
             signature_ids: 0,
             imported_functions: 0,
             imported_tables: 0,
@@ -183,6 +188,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             imported_globals: 0,
             defined_tables: 0,
             defined_memories: 0,
+            defined_memory_pointers: 0,
             defined_globals: 0,
             defined_anyfuncs: 0,
             builtin_functions: 0,
@@ -198,10 +204,14 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             .externref_activations_table
             .checked_add(u32::from(ret.ptr.size()))
             .unwrap();
-        ret.signature_ids = ret
+        ret.epoch_ptr = ret
             .store
             .checked_add(u32::from(ret.ptr.size() * 2))
             .unwrap();
+        ret.signature_ids = ret
+            .epoch_ptr
+            .checked_add(u32::from(ret.ptr.size()))
+            .unwrap();
         ret.imported_functions = ret
             .signature_ids
             .checked_add(
@@ -251,11 +261,19 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
                     .unwrap(),
             )
             .unwrap();
+        ret.defined_memory_pointers = ret
+            .defined_memories
+            .checked_add(
+                ret.num_defined_memories
+                    .checked_mul(u32::from(ret.size_of_vmmemory_definition()))
+                    .unwrap(),
+            )
+            .unwrap();
         ret.defined_globals = align(
-            ret.defined_memories
+            ret.defined_memory_pointers
                 .checked_add(
                     ret.num_defined_memories
-                        .checked_mul(u32::from(ret.size_of_vmmemory_definition()))
+                        .checked_mul(u32::from(ret.size_of_vmmemory_pointer()))
                         .unwrap(),
                 )
                 .unwrap(),
@@ -418,6 +436,12 @@ impl<P: PtrSize> VMOffsets<P> {
     pub fn size_of_vmmemory_definition(&self) -> u8 {
         2 * self.pointer_size()
     }
+
+    /// Return the size of `*mut VMMemoryDefinition`.
+    #[inline]
+    pub fn size_of_vmmemory_pointer(&self) -> u8 {
+        self.pointer_size()
+    }
 }
 
 /// Offsets for `VMGlobalImport`.
@@ -465,9 +489,45 @@ impl<P: PtrSize> VMOffsets<P> {
     }
 
     /// Return the offset of the `fuel_consumed` field of `VMInterrupts`
+    ///
+    /// The 64-bit counters are 8-byte aligned, so on 32-bit targets there's
+    /// padding between this and `stack_limit`.
     #[inline]
     pub fn vminterrupts_fuel_consumed(&self) -> u8 {
-        self.pointer_size()
+        align(u32::from(self.pointer_size()), 8) as u8
+    }
+
+    /// Return the offset of the `epoch_deadline` field of `VMInterrupts`
+    #[inline]
+    pub fn vminterrupts_epoch_deadline(&self) -> u8 {
+        self.vminterrupts_fuel_consumed() + 8
+    }
+
+    /// Return the offset of the `debug_hooks` field of `VMInterrupts`
+    #[inline]
+    pub fn vminterrupts_debug_hooks(&self) -> u8 {
+        self.vminterrupts_epoch_deadline() + 8
+    }
+}
+
+/// Offsets for `VMDebugValue`.
+impl<P: PtrSize> VMOffsets<P> {
+    /// The offset of the `ty` field.
+    #[inline]
+    pub fn vmdebug_value_ty(&self) -> u8 {
+        0
+    }
+
+    /// The offset of the `value` field.
+    #[inline]
+    pub fn vmdebug_value_value(&self) -> u8 {
+        16
+    }
+
+    /// Return the size of `VMDebugValue`.
+    #[inline]
+    pub fn size_of_vmdebug_value(&self) -> u8 {
+        32
     }
 }
 
@@ -520,6 +580,12 @@ impl<P: PtrSize> VMOffsets<P> {
         self.store
     }
 
+    /// The offset of the `*const AtomicU64` epoch counter pointer.
+    #[inline]
+    pub fn vmctx_epoch_ptr(&self) -> u32 {
+        self.epoch_ptr
+    }
+
     /// The offset of the `signature_ids` array.
     #[inline]
     pub fn vmctx_signature_ids_begin(&self) -> u32 {
@@ -564,6 +630,12 @@ impl<P: PtrSize> VMOffsets<P> {
         self.defined_memories
     }
 
+    /// The offset of the `memory_pointers` array.
+    #[inline]
+    pub fn vmctx_memory_pointers_begin(&self) -> u32 {
+        self.defined_memory_pointers
+    }
+
     /// The offset of the `globals` array.
     #[inline]
     pub fn vmctx_globals_begin(&self) -> u32 {
@@ -642,6 +714,18 @@ impl<P: PtrSize> VMOffsets<P> {
         self.vmctx_memories_begin() + index.as_u32() * u32::from(self.size_of_vmmemory_definition())
     }
 
+    /// Return the offset to the `*mut VMMemoryDefinition` index `index`.
+    ///
+    /// For memories owned by the instance this points at the corresponding
+    /// entry in the `memories` array, but for shared memories it points at a
+    /// definition which lives outside of the instance.
+    #[inline]
+    pub fn vmctx_vmmemory_pointer(&self, index: DefinedMemoryIndex) -> u32 {
+        assert_lt!(index.as_u32(), self.num_defined_memories);
+        self.vmctx_memory_pointers_begin()
+            + index.as_u32() * u32::from(self.size_of_vmmemory_pointer())
+    }
+
     /// Return the offset to the `VMGlobalDefinition` index `index`.
     #[inline]
     pub fn vmctx_vmglobal_definition(&self, index: DefinedGlobalIndex) -> u32 {
@@ -40,6 +40,9 @@ pub fn dummy_extern<T>(store: &mut Store<T>, ty: ExternType) -> Result<Extern> {
         ExternType::Func(func_ty) => Extern::Func(dummy_func(store, func_ty)),
         ExternType::Global(global_ty) => Extern::Global(dummy_global(store, global_ty)),
         ExternType::Table(table_ty) => Extern::Table(dummy_table(store, table_ty)?),
+        ExternType::Memory(mem_ty) if mem_ty.is_shared() => {
+            Extern::SharedMemory(dummy_shared_memory(store.engine(), mem_ty)?)
+        }
         ExternType::Memory(mem_ty) => Extern::Memory(dummy_memory(store, mem_ty)?),
         ExternType::Instance(instance_ty) => Extern::Instance(dummy_instance(store, instance_ty)?),
         ExternType::Module(module_ty) => Extern::Module(dummy_module(store.engine(), module_ty)),
@@ -91,6 +94,11 @@ pub fn dummy_memory<T>(store: &mut Store<T>, ty: MemoryType) -> Result<Memory> {
     Memory::new(store, ty)
 }
 
+/// Construct a dummy shared memory for the given memory type.
+pub fn dummy_shared_memory(engine: &Engine, ty: MemoryType) -> Result<SharedMemory> {
+    SharedMemory::new(engine, ty)
+}
+
 /// Construct a dummy instance for the given instance type.
 ///
 /// This is done by using the expected type to generate a module on-the-fly
@@ -153,12 +161,13 @@ impl WatGenerator {
             ExternType::Memory(mem) => {
                 write!(
                     self.dst,
-                    "(memory {} {})",
+                    "(memory {} {} {})",
                     mem.minimum(),
                     match mem.maximum() {
                         Some(max) => max.to_string(),
                         None => String::new(),
-                    }
+                    },
+                    if mem.is_shared() { "shared" } else { "" },
                 )
                 .unwrap();
             }
@@ -241,13 +250,14 @@ impl WatGenerator {
             ExternType::Memory(mem) => {
                 write!(
                     self.dst,
-                    "(memory ${} {} {})\n",
+                    "(memory ${} {} {} {})\n",
                     name,
                     mem.minimum(),
                     match mem.maximum() {
                         Some(max) => max.to_string(),
                         None => String::new(),
-                    }
+                    },
+                    if mem.is_shared() { "shared" } else { "" },
                 )
                 .unwrap();
             }
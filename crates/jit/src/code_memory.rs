@@ -5,29 +5,21 @@ use anyhow::{Context, Result};
 use object::read::{File as ObjectFile, Object, ObjectSection, ObjectSymbol};
 use std::collections::BTreeMap;
 use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::sync::Arc;
 use wasmtime_environ::obj::{try_parse_func_name, try_parse_trampoline_name};
 use wasmtime_environ::{FuncIndex, SignatureIndex};
-use wasmtime_runtime::{Mmap, VMFunctionBody};
+use wasmtime_runtime::{Mmap, MmapVec, VMFunctionBody};
 
 struct CodeMemoryEntry {
-    mmap: ManuallyDrop<Mmap>,
+    mmap: ManuallyDrop<Arc<Mmap>>,
     unwind_registration: ManuallyDrop<Option<UnwindRegistration>>,
-    text_len: usize,
+    /// The range of `mmap` holding the text section. Any unwind information
+    /// immediately follows it.
+    text: Range<usize>,
     unwind_info_len: usize,
 }
 
-impl CodeMemoryEntry {
-    fn new(text_len: usize, unwind_info_len: usize) -> Result<Self> {
-        let mmap = ManuallyDrop::new(Mmap::with_at_least(text_len + unwind_info_len)?);
-        Ok(Self {
-            mmap,
-            unwind_registration: ManuallyDrop::new(None),
-            text_len,
-            unwind_info_len,
-        })
-    }
-}
-
 impl Drop for CodeMemoryEntry {
     fn drop(&mut self) {
         unsafe {
@@ -97,18 +89,16 @@ impl CodeMemory {
     /// Make all allocated memory executable.
     pub fn publish(&mut self) {
         for entry in &mut self.entries[self.published..] {
-            assert!(!entry.mmap.is_empty());
+            assert!(!entry.text.is_empty());
 
             unsafe {
                 // Switch the executable portion from read/write to
                 // read/execute, notably not using read/write/execute to prevent
                 // modifications.
-                region::protect(
-                    entry.mmap.as_mut_ptr(),
-                    entry.text_len,
-                    region::Protection::READ_EXECUTE,
-                )
-                .expect("unable to make memory readonly and executable");
+                entry
+                    .mmap
+                    .make_executable(entry.text.clone())
+                    .expect("unable to make memory readonly and executable");
 
                 if entry.unwind_info_len == 0 {
                     continue;
@@ -120,8 +110,8 @@ impl CodeMemory {
                 // our just-published JIT functions.
                 *entry.unwind_registration = Some(
                     UnwindRegistration::new(
-                        entry.mmap.as_mut_ptr(),
-                        entry.mmap.as_mut_ptr().add(entry.text_len),
+                        entry.mmap.as_mut_ptr().add(entry.text.start),
+                        entry.mmap.as_mut_ptr().add(entry.text.end),
                         entry.unwind_info_len,
                     )
                     .expect("failed to create unwind info registration"),
@@ -179,45 +169,127 @@ impl CodeMemory {
         // Allocate memory for the text section and unwinding information if it
         // is present. Then we can copy in all of the code and unwinding memory
         // over.
-        let entry = CodeMemoryEntry::new(text_section_size, unwind_section_size)?;
-        self.entries.push(entry);
-        let entry = self.entries.last_mut().unwrap();
-        entry.mmap.as_mut_slice()[..text_section_size].copy_from_slice(
+        let mut mmap = Mmap::with_at_least(text_section_size + unwind_section_size)?;
+        mmap.as_mut_slice()[..text_section_size].copy_from_slice(
             text_section
                 .data()
                 .with_context(|| "cannot read text section data")?,
         );
         if let Some(section) = unwind_section {
-            entry.mmap.as_mut_slice()[text_section_size..][..unwind_section_size].copy_from_slice(
+            mmap.as_mut_slice()[text_section_size..][..unwind_section_size].copy_from_slice(
                 section
                     .data()
                     .with_context(|| "cannot read unwind section data")?,
             );
         }
+        self.entries.push(CodeMemoryEntry {
+            mmap: ManuallyDrop::new(Arc::new(mmap)),
+            unwind_registration: ManuallyDrop::new(None),
+            text: 0..text_section_size,
+            unwind_info_len: unwind_section_size,
+        });
+        let entry = self.entries.last().unwrap();
 
-        // Track locations of all defined functions and trampolines.
-        let mut funcs = BTreeMap::new();
-        let mut trampolines = BTreeMap::new();
-        for sym in obj.symbols() {
-            match sym.name() {
-                Ok(name) => {
-                    if let Some(index) = try_parse_func_name(name) {
-                        let is_import = sym.section_index().is_none();
-                        if !is_import {
-                            funcs.insert(index, (sym.address() as usize, sym.size() as usize));
-                        }
-                    } else if let Some(index) = try_parse_trampoline_name(name) {
-                        trampolines.insert(index, (sym.address() as usize, sym.size() as usize));
-                    }
-                }
-                Err(_) => (),
-            }
+        let (funcs, trampolines) = symbol_ranges(obj);
+        Ok(CodeMemoryObjectAllocation {
+            code_range: unsafe {
+                std::slice::from_raw_parts_mut(entry.mmap.as_mut_ptr(), text_section_size)
+            },
+            funcs,
+            trampolines,
+        })
+    }
+
+    /// Like `allocate_for_object`, except that the text section of `obj` is
+    /// used where it sits within `image` rather than being copied.
+    ///
+    /// The `obj` provided must have been parsed from the contents of `image`.
+    /// When the allocation is published the pages of `image` containing the
+    /// text section are made executable in place, and the underlying mapping
+    /// of `image` is kept alive for as long as this `CodeMemory` is.
+    ///
+    /// This requires that the text section starts at a page-aligned address,
+    /// that its size is a multiple of the page size, and that any unwind
+    /// information immediately follows it. Compilation lays out images this
+    /// way for hosts with 4KiB pages, but if these requirements aren't met
+    /// this falls back to copying the text section like `allocate_for_object`.
+    pub fn allocate_for_object_in_place<'a>(
+        &'a mut self,
+        image: &MmapVec,
+        obj: &ObjectFile,
+    ) -> Result<CodeMemoryObjectAllocation<'a>> {
+        let text_section = obj.section_by_name(".text").unwrap();
+        let text = text_section
+            .data()
+            .with_context(|| "cannot read text section data")?;
+        let unwind = match obj.section_by_name(UnwindRegistration::section_name()) {
+            Some(section) => section
+                .data()
+                .with_context(|| "cannot read unwind section data")?,
+            None => &[],
+        };
+
+        let page_size = region::page::size();
+        let image_start = image.as_ptr() as usize;
+        let text_start = text.as_ptr() as usize;
+        assert!(image_start <= text_start && text_start + text.len() <= image_start + image.len());
+        let in_place = !text.is_empty()
+            && text_start % page_size == 0
+            && text.len() % page_size == 0
+            && (unwind.is_empty() || unwind.as_ptr() as usize == text_start + text.len());
+        if !in_place {
+            return self.allocate_for_object(obj);
         }
 
+        let start = image.range().start + (text_start - image_start);
+        self.entries.push(CodeMemoryEntry {
+            mmap: ManuallyDrop::new(image.mmap().clone()),
+            unwind_registration: ManuallyDrop::new(None),
+            text: start..start + text.len(),
+            unwind_info_len: unwind.len(),
+        });
+        let entry = self.entries.last().unwrap();
+
+        let (funcs, trampolines) = symbol_ranges(obj);
         Ok(CodeMemoryObjectAllocation {
-            code_range: &mut entry.mmap.as_mut_slice()[..text_section_size],
+            // Note that `obj` also borrows `image`, but it's only used to read
+            // sections other than the text section while this is alive.
+            code_range: unsafe {
+                std::slice::from_raw_parts_mut(
+                    entry.mmap.as_mut_ptr().add(entry.text.start),
+                    text.len(),
+                )
+            },
             funcs,
             trampolines,
         })
     }
 }
+
+/// Returns the locations, relative to the text section, of all defined
+/// functions and trampolines in `obj`.
+fn symbol_ranges(
+    obj: &ObjectFile,
+) -> (
+    BTreeMap<FuncIndex, (usize, usize)>,
+    BTreeMap<SignatureIndex, (usize, usize)>,
+) {
+    let mut funcs = BTreeMap::new();
+    let mut trampolines = BTreeMap::new();
+    for sym in obj.symbols() {
+        match sym.name() {
+            Ok(name) => {
+                if let Some(index) = try_parse_func_name(name) {
+                    let is_import = sym.section_index().is_none();
+                    if !is_import {
+                        funcs.insert(index, (sym.address() as usize, sym.size() as usize));
+                    }
+                } else if let Some(index) = try_parse_trampoline_name(name) {
+                    trampolines.insert(index, (sym.address() as usize, sym.size() as usize));
+                }
+            }
+            Err(_) => (),
+        }
+    }
+    (funcs, trampolines)
+}
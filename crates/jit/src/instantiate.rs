@@ -11,16 +11,19 @@ use anyhow::{anyhow, Context, Result};
 use object::read::File;
 use object::write::{Object, StandardSegment};
 use object::{Object as _, ObjectSection, SectionKind};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::Range;
 use std::sync::Arc;
 use thiserror::Error;
 use wasmtime_environ::{
     CompileError, DefinedFuncIndex, FunctionInfo, InstanceSignature, InstanceTypeIndex, Module,
-    ModuleSignature, ModuleTranslation, ModuleTypeIndex, PrimaryMap, SignatureIndex,
-    StackMapInformation, Tunables, WasmFuncType, ELF_WASMTIME_ADDRMAP, ELF_WASMTIME_TRAPS,
+    ModuleSignature, ModuleTranslation, ModuleTypeIndex, PrimaryMap, SignatureIndex, Tunables,
+    WasmFuncType, ELF_WASMTIME_ADDRMAP, ELF_WASMTIME_EXCEPTIONS, ELF_WASMTIME_STACK_MAP,
+    ELF_WASMTIME_TRAPS,
+};
+use wasmtime_runtime::{
+    GdbJitImageRegistration, InstantiationError, MmapVec, VMFunctionBody, VMTrampoline,
 };
-use wasmtime_runtime::{GdbJitImageRegistration, InstantiationError, VMFunctionBody, VMTrampoline};
 
 /// This is the name of the section in the final ELF image which contains
 /// concatenated data segments from the original wasm module.
@@ -69,12 +72,28 @@ pub enum SetupError {
 }
 
 /// Final result of compilation which supports serialization to disk.
-#[derive(Serialize, Deserialize)]
 pub struct CompilationArtifacts {
     // NB: this structure is in a transitionary phase and will soon go away. At
-    // this time it only contains the ELF image created by compilation, and in
-    // the near future even this will be removed.
-    obj: Box<[u8]>,
+    // this time it only contains the ELF image created by compilation, which
+    // is stored in an `MmapVec` so its text section can be made executable in
+    // place.
+    obj: MmapVec,
+}
+
+// Artifacts are serialized as a plain list of bytes, which is how the ELF
+// image was encoded before it was stored in an `MmapVec`.
+impl Serialize for CompilationArtifacts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.obj[..].serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CompilationArtifacts {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let obj = MmapVec::from_slice(&bytes).map_err(serde::de::Error::custom)?;
+        Ok(CompilationArtifacts { obj })
+    }
 }
 
 /// Secondary in-memory results of compilation.
@@ -212,7 +231,7 @@ impl CompilationArtifacts {
 
         return Ok((
             CompilationArtifacts {
-                obj: obj.write()?.into(),
+                obj: MmapVec::from_slice(&obj.write()?)?,
             },
             info,
         ));
@@ -233,12 +252,27 @@ impl CompilationArtifacts {
             obj.append_section_data(section_id, data, 1);
         }
     }
+
+    /// Creates artifacts from the ELF image `obj` which was previously
+    /// produced by compilation and written out with [`CompilationArtifacts::obj`].
+    pub fn from_mmap(obj: MmapVec) -> CompilationArtifacts {
+        CompilationArtifacts { obj }
+    }
+
+    /// Returns the ELF image of these artifacts.
+    pub fn obj(&self) -> &[u8] {
+        &self.obj
+    }
 }
 
 struct FinishedFunctions(PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>);
 unsafe impl Send for FinishedFunctions {}
 unsafe impl Sync for FinishedFunctions {}
 
+struct FinishedTrampolines(Vec<(SignatureIndex, *mut [VMFunctionBody])>);
+unsafe impl Send for FinishedTrampolines {}
+unsafe impl Sync for FinishedTrampolines {}
+
 /// This is intended to mirror the type tables in `wasmtime_environ`, except that
 /// it doesn't store the native signatures which are no longer needed past compilation.
 #[derive(Serialize, Deserialize)]
@@ -270,13 +304,17 @@ pub struct CompiledModule {
     wasm_data: Range<usize>,
     address_map_data: Range<usize>,
     trap_data: Range<usize>,
+    stack_map_data: Range<usize>,
+    exception_data: Range<usize>,
     artifacts: CompilationArtifacts,
     module: Arc<Module>,
     funcs: PrimaryMap<DefinedFuncIndex, FunctionInfo>,
     meta: Metadata,
     code: Arc<ModuleCode>,
     finished_functions: FinishedFunctions,
-    trampolines: Vec<(SignatureIndex, VMTrampoline)>,
+    #[cfg(target_arch = "arm")]
+    function_entries: FinishedFunctions,
+    finished_trampolines: FinishedTrampolines,
 }
 
 impl CompiledModule {
@@ -291,10 +329,44 @@ impl CompiledModule {
     ///
     /// The `profiler` argument here is used to inform JIT profiling runtimes
     /// about new code that is loaded.
+    ///
+    /// The text section of the artifacts is copied into freshly allocated
+    /// executable memory, which leaves the artifacts themselves untouched so
+    /// they can be serialized later on.
     pub fn from_artifacts(
         artifacts: CompilationArtifacts,
         info: Option<CompiledModuleInfo>,
         profiler: &dyn ProfilingAgent,
+    ) -> Result<Arc<Self>> {
+        Self::new(artifacts, info, profiler, false)
+    }
+
+    /// Same as `from_artifacts`, except that the text section of `artifacts`
+    /// is linked and made executable where it sits, if possible, instead of
+    /// being copied.
+    ///
+    /// This is intended for artifacts which were just loaded from a serialized
+    /// module, where `artifacts` may be mapped directly from a file on disk.
+    /// Only the pages of the image which are actually used are then read in,
+    /// and the trap, address map, and stack map sections are only consulted
+    /// when needed.
+    ///
+    /// Note that the artifacts of the returned module contain relocated code
+    /// afterwards. Relocation is idempotent so they can still be serialized
+    /// and loaded again, but the serialized bytes will differ between
+    /// processes.
+    pub fn from_artifacts_in_place(
+        artifacts: CompilationArtifacts,
+        profiler: &dyn ProfilingAgent,
+    ) -> Result<Arc<Self>> {
+        Self::new(artifacts, None, profiler, true)
+    }
+
+    fn new(
+        artifacts: CompilationArtifacts,
+        info: Option<CompiledModuleInfo>,
+        profiler: &dyn ProfilingAgent,
+        in_place: bool,
     ) -> Result<Arc<Self>> {
         let obj = File::parse(&artifacts.obj[..])
             .with_context(|| "failed to parse internal ELF compilation artifact")?;
@@ -317,17 +389,27 @@ impl CompiledModule {
         let wasm_data = subslice_range(section(ELF_WASM_DATA)?, &artifacts.obj);
         let address_map_data = subslice_range(section(ELF_WASMTIME_ADDRMAP)?, &artifacts.obj);
         let trap_data = subslice_range(section(ELF_WASMTIME_TRAPS)?, &artifacts.obj);
+        let stack_map_data = subslice_range(section(ELF_WASMTIME_STACK_MAP)?, &artifacts.obj);
+        let exception_data = subslice_range(section(ELF_WASMTIME_EXCEPTIONS)?, &artifacts.obj);
 
         // Allocate all of the compiled functions into executable memory,
-        // copying over their contents.
-        let (code_memory, code_range, finished_functions, trampolines) =
-            build_code_memory(&obj, &module).map_err(|message| {
+        // either copying over their contents or using them in place.
+        let image = if in_place { Some(&artifacts.obj) } else { None };
+        let (code_memory, code_range, finished_functions, finished_trampolines) =
+            build_code_memory(&obj, image, &module).map_err(|message| {
                 SetupError::Instantiate(InstantiationError::Resource(anyhow::anyhow!(
                     "failed to build code memory for functions: {}",
                     message
                 )))
             })?;
 
+        #[cfg(target_arch = "arm")]
+        let function_entries = FinishedFunctions(
+            finished_functions
+                .values()
+                .map(|body| unsafe { VMFunctionBody::entry(*body) })
+                .collect(),
+        );
         let finished_functions = FinishedFunctions(finished_functions);
         let start = code_range.0 as usize;
         let end = start + code_range.1;
@@ -340,13 +422,17 @@ impl CompiledModule {
             wasm_data,
             address_map_data,
             trap_data,
+            stack_map_data,
+            exception_data,
             code: Arc::new(ModuleCode {
                 range: (start, end),
                 code_memory,
                 dbg_jit_registration: None,
             }),
             finished_functions,
-            trampolines,
+            #[cfg(target_arch = "arm")]
+            function_entries,
+            finished_trampolines,
         };
         ret.register_debug_and_profiling(profiler)?;
 
@@ -403,6 +489,21 @@ impl CompiledModule {
         &self.artifacts.obj[self.trap_data.clone()]
     }
 
+    /// Returns the encoded stack map information for this compiled image.
+    ///
+    /// For more information see `wasmtime_environ::lookup_stack_map`.
+    pub fn stack_map_data(&self) -> &[u8] {
+        &self.artifacts.obj[self.stack_map_data.clone()]
+    }
+
+    /// Returns the encoded exception handler information for this compiled
+    /// image.
+    ///
+    /// For more information see `wasmtime_environ::lookup_exception_handler`.
+    pub fn exception_data(&self) -> &[u8] {
+        &self.artifacts.obj[self.exception_data.clone()]
+    }
+
     /// Return a reference-counting pointer to a module.
     pub fn module(&self) -> &Arc<Module> {
         &self.module
@@ -419,23 +520,32 @@ impl CompiledModule {
         &self.finished_functions.0
     }
 
-    /// Returns the per-signature trampolines for this module.
-    pub fn trampolines(&self) -> &[(SignatureIndex, VMTrampoline)] {
-        &self.trampolines
+    /// Returns the addresses to call this module's functions through, which
+    /// are their bodies except on 32-bit ARM; see [`VMFunctionBody::entry`].
+    #[inline]
+    pub fn function_entries(&self) -> &PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]> {
+        #[cfg(target_arch = "arm")]
+        return &self.function_entries.0;
+        #[cfg(not(target_arch = "arm"))]
+        return &self.finished_functions.0;
     }
 
-    /// Returns the stack map information for all functions defined in this
-    /// module.
-    ///
-    /// The iterator returned iterates over the span of the compiled function in
-    /// memory with the stack maps associated with those bytes.
-    pub fn stack_maps(
-        &self,
-    ) -> impl Iterator<Item = (*mut [VMFunctionBody], &[StackMapInformation])> {
-        self.finished_functions()
-            .values()
-            .copied()
-            .zip(self.funcs.values().map(|f| f.stack_maps.as_slice()))
+    /// Returns the bodies of the per-signature trampolines for this module.
+    #[inline]
+    pub fn finished_trampolines(&self) -> &[(SignatureIndex, *mut [VMFunctionBody])] {
+        &self.finished_trampolines.0
+    }
+
+    /// Returns the per-signature trampolines for this module.
+    pub fn trampolines(&self) -> impl Iterator<Item = (SignatureIndex, VMTrampoline)> + '_ {
+        self.finished_trampolines().iter().map(|(i, body)| {
+            let fnptr = unsafe {
+                std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(
+                    (*VMFunctionBody::entry(*body)).as_ptr(),
+                )
+            };
+            (*i, fnptr)
+        })
     }
 
     /// Lookups a defined function by a program counter value.
@@ -549,16 +659,20 @@ impl<'a> SymbolizeContext<'a> {
 
 fn build_code_memory(
     obj: &File,
+    image: Option<&MmapVec>,
     module: &Module,
 ) -> Result<(
     CodeMemory,
     (*const u8, usize),
     PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
-    Vec<(SignatureIndex, VMTrampoline)>,
+    FinishedTrampolines,
 )> {
     let mut code_memory = CodeMemory::new();
 
-    let allocation = code_memory.allocate_for_object(obj)?;
+    let allocation = match image {
+        Some(image) => code_memory.allocate_for_object_in_place(image, obj)?,
+        None => code_memory.allocate_for_object(obj)?,
+    };
 
     // Populate the finished functions from the allocation
     let mut finished_functions = PrimaryMap::with_capacity(allocation.funcs_len());
@@ -583,9 +697,8 @@ fn build_code_memory(
     // Populate the trampolines from the allocation
     let mut trampolines = Vec::with_capacity(allocation.trampolines_len());
     for (i, fat_ptr) in allocation.trampolines() {
-        let fnptr =
-            unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(fat_ptr.as_ptr()) };
-        trampolines.push((i, fnptr));
+        let fat_ptr: *mut [VMFunctionBody] = fat_ptr;
+        trampolines.push((i, fat_ptr));
     }
 
     link_module(obj, allocation.code_range);
@@ -595,7 +708,12 @@ fn build_code_memory(
     // Make all code compiled thus far executable.
     code_memory.publish();
 
-    Ok((code_memory, code_range, finished_functions, trampolines))
+    Ok((
+        code_memory,
+        code_range,
+        finished_functions,
+        FinishedTrampolines(trampolines),
+    ))
 }
 
 /// Returns the range of `inner` within `outer`, such that `outer[range]` is the
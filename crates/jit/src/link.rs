@@ -5,6 +5,7 @@ use object::{elf, File, NativeEndian as NE, ObjectSymbol, RelocationEncoding, Re
 use std::convert::TryFrom;
 use wasmtime_runtime::libcalls;
 
+type U16 = object::U16Bytes<NE>;
 type U32 = object::U32Bytes<NE>;
 type I32 = object::I32Bytes<NE>;
 type U64 = object::U64Bytes<NE>;
@@ -33,7 +34,14 @@ fn apply_reloc(obj: &File, code: &mut [u8], offset: u64, r: Relocation) {
             // wasm function or runtime libcall.
             let sym = obj.symbol_by_index(i).unwrap();
             if sym.is_local() {
-                &code[sym.address() as usize] as *const u8 as usize
+                let addr = &code[sym.address() as usize] as *const u8 as usize;
+                // Code for 32-bit ARM is Thumb-2, whose addresses have their
+                // low bit set so that indirect calls stay in Thumb state.
+                if cfg!(target_arch = "arm") {
+                    addr | 1
+                } else {
+                    addr
+                }
             } else {
                 match sym.name() {
                     Ok(name) => {
@@ -60,6 +68,17 @@ fn apply_reloc(obj: &File, code: &mut [u8], offset: u64, r: Relocation) {
             reloc_address.set(NE, reloc_abs);
         }
         #[cfg(target_pointer_width = "32")]
+        (RelocationKind::Absolute, RelocationEncoding::Generic, 32) => {
+            let reloc_address = reloc_address::<U32>(code, offset);
+            let addend = if r.has_implicit_addend() {
+                reloc_address.get(NE)
+            } else {
+                r.addend() as u32
+            };
+            let reloc_abs = (target_func_address as u32).wrapping_add(addend);
+            reloc_address.set(NE, reloc_abs);
+        }
+        #[cfg(target_pointer_width = "32")]
         (RelocationKind::Relative, RelocationEncoding::Generic, 32) => {
             let reloc_address = reloc_address::<U32>(code, offset);
             let reloc_delta_u32 = (target_func_address as u32)
@@ -116,6 +135,57 @@ fn apply_reloc(obj: &File, code: &mut [u8], offset: u64, r: Relocation) {
             let new_insn = (insn & 0xfc00_0000) | (delta_bits & 0x03ff_ffff);
             reloc_address.set(NE, new_insn);
         }
+        (RelocationKind::Elf(elf::R_ARM_THM_PC22), RelocationEncoding::Generic, _) => {
+            // `R_ARM_THM_CALL`, under its older name: a Thumb-2 `bl`, whose
+            // offset is split across its two halfwords and holds the addend.
+            let hi = u32::from(reloc_address::<U16>(code, offset).get(NE));
+            let lo = u32::from(reloc_address::<U16>(code, offset + 2).get(NE));
+            let s = (hi >> 10) & 1;
+            let i1 = !((lo >> 13) ^ s) & 1;
+            let i2 = !((lo >> 11) ^ s) & 1;
+            let imm =
+                (s << 24) | (i1 << 23) | (i2 << 22) | ((hi & 0x3ff) << 12) | ((lo & 0x7ff) << 1);
+            let addend = ((imm << 7) as i32 >> 7) as i64;
+            let mut pc = &code[offset as usize] as *const u8 as i64;
+            // Compiled code is Thumb, but libcalls may be ARM code, which has
+            // the low bit of its address clear. Those need a `blx`, which is
+            // relative to the word-aligned PC.
+            let lo = if target_func_address & 1 == 0 {
+                pc &= !3;
+                lo & !(1 << 12)
+            } else {
+                lo
+            };
+            let reloc_delta = (target_func_address as i64)
+                .wrapping_sub(pc)
+                .wrapping_add(addend);
+            assert!(reloc_delta >= -(1 << 24) && reloc_delta < (1 << 24));
+            let delta = reloc_delta as u32;
+            let s = (delta >> 24) & 1;
+            let j1 = !((delta >> 23) ^ s) & 1;
+            let j2 = !((delta >> 22) ^ s) & 1;
+            let hi = (hi & 0xf800) | (s << 10) | ((delta >> 12) & 0x3ff);
+            let lo = (lo & 0xd000) | (j1 << 13) | (j2 << 11) | ((delta >> 1) & 0x7ff);
+            reloc_address::<U16>(code, offset).set(NE, hi as u16);
+            reloc_address::<U16>(code, offset + 2).set(NE, lo as u16);
+        }
+        (RelocationKind::Elf(elf::R_RISCV_CALL_PLT), RelocationEncoding::Generic, _) => {
+            // An `auipc` + `jalr` pair: the upper 20 bits of the delta go in
+            // the `auipc` and the sign-extended lower 12 bits in the `jalr`.
+            let auipc_address = reloc_address::<U32>(code, offset);
+            let reloc_delta = (target_func_address as i64)
+                .wrapping_sub(auipc_address as *const _ as i64)
+                .wrapping_add(r.addend());
+            let reloc_delta =
+                i32::try_from(reloc_delta).expect("relocation too large to fit in i32");
+            let hi20 = (reloc_delta as u32).wrapping_add(0x800) & 0xffff_f000;
+            let lo12 = (reloc_delta as u32).wrapping_sub(hi20) & 0xfff;
+            let auipc = auipc_address.get(NE);
+            auipc_address.set(NE, (auipc & 0xfff) | hi20);
+            let jalr_address = reloc_address::<U32>(code, offset + 4);
+            let jalr = jalr_address.get(NE);
+            jalr_address.set(NE, (jalr & 0x000f_ffff) | (lo12 << 20));
+        }
         other => panic!("unsupported reloc kind: {:?}", other),
     }
 }
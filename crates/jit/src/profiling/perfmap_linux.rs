@@ -0,0 +1,79 @@
+//! Support for `perf` map files, which let `perf` symbolize jitted code.
+//!
+//! A map file is a plain text file at `/tmp/perf-<pid>.map` with one line per
+//! jitted function, listing its start address, size and name in that order,
+//! with the address and size in hexadecimal. `perf` picks up the file
+//! automatically when reporting, unlike jitdump no `perf inject` step is
+//! needed, but there's no source line information either.
+//!
+//! Usage Example:
+//!     Record
+//!         perf record -g target/debug/wasmtime --perfmap test.wasm
+//!     Report
+//!         perf report
+
+use crate::{CompiledModule, ProfilingAgent};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::sync::Mutex;
+use wasmtime_environ::EntityRef;
+
+/// Interface for driving the creation of `perf` map files.
+pub struct PerfMapAgent {
+    file: Mutex<File>,
+}
+
+impl PerfMapAgent {
+    /// Initialize a PerfMapAgent, creating the map file for this process.
+    pub fn new() -> Result<Self> {
+        let filename = format!("/tmp/perf-{}.map", process::id());
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&filename)
+            .with_context(|| format!("failed to open perf map file {}", filename))?;
+        Ok(PerfMapAgent {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn write_module(file: &File, module: &CompiledModule) -> io::Result<()> {
+        // Buffer all of the module's lines so they're written out together,
+        // as `perf` may read the file at any time.
+        let mut writer = BufWriter::new(file);
+        for (idx, func) in module.finished_functions() {
+            let (addr, len) = unsafe { ((**func).as_ptr() as *const u8, (**func).len()) };
+            let name = super::debug_name(module.module(), idx);
+            Self::write_line(&mut writer, addr, len, &name)?;
+        }
+        for (sig, body) in module.finished_trampolines() {
+            let (addr, len) = unsafe { ((**body).as_ptr() as *const u8, (**body).len()) };
+            let name = format!("wasm::trampoline[{}]", sig.index());
+            Self::write_line(&mut writer, addr, len, &name)?;
+        }
+        writer.flush()
+    }
+
+    fn write_line(
+        writer: &mut impl Write,
+        addr: *const u8,
+        len: usize,
+        name: &str,
+    ) -> io::Result<()> {
+        // Names are the remainder of the line, so they may contain spaces
+        // but must not span multiple lines.
+        let name = name.replace(|c| c == '\n' || c == '\r', " ");
+        writeln!(writer, "{:x} {:x} {}", addr as usize, len, name)
+    }
+}
+
+impl ProfilingAgent for PerfMapAgent {
+    fn module_load(&self, module: &CompiledModule, _dbg_image: Option<&[u8]>) {
+        let file = self.file.lock().unwrap();
+        if let Err(err) = Self::write_module(&file, module) {
+            log::warn!("PerfMap: module_load failed writing perf map: {:?}", err);
+        }
+    }
+}
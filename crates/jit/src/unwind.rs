@@ -5,6 +5,9 @@ cfg_if::cfg_if! {
     } else if #[cfg(all(windows, target_arch = "x86"))] {
         mod winx32;
         pub use self::winx32::*;
+    } else if #[cfg(all(unix, target_arch = "arm"))] {
+        mod arm;
+        pub use self::arm::*;
     } else if #[cfg(unix)] {
         mod systemv;
         pub use self::systemv::*;
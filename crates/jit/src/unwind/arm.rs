@@ -0,0 +1,27 @@
+//! Module for unwind registration on 32-bit ARM.
+
+use anyhow::Result;
+
+/// Represents a registration of function unwind information on 32-bit ARM.
+///
+/// The system unwinder on 32-bit ARM Linux uses the ARM EHABI tables rather
+/// than `.eh_frame`, and libgcc has no `__register_frame` there, so the
+/// `.eh_frame` that Cranelift generates isn't registered. Backtraces and stack
+/// walks therefore stop at the youngest Wasm frame.
+pub struct UnwindRegistration {}
+
+impl UnwindRegistration {
+    /// Accepts precompiled unwinding information without registering it; see
+    /// the type's documentation.
+    pub unsafe fn new(
+        _base_address: *mut u8,
+        _unwind_info: *mut u8,
+        _unwind_len: usize,
+    ) -> Result<UnwindRegistration> {
+        Ok(UnwindRegistration {})
+    }
+
+    pub fn section_name() -> &'static str {
+        "_wasmtime_eh_frame"
+    }
+}
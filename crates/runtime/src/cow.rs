@@ -0,0 +1,445 @@
+//! Copy-on-write initialization support: creation of backing images for
+//! modules, and logic to support mapping these backing images into memory.
+//!
+//! A module whose linear memories are initialized with `Paged` memory
+//! initialization has the initial contents of each memory known ahead of
+//! time. Those contents are written once into an in-memory file (a `memfd`)
+//! and every instantiation then maps that file copy-on-write into the linear
+//! memory instead of copying each data segment. Pages that are never written
+//! are shared between all instances, and resetting a memory for reuse is a
+//! single `madvise` which throws away any private copies of modified pages.
+
+use crate::InstantiationError;
+use anyhow::{bail, Context, Result};
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::Arc;
+use wasmtime_environ::{DefinedMemoryIndex, MemoryInitialization, Module, PrimaryMap};
+
+const WASM_PAGE_SIZE: u64 = wasmtime_environ::WASM_PAGE_SIZE as u64;
+
+/// Backing images for the defined memories of a module.
+///
+/// This is built once, when a module is loaded or compiled, and then used
+/// for every instantiation of that module.
+#[derive(Debug)]
+pub struct ModuleMemoryImages {
+    memories: PrimaryMap<DefinedMemoryIndex, Option<Arc<MemoryImage>>>,
+}
+
+impl ModuleMemoryImages {
+    /// Creates the memory images for `module`, whose data segments are
+    /// stored in `wasm_data`.
+    ///
+    /// Returns `Ok(None)` if the module's memory initialization isn't
+    /// suitable for copy-on-write images, in which case memories are
+    /// initialized by copying data as usual.
+    pub fn new(module: &Module, wasm_data: &[u8]) -> Result<Option<ModuleMemoryImages>> {
+        let map = match &module.memory_initialization {
+            MemoryInitialization::Paged { map, .. } => map,
+            MemoryInitialization::Segmented(_) => return Ok(None),
+        };
+
+        let mut memories = PrimaryMap::with_capacity(map.len());
+        for (defined_index, pages) in map {
+            let index = module.memory_index(defined_index);
+            // Shared memories aren't owned by any one instance, so they are
+            // always initialized by copying; memories without any data don't
+            // need an image at all since they start out zeroed.
+            let image = if module.memory_plans[index].memory.shared || pages.is_empty() {
+                None
+            } else {
+                Some(Arc::new(MemoryImage::new(pages, wasm_data)?))
+            };
+            memories.push(image);
+        }
+        Ok(Some(ModuleMemoryImages { memories }))
+    }
+
+    /// Returns the image for the given defined memory, if it has one.
+    pub fn get_memory_image(&self, defined_index: DefinedMemoryIndex) -> Option<&Arc<MemoryImage>> {
+        self.memories[defined_index].as_ref()
+    }
+}
+
+/// The initial contents of one linear memory, backed by a file which can be
+/// mapped copy-on-write into a linear memory.
+#[derive(Debug)]
+pub struct MemoryImage {
+    /// The file holding the image's contents. This is currently always an
+    /// anonymous in-memory file created with `memfd_create`.
+    fd: File,
+    /// Length of the image, in bytes. This is always a multiple of the wasm
+    /// page size and so also of the host page size.
+    len: usize,
+    /// Offset within `fd` at which the image starts. Always page-aligned.
+    fd_offset: u64,
+}
+
+impl MemoryImage {
+    fn new(pages: &[(u64, std::ops::Range<u32>)], wasm_data: &[u8]) -> Result<MemoryImage> {
+        // Pages are sorted by their page index, so the last page determines
+        // the length of the image. Pages which aren't present are holes in
+        // the file and read as zeros.
+        let last = pages.last().unwrap().0;
+        let len = (last + 1)
+            .checked_mul(WASM_PAGE_SIZE)
+            .and_then(|len| usize::try_from(len).ok())
+            .context("memory image is too large")?;
+
+        let fd = create_memfd()?;
+        fd.set_len(len as u64)
+            .context("failed to set the length of a memory image")?;
+        for (page_index, range) in pages {
+            let data = &wasm_data[range.start as usize..range.end as usize];
+            fd.write_all_at(data, page_index * WASM_PAGE_SIZE)
+                .context("failed to write to a memory image")?;
+        }
+
+        // Seal the file so that its contents can no longer change, which
+        // guarantees that every instance mapping it sees the same data.
+        unsafe {
+            let seals = libc::F_SEAL_GROW | libc::F_SEAL_SHRINK | libc::F_SEAL_WRITE;
+            if libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, seals) != 0 {
+                bail!(
+                    "failed to seal memory image: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+
+        Ok(MemoryImage {
+            fd,
+            len,
+            fd_offset: 0,
+        })
+    }
+
+    /// Returns the length of this image, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Maps this image copy-on-write at `base`, replacing whatever was
+    /// mapped there before.
+    ///
+    /// # Safety
+    ///
+    /// The `len()` bytes starting at `base` must be a reserved region of
+    /// memory which isn't in use by anything else.
+    pub(crate) unsafe fn map_at(&self, base: *mut u8) -> Result<()> {
+        let ptr = libc::mmap(
+            base as *mut libc::c_void,
+            self.len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            self.fd.as_raw_fd(),
+            self.fd_offset as libc::off_t,
+        );
+        if ptr as *mut u8 != base {
+            bail!(
+                "failed to map memory image: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn create_memfd() -> Result<File> {
+    let name = CStr::from_bytes_with_nul(b"wasm-memory-image\0").unwrap();
+    let fd =
+        unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        bail!(
+            "failed to create memory image: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// A single slot of a static linear memory which may have a memory image
+/// mapped into it.
+///
+/// The slot remembers which image is currently mapped so that it can be
+/// reused without any remapping if the next instantiation uses the same
+/// image, which is the common case for a pooling allocator that serves many
+/// instances of the same module.
+///
+/// The slot is "dirty" while a memory is using it. Before it can be used
+/// again it must be reset with `clear_and_remain_ready`, which discards all
+/// modifications with `madvise` so that the image's pages read as their
+/// original contents and all other pages read as zeros.
+#[derive(Debug)]
+pub struct MemoryImageSlot {
+    /// The base address of the slot.
+    base: usize,
+    /// The total size of the slot, in bytes. Everything beyond `accessible`
+    /// is mapped `PROT_NONE`.
+    static_size: usize,
+    /// The image currently mapped at the start of the slot, if any.
+    image: Option<Arc<MemoryImage>>,
+    /// The number of bytes from the start of the slot which are currently
+    /// readable and writable.
+    accessible: usize,
+    /// Whether the slot is in use and may contain modified pages.
+    dirty: bool,
+    /// Whether the slot should be reset to fresh `PROT_NONE` memory when it
+    /// is dropped. This is disabled when the underlying region is going away
+    /// anyway.
+    clear_on_drop: bool,
+}
+
+impl MemoryImageSlot {
+    /// Creates a new slot for the `static_size` bytes starting at `base`.
+    ///
+    /// The region is assumed to be reserved and entirely inaccessible.
+    /// Returns `None` if copy-on-write memory images aren't supported on this
+    /// platform.
+    pub(crate) fn create(base: *mut u8, static_size: usize) -> Option<Self> {
+        Some(MemoryImageSlot {
+            base: base as usize,
+            static_size,
+            image: None,
+            accessible: 0,
+            dirty: false,
+            clear_on_drop: true,
+        })
+    }
+
+    /// Prevents this slot from resetting its region when it's dropped.
+    pub(crate) fn no_clear_on_drop(&mut self) {
+        self.clear_on_drop = false;
+    }
+
+    /// Prepares this slot for a new memory of `initial_size_bytes` bytes
+    /// whose initial contents are `maybe_image`.
+    pub(crate) fn instantiate(
+        &mut self,
+        initial_size_bytes: usize,
+        maybe_image: Option<&Arc<MemoryImage>>,
+    ) -> Result<(), InstantiationError> {
+        assert!(!self.dirty);
+        assert!(initial_size_bytes <= self.static_size);
+
+        let same_image = match (&self.image, maybe_image) {
+            (Some(current), Some(new)) => Arc::ptr_eq(current, new),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same_image {
+            // Get rid of the previous image, if any, by replacing the whole
+            // slot with fresh inaccessible memory.
+            if self.image.is_some() {
+                self.reset_with_anon_memory()
+                    .map_err(InstantiationError::Resource)?;
+                self.image = None;
+                self.accessible = 0;
+            }
+            if let Some(image) = maybe_image {
+                assert!(image.len <= initial_size_bytes);
+                unsafe {
+                    image
+                        .map_at(self.base as *mut u8)
+                        .map_err(InstantiationError::Resource)?;
+                }
+                self.image = Some(image.clone());
+            }
+        }
+
+        // Adjust the accessible region to the initial size of this memory.
+        if initial_size_bytes > self.accessible {
+            self.protect(
+                self.accessible,
+                initial_size_bytes - self.accessible,
+                region::Protection::READ_WRITE,
+            )
+            .map_err(InstantiationError::Resource)?;
+        } else if initial_size_bytes < self.accessible {
+            self.protect(
+                initial_size_bytes,
+                self.accessible - initial_size_bytes,
+                region::Protection::NONE,
+            )
+            .map_err(InstantiationError::Resource)?;
+        }
+        self.accessible = initial_size_bytes;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Makes the first `size_bytes` of this slot accessible, used when the
+    /// memory in this slot grows.
+    pub(crate) fn set_heap_limit(&mut self, size_bytes: usize) -> Result<()> {
+        assert!(size_bytes <= self.static_size);
+        if size_bytes > self.accessible {
+            self.protect(
+                self.accessible,
+                size_bytes - self.accessible,
+                region::Protection::READ_WRITE,
+            )?;
+            self.accessible = size_bytes;
+        }
+        Ok(())
+    }
+
+    /// Returns whether an image is mapped into this slot, meaning that the
+    /// memory using it doesn't need to be initialized any further.
+    pub(crate) fn has_image(&self) -> bool {
+        self.image.is_some()
+    }
+
+    /// Returns whether this slot is in use and needs to be cleared before it
+    /// can be reused.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Discards all modifications made to this slot so it's ready to be
+    /// instantiated again.
+    ///
+    /// The image, if any, stays mapped and the accessible region is left as
+    /// is; both are adjusted by the next `instantiate` if necessary.
+    pub(crate) fn clear_and_remain_ready(&mut self) -> Result<()> {
+        assert!(self.dirty);
+        if self.accessible > 0 {
+            unsafe {
+                // For the private file mapping of the image this restores the
+                // pages to the contents of the file, and all other pages will
+                // read as zeros on their next access.
+                if libc::madvise(
+                    self.base as *mut libc::c_void,
+                    self.accessible,
+                    libc::MADV_DONTNEED,
+                ) != 0
+                {
+                    bail!(
+                        "madvise failed to reset memory image slot: {}",
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn protect(&self, offset: usize, len: usize, protection: region::Protection) -> Result<()> {
+        unsafe {
+            region::protect((self.base + offset) as *const u8, len, protection)
+                .context("failed to change the protection of a memory image slot")
+        }
+    }
+
+    fn reset_with_anon_memory(&self) -> Result<()> {
+        if self.static_size == 0 {
+            return Ok(());
+        }
+        unsafe {
+            let ptr = libc::mmap(
+                self.base as *mut libc::c_void,
+                self.static_size,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+            if ptr as usize != self.base {
+                bail!(
+                    "mmap failed to reset memory image slot: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemoryImageSlot {
+    fn drop(&mut self) {
+        // If the slot is dropped without being returned to its owner, for
+        // example because instantiation failed, then the region is put back
+        // into the state that a freshly created slot expects. There's not
+        // much we can do if that fails and the region can no longer be
+        // trusted, so panic.
+        if self.clear_on_drop {
+            self.reset_with_anon_memory()
+                .expect("failed to reset memory image slot");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryImage, MemoryImageSlot};
+    use crate::mmap::Mmap;
+    use std::sync::Arc;
+
+    const PAGE: usize = wasmtime_environ::WASM_PAGE_SIZE as usize;
+
+    fn image(pages: &[(u64, u8)]) -> Arc<MemoryImage> {
+        let mut data = Vec::new();
+        let mut list = Vec::new();
+        for (index, byte) in pages {
+            let start = data.len() as u32;
+            data.extend(std::iter::repeat(*byte).take(PAGE));
+            list.push((*index, start..data.len() as u32));
+        }
+        Arc::new(MemoryImage::new(&list, &data).unwrap())
+    }
+
+    #[test]
+    fn instantiate_and_clear() {
+        let mmap = Mmap::accessible_reserved(0, 4 * PAGE).unwrap();
+        let mut slot = MemoryImageSlot::create(mmap.as_mut_ptr(), 4 * PAGE).unwrap();
+        slot.no_clear_on_drop();
+        let image = image(&[(1, 0xaa)]);
+
+        slot.instantiate(2 * PAGE, Some(&image)).unwrap();
+        assert!(slot.has_image());
+        let memory = unsafe { std::slice::from_raw_parts_mut(mmap.as_mut_ptr(), 3 * PAGE) };
+        assert_eq!(memory[0], 0);
+        assert_eq!(memory[PAGE], 0xaa);
+        memory[0] = 1;
+        memory[PAGE] = 2;
+
+        // Growing makes the next page accessible as zeros.
+        slot.set_heap_limit(3 * PAGE).unwrap();
+        memory[2 * PAGE] = 3;
+
+        // Clearing restores the image and zeroes everything else.
+        slot.clear_and_remain_ready().unwrap();
+        assert!(!slot.is_dirty());
+        slot.instantiate(3 * PAGE, Some(&image)).unwrap();
+        assert_eq!(memory[0], 0);
+        assert_eq!(memory[PAGE], 0xaa);
+        assert_eq!(memory[2 * PAGE], 0);
+        slot.clear_and_remain_ready().unwrap();
+    }
+
+    #[test]
+    fn switch_images() {
+        let mmap = Mmap::accessible_reserved(0, 4 * PAGE).unwrap();
+        let mut slot = MemoryImageSlot::create(mmap.as_mut_ptr(), 4 * PAGE).unwrap();
+        slot.no_clear_on_drop();
+        let memory = unsafe { std::slice::from_raw_parts_mut(mmap.as_mut_ptr(), 2 * PAGE) };
+
+        slot.instantiate(2 * PAGE, Some(&image(&[(0, 1), (1, 2)])))
+            .unwrap();
+        assert_eq!((memory[0], memory[PAGE]), (1, 2));
+        slot.clear_and_remain_ready().unwrap();
+
+        slot.instantiate(2 * PAGE, Some(&image(&[(1, 3)]))).unwrap();
+        assert_eq!((memory[0], memory[PAGE]), (0, 3));
+        slot.clear_and_remain_ready().unwrap();
+
+        slot.instantiate(PAGE, None).unwrap();
+        assert!(!slot.has_image());
+        assert_eq!(memory[0], 0);
+        slot.clear_and_remain_ready().unwrap();
+    }
+}
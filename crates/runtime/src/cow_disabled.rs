@@ -0,0 +1,86 @@
+//! Shims for copy-on-write memory images when they are not supported, either
+//! because the `memory-init-cow` feature is disabled or because the platform
+//! doesn't support them.
+//!
+//! None of these types can ever be constructed, so memories are always
+//! initialized by copying their data segments.
+
+#![allow(dead_code)]
+
+use crate::InstantiationError;
+use anyhow::Result;
+use std::sync::Arc;
+use wasmtime_environ::{DefinedMemoryIndex, Module};
+
+/// Backing images for the defined memories of a module.
+#[derive(Debug)]
+pub enum ModuleMemoryImages {}
+
+impl ModuleMemoryImages {
+    /// Always returns `Ok(None)` since images aren't supported.
+    pub fn new(_module: &Module, _wasm_data: &[u8]) -> Result<Option<ModuleMemoryImages>> {
+        Ok(None)
+    }
+
+    /// Returns the image for the given defined memory, if it has one.
+    pub fn get_memory_image(
+        &self,
+        _defined_index: DefinedMemoryIndex,
+    ) -> Option<&Arc<MemoryImage>> {
+        match *self {}
+    }
+}
+
+/// The initial contents of one linear memory.
+#[derive(Debug)]
+pub enum MemoryImage {}
+
+impl MemoryImage {
+    /// Returns the length of this image, in bytes.
+    pub fn len(&self) -> usize {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn map_at(&self, _base: *mut u8) -> Result<()> {
+        match *self {}
+    }
+}
+
+/// A single slot of a static linear memory which may have a memory image
+/// mapped into it.
+#[derive(Debug)]
+pub enum MemoryImageSlot {}
+
+impl MemoryImageSlot {
+    pub(crate) fn create(_base: *mut u8, _static_size: usize) -> Option<Self> {
+        None
+    }
+
+    pub(crate) fn no_clear_on_drop(&mut self) {
+        match *self {}
+    }
+
+    pub(crate) fn instantiate(
+        &mut self,
+        _initial_size_bytes: usize,
+        _maybe_image: Option<&Arc<MemoryImage>>,
+    ) -> Result<(), InstantiationError> {
+        match *self {}
+    }
+
+    pub(crate) fn set_heap_limit(&mut self, _size_bytes: usize) -> Result<()> {
+        match *self {}
+    }
+
+    pub(crate) fn has_image(&self) -> bool {
+        match *self {}
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        match *self {}
+    }
+
+    pub(crate) fn clear_and_remain_ready(&mut self) -> Result<()> {
+        match *self {}
+    }
+}
@@ -0,0 +1,89 @@
+//! Runtime state for the exception handling proposal.
+//!
+//! A thrown exception is recorded here as pending, and then the native stack
+//! is unwound with `raise_exception` to the landing pad of the youngest wasm
+//! frame whose call has one, according to the exception tables that are
+//! compiled alongside each module. The landing pad inspects the pending
+//! exception to decide which `catch` clause takes it, or resumes unwinding if
+//! none does. When unwinding reaches the host with the exception still pending
+//! it's reported as a trap.
+
+use crate::vmcontext::VMSharedSignatureIndex;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// An exception thrown by wasm or by the host.
+#[derive(Debug, Clone)]
+pub struct VMException {
+    /// The store-unique identifier of the exception's tag.
+    pub tag: u32,
+    /// The values of the exception's payload, one for each parameter of the
+    /// tag's signature, in the same representation as `VMInvokeArgument`.
+    pub payload: Box<[u128]>,
+}
+
+/// The state of the exceptions within a store.
+#[derive(Debug, Default)]
+pub struct ExceptionState {
+    /// The signature of each tag allocated in this store, indexed by the tag's
+    /// identifier.
+    tags: Vec<VMSharedSignatureIndex>,
+
+    /// The exception that is currently propagating, if any.
+    pending: Option<VMException>,
+
+    /// Exceptions caught by `catch` and `catch_all` clauses, which may be
+    /// rethrown later on.
+    ///
+    /// Compiled code identifies a caught exception by the address of the
+    /// stack slot its payload is written to, so an entry here is replaced as
+    /// soon as that slot is reused by a later catch.
+    caught: HashMap<usize, VMException>,
+}
+
+impl ExceptionState {
+    /// Allocates a new tag with the given signature, returning its identifier.
+    pub fn allocate_tag(&mut self, signature: VMSharedSignatureIndex) -> u32 {
+        let id = u32::try_from(self.tags.len()).expect("too many tags allocated");
+        self.tags.push(signature);
+        id
+    }
+
+    /// Returns the signature of the tag `id`, which must have been allocated
+    /// by `allocate_tag`.
+    pub fn tag_signature(&self, id: u32) -> VMSharedSignatureIndex {
+        self.tags[id as usize]
+    }
+
+    /// Returns the exception that's currently propagating, if any.
+    pub fn pending(&self) -> Option<&VMException> {
+        self.pending.as_ref()
+    }
+
+    /// Makes `exception` the pending exception, replacing any other one.
+    pub fn throw(&mut self, exception: VMException) {
+        debug_assert!((exception.tag as usize) < self.tags.len());
+        self.pending = Some(exception);
+    }
+
+    /// Takes the pending exception, if any, so that it's no longer pending.
+    pub fn take_pending(&mut self) -> Option<VMException> {
+        self.pending.take()
+    }
+
+    /// Takes the pending exception on behalf of a catch clause and remembers
+    /// it under `handle` so that it can be rethrown.
+    pub(crate) fn catch(&mut self, handle: usize) -> &VMException {
+        let exception = self
+            .take_pending()
+            .expect("caught an exception while none is pending");
+        self.caught.insert(handle, exception);
+        &self.caught[&handle]
+    }
+
+    /// Makes the exception caught under `handle` pending again.
+    pub(crate) fn rethrow(&mut self, handle: usize) {
+        let exception = self.caught[&handle].clone();
+        self.throw(exception);
+    }
+}
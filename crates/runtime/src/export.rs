@@ -1,5 +1,6 @@
 use crate::vmcontext::{
-    VMCallerCheckedAnyfunc, VMContext, VMGlobalDefinition, VMMemoryDefinition, VMTableDefinition,
+    VMCallerCheckedAnyfunc, VMContext, VMGlobalDefinition, VMMemoryDefinition,
+    VMSharedSignatureIndex, VMTableDefinition,
 };
 use std::ptr::NonNull;
 use wasmtime_environ::{Global, MemoryPlan, TablePlan};
@@ -17,6 +18,9 @@ pub enum Export {
 
     /// A global export value.
     Global(ExportGlobal),
+
+    /// An exception tag export value.
+    Tag(ExportTag),
 }
 
 /// A function export value.
@@ -104,3 +108,19 @@ impl From<ExportGlobal> for Export {
         Export::Global(func)
     }
 }
+
+/// An exception tag export value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExportTag {
+    /// The identifier of the tag, unique within its store.
+    pub id: u32,
+    /// The signature of the tag, whose parameters are the payload of the
+    /// exceptions it tags.
+    pub signature: VMSharedSignatureIndex,
+}
+
+impl From<ExportTag> for Export {
+    fn from(tag: ExportTag) -> Export {
+        Export::Tag(tag)
+    }
+}
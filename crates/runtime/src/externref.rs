@@ -754,7 +754,16 @@ pub trait ModuleInfoLookup {
 /// Used by the runtime to query module information.
 pub trait ModuleInfo {
     /// Lookup the stack map at a program counter value.
-    fn lookup_stack_map(&self, pc: usize) -> Option<&StackMap>;
+    ///
+    /// Stack maps are decoded from the module's compilation image on demand,
+    /// so the returned value is owned.
+    fn lookup_stack_map(&self, pc: usize) -> Option<StackMap>;
+
+    /// Lookup the landing pad of the call whose return address is `pc`.
+    ///
+    /// Returns the address of the landing pad, along with the distance from
+    /// the frame pointer down to the stack pointer that it expects.
+    fn lookup_exception_handler(&self, pc: usize) -> Option<(usize, usize)>;
 }
 
 #[derive(Debug, Default)]
@@ -866,35 +875,46 @@ pub unsafe fn gc(
         });
     }
 
+    // The stack map of the previous (younger) Wasm frame, if we have yet to
+    // scan it. A Wasm callee that received more stack arguments than its
+    // caller passed, or that was reached by a tail call, moves its frame
+    // record, so the SP the unwinder reports for its caller is not the SP the
+    // caller's stack map is relative to. The caller's frame pointer is always
+    // right though: it is two words below the caller's CFA, which is the SP
+    // reported for the next older frame. Therefore we scan each Wasm frame
+    // once we reach the frame after it, and find its SP from its frame
+    // pointer. On s390x, where Wasm frames have no frame pointer and tail
+    // calls are unsupported, we use the reported SP directly.
+    let mut pending_stack_map: Option<StackMap> = None;
+
     backtrace::trace(|frame| {
         let pc = frame.ip() as usize;
         let sp = frame.sp() as usize;
 
+        if let Some(stack_map) = pending_stack_map.take() {
+            let fp = sp - 2 * mem::size_of::<usize>();
+            let sp = fp - stack_map.mapped_words() as usize * mem::size_of::<usize>();
+            scan_stack_map(
+                sp,
+                &stack_map,
+                &activations_table_set,
+                &mut externref_activations_table.precise_stack_roots,
+            );
+        }
+
         if let Some(module_info) = module_info_lookup.lookup(pc) {
             if let Some(stack_map) = module_info.lookup_stack_map(pc) {
                 debug_assert!(sp != 0, "we should always get a valid SP for Wasm frames");
 
-                for i in 0..(stack_map.mapped_words() as usize) {
-                    if stack_map.get_bit(i) {
-                        // Stack maps have one bit per word in the frame, and the
-                        // zero^th bit is the *lowest* addressed word in the frame,
-                        // i.e. the closest to the SP. So to get the `i`^th word in
-                        // this frame, we add `i * sizeof(word)` to the SP.
-                        let ptr_to_ref = sp + i * mem::size_of::<usize>();
-
-                        let r = std::ptr::read(ptr_to_ref as *const *mut VMExternData);
-                        debug_assert!(
-                            r.is_null() || activations_table_set.contains(&r),
-                            "every on-stack externref inside a Wasm frame should \
-                            have an entry in the VMExternRefActivationsTable"
-                        );
-                        if let Some(r) = NonNull::new(r) {
-                            VMExternRefActivationsTable::insert_precise_stack_root(
-                                &mut externref_activations_table.precise_stack_roots,
-                                r,
-                            );
-                        }
-                    }
+                if cfg!(target_arch = "s390x") {
+                    scan_stack_map(
+                        sp,
+                        &stack_map,
+                        &activations_table_set,
+                        &mut externref_activations_table.precise_stack_roots,
+                    );
+                } else {
+                    pending_stack_map = Some(stack_map);
                 }
             }
         }
@@ -929,6 +949,35 @@ pub unsafe fn gc(
     log::debug!("end GC");
 }
 
+/// Inserts the references in the Wasm frame whose SP is `sp`, as described by
+/// `stack_map`, into `precise_stack_roots`.
+unsafe fn scan_stack_map(
+    sp: usize,
+    stack_map: &StackMap,
+    activations_table_set: &DebugOnly<HashSet<*mut VMExternData>>,
+    precise_stack_roots: &mut HashSet<VMExternRefWithTraits>,
+) {
+    for i in 0..(stack_map.mapped_words() as usize) {
+        if stack_map.get_bit(i) {
+            // Stack maps have one bit per word in the frame, and the
+            // zero^th bit is the *lowest* addressed word in the frame,
+            // i.e. the closest to the SP. So to get the `i`^th word in
+            // this frame, we add `i * sizeof(word)` to the SP.
+            let ptr_to_ref = sp + i * mem::size_of::<usize>();
+
+            let r = std::ptr::read(ptr_to_ref as *const *mut VMExternData);
+            debug_assert!(
+                r.is_null() || activations_table_set.contains(&r),
+                "every on-stack externref inside a Wasm frame should \
+                have an entry in the VMExternRefActivationsTable"
+            );
+            if let Some(r) = NonNull::new(r) {
+                VMExternRefActivationsTable::insert_precise_stack_root(precise_stack_roots, r);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
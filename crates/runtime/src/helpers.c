@@ -1,4 +1,5 @@
 #include <setjmp.h>
+#include <stdlib.h>
 
 // Note that `sigsetjmp` and `siglongjmp` are used here where possible to
 // explicitly pass a 0 argument to `sigsetjmp` that we don't need to preserve
@@ -32,3 +33,40 @@ void wasmtime_longjmp(void *JmpBuf) {
   platform_jmp_buf *buf = (platform_jmp_buf*) JmpBuf;
   platform_longjmp(*buf, 1);
 }
+
+// Resumes execution at `pc` with the stack and frame pointers set to `sp` and
+// `fp`, which is how the unwinder transfers control to the landing pad of a
+// wasm frame when an exception is thrown. Everything on the stack below `sp`
+// is discarded, and the landing pad assumes nothing about other registers.
+void wasmtime_resume(void *pc, void *sp, void *fp) {
+#if defined(__x86_64__)
+  __asm__ volatile(
+      "mov %1, %%rsp\n"
+      "mov %2, %%rbp\n"
+      "jmp *%0\n"
+      :
+      : "a"(pc), "c"(sp), "d"(fp));
+#elif defined(__aarch64__)
+  register void *x9 __asm__("x9") = pc;
+  register void *x10 __asm__("x10") = sp;
+  register void *x11 __asm__("x11") = fp;
+  __asm__ volatile(
+      "mov sp, x10\n"
+      "mov x29, x11\n"
+      "br x9\n"
+      :
+      : "r"(x9), "r"(x10), "r"(x11));
+#elif defined(__riscv) && __riscv_xlen == 64
+  register void *t0 __asm__("t0") = pc;
+  register void *t1 __asm__("t1") = sp;
+  register void *t2 __asm__("t2") = fp;
+  __asm__ volatile(
+      "mv sp, t1\n"
+      "mv s0, t2\n"
+      "jr t0\n"
+      :
+      : "r"(t0), "r"(t1), "r"(t2));
+#endif
+  // Exceptions aren't supported on other targets, see `Config::wasm_exceptions`.
+  abort();
+}
@@ -1,3 +1,4 @@
+use crate::export::ExportTag;
 use crate::vmcontext::{VMFunctionImport, VMGlobalImport, VMMemoryImport, VMTableImport};
 
 /// Resolved import pointers.
@@ -24,4 +25,7 @@ pub struct Imports<'a> {
 
     /// Resolved addresses for imported globals.
     pub globals: &'a [VMGlobalImport],
+
+    /// Resolved imported exception tags.
+    pub tags: &'a [ExportTag],
 }
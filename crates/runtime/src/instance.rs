@@ -11,7 +11,7 @@ use crate::vmcontext::{
     VMCallerCheckedAnyfunc, VMContext, VMFunctionImport, VMGlobalDefinition, VMGlobalImport,
     VMInterrupts, VMMemoryDefinition, VMMemoryImport, VMTableDefinition, VMTableImport,
 };
-use crate::{ExportFunction, ExportGlobal, ExportMemory, ExportTable, Store};
+use crate::{ExportFunction, ExportGlobal, ExportMemory, ExportTable, ExportTag, Store};
 use memoffset::offset_of;
 use more_asserts::assert_lt;
 use std::alloc::Layout;
@@ -20,12 +20,13 @@ use std::convert::TryFrom;
 use std::hash::Hash;
 use std::ops::Range;
 use std::ptr::NonNull;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::{mem, ptr, slice};
 use wasmtime_environ::{
     packed_option::ReservedValue, DataIndex, DefinedGlobalIndex, DefinedMemoryIndex,
     DefinedTableIndex, ElemIndex, EntityIndex, EntityRef, EntitySet, FuncIndex, GlobalIndex,
-    HostPtr, MemoryIndex, Module, PrimaryMap, TableIndex, TrapCode, VMOffsets, WasmType,
+    HostPtr, MemoryIndex, Module, PrimaryMap, TableIndex, TagIndex, TrapCode, VMOffsets, WasmType,
 };
 
 mod allocator;
@@ -147,6 +148,9 @@ pub(crate) struct Instance {
     /// If the index is present in the set, the segment has been dropped.
     dropped_data: EntitySet<DataIndex>,
 
+    /// The exception tags of this instance, both imported and defined.
+    tags: PrimaryMap<TagIndex, ExportTag>,
+
     /// A slice pointing to all data that is referenced by this instance. This
     /// data is managed externally so this is effectively an unsafe reference,
     /// and this does not live for the `'static` lifetime so the API boundaries
@@ -229,18 +233,32 @@ impl Instance {
 
     /// Return the indexed `VMMemoryDefinition`.
     fn memory(&self, index: DefinedMemoryIndex) -> VMMemoryDefinition {
-        unsafe { *self.memory_ptr(index) }
+        self.memories[index].vmmemory()
     }
 
     /// Set the indexed memory to `VMMemoryDefinition`.
+    ///
+    /// Shared memories manage their own definition, so this is a noop for
+    /// them.
     fn set_memory(&self, index: DefinedMemoryIndex, mem: VMMemoryDefinition) {
+        if self.memories[index].as_shared_memory().is_some() {
+            return;
+        }
         unsafe {
-            *self.memory_ptr(index) = mem;
+            *self.owned_memory_ptr(index) = mem;
         }
     }
 
     /// Return the indexed `VMMemoryDefinition`.
+    ///
+    /// For shared memories this points outside of this instance.
     fn memory_ptr(&self, index: DefinedMemoryIndex) -> *mut VMMemoryDefinition {
+        unsafe { *self.vmctx_plus_offset(self.offsets.vmctx_vmmemory_pointer(index)) }
+    }
+
+    /// Return the `VMMemoryDefinition` for the indexed memory which is stored
+    /// inline within this instance's `VMContext`.
+    fn owned_memory_ptr(&self, index: DefinedMemoryIndex) -> *mut VMMemoryDefinition {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_vmmemory_definition(index)) }
     }
 
@@ -274,6 +292,11 @@ impl Instance {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_interrupts()) }
     }
 
+    /// Return a pointer to the global epoch counter used by this instance.
+    pub fn epoch_ptr(&self) -> *mut *const AtomicU64 {
+        unsafe { self.vmctx_plus_offset(self.offsets.vmctx_epoch_ptr()) }
+    }
+
     /// Return a pointer to the `VMExternRefActivationsTable`.
     pub fn externref_activations_table(&self) -> *mut *mut VMExternRefActivationsTable {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_externref_activations_table()) }
@@ -300,6 +323,11 @@ impl Instance {
         *self.vmctx_plus_offset(self.offsets.vmctx_store()) = store;
     }
 
+    /// Returns the store-unique identifier of the tag `index`.
+    pub(crate) fn tag_id(&self, index: TagIndex) -> u32 {
+        self.tags[index].id
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     #[inline]
     pub fn vmctx(&self) -> &VMContext {
@@ -362,6 +390,8 @@ impl Instance {
             }
             .into(),
 
+            EntityIndex::Tag(index) => self.tags[*index].into(),
+
             EntityIndex::Instance(_) | EntityIndex::Module(_) => {
                 panic!("can't use this api for modules/instances")
             }
@@ -403,16 +433,16 @@ impl Instance {
     }
 
     /// Return the memory index for the given `VMMemoryDefinition`.
+    ///
+    /// Definitions of shared memories don't live inside of the `VMContext` so
+    /// this can't use pointer arithmetic, and instead searches through the
+    /// (typically very short) list of defined memories.
     unsafe fn memory_index(&self, memory: &VMMemoryDefinition) -> DefinedMemoryIndex {
-        let index = DefinedMemoryIndex::new(
-            usize::try_from(
-                (memory as *const VMMemoryDefinition)
-                    .offset_from(self.memory_ptr(DefinedMemoryIndex::new(0))),
-            )
-            .unwrap(),
-        );
-        assert_lt!(index.index(), self.memories.len());
-        index
+        let memory = memory as *const VMMemoryDefinition as *mut VMMemoryDefinition;
+        self.memories
+            .keys()
+            .find(|index| self.memory_ptr(*index) == memory)
+            .expect("memory definition not owned by this instance")
     }
 
     /// Grow memory by the specified amount of pages.
@@ -511,6 +541,26 @@ impl Instance {
         unsafe { Some(&*self.vmctx_plus_offset(self.offsets.vmctx_anyfunc(index))) }
     }
 
+    /// Returns the index of the function whose `VMCallerCheckedAnyfunc` lives
+    /// at `anyfunc` within this instance's `VMContext`, if it's one of ours.
+    pub(crate) fn anyfunc_index(
+        &self,
+        anyfunc: *const VMCallerCheckedAnyfunc,
+    ) -> Option<FuncIndex> {
+        let base = unsafe { self.anyfunc_base() } as usize;
+        let size = usize::from(self.offsets.size_of_vmcaller_checked_anyfunc());
+        let offset = (anyfunc as usize).checked_sub(base)?;
+        if offset % size != 0 {
+            return None;
+        }
+        let index = FuncIndex::new(offset / size);
+        if index.index() < self.module.functions.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
     unsafe fn anyfunc_base(&self) -> *mut VMCallerCheckedAnyfunc {
         self.vmctx_plus_offset(self.offsets.vmctx_anyfuncs_begin())
     }
@@ -603,6 +653,21 @@ impl Instance {
         ptr::addr_of_mut!(self.memories[index])
     }
 
+    /// Get a memory by index regardless of whether it is locally-defined or
+    /// an imported, foreign memory.
+    pub(crate) fn get_runtime_memory(&mut self, index: MemoryIndex) -> *mut Memory {
+        if let Some(defined_index) = self.module.defined_memory_index(index) {
+            self.get_defined_memory(defined_index)
+        } else {
+            let import = self.imported_memory(index);
+            unsafe {
+                let foreign_instance = (*import.vmctx).instance_mut();
+                let foreign_memory_index = foreign_instance.memory_index(&*import.from);
+                foreign_instance.get_defined_memory(foreign_memory_index)
+            }
+        }
+    }
+
     /// Do a `memory.copy`
     ///
     /// # Errors
@@ -874,6 +939,13 @@ impl InstanceHandle {
         self.instance_mut().get_defined_table(index)
     }
 
+    /// Returns the index of the function that `anyfunc` refers to, if it's a
+    /// function reference created by this instance, for example one read out
+    /// of a table or global.
+    pub fn anyfunc_index(&self, anyfunc: *const VMCallerCheckedAnyfunc) -> Option<FuncIndex> {
+        self.instance().anyfunc_index(anyfunc)
+    }
+
     /// Return a reference to the contained `Instance`.
     #[inline]
     pub(crate) fn instance(&self) -> &Instance {
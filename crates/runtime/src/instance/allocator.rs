@@ -7,7 +7,7 @@ use crate::vmcontext::{
     VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMGlobalDefinition,
     VMSharedSignatureIndex,
 };
-use crate::Store;
+use crate::{ExportTag, ModuleMemoryImages, Store};
 use anyhow::Result;
 use std::alloc;
 use std::any::Any;
@@ -34,7 +34,8 @@ pub struct InstanceAllocationRequest<'a> {
     /// The module being instantiated.
     pub module: Arc<Module>,
 
-    /// The finished (JIT) functions for the module.
+    /// The addresses to call the module's defined functions through; see
+    /// [`VMFunctionBody::entry`].
     pub finished_functions: &'a PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
 
     /// The imports to use for the instantiation.
@@ -71,6 +72,13 @@ pub struct InstanceAllocationRequest<'a> {
     /// responsibility of the callee when allocating to ensure that this data
     /// outlives the instance.
     pub wasm_data: *const [u8],
+
+    /// The copy-on-write images of the module's linear memories, if they
+    /// were created for this module.
+    ///
+    /// Memories with an image mapped into them aren't initialized by copying
+    /// data segments.
+    pub memory_images: Option<&'a ModuleMemoryImages>,
 }
 
 /// An link error while instantiating a module.
@@ -397,6 +405,12 @@ fn initialize_instance(
     match &module.memory_initialization {
         MemoryInitialization::Paged { map, out_of_bounds } => {
             for (index, pages) in map {
+                // Memories which had a copy-on-write image mapped into them
+                // already have the contents of these pages.
+                if !instance.memories[index].needs_init() {
+                    continue;
+                }
+
                 let memory = instance.memory(index);
                 let slice =
                     unsafe { slice::from_raw_parts_mut(memory.base, memory.current_length) };
@@ -428,6 +442,7 @@ fn initialize_instance(
 unsafe fn initialize_vmcontext(instance: &mut Instance, req: InstanceAllocationRequest) {
     if let Some(store) = req.store {
         *instance.interrupts() = (*store).vminterrupts();
+        *instance.epoch_ptr() = (*store).epoch_ptr();
         *instance.externref_activations_table() = (*store).externref_activations_table().0;
         instance.set_store(store);
     }
@@ -509,18 +524,43 @@ unsafe fn initialize_vmcontext(instance: &mut Instance, req: InstanceAllocationR
         ptr = ptr.add(1);
     }
 
-    // Initialize the defined memories
-    let mut ptr = instance.vmctx_plus_offset(instance.offsets.vmctx_memories_begin());
+    // Initialize the defined memories, along with the pointers to their
+    // definitions. Shared memories own their definition so the pointer refers
+    // to that instead of the `VMContext`.
     for i in 0..module.memory_plans.len() - module.num_imported_memories {
+        let index = DefinedMemoryIndex::new(i);
+        let memory = &instance.memories[index];
+        let owned_ptr = instance.owned_memory_ptr(index);
+        ptr::write(owned_ptr, memory.vmmemory());
+        let definition_ptr = match memory.as_shared_memory() {
+            Some(shared) => shared.vmmemory_ptr(),
+            None => owned_ptr,
+        };
         ptr::write(
-            ptr,
-            instance.memories[DefinedMemoryIndex::new(i)].vmmemory(),
+            instance.vmctx_plus_offset(instance.offsets.vmctx_vmmemory_pointer(index)),
+            definition_ptr,
         );
-        ptr = ptr.add(1);
     }
 
     // Initialize the defined globals
     initialize_vmcontext_globals(instance);
+
+    // Initialize the tags, allocating identifiers for the defined ones
+    let module = instance.module.clone();
+    debug_assert_eq!(req.imports.tags.len(), module.num_imported_tags);
+    instance.tags.clear();
+    for tag in req.imports.tags {
+        instance.tags.push(*tag);
+    }
+    for tag in module.tags.values().skip(module.num_imported_tags) {
+        let signature = req.shared_signatures.lookup(tag.ty);
+        let store = req
+            .store
+            .expect("tags can only be defined by instances in a store");
+        let (exceptions, _) = (*store).exceptions();
+        let id = exceptions.allocate_tag(signature);
+        instance.tags.push(ExportTag { id, signature });
+    }
 }
 
 unsafe fn initialize_vmcontext_globals(instance: &Instance) {
@@ -614,6 +654,7 @@ impl OnDemandInstanceAllocator {
     fn create_memories(
         &self,
         module: &Module,
+        memory_images: Option<&ModuleMemoryImages>,
         mut limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Result<PrimaryMap<DefinedMemoryIndex, Memory>, InstantiationError> {
         let creator = self
@@ -624,8 +665,10 @@ impl OnDemandInstanceAllocator {
         let mut memories: PrimaryMap<DefinedMemoryIndex, _> =
             PrimaryMap::with_capacity(module.memory_plans.len() - num_imports);
         for plan in &module.memory_plans.values().as_slice()[num_imports..] {
+            let defined_index = DefinedMemoryIndex::new(memories.len());
+            let image = memory_images.and_then(|images| images.get_memory_image(defined_index));
             memories.push(
-                Memory::new_dynamic(plan, creator, borrow_limiter(&mut limiter))
+                Memory::new_dynamic(plan, creator, borrow_limiter(&mut limiter), image)
                     .map_err(InstantiationError::Resource)?,
             );
         }
@@ -648,7 +691,8 @@ unsafe impl InstanceAllocator for OnDemandInstanceAllocator {
         mut req: InstanceAllocationRequest,
     ) -> Result<InstanceHandle, InstantiationError> {
         let mut limiter = req.store.and_then(|s| (*s).limiter());
-        let memories = self.create_memories(&req.module, borrow_limiter(&mut limiter))?;
+        let memories =
+            self.create_memories(&req.module, req.memory_images, borrow_limiter(&mut limiter))?;
         let tables = Self::create_tables(&req.module, borrow_limiter(&mut limiter))?;
 
         let host_state = std::mem::replace(&mut req.host_state, Box::new(()));
@@ -661,6 +705,7 @@ unsafe impl InstanceAllocator for OnDemandInstanceAllocator {
                 tables,
                 dropped_elements: EntitySet::with_capacity(req.module.passive_elements.len()),
                 dropped_data: EntitySet::with_capacity(req.module.passive_data_map.len()),
+                tags: PrimaryMap::with_capacity(req.module.tags.len()),
                 host_state,
                 wasm_data: &*req.wasm_data,
                 vmctx: VMContext {
@@ -12,7 +12,9 @@ use super::{
     initialize_instance, initialize_vmcontext, InstanceAllocationRequest, InstanceAllocator,
     InstanceHandle, InstantiationError, ResourceLimiter,
 };
-use crate::{instance::Instance, Memory, Mmap, Table, VMContext};
+use crate::{
+    instance::Instance, Memory, MemoryImageSlot, Mmap, ModuleMemoryImages, Table, VMContext,
+};
 use anyhow::{anyhow, bail, Context, Result};
 use rand::Rng;
 use std::convert::TryFrom;
@@ -20,8 +22,8 @@ use std::marker;
 use std::mem;
 use std::sync::{Arc, Mutex};
 use wasmtime_environ::{
-    EntitySet, HostPtr, MemoryStyle, Module, PrimaryMap, Tunables, VMOffsets, VMOffsetsFields,
-    WASM_PAGE_SIZE,
+    DefinedMemoryIndex, EntityRef, EntitySet, HostPtr, MemoryStyle, Module, PrimaryMap, Tunables,
+    VMOffsets, VMOffsetsFields, WASM_PAGE_SIZE,
 };
 
 cfg_if::cfg_if! {
@@ -197,6 +199,13 @@ impl ModuleLimits {
                 );
             }
 
+            if plan.memory.shared {
+                bail!(
+                    "memory index {} is a shared memory which is not supported by the pooling allocator",
+                    i,
+                );
+            }
+
             if let MemoryStyle::Dynamic { .. } = plan.style {
                 bail!(
                     "memory index {} has an unsupported dynamic memory plan style",
@@ -363,6 +372,7 @@ impl InstancePool {
                     tables: PrimaryMap::with_capacity(limits.tables as usize),
                     dropped_elements: EntitySet::new(),
                     dropped_data: EntitySet::new(),
+                    tags: PrimaryMap::new(),
                     host_state: Box::new(()),
                     wasm_data: &[],
                     vmctx: VMContext {
@@ -387,9 +397,10 @@ impl InstancePool {
 
         let mut limiter = req.store.and_then(|s| (*s).limiter());
         Self::set_instance_memories(
+            index,
             instance,
-            self.memories.get(index),
-            self.memories.max_wasm_pages,
+            &self.memories,
+            req.memory_images,
             borrow_limiter(&mut limiter),
         )?;
 
@@ -446,7 +457,9 @@ impl InstancePool {
         let instance = unsafe { &mut *handle.instance };
 
         // Decommit any linear memories that were used
-        for (memory, base) in instance.memories.values_mut().zip(self.memories.get(index)) {
+        for ((def_mem_idx, memory), base) in
+            instance.memories.iter_mut().zip(self.memories.get(index))
+        {
             let mut memory = mem::take(memory);
             debug_assert!(memory.is_static());
 
@@ -458,12 +471,27 @@ impl InstancePool {
             drop(&mut memory); // require mutable on all platforms, not just uffd
 
             let size = memory.byte_size();
-            drop(memory);
-            decommit_memory_pages(base, size).expect("failed to decommit linear memory pages");
+            match memory.unwrap_static_image() {
+                // Memories using a copy-on-write image slot are reset by the
+                // slot itself, which keeps any image mapped for the next
+                // instance using this slot.
+                Some(mut image) => {
+                    image
+                        .clear_and_remain_ready()
+                        .expect("failed to reset memory image slot");
+                    self.memories
+                        .return_memory_image_slot(index, def_mem_idx, image);
+                }
+                None => {
+                    decommit_memory_pages(base, size)
+                        .expect("failed to decommit linear memory pages");
+                }
+            }
         }
 
         instance.memories.clear();
         instance.dropped_data.clear();
+        instance.tags.clear();
 
         // Decommit any tables that were used
         for (table, base) in instance.tables.values_mut().zip(self.tables.get(index)) {
@@ -500,29 +528,50 @@ impl InstancePool {
     }
 
     fn set_instance_memories(
+        instance_index: usize,
         instance: &mut Instance,
-        mut memories: impl Iterator<Item = *mut u8>,
-        max_pages: u64,
+        memories: &MemoryPool,
+        memory_images: Option<&ModuleMemoryImages>,
         mut limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Result<(), InstantiationError> {
         let module = instance.module.as_ref();
 
         debug_assert!(instance.memories.is_empty());
 
-        for plan in
-            (&module.memory_plans.values().as_slice()[module.num_imported_memories..]).iter()
+        for (plan, base) in (&module.memory_plans.values().as_slice()
+            [module.num_imported_memories..])
+            .iter()
+            .zip(memories.get(instance_index))
         {
+            let defined_index = DefinedMemoryIndex::new(instance.memories.len());
             let memory = unsafe {
                 std::slice::from_raw_parts_mut(
-                    memories.next().unwrap(),
-                    (max_pages as usize) * (WASM_PAGE_SIZE as usize),
+                    base,
+                    (memories.max_wasm_pages as usize) * (WASM_PAGE_SIZE as usize),
                 )
             };
+
+            // If copy-on-write memory images are supported then the slot for
+            // this memory is prepared with the memory's image, if any. Should
+            // anything fail from here on the slot is dropped, which resets it
+            // to fresh inaccessible memory for the next user.
+            let slot = match memories.take_memory_image_slot(instance_index, defined_index) {
+                Some(mut slot) => {
+                    let image =
+                        memory_images.and_then(|images| images.get_memory_image(defined_index));
+                    let initial_size = plan.memory.minimum * u64::from(WASM_PAGE_SIZE);
+                    slot.instantiate(initial_size as usize, image)?;
+                    Some(slot)
+                }
+                None => None,
+            };
+
             instance.memories.push(
                 Memory::new_static(
                     plan,
                     memory,
                     commit_memory_pages,
+                    slot,
                     borrow_limiter(&mut limiter),
                 )
                 .map_err(InstantiationError::Resource)?,
@@ -602,6 +651,11 @@ struct MemoryPool {
     max_memories: usize,
     max_instances: usize,
     max_wasm_pages: u64,
+    // The copy-on-write image slot for each linear memory in the pool, which
+    // is held here whenever no instance is using the memory. A slot is
+    // created when a memory is first used, and `None` is also stored for
+    // every memory if copy-on-write images aren't supported.
+    image_slots: Vec<Mutex<Option<MemoryImageSlot>>>,
 }
 
 impl MemoryPool {
@@ -676,6 +730,10 @@ impl MemoryPool {
         let mapping = Mmap::accessible_reserved(0, allocation_size)
             .context("failed to create memory pool mapping")?;
 
+        let image_slots = (0..max_memories * max_instances)
+            .map(|_| Mutex::new(None))
+            .collect();
+
         let pool = Self {
             mapping,
             memory_size,
@@ -683,6 +741,7 @@ impl MemoryPool {
             max_memories,
             max_instances,
             max_wasm_pages: module_limits.memory_pages,
+            image_slots,
         };
 
         // uffd support requires some special setup for the memory pool
@@ -704,6 +763,51 @@ impl MemoryPool {
         let size = self.memory_size;
         (0..self.max_memories).map(move |i| unsafe { base.add(i * size) })
     }
+
+    /// Takes the copy-on-write image slot for a memory of an instance out of
+    /// the pool, creating it if this is the first time it's used.
+    ///
+    /// Returns `None` if copy-on-write memory images aren't supported.
+    fn take_memory_image_slot(
+        &self,
+        instance_index: usize,
+        memory_index: DefinedMemoryIndex,
+    ) -> Option<MemoryImageSlot> {
+        let idx = instance_index * self.max_memories + memory_index.index();
+        let slot = self.image_slots[idx].lock().unwrap().take();
+        slot.or_else(|| {
+            let base = self.get(instance_index).nth(memory_index.index()).unwrap();
+            MemoryImageSlot::create(
+                base,
+                (self.max_wasm_pages as usize) * (WASM_PAGE_SIZE as usize),
+            )
+        })
+    }
+
+    /// Returns a slot taken with `take_memory_image_slot` once it has been
+    /// cleared.
+    fn return_memory_image_slot(
+        &self,
+        instance_index: usize,
+        memory_index: DefinedMemoryIndex,
+        slot: MemoryImageSlot,
+    ) {
+        assert!(!slot.is_dirty());
+        let idx = instance_index * self.max_memories + memory_index.index();
+        *self.image_slots[idx].lock().unwrap() = Some(slot);
+    }
+}
+
+impl Drop for MemoryPool {
+    fn drop(&mut self) {
+        // The entire pool mapping is about to go away, so there's no need for
+        // the slots to reset their part of it.
+        for slot in self.image_slots.iter_mut() {
+            if let Some(slot) = slot.get_mut().unwrap() {
+                slot.no_clear_on_drop();
+            }
+        }
+    }
 }
 
 /// Represents a pool of WebAssembly tables.
@@ -1408,11 +1512,13 @@ mod test {
                                 tables: &[],
                                 memories: &[],
                                 globals: &[],
+                                tags: &[],
                             },
                             shared_signatures: VMSharedSignatureIndex::default().into(),
                             host_state: Box::new(()),
                             store: None,
                             wasm_data: &[],
+                            memory_images: None,
                         },
                     )
                     .expect("allocation should succeed"),
@@ -1431,11 +1537,13 @@ mod test {
                     tables: &[],
                     memories: &[],
                     globals: &[],
+                    tags: &[],
                 },
                 shared_signatures: VMSharedSignatureIndex::default().into(),
                 host_state: Box::new(()),
                 store: None,
                 wasm_data: &[],
+                memory_images: None,
             },
         ) {
             Err(InstantiationError::Limit(3)) => {}
@@ -528,11 +528,13 @@ mod test {
                                     tables: &[],
                                     memories: &[],
                                     globals: &[],
+                                    tags: &[],
                                 },
                                 shared_signatures: VMSharedSignatureIndex::default().into(),
                                 host_state: Box::new(()),
                                 store: None,
                                 wasm_data: &[],
+                                memory_images: None,
                             },
                         )
                         .expect("instance should allocate"),
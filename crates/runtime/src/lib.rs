@@ -21,7 +21,10 @@
 )]
 
 use std::error::Error;
+use std::sync::atomic::AtomicU64;
+use wasmtime_environ::FuncIndex;
 
+mod exception;
 mod export;
 mod externref;
 mod imports;
@@ -29,6 +32,8 @@ mod instance;
 mod jit_int;
 mod memory;
 mod mmap;
+mod mmap_vec;
+mod parking_spot;
 mod table;
 mod traphandlers;
 mod vmcontext;
@@ -36,6 +41,17 @@ mod vmcontext;
 pub mod debug_builtins;
 pub mod libcalls;
 
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "memory-init-cow", target_os = "linux", not(feature = "uffd")))] {
+        mod cow;
+        pub use crate::cow::{MemoryImage, MemoryImageSlot, ModuleMemoryImages};
+    } else {
+        mod cow_disabled;
+        pub use crate::cow_disabled::{MemoryImage, MemoryImageSlot, ModuleMemoryImages};
+    }
+}
+
+pub use crate::exception::{ExceptionState, VMException};
 pub use crate::export::*;
 pub use crate::externref::*;
 pub use crate::imports::Imports;
@@ -46,17 +62,20 @@ pub use crate::instance::{
     DEFAULT_MEMORY_LIMIT, DEFAULT_TABLE_LIMIT,
 };
 pub use crate::jit_int::GdbJitImageRegistration;
-pub use crate::memory::{Memory, RuntimeLinearMemory, RuntimeMemoryCreator};
+pub use crate::memory::{
+    Memory, RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory, WaitResult,
+};
 pub use crate::mmap::Mmap;
+pub use crate::mmap_vec::MmapVec;
 pub use crate::table::{Table, TableElement};
 pub use crate::traphandlers::{
-    catch_traps, init_traps, raise_lib_trap, raise_user_trap, resume_panic, tls_eager_initialize,
-    SignalHandler, TlsRestore, Trap,
+    catch_traps, init_traps, raise_exception, raise_lib_trap, raise_user_trap, resume_panic,
+    tls_eager_initialize, SignalHandler, TlsRestore, Trap,
 };
 pub use crate::vmcontext::{
-    VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMGlobalDefinition,
-    VMGlobalImport, VMInterrupts, VMInvokeArgument, VMMemoryDefinition, VMMemoryImport,
-    VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
+    VMCallerCheckedAnyfunc, VMContext, VMDebugValue, VMFunctionBody, VMFunctionImport,
+    VMGlobalDefinition, VMGlobalImport, VMInterrupts, VMInvokeArgument, VMMemoryDefinition,
+    VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
 };
 
 /// Version number of this crate.
@@ -82,6 +101,11 @@ pub unsafe trait Store {
     /// in the `VMContext`.
     fn vminterrupts(&self) -> *mut VMInterrupts;
 
+    /// Returns a pointer to the global epoch counter.
+    ///
+    /// Used to configure the `VMContext` on initialization.
+    fn epoch_ptr(&self) -> *const AtomicU64;
+
     /// Returns the externref management structures necessary for this store.
     ///
     /// The first element returned is the table in which externrefs are stored
@@ -98,4 +122,33 @@ pub unsafe trait Store {
     /// is returned that's raised as a trap. Otherwise wasm execution will
     /// continue as normal.
     fn out_of_gas(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Callback invoked whenever a wasm instance observes that the epoch
+    /// counter has reached this store's deadline. If an error is returned
+    /// that's raised as a trap. Otherwise wasm execution will continue with
+    /// the returned value as the new deadline.
+    fn new_epoch(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>>;
+
+    /// Callback invoked by code compiled with guest debugging support before
+    /// executing the instruction at `offset` within the body of `func_index`,
+    /// whenever `VMInterrupts::debug_hooks` is set. The values of the
+    /// function's locals and of the operand stack are passed as `locals` and
+    /// `stack`, with the top of the stack last. If an error is returned that's
+    /// raised as a trap.
+    fn debug_hook(
+        &mut self,
+        vmctx: *mut VMContext,
+        func_index: FuncIndex,
+        offset: u32,
+        locals: &[VMDebugValue],
+        stack: &[VMDebugValue],
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Returns the exception management structures necessary for this store.
+    ///
+    /// The first element returned is the state of the exceptions thrown and
+    /// caught within this store, which also allocates the identifiers of tags
+    /// defined by its instances, and the second element is how to look up
+    /// module information for unwinding to landing pads.
+    fn exceptions(&mut self) -> (&mut ExceptionState, &dyn ModuleInfoLookup);
 }
@@ -56,15 +56,19 @@
 //!   }
 //!   ```
 
+use crate::exception::VMException;
 use crate::externref::VMExternRef;
 use crate::instance::Instance;
 use crate::table::{Table, TableElementType};
 use crate::traphandlers::{raise_lib_trap, Trap};
-use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, VMDebugValue};
 use backtrace::Backtrace;
 use std::mem;
 use std::ptr::{self, NonNull};
-use wasmtime_environ::{DataIndex, ElemIndex, GlobalIndex, MemoryIndex, TableIndex, TrapCode};
+use std::time::{Duration, Instant};
+use wasmtime_environ::{
+    DataIndex, ElemIndex, FuncIndex, GlobalIndex, MemoryIndex, TableIndex, TagIndex, TrapCode,
+};
 
 const TOINT_32: f32 = 1.0 / f32::EPSILON;
 const TOINT_64: f64 = 1.0 / f64::EPSILON;
@@ -437,11 +441,11 @@ pub unsafe extern "C" fn wasmtime_externref_global_set(
 }
 
 #[derive(Debug)]
-struct Unimplemented(&'static str);
-impl std::error::Error for Unimplemented {}
-impl std::fmt::Display for Unimplemented {
+struct AtomicWaitNonSharedMemory;
+impl std::error::Error for AtomicWaitNonSharedMemory {}
+impl std::fmt::Display for AtomicWaitNonSharedMemory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "unimplemented: {}", self.0)
+        write!(f, "atomic wait on non-shared memory")
     }
 }
 
@@ -450,19 +454,18 @@ pub unsafe extern "C" fn wasmtime_memory_atomic_notify(
     vmctx: *mut VMContext,
     memory_index: u32,
     addr: usize,
-    _count: u32,
+    count: u32,
 ) -> u32 {
     let result = {
         let memory = MemoryIndex::from_u32(memory_index);
-        let instance = (*vmctx).instance();
-        // this should never overflow since addr + 4 either hits a guard page
-        // or it's been validated to be in-bounds already. Double-check for now
-        // just to be sure.
-        let addr_to_check = addr.checked_add(4).unwrap();
-        validate_atomic_addr(instance, memory, addr_to_check).and_then(|()| {
-            Err(Trap::User(Box::new(Unimplemented(
-                "wasm atomics (fn wasmtime_memory_atomic_notify) unsupported",
-            ))))
+        let instance = (*vmctx).instance_mut();
+        validate_atomic_addr(instance, memory, addr, 4).and_then(|wasm_addr| {
+            match (*instance.get_runtime_memory(memory)).as_shared_memory() {
+                Some(shared) => shared.atomic_notify(wasm_addr, count),
+                // Notifying a non-shared memory can never wake anything up
+                // since no thread can be waiting on it.
+                None => Ok(0),
+            }
         })
     };
     match result {
@@ -476,23 +479,22 @@ pub unsafe extern "C" fn wasmtime_memory_atomic_wait32(
     vmctx: *mut VMContext,
     memory_index: u32,
     addr: usize,
-    _expected: u32,
-    _timeout: u64,
+    expected: u32,
+    timeout: u64,
 ) -> u32 {
     let result = {
         let memory = MemoryIndex::from_u32(memory_index);
-        let instance = (*vmctx).instance();
-        // see wasmtime_memory_atomic_notify for why this shouldn't overflow
-        // but we still double-check
-        let addr_to_check = addr.checked_add(4).unwrap();
-        validate_atomic_addr(instance, memory, addr_to_check).and_then(|()| {
-            Err(Trap::User(Box::new(Unimplemented(
-                "wasm atomics (fn wasmtime_memory_atomic_wait32) unsupported",
-            ))))
+        let instance = (*vmctx).instance_mut();
+        let deadline = wait_deadline(timeout);
+        validate_atomic_addr(instance, memory, addr, 4).and_then(|wasm_addr| {
+            match (*instance.get_runtime_memory(memory)).as_shared_memory() {
+                Some(shared) => shared.atomic_wait32(wasm_addr, expected, deadline),
+                None => Err(Trap::User(Box::new(AtomicWaitNonSharedMemory))),
+            }
         })
     };
     match result {
-        Ok(n) => n,
+        Ok(n) => n as u32,
         Err(e) => raise_lib_trap(e),
     }
 }
@@ -502,27 +504,37 @@ pub unsafe extern "C" fn wasmtime_memory_atomic_wait64(
     vmctx: *mut VMContext,
     memory_index: u32,
     addr: usize,
-    _expected: u64,
-    _timeout: u64,
+    expected: u64,
+    timeout: u64,
 ) -> u32 {
     let result = {
         let memory = MemoryIndex::from_u32(memory_index);
-        let instance = (*vmctx).instance();
-        // see wasmtime_memory_atomic_notify for why this shouldn't overflow
-        // but we still double-check
-        let addr_to_check = addr.checked_add(8).unwrap();
-        validate_atomic_addr(instance, memory, addr_to_check).and_then(|()| {
-            Err(Trap::User(Box::new(Unimplemented(
-                "wasm atomics (fn wasmtime_memory_atomic_wait64) unsupported",
-            ))))
+        let instance = (*vmctx).instance_mut();
+        let deadline = wait_deadline(timeout);
+        validate_atomic_addr(instance, memory, addr, 8).and_then(|wasm_addr| {
+            match (*instance.get_runtime_memory(memory)).as_shared_memory() {
+                Some(shared) => shared.atomic_wait64(wasm_addr, expected, deadline),
+                None => Err(Trap::User(Box::new(AtomicWaitNonSharedMemory))),
+            }
         })
     };
     match result {
-        Ok(n) => n,
+        Ok(n) => n as u32,
         Err(e) => raise_lib_trap(e),
     }
 }
 
+/// Converts the relative timeout, in nanoseconds, of a wait instruction into
+/// an absolute deadline. Negative timeouts mean that the wait never times out.
+fn wait_deadline(timeout: u64) -> Option<Instant> {
+    let timeout = timeout as i64;
+    if timeout < 0 {
+        None
+    } else {
+        Some(Instant::now() + Duration::from_nanos(timeout as u64))
+    }
+}
+
 /// For atomic operations we still check the actual address despite this also
 /// being checked via the `heap_addr` instruction in cranelift. The reason for
 /// that is because the `heap_addr` instruction can defer to a later segfault to
@@ -532,18 +544,28 @@ pub unsafe extern "C" fn wasmtime_memory_atomic_wait64(
 /// In the situations where bounds checks were elided in JIT code (because oob
 /// would then be later guaranteed to segfault) this manual check is here
 /// so we don't segfault from Rust.
+///
+/// The `addr` given here is the host address computed by compiled code, and
+/// on success the corresponding wasm address within the memory is returned.
 unsafe fn validate_atomic_addr(
     instance: &Instance,
     memory: MemoryIndex,
     addr: usize,
-) -> Result<(), Trap> {
-    if addr > instance.get_memory(memory).current_length {
-        return Err(Trap::Wasm {
+    access_size: usize,
+) -> Result<u64, Trap> {
+    let memory = instance.get_memory(memory);
+    let wasm_addr = addr
+        .checked_sub(memory.base as usize)
+        .filter(|wasm_addr| {
+            wasm_addr
+                .checked_add(access_size)
+                .map_or(false, |end| end <= memory.current_length)
+        })
+        .ok_or_else(|| Trap::Wasm {
             trap_code: TrapCode::HeapOutOfBounds,
             backtrace: Backtrace::new_unresolved(),
-        });
-    }
-    Ok(())
+        })?;
+    Ok(wasm_addr as u64)
 }
 
 /// Hook for when an instance runs out of fuel.
@@ -553,3 +575,92 @@ pub unsafe extern "C" fn wasmtime_out_of_gas(vmctx: *mut VMContext) {
         Err(err) => crate::traphandlers::raise_user_trap(err),
     }
 }
+
+/// Hook for when an instance observes that the epoch has changed.
+pub unsafe extern "C" fn wasmtime_new_epoch(vmctx: *mut VMContext) -> u64 {
+    match (*(*vmctx).instance().store()).new_epoch() {
+        Ok(new_deadline) => new_deadline,
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Hook invoked before a wasm instruction when guest debugging has requested
+/// it.
+pub unsafe extern "C" fn wasmtime_debug_hook(
+    vmctx: *mut VMContext,
+    func_index: u32,
+    offset: u32,
+    values: *const VMDebugValue,
+    num_locals: u32,
+    num_stack: u32,
+) {
+    let values = std::slice::from_raw_parts(values, (num_locals + num_stack) as usize);
+    let (locals, stack) = values.split_at(num_locals as usize);
+    let store = (*vmctx).instance().store();
+    let result = (*store).debug_hook(
+        vmctx,
+        FuncIndex::from_u32(func_index),
+        offset,
+        locals,
+        stack,
+    );
+    if let Err(err) = result {
+        crate::traphandlers::raise_user_trap(err);
+    }
+}
+
+/// Implementation of wasm's `throw` instruction, which throws an exception with
+/// the tag `tag_index` and the `len` values at `payload`.
+pub unsafe extern "C" fn wasmtime_throw_exception(
+    vmctx: *mut VMContext,
+    tag_index: u32,
+    payload: *const u128,
+    len: u32,
+) {
+    let instance = (*vmctx).instance();
+    let exception = VMException {
+        tag: instance.tag_id(TagIndex::from_u32(tag_index)),
+        payload: std::slice::from_raw_parts(payload, len as usize).into(),
+    };
+    let (exceptions, modules) = (*instance.store()).exceptions();
+    exceptions.throw(exception);
+    crate::traphandlers::raise_exception(modules);
+}
+
+/// Returns whether the pending exception has the tag `tag_index`.
+pub unsafe extern "C" fn wasmtime_exception_matches(vmctx: *mut VMContext, tag_index: u32) -> u32 {
+    let instance = (*vmctx).instance();
+    let tag = instance.tag_id(TagIndex::from_u32(tag_index));
+    let (exceptions, _) = (*instance.store()).exceptions();
+    exceptions.pending().map_or(false, |e| e.tag == tag) as u32
+}
+
+/// Implementation of the start of a `catch` or `catch_all` clause, which takes
+/// the pending exception and writes up to `len` values of its payload to
+/// `payload`. The address of `payload` identifies the caught exception for a
+/// later `rethrow`.
+pub unsafe extern "C" fn wasmtime_catch_exception(
+    vmctx: *mut VMContext,
+    payload: *mut u128,
+    len: u32,
+) {
+    let (exceptions, _) = (*(*vmctx).instance().store()).exceptions();
+    let exception = exceptions.catch(payload as usize);
+    let len = exception.payload.len().min(len as usize);
+    ptr::copy_nonoverlapping(exception.payload.as_ptr(), payload, len);
+}
+
+/// Implementation of wasm's `rethrow` instruction, which throws the exception
+/// caught with `payload` again.
+pub unsafe extern "C" fn wasmtime_rethrow_exception(vmctx: *mut VMContext, payload: *mut u128) {
+    let (exceptions, modules) = (*(*vmctx).instance().store()).exceptions();
+    exceptions.rethrow(payload as usize);
+    crate::traphandlers::raise_exception(modules);
+}
+
+/// Continues unwinding the pending exception when no `catch` clause of the
+/// function that calls this takes it.
+pub unsafe extern "C" fn wasmtime_resume_exception(vmctx: *mut VMContext) {
+    let (_, modules) = (*(*vmctx).instance().store()).exceptions();
+    crate::traphandlers::raise_exception(modules);
+}
@@ -3,12 +3,19 @@
 //! `RuntimeLinearMemory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
 use crate::mmap::Mmap;
+use crate::parking_spot::{ParkResult, ParkingSpot};
+use crate::traphandlers::Trap;
 use crate::vmcontext::VMMemoryDefinition;
 use crate::ResourceLimiter;
+use crate::{MemoryImage, MemoryImageSlot};
 use anyhow::{bail, format_err, Result};
 use more_asserts::{assert_ge, assert_le};
+use std::cell::UnsafeCell;
 use std::convert::TryFrom;
-use wasmtime_environ::{MemoryPlan, MemoryStyle, WASM32_MAX_PAGES, WASM64_MAX_PAGES};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use wasmtime_environ::{MemoryPlan, MemoryStyle, TrapCode, WASM32_MAX_PAGES, WASM64_MAX_PAGES};
 
 const WASM_PAGE_SIZE: usize = wasmtime_environ::WASM_PAGE_SIZE as usize;
 const WASM_PAGE_SIZE_U64: u64 = wasmtime_environ::WASM_PAGE_SIZE as u64;
@@ -16,12 +23,32 @@ const WASM_PAGE_SIZE_U64: u64 = wasmtime_environ::WASM_PAGE_SIZE as u64;
 /// A memory allocator
 pub trait RuntimeMemoryCreator: Send + Sync {
     /// Create new RuntimeLinearMemory
+    ///
+    /// If `memory_image` is specified then it contains the initial contents
+    /// of the memory, and the creator may map it into the new memory instead
+    /// of having the memory initialized by copying data segments. Whether
+    /// the image was used is reported by `RuntimeLinearMemory::needs_init`.
     fn new_memory(
         &self,
         plan: &MemoryPlan,
         minimum: usize,
         maximum: Option<usize>,
+        memory_image: Option<&Arc<MemoryImage>>,
     ) -> Result<Box<dyn RuntimeLinearMemory>>;
+
+    /// Create a new `SharedMemory` for a plan whose memory type is `shared`.
+    ///
+    /// Shared memories must never move once created, so by default they're
+    /// always backed by a reservation of their maximum size rather than by
+    /// `new_memory`.
+    fn new_shared_memory(
+        &self,
+        plan: &MemoryPlan,
+        minimum: usize,
+        maximum: Option<usize>,
+    ) -> Result<SharedMemory> {
+        SharedMemory::new_with_sizes(plan.clone(), minimum, maximum)
+    }
 }
 
 /// A default memory allocator used by Wasmtime
@@ -34,8 +61,14 @@ impl RuntimeMemoryCreator for DefaultMemoryCreator {
         plan: &MemoryPlan,
         minimum: usize,
         maximum: Option<usize>,
+        memory_image: Option<&Arc<MemoryImage>>,
     ) -> Result<Box<dyn RuntimeLinearMemory>> {
-        Ok(Box::new(MmapMemory::new(plan, minimum, maximum)?))
+        Ok(Box::new(MmapMemory::new(
+            plan,
+            minimum,
+            maximum,
+            memory_image,
+        )?))
     }
 }
 
@@ -57,6 +90,13 @@ pub trait RuntimeLinearMemory: Send + Sync {
     /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm
     /// code.
     fn vmmemory(&self) -> VMMemoryDefinition;
+
+    /// Returns whether this memory still needs its data segments copied into
+    /// it, which is the case unless a memory image was mapped when it was
+    /// created.
+    fn needs_init(&self) -> bool {
+        true
+    }
 }
 
 /// A linear memory instance.
@@ -85,11 +125,24 @@ pub struct MmapMemory {
     // optimize loads and stores with constant offsets.
     pre_guard_size: usize,
     offset_guard_size: usize,
+
+    // The memory image which was mapped at the start of this memory when it
+    // was created, if any. This is cleared if the memory moves since the
+    // image is then no longer mapped.
+    memory_image: Option<Arc<MemoryImage>>,
 }
 
 impl MmapMemory {
     /// Create a new linear memory instance with specified minimum and maximum number of wasm pages.
-    pub fn new(plan: &MemoryPlan, minimum: usize, maximum: Option<usize>) -> Result<Self> {
+    ///
+    /// If `memory_image` is specified it's mapped copy-on-write at the start
+    /// of the memory.
+    pub fn new(
+        plan: &MemoryPlan,
+        minimum: usize,
+        maximum: Option<usize>,
+        memory_image: Option<&Arc<MemoryImage>>,
+    ) -> Result<Self> {
         // It's a programmer error for these two configuration values to exceed
         // the host available address space, so panic if such a configuration is
         // found (mostly an issue for hypothetical 32-bit hosts).
@@ -117,6 +170,13 @@ impl MmapMemory {
             mmap.make_accessible(pre_guard_bytes, minimum)?;
         }
 
+        if let Some(image) = memory_image {
+            assert_le!(image.len(), minimum);
+            unsafe {
+                image.map_at(mmap.as_mut_ptr().add(pre_guard_bytes))?;
+            }
+        }
+
         Ok(Self {
             mmap,
             accessible: minimum,
@@ -124,6 +184,7 @@ impl MmapMemory {
             pre_guard_size: pre_guard_bytes,
             offset_guard_size: offset_guard_bytes,
             extra_to_reserve_on_growth,
+            memory_image: memory_image.cloned(),
         })
     }
 }
@@ -158,6 +219,7 @@ impl RuntimeLinearMemory for MmapMemory {
                 .copy_from_slice(&self.mmap.as_slice()[self.pre_guard_size..][..self.accessible]);
 
             self.mmap = new_mmap;
+            self.memory_image = None;
         } else {
             // If the new size of this heap fits within the existing allocation
             // then all we need to do is to make the new pages accessible. This
@@ -185,6 +247,242 @@ impl RuntimeLinearMemory for MmapMemory {
             current_length: self.accessible,
         }
     }
+
+    fn needs_init(&self) -> bool {
+        self.memory_image.is_none()
+    }
+}
+
+/// The result of a `memory.atomic.wait32` or `memory.atomic.wait64`
+/// operation.
+///
+/// The discriminants match the values that the wasm instructions return.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WaitResult {
+    /// The waiting thread was woken up by a call to `memory.atomic.notify`.
+    Ok = 0,
+    /// The value in memory did not match the expected value, so the thread
+    /// never went to sleep.
+    Mismatch = 1,
+    /// The timeout elapsed before the waiting thread was woken up.
+    TimedOut = 2,
+}
+
+/// A linear memory which can be shared between many instances, possibly
+/// living in different stores on different threads.
+///
+/// Shared memories always have a maximum size and are reserved up front so
+/// that growing them never relocates their base address. Their
+/// `VMMemoryDefinition` lives in this structure rather than in the `VMContext`
+/// of any one instance so all instances using the memory observe growth.
+#[derive(Clone)]
+pub struct SharedMemory(Arc<SharedMemoryInner>);
+
+struct SharedMemoryInner {
+    plan: MemoryPlan,
+    memory: Mutex<MmapMemory>,
+    // Only ever mutated while `memory` is locked, and `current_length` is
+    // always accessed atomically from Rust since compiled code may be reading
+    // it concurrently.
+    definition: UnsafeCell<VMMemoryDefinition>,
+    spot: ParkingSpot,
+}
+
+// The `UnsafeCell` above is only ever mutated while the memory lock is held
+// and all other accesses are atomic, so sharing this structure is safe.
+unsafe impl Send for SharedMemoryInner {}
+unsafe impl Sync for SharedMemoryInner {}
+
+impl SharedMemory {
+    /// Creates a new shared memory for the specified plan.
+    ///
+    /// The plan's memory type must be `shared` and must have a maximum size.
+    pub fn new(plan: MemoryPlan) -> Result<Self> {
+        let (minimum, maximum) = Memory::limit_new(&plan, None)?;
+        Self::new_with_sizes(plan, minimum, maximum)
+    }
+
+    pub(crate) fn new_with_sizes(
+        plan: MemoryPlan,
+        minimum: usize,
+        maximum: Option<usize>,
+    ) -> Result<Self> {
+        if !plan.memory.shared {
+            bail!("memory type is not shared");
+        }
+        let maximum_pages = match plan.memory.maximum {
+            Some(max) => max,
+            None => bail!("shared memories must have a maximum size"),
+        };
+
+        // The base pointer of a shared memory can never move, so it must be
+        // reserved up front. Compiled code for static heaps elides bounds
+        // checks based on the plan's bound and guard region, so those are
+        // reserved in full. Compiled code for dynamic heaps bounds-checks every
+        // access, so reserving just the maximum size is enough for those.
+        let memory = match plan.style {
+            MemoryStyle::Static { .. } => MmapMemory::new(&plan, minimum, maximum, None)?,
+            MemoryStyle::Dynamic { .. } => {
+                let reservation_plan = MemoryPlan {
+                    style: MemoryStyle::Static {
+                        bound: std::cmp::max(maximum_pages, plan.memory.minimum),
+                    },
+                    ..plan.clone()
+                };
+                MmapMemory::new(&reservation_plan, minimum, maximum, None)?
+            }
+        };
+        let definition = memory.vmmemory();
+        Ok(SharedMemory(Arc::new(SharedMemoryInner {
+            plan,
+            memory: Mutex::new(memory),
+            definition: UnsafeCell::new(definition),
+            spot: ParkingSpot::default(),
+        })))
+    }
+
+    /// Returns the plan this shared memory was created with.
+    pub fn plan(&self) -> &MemoryPlan {
+        &self.0.plan
+    }
+
+    /// Returns the current size of this memory, in bytes.
+    pub fn byte_size(&self) -> usize {
+        self.current_length().load(Ordering::SeqCst)
+    }
+
+    /// Returns the maximum size of this memory, in bytes.
+    pub fn maximum_byte_size(&self) -> Option<usize> {
+        self.0.memory.lock().unwrap().maximum_byte_size()
+    }
+
+    /// Returns a pointer to the `VMMemoryDefinition` of this memory.
+    ///
+    /// The returned pointer is stable for as long as any clone of this
+    /// `SharedMemory` is alive, and it's what all `VMContext`s that use this
+    /// memory point to.
+    pub fn vmmemory_ptr(&self) -> *mut VMMemoryDefinition {
+        self.0.definition.get()
+    }
+
+    /// Returns a snapshot of the current `VMMemoryDefinition` of this memory.
+    pub fn vmmemory(&self) -> VMMemoryDefinition {
+        VMMemoryDefinition {
+            base: unsafe { (*self.vmmemory_ptr()).base },
+            current_length: self.byte_size(),
+        }
+    }
+
+    fn current_length(&self) -> &AtomicUsize {
+        unsafe { &*(std::ptr::addr_of_mut!((*self.vmmemory_ptr()).current_length) as *const _) }
+    }
+
+    /// Grows this memory by `delta_pages` wasm pages.
+    ///
+    /// Returns the previous size of the memory in bytes on success, or `None`
+    /// if the memory could not be grown. The base pointer of the memory never
+    /// changes.
+    pub fn grow(&self, delta_pages: u64) -> Option<usize> {
+        let mut memory = self.0.memory.lock().unwrap();
+        let old_byte_size = memory.byte_size();
+        if delta_pages == 0 {
+            return Some(old_byte_size);
+        }
+        let new_byte_size = usize::try_from(delta_pages)
+            .ok()?
+            .checked_mul(WASM_PAGE_SIZE)?
+            .checked_add(old_byte_size)?;
+        if let Some(max) = memory.maximum_byte_size() {
+            if new_byte_size > max {
+                return None;
+            }
+        }
+        memory.grow_to(new_byte_size)?;
+        debug_assert_eq!(memory.vmmemory().base, unsafe {
+            (*self.vmmemory_ptr()).base
+        });
+        self.current_length().store(new_byte_size, Ordering::SeqCst);
+        Some(old_byte_size)
+    }
+
+    /// Implementation of `memory.atomic.notify` for this shared memory.
+    ///
+    /// Wakes up at most `count` threads waiting on the wasm address `addr`,
+    /// returning the number of threads that were woken up.
+    pub fn atomic_notify(&self, addr: u64, count: u32) -> Result<u32, Trap> {
+        let host_addr = self.validate_atomic_addr(addr, 4)?;
+        Ok(self.0.spot.unpark(host_addr as u64, count))
+    }
+
+    /// Implementation of `memory.atomic.wait32` for this shared memory.
+    ///
+    /// Blocks the current thread until it's notified, or until `deadline` is
+    /// reached if one is specified, if the 32-bit value at the wasm address
+    /// `addr` is equal to `expected`.
+    pub fn atomic_wait32(
+        &self,
+        addr: u64,
+        expected: u32,
+        deadline: Option<Instant>,
+    ) -> Result<WaitResult, Trap> {
+        let host_addr = self.validate_atomic_addr(addr, 4)?;
+        let atomic = unsafe { &*(host_addr as *const AtomicU32) };
+        Ok(self.wait(
+            host_addr,
+            || atomic.load(Ordering::SeqCst) == expected,
+            deadline,
+        ))
+    }
+
+    /// Implementation of `memory.atomic.wait64` for this shared memory.
+    ///
+    /// Same as [`SharedMemory::atomic_wait32`] except that a 64-bit value is
+    /// compared.
+    pub fn atomic_wait64(
+        &self,
+        addr: u64,
+        expected: u64,
+        deadline: Option<Instant>,
+    ) -> Result<WaitResult, Trap> {
+        let host_addr = self.validate_atomic_addr(addr, 8)?;
+        let atomic = unsafe { &*(host_addr as *const AtomicU64) };
+        Ok(self.wait(
+            host_addr,
+            || atomic.load(Ordering::SeqCst) == expected,
+            deadline,
+        ))
+    }
+
+    fn wait(
+        &self,
+        host_addr: usize,
+        validate: impl FnOnce() -> bool,
+        deadline: Option<Instant>,
+    ) -> WaitResult {
+        match self.0.spot.park(host_addr as u64, validate, deadline) {
+            ParkResult::Unparked => WaitResult::Ok,
+            ParkResult::Invalid => WaitResult::Mismatch,
+            ParkResult::TimedOut => WaitResult::TimedOut,
+        }
+    }
+
+    /// Checks that an `access_size`-byte atomic access at the wasm address
+    /// `addr` is aligned and in bounds, returning the host address of the
+    /// access.
+    fn validate_atomic_addr(&self, addr: u64, access_size: u64) -> Result<usize, Trap> {
+        if addr % access_size != 0 {
+            return Err(Trap::wasm(TrapCode::HeapMisaligned));
+        }
+        let end = addr
+            .checked_add(access_size)
+            .and_then(|end| usize::try_from(end).ok());
+        match end {
+            Some(end) if end <= self.byte_size() => {}
+            _ => return Err(Trap::wasm(TrapCode::HeapOutOfBounds)),
+        }
+        let base = unsafe { (*self.vmmemory_ptr()).base };
+        Ok(base as usize + addr as usize)
+    }
 }
 
 /// Representation of a runtime wasm linear memory.
@@ -204,6 +502,11 @@ pub enum Memory {
         /// fault.
         make_accessible: fn(*mut u8, usize) -> Result<()>,
 
+        /// The slot this memory lives in when copy-on-write memory images are
+        /// in use. If present this is used instead of `make_accessible` to
+        /// grow the memory, and it may have an image mapped into it.
+        memory_image: Option<MemoryImageSlot>,
+
         /// Stores the pages in the linear memory that have faulted as guard pages when using the `uffd` feature.
         /// These pages need their protection level reset before the memory can grow.
         #[cfg(all(feature = "uffd", target_os = "linux"))]
@@ -213,24 +516,47 @@ pub enum Memory {
     /// A "dynamic" memory whose data is managed at runtime and lifetime is tied
     /// to this instance.
     Dynamic(Box<dyn RuntimeLinearMemory>),
+
+    /// A shared memory whose lifetime isn't tied to this instance, and which
+    /// may be concurrently used by other instances.
+    Shared(SharedMemory),
 }
 
 impl Memory {
     /// Create a new dynamic (movable) memory instance for the specified plan.
+    ///
+    /// The `memory_image` is the initial contents of the memory, if known,
+    /// and is passed along to `creator`.
     pub fn new_dynamic(
         plan: &MemoryPlan,
         creator: &dyn RuntimeMemoryCreator,
         limiter: Option<&mut dyn ResourceLimiter>,
+        memory_image: Option<&Arc<MemoryImage>>,
     ) -> Result<Self> {
         let (minimum, maximum) = Self::limit_new(plan, limiter)?;
-        Ok(Memory::Dynamic(creator.new_memory(plan, minimum, maximum)?))
+        if plan.memory.shared {
+            return Ok(Memory::Shared(
+                creator.new_shared_memory(plan, minimum, maximum)?,
+            ));
+        }
+        Ok(Memory::Dynamic(creator.new_memory(
+            plan,
+            minimum,
+            maximum,
+            memory_image,
+        )?))
     }
 
     /// Create a new static (immovable) memory instance for the specified plan.
+    ///
+    /// If `memory_image` is specified then it's the slot that `base` refers
+    /// to, which must already have been instantiated with this memory's
+    /// initial size.
     pub fn new_static(
         plan: &MemoryPlan,
         base: &'static mut [u8],
         make_accessible: fn(*mut u8, usize) -> Result<()>,
+        memory_image: Option<MemoryImageSlot>,
         limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Result<Self> {
         let (minimum, maximum) = Self::limit_new(plan, limiter)?;
@@ -240,7 +566,7 @@ impl Memory {
             _ => base,
         };
 
-        if minimum > 0 {
+        if memory_image.is_none() && minimum > 0 {
             make_accessible(base.as_mut_ptr(), minimum)?;
         }
 
@@ -248,6 +574,7 @@ impl Memory {
             base,
             size: minimum,
             make_accessible,
+            memory_image,
             #[cfg(all(feature = "uffd", target_os = "linux"))]
             guard_page_faults: Vec::new(),
         })
@@ -347,6 +674,7 @@ impl Memory {
         match self {
             Memory::Static { size, .. } => *size,
             Memory::Dynamic(mem) => mem.byte_size(),
+            Memory::Shared(mem) => mem.byte_size(),
         }
     }
 
@@ -360,6 +688,7 @@ impl Memory {
         match self {
             Memory::Static { base, .. } => Some(base.len()),
             Memory::Dynamic(mem) => mem.maximum_byte_size(),
+            Memory::Shared(mem) => mem.maximum_byte_size(),
         }
     }
 
@@ -372,12 +701,48 @@ impl Memory {
         }
     }
 
+    /// Returns whether this memory still needs to be initialized with the
+    /// module's data segments, which isn't the case if a copy-on-write memory
+    /// image was mapped into it.
+    pub fn needs_init(&self) -> bool {
+        match self {
+            Memory::Static {
+                memory_image: Some(slot),
+                ..
+            } => !slot.has_image(),
+            Memory::Static { .. } | Memory::Shared(_) => true,
+            Memory::Dynamic(mem) => mem.needs_init(),
+        }
+    }
+
+    /// Consumes a static memory, returning the memory image slot that it was
+    /// using, if any.
+    pub(crate) fn unwrap_static_image(self) -> Option<MemoryImageSlot> {
+        match self {
+            Memory::Static { memory_image, .. } => memory_image,
+            Memory::Dynamic(_) | Memory::Shared(_) => {
+                unreachable!("only static memories use memory image slots")
+            }
+        }
+    }
+
+    /// Returns the underlying shared memory, if this is a shared memory.
+    pub fn as_shared_memory(&self) -> Option<&SharedMemory> {
+        match self {
+            Memory::Shared(mem) => Some(mem),
+            _ => None,
+        }
+    }
+
     /// Grow memory by the specified amount of wasm pages.
     ///
     /// Returns `None` if memory can't be grown by the specified amount
     /// of wasm pages. Returns `Some` with the old size of memory, in bytes, on
     /// successful growth.
     ///
+    /// Note that the `limiter` is not consulted for shared memories since they
+    /// don't belong to any one store.
+    ///
     /// # Safety
     ///
     /// Resizing the memory can reallocate the memory buffer for dynamic memories.
@@ -391,6 +756,10 @@ impl Memory {
         delta_pages: u64,
         limiter: Option<&mut dyn ResourceLimiter>,
     ) -> Option<usize> {
+        if let Memory::Shared(mem) = self {
+            return mem.grow(delta_pages);
+        }
+
         let old_byte_size = self.byte_size();
         if delta_pages == 0 {
             return Some(old_byte_size);
@@ -426,21 +795,26 @@ impl Memory {
                 base,
                 size,
                 make_accessible,
+                memory_image,
                 ..
             } => {
                 if new_byte_size > base.len() {
                     return None;
                 }
 
-                make_accessible(
-                    base.as_mut_ptr().add(old_byte_size),
-                    new_byte_size - old_byte_size,
-                )
-                .ok()?;
+                match memory_image {
+                    Some(slot) => slot.set_heap_limit(new_byte_size).ok()?,
+                    None => make_accessible(
+                        base.as_mut_ptr().add(old_byte_size),
+                        new_byte_size - old_byte_size,
+                    )
+                    .ok()?,
+                }
 
                 *size = new_byte_size;
             }
             Memory::Dynamic(mem) => mem.grow_to(new_byte_size)?,
+            Memory::Shared(_) => unreachable!(),
         }
         Some(old_byte_size)
     }
@@ -453,6 +827,7 @@ impl Memory {
                 current_length: *size,
             },
             Memory::Dynamic(mem) => mem.vmmemory(),
+            Memory::Shared(mem) => mem.vmmemory(),
         }
     }
 
@@ -474,7 +849,7 @@ impl Memory {
             } => {
                 guard_page_faults.push((page_addr as usize, size, reset));
             }
-            Memory::Dynamic(_) => {
+            Memory::Dynamic(_) | Memory::Shared(_) => {
                 unreachable!("dynamic memories should not have guard page faults")
             }
         }
@@ -495,7 +870,7 @@ impl Memory {
                     reset(addr as *mut u8, len)?;
                 }
             }
-            Memory::Dynamic(_) => {
+            Memory::Dynamic(_) | Memory::Shared(_) => {
                 unreachable!("dynamic memories should not have guard page faults")
             }
         }
@@ -511,6 +886,7 @@ impl Default for Memory {
             base: &mut [],
             size: 0,
             make_accessible: |_, _| unreachable!(),
+            memory_image: None,
             #[cfg(all(feature = "uffd", target_os = "linux"))]
             guard_page_faults: Vec::new(),
         }
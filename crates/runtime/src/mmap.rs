@@ -1,9 +1,13 @@
 //! Low-level abstraction for allocating and managing zero-filled pages
 //! of memory.
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use more_asserts::assert_le;
+use std::convert::TryFrom;
+use std::fs::File;
 use std::io;
+use std::ops::Range;
+use std::path::Path;
 use std::ptr;
 use std::slice;
 
@@ -44,6 +48,67 @@ impl Mmap {
         Self::accessible_reserved(rounded_size, rounded_size)
     }
 
+    /// Creates a new `Mmap` which maps the entire contents of the file at
+    /// `path` into memory.
+    ///
+    /// The mapping is private and copy-on-write: it's readable and writable,
+    /// but writes are never reflected back into the file itself.
+    #[cfg(not(target_os = "windows"))]
+    pub fn from_file(path: &Path) -> Result<Self> {
+        use std::os::unix::prelude::*;
+
+        let file = File::open(path).context("failed to open file")?;
+        let len = file
+            .metadata()
+            .context("failed to get file metadata")?
+            .len();
+        let len = usize::try_from(len).map_err(|_| anyhow::anyhow!("file too large to map"))?;
+        if len == 0 {
+            return Ok(Self::new());
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error())
+                .context(format!("mmap failed to map {:#x} bytes of file", len));
+        }
+
+        Ok(Self {
+            ptr: ptr as usize,
+            len,
+        })
+    }
+
+    /// Creates a new `Mmap` which contains the entire contents of the file at
+    /// `path`.
+    ///
+    /// Note that on Windows the file is currently read into anonymous memory
+    /// rather than being mapped.
+    #[cfg(target_os = "windows")]
+    pub fn from_file(path: &Path) -> Result<Self> {
+        use std::io::Read;
+
+        let mut file = File::open(path).context("failed to open file")?;
+        let len = file
+            .metadata()
+            .context("failed to get file metadata")?
+            .len();
+        let len = usize::try_from(len).map_err(|_| anyhow::anyhow!("file too large to map"))?;
+        let mut mmap = Self::with_at_least(len)?;
+        file.read_exact(&mut mmap.as_mut_slice()[..len])
+            .context("failed to read file")?;
+        mmap.len = len;
+        Ok(mmap)
+    }
+
     /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
     /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
     /// must be native page-size multiples.
@@ -227,6 +292,41 @@ impl Mmap {
         Ok(())
     }
 
+    /// Makes the specified `range` within this `Mmap` read-only.
+    ///
+    /// # Unsafety
+    ///
+    /// The caller must ensure that nothing writes to `range` afterwards.
+    pub unsafe fn make_readonly(&self, range: Range<usize>) -> Result<()> {
+        assert!(range.start <= self.len);
+        assert!(range.end <= self.len);
+        assert!(range.start <= range.end);
+        region::protect(
+            self.as_ptr().add(range.start),
+            range.end - range.start,
+            region::Protection::READ,
+        )?;
+        Ok(())
+    }
+
+    /// Makes the specified `range` within this `Mmap` readable and executable.
+    ///
+    /// # Unsafety
+    ///
+    /// The caller must ensure that `range` contains valid machine code and that
+    /// nothing writes to `range` afterwards.
+    pub unsafe fn make_executable(&self, range: Range<usize>) -> Result<()> {
+        assert!(range.start <= self.len);
+        assert!(range.end <= self.len);
+        assert!(range.start <= range.end);
+        region::protect(
+            self.as_ptr().add(range.start),
+            range.end - range.start,
+            region::Protection::READ_EXECUTE,
+        )?;
+        Ok(())
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
@@ -0,0 +1,187 @@
+use crate::Mmap;
+use anyhow::{Context, Result};
+use std::ops::{Deref, DerefMut, Range};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A type akin to `Vec<u8>`, but backed by `mmap` and able to be split.
+///
+/// This type is used to store the compiled images of wasm modules, either
+/// copied into anonymous memory or mapped directly from a file on disk. Using
+/// an `Mmap` as the backing store means that page-aligned portions of the image,
+/// such as its text section, can have their protections changed in place.
+///
+/// Each `MmapVec` owns a unique `range` of its underlying `Mmap`, which can be
+/// shared with other `MmapVec`s created through `split_off`. This is why
+/// mutable access to the contents is safe.
+pub struct MmapVec {
+    mmap: Arc<Mmap>,
+    range: Range<usize>,
+}
+
+impl MmapVec {
+    /// Consumes an existing `mmap` and wraps it up into an `MmapVec`.
+    ///
+    /// The returned `MmapVec` will have the `size` specified, which can be
+    /// smaller than the region mapped by the `Mmap`. The returned `MmapVec`
+    /// will only have at most `size` bytes accessible.
+    pub fn new(mmap: Mmap, size: usize) -> MmapVec {
+        assert!(size <= mmap.len());
+        MmapVec {
+            mmap: Arc::new(mmap),
+            range: 0..size,
+        }
+    }
+
+    /// Creates a new zero-initialized `MmapVec` with the given `size`.
+    ///
+    /// This commits the memory as read-write.
+    pub fn with_capacity(size: usize) -> Result<MmapVec> {
+        Ok(MmapVec::new(Mmap::with_at_least(size)?, size))
+    }
+
+    /// Creates a new `MmapVec` from the contents of an existing `slice`.
+    ///
+    /// A new `MmapVec` is allocated to hold the contents of `slice` and then
+    /// `slice` is copied into the new mmap. It's recommended to avoid this
+    /// method if possible to avoid the need to copy data around.
+    pub fn from_slice(slice: &[u8]) -> Result<MmapVec> {
+        let mut result = MmapVec::with_capacity(slice.len())?;
+        result.copy_from_slice(slice);
+        Ok(result)
+    }
+
+    /// Creates a new `MmapVec` which is the contents of the file at `path`.
+    ///
+    /// The file is mapped privately, so the returned `MmapVec` can be modified
+    /// without the modifications being written back to the file.
+    pub fn from_file(path: &Path) -> Result<MmapVec> {
+        let mmap = Mmap::from_file(path)
+            .with_context(|| format!("failed to create mmap for file: {}", path.display()))?;
+        let len = mmap.len();
+        Ok(MmapVec::new(mmap, len))
+    }
+
+    /// Splits the collection into two at the given index.
+    ///
+    /// Returns a separate `MmapVec` which shares the underlying mapping, but
+    /// only has access to elements from `[at, len)`. After this call `self`
+    /// only has access to elements in `[0, at)`.
+    pub fn split_off(&mut self, at: usize) -> MmapVec {
+        assert!(at <= self.range.len());
+
+        // Create a new `MmapVec` which refers to the same underlying mmap, but
+        // has a disjoint range from ours. Our own range is adjusted to be
+        // disjoint just after `ret` is created.
+        let ret = MmapVec {
+            mmap: self.mmap.clone(),
+            range: self.range.start + at..self.range.end,
+        };
+        self.range.end = self.range.start + at;
+        return ret;
+    }
+
+    /// Returns the underlying mapping of this `MmapVec`.
+    ///
+    /// This can be used to keep the mapping alive, for example, for code
+    /// which has been made executable where it sits.
+    pub fn mmap(&self) -> &Arc<Mmap> {
+        &self.mmap
+    }
+
+    /// Returns the range, within `self.mmap()`, that this `MmapVec` covers.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Makes the specified `range` within this `MmapVec` read-only.
+    ///
+    /// # Unsafety
+    ///
+    /// The caller must ensure that nothing writes to `range` afterwards.
+    pub unsafe fn make_readonly(&self, range: Range<usize>) -> Result<()> {
+        assert!(range.end <= self.range.len());
+        self.mmap
+            .make_readonly(range.start + self.range.start..range.end + self.range.start)
+    }
+
+    /// Makes the specified `range` within this `MmapVec` readable and
+    /// executable.
+    ///
+    /// # Unsafety
+    ///
+    /// The caller must ensure that `range` contains valid machine code and that
+    /// nothing writes to `range` afterwards.
+    pub unsafe fn make_executable(&self, range: Range<usize>) -> Result<()> {
+        assert!(range.end <= self.range.len());
+        self.mmap
+            .make_executable(range.start + self.range.start..range.end + self.range.start)
+    }
+}
+
+impl Deref for MmapVec {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.mmap.as_slice()[self.range.clone()]
+    }
+}
+
+impl DerefMut for MmapVec {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: The underlying mmap is protected behind an `Arc` which means
+        // there there can be many references to it. We are guaranteed, though,
+        // that each reference to the underlying `mmap` has a disjoint `range`
+        // listed that it can access. This means that despite having shared
+        // access to the mmap itself we have exclusive ownership of the bytes
+        // specified in `self.range`. This should help us safely hand out the
+        // mutable access to the bytes.
+        unsafe {
+            let slice = std::slice::from_raw_parts_mut(self.mmap.as_mut_ptr(), self.mmap.len());
+            &mut slice[self.range.clone()]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MmapVec;
+
+    #[test]
+    fn smoke() {
+        let mut mmap = MmapVec::with_capacity(10).unwrap();
+        assert_eq!(mmap.len(), 10);
+        assert_eq!(&mmap[..], &[0; 10]);
+
+        mmap[0] = 1;
+        mmap[2] = 3;
+        assert!(mmap.get(10).is_none());
+        assert_eq!(mmap[0], 1);
+        assert_eq!(mmap[2], 3);
+    }
+
+    #[test]
+    fn split_off() {
+        let mut vec = Vec::from([1, 2, 3, 4]);
+        let mut mmap = MmapVec::from_slice(&vec).unwrap();
+        assert_eq!(&mmap[..], &vec[..]);
+        // remove nothing
+        assert_eq!(
+            &mmap.split_off(vec.len())[..],
+            &vec.split_off(vec.len())[..]
+        );
+        assert_eq!(&mmap[..], &vec[..]);
+        // remove 1 element
+        assert_eq!(
+            &mmap.split_off(vec.len() - 1)[..],
+            &vec.split_off(vec.len() - 1)[..]
+        );
+        assert_eq!(&mmap[..], &vec[..]);
+        // remove 2 elements
+        assert_eq!(
+            &mmap.split_off(vec.len() - 2)[..],
+            &vec.split_off(vec.len() - 2)[..]
+        );
+        assert_eq!(&mmap[..], &vec[..]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Implementation of the parking lot used by `memory.atomic.wait` and
+//! `memory.atomic.notify`.
+//!
+//! Threads which wait on an address of a shared linear memory are "parked" in
+//! a queue keyed by that address until another thread notifies the same
+//! address or the wait times out. Waiters for a given address are woken in
+//! the order that they started waiting.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+/// The result of parking a thread with [`ParkingSpot::park`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParkResult {
+    /// The thread was woken up by a call to [`ParkingSpot::unpark`].
+    Unparked,
+    /// The `validate` callback returned `false` so the thread never parked.
+    Invalid,
+    /// The timeout elapsed before the thread was woken up.
+    TimedOut,
+}
+
+/// A set of queues of parked threads, keyed by address.
+#[derive(Default, Debug)]
+pub struct ParkingSpot {
+    inner: Mutex<HashMap<u64, VecDeque<Arc<Waiter>>>>,
+}
+
+#[derive(Default, Debug)]
+struct Waiter {
+    cvar: Condvar,
+    notified: AtomicBool,
+}
+
+impl ParkingSpot {
+    /// Parks the current thread on `key` until it is unparked or `deadline`,
+    /// if specified, is reached.
+    ///
+    /// The `validate` callback is invoked while the internal lock is held, and
+    /// the thread only parks if it returns `true`. This guarantees that no
+    /// call to `unpark` for the same key can be missed between the check
+    /// performed by `validate` and the thread going to sleep.
+    pub fn park(
+        &self,
+        key: u64,
+        validate: impl FnOnce() -> bool,
+        deadline: Option<Instant>,
+    ) -> ParkResult {
+        let mut inner = self.inner.lock().unwrap();
+        if !validate() {
+            return ParkResult::Invalid;
+        }
+
+        let waiter = Arc::new(Waiter::default());
+        inner.entry(key).or_default().push_back(waiter.clone());
+
+        loop {
+            inner = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now < deadline {
+                        waiter.cvar.wait_timeout(inner, deadline - now).unwrap().0
+                    } else {
+                        inner
+                    }
+                }
+                None => waiter.cvar.wait(inner).unwrap(),
+            };
+
+            if waiter.notified.load(Ordering::SeqCst) {
+                return ParkResult::Unparked;
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    // We were never notified, so we're still in the queue and
+                    // need to remove ourselves from it.
+                    let queue = inner.get_mut(&key).unwrap();
+                    queue.retain(|w| !Arc::ptr_eq(w, &waiter));
+                    if queue.is_empty() {
+                        inner.remove(&key);
+                    }
+                    return ParkResult::TimedOut;
+                }
+            }
+        }
+    }
+
+    /// Unparks at most `count` threads parked on `key`, returning how many
+    /// threads were actually woken up.
+    pub fn unpark(&self, key: u64, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let queue = match inner.get_mut(&key) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+
+        let mut unparked = 0;
+        while unparked < count {
+            let waiter = match queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            waiter.notified.store(true, Ordering::SeqCst);
+            waiter.cvar.notify_one();
+            unparked += 1;
+        }
+
+        if queue.is_empty() {
+            inner.remove(&key);
+        }
+        unparked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParkResult, ParkingSpot};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn invalid_does_not_park() {
+        let spot = ParkingSpot::default();
+        assert_eq!(spot.park(0, || false, None), ParkResult::Invalid);
+        assert_eq!(spot.unpark(0, u32::MAX), 0);
+    }
+
+    #[test]
+    fn times_out() {
+        let spot = ParkingSpot::default();
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert_eq!(spot.park(0, || true, Some(deadline)), ParkResult::TimedOut);
+        assert_eq!(spot.unpark(0, u32::MAX), 0);
+    }
+
+    #[test]
+    fn unpark_wakes_waiters() {
+        let spot = Arc::new(ParkingSpot::default());
+        let parked = Arc::new(AtomicU32::new(0));
+        let threads = (0..4)
+            .map(|_| {
+                let spot = spot.clone();
+                let parked = parked.clone();
+                thread::spawn(move || {
+                    spot.park(
+                        1,
+                        || {
+                            parked.fetch_add(1, Ordering::SeqCst);
+                            true
+                        },
+                        None,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Wait for every thread to be parked before waking them up in two
+        // batches.
+        while parked.load(Ordering::SeqCst) != 4 {
+            thread::yield_now();
+        }
+        assert_eq!(spot.unpark(2, 4), 0);
+        assert_eq!(spot.unpark(1, 3), 3);
+        assert_eq!(spot.unpark(1, 3), 1);
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), ParkResult::Unparked);
+        }
+    }
+}
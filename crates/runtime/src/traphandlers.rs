@@ -1,12 +1,12 @@
 //! WebAssembly trap handling, which is built on top of the lower-level
 //! signalhandling mechanisms.
 
-use crate::{VMContext, VMInterrupts};
+use crate::{ModuleInfoLookup, VMContext, VMInterrupts};
 use backtrace::Backtrace;
 use std::any::Any;
 use std::cell::{Cell, UnsafeCell};
 use std::error::Error;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::ptr;
 use std::sync::atomic::Ordering::SeqCst;
 use std::sync::Once;
@@ -24,6 +24,7 @@ extern "C" {
         callee: *mut VMContext,
     ) -> i32;
     fn wasmtime_longjmp(jmp_buf: *const u8) -> !;
+    fn wasmtime_resume(pc: usize, sp: usize, fp: usize) -> !;
 }
 
 cfg_if::cfg_if! {
@@ -98,6 +99,55 @@ pub unsafe fn raise_lib_trap(trap: Trap) -> ! {
     tls::with(|info| info.unwrap().unwind_with(UnwindReason::LibTrap(trap)))
 }
 
+/// Unwinds the stack to the landing pad of the youngest wasm frame that has one
+/// for the call it's making, which then handles the exception that's pending
+/// in the store. `modules` must know about all wasm code on the stack.
+///
+/// Host frames are skipped until the youngest wasm frame, but unwinding stops
+/// at the next host frame after that, which is where wasm was entered from the
+/// host. If no wasm frame in between has a landing pad then this raises
+/// `Trap::Exception` instead, and the exception is left pending.
+///
+/// # Safety
+///
+/// Only safe to call when wasm code is on the stack, aka `catch_traps` must
+/// have been previously called. Additionally no Rust destructors can be on the
+/// stack. They will be skipped and not executed.
+pub unsafe fn raise_exception(modules: &dyn ModuleInfoLookup) -> ! {
+    // The landing pad of the previous (younger) wasm frame, if it has one, and
+    // the frame pointer, stack pointer and pc to resume there with. A frame's
+    // frame pointer is two words below its CFA, which is the SP reported for
+    // the next older frame (see `gc`), so the landing pad is only resumed once
+    // we reach the frame after it.
+    let mut handler = None;
+    let mut resume = None;
+    let mut found_wasm = false;
+
+    backtrace::trace(|frame| {
+        if let Some((landing_pad, sp_offset)) = handler {
+            let fp = frame.sp() as usize - 2 * mem::size_of::<usize>();
+            resume = Some((landing_pad, fp - sp_offset, fp));
+            return false;
+        }
+        let pc = frame.ip() as usize;
+        match modules.lookup(pc) {
+            Some(module) => {
+                found_wasm = true;
+                handler = module.lookup_exception_handler(pc);
+                true
+            }
+            None => !found_wasm,
+        }
+    });
+
+    match resume {
+        Some((pc, sp, fp)) => wasmtime_resume(pc, sp, fp),
+        None => raise_lib_trap(Trap::Exception {
+            backtrace: Backtrace::new_unresolved(),
+        }),
+    }
+}
+
 /// Carries a Rust panic across wasm code and resumes the panic on the other
 /// side.
 ///
@@ -141,6 +191,13 @@ pub enum Trap {
         /// Native stack backtrace at the time the OOM occurred
         backtrace: Backtrace,
     },
+
+    /// A trap indicating that an exception unwound all wasm frames since wasm
+    /// was entered without being caught. The exception is left pending.
+    Exception {
+        /// Native stack backtrace at the time unwinding reached the host
+        backtrace: Backtrace,
+    },
 }
 
 impl Trap {
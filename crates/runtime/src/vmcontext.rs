@@ -60,6 +60,26 @@ mod test_vmfunction_import {
 #[repr(C)]
 pub struct VMFunctionBody(u8);
 
+impl VMFunctionBody {
+    /// Returns the address to call the compiled code in `body` through.
+    ///
+    /// Cranelift generates Thumb-2 code for 32-bit ARM, where indirect calls
+    /// only enter Thumb state if the address has its low bit set. Everywhere
+    /// else this is `body` itself.
+    ///
+    /// # Unsafety
+    ///
+    /// `body` must point to compiled code.
+    pub unsafe fn entry(body: *mut [VMFunctionBody]) -> *mut [VMFunctionBody] {
+        if cfg!(target_arch = "arm") {
+            let start = ((*body).as_ptr() as usize | 1) as *mut VMFunctionBody;
+            std::ptr::slice_from_raw_parts_mut(start, (*body).len())
+        } else {
+            body
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_vmfunction_body {
     use super::VMFunctionBody;
@@ -631,6 +651,18 @@ impl VMBuiltinFunctionsArray {
         ptrs[BuiltinFunctionIndex::memory_atomic_wait64().index() as usize] =
             wasmtime_memory_atomic_wait64 as usize;
         ptrs[BuiltinFunctionIndex::out_of_gas().index() as usize] = wasmtime_out_of_gas as usize;
+        ptrs[BuiltinFunctionIndex::new_epoch().index() as usize] = wasmtime_new_epoch as usize;
+        ptrs[BuiltinFunctionIndex::debug_hook().index() as usize] = wasmtime_debug_hook as usize;
+        ptrs[BuiltinFunctionIndex::throw_exception().index() as usize] =
+            wasmtime_throw_exception as usize;
+        ptrs[BuiltinFunctionIndex::exception_matches().index() as usize] =
+            wasmtime_exception_matches as usize;
+        ptrs[BuiltinFunctionIndex::catch_exception().index() as usize] =
+            wasmtime_catch_exception as usize;
+        ptrs[BuiltinFunctionIndex::rethrow_exception().index() as usize] =
+            wasmtime_rethrow_exception as usize;
+        ptrs[BuiltinFunctionIndex::resume_exception().index() as usize] =
+            wasmtime_resume_exception as usize;
 
         if cfg!(debug_assertions) {
             for i in 0..ptrs.len() {
@@ -687,6 +719,11 @@ pub struct VMInterrupts {
     /// modules. For more information see `crates/environ/src/cranelift.rs`.
     pub stack_limit: AtomicUsize,
 
+    /// Padding to align `fuel_consumed` to 8 bytes on 32-bit hosts, as it is
+    /// on every target the compiler supports.
+    #[cfg(target_pointer_width = "32")]
+    _padding: u32,
+
     /// Indicator of how much fuel has been consumed and is remaining to
     /// WebAssembly.
     ///
@@ -694,15 +731,27 @@ pub struct VMInterrupts {
     /// turning positive a wasm trap will be generated. This field is only
     /// modified if wasm is configured to consume fuel.
     pub fuel_consumed: UnsafeCell<i64>,
+
+    /// Deadline epoch for interruption: if epoch-based interruption
+    /// is enabled and the global (per engine) epoch counter is
+    /// observed to reach or exceed this value, the guest code will
+    /// yield if running asynchronously, or trap otherwise.
+    pub epoch_deadline: UnsafeCell<u64>,
+
+    /// Whether code compiled with guest debugging support should call into
+    /// the runtime's debug hook before each wasm instruction. This is nonzero
+    /// when a store is single-stepping or has breakpoints set.
+    pub debug_hooks: UnsafeCell<u32>,
 }
 
 // The `VMInterrupts` type is a pod-type with no destructor, and we only access
 // `stack_limit` from other threads, so add in these trait impls which are
-// otherwise not available due to the `fuel_consumed` variable in
-// `VMInterrupts`.
+// otherwise not available due to the `fuel_consumed`, `epoch_deadline` and
+// `debug_hooks` variables in `VMInterrupts`.
 //
-// Note that users of `fuel_consumed` understand that the unsafety encompasses
-// ensuring that it's only mutated/accessed from one thread dynamically.
+// Note that users of those variables understand that the
+// unsafety encompasses ensuring that they're only mutated/accessed from one
+// thread dynamically.
 unsafe impl Send for VMInterrupts {}
 unsafe impl Sync for VMInterrupts {}
 
@@ -718,7 +767,11 @@ impl Default for VMInterrupts {
     fn default() -> VMInterrupts {
         VMInterrupts {
             stack_limit: AtomicUsize::new(usize::max_value()),
+            #[cfg(target_pointer_width = "32")]
+            _padding: 0,
             fuel_consumed: UnsafeCell::new(0),
+            epoch_deadline: UnsafeCell::new(0),
+            debug_hooks: UnsafeCell::new(0),
         }
     }
 }
@@ -738,6 +791,58 @@ mod test_vminterrupts {
             offset_of!(VMInterrupts, stack_limit),
             usize::from(offsets.vminterrupts_stack_limit())
         );
+        assert_eq!(
+            offset_of!(VMInterrupts, fuel_consumed),
+            usize::from(offsets.vminterrupts_fuel_consumed())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, epoch_deadline),
+            usize::from(offsets.vminterrupts_epoch_deadline())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, debug_hooks),
+            usize::from(offsets.vminterrupts_debug_hooks())
+        );
+    }
+}
+
+/// A wasm local or operand stack value passed to the runtime's debug hook by
+/// code compiled with guest debugging support.
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+pub struct VMDebugValue {
+    /// The type of `value`, as encoded in the wasm binary format, for example
+    /// `0x7f` for `i32`.
+    pub ty: u32,
+    _padding: [u8; 12],
+    /// The value itself, stored in the low bytes as its in-memory
+    /// representation.
+    pub value: [u8; 16],
+}
+
+#[cfg(test)]
+mod test_vmdebug_value {
+    use super::VMDebugValue;
+    use memoffset::offset_of;
+    use std::mem::size_of;
+    use wasmtime_environ::{Module, VMOffsets};
+
+    #[test]
+    fn check_vmdebug_value_offsets() {
+        let module = Module::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+        assert_eq!(
+            size_of::<VMDebugValue>(),
+            usize::from(offsets.size_of_vmdebug_value())
+        );
+        assert_eq!(
+            offset_of!(VMDebugValue, ty),
+            usize::from(offsets.vmdebug_value_ty())
+        );
+        assert_eq!(
+            offset_of!(VMDebugValue, value),
+            usize::from(offsets.vmdebug_value_value())
+        );
     }
 }
 
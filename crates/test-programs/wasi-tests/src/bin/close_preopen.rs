@@ -7,23 +7,24 @@ unsafe fn test_close_preopen(dir_fd: wasi::Fd) {
 
     assert_gt!(dir_fd, pre_fd, "dir_fd number");
 
-    // Try to close a preopened directory handle.
-    assert_errno!(
-        wasi::fd_close(pre_fd)
-            .expect_err("closing a preopened file descriptor")
-            .raw_error(),
-        wasi::ERRNO_NOTSUP
-    );
+    // Renumber a preopened directory handle over another handle.
+    wasi::fd_renumber(pre_fd, dir_fd).expect("renumbering a preopened file descriptor");
 
-    // Try to renumber over a preopened directory handle.
+    // Ensure that pre_fd is closed.
     assert_errno!(
-        wasi::fd_renumber(dir_fd, pre_fd)
-            .expect_err("renumbering over a preopened file descriptor")
+        wasi::fd_prestat_get(pre_fd)
+            .expect_err("getting the prestat of a renumbered preopen")
             .raw_error(),
-        wasi::ERRNO_NOTSUP
+        wasi::ERRNO_BADF
     );
 
-    // Ensure that dir_fd is still open.
+    // Ensure that dir_fd is now the preopen.
+    let prestat = wasi::fd_prestat_get(dir_fd).expect("failed fd_prestat_get");
+    assert_eq!(
+        prestat.tag,
+        wasi::PREOPENTYPE_DIR,
+        "expected the renumbered preopen to be a directory",
+    );
     let dir_fdstat = wasi::fd_fdstat_get(dir_fd).expect("failed fd_fdstat_get");
     assert_eq!(
         dir_fdstat.fs_filetype,
@@ -31,20 +32,25 @@ unsafe fn test_close_preopen(dir_fd: wasi::Fd) {
         "expected the scratch directory to be a directory",
     );
 
-    // Try to renumber a preopened directory handle.
+    // Renumber it back.
+    wasi::fd_renumber(dir_fd, pre_fd).expect("renumbering a preopened file descriptor");
+    wasi::fd_prestat_get(pre_fd).expect("failed fd_prestat_get");
+
+    // Close the preopened directory handle.
+    wasi::fd_close(pre_fd).expect("closing a preopened file descriptor");
+
+    // Ensure that pre_fd is closed.
     assert_errno!(
-        wasi::fd_renumber(pre_fd, dir_fd)
-            .expect_err("renumbering over a preopened file descriptor")
+        wasi::fd_prestat_get(pre_fd)
+            .expect_err("getting the prestat of a closed preopen")
             .raw_error(),
-        wasi::ERRNO_NOTSUP
+        wasi::ERRNO_BADF
     );
-
-    // Ensure that dir_fd is still open.
-    let dir_fdstat = wasi::fd_fdstat_get(dir_fd).expect("failed fd_fdstat_get");
-    assert_eq!(
-        dir_fdstat.fs_filetype,
-        wasi::FILETYPE_DIRECTORY,
-        "expected the scratch directory to be a directory",
+    assert_errno!(
+        wasi::fd_close(pre_fd)
+            .expect_err("closing an already closed preopen")
+            .raw_error(),
+        wasi::ERRNO_BADF
     );
 }
 
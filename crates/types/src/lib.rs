@@ -197,6 +197,8 @@ pub enum EntityIndex {
     Module(ModuleIndex),
     /// Instance index.
     Instance(InstanceIndex),
+    /// Tag index.
+    Tag(TagIndex),
 }
 
 /// A type of an item in a wasm module where an item is typically something that
@@ -321,17 +323,9 @@ impl From<wasmparser::MemoryType> for Memory {
     }
 }
 
-/// WebAssembly event.
+/// WebAssembly exception tag.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Tag {
-    /// The event signature type.
-    pub ty: TypeIndex,
-}
-
-impl From<wasmparser::TagType> for Tag {
-    fn from(ty: wasmparser::TagType) -> Tag {
-        Tag {
-            ty: TypeIndex::from_u32(ty.type_index),
-        }
-    }
+    /// The signature of the tag; its parameters are the exception's payload.
+    pub ty: SignatureIndex,
 }
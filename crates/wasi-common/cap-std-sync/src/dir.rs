@@ -310,7 +310,7 @@ fn convert_systimespec(t: Option<wasi_common::SystemTimeSpec>) -> Option<SystemT
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::Dir;
     use cap_std::ambient_authority;
     #[test]
@@ -389,7 +389,9 @@ mod test {
         );
     }
 
-    fn run<F: std::future::Future>(future: F) -> F::Output {
+    /// Runs a future which never waits, as the `WasiFile` and `WasiDir`
+    /// impls of this crate are all synchronous.
+    pub(crate) fn run<F: std::future::Future>(future: F) -> F::Output {
         use std::pin::Pin;
         use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 
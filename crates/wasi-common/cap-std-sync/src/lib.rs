@@ -14,6 +14,9 @@
 //! `cap_std::fs::Dir`, and provides convenience methods for inheriting the
 //! parent process's stdio, args, and env.
 //!
+//! The `net` module wraps `cap_std`'s TCP and Unix sockets as `WasiFile`s,
+//! which can be handed to a guest with `WasiCtxBuilder::preopened_socket`.
+//!
 //! For the convenience of consumers, `cap_std::fs::Dir` is re-exported from
 //! this crate. This saves consumers tracking an additional dep on the exact
 //! version of cap_std used by this crate, if they want to avoid it.
@@ -34,17 +37,21 @@
 pub mod clocks;
 pub mod dir;
 pub mod file;
+pub mod manifest;
+pub mod net;
 pub mod sched;
 pub mod stdio;
 
 pub use cap_std::ambient_authority;
 pub use cap_std::fs::Dir;
 pub use clocks::clocks_ctx;
+pub use manifest::Manifest;
 pub use sched::sched_ctx;
 
+use anyhow::Context;
 use cap_rand::RngCore;
 use std::path::Path;
-use wasi_common::{table::Table, Error, WasiCtx, WasiFile};
+use wasi_common::{dir::DirCaps, file::FileCaps, table::Table, Error, WasiCtx, WasiFile};
 
 pub struct WasiCtxBuilder(WasiCtx);
 
@@ -118,6 +125,65 @@ impl WasiCtxBuilder {
         self.0.push_preopened_dir(dir, guest_path)?;
         Ok(self)
     }
+    /// Like `preopened_dir`, but limits what the guest can do in `dir`; see
+    /// [`manifest::Access::caps`] for read-only capabilities.
+    pub fn preopened_dir_with_caps(
+        mut self,
+        dir: Dir,
+        caps: DirCaps,
+        file_caps: FileCaps,
+        guest_path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        let dir = Box::new(crate::dir::Dir::from_cap_std(dir));
+        self.0
+            .push_preopened_dir_with_caps(dir, caps, file_caps, guest_path)?;
+        Ok(self)
+    }
+    /// Inserts `socket` into the table at `fd`. Guests can't discover
+    /// sockets like preopened directories, so `fd` has to be agreed upon
+    /// with the guest, and should come after any preopened directories.
+    pub fn preopened_socket(mut self, fd: u32, socket: impl Into<net::Socket>) -> Self {
+        let socket: Box<dyn WasiFile> = socket.into().into();
+        let caps = FileCaps::FDSTAT_SET_FLAGS
+            | FileCaps::FILESTAT_GET
+            | FileCaps::READ
+            | FileCaps::POLL_READWRITE;
+        self.0.insert_file(fd, socket, caps);
+        self
+    }
+    /// Limits the number of descriptors the guest can have open, counting
+    /// stdio and preopens. Once it has that many, `path_open` and
+    /// `sock_accept` fail with `EMFILE`; the preopens themselves are always
+    /// added, whether they come before or after this.
+    pub fn fd_limit(mut self, limit: u32) -> Self {
+        self.0.table().set_limit(limit);
+        self
+    }
+    /// Adds the arguments, environment, directories and descriptor limit
+    /// declared by `manifest`. The module's file name is passed as the first
+    /// argument, as a command line would.
+    pub fn manifest(mut self, manifest: &Manifest) -> Result<Self, anyhow::Error> {
+        let name = manifest
+            .module
+            .as_ref()
+            .and_then(|module| module.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        self = self.arg(name)?.args(&manifest.args)?;
+        for (var, value) in manifest.env.iter() {
+            self = self.env(var, value)?;
+        }
+        for dir in manifest.dirs.iter() {
+            let (caps, file_caps) = dir.access.caps();
+            let host = Dir::open_ambient_dir(&dir.host, ambient_authority())
+                .with_context(|| format!("failed to open directory '{}'", dir.host.display()))?;
+            self = self.preopened_dir_with_caps(host, caps, file_caps, dir.guest_path())?;
+        }
+        if let Some(limit) = manifest.fd_limit {
+            self = self.fd_limit(limit);
+        }
+        Ok(self)
+    }
     pub fn build(self) -> WasiCtx {
         self.0
     }
@@ -0,0 +1,274 @@
+//! A manifest declares how a WASI program is run in a TOML file, so that the
+//! `wasmtime run --manifest` command and embedders using
+//! [`WasiCtxBuilder::manifest`](crate::WasiCtxBuilder::manifest) agree on it:
+//!
+//! ```toml
+//! module = "app.wasm"
+//! args = ["--verbose"]
+//! wasi-modules = "wasi-common"
+//! fuel = 1000000
+//! timeout = "10s"
+//! fd-limit = 64
+//!
+//! [env]
+//! LOG = "debug"
+//!
+//! [[dir]]
+//! guest = "/data"
+//! host = "data"
+//! access = "read-only"
+//!
+//! [[preload]]
+//! name = "libc"
+//! path = "libc.wasm"
+//!
+//! [limits]
+//! memory-size = 16777216
+//! instances = 1
+//! ```
+//!
+//! Only the WASI context is built from a manifest by this crate; the module,
+//! preloads, limits, fuel, timeout and WASI modules are for the embedder to
+//! apply.
+
+use anyhow::{Context, Result};
+use serde::{de::Deserializer, Deserialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wasi_common::{dir::DirCaps, file::FileCaps};
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Manifest {
+    /// The module to run.
+    pub module: Option<PathBuf>,
+    /// The arguments passed to the module, following its name.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The environment variables passed to the module.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The host directories the module can access.
+    #[serde(default, rename = "dir")]
+    pub dirs: Vec<MappedDir>,
+    /// The modules to link, in order, before the main module.
+    #[serde(default, rename = "preload")]
+    pub preloads: Vec<Preload>,
+    /// Limits on the resources the module can create.
+    #[serde(default)]
+    pub limits: Limits,
+    /// The fuel the module can consume before it traps.
+    pub fuel: Option<u64>,
+    /// How long the module can run before it's interrupted, e.g. `"100ms"`.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// The WASI modules to enable, written like `--wasi-modules`.
+    pub wasi_modules: Option<String>,
+    /// The number of descriptors the module can have open.
+    pub fd_limit: Option<u32>,
+}
+
+/// A host directory preopened for the module.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MappedDir {
+    /// The path the module sees; this is the host path if it's missing.
+    pub guest: Option<String>,
+    pub host: PathBuf,
+    #[serde(default)]
+    pub access: Access,
+}
+
+impl MappedDir {
+    pub fn guest_path(&self) -> String {
+        match &self.guest {
+            Some(guest) => guest.clone(),
+            None => self.host.display().to_string(),
+        }
+    }
+}
+
+/// What the module can do in a [`MappedDir`].
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Access {
+    /// The module can list the directory and read its files, but can't
+    /// change anything in it.
+    ReadOnly,
+    #[default]
+    ReadWrite,
+}
+
+impl Access {
+    /// Returns the capabilities of the directory and of the files opened in
+    /// it.
+    pub fn caps(self) -> (DirCaps, FileCaps) {
+        match self {
+            Access::ReadOnly => (
+                DirCaps::OPEN
+                    | DirCaps::READDIR
+                    | DirCaps::READLINK
+                    | DirCaps::PATH_FILESTAT_GET
+                    | DirCaps::FILESTAT_GET,
+                FileCaps::READ
+                    | FileCaps::SEEK
+                    | FileCaps::TELL
+                    | FileCaps::ADVISE
+                    | FileCaps::FDSTAT_SET_FLAGS
+                    | FileCaps::FILESTAT_GET
+                    | FileCaps::POLL_READWRITE,
+            ),
+            Access::ReadWrite => (DirCaps::all(), FileCaps::all()),
+        }
+    }
+}
+
+/// A module linked under `name` before the main module.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Preload {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Mirrors the settings of wasmtime's `StoreLimitsBuilder`; missing limits
+/// keep their defaults.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Limits {
+    pub memory_size: Option<usize>,
+    pub table_elements: Option<u32>,
+    pub instances: Option<usize>,
+    pub tables: Option<usize>,
+    pub memories: Option<usize>,
+}
+
+impl Limits {
+    /// Whether any limit is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+impl Manifest {
+    /// Parses a manifest; relative paths in it are left as they are.
+    pub fn parse(toml: &str) -> Result<Manifest> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Reads the manifest at `path`. Relative paths in it are resolved
+    /// against the directory containing the manifest, so it can be used
+    /// from anywhere.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Manifest> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest `{}`", path.display()))?;
+        let mut manifest = Manifest::parse(&contents)
+            .with_context(|| format!("failed to parse manifest `{}`", path.display()))?;
+        if let Some(base) = path.parent() {
+            manifest.resolve(base);
+        }
+        Ok(manifest)
+    }
+
+    fn resolve(&mut self, base: &Path) {
+        if let Some(module) = &mut self.module {
+            *module = base.join(&module);
+        }
+        for dir in self.dirs.iter_mut() {
+            // The guest sees the path as it's written in the manifest.
+            if dir.guest.is_none() {
+                dir.guest = Some(dir.guest_path());
+            }
+            dir.host = base.join(&dir.host);
+        }
+        for preload in self.preloads.iter_mut() {
+            preload.path = base.join(&preload.path);
+        }
+    }
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let manifest = Manifest::parse(
+            r#"
+            module = "app.wasm"
+            args = ["--verbose"]
+            fuel = 1000
+            timeout = "1m 30s"
+
+            [env]
+            LOG = "debug"
+
+            [[dir]]
+            guest = "/data"
+            host = "data"
+            access = "read-only"
+
+            [[dir]]
+            host = "tmp"
+
+            [[preload]]
+            name = "libc"
+            path = "libc.wasm"
+
+            [limits]
+            memory-size = 65536
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.module, Some(PathBuf::from("app.wasm")));
+        assert_eq!(manifest.args, ["--verbose"]);
+        assert_eq!(manifest.env["LOG"], "debug");
+        assert_eq!(manifest.fuel, Some(1000));
+        assert_eq!(manifest.timeout, Some(Duration::from_secs(90)));
+        assert_eq!(manifest.dirs[0].guest_path(), "/data");
+        assert_eq!(manifest.dirs[0].access, Access::ReadOnly);
+        assert_eq!(manifest.dirs[1].guest_path(), "tmp");
+        assert_eq!(manifest.dirs[1].access, Access::ReadWrite);
+        assert_eq!(manifest.preloads[0].name, "libc");
+        assert_eq!(manifest.limits.memory_size, Some(65536));
+        assert_eq!(manifest.limits.instances, None);
+        assert_eq!(manifest.wasi_modules, None);
+
+        assert_eq!(Manifest::parse("").unwrap(), Manifest::default());
+        assert!(Manifest::parse("modules = \"app.wasm\"").is_err());
+        assert!(Manifest::parse("timeout = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let mut manifest = Manifest::parse(
+            r#"
+            module = "app.wasm"
+            [[dir]]
+            host = "data"
+            [[dir]]
+            guest = "/tmp"
+            host = "/var/tmp"
+            "#,
+        )
+        .unwrap();
+        manifest.resolve(Path::new("/srv/app"));
+        assert_eq!(manifest.module, Some(PathBuf::from("/srv/app/app.wasm")));
+        assert_eq!(manifest.dirs[0].guest_path(), "data");
+        assert_eq!(manifest.dirs[0].host, Path::new("/srv/app/data"));
+        assert_eq!(manifest.dirs[1].guest_path(), "/tmp");
+        assert_eq!(manifest.dirs[1].host, Path::new("/var/tmp"));
+    }
+}
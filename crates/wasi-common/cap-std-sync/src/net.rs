@@ -0,0 +1,423 @@
+use crate::file::from_sysif_fdflags;
+#[cfg(unix)]
+use io_lifetimes::{AsFd, BorrowedFd};
+#[cfg(windows)]
+use io_lifetimes::{AsSocket, BorrowedSocket};
+use std::any::Any;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use system_interface::{
+    fs::GetSetFdFlags,
+    io::{Peek, ReadReady},
+};
+use wasi_common::{
+    file::{Advice, FdFlags, FileType, Filestat, RiFlags, RoFlags, SdFlags, WasiFile},
+    Error, ErrorExt,
+};
+
+/// A socket which can be inserted into a `WasiCtx`, typically with
+/// `WasiCtxBuilder::preopened_socket`.
+pub enum Socket {
+    TcpListener(cap_std::net::TcpListener),
+    TcpStream(cap_std::net::TcpStream),
+    #[cfg(unix)]
+    UnixListener(cap_std::os::unix::net::UnixListener),
+    #[cfg(unix)]
+    UnixStream(cap_std::os::unix::net::UnixStream),
+}
+
+impl From<cap_std::net::TcpListener> for Socket {
+    fn from(listener: cap_std::net::TcpListener) -> Self {
+        Socket::TcpListener(listener)
+    }
+}
+
+impl From<cap_std::net::TcpStream> for Socket {
+    fn from(stream: cap_std::net::TcpStream) -> Self {
+        Socket::TcpStream(stream)
+    }
+}
+
+#[cfg(unix)]
+impl From<cap_std::os::unix::net::UnixListener> for Socket {
+    fn from(listener: cap_std::os::unix::net::UnixListener) -> Self {
+        Socket::UnixListener(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<cap_std::os::unix::net::UnixStream> for Socket {
+    fn from(stream: cap_std::os::unix::net::UnixStream) -> Self {
+        Socket::UnixStream(stream)
+    }
+}
+
+impl From<Socket> for Box<dyn WasiFile> {
+    fn from(socket: Socket) -> Self {
+        match socket {
+            Socket::TcpListener(listener) => Box::new(TcpListener::from_cap_std(listener)),
+            Socket::TcpStream(stream) => Box::new(TcpStream::from_cap_std(stream)),
+            #[cfg(unix)]
+            Socket::UnixListener(listener) => Box::new(UnixListener::from_cap_std(listener)),
+            #[cfg(unix)]
+            Socket::UnixStream(stream) => Box::new(UnixStream::from_cap_std(stream)),
+        }
+    }
+}
+
+// Implements `WasiFile` for a socket type with the given socket-specific
+// methods, in addition to the operations sockets share with other files.
+// This is a single macro, rather than one for the shared methods, because
+// `async_trait` has to see every method of the impl.
+macro_rules! wasi_socket_impl {
+    ($ty:ty, { $($methods:tt)* }) => {
+        #[async_trait::async_trait]
+        impl WasiFile for $ty {
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            async fn datasync(&self) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn sync(&self) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn get_filetype(&self) -> Result<FileType, Error> {
+                Ok(FileType::SocketStream)
+            }
+            async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+                let fdflags = self.0.get_fd_flags()?;
+                Ok(from_sysif_fdflags(fdflags))
+            }
+            async fn set_fdflags(&mut self, fdflags: FdFlags) -> Result<(), Error> {
+                if fdflags == FdFlags::NONBLOCK {
+                    self.0.set_nonblocking(true)?;
+                } else if fdflags.is_empty() {
+                    self.0.set_nonblocking(false)?;
+                } else {
+                    return Err(Error::invalid_argument()
+                        .context("cannot set anything but NONBLOCK on a socket"));
+                }
+                Ok(())
+            }
+            async fn get_filestat(&self) -> Result<Filestat, Error> {
+                Ok(Filestat {
+                    device_id: 0,
+                    inode: 0,
+                    filetype: self.get_filetype().await?,
+                    nlink: 0,
+                    size: 0,
+                    atim: None,
+                    mtim: None,
+                    ctim: None,
+                })
+            }
+            async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn advise(&self, _offset: u64, _len: u64, _advice: Advice) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn allocate(&self, _offset: u64, _len: u64) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn set_times(
+                &self,
+                _atime: Option<wasi_common::SystemTimeSpec>,
+                _mtime: Option<wasi_common::SystemTimeSpec>,
+            ) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn read_vectored_at<'a>(
+                &self,
+                _bufs: &mut [io::IoSliceMut<'a>],
+                _offset: u64,
+            ) -> Result<u64, Error> {
+                Err(Error::seek_pipe())
+            }
+            async fn write_vectored_at<'a>(
+                &self,
+                _bufs: &[io::IoSlice<'a>],
+                _offset: u64,
+            ) -> Result<u64, Error> {
+                Err(Error::seek_pipe())
+            }
+            async fn seek(&self, _pos: std::io::SeekFrom) -> Result<u64, Error> {
+                Err(Error::seek_pipe())
+            }
+            async fn readable(&self) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+            async fn writable(&self) -> Result<(), Error> {
+                Err(Error::badf())
+            }
+
+            $($methods)*
+        }
+
+        #[cfg(unix)]
+        impl AsFd for $ty {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.0.as_fd()
+            }
+        }
+        #[cfg(windows)]
+        impl AsSocket for $ty {
+            fn as_socket(&self) -> BorrowedSocket<'_> {
+                self.0.as_socket()
+            }
+        }
+    };
+}
+
+macro_rules! wasi_listener_impl {
+    ($ty:ident, $inner:ty, $stream:ident) => {
+        pub struct $ty($inner);
+
+        impl $ty {
+            pub fn from_cap_std(listener: $inner) -> Self {
+                $ty(listener)
+            }
+
+            /// Accepts a new connection, whose socket has `fdflags` set.
+            pub fn accept(&self, fdflags: FdFlags) -> Result<$stream, Error> {
+                let (stream, _) = self.0.accept()?;
+                let stream = $stream::from_cap_std(stream);
+                if fdflags == FdFlags::NONBLOCK {
+                    stream.0.set_nonblocking(true)?;
+                } else if !fdflags.is_empty() {
+                    return Err(Error::invalid_argument()
+                        .context("cannot set anything but NONBLOCK on a socket"));
+                }
+                Ok(stream)
+            }
+        }
+
+        wasi_socket_impl!($ty, {
+            async fn read_vectored<'a>(
+                &self,
+                _bufs: &mut [io::IoSliceMut<'a>],
+            ) -> Result<u64, Error> {
+                Err(Error::badf())
+            }
+            async fn write_vectored<'a>(&self, _bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+                Err(Error::badf())
+            }
+            async fn peek(&self, _buf: &mut [u8]) -> Result<u64, Error> {
+                Err(Error::badf())
+            }
+            async fn num_ready_bytes(&self) -> Result<u64, Error> {
+                // A listener which polls as readable has a connection to
+                // accept, rather than bytes to read.
+                Ok(1)
+            }
+            async fn sock_accept(&self, fdflags: FdFlags) -> Result<Box<dyn WasiFile>, Error> {
+                Ok(Box::new(self.accept(fdflags)?))
+            }
+        });
+    };
+}
+
+macro_rules! wasi_stream_impl {
+    ($ty:ident, $inner:ty) => {
+        pub struct $ty($inner);
+
+        impl $ty {
+            pub fn from_cap_std(stream: $inner) -> Self {
+                $ty(stream)
+            }
+        }
+
+        wasi_socket_impl!($ty, {
+            async fn read_vectored<'a>(
+                &self,
+                bufs: &mut [io::IoSliceMut<'a>],
+            ) -> Result<u64, Error> {
+                let n = (&self.0).read_vectored(bufs)?;
+                Ok(n.try_into()?)
+            }
+            async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+                let n = (&self.0).write_vectored(bufs)?;
+                Ok(n.try_into()?)
+            }
+            async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
+                let n = Peek::peek(&self.0, buf)?;
+                Ok(n.try_into()?)
+            }
+            async fn num_ready_bytes(&self) -> Result<u64, Error> {
+                Ok(self.0.num_ready_bytes()?)
+            }
+            async fn sock_recv<'a>(
+                &self,
+                ri_data: &mut [io::IoSliceMut<'a>],
+                ri_flags: RiFlags,
+            ) -> Result<(u64, RoFlags), Error> {
+                if ri_flags.contains(RiFlags::RECV_PEEK | RiFlags::RECV_WAITALL) {
+                    return Err(Error::not_supported()
+                        .context("cannot both peek and wait for all data on a socket"));
+                }
+                let n = if ri_flags.contains(RiFlags::RECV_PEEK) {
+                    match ri_data.iter_mut().find(|buf| !buf.is_empty()) {
+                        Some(buf) => Peek::peek(&self.0, buf)?,
+                        None => 0,
+                    }
+                } else if ri_flags.contains(RiFlags::RECV_WAITALL) {
+                    for buf in ri_data.iter_mut() {
+                        (&self.0).read_exact(buf)?;
+                    }
+                    ri_data.iter().map(|buf| buf.len()).sum()
+                } else {
+                    (&self.0).read_vectored(ri_data)?
+                };
+                Ok((n.try_into()?, RoFlags::empty()))
+            }
+            async fn sock_send<'a>(&self, si_data: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+                let n = (&self.0).write_vectored(si_data)?;
+                Ok(n.try_into()?)
+            }
+            async fn sock_shutdown(&self, how: SdFlags) -> Result<(), Error> {
+                let how = if how == SdFlags::RD | SdFlags::WR {
+                    Shutdown::Both
+                } else if how == SdFlags::RD {
+                    Shutdown::Read
+                } else if how == SdFlags::WR {
+                    Shutdown::Write
+                } else {
+                    return Err(Error::invalid_argument());
+                };
+                self.0.shutdown(how)?;
+                Ok(())
+            }
+        });
+    };
+}
+
+wasi_listener_impl!(TcpListener, cap_std::net::TcpListener, TcpStream);
+wasi_stream_impl!(TcpStream, cap_std::net::TcpStream);
+#[cfg(unix)]
+wasi_listener_impl!(
+    UnixListener,
+    cap_std::os::unix::net::UnixListener,
+    UnixStream
+);
+#[cfg(unix)]
+wasi_stream_impl!(UnixStream, cap_std::os::unix::net::UnixStream);
+
+#[cfg(test)]
+mod test {
+    use super::Socket;
+    use crate::dir::test::run;
+    use std::io::{IoSlice, IoSliceMut, Read, Write};
+    use std::thread;
+    use wasi_common::file::{FdFlags, FileType, RiFlags, SdFlags, WasiFile};
+
+    /// Accepts one connection on `listener`, checks `sock_recv` both with
+    /// and without flags, replies, and then shuts down writing so that the
+    /// peer sees the end of the stream.
+    fn serve_one(listener: Box<dyn WasiFile>) {
+        assert_eq!(
+            run(listener.get_filetype()).unwrap(),
+            FileType::SocketStream
+        );
+        // Listeners have nothing to read or write, only connections to accept.
+        let mut buf = [0; 4];
+        assert!(run(listener.read_vectored(&mut [IoSliceMut::new(&mut buf)])).is_err());
+        assert!(run(listener.sock_send(&[IoSlice::new(b"nope")])).is_err());
+
+        let stream = run(listener.sock_accept(FdFlags::empty())).expect("accept a connection");
+        assert_eq!(run(stream.get_filetype()).unwrap(), FileType::SocketStream);
+        assert_eq!(run(stream.get_fdflags()).unwrap(), FdFlags::empty());
+        assert!(run(stream.sock_accept(FdFlags::empty())).is_err());
+
+        // Peeking leaves the data in place for the next receive, and waiting
+        // for all data fills every buffer despite the split.
+        let mut peeked = [0; 4];
+        let (n, _) = run(stream.sock_recv(&mut [IoSliceMut::new(&mut peeked)], RiFlags::RECV_PEEK))
+            .expect("peek");
+        assert!(n > 0 && n <= 4);
+        assert_eq!(&peeked[..n as usize], &b"ping"[..n as usize]);
+        let (mut a, mut b) = ([0; 1], [0; 3]);
+        let (n, _) = run(stream.sock_recv(
+            &mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)],
+            RiFlags::RECV_WAITALL,
+        ))
+        .expect("receive");
+        assert_eq!(n, 4);
+        assert_eq!((&a, &b), (b"p", b"ing"));
+        assert!(run(stream.sock_recv(
+            &mut [IoSliceMut::new(&mut buf)],
+            RiFlags::RECV_PEEK | RiFlags::RECV_WAITALL,
+        ))
+        .is_err());
+
+        let n = run(stream.sock_send(&[IoSlice::new(b"po"), IoSlice::new(b"ng")])).expect("send");
+        assert_eq!(n, 4);
+        assert!(run(stream.sock_shutdown(SdFlags::empty())).is_err());
+        run(stream.sock_shutdown(SdFlags::WR)).expect("shut down writing");
+        assert!(run(stream.sock_send(&[IoSlice::new(b"late")])).is_err());
+    }
+
+    /// The client side of `serve_one`.
+    fn client(mut stream: impl Read + Write) {
+        stream.write_all(b"ping").unwrap();
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).unwrap();
+        assert_eq!(reply, b"pong");
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let listener = Socket::from(cap_std::net::TcpListener::from_std(listener)).into();
+        let client = thread::spawn(move || client(std::net::TcpStream::connect(addr).unwrap()));
+        serve_one(listener);
+        client.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("cap-std-sync")
+            .tempdir()
+            .expect("create temporary dir");
+        let path = tempdir.path().join("socket");
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let listener =
+            Socket::from(cap_std::os::unix::net::UnixListener::from_std(listener)).into();
+        let client =
+            thread::spawn(move || client(std::os::unix::net::UnixStream::connect(path).unwrap()));
+        serve_one(listener);
+        client.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nonblocking_accept() {
+        let (a, b) = std::os::unix::net::UnixStream::pair().unwrap();
+        let stream: Box<dyn WasiFile> =
+            Socket::from(cap_std::os::unix::net::UnixStream::from_std(a)).into();
+        drop(b);
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("cap-std-sync")
+            .tempdir()
+            .expect("create temporary dir");
+        let listener = std::os::unix::net::UnixListener::bind(tempdir.path().join("s")).unwrap();
+        let mut listener: Box<dyn WasiFile> =
+            Socket::from(cap_std::os::unix::net::UnixListener::from_std(listener)).into();
+        run(listener.set_fdflags(FdFlags::NONBLOCK)).unwrap();
+        assert_eq!(run(listener.get_fdflags()).unwrap(), FdFlags::NONBLOCK);
+        // No one is connecting, so accepting fails instead of blocking.
+        assert!(run(listener.sock_accept(FdFlags::empty())).is_err());
+        assert!(run(listener.set_fdflags(FdFlags::APPEND)).is_err());
+
+        // The peer is gone, so the stream reads as closed.
+        let mut buf = [0; 4];
+        let (n, _) = run(stream.sock_recv(&mut [IoSliceMut::new(&mut buf)], RiFlags::empty()))
+            .expect("receive");
+        assert_eq!(n, 0);
+    }
+}
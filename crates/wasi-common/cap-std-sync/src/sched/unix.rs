@@ -96,6 +96,18 @@ fn wasi_file_fd(f: &dyn WasiFile) -> Option<BorrowedFd<'_>> {
         Some(a.downcast_ref::<crate::stdio::Stdout>().unwrap().as_fd())
     } else if a.is::<crate::stdio::Stderr>() {
         Some(a.downcast_ref::<crate::stdio::Stderr>().unwrap().as_fd())
+    } else if a.is::<crate::net::TcpListener>() {
+        Some(a.downcast_ref::<crate::net::TcpListener>().unwrap().as_fd())
+    } else if a.is::<crate::net::TcpStream>() {
+        Some(a.downcast_ref::<crate::net::TcpStream>().unwrap().as_fd())
+    } else if a.is::<crate::net::UnixListener>() {
+        Some(
+            a.downcast_ref::<crate::net::UnixListener>()
+                .unwrap()
+                .as_fd(),
+        )
+    } else if a.is::<crate::net::UnixStream>() {
+        Some(a.downcast_ref::<crate::net::UnixStream>().unwrap().as_fd())
     } else {
         None
     }
@@ -9,9 +9,9 @@
 // taken the time to improve it. See bug #2880.
 
 use anyhow::Context;
-use io_lifetimes::AsHandle;
+use io_lifetimes::{AsHandle, AsSocket};
 use std::ops::Deref;
-use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::os::windows::io::{AsRawHandle, AsRawSocket, RawHandle};
 use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
 use std::sync::Mutex;
 use std::thread;
@@ -170,6 +170,20 @@ pub fn wasi_file_raw_handle(f: &dyn WasiFile) -> Option<RawHandle> {
                 .as_handle()
                 .as_raw_handle(),
         )
+    } else if a.is::<crate::net::TcpListener>() {
+        Some(
+            a.downcast_ref::<crate::net::TcpListener>()
+                .unwrap()
+                .as_socket()
+                .as_raw_socket() as RawHandle,
+        )
+    } else if a.is::<crate::net::TcpStream>() {
+        Some(
+            a.downcast_ref::<crate::net::TcpStream>()
+                .unwrap()
+                .as_socket()
+                .as_raw_socket() as RawHandle,
+        )
     } else {
         None
     }
@@ -0,0 +1,416 @@
+use crate::file::File;
+use crate::fs::MemFs;
+use std::any::Any;
+use std::path::PathBuf;
+use wasi_common::{
+    dir::{ReaddirCursor, ReaddirEntity, WasiDir},
+    file::{FdFlags, Filestat, OFlags, WasiFile},
+    Error, ErrorExt, SystemTimeSpec,
+};
+
+/// An open directory of a [`MemFs`].
+pub struct Dir {
+    fs: MemFs,
+    ino: u64,
+}
+
+impl Dir {
+    /// Creates a `Dir` for the inode `ino`, whose handle count has already
+    /// been incremented for it.
+    pub(crate) fn from_handle(fs: MemFs, ino: u64) -> Self {
+        Dir { fs, ino }
+    }
+
+    pub fn open_file_(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<File, Error> {
+        let mut fs = self.fs.lock();
+        let ino = fs.open_file(self.ino, path, symlink_follow, oflags)?;
+        fs.open_handle(ino);
+        Ok(File::from_handle(self.fs.clone(), ino, write, fdflags))
+    }
+
+    pub fn open_dir_(&self, symlink_follow: bool, path: &str) -> Result<Self, Error> {
+        let mut fs = self.fs.lock();
+        let ino = fs.open_dir(self.ino, path, symlink_follow)?;
+        fs.open_handle(ino);
+        Ok(Dir::from_handle(self.fs.clone(), ino))
+    }
+
+    fn same_fs<'a>(&self, other: &'a dyn WasiDir) -> Result<&'a Self, Error> {
+        let other = other
+            .as_any()
+            .downcast_ref::<Self>()
+            .ok_or(Error::badf().context("failed downcast to memfs Dir"))?;
+        if !self.fs.same_fs(&other.fs) {
+            return Err(Error::cross_device());
+        }
+        Ok(other)
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        self.fs.lock().close_handle(self.ino);
+    }
+}
+
+#[async_trait::async_trait]
+impl WasiDir for Dir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        _read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        let f = self.open_file_(symlink_follow, path, oflags, write, fdflags)?;
+        Ok(Box::new(f))
+    }
+
+    async fn open_dir(&self, symlink_follow: bool, path: &str) -> Result<Box<dyn WasiDir>, Error> {
+        let d = self.open_dir_(symlink_follow, path)?;
+        Ok(Box::new(d))
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        self.fs.lock().create_dir(self.ino, path)
+    }
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        // The entries are collected up front, so the cursor of an entry is
+        // its index in the directory as of this call.
+        let entries = self.fs.lock().readdir(self.ino)?;
+        let rd = entries
+            .into_iter()
+            .enumerate()
+            .map(|(ix, (name, inode, filetype))| {
+                Ok(ReaddirEntity {
+                    next: ReaddirCursor::from(ix as u64 + 1),
+                    filetype,
+                    inode,
+                    name,
+                })
+            })
+            .skip(u64::from(cursor) as usize);
+        Ok(Box::new(rd))
+    }
+
+    async fn symlink(&self, src_path: &str, dest_path: &str) -> Result<(), Error> {
+        self.fs.lock().symlink(self.ino, src_path, dest_path)
+    }
+    async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        self.fs.lock().remove_dir(self.ino, path)
+    }
+
+    async fn unlink_file(&self, path: &str) -> Result<(), Error> {
+        self.fs.lock().unlink_file(self.ino, path)
+    }
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        let link = self.fs.lock().read_link(self.ino, path)?;
+        Ok(PathBuf::from(link))
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(self.fs.lock().filestat(self.ino))
+    }
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        let fs = self.fs.lock();
+        let ino = fs.resolve(self.ino, path, follow_symlinks)?;
+        Ok(fs.filestat(ino))
+    }
+    async fn rename(
+        &self,
+        src_path: &str,
+        dest_dir: &dyn WasiDir,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let dest_dir = self.same_fs(dest_dir)?;
+        self.fs
+            .lock()
+            .rename(self.ino, src_path, dest_dir.ino, dest_path)
+    }
+    async fn hard_link(
+        &self,
+        src_path: &str,
+        target_dir: &dyn WasiDir,
+        target_path: &str,
+    ) -> Result<(), Error> {
+        let target_dir = self.same_fs(target_dir)?;
+        self.fs
+            .lock()
+            .hard_link(self.ino, src_path, target_dir.ino, target_path)
+    }
+    async fn set_times(
+        &self,
+        path: &str,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+        follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        let mut fs = self.fs.lock();
+        let ino = fs.resolve(self.ino, path, follow_symlinks)?;
+        fs.set_times(ino, atime, mtime);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::MemFs;
+    use std::io::{IoSlice, IoSliceMut};
+    use wasi_common::{
+        dir::{ReaddirCursor, WasiDir},
+        file::{FdFlags, FileType, OFlags},
+        Error, ErrorKind,
+    };
+
+    fn is(err: Error, kind: fn(&ErrorKind) -> bool) -> bool {
+        err.downcast_ref::<ErrorKind>().map_or(false, kind)
+    }
+
+    fn write(dir: &dyn WasiDir, path: &str, contents: &[u8]) -> Result<(), Error> {
+        let f = run(dir.open_file(
+            false,
+            path,
+            OFlags::CREATE | OFlags::TRUNCATE,
+            false,
+            true,
+            FdFlags::empty(),
+        ))?;
+        run(f.write_vectored(&[IoSlice::new(contents)]))?;
+        Ok(())
+    }
+
+    pub(crate) fn read(dir: &dyn WasiDir, path: &str) -> Result<Vec<u8>, Error> {
+        let f = run(dir.open_file(true, path, OFlags::empty(), true, false, FdFlags::empty()))?;
+        let mut buf = vec![0; 1024];
+        let n = run(f.read_vectored(&mut [IoSliceMut::new(&mut buf)]))?;
+        buf.truncate(n as usize);
+        Ok(buf)
+    }
+
+    #[test]
+    fn files_and_dirs() {
+        let fs = MemFs::new();
+        let root = fs.root();
+        run(root.create_dir("d")).expect("create d");
+        write(&root, "d/f", b"hello").expect("write d/f");
+        assert_eq!(read(&root, "d/f").unwrap(), b"hello");
+        assert_eq!(read(&root, "./d/../d/f").unwrap(), b"hello");
+        assert_eq!(fs.used_bytes(), 5);
+
+        let f = run(root.open_file(false, "d/f", OFlags::empty(), true, true, FdFlags::APPEND))
+            .unwrap();
+        run(f.write_vectored(&[IoSlice::new(b", "), IoSlice::new(b"world")])).unwrap();
+        assert_eq!(run(f.get_filestat()).unwrap().size, 12);
+        assert_eq!(read(&root, "d/f").unwrap(), b"hello, world");
+
+        let d = run(root.open_dir(false, "d")).unwrap();
+        write(&*d, "g", b"").unwrap();
+        let names = |cursor: u64| {
+            run(d.readdir(ReaddirCursor::from(cursor)))
+                .unwrap()
+                .map(|e| e.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(0), [".", "..", "f", "g"]);
+        assert_eq!(names(3), ["g"]);
+        assert_eq!(
+            run(root.get_path_filestat("d", true)).unwrap().filetype,
+            FileType::Directory
+        );
+
+        assert!(is(run(root.remove_dir("d")).unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Notempty
+        )));
+        assert!(is(run(root.unlink_file("d")).unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Isdir
+        )));
+        run(root.unlink_file("d/f")).unwrap();
+        run(root.unlink_file("d/g")).unwrap();
+        run(root.remove_dir("d")).unwrap();
+        // The open file keeps its contents until it's closed.
+        assert_eq!(fs.used_bytes(), 12);
+        drop(f);
+        assert_eq!(fs.used_bytes(), 0);
+        assert!(run(d.create_dir("e")).is_err());
+    }
+
+    #[test]
+    fn path_resolution() {
+        let fs = MemFs::new();
+        let root = fs.root();
+        run(root.create_dir("a")).unwrap();
+        write(&root, "a/f", b"contents").unwrap();
+        run(root.symlink("a/f", "link")).unwrap();
+        run(root.symlink("../a", "a/up")).unwrap();
+        run(root.symlink("loop", "loop")).unwrap();
+        run(root.symlink("..", "a/escape")).unwrap();
+
+        assert_eq!(read(&root, "link").unwrap(), b"contents");
+        assert_eq!(read(&root, "a/up/up/f").unwrap(), b"contents");
+        assert_eq!(run(root.read_link("link")).unwrap().to_str(), Some("a/f"));
+        assert_eq!(
+            run(root.get_path_filestat("link", false)).unwrap().filetype,
+            FileType::SymbolicLink
+        );
+        assert!(is(
+            run(root.open_file(
+                false,
+                "link",
+                OFlags::empty(),
+                true,
+                false,
+                FdFlags::empty()
+            ))
+            .err()
+            .unwrap(),
+            |k| matches!(k, ErrorKind::Loop)
+        ));
+        assert!(is(read(&root, "loop").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Loop
+        )));
+        assert!(is(read(&root, "a/f/").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Notdir
+        )));
+
+        // Paths can't lead out of the directory they're resolved in.
+        let a = run(root.open_dir(false, "a")).unwrap();
+        assert_eq!(read(&*a, "f").unwrap(), b"contents");
+        for path in &["../a/f", "/a/f", "escape/a/f", "up/f"] {
+            assert!(
+                is(read(&*a, path).unwrap_err(), |k| matches!(
+                    k,
+                    ErrorKind::Perm
+                )),
+                "{}",
+                path
+            );
+        }
+        assert!(is(read(&root, "/a/f").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Perm
+        )));
+    }
+
+    #[test]
+    fn rename_and_link() {
+        let fs = MemFs::new();
+        let root = fs.root();
+        run(root.create_dir("a")).unwrap();
+        run(root.create_dir("a/b")).unwrap();
+        write(&root, "x", b"x").unwrap();
+        write(&root, "y", b"y").unwrap();
+
+        assert!(is(
+            run(root.rename("a", &root, "a/b/c")).unwrap_err(),
+            |k| { matches!(k, ErrorKind::Inval) }
+        ));
+        run(root.rename("a/b", &root, "c")).unwrap();
+        run(root.rename("x", &root, "y")).unwrap();
+        assert_eq!(read(&root, "y").unwrap(), b"x");
+        assert!(run(root.get_path_filestat("x", false)).is_err());
+        assert_eq!(fs.used_bytes(), 1);
+
+        let c = run(root.open_dir(false, "c")).unwrap();
+        run(root.hard_link("y", &*c, "z")).unwrap();
+        assert_eq!(run(root.get_path_filestat("y", false)).unwrap().nlink, 2);
+        run(root.unlink_file("y")).unwrap();
+        assert_eq!(read(&*c, "z").unwrap(), b"x");
+
+        let other = MemFs::new().root();
+        assert!(is(run(root.rename("c", &other, "c")).unwrap_err(), |k| {
+            matches!(k, ErrorKind::Xdev)
+        }));
+    }
+
+    #[test]
+    fn quota() {
+        let fs = MemFs::with_quota(10);
+        let root = fs.root();
+        write(&root, "f", b"12345678").unwrap();
+        assert!(is(write(&root, "g", b"123").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Nospc
+        )));
+        assert!(is(
+            run(root.symlink("long target", "l")).unwrap_err(),
+            |k| { matches!(k, ErrorKind::Nospc) }
+        ));
+        run(root.unlink_file("f")).unwrap();
+        run(root.unlink_file("g")).unwrap();
+        assert_eq!(fs.used_bytes(), 0);
+        write(&root, "g", b"1234567890").unwrap();
+        assert_eq!(fs.used_bytes(), 10);
+    }
+
+    #[test]
+    fn copy_host_dir() {
+        let tempdir = tempfile::Builder::new()
+            .prefix("wasi-memfs")
+            .tempdir()
+            .expect("create temporary dir");
+        std::fs::create_dir(tempdir.path().join("d")).unwrap();
+        std::fs::write(tempdir.path().join("d/f"), b"from the host").unwrap();
+
+        let fs = MemFs::new();
+        fs.copy_host_dir(tempdir.path()).unwrap();
+        std::fs::remove_dir_all(tempdir.path().join("d")).unwrap();
+        assert_eq!(read(&fs.root(), "d/f").unwrap(), b"from the host");
+    }
+
+    pub(crate) fn run<F: std::future::Future>(future: F) -> F::Output {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        let mut f = Pin::from(Box::new(future));
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => panic!("memfs operations should never be pending"),
+        }
+
+        fn dummy_waker() -> Waker {
+            return unsafe { Waker::from_raw(clone(5 as *const _)) };
+
+            unsafe fn clone(ptr: *const ()) -> RawWaker {
+                assert_eq!(ptr as usize, 5);
+                const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+                RawWaker::new(ptr, &VTABLE)
+            }
+
+            unsafe fn wake(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn wake_by_ref(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn drop(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::fs::MemFs;
+use std::any::Any;
+use std::convert::TryInto;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use wasi_common::{
+    file::{Advice, FdFlags, FileType, Filestat, WasiFile},
+    Error, ErrorExt, SystemTimeSpec,
+};
+
+/// An open regular file of a [`MemFs`].
+pub struct File {
+    fs: MemFs,
+    ino: u64,
+    write: bool,
+    fdflags: FdFlags,
+    position: AtomicU64,
+}
+
+impl File {
+    /// Creates a `File` for the inode `ino`, whose handle count has already
+    /// been incremented for it.
+    pub(crate) fn from_handle(fs: MemFs, ino: u64, write: bool, fdflags: FdFlags) -> Self {
+        File {
+            fs,
+            ino,
+            write,
+            fdflags,
+            position: AtomicU64::new(0),
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.write {
+            Ok(())
+        } else {
+            Err(Error::badf().context("file is not opened for writing"))
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        self.fs.lock().close_handle(self.ino);
+    }
+}
+
+#[async_trait::async_trait]
+impl WasiFile for File {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn datasync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::RegularFile)
+    }
+    async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+        Ok(self.fdflags)
+    }
+    async fn set_fdflags(&mut self, fdflags: FdFlags) -> Result<(), Error> {
+        // Every operation on the file is synchronous and never blocks, so all
+        // that matters is `APPEND`.
+        self.fdflags = fdflags;
+        Ok(())
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(self.fs.lock().filestat(self.ino))
+    }
+    async fn set_filestat_size(&self, size: u64) -> Result<(), Error> {
+        self.check_writable()?;
+        self.fs.lock().set_size(self.ino, size)
+    }
+    async fn advise(&self, _offset: u64, _len: u64, _advice: Advice) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn allocate(&self, offset: u64, len: u64) -> Result<(), Error> {
+        self.check_writable()?;
+        let end = offset.checked_add(len).ok_or_else(Error::overflow)?;
+        let mut fs = self.fs.lock();
+        if end > fs.size(self.ino) {
+            fs.set_size(self.ino, end)?;
+        }
+        Ok(())
+    }
+    async fn set_times(
+        &self,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+    ) -> Result<(), Error> {
+        self.fs.lock().set_times(self.ino, atime, mtime);
+        Ok(())
+    }
+    async fn read_vectored<'a>(&self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        let fs = self.fs.lock();
+        let position = self.position.load(Ordering::SeqCst);
+        let n = fs.read_at(self.ino, bufs, position)?;
+        self.position.store(position + n, Ordering::SeqCst);
+        Ok(n)
+    }
+    async fn read_vectored_at<'a>(
+        &self,
+        bufs: &mut [io::IoSliceMut<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        self.fs.lock().read_at(self.ino, bufs, offset)
+    }
+    async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        self.check_writable()?;
+        let mut fs = self.fs.lock();
+        let position = if self.fdflags.contains(FdFlags::APPEND) {
+            fs.size(self.ino)
+        } else {
+            self.position.load(Ordering::SeqCst)
+        };
+        let n = fs.write_at(self.ino, bufs, position)?;
+        self.position.store(position + n, Ordering::SeqCst);
+        Ok(n)
+    }
+    async fn write_vectored_at<'a>(
+        &self,
+        bufs: &[io::IoSlice<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        self.check_writable()?;
+        self.fs.lock().write_at(self.ino, bufs, offset)
+    }
+    async fn seek(&self, pos: std::io::SeekFrom) -> Result<u64, Error> {
+        let fs = self.fs.lock();
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(offset) => (0, offset.try_into()?),
+            io::SeekFrom::Current(offset) => (self.position.load(Ordering::SeqCst), offset),
+            io::SeekFrom::End(offset) => (fs.size(self.ino), offset),
+        };
+        let base: i64 = base.try_into()?;
+        let position = base
+            .checked_add(offset)
+            .filter(|p| *p >= 0)
+            .ok_or_else(|| Error::invalid_argument().context("seek to a negative offset"))?;
+        self.position.store(position as u64, Ordering::SeqCst);
+        Ok(position as u64)
+    }
+    async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
+        let fs = self.fs.lock();
+        let position = self.position.load(Ordering::SeqCst);
+        fs.read_at(self.ino, &mut [io::IoSliceMut::new(buf)], position)
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        let fs = self.fs.lock();
+        Ok(fs
+            .size(self.ino)
+            .saturating_sub(self.position.load(Ordering::SeqCst)))
+    }
+    async fn readable(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn writable(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,807 @@
+use crate::dir::Dir;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::{IoSlice, IoSliceMut, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use wasi_common::{
+    file::{FileType, Filestat, OFlags},
+    Error, ErrorExt, SystemTimeSpec,
+};
+
+/// The inode number of the root directory of every `MemFs`.
+const ROOT: u64 = 1;
+
+/// The maximum number of symbolic links followed while resolving a single
+/// path, as on Linux.
+const MAX_SYMLINKS: u32 = 40;
+
+/// An in-memory filesystem.
+///
+/// Guests access the filesystem through the [`Dir`] returned by
+/// [`MemFs::root`], which can be preopened in a `WasiCtx`. Clones of a
+/// `MemFs` refer to the same filesystem, so the host can keep one around to
+/// seed it or to inspect its usage.
+#[derive(Clone)]
+pub struct MemFs(Arc<Mutex<FsState>>);
+
+impl MemFs {
+    /// Creates an empty filesystem without a quota.
+    pub fn new() -> Self {
+        Self::with_limit(None)
+    }
+
+    /// Creates an empty filesystem in which the contents of files and
+    /// symbolic links can't take up more than `max_bytes` bytes in total.
+    ///
+    /// Operations which would exceed the quota fail with `ENOSPC`.
+    pub fn with_quota(max_bytes: u64) -> Self {
+        Self::with_limit(Some(max_bytes))
+    }
+
+    fn with_limit(quota: Option<u64>) -> Self {
+        let now = SystemTime::now();
+        let root = Inode {
+            kind: Kind::Dir {
+                entries: BTreeMap::new(),
+                parent: ROOT,
+            },
+            // The root directory doesn't have a name, but it should never be
+            // considered removed.
+            nlink: 1,
+            handles: 0,
+            atim: now,
+            mtim: now,
+            ctim: now,
+        };
+        let mut inodes = HashMap::new();
+        inodes.insert(ROOT, root);
+        MemFs(Arc::new(Mutex::new(FsState {
+            inodes,
+            next_ino: ROOT + 1,
+            quota,
+            used: 0,
+        })))
+    }
+
+    /// Returns a handle to the root directory of the filesystem.
+    ///
+    /// As with directories opened from the host's filesystem, paths resolved
+    /// relative to the handle can't refer to anything outside of it.
+    pub fn root(&self) -> Dir {
+        self.lock().open_handle(ROOT);
+        Dir::from_handle(self.clone(), ROOT)
+    }
+
+    /// Returns the number of bytes used by the contents of files and symbolic
+    /// links.
+    pub fn used_bytes(&self) -> u64 {
+        self.lock().used
+    }
+
+    /// Returns the quota given to [`MemFs::with_quota`], if any.
+    pub fn quota(&self) -> Option<u64> {
+        self.lock().quota
+    }
+
+    /// Extracts the tar archive read from `archive` into the root directory.
+    ///
+    /// Regular files, directories, symbolic links and hard links are
+    /// extracted, with their modification times. Entries of other types, such
+    /// as devices, are skipped, as are file modes and ownership. Entries with
+    /// paths leading outside of the root directory are rejected.
+    pub fn unpack_tar(&self, archive: impl Read) -> Result<(), Error> {
+        crate::tar::unpack(&mut self.lock(), archive)
+    }
+
+    /// Copies the contents of the host directory at `path` into the root
+    /// directory, recursively.
+    ///
+    /// Regular files, directories and symbolic links are copied, with their
+    /// modification times. Other kinds of files are skipped. The filesystem
+    /// doesn't refer back to the host directory afterwards.
+    pub fn copy_host_dir(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.lock().copy_host_dir(ROOT, path.as_ref())
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, FsState> {
+        self.0.lock().unwrap()
+    }
+
+    pub(crate) fn same_fs(&self, other: &MemFs) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Default for MemFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) struct FsState {
+    inodes: HashMap<u64, Inode>,
+    next_ino: u64,
+    quota: Option<u64>,
+    used: u64,
+}
+
+struct Inode {
+    kind: Kind,
+    /// The number of directory entries referring to the inode.
+    nlink: u64,
+    /// The number of open `Dir` and `File` handles referring to the inode.
+    handles: u64,
+    atim: SystemTime,
+    mtim: SystemTime,
+    ctim: SystemTime,
+}
+
+enum Kind {
+    File(Vec<u8>),
+    Dir {
+        entries: BTreeMap<String, u64>,
+        parent: u64,
+    },
+    Symlink(String),
+}
+
+impl Kind {
+    fn filetype(&self) -> FileType {
+        match self {
+            Kind::File(_) => FileType::RegularFile,
+            Kind::Dir { .. } => FileType::Directory,
+            Kind::Symlink(_) => FileType::SymbolicLink,
+        }
+    }
+
+    /// The number of bytes the inode counts against the quota.
+    fn size(&self) -> u64 {
+        match self {
+            Kind::File(data) => data.len() as u64,
+            Kind::Dir { .. } => 0,
+            Kind::Symlink(target) => target.len() as u64,
+        }
+    }
+}
+
+fn is_dot(name: &str) -> bool {
+    name == "." || name == ".."
+}
+
+impl FsState {
+    fn inode(&self, ino: u64) -> &Inode {
+        &self.inodes[&ino]
+    }
+
+    fn inode_mut(&mut self, ino: u64) -> &mut Inode {
+        self.inodes.get_mut(&ino).unwrap()
+    }
+
+    fn entries(&self, dir: u64) -> Result<&BTreeMap<String, u64>, Error> {
+        match &self.inode(dir).kind {
+            Kind::Dir { entries, .. } => Ok(entries),
+            _ => Err(Error::not_dir()),
+        }
+    }
+
+    fn entries_mut(&mut self, dir: u64) -> &mut BTreeMap<String, u64> {
+        match &mut self.inode_mut(dir).kind {
+            Kind::Dir { entries, .. } => entries,
+            _ => unreachable!("not a directory"),
+        }
+    }
+
+    fn data(&self, ino: u64) -> Result<&Vec<u8>, Error> {
+        match &self.inode(ino).kind {
+            Kind::File(data) => Ok(data),
+            Kind::Dir { .. } => Err(Error::is_dir()),
+            Kind::Symlink(_) => Err(Error::badf()),
+        }
+    }
+
+    fn data_mut(&mut self, ino: u64) -> &mut Vec<u8> {
+        match &mut self.inode_mut(ino).kind {
+            Kind::File(data) => data,
+            _ => unreachable!("not a regular file"),
+        }
+    }
+
+    /// Accounts for contents growing or shrinking from `old` to `new` bytes,
+    /// failing if they'd grow beyond the quota.
+    fn charge(&mut self, old: u64, new: u64) -> Result<(), Error> {
+        let used = self.used - old + new;
+        if new > old && self.quota.map_or(false, |quota| used > quota) {
+            return Err(Error::no_space());
+        }
+        self.used = used;
+        Ok(())
+    }
+
+    fn alloc(&mut self, kind: Kind) -> Result<u64, Error> {
+        self.charge(0, kind.size())?;
+        let now = SystemTime::now();
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.inodes.insert(
+            ino,
+            Inode {
+                kind,
+                nlink: 0,
+                handles: 0,
+                atim: now,
+                mtim: now,
+                ctim: now,
+            },
+        );
+        Ok(ino)
+    }
+
+    /// Frees `ino` if nothing refers to it anymore.
+    fn release(&mut self, ino: u64) {
+        let inode = self.inode(ino);
+        if inode.nlink == 0 && inode.handles == 0 {
+            let inode = self.inodes.remove(&ino).unwrap();
+            self.used -= inode.kind.size();
+        }
+    }
+
+    pub(crate) fn open_handle(&mut self, ino: u64) {
+        self.inode_mut(ino).handles += 1;
+    }
+
+    pub(crate) fn close_handle(&mut self, ino: u64) {
+        self.inode_mut(ino).handles -= 1;
+        self.release(ino);
+    }
+
+    fn link(&mut self, dir: u64, name: &str, ino: u64) {
+        let now = SystemTime::now();
+        self.entries_mut(dir).insert(name.to_owned(), ino);
+        let dir = self.inode_mut(dir);
+        dir.mtim = now;
+        dir.ctim = now;
+        let inode = self.inode_mut(ino);
+        inode.nlink += 1;
+        inode.ctim = now;
+    }
+
+    fn unlink(&mut self, dir: u64, name: &str) {
+        let now = SystemTime::now();
+        let ino = self.entries_mut(dir).remove(name).unwrap();
+        let dir = self.inode_mut(dir);
+        dir.mtim = now;
+        dir.ctim = now;
+        let inode = self.inode_mut(ino);
+        inode.nlink -= 1;
+        inode.ctim = now;
+        self.release(ino);
+    }
+
+    /// Walks `path` starting from the last directory in `stack`, pushing the
+    /// inodes of the directories it passes through. The first directory in
+    /// `stack` is the one paths are resolved relative to, and `..` can't lead
+    /// outside of it.
+    fn walk(
+        &self,
+        stack: &mut Vec<u64>,
+        path: &str,
+        follow: bool,
+        symlinks: &mut u32,
+    ) -> Result<(), Error> {
+        if path.starts_with('/') {
+            return Err(Error::perm().context("absolute path"));
+        }
+        let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+        while let Some(name) = components.next() {
+            let dir = *stack.last().unwrap();
+            let entries = self.entries(dir)?;
+            match name {
+                "." => continue,
+                ".." if stack.len() == 1 => {
+                    return Err(Error::perm().context("path leads outside of the directory"));
+                }
+                ".." => {
+                    stack.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            let ino = *entries.get(name).ok_or_else(Error::not_found)?;
+            match &self.inode(ino).kind {
+                Kind::Symlink(target) if follow || components.peek().is_some() => {
+                    *symlinks += 1;
+                    if *symlinks > MAX_SYMLINKS {
+                        return Err(Error::symlink_loop());
+                    }
+                    if target.is_empty() {
+                        return Err(Error::not_found());
+                    }
+                    self.walk(stack, target, true, symlinks)?;
+                }
+                _ => stack.push(ino),
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves `path` relative to the directory `base`, following a
+    /// symbolic link in its last component if `follow` is set.
+    pub(crate) fn resolve(&self, base: u64, path: &str, follow: bool) -> Result<u64, Error> {
+        if path.is_empty() {
+            return Err(Error::not_found());
+        }
+        // A trailing slash refers to a directory, through a symbolic link if
+        // need be.
+        let must_be_dir = path.ends_with('/');
+        let mut stack = vec![base];
+        self.walk(&mut stack, path, follow || must_be_dir, &mut 0)?;
+        let ino = *stack.last().unwrap();
+        if must_be_dir {
+            self.entries(ino)?;
+        }
+        Ok(ino)
+    }
+
+    /// Resolves all but the last component of `path` relative to the
+    /// directory `base`, returning the directory containing the last
+    /// component and its name, which may be `.` or `..`.
+    fn resolve_parent<'a>(&self, base: u64, path: &'a str) -> Result<(u64, &'a str), Error> {
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(if path.is_empty() {
+                Error::not_found()
+            } else {
+                Error::perm().context("absolute path")
+            });
+        }
+        let (dir_path, name) = match trimmed.rfind('/') {
+            Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+            None => ("", trimmed),
+        };
+        let mut stack = vec![base];
+        self.walk(&mut stack, dir_path, true, &mut 0)?;
+        let dir = *stack.last().unwrap();
+        self.entries(dir)?;
+        if self.inode(dir).nlink == 0 {
+            // Nothing can be created in a directory which has been removed.
+            return Err(Error::not_found());
+        }
+        Ok((dir, name))
+    }
+
+    fn exists(&self, dir: u64, name: &str) -> Result<bool, Error> {
+        Ok(is_dot(name) || self.entries(dir)?.contains_key(name))
+    }
+
+    fn lookup(&self, dir: u64, name: &str) -> Result<u64, Error> {
+        if is_dot(name) {
+            return Err(Error::invalid_argument());
+        }
+        self.entries(dir)?
+            .get(name)
+            .copied()
+            .ok_or_else(Error::not_found)
+    }
+
+    pub(crate) fn open_file(
+        &mut self,
+        base: u64,
+        path: &str,
+        follow: bool,
+        oflags: OFlags,
+    ) -> Result<u64, Error> {
+        let ino = if oflags.contains(OFlags::CREATE) {
+            let (dir, name) = self.resolve_parent(base, path)?;
+            if self.exists(dir, name)? {
+                if oflags.contains(OFlags::EXCLUSIVE) {
+                    return Err(Error::exist());
+                }
+                self.resolve(base, path, follow)?
+            } else if path.ends_with('/') {
+                return Err(Error::is_dir());
+            } else {
+                let ino = self.alloc(Kind::File(Vec::new()))?;
+                self.link(dir, name, ino);
+                ino
+            }
+        } else {
+            self.resolve(base, path, follow)?
+        };
+        match self.inode(ino).kind {
+            Kind::File(_) => {}
+            Kind::Dir { .. } => return Err(Error::is_dir()),
+            // The last component is a symbolic link which wasn't followed.
+            Kind::Symlink(_) => return Err(Error::symlink_loop()),
+        }
+        if oflags.contains(OFlags::TRUNCATE) && !self.data(ino)?.is_empty() {
+            self.set_size(ino, 0)?;
+        }
+        Ok(ino)
+    }
+
+    pub(crate) fn open_dir(&self, base: u64, path: &str, follow: bool) -> Result<u64, Error> {
+        let ino = self.resolve(base, path, follow)?;
+        self.entries(ino)?;
+        Ok(ino)
+    }
+
+    pub(crate) fn create_dir(&mut self, base: u64, path: &str) -> Result<(), Error> {
+        let (dir, name) = self.resolve_parent(base, path)?;
+        if self.exists(dir, name)? {
+            return Err(Error::exist());
+        }
+        let ino = self.alloc(Kind::Dir {
+            entries: BTreeMap::new(),
+            parent: dir,
+        })?;
+        self.link(dir, name, ino);
+        Ok(())
+    }
+
+    /// Returns the name, inode number and type of each entry of the
+    /// directory `dir`, starting with `.` and `..`.
+    pub(crate) fn readdir(&self, dir: u64) -> Result<Vec<(String, u64, FileType)>, Error> {
+        let parent = match &self.inode(dir).kind {
+            Kind::Dir { parent, .. } => *parent,
+            _ => return Err(Error::not_dir()),
+        };
+        let mut result = vec![
+            (".".to_owned(), dir, FileType::Directory),
+            ("..".to_owned(), parent, FileType::Directory),
+        ];
+        for (name, ino) in self.entries(dir)? {
+            result.push((name.clone(), *ino, self.inode(*ino).kind.filetype()));
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn symlink(&mut self, base: u64, target: &str, path: &str) -> Result<(), Error> {
+        let (dir, name) = self.resolve_parent(base, path)?;
+        if self.exists(dir, name)? {
+            return Err(Error::exist());
+        }
+        let ino = self.alloc(Kind::Symlink(target.to_owned()))?;
+        self.link(dir, name, ino);
+        Ok(())
+    }
+
+    pub(crate) fn remove_dir(&mut self, base: u64, path: &str) -> Result<(), Error> {
+        let (dir, name) = self.resolve_parent(base, path)?;
+        if name == ".." {
+            return Err(Error::not_empty());
+        }
+        let ino = self.lookup(dir, name)?;
+        if !self.entries(ino)?.is_empty() {
+            return Err(Error::not_empty());
+        }
+        self.unlink(dir, name);
+        Ok(())
+    }
+
+    pub(crate) fn unlink_file(&mut self, base: u64, path: &str) -> Result<(), Error> {
+        let (dir, name) = self.resolve_parent(base, path)?;
+        if is_dot(name) {
+            return Err(Error::is_dir());
+        }
+        let ino = self.lookup(dir, name)?;
+        if let Kind::Dir { .. } = self.inode(ino).kind {
+            return Err(Error::is_dir());
+        }
+        if path.ends_with('/') {
+            return Err(Error::not_dir());
+        }
+        self.unlink(dir, name);
+        Ok(())
+    }
+
+    pub(crate) fn read_link(&self, base: u64, path: &str) -> Result<String, Error> {
+        let (dir, name) = self.resolve_parent(base, path)?;
+        let ino = self.lookup(dir, name)?;
+        match &self.inode(ino).kind {
+            Kind::Symlink(target) => Ok(target.clone()),
+            _ => Err(Error::invalid_argument().context("not a symbolic link")),
+        }
+    }
+
+    pub(crate) fn rename(
+        &mut self,
+        src_base: u64,
+        src_path: &str,
+        dest_base: u64,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let (src_dir, src_name) = self.resolve_parent(src_base, src_path)?;
+        let (dest_dir, dest_name) = self.resolve_parent(dest_base, dest_path)?;
+        if is_dot(dest_name) {
+            return Err(Error::invalid_argument());
+        }
+        let ino = self.lookup(src_dir, src_name)?;
+        let is_dir = matches!(self.inode(ino).kind, Kind::Dir { .. });
+        if !is_dir && (src_path.ends_with('/') || dest_path.ends_with('/')) {
+            return Err(Error::not_dir());
+        }
+        let existing = self.entries(dest_dir)?.get(dest_name).copied();
+        if let Some(existing) = existing {
+            if existing == ino {
+                return Ok(());
+            }
+            match &self.inode(existing).kind {
+                Kind::Dir { entries, .. } if is_dir => {
+                    if !entries.is_empty() {
+                        return Err(Error::not_empty());
+                    }
+                }
+                Kind::Dir { .. } => return Err(Error::is_dir()),
+                _ if is_dir => return Err(Error::not_dir()),
+                _ => {}
+            }
+        }
+        if is_dir {
+            // A directory can't be moved into itself, or into one of the
+            // directories it contains.
+            let mut dir = dest_dir;
+            loop {
+                if dir == ino {
+                    return Err(Error::invalid_argument());
+                }
+                match self.inode(dir).kind {
+                    Kind::Dir { parent, .. } if parent != dir => dir = parent,
+                    _ => break,
+                }
+            }
+        }
+
+        if existing.is_some() {
+            self.unlink(dest_dir, dest_name);
+        }
+        self.link(dest_dir, dest_name, ino);
+        self.unlink(src_dir, src_name);
+        if let Kind::Dir { parent, .. } = &mut self.inode_mut(ino).kind {
+            *parent = dest_dir;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn hard_link(
+        &mut self,
+        src_base: u64,
+        src_path: &str,
+        dest_base: u64,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let ino = self.resolve(src_base, src_path, false)?;
+        if let Kind::Dir { .. } = self.inode(ino).kind {
+            return Err(Error::perm().context("hard link to a directory"));
+        }
+        let (dir, name) = self.resolve_parent(dest_base, dest_path)?;
+        if self.exists(dir, name)? {
+            return Err(Error::exist());
+        }
+        self.link(dir, name, ino);
+        Ok(())
+    }
+
+    pub(crate) fn filestat(&self, ino: u64) -> Filestat {
+        let inode = self.inode(ino);
+        Filestat {
+            device_id: 0,
+            inode: ino,
+            filetype: inode.kind.filetype(),
+            nlink: inode.nlink,
+            size: inode.kind.size(),
+            atim: Some(inode.atim),
+            mtim: Some(inode.mtim),
+            ctim: Some(inode.ctim),
+        }
+    }
+
+    pub(crate) fn set_times(
+        &mut self,
+        ino: u64,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+    ) {
+        let now = SystemTime::now();
+        let time = |spec: SystemTimeSpec| match spec {
+            SystemTimeSpec::SymbolicNow => now,
+            SystemTimeSpec::Absolute(t) => t.into_std(),
+        };
+        let inode = self.inode_mut(ino);
+        if let Some(atime) = atime {
+            inode.atim = time(atime);
+        }
+        if let Some(mtime) = mtime {
+            inode.mtim = time(mtime);
+        }
+        inode.ctim = now;
+    }
+
+    pub(crate) fn size(&self, ino: u64) -> u64 {
+        self.inode(ino).kind.size()
+    }
+
+    pub(crate) fn set_size(&mut self, ino: u64, size: u64) -> Result<(), Error> {
+        let size = usize::try_from(size)?;
+        let old = self.data(ino)?.len();
+        self.charge(old as u64, size as u64)?;
+        self.data_mut(ino).resize(size, 0);
+        let now = SystemTime::now();
+        let inode = self.inode_mut(ino);
+        inode.mtim = now;
+        inode.ctim = now;
+        Ok(())
+    }
+
+    pub(crate) fn read_at(
+        &self,
+        ino: u64,
+        bufs: &mut [IoSliceMut<'_>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        let data = self.data(ino)?;
+        let mut pos = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(data.len());
+        let start = pos;
+        for buf in bufs.iter_mut() {
+            let n = buf.len().min(data.len() - pos);
+            buf[..n].copy_from_slice(&data[pos..pos + n]);
+            pos += n;
+            if pos == data.len() {
+                break;
+            }
+        }
+        Ok((pos - start) as u64)
+    }
+
+    pub(crate) fn write_at(
+        &mut self,
+        ino: u64,
+        bufs: &[IoSlice<'_>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        let mut pos = usize::try_from(offset)?;
+        let end = pos.checked_add(len).ok_or_else(Error::overflow)?;
+        let old = self.data(ino)?.len();
+        if end > old {
+            self.charge(old as u64, end as u64)?;
+        }
+        let data = self.data_mut(ino);
+        if end > old {
+            data.resize(end, 0);
+        }
+        for buf in bufs {
+            data[pos..pos + buf.len()].copy_from_slice(buf);
+            pos += buf.len();
+        }
+        let now = SystemTime::now();
+        let inode = self.inode_mut(ino);
+        inode.mtim = now;
+        inode.ctim = now;
+        Ok(len as u64)
+    }
+
+    // Seeding the filesystem. Paths given to these methods are relative to the
+    // root directory, and missing directories leading up to them are created.
+
+    /// Returns the directory containing `path`, creating it if need be, and
+    /// the name of `path` within it.
+    fn seed_parent<'a>(&mut self, path: &'a str) -> Result<(u64, &'a str), Error> {
+        let components = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect::<Vec<_>>();
+        if components.contains(&"..") {
+            return Err(Error::perm().context(format!("path `{}` leads outside of the root", path)));
+        }
+        let (name, dirs) = components
+            .split_last()
+            .ok_or_else(|| Error::invalid_argument().context("empty path"))?;
+        let mut dir = ROOT;
+        for component in dirs {
+            dir = self.make_dir(dir, component)?;
+        }
+        Ok((dir, *name))
+    }
+
+    /// Returns the directory `name` in `dir`, creating it if it doesn't exist.
+    fn make_dir(&mut self, dir: u64, name: &str) -> Result<u64, Error> {
+        if let Some(&ino) = self.entries(dir)?.get(name) {
+            self.entries(ino)?;
+            return Ok(ino);
+        }
+        let ino = self.alloc(Kind::Dir {
+            entries: BTreeMap::new(),
+            parent: dir,
+        })?;
+        self.link(dir, name, ino);
+        Ok(ino)
+    }
+
+    /// Creates `name` in `dir`, replacing any file or symbolic link of the
+    /// same name.
+    fn replace(&mut self, dir: u64, name: &str, kind: Kind) -> Result<u64, Error> {
+        if let Some(&ino) = self.entries(dir)?.get(name) {
+            if let Kind::Dir { .. } = self.inode(ino).kind {
+                return Err(Error::is_dir());
+            }
+            self.unlink(dir, name);
+        }
+        let ino = self.alloc(kind)?;
+        self.link(dir, name, ino);
+        Ok(ino)
+    }
+
+    pub(crate) fn seed_dir(&mut self, path: &str) -> Result<u64, Error> {
+        let (dir, name) = self.seed_parent(path)?;
+        self.make_dir(dir, name)
+    }
+
+    pub(crate) fn seed_file(&mut self, path: &str, data: Vec<u8>) -> Result<u64, Error> {
+        let (dir, name) = self.seed_parent(path)?;
+        self.replace(dir, name, Kind::File(data))
+    }
+
+    pub(crate) fn seed_symlink(&mut self, path: &str, target: String) -> Result<u64, Error> {
+        let (dir, name) = self.seed_parent(path)?;
+        self.replace(dir, name, Kind::Symlink(target))
+    }
+
+    pub(crate) fn seed_hard_link(&mut self, path: &str, target: &str) -> Result<u64, Error> {
+        let ino = self.resolve(ROOT, target, false)?;
+        if let Kind::Dir { .. } = self.inode(ino).kind {
+            return Err(Error::perm().context("hard link to a directory"));
+        }
+        let (dir, name) = self.seed_parent(path)?;
+        if let Some(&existing) = self.entries(dir)?.get(name) {
+            if existing == ino {
+                return Ok(ino);
+            }
+            if let Kind::Dir { .. } = self.inode(existing).kind {
+                return Err(Error::is_dir());
+            }
+            self.unlink(dir, name);
+        }
+        self.link(dir, name, ino);
+        Ok(ino)
+    }
+
+    pub(crate) fn set_mtime(&mut self, ino: u64, mtime: SystemTime) {
+        self.inode_mut(ino).mtim = mtime;
+    }
+
+    fn copy_host_dir(&mut self, dir: u64, path: &Path) -> Result<(), Error> {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|_| Error::illegal_byte_sequence().context("filename"))?;
+            // Unlike `std::fs::metadata`, this doesn't follow symbolic links.
+            let meta = entry.metadata()?;
+            let ino = if meta.is_dir() {
+                let ino = self.make_dir(dir, &name)?;
+                self.copy_host_dir(ino, &entry.path())?;
+                ino
+            } else if meta.file_type().is_symlink() {
+                let target = std::fs::read_link(entry.path())?
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|_| Error::illegal_byte_sequence().context("symlink target"))?;
+                self.replace(dir, &name, Kind::Symlink(target))?
+            } else if meta.is_file() {
+                let data = std::fs::read(entry.path())?;
+                self.replace(dir, &name, Kind::File(data))?
+            } else {
+                continue;
+            };
+            if let Ok(mtime) = meta.modified() {
+                self.set_mtime(ino, mtime);
+            }
+        }
+        Ok(())
+    }
+}
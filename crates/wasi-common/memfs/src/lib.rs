@@ -0,0 +1,34 @@
+//! The `wasi-memfs` crate provides impls of `WasiDir` and `WasiFile` for a
+//! filesystem which lives entirely in memory, and never touches the host's
+//! filesystem. This gives each guest a private scratch filesystem, which is
+//! discarded along with its `MemFs`.
+//!
+//! A `MemFs` supports directories, regular files, symbolic links and hard
+//! links, with timestamps. It can be limited to a quota on the size of its
+//! contents, and seeded from a tar archive or a copy of a host directory:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), wasi_common::Error> {
+//! let fs = wasi_memfs::MemFs::with_quota(64 << 20);
+//! fs.unpack_tar(std::fs::File::open("rootfs.tar")?)?;
+//! let root: Box<dyn wasi_common::WasiDir> = Box::new(fs.root());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The root directory can then be preopened with
+//! `WasiCtx::push_preopened_dir`. Like `wasi-cap-std-sync`'s `Dir`, a `Dir`
+//! of a `MemFs` confines path resolution to itself: absolute paths, and `..`
+//! or symbolic links leading out of the directory, are rejected.
+//!
+//! Files of a `MemFs` are always ready for reading and writing, but note that
+//! the `wasi-cap-std-sync` scheduler can only poll the files it knows about.
+
+mod dir;
+mod file;
+mod fs;
+mod tar;
+
+pub use dir::Dir;
+pub use file::File;
+pub use fs::MemFs;
@@ -0,0 +1,269 @@
+//! Extraction of tar archives, in the ustar, GNU and pax formats.
+
+use crate::fs::FsState;
+use std::convert::TryFrom;
+use std::io::{self, Read};
+use std::time::{Duration, UNIX_EPOCH};
+use wasi_common::{Error, ErrorExt};
+
+const BLOCK_SIZE: usize = 512;
+
+pub(crate) fn unpack(fs: &mut FsState, mut archive: impl Read) -> Result<(), Error> {
+    // Names given by the GNU and pax extensions for the next entry.
+    let mut next_path = None;
+    let mut next_link = None;
+    let mut header = [0; BLOCK_SIZE];
+    loop {
+        if !read_block(&mut archive, &mut header)? || header.iter().all(|b| *b == 0) {
+            // The end of the archive is marked by zeroed blocks.
+            return Ok(());
+        }
+        verify_checksum(&header)?;
+
+        let size = usize::try_from(number(&header[124..136])?)?;
+        let mut data = vec![0; size];
+        archive.read_exact(&mut data)?;
+        let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+        archive.read_exact(&mut [0; BLOCK_SIZE][..padding])?;
+
+        let typeflag = header[156];
+        match typeflag {
+            b'L' => {
+                next_path = Some(string(&data)?);
+                continue;
+            }
+            b'K' => {
+                next_link = Some(string(&data)?);
+                continue;
+            }
+            b'x' => {
+                for (key, value) in pax_records(&data)? {
+                    match key {
+                        "path" => next_path = Some(value.to_owned()),
+                        "linkpath" => next_link = Some(value.to_owned()),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let path = match next_path.take() {
+            Some(path) => path,
+            None => header_path(&header)?,
+        };
+        let link = match next_link.take() {
+            Some(link) => link,
+            None => string(&header[157..257])?,
+        };
+        let mtime = UNIX_EPOCH + Duration::from_secs(number(&header[136..148])?);
+        if path.split('/').all(|c| c.is_empty() || c == ".") {
+            // The root directory itself.
+            continue;
+        }
+
+        let ino = match typeflag {
+            b'5' => fs.seed_dir(&path)?,
+            // Old archives mark directories with a trailing slash only.
+            b'0' | b'\0' if path.ends_with('/') => fs.seed_dir(&path)?,
+            b'0' | b'\0' | b'7' => fs.seed_file(&path, data)?,
+            b'1' => fs.seed_hard_link(&path, &link)?,
+            b'2' => fs.seed_symlink(&path, link)?,
+            // Devices, fifos and extensions we don't know about.
+            _ => continue,
+        };
+        fs.set_mtime(ino, mtime);
+    }
+}
+
+/// Reads a whole block, returning `false` if the archive ends before it.
+fn read_block(archive: &mut impl Read, block: &mut [u8; BLOCK_SIZE]) -> Result<bool, Error> {
+    let mut read = 0;
+    while read < BLOCK_SIZE {
+        match archive.read(&mut block[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(invalid("truncated header")),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+fn verify_checksum(header: &[u8; BLOCK_SIZE]) -> Result<(), Error> {
+    let expected = number(&header[148..156])?;
+    // The checksum is computed with its own field filled with spaces.
+    let actual = header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b })
+        .map(u64::from)
+        .sum::<u64>();
+    if actual != expected {
+        return Err(invalid("header checksum mismatch"));
+    }
+    Ok(())
+}
+
+/// Returns the path of the entry, which ustar archives may split into a
+/// prefix and a name.
+fn header_path(header: &[u8; BLOCK_SIZE]) -> Result<String, Error> {
+    let name = string(&header[0..100])?;
+    if &header[257..263] == b"ustar\0" {
+        let prefix = string(&header[345..500])?;
+        if !prefix.is_empty() {
+            return Ok(format!("{}/{}", prefix, name));
+        }
+    }
+    Ok(name)
+}
+
+/// Parses a numeric header field, which is either octal text or, for large
+/// values in GNU archives, big-endian binary with the high bit set.
+fn number(field: &[u8]) -> Result<u64, Error> {
+    if field[0] & 0x80 != 0 {
+        let mut value = u64::from(field[0] & 0x7f);
+        for b in &field[1..] {
+            value = value
+                .checked_mul(256)
+                .and_then(|v| v.checked_add(u64::from(*b)))
+                .ok_or_else(|| invalid("numeric field overflows"))?;
+        }
+        return Ok(value);
+    }
+    let text = std::str::from_utf8(field).map_err(|_| invalid("numeric field isn't text"))?;
+    let text = text.trim_matches(|c| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid("numeric field isn't octal"))
+}
+
+/// Returns the string in a NUL-terminated or NUL-padded field.
+fn string(field: &[u8]) -> Result<String, Error> {
+    let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    let s = std::str::from_utf8(&field[..len])
+        .map_err(|_| Error::illegal_byte_sequence().context("tar entry name"))?;
+    Ok(s.to_owned())
+}
+
+/// Parses the `<length> <key>=<value>\n` records of a pax extended header.
+fn pax_records(data: &[u8]) -> Result<Vec<(&str, &str)>, Error> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() && rest[0] != 0 {
+        let space = rest
+            .iter()
+            .position(|b| *b == b' ')
+            .ok_or_else(|| invalid("malformed pax record"))?;
+        let len = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+            .filter(|len| *len > space + 1 && *len <= rest.len())
+            .ok_or_else(|| invalid("malformed pax record"))?;
+        let record = std::str::from_utf8(&rest[space + 1..len - 1])
+            .map_err(|_| Error::illegal_byte_sequence().context("pax record"))?;
+        let eq = record
+            .find('=')
+            .ok_or_else(|| invalid("malformed pax record"))?;
+        records.push((&record[..eq], &record[eq + 1..]));
+        rest = &rest[len..];
+    }
+    Ok(records)
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::invalid_argument().context(format!("invalid tar archive: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dir::test::{read, run};
+    use crate::MemFs;
+    use std::time::{Duration, UNIX_EPOCH};
+    use wasi_common::{ErrorKind, WasiDir};
+
+    const MTIME: u64 = 1_000_000_000;
+
+    fn entry(archive: &mut Vec<u8>, typeflag: u8, path: &str, link: &str, data: &[u8]) {
+        let mut header = [0; 512];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[136..147].copy_from_slice(format!("{:011o}", MTIME).as_bytes());
+        header[156] = typeflag;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum = header.iter().map(|b| u64::from(*b)).sum::<u64>();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize((archive.len() + 511) / 512 * 512, 0);
+    }
+
+    #[test]
+    fn unpack() {
+        let long_name = format!("d/{}", "x".repeat(150));
+        let mut archive = Vec::new();
+        entry(&mut archive, b'5', "d/", "", b"");
+        entry(&mut archive, b'0', "d/f", "", b"file contents");
+        entry(&mut archive, b'2', "d/symlink", "f", b"");
+        entry(&mut archive, b'1', "hardlink", "d/f", b"");
+        entry(&mut archive, b'0', "./implicit/dirs/g", "", b"g");
+        entry(
+            &mut archive,
+            b'L',
+            "././@LongLink",
+            "",
+            long_name.as_bytes(),
+        );
+        entry(&mut archive, b'0', "truncated name", "", b"long");
+        let pax = "19 path=d/pax-name\n";
+        entry(&mut archive, b'x', "pax header", "", pax.as_bytes());
+        entry(&mut archive, b'0', "truncated name", "", b"pax");
+        entry(&mut archive, b'3', "d/tty", "", b"");
+        archive.extend_from_slice(&[0; 1024]);
+
+        let fs = MemFs::new();
+        fs.unpack_tar(&archive[..]).unwrap();
+        let root = fs.root();
+        assert_eq!(read(&root, "d/f").unwrap(), b"file contents");
+        assert_eq!(read(&root, "d/symlink").unwrap(), b"file contents");
+        assert_eq!(read(&root, "hardlink").unwrap(), b"file contents");
+        assert_eq!(read(&root, "implicit/dirs/g").unwrap(), b"g");
+        assert_eq!(read(&root, &long_name).unwrap(), b"long");
+        assert_eq!(read(&root, "d/pax-name").unwrap(), b"pax");
+        assert!(read(&root, "d/tty").is_err());
+        assert!(read(&root, "truncated name").is_err());
+
+        let stat = run(root.get_path_filestat("d/f", false)).unwrap();
+        assert_eq!(stat.nlink, 2);
+        assert_eq!(stat.mtim, Some(UNIX_EPOCH + Duration::from_secs(MTIME)));
+    }
+
+    #[test]
+    fn unpack_rejects_escapes() {
+        let mut archive = Vec::new();
+        entry(&mut archive, b'0', "d/../../f", "", b"");
+        let err = MemFs::new().unpack_tar(&archive[..]).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(ErrorKind::Perm)),
+            "{:?}",
+            err
+        );
+
+        let mut archive = Vec::new();
+        entry(&mut archive, b'0', "f", "", b"");
+        archive[0] = b'g';
+        let err = MemFs::new().unpack_tar(&archive[..]).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(ErrorKind::Inval)),
+            "{:?}",
+            err
+        );
+    }
+}
@@ -0,0 +1,820 @@
+use cap_std::time::SystemTime;
+use std::any::Any;
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, IoSlice, IoSliceMut};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use wasi_common::{
+    dir::{ReaddirCursor, ReaddirEntity, WasiDir},
+    file::{FdFlags, FileType, Filestat, OFlags, WasiFile},
+    Error, ErrorExt, ErrorKind, SystemTimeSpec,
+};
+
+/// The number of symbolic links which may be followed while resolving a
+/// path, before failing with `ELOOP`.
+const MAX_SYMLINKS: usize = 40;
+
+/// A directory of an overlay filesystem, which merges the contents of a
+/// lower and an upper `WasiDir`.
+pub struct OverlayDir {
+    layers: Arc<Layers>,
+    /// The path of this directory, from the root of the overlay.
+    path: Vec<String>,
+}
+
+struct Layers {
+    lower: Box<dyn WasiDir>,
+    upper: Box<dyn WasiDir>,
+    /// Paths, from the root of the overlay, of entries of the lower layer
+    /// which have been removed. Each hides everything beneath it as well.
+    whiteouts: Mutex<HashSet<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Upper,
+    Lower,
+}
+
+impl OverlayDir {
+    /// Creates an overlay which shows the contents of `upper` on top of
+    /// those of `lower`. Only `upper` is ever modified.
+    pub fn new(lower: Box<dyn WasiDir>, upper: Box<dyn WasiDir>) -> Self {
+        OverlayDir {
+            layers: Arc::new(Layers {
+                lower,
+                upper,
+                whiteouts: Mutex::new(HashSet::new()),
+            }),
+            path: Vec::new(),
+        }
+    }
+
+    async fn resolve(&self, path: &str, symlink_follow: bool) -> Result<Vec<String>, Error> {
+        self.layers.resolve(&self.path, path, symlink_follow).await
+    }
+
+    fn same_overlay<'a>(&self, other: &'a dyn WasiDir) -> Result<&'a Self, Error> {
+        let other = other
+            .as_any()
+            .downcast_ref::<Self>()
+            .ok_or(Error::badf().context("failed downcast to OverlayDir"))?;
+        if !Arc::ptr_eq(&self.layers, &other.layers) {
+            return Err(Error::cross_device());
+        }
+        Ok(other)
+    }
+}
+
+#[async_trait::async_trait]
+impl WasiDir for OverlayDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        let path = self.resolve(path, symlink_follow).await?;
+        let name = join(&path);
+        match self.layers.locate(&path).await? {
+            Some(_) if oflags.contains(OFlags::CREATE | OFlags::EXCLUSIVE) => Err(Error::exist()),
+            Some((Layer::Lower, _)) if write || oflags.contains(OFlags::TRUNCATE) => {
+                self.layers.copy_up(&path).await?;
+                let oflags = oflags - OFlags::CREATE;
+                (self.layers.upper)
+                    .open_file(symlink_follow, &name, oflags, read, write, fdflags)
+                    .await
+            }
+            Some((Layer::Lower, _)) => {
+                let oflags = oflags - OFlags::CREATE;
+                (self.layers.lower)
+                    .open_file(symlink_follow, &name, oflags, read, write, fdflags)
+                    .await
+            }
+            Some((Layer::Upper, _)) => {
+                (self.layers.upper)
+                    .open_file(symlink_follow, &name, oflags, read, write, fdflags)
+                    .await
+            }
+            None if oflags.contains(OFlags::CREATE) => {
+                self.layers.copy_up_parent(&path).await?;
+                (self.layers.upper)
+                    .open_file(symlink_follow, &name, oflags, read, write, fdflags)
+                    .await
+            }
+            None => Err(Error::not_found()),
+        }
+    }
+
+    async fn open_dir(&self, symlink_follow: bool, path: &str) -> Result<Box<dyn WasiDir>, Error> {
+        let path = self.resolve(path, symlink_follow).await?;
+        match self.layers.locate(&path).await? {
+            Some((_, stat)) if stat.filetype == FileType::Directory => Ok(Box::new(OverlayDir {
+                layers: self.layers.clone(),
+                path,
+            })),
+            Some(_) => Err(Error::not_dir()),
+            None => Err(Error::not_found()),
+        }
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        let path = self.resolve(path, false).await?;
+        if self.layers.locate(&path).await?.is_some() {
+            return Err(Error::exist());
+        }
+        self.layers.copy_up_parent(&path).await?;
+        self.layers.upper.create_dir(&join(&path)).await
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        let entries = self.layers.readdir(&self.path).await?;
+        // The layers' cursors don't mean anything in the merged listing, so
+        // number the entries afresh.
+        Ok(Box::new(
+            entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    entry.map(|entry| ReaddirEntity {
+                        next: ReaddirCursor::from(i as u64 + 1),
+                        ..entry
+                    })
+                })
+                .skip(u64::from(cursor) as usize),
+        ))
+    }
+
+    async fn symlink(&self, src_path: &str, dest_path: &str) -> Result<(), Error> {
+        let path = self.resolve(dest_path, false).await?;
+        if self.layers.locate(&path).await?.is_some() {
+            return Err(Error::exist());
+        }
+        self.layers.copy_up_parent(&path).await?;
+        self.layers.upper.symlink(src_path, &join(&path)).await
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        let path = self.resolve(path, false).await?;
+        if path.len() == self.path.len() {
+            return Err(Error::invalid_argument().context("cannot remove the directory itself"));
+        }
+        let (layer, stat) = self
+            .layers
+            .locate(&path)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if stat.filetype != FileType::Directory {
+            return Err(Error::not_dir());
+        }
+        if !self.layers.is_empty_dir(&path).await? {
+            return Err(Error::not_empty());
+        }
+        self.layers.remove(&path, layer, stat.filetype).await
+    }
+
+    async fn unlink_file(&self, path: &str) -> Result<(), Error> {
+        let path = self.resolve(path, false).await?;
+        let (layer, stat) = self
+            .layers
+            .locate(&path)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if stat.filetype == FileType::Directory {
+            return Err(Error::is_dir());
+        }
+        self.layers.remove(&path, layer, stat.filetype).await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        let path = self.resolve(path, false).await?;
+        let (layer, _) = self
+            .layers
+            .locate(&path)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        self.layers.layer(layer).read_link(&join(&path)).await
+    }
+
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        let (_, stat) = self
+            .layers
+            .locate(&self.path)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        Ok(stat)
+    }
+
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        let path = self.resolve(path, follow_symlinks).await?;
+        let (_, stat) = self
+            .layers
+            .locate(&path)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        Ok(stat)
+    }
+
+    async fn rename(
+        &self,
+        src_path: &str,
+        dest_dir: &dyn WasiDir,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let dest_dir = self.same_overlay(dest_dir)?;
+        let src = self.resolve(src_path, false).await?;
+        let dest = dest_dir.resolve(dest_path, false).await?;
+        let (layer, stat) = self
+            .layers
+            .locate(&src)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if src == dest {
+            return Ok(());
+        }
+        let is_dir = stat.filetype == FileType::Directory;
+        if is_dir && dest.starts_with(&src) {
+            return Err(Error::invalid_argument().context("cannot move a directory into itself"));
+        }
+        if let Some((_, dest_stat)) = self.layers.locate(&dest).await? {
+            match (is_dir, dest_stat.filetype == FileType::Directory) {
+                (true, true) => {
+                    if !self.layers.is_empty_dir(&dest).await? {
+                        return Err(Error::not_empty());
+                    }
+                }
+                (false, true) => return Err(Error::is_dir()),
+                (true, false) => return Err(Error::not_dir()),
+                (false, false) => {}
+            }
+        }
+
+        let src_in_lower = self.layers.lower_stat(&src).await?.is_some();
+        let dest_in_lower = self.layers.lower_stat(&dest).await?.is_some();
+        if src_in_lower && is_dir {
+            // Moving the directory would mean copying up everything beneath
+            // it; like overlayfs, leave that to the guest.
+            return Err(
+                Error::cross_device().context("cannot rename a directory of the lower layer")
+            );
+        }
+        if layer == Layer::Lower {
+            self.layers.copy_up(&src).await?;
+        }
+        self.layers.copy_up_parent(&dest).await?;
+        let upper = &*self.layers.upper;
+        upper.rename(&join(&src), upper, &join(&dest)).await?;
+        if src_in_lower {
+            self.layers.add_whiteout(&src);
+        }
+        if dest_in_lower {
+            self.layers.add_whiteout(&dest);
+        }
+        Ok(())
+    }
+
+    async fn hard_link(
+        &self,
+        src_path: &str,
+        target_dir: &dyn WasiDir,
+        target_path: &str,
+    ) -> Result<(), Error> {
+        let target_dir = self.same_overlay(target_dir)?;
+        let src = self.resolve(src_path, false).await?;
+        let target = target_dir.resolve(target_path, false).await?;
+        let (layer, stat) = self
+            .layers
+            .locate(&src)
+            .await?
+            .ok_or_else(Error::not_found)?;
+        if stat.filetype == FileType::Directory {
+            return Err(Error::perm().context("cannot hard link a directory"));
+        }
+        if self.layers.locate(&target).await?.is_some() {
+            return Err(Error::exist());
+        }
+        if layer == Layer::Lower {
+            self.layers.copy_up(&src).await?;
+        }
+        self.layers.copy_up_parent(&target).await?;
+        let upper = &*self.layers.upper;
+        upper.hard_link(&join(&src), upper, &join(&target)).await
+    }
+
+    async fn set_times(
+        &self,
+        path: &str,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+        follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        let path = self.resolve(path, follow_symlinks).await?;
+        match self.layers.locate(&path).await? {
+            Some((Layer::Lower, _)) => self.layers.copy_up(&path).await?,
+            Some((Layer::Upper, _)) => {}
+            None => return Err(Error::not_found()),
+        }
+        (self.layers.upper)
+            .set_times(&join(&path), atime, mtime, follow_symlinks)
+            .await
+    }
+}
+
+impl Layers {
+    fn layer(&self, layer: Layer) -> &dyn WasiDir {
+        match layer {
+            Layer::Upper => &*self.upper,
+            Layer::Lower => &*self.lower,
+        }
+    }
+
+    fn is_whiteout(&self, path: &[String]) -> bool {
+        let whiteouts = self.whiteouts.lock().unwrap();
+        (1..=path.len()).any(|len| whiteouts.contains(&join(&path[..len])))
+    }
+
+    fn add_whiteout(&self, path: &[String]) {
+        self.whiteouts.lock().unwrap().insert(join(path));
+    }
+
+    /// Returns the status of the entry at `path` in a single layer, without
+    /// following a final symbolic link.
+    async fn stat(&self, layer: Layer, path: &[String]) -> Result<Option<Filestat>, Error> {
+        match self
+            .layer(layer)
+            .get_path_filestat(&join(path), false)
+            .await
+        {
+            Ok(stat) => Ok(Some(stat)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the status of the lower layer's entry at `path`, unless it is
+    /// hidden by a whiteout.
+    async fn lower_stat(&self, path: &[String]) -> Result<Option<Filestat>, Error> {
+        if self.is_whiteout(path) {
+            return Ok(None);
+        }
+        self.stat(Layer::Lower, path).await
+    }
+
+    /// Finds the layer which provides the entry at `path`, whose parent
+    /// directories have already been resolved.
+    async fn locate(&self, path: &[String]) -> Result<Option<(Layer, Filestat)>, Error> {
+        if let Some(stat) = self.stat(Layer::Upper, path).await? {
+            return Ok(Some((Layer::Upper, stat)));
+        }
+        Ok(self
+            .lower_stat(path)
+            .await?
+            .map(|stat| (Layer::Lower, stat)))
+    }
+
+    /// Resolves `path`, relative to the directory at `base`, to a path from
+    /// the root of the overlay which contains no symbolic links, except
+    /// perhaps a final one when `symlink_follow` is false.
+    async fn resolve(
+        &self,
+        base: &[String],
+        path: &str,
+        symlink_follow: bool,
+    ) -> Result<Vec<String>, Error> {
+        if path.starts_with('/') {
+            return Err(Error::perm().context("absolute paths are not permitted"));
+        }
+        // A trailing slash requires the path to name a directory, and so
+        // follows a final symbolic link.
+        let must_be_dir = path.ends_with('/');
+        let symlink_follow = symlink_follow || must_be_dir;
+        let mut resolved = base.to_vec();
+        let mut pending = path.split('/').map(str::to_owned).collect::<VecDeque<_>>();
+        let mut symlinks = 0;
+        while let Some(component) = pending.pop_front() {
+            match component.as_str() {
+                "" | "." => continue,
+                ".." if resolved.len() == base.len() => {
+                    return Err(Error::perm().context("path escapes the directory"));
+                }
+                ".." => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => resolved.push(component),
+            }
+            let last = pending.iter().all(|c| c.is_empty() || c == ".");
+            if last && !symlink_follow {
+                break;
+            }
+            let (layer, stat) = match self.locate(&resolved).await? {
+                Some(found) => found,
+                None if last => break,
+                None => return Err(Error::not_found()),
+            };
+            match stat.filetype {
+                FileType::SymbolicLink => {
+                    symlinks += 1;
+                    if symlinks > MAX_SYMLINKS {
+                        return Err(Error::symlink_loop());
+                    }
+                    let target = self.layer(layer).read_link(&join(&resolved)).await?;
+                    let target = target
+                        .into_os_string()
+                        .into_string()
+                        .map_err(|_| Error::illegal_byte_sequence().context("symlink target"))?;
+                    if target.starts_with('/') {
+                        return Err(Error::perm().context("symlink to an absolute path"));
+                    }
+                    resolved.pop();
+                    for component in target.split('/').rev() {
+                        pending.push_front(component.to_owned());
+                    }
+                }
+                FileType::Directory => {}
+                _ if last => {}
+                _ => return Err(Error::not_dir()),
+            }
+        }
+        if must_be_dir {
+            if let Some((_, stat)) = self.locate(&resolved).await? {
+                if stat.filetype != FileType::Directory {
+                    return Err(Error::not_dir());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Lists the merged contents of the directory at `path`.
+    async fn readdir(&self, path: &[String]) -> Result<Vec<Result<ReaddirEntity, Error>>, Error> {
+        let mut entries = Vec::new();
+        let mut names = HashSet::new();
+        if let Some(stat) = self.stat(Layer::Upper, path).await? {
+            if stat.filetype == FileType::Directory {
+                let dir = self.upper.open_dir(false, &join(path)).await?;
+                for entry in dir.readdir(ReaddirCursor::from(0)).await? {
+                    if let Ok(entry) = &entry {
+                        names.insert(entry.name.clone());
+                    }
+                    entries.push(entry);
+                }
+            }
+        }
+        if let Some(stat) = self.lower_stat(path).await? {
+            if stat.filetype == FileType::Directory {
+                let dir = self.lower.open_dir(false, &join(path)).await?;
+                for entry in dir.readdir(ReaddirCursor::from(0)).await? {
+                    if let Ok(entry) = &entry {
+                        if names.contains(&entry.name) {
+                            continue;
+                        }
+                        let mut child = path.to_vec();
+                        child.push(entry.name.clone());
+                        if entry.name != "." && entry.name != ".." && self.is_whiteout(&child) {
+                            continue;
+                        }
+                    }
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn is_empty_dir(&self, path: &[String]) -> Result<bool, Error> {
+        for entry in self.readdir(path).await? {
+            let entry = entry?;
+            if entry.name != "." && entry.name != ".." {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Removes the entry at `path`, found in `layer`, from the merged view.
+    async fn remove(&self, path: &[String], layer: Layer, filetype: FileType) -> Result<(), Error> {
+        let in_lower = self.lower_stat(path).await?.is_some();
+        if layer == Layer::Upper {
+            if filetype == FileType::Directory {
+                self.upper.remove_dir(&join(path)).await?;
+            } else {
+                self.upper.unlink_file(&join(path)).await?;
+            }
+        }
+        if in_lower {
+            self.add_whiteout(path);
+        }
+        Ok(())
+    }
+
+    /// Copies the entry at `path` up from the lower layer, along with those
+    /// of its parent directories which the upper layer doesn't contain.
+    async fn copy_up(&self, path: &[String]) -> Result<(), Error> {
+        for len in 1..=path.len() {
+            if self.stat(Layer::Upper, &path[..len]).await?.is_none() {
+                self.copy_entry(&path[..len]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies up the parent directories of `path`, so that it can be created
+    /// in the upper layer.
+    async fn copy_up_parent(&self, path: &[String]) -> Result<(), Error> {
+        match path.split_last() {
+            Some((_, parent)) => self.copy_up(parent).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn copy_entry(&self, path: &[String]) -> Result<(), Error> {
+        let stat = self.lower_stat(path).await?.ok_or_else(Error::not_found)?;
+        let name = join(path);
+        match stat.filetype {
+            FileType::Directory => self.upper.create_dir(&name).await?,
+            FileType::RegularFile => {
+                let src = (self.lower)
+                    .open_file(false, &name, OFlags::empty(), true, false, FdFlags::empty())
+                    .await?;
+                let dest = (self.upper)
+                    .open_file(
+                        false,
+                        &name,
+                        OFlags::CREATE | OFlags::EXCLUSIVE,
+                        false,
+                        true,
+                        FdFlags::empty(),
+                    )
+                    .await?;
+                let mut buf = vec![0; 64 * 1024];
+                loop {
+                    let n = src.read_vectored(&mut [IoSliceMut::new(&mut buf)]).await? as usize;
+                    if n == 0 {
+                        break;
+                    }
+                    let mut written = 0;
+                    while written < n {
+                        match dest
+                            .write_vectored(&[IoSlice::new(&buf[written..n])])
+                            .await?
+                        {
+                            0 => return Err(Error::io().context("copy up wrote nothing")),
+                            w => written += w as usize,
+                        }
+                    }
+                }
+            }
+            FileType::SymbolicLink => {
+                let target = self.lower.read_link(&name).await?;
+                let target = target
+                    .to_str()
+                    .ok_or_else(|| Error::illegal_byte_sequence().context("symlink target"))?;
+                // Not every platform can set the times of a symbolic link.
+                return self.upper.symlink(target, &name).await;
+            }
+            _ => return Err(Error::not_supported().context("cannot copy up a special file")),
+        }
+        let time = |t: Option<std::time::SystemTime>| {
+            t.map(|t| SystemTimeSpec::Absolute(SystemTime::from_std(t)))
+        };
+        (self.upper)
+            .set_times(&name, time(stat.atim), time(stat.mtim), false)
+            .await
+    }
+}
+
+fn join(path: &[String]) -> String {
+    if path.is_empty() {
+        ".".to_owned()
+    } else {
+        path.join("/")
+    }
+}
+
+fn is_not_found(err: &Error) -> bool {
+    match err.downcast_ref::<ErrorKind>() {
+        Some(kind) => matches!(kind, ErrorKind::Noent),
+        None => err
+            .downcast_ref::<io::Error>()
+            .map_or(false, |e| e.kind() == io::ErrorKind::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OverlayDir;
+    use std::io::{IoSlice, IoSliceMut};
+    use wasi_common::{
+        dir::{ReaddirCursor, WasiDir},
+        file::{FdFlags, OFlags},
+        Error, ErrorKind,
+    };
+    use wasi_memfs::MemFs;
+
+    fn is(err: Error, kind: fn(&ErrorKind) -> bool) -> bool {
+        err.downcast_ref::<ErrorKind>().map_or(false, kind)
+    }
+
+    fn write(dir: &dyn WasiDir, path: &str, contents: &[u8]) -> Result<(), Error> {
+        let f = run(dir.open_file(
+            false,
+            path,
+            OFlags::CREATE | OFlags::TRUNCATE,
+            false,
+            true,
+            FdFlags::empty(),
+        ))?;
+        run(f.write_vectored(&[IoSlice::new(contents)]))?;
+        Ok(())
+    }
+
+    fn read(dir: &dyn WasiDir, path: &str) -> Result<Vec<u8>, Error> {
+        let f = run(dir.open_file(true, path, OFlags::empty(), true, false, FdFlags::empty()))?;
+        let mut buf = vec![0; 1024];
+        let n = run(f.read_vectored(&mut [IoSliceMut::new(&mut buf)]))?;
+        buf.truncate(n as usize);
+        Ok(buf)
+    }
+
+    fn names(dir: &dyn WasiDir) -> Vec<String> {
+        let mut names = run(dir.readdir(ReaddirCursor::from(0)))
+            .unwrap()
+            .map(|entry| entry.unwrap().name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Returns an overlay whose lower layer contains `d/f`, `d/e/g` and a
+    /// symlink `link` to `d`, along with the memfs of each layer.
+    fn overlay() -> (OverlayDir, MemFs, MemFs) {
+        let lower = MemFs::new();
+        let root = lower.root();
+        run(root.create_dir("d")).unwrap();
+        run(root.create_dir("d/e")).unwrap();
+        write(&root, "d/f", b"lower f").unwrap();
+        write(&root, "d/e/g", b"lower g").unwrap();
+        run(root.symlink("d", "link")).unwrap();
+        let upper = MemFs::new();
+        let dir = OverlayDir::new(Box::new(lower.root()), Box::new(upper.root()));
+        (dir, lower, upper)
+    }
+
+    #[test]
+    fn copy_up() {
+        let (dir, lower, upper) = overlay();
+        assert_eq!(read(&dir, "d/f").unwrap(), b"lower f");
+        assert_eq!(read(&dir, "link/e/g").unwrap(), b"lower g");
+        assert_eq!(upper.used_bytes(), 0);
+
+        write(&dir, "link/e/g", b"upper g").expect("write through the symlink");
+        assert_eq!(read(&dir, "d/e/g").unwrap(), b"upper g");
+        assert_eq!(read(&lower.root(), "d/e/g").unwrap(), b"lower g");
+        assert_eq!(read(&upper.root(), "d/e/g").unwrap(), b"upper g");
+        assert!(read(&upper.root(), "d/f").is_err());
+
+        // Appending copies the existing contents up first.
+        let f =
+            run(dir.open_file(false, "d/f", OFlags::empty(), true, true, FdFlags::APPEND)).unwrap();
+        run(f.write_vectored(&[IoSlice::new(b", appended")])).unwrap();
+        assert_eq!(read(&dir, "d/f").unwrap(), b"lower f, appended");
+        assert_eq!(read(&lower.root(), "d/f").unwrap(), b"lower f");
+
+        write(&dir, "d/new", b"new").unwrap();
+        assert_eq!(names(&dir), [".", "..", "d", "link"]);
+        let d = run(dir.open_dir(false, "d")).unwrap();
+        assert_eq!(names(&*d), [".", "..", "e", "f", "new"]);
+        assert_eq!(
+            names(&lower.root().open_dir_(false, "d").unwrap()),
+            [".", "..", "e", "f"]
+        );
+
+        let err = run(dir.open_file(
+            false,
+            "d/f",
+            OFlags::CREATE | OFlags::EXCLUSIVE,
+            false,
+            true,
+            FdFlags::empty(),
+        ))
+        .err()
+        .unwrap();
+        assert!(is(err, |k| matches!(k, ErrorKind::Exist)));
+        assert!(is(read(&dir, "../d/f").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Perm
+        )));
+    }
+
+    #[test]
+    fn whiteouts() {
+        let (dir, lower, _upper) = overlay();
+        run(dir.unlink_file("d/f")).expect("unlink a lower file");
+        assert!(is(read(&dir, "d/f").unwrap_err(), |k| matches!(
+            k,
+            ErrorKind::Noent
+        )));
+        assert_eq!(read(&lower.root(), "d/f").unwrap(), b"lower f");
+        write(&dir, "d/f", b"recreated").unwrap();
+        assert_eq!(read(&dir, "d/f").unwrap(), b"recreated");
+
+        assert!(is(run(dir.remove_dir("d/e")).unwrap_err(), |k| {
+            matches!(k, ErrorKind::Notempty)
+        }));
+        run(dir.unlink_file("d/e/g")).unwrap();
+        run(dir.remove_dir("d/e")).expect("remove an emptied lower dir");
+        let d = run(dir.open_dir(false, "d")).unwrap();
+        assert_eq!(names(&*d), [".", "..", "f"]);
+
+        // A directory created over a whiteout doesn't show the lower
+        // layer's contents.
+        run(dir.create_dir("d/e")).unwrap();
+        assert_eq!(names(&*run(d.open_dir(false, "e")).unwrap()), [".", ".."]);
+        assert!(read(&dir, "d/e/g").is_err());
+
+        run(dir.unlink_file("link")).unwrap();
+        assert!(run(dir.get_path_filestat("link", false)).is_err());
+        assert_eq!(names(&dir), [".", "..", "d"]);
+    }
+
+    #[test]
+    fn rename_and_link() {
+        let (dir, lower, upper) = overlay();
+        run(dir.rename("d/f", &dir, "f")).expect("rename a lower file");
+        assert_eq!(read(&dir, "f").unwrap(), b"lower f");
+        assert!(read(&dir, "d/f").is_err());
+        assert_eq!(read(&lower.root(), "d/f").unwrap(), b"lower f");
+
+        let d = run(dir.open_dir(false, "d")).unwrap();
+        run(d.hard_link("e/g", &dir, "g")).expect("link a lower file");
+        write(&dir, "g", b"linked").unwrap();
+        assert_eq!(read(&dir, "d/e/g").unwrap(), b"linked");
+        assert_eq!(read(&lower.root(), "d/e/g").unwrap(), b"lower g");
+
+        // Renaming over a lower file hides it.
+        write(&dir, "h", b"h").unwrap();
+        run(dir.rename("h", &*d, "e/g")).unwrap();
+        assert_eq!(read(&dir, "d/e/g").unwrap(), b"h");
+
+        let err = run(dir.rename("d", &dir, "moved")).unwrap_err();
+        assert!(is(err, |k| matches!(k, ErrorKind::Xdev)));
+        run(dir.create_dir("new")).unwrap();
+        write(&dir, "new/i", b"i").unwrap();
+        run(dir.rename("new", &dir, "d/new")).expect("rename an upper dir");
+        assert_eq!(read(&dir, "d/new/i").unwrap(), b"i");
+        assert_eq!(read(&upper.root(), "d/new/i").unwrap(), b"i");
+
+        let other = OverlayDir::new(Box::new(MemFs::new().root()), Box::new(MemFs::new().root()));
+        let err = run(dir.rename("f", &other, "f")).unwrap_err();
+        assert!(is(err, |k| matches!(k, ErrorKind::Xdev)));
+    }
+
+    fn run<F: std::future::Future>(future: F) -> F::Output {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        let mut f = Pin::from(Box::new(future));
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => panic!("memfs operations should never be pending"),
+        }
+
+        fn dummy_waker() -> Waker {
+            return unsafe { Waker::from_raw(clone(5 as *const _)) };
+
+            unsafe fn clone(ptr: *const ()) -> RawWaker {
+                assert_eq!(ptr as usize, 5);
+                const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+                RawWaker::new(ptr, &VTABLE)
+            }
+
+            unsafe fn wake(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn wake_by_ref(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn drop(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+        }
+    }
+}
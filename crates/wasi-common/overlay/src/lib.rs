@@ -0,0 +1,37 @@
+//! The `wasi-overlay` crate provides an `OverlayDir`, a `WasiDir` which
+//! combines a read-only lower directory with a writable upper directory, in
+//! the manner of a union or overlay filesystem. Both layers may be any
+//! `WasiDir`, such as a `wasi-cap-std-sync` `Dir` or a `wasi-memfs` `Dir`:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), wasi_common::Error> {
+//! let assets = wasi_memfs::MemFs::new();
+//! assets.unpack_tar(std::fs::File::open("assets.tar")?)?;
+//! let scratch = wasi_memfs::MemFs::with_quota(16 << 20);
+//! let dir = wasi_overlay::OverlayDir::new(Box::new(assets.root()), Box::new(scratch.root()));
+//! let root: Box<dyn wasi_common::WasiDir> = Box::new(dir);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Reads fall through to the lower layer for anything which the upper layer
+//! doesn't contain. The lower layer is never modified: the first write to a
+//! file or directory of the lower layer copies it, along with its parent
+//! directories, up into the upper layer. Removing or renaming an entry of the
+//! lower layer records a whiteout, which hides it, and everything beneath it,
+//! from then on. Like Linux's overlayfs, renaming a directory which exists in
+//! the lower layer fails with `EXDEV`, and files which were opened read-only
+//! before being copied up don't see later writes.
+//!
+//! Whiteouts live in memory, and are shared by every `OverlayDir` opened from
+//! the same root. An `OverlayDir` is inserted into a `WasiCtx` with
+//! `push_preopened_dir` like any other `WasiDir`, and the `DirCaps` and
+//! `FileCaps` given there apply to it as usual.
+//!
+//! Path resolution happens in the overlay, which confines it to the opened
+//! directory the way `wasi-cap-std-sync`'s `Dir` does: absolute paths, and
+//! `..` or symbolic links leading out of the directory, are rejected.
+
+mod dir;
+
+pub use dir::OverlayDir;
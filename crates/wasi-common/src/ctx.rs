@@ -1,6 +1,7 @@
 use crate::clocks::WasiClocks;
 use crate::dir::{DirCaps, DirEntry, WasiDir};
 use crate::file::{FileCaps, FileEntry, WasiFile};
+use crate::policy::WasiPolicy;
 use crate::sched::WasiSched;
 use crate::string_array::{StringArray, StringArrayError};
 use crate::table::Table;
@@ -15,6 +16,7 @@ pub struct WasiCtx {
     pub clocks: WasiClocks,
     pub sched: Box<dyn WasiSched>,
     pub table: Table,
+    pub policy: Option<Box<dyn WasiPolicy>>,
 }
 
 impl WasiCtx {
@@ -31,6 +33,7 @@ impl WasiCtx {
             clocks,
             sched,
             table,
+            policy: None,
         };
         s.set_stdin(Box::new(crate::pipe::ReadPipe::new(std::io::empty())));
         s.set_stdout(Box::new(crate::pipe::WritePipe::new(std::io::sink())));
@@ -82,13 +85,28 @@ impl WasiCtx {
         self.insert_file(2, f, FileCaps::all());
     }
 
+    /// Consults `policy` before each hostcall from now on.
+    pub fn set_policy(&mut self, policy: Box<dyn WasiPolicy>) {
+        self.policy = Some(policy);
+    }
+
     pub fn push_preopened_dir(
         &mut self,
         dir: Box<dyn WasiDir>,
         path: impl AsRef<Path>,
     ) -> Result<(), Error> {
-        let caps = DirCaps::all();
-        let file_caps = FileCaps::all();
+        self.push_preopened_dir_with_caps(dir, DirCaps::all(), FileCaps::all(), path)
+    }
+
+    /// Like `push_preopened_dir`, but the guest can only use `caps` on the
+    /// directory and `file_caps` on the files it opens in it.
+    pub fn push_preopened_dir_with_caps(
+        &mut self,
+        dir: Box<dyn WasiDir>,
+        caps: DirCaps,
+        file_caps: FileCaps,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Error> {
         self.table().push(Box::new(DirEntry::new(
             caps,
             file_caps,
@@ -97,4 +115,24 @@ impl WasiCtx {
         )))?;
         Ok(())
     }
+
+    /// Replaces every file and directory in the table with `file(f)` or
+    /// `dir(d)`, keeping their capabilities.
+    pub(crate) fn map_table(
+        &mut self,
+        file: impl Fn(Box<dyn WasiFile>) -> Box<dyn WasiFile>,
+        dir: impl Fn(Box<dyn WasiDir>) -> Box<dyn WasiDir>,
+    ) {
+        for fd in self.table.keys() {
+            let entry = self.table.delete(fd).unwrap();
+            let entry = match entry.downcast::<FileEntry>() {
+                Ok(f) => Box::new((*f).map_file(&file)) as Box<dyn std::any::Any + Send + Sync>,
+                Err(entry) => match entry.downcast::<DirEntry>() {
+                    Ok(d) => Box::new((*d).map_dir(&dir)),
+                    Err(entry) => entry,
+                },
+            };
+            self.table.insert_at(fd, entry);
+        }
+    }
 }
@@ -72,6 +72,13 @@ impl DirEntry {
             dir,
         }
     }
+    /// Replaces the directory with `f(dir)`, keeping its capabilities.
+    pub(crate) fn map_dir(self, f: impl FnOnce(Box<dyn WasiDir>) -> Box<dyn WasiDir>) -> Self {
+        DirEntry {
+            dir: f(self.dir),
+            ..self
+        }
+    }
     pub fn capable_of_dir(&self, caps: DirCaps) -> Result<(), Error> {
         if self.caps.contains(caps) {
             Ok(())
@@ -151,21 +158,12 @@ pub struct DirFdStat {
 
 pub(crate) trait TableDirExt {
     fn get_dir(&self, fd: u32) -> Result<&DirEntry, Error>;
-    fn is_preopen(&self, fd: u32) -> bool;
 }
 
 impl TableDirExt for crate::table::Table {
     fn get_dir(&self, fd: u32) -> Result<&DirEntry, Error> {
         self.get(fd)
     }
-    fn is_preopen(&self, fd: u32) -> bool {
-        if self.is::<DirEntry>(fd) {
-            let dir_entry: &DirEntry = self.get(fd).unwrap();
-            dir_entry.preopen_path.is_some()
-        } else {
-            false
-        }
-    }
 }
 
 #[derive(Debug, Clone)]
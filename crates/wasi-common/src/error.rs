@@ -28,7 +28,7 @@ pub use anyhow::{Context, Error};
 /// Internal error type for the `wasi-common` crate.
 /// Contains variants of the WASI `$errno` type are added according to what is actually used internally by
 /// the crate. Not all values are represented presently.
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum ErrorKind {
     /// Errno::Noent: No such file or directory
     #[error("Noent: No such file or directory")]
@@ -51,24 +51,45 @@ pub enum ErrorKind {
     /// Errno::Io: I/O error
     #[error("Io: I/O error")]
     Io,
+    /// Errno::Isdir: Is a directory
+    #[error("Isdir: Is a directory")]
+    Isdir,
+    /// Errno::Loop: Too many levels of symbolic links
+    #[error("Loop: Too many levels of symbolic links")]
+    Loop,
+    /// Errno::Mfile: Too many open files
+    #[error("Mfile: Too many open files")]
+    Mfile,
     /// Errno::Nametoolong: Filename too long
     #[error("Nametoolong: Filename too long")]
     Nametoolong,
+    /// Errno::Nospc: No space left on device
+    #[error("Nospc: No space left on device")]
+    Nospc,
     /// Errno::Notdir: Not a directory or a symbolic link to a directory.
     #[error("Notdir: Not a directory or a symbolic link to a directory")]
     Notdir,
+    /// Errno::Notempty: Directory not empty
+    #[error("Notempty: Directory not empty")]
+    Notempty,
     /// Errno::Notsup: Not supported, or operation not supported on socket.
     #[error("Notsup: Not supported, or operation not supported on socket")]
     Notsup,
     /// Errno::Overflow: Value too large to be stored in data type.
     #[error("Overflow: Value too large to be stored in data type")]
     Overflow,
+    /// Errno::Perm: Operation not permitted
+    #[error("Perm: Operation not permitted")]
+    Perm,
     /// Errno::Range: Result too large
     #[error("Range: Result too large")]
     Range,
     /// Errno::Spipe: Invalid seek
     #[error("Spipe: Invalid seek")]
     Spipe,
+    /// Errno::Xdev: Cross-device link
+    #[error("Xdev: Cross-device link")]
+    Xdev,
     /// Errno::NotCapable: Not capable
     #[error("Not capable")]
     NotCapable,
@@ -83,12 +104,19 @@ pub trait ErrorExt {
     fn illegal_byte_sequence() -> Self;
     fn invalid_argument() -> Self;
     fn io() -> Self;
+    fn is_dir() -> Self;
+    fn symlink_loop() -> Self;
+    fn too_many_open_files() -> Self;
     fn name_too_long() -> Self;
+    fn no_space() -> Self;
     fn not_dir() -> Self;
+    fn not_empty() -> Self;
     fn not_supported() -> Self;
     fn overflow() -> Self;
+    fn perm() -> Self;
     fn range() -> Self;
     fn seek_pipe() -> Self;
+    fn cross_device() -> Self;
     fn not_capable() -> Self;
 }
 
@@ -117,24 +145,45 @@ impl ErrorExt for Error {
     fn io() -> Self {
         ErrorKind::Io.into()
     }
+    fn is_dir() -> Self {
+        ErrorKind::Isdir.into()
+    }
+    fn symlink_loop() -> Self {
+        ErrorKind::Loop.into()
+    }
+    fn too_many_open_files() -> Self {
+        ErrorKind::Mfile.into()
+    }
     fn name_too_long() -> Self {
         ErrorKind::Nametoolong.into()
     }
+    fn no_space() -> Self {
+        ErrorKind::Nospc.into()
+    }
     fn not_dir() -> Self {
         ErrorKind::Notdir.into()
     }
+    fn not_empty() -> Self {
+        ErrorKind::Notempty.into()
+    }
     fn not_supported() -> Self {
         ErrorKind::Notsup.into()
     }
     fn overflow() -> Self {
         ErrorKind::Overflow.into()
     }
+    fn perm() -> Self {
+        ErrorKind::Perm.into()
+    }
     fn range() -> Self {
         ErrorKind::Range.into()
     }
     fn seek_pipe() -> Self {
         ErrorKind::Spipe.into()
     }
+    fn cross_device() -> Self {
+        ErrorKind::Xdev.into()
+    }
     fn not_capable() -> Self {
         ErrorKind::NotCapable.into()
     }
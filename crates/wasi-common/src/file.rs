@@ -37,6 +37,25 @@ pub trait WasiFile: Send + Sync {
 
     async fn readable(&self) -> Result<(), Error>;
     async fn writable(&self) -> Result<(), Error>;
+
+    // Socket operations. Files which aren't sockets keep these default
+    // implementations, which fail with `badf`.
+    async fn sock_accept(&self, _fdflags: FdFlags) -> Result<Box<dyn WasiFile>, Error> {
+        Err(Error::badf())
+    }
+    async fn sock_recv<'a>(
+        &self,
+        _ri_data: &mut [std::io::IoSliceMut<'a>],
+        _ri_flags: RiFlags,
+    ) -> Result<(u64, RoFlags), Error> {
+        Err(Error::badf())
+    }
+    async fn sock_send<'a>(&self, _si_data: &[std::io::IoSlice<'a>]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn sock_shutdown(&self, _how: SdFlags) -> Result<(), Error> {
+        Err(Error::badf())
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -62,6 +81,26 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct RiFlags: u32 {
+        const RECV_PEEK    = 0b1;
+        const RECV_WAITALL = 0b10;
+    }
+}
+
+bitflags! {
+    pub struct RoFlags: u32 {
+        const RECV_DATA_TRUNCATED = 0b1;
+    }
+}
+
+bitflags! {
+    pub struct SdFlags: u32 {
+        const RD = 0b1;
+        const WR = 0b10;
+    }
+}
+
 bitflags! {
     pub struct OFlags: u32 {
         const CREATE    = 0b1;
@@ -106,6 +145,14 @@ impl FileEntry {
         FileEntry { caps, file }
     }
 
+    /// Replaces the file with `f(file)`, keeping its capabilities.
+    pub(crate) fn map_file(self, f: impl FnOnce(Box<dyn WasiFile>) -> Box<dyn WasiFile>) -> Self {
+        FileEntry {
+            caps: self.caps,
+            file: f(self.file),
+        }
+    }
+
     pub fn capable_of(&self, caps: FileCaps) -> Result<(), Error> {
         if self.caps.contains(caps) {
             Ok(())
@@ -161,6 +208,7 @@ bitflags! {
         const FILESTAT_SET_SIZE  = 0b10000000000;
         const FILESTAT_SET_TIMES = 0b100000000000;
         const POLL_READWRITE     = 0b1000000000000;
+        const SOCK_SHUTDOWN      = 0b10000000000000;
     }
 }
 
@@ -56,11 +56,14 @@ pub mod dir;
 mod error;
 pub mod file;
 pub mod pipe;
+pub mod policy;
 pub mod random;
+pub mod replay;
 pub mod sched;
 pub mod snapshots;
 mod string_array;
 pub mod table;
+pub mod virtual_clock;
 
 pub use cap_rand::RngCore;
 pub use clocks::{SystemTimeSpec, WasiClocks, WasiMonotonicClock, WasiSystemClock};
@@ -68,6 +71,7 @@ pub use ctx::WasiCtx;
 pub use dir::WasiDir;
 pub use error::{Context, Error, ErrorExt, ErrorKind};
 pub use file::WasiFile;
+pub use policy::WasiPolicy;
 pub use sched::{Poll, WasiSched};
 pub use string_array::StringArrayError;
 pub use table::Table;
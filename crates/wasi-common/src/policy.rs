@@ -0,0 +1,417 @@
+//! Policies which are consulted before each hostcall, to audit or restrict
+//! what a guest does beyond the static `DirCaps` and `FileCaps` of its
+//! descriptors.
+//!
+//! A `WasiPolicy` is given to a `WasiCtx` with `WasiCtx::set_policy`. Every
+//! hostcall describes itself to the policy as a [`Call`] before it does
+//! anything else, and the policy's [`Decision`] allows it, denies it with an
+//! errno of the policy's choosing, or rewrites the path it operates on.
+//! Preview0 calls are described by the names of their preview1 equivalents.
+//! Calls which operate on two descriptors or paths, such as `fd_renumber`
+//! and `path_rename`, consult the policy once for each, in argument order.
+//!
+//! [`JsonAuditLog`] is a policy which logs every call, and the decision of
+//! another policy about it, as a line of JSON.
+
+use crate::dir::DirCaps;
+use crate::file::FileCaps;
+use crate::{Error, ErrorKind, WasiCtx};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Decides whether, and how, the hostcalls of a guest go ahead.
+pub trait WasiPolicy: Send + Sync {
+    fn check(&self, call: &Call) -> Decision;
+}
+
+/// A hostcall which is about to happen.
+#[derive(Debug, Clone)]
+pub struct Call<'a> {
+    /// The name of the preview1 function, e.g. `"path_open"`.
+    pub name: &'static str,
+    /// The descriptor the call operates on, if any.
+    pub fd: Option<u32>,
+    /// The path the call operates on, relative to `fd`, as the guest gave it.
+    pub path: Option<&'a str>,
+    /// The capabilities the call requires.
+    pub rights: Rights,
+}
+
+/// The capabilities a hostcall requires: `dir` if `fd` is a directory, and
+/// `file` if it's a file. `path_open` requires `dir` of its directory, and
+/// asks for `file` for the file it opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rights {
+    pub dir: DirCaps,
+    pub file: FileCaps,
+}
+
+impl Rights {
+    pub fn none() -> Self {
+        Rights {
+            dir: DirCaps::empty(),
+            file: FileCaps::empty(),
+        }
+    }
+}
+
+impl From<DirCaps> for Rights {
+    fn from(dir: DirCaps) -> Self {
+        Rights {
+            dir,
+            file: FileCaps::empty(),
+        }
+    }
+}
+
+impl From<FileCaps> for Rights {
+    fn from(file: FileCaps) -> Self {
+        Rights {
+            dir: DirCaps::empty(),
+            file,
+        }
+    }
+}
+
+/// What a `WasiPolicy` decides about a `Call`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    /// Fails the call with the errno of the given kind.
+    Deny(ErrorKind),
+    /// Allows the call, but on the given path instead of the guest's. Calls
+    /// which don't operate on a path are just allowed.
+    Rewrite(String),
+}
+
+impl WasiCtx {
+    /// Consults the policy, if any, about a call which doesn't operate on
+    /// a path.
+    pub(crate) fn check(
+        &self,
+        name: &'static str,
+        fd: Option<u32>,
+        rights: impl Into<Rights>,
+    ) -> Result<(), Error> {
+        self.consult(name, fd, None, rights.into()).map(drop)
+    }
+
+    /// Consults the policy, if any, about a call which operates on `path`,
+    /// returning the path which the call should go ahead with.
+    pub(crate) fn check_path<'a>(
+        &self,
+        name: &'static str,
+        fd: u32,
+        path: &'a str,
+        rights: impl Into<Rights>,
+    ) -> Result<Cow<'a, str>, Error> {
+        match self.consult(name, Some(fd), Some(path), rights.into())? {
+            Some(rewritten) => Ok(Cow::Owned(rewritten)),
+            None => Ok(Cow::Borrowed(path)),
+        }
+    }
+
+    /// Returns the path the policy rewrote the call to, if it did.
+    fn consult(
+        &self,
+        name: &'static str,
+        fd: Option<u32>,
+        path: Option<&str>,
+        rights: Rights,
+    ) -> Result<Option<String>, Error> {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return Ok(None),
+        };
+        let call = Call {
+            name,
+            fd,
+            path,
+            rights,
+        };
+        match policy.check(&call) {
+            Decision::Allow => Ok(None),
+            Decision::Deny(kind) => {
+                Err(Error::from(kind).context(format!("{} denied by policy", name)))
+            }
+            Decision::Rewrite(rewritten) => Ok(path.map(|_| rewritten)),
+        }
+    }
+}
+
+/// A policy which allows every call.
+pub struct AllowAll;
+
+impl WasiPolicy for AllowAll {
+    fn check(&self, _call: &Call) -> Decision {
+        Decision::Allow
+    }
+}
+
+/// A policy which writes every call, and what another policy decided about
+/// it, to an audit log, one JSON object per line:
+///
+/// ```json
+/// {"time":1629000000.123,"call":"path_open","fd":3,"path":"data.txt","rights":{"dir":["OPEN"],"file":["READ","SEEK"]},"decision":"allow"}
+/// {"time":1629000000.124,"call":"path_unlink_file","fd":3,"path":"data.txt","rights":{"dir":["UNLINK_FILE"],"file":[]},"decision":"deny","errno":"perm"}
+/// {"time":1629000000.125,"call":"path_open","fd":3,"path":"old.txt","rights":{"dir":["OPEN"],"file":["READ"]},"decision":"rewrite","rewritten":"new.txt"}
+/// ```
+///
+/// Lines are written as the calls happen, without buffering. If a line
+/// can't be written, the call is denied with `EIO`, so that no call goes
+/// unaudited.
+pub struct JsonAuditLog {
+    out: Mutex<Box<dyn Write + Send>>,
+    policy: Box<dyn WasiPolicy>,
+}
+
+impl JsonAuditLog {
+    /// Creates an audit log, written to `out`, which allows every call.
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self::with_policy(out, Box::new(AllowAll))
+    }
+
+    /// Creates an audit log, written to `out`, of the decisions of `policy`.
+    pub fn with_policy(out: impl Write + Send + 'static, policy: Box<dyn WasiPolicy>) -> Self {
+        JsonAuditLog {
+            out: Mutex::new(Box::new(out)),
+            policy,
+        }
+    }
+}
+
+impl WasiPolicy for JsonAuditLog {
+    fn check(&self, call: &Call) -> Decision {
+        let decision = self.policy.check(call);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut line = format!(
+            "{{\"time\":{}.{:03},\"call\":\"{}\"",
+            time.as_secs(),
+            time.subsec_millis(),
+            call.name
+        );
+        if let Some(fd) = call.fd {
+            write!(line, ",\"fd\":{}", fd).unwrap();
+        }
+        if let Some(path) = call.path {
+            line.push_str(",\"path\":");
+            json_string(&mut line, path);
+        }
+        line.push_str(",\"rights\":{\"dir\":");
+        json_flags(
+            &mut line,
+            DIR_CAP_NAMES
+                .iter()
+                .filter(|(cap, _)| call.rights.dir.contains(*cap))
+                .map(|(_, name)| *name),
+        );
+        line.push_str(",\"file\":");
+        json_flags(
+            &mut line,
+            FILE_CAP_NAMES
+                .iter()
+                .filter(|(cap, _)| call.rights.file.contains(*cap))
+                .map(|(_, name)| *name),
+        );
+        line.push('}');
+        match &decision {
+            Decision::Allow => line.push_str(",\"decision\":\"allow\""),
+            Decision::Deny(kind) => write!(
+                line,
+                ",\"decision\":\"deny\",\"errno\":\"{}\"",
+                format!("{:?}", kind).to_lowercase()
+            )
+            .unwrap(),
+            Decision::Rewrite(path) => {
+                line.push_str(",\"decision\":\"rewrite\",\"rewritten\":");
+                json_string(&mut line, path);
+            }
+        }
+        line.push_str("}\n");
+        match self.out.lock().unwrap().write_all(line.as_bytes()) {
+            Ok(()) => decision,
+            Err(_) => Decision::Deny(ErrorKind::Io),
+        }
+    }
+}
+
+/// The names that the JSON audit log uses for each directory capability.
+const DIR_CAP_NAMES: &[(DirCaps, &str)] = &[
+    (DirCaps::CREATE_DIRECTORY, "CREATE_DIRECTORY"),
+    (DirCaps::CREATE_FILE, "CREATE_FILE"),
+    (DirCaps::LINK_SOURCE, "LINK_SOURCE"),
+    (DirCaps::LINK_TARGET, "LINK_TARGET"),
+    (DirCaps::OPEN, "OPEN"),
+    (DirCaps::READDIR, "READDIR"),
+    (DirCaps::READLINK, "READLINK"),
+    (DirCaps::RENAME_SOURCE, "RENAME_SOURCE"),
+    (DirCaps::RENAME_TARGET, "RENAME_TARGET"),
+    (DirCaps::SYMLINK, "SYMLINK"),
+    (DirCaps::REMOVE_DIRECTORY, "REMOVE_DIRECTORY"),
+    (DirCaps::UNLINK_FILE, "UNLINK_FILE"),
+    (DirCaps::PATH_FILESTAT_GET, "PATH_FILESTAT_GET"),
+    (DirCaps::PATH_FILESTAT_SET_TIMES, "PATH_FILESTAT_SET_TIMES"),
+    (DirCaps::FILESTAT_GET, "FILESTAT_GET"),
+    (DirCaps::FILESTAT_SET_TIMES, "FILESTAT_SET_TIMES"),
+];
+
+/// The names that the JSON audit log uses for each file capability.
+const FILE_CAP_NAMES: &[(FileCaps, &str)] = &[
+    (FileCaps::DATASYNC, "DATASYNC"),
+    (FileCaps::READ, "READ"),
+    (FileCaps::SEEK, "SEEK"),
+    (FileCaps::FDSTAT_SET_FLAGS, "FDSTAT_SET_FLAGS"),
+    (FileCaps::SYNC, "SYNC"),
+    (FileCaps::TELL, "TELL"),
+    (FileCaps::WRITE, "WRITE"),
+    (FileCaps::ADVISE, "ADVISE"),
+    (FileCaps::ALLOCATE, "ALLOCATE"),
+    (FileCaps::FILESTAT_GET, "FILESTAT_GET"),
+    (FileCaps::FILESTAT_SET_SIZE, "FILESTAT_SET_SIZE"),
+    (FileCaps::FILESTAT_SET_TIMES, "FILESTAT_SET_TIMES"),
+    (FileCaps::POLL_READWRITE, "POLL_READWRITE"),
+    (FileCaps::SOCK_SHUTDOWN, "SOCK_SHUTDOWN"),
+];
+
+/// Writes flag names as a JSON array.
+fn json_flags<'a>(out: &mut String, names: impl Iterator<Item = &'a str>) {
+    out.push('[');
+    for (i, name) in names.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json_string(out, name);
+    }
+    out.push(']');
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Denies unlinking, and redirects `old.txt` to `new.txt`.
+    struct Restrict;
+
+    impl WasiPolicy for Restrict {
+        fn check(&self, call: &Call) -> Decision {
+            match (call.name, call.path) {
+                ("path_unlink_file", _) => Decision::Deny(ErrorKind::Perm),
+                (_, Some("old.txt")) => Decision::Rewrite("new.txt".to_owned()),
+                _ => Decision::Allow,
+            }
+        }
+    }
+
+    #[test]
+    fn audit_log() {
+        let out = SharedBuf::default();
+        let log = JsonAuditLog::with_policy(out.clone(), Box::new(Restrict));
+        let read = Rights {
+            dir: DirCaps::OPEN,
+            file: FileCaps::READ | FileCaps::SEEK,
+        };
+        let calls = [
+            (
+                Call {
+                    name: "path_open",
+                    fd: Some(3),
+                    path: Some("dir/\"quoted\"\n"),
+                    rights: read,
+                },
+                Decision::Allow,
+            ),
+            (
+                Call {
+                    name: "path_unlink_file",
+                    fd: Some(3),
+                    path: Some("data.txt"),
+                    rights: DirCaps::UNLINK_FILE.into(),
+                },
+                Decision::Deny(ErrorKind::Perm),
+            ),
+            (
+                Call {
+                    name: "path_open",
+                    fd: Some(3),
+                    path: Some("old.txt"),
+                    rights: read,
+                },
+                Decision::Rewrite("new.txt".to_owned()),
+            ),
+            (
+                Call {
+                    name: "random_get",
+                    fd: None,
+                    path: None,
+                    rights: Rights::none(),
+                },
+                Decision::Allow,
+            ),
+        ];
+        for (call, decision) in calls.iter() {
+            assert_eq!(log.check(call), *decision);
+        }
+
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines = out
+            .lines()
+            // Strip the time, which varies.
+            .map(|line| &line[line.find(",\"call\"").unwrap() + 1..])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                r#""call":"path_open","fd":3,"path":"dir/\"quoted\"\n","rights":{"dir":["OPEN"],"file":["READ","SEEK"]},"decision":"allow"}"#,
+                r#""call":"path_unlink_file","fd":3,"path":"data.txt","rights":{"dir":["UNLINK_FILE"],"file":[]},"decision":"deny","errno":"perm"}"#,
+                r#""call":"path_open","fd":3,"path":"old.txt","rights":{"dir":["OPEN"],"file":["READ","SEEK"]},"decision":"rewrite","rewritten":"new.txt"}"#,
+                r#""call":"random_get","rights":{"dir":[],"file":[]},"decision":"allow"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn cap_names_cover_every_cap() {
+        let dir = DIR_CAP_NAMES
+            .iter()
+            .fold(DirCaps::empty(), |caps, (cap, _)| caps | *cap);
+        assert_eq!(dir, DirCaps::all());
+        let file = FILE_CAP_NAMES
+            .iter()
+            .fold(FileCaps::empty(), |caps, (cap, _)| caps | *cap);
+        assert_eq!(file, FileCaps::all());
+    }
+}
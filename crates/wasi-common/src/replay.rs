@@ -0,0 +1,1222 @@
+//! Recording and replaying of the nondeterminism which a guest observes
+//! through WASI, so that a run can be reproduced exactly.
+//!
+//! A [`Recorder`] wraps the clocks, random number generator, scheduler,
+//! files and directories of a `WasiCtx`, and logs every nondeterministic
+//! result which they produce to a trace. A [`Replayer`] wraps another
+//! `WasiCtx` in the same way, and feeds the results of the trace back to the
+//! guest in order, without consulting the host. The trace records:
+//!
+//! * the arguments and environment variables,
+//! * clock readings and resolutions,
+//! * random bytes,
+//! * the data read from files, including stdin, and errors reading them,
+//! * the number of bytes ready to be read from files, and
+//! * which subscriptions of `poll_oneoff` are ready, and for how many bytes.
+//!
+//! Everything else is passed through to the files and directories of the
+//! replaying `WasiCtx`. In particular, file metadata, directory listings and
+//! socket operations aren't recorded, so a replay should be given the same
+//! preopened directories as the recording. Files are wrapped when they're
+//! opened from a wrapped directory, but files which are inserted into the
+//! context afterwards must be wrapped with [`Recorder::file`] or
+//! [`Replayer::file`].
+//!
+//! If the guest asks for something different to what was recorded next, the
+//! replay has diverged from the trace, and traps. Clocks and random number
+//! generators can't fail, so they panic instead.
+
+use crate::clocks::{WasiClocks, WasiMonotonicClock, WasiSystemClock};
+use crate::dir::{ReaddirCursor, ReaddirEntity, WasiDir};
+use crate::file::{
+    Advice, FdFlags, FileType, Filestat, OFlags, RiFlags, RoFlags, SdFlags, WasiFile,
+};
+use crate::sched::{Poll, RwEventFlags, Subscription, WasiSched};
+use crate::string_array::StringArray;
+use crate::{Error, ErrorExt, ErrorKind, SystemTimeSpec, WasiCtx};
+use cap_rand::RngCore;
+use cap_std::time::{Duration, Instant, SystemTime};
+use std::any::Any;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// The start of every trace, whose last byte is the version of its format.
+const MAGIC: &[u8] = b"\0wasi-trace\x01";
+
+/// Records the nondeterminism observed by a guest to a trace.
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<Recording>>);
+
+struct Recording {
+    out: BufWriter<Box<dyn Write + Send>>,
+    /// Whether logging is suspended, while the scheduler polls, because
+    /// replays don't poll.
+    suspended: bool,
+    /// The first error writing the trace, which is reported by `flush`.
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    /// Creates a `Recorder` which writes its trace to `trace`.
+    pub fn new(trace: impl Write + Send + 'static) -> Self {
+        let mut out = BufWriter::new(Box::new(trace) as Box<dyn Write + Send>);
+        let error = out.write_all(MAGIC).err();
+        Recorder(Arc::new(Mutex::new(Recording {
+            out,
+            suspended: false,
+            error,
+        })))
+    }
+
+    /// Wraps the clocks, random number generator, scheduler, files and
+    /// directories of `ctx` to record what they produce, and records its
+    /// arguments and environment variables.
+    pub fn record(&self, ctx: WasiCtx) -> WasiCtx {
+        self.log(Event::Args(ctx.args.elems().to_vec()));
+        self.log(Event::Env(ctx.env.elems().to_vec()));
+        wrap(ctx, Trace::Record(self.clone()))
+    }
+
+    /// Wraps `file` to record the data read from it.
+    pub fn file(&self, file: Box<dyn WasiFile>) -> Box<dyn WasiFile> {
+        Trace::Record(self.clone()).file(file)
+    }
+
+    /// Wraps `dir` to record the data read from the files opened from it.
+    pub fn dir(&self, dir: Box<dyn WasiDir>) -> Box<dyn WasiDir> {
+        Trace::Record(self.clone()).dir(dir)
+    }
+
+    /// Writes out everything recorded so far, reporting any error which
+    /// happened while writing the trace.
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut recording = self.0.lock().unwrap();
+        if let Some(e) = recording.error.take() {
+            return Err(Error::from(e).context("failed to write trace"));
+        }
+        recording.out.flush()?;
+        Ok(())
+    }
+
+    fn log(&self, event: Event) {
+        let mut recording = self.0.lock().unwrap();
+        if recording.suspended || recording.error.is_some() {
+            return;
+        }
+        let mut bytes = Vec::new();
+        event.encode(&mut bytes);
+        if let Err(e) = recording.out.write_all(&bytes) {
+            recording.error = Some(e);
+        }
+    }
+
+    fn suspend(&self, suspended: bool) {
+        self.0.lock().unwrap().suspended = suspended;
+    }
+}
+
+/// Replays the nondeterminism recorded in a trace to a guest.
+#[derive(Clone)]
+pub struct Replayer(Arc<Mutex<BufReader<Box<dyn Read + Send>>>>);
+
+impl Replayer {
+    /// Creates a `Replayer` which reads its trace from `trace`.
+    pub fn new(trace: impl Read + Send + 'static) -> Result<Self, Error> {
+        let mut input = BufReader::new(Box::new(trace) as Box<dyn Read + Send>);
+        let mut magic = Vec::new();
+        (&mut input)
+            .take(MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if magic != MAGIC {
+            return Err(Error::invalid_argument()
+                .context("not a WASI trace, or one from an incompatible version"));
+        }
+        Ok(Replayer(Arc::new(Mutex::new(input))))
+    }
+
+    /// Wraps the clocks, random number generator, scheduler, files and
+    /// directories of `ctx` to replay what they produced, and replaces its
+    /// arguments and environment variables with the recorded ones.
+    pub fn replay(&self, mut ctx: WasiCtx) -> Result<WasiCtx, Error> {
+        let args = self.next("the arguments", |e| match e {
+            Event::Args(args) => Some(args),
+            _ => None,
+        })?;
+        let env = self.next("the environment", |e| match e {
+            Event::Env(env) => Some(env),
+            _ => None,
+        })?;
+        ctx.args = StringArray::new();
+        for arg in args {
+            ctx.args.push(arg)?;
+        }
+        ctx.env = StringArray::new();
+        for var in env {
+            ctx.env.push(var)?;
+        }
+        Ok(wrap(ctx, Trace::Replay(self.clone())))
+    }
+
+    /// Wraps `file` to replay the data read from it.
+    pub fn file(&self, file: Box<dyn WasiFile>) -> Box<dyn WasiFile> {
+        Trace::Replay(self.clone()).file(file)
+    }
+
+    /// Wraps `dir` to replay the data read from the files opened from it.
+    pub fn dir(&self, dir: Box<dyn WasiDir>) -> Box<dyn WasiDir> {
+        Trace::Replay(self.clone()).dir(dir)
+    }
+
+    /// Reads the next event of the trace, which `f` extracts the result
+    /// from if it's the `expected` kind of event.
+    fn next<T>(&self, expected: &str, f: impl FnOnce(Event) -> Option<T>) -> Result<T, Error> {
+        let mut input = self.0.lock().unwrap();
+        let (found, result) = match Event::decode(&mut *input)? {
+            Some(event) => (event.describe(), f(event)),
+            None => ("the end of the trace", None),
+        };
+        result.ok_or_else(|| {
+            Error::trap(format!(
+                "replay diverged from the trace: expected {}, found {}",
+                expected, found
+            ))
+        })
+    }
+
+    /// Like `next`, for the interfaces which can't fail.
+    fn expect<T>(&self, expected: &str, f: impl FnOnce(Event) -> Option<T>) -> T {
+        match self.next(expected, f) {
+            Ok(value) => value,
+            Err(e) => panic!("{:#}", e),
+        }
+    }
+
+    /// Replays a read into `bufs`.
+    fn read(&self, bufs: &mut [io::IoSliceMut<'_>]) -> Result<u64, Error> {
+        let data = self
+            .next("a read", |e| match e {
+                Event::Read(data) => Some(data),
+                _ => None,
+            })?
+            .map_err(RecordedError::into_error)?;
+        let mut rest = &data[..];
+        for buf in bufs.iter_mut() {
+            let n = buf.len().min(rest.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            rest = &rest[n..];
+        }
+        if !rest.is_empty() {
+            return Err(Error::trap(
+                "replay diverged from the trace: a read into a smaller buffer",
+            ));
+        }
+        Ok(data.len() as u64)
+    }
+}
+
+#[derive(Clone)]
+enum Trace {
+    Record(Recorder),
+    Replay(Replayer),
+}
+
+impl Trace {
+    fn file(self, inner: Box<dyn WasiFile>) -> Box<dyn WasiFile> {
+        Box::new(TraceFile { inner, trace: self })
+    }
+
+    fn dir(self, inner: Box<dyn WasiDir>) -> Box<dyn WasiDir> {
+        Box::new(TraceDir { inner, trace: self })
+    }
+}
+
+fn wrap(mut ctx: WasiCtx, trace: Trace) -> WasiCtx {
+    let creation_time = ctx.clocks.creation_time;
+    ctx.clocks = WasiClocks {
+        system: Box::new(TraceSystemClock {
+            inner: ctx.clocks.system,
+            trace: trace.clone(),
+        }),
+        monotonic: Box::new(TraceMonotonicClock {
+            inner: ctx.clocks.monotonic,
+            creation_time,
+            trace: trace.clone(),
+        }),
+        creation_time,
+    };
+    ctx.random = Box::new(TraceRandom {
+        inner: ctx.random,
+        trace: trace.clone(),
+    });
+    ctx.sched = Box::new(TraceSched {
+        inner: ctx.sched,
+        trace: trace.clone(),
+    });
+    ctx.map_table(|f| trace.clone().file(f), |d| trace.clone().dir(d));
+    ctx
+}
+
+struct TraceSystemClock {
+    inner: Box<dyn WasiSystemClock>,
+    trace: Trace,
+}
+
+impl WasiSystemClock for TraceSystemClock {
+    fn resolution(&self) -> Duration {
+        match &self.trace {
+            Trace::Record(r) => {
+                let resolution = self.inner.resolution();
+                r.log(Event::SystemClockResolution(resolution));
+                resolution
+            }
+            Trace::Replay(r) => r.expect("the system clock's resolution", |e| match e {
+                Event::SystemClockResolution(resolution) => Some(resolution),
+                _ => None,
+            }),
+        }
+    }
+    fn now(&self, precision: Duration) -> SystemTime {
+        match &self.trace {
+            Trace::Record(r) => {
+                let now = self.inner.now(precision);
+                let since_epoch = now
+                    .into_std()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                r.log(Event::SystemClockNow(since_epoch));
+                now
+            }
+            Trace::Replay(r) => {
+                let since_epoch = r.expect("a system clock reading", |e| match e {
+                    Event::SystemClockNow(since_epoch) => Some(since_epoch),
+                    _ => None,
+                });
+                SystemTime::from_std(UNIX_EPOCH + since_epoch)
+            }
+        }
+    }
+}
+
+/// Monotonic clock readings are recorded relative to the creation time of
+/// the context, since `Instant`s are meaningless outside of the process.
+struct TraceMonotonicClock {
+    inner: Box<dyn WasiMonotonicClock>,
+    creation_time: Instant,
+    trace: Trace,
+}
+
+impl WasiMonotonicClock for TraceMonotonicClock {
+    fn resolution(&self) -> Duration {
+        match &self.trace {
+            Trace::Record(r) => {
+                let resolution = self.inner.resolution();
+                r.log(Event::MonotonicClockResolution(resolution));
+                resolution
+            }
+            Trace::Replay(r) => r.expect("the monotonic clock's resolution", |e| match e {
+                Event::MonotonicClockResolution(resolution) => Some(resolution),
+                _ => None,
+            }),
+        }
+    }
+    fn now(&self, precision: Duration) -> Instant {
+        match &self.trace {
+            Trace::Record(r) => {
+                let now = self.inner.now(precision);
+                let elapsed = now
+                    .checked_duration_since(self.creation_time)
+                    .unwrap_or_default();
+                r.log(Event::MonotonicClockNow(elapsed));
+                now
+            }
+            Trace::Replay(r) => {
+                let elapsed = r.expect("a monotonic clock reading", |e| match e {
+                    Event::MonotonicClockNow(elapsed) => Some(elapsed),
+                    _ => None,
+                });
+                self.creation_time + elapsed
+            }
+        }
+    }
+}
+
+struct TraceRandom {
+    inner: Box<dyn RngCore + Send + Sync>,
+    trace: Trace,
+}
+
+impl RngCore for TraceRandom {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        match &self.trace {
+            Trace::Record(r) => {
+                self.inner.fill_bytes(buf);
+                r.log(Event::Random(buf.to_vec()));
+            }
+            Trace::Replay(r) => {
+                let expected = format!("{} random bytes", buf.len());
+                let bytes = r.expect(&expected, |e| match e {
+                    Event::Random(bytes) if bytes.len() == buf.len() => Some(bytes),
+                    _ => None,
+                });
+                buf.copy_from_slice(&bytes);
+            }
+        }
+    }
+    fn try_fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), cap_rand::Error> {
+        if let Trace::Record(r) = &self.trace {
+            self.inner.try_fill_bytes(buf)?;
+            r.log(Event::Random(buf.to_vec()));
+        } else {
+            self.fill_bytes(buf);
+        }
+        Ok(())
+    }
+}
+
+struct TraceSched {
+    inner: Box<dyn WasiSched>,
+    trace: Trace,
+}
+
+#[wiggle::async_trait]
+impl WasiSched for TraceSched {
+    async fn poll_oneoff<'a>(&self, poll: &mut Poll<'a>) -> Result<(), Error> {
+        match &self.trace {
+            Trace::Record(r) => {
+                // The clocks which the scheduler reads while it waits for
+                // the subscriptions aren't read by a replay.
+                r.suspend(true);
+                let result = self.inner.poll_oneoff(poll).await;
+                r.suspend(false);
+                let recorded = match &result {
+                    Ok(()) => Ok(poll
+                        .rw_subscriptions()
+                        .map(|sub| match sub {
+                            Subscription::Read(rw) | Subscription::Write(rw) => {
+                                let status = rw.result();
+                                let recorded = status.as_ref().map(|status| match status {
+                                    Ok((size, flags)) => Ok((*size, flags.bits())),
+                                    Err(e) => Err(RecordedError::new(e)),
+                                });
+                                match status {
+                                    Some(Ok((size, flags))) => rw.complete(size, flags),
+                                    Some(Err(e)) => rw.error(e),
+                                    None => {}
+                                }
+                                recorded
+                            }
+                            Subscription::MonotonicClock(_) => unreachable!(),
+                        })
+                        .collect()),
+                    Err(e) => Err(RecordedError::new(e)),
+                };
+                r.log(Event::Poll(recorded));
+                result
+            }
+            Trace::Replay(r) => {
+                let recorded = r.next("a poll_oneoff", |e| match e {
+                    Event::Poll(recorded) => Some(recorded),
+                    _ => None,
+                })?;
+                let mut recorded = recorded.map_err(RecordedError::into_error)?.into_iter();
+                for sub in poll.rw_subscriptions() {
+                    let status = recorded.next().ok_or_else(|| {
+                        Error::trap("replay diverged from the trace: more subscriptions to poll")
+                    })?;
+                    match sub {
+                        Subscription::Read(rw) | Subscription::Write(rw) => match status {
+                            Some(Ok((size, flags))) => {
+                                rw.complete(size, RwEventFlags::from_bits_truncate(flags))
+                            }
+                            Some(Err(e)) => rw.error(e.into_error()),
+                            None => {}
+                        },
+                        Subscription::MonotonicClock(_) => unreachable!(),
+                    }
+                }
+                if recorded.next().is_some() {
+                    return Err(Error::trap(
+                        "replay diverged from the trace: fewer subscriptions to poll",
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+    async fn sched_yield(&self) -> Result<(), Error> {
+        match &self.trace {
+            Trace::Record(_) => self.inner.sched_yield().await,
+            Trace::Replay(_) => Ok(()),
+        }
+    }
+    async fn sleep(&self, duration: Duration) -> Result<(), Error> {
+        // Replays don't wait: the guest sees the recorded clock readings
+        // when it wakes up anyway.
+        match &self.trace {
+            Trace::Record(_) => self.inner.sleep(duration).await,
+            Trace::Replay(_) => Ok(()),
+        }
+    }
+}
+
+/// A file whose reads are recorded or replayed. Its `as_any` is that of the
+/// file it wraps, so that schedulers and `WasiDir`s can downcast it.
+struct TraceFile {
+    inner: Box<dyn WasiFile>,
+    trace: Trace,
+}
+
+#[wiggle::async_trait]
+impl WasiFile for TraceFile {
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+    async fn datasync(&self) -> Result<(), Error> {
+        self.inner.datasync().await
+    }
+    async fn sync(&self) -> Result<(), Error> {
+        self.inner.sync().await
+    }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        self.inner.get_filetype().await
+    }
+    async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+        self.inner.get_fdflags().await
+    }
+    async fn set_fdflags(&mut self, flags: FdFlags) -> Result<(), Error> {
+        self.inner.set_fdflags(flags).await
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.inner.get_filestat().await
+    }
+    async fn set_filestat_size(&self, size: u64) -> Result<(), Error> {
+        self.inner.set_filestat_size(size).await
+    }
+    async fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<(), Error> {
+        self.inner.advise(offset, len, advice).await
+    }
+    async fn allocate(&self, offset: u64, len: u64) -> Result<(), Error> {
+        self.inner.allocate(offset, len).await
+    }
+    async fn set_times(
+        &self,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+    ) -> Result<(), Error> {
+        self.inner.set_times(atime, mtime).await
+    }
+    async fn read_vectored<'a>(&self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        match &self.trace {
+            Trace::Record(r) => {
+                let result = self.inner.read_vectored(bufs).await;
+                r.log(Event::Read(gather(&result, bufs)));
+                result
+            }
+            Trace::Replay(r) => {
+                let n = r.read(bufs)?;
+                // Keep the file's position where the guest expects it to be.
+                // Streams such as stdin can't seek, and don't need to.
+                let _ = self.inner.seek(io::SeekFrom::Current(n as i64)).await;
+                Ok(n)
+            }
+        }
+    }
+    async fn read_vectored_at<'a>(
+        &self,
+        bufs: &mut [io::IoSliceMut<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        match &self.trace {
+            Trace::Record(r) => {
+                let result = self.inner.read_vectored_at(bufs, offset).await;
+                r.log(Event::Read(gather(&result, bufs)));
+                result
+            }
+            Trace::Replay(r) => r.read(bufs),
+        }
+    }
+    async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        self.inner.write_vectored(bufs).await
+    }
+    async fn write_vectored_at<'a>(
+        &self,
+        bufs: &[io::IoSlice<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        self.inner.write_vectored_at(bufs, offset).await
+    }
+    async fn seek(&self, pos: io::SeekFrom) -> Result<u64, Error> {
+        self.inner.seek(pos).await
+    }
+    async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
+        match &self.trace {
+            Trace::Record(r) => {
+                let result = self.inner.peek(buf).await;
+                r.log(Event::Read(gather(&result, &[io::IoSliceMut::new(buf)])));
+                result
+            }
+            Trace::Replay(r) => r.read(&mut [io::IoSliceMut::new(buf)]),
+        }
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        match &self.trace {
+            Trace::Record(r) => {
+                let result = self.inner.num_ready_bytes().await;
+                r.log(Event::ReadyBytes(match &result {
+                    Ok(n) => Ok(*n),
+                    Err(e) => Err(RecordedError::new(e)),
+                }));
+                result
+            }
+            Trace::Replay(r) => r
+                .next("a count of ready bytes", |e| match e {
+                    Event::ReadyBytes(n) => Some(n),
+                    _ => None,
+                })?
+                .map_err(RecordedError::into_error),
+        }
+    }
+    async fn readable(&self) -> Result<(), Error> {
+        self.inner.readable().await
+    }
+    async fn writable(&self) -> Result<(), Error> {
+        self.inner.writable().await
+    }
+    async fn sock_accept(&self, fdflags: FdFlags) -> Result<Box<dyn WasiFile>, Error> {
+        self.inner.sock_accept(fdflags).await
+    }
+    async fn sock_recv<'a>(
+        &self,
+        ri_data: &mut [io::IoSliceMut<'a>],
+        ri_flags: RiFlags,
+    ) -> Result<(u64, RoFlags), Error> {
+        self.inner.sock_recv(ri_data, ri_flags).await
+    }
+    async fn sock_send<'a>(&self, si_data: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        self.inner.sock_send(si_data).await
+    }
+    async fn sock_shutdown(&self, how: SdFlags) -> Result<(), Error> {
+        self.inner.sock_shutdown(how).await
+    }
+}
+
+/// Returns the data which `result` says was read into `bufs`, for a trace.
+fn gather(
+    result: &Result<u64, Error>,
+    bufs: &[io::IoSliceMut<'_>],
+) -> Result<Vec<u8>, RecordedError> {
+    match result {
+        Ok(n) => Ok(bufs
+            .iter()
+            .flat_map(|buf| buf.iter())
+            .take(*n as usize)
+            .copied()
+            .collect()),
+        Err(e) => Err(RecordedError::new(e)),
+    }
+}
+
+/// A directory whose files are wrapped in `TraceFile`s. Like `TraceFile`,
+/// its `as_any` is that of the directory it wraps.
+struct TraceDir {
+    inner: Box<dyn WasiDir>,
+    trace: Trace,
+}
+
+#[wiggle::async_trait]
+impl WasiDir for TraceDir {
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        let file = self
+            .inner
+            .open_file(symlink_follow, path, oflags, read, write, fdflags)
+            .await?;
+        Ok(self.trace.clone().file(file))
+    }
+    async fn open_dir(&self, symlink_follow: bool, path: &str) -> Result<Box<dyn WasiDir>, Error> {
+        let dir = self.inner.open_dir(symlink_follow, path).await?;
+        Ok(self.trace.clone().dir(dir))
+    }
+    async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        self.inner.create_dir(path).await
+    }
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        self.inner.readdir(cursor).await
+    }
+    async fn symlink(&self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        self.inner.symlink(old_path, new_path).await
+    }
+    async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        self.inner.remove_dir(path).await
+    }
+    async fn unlink_file(&self, path: &str) -> Result<(), Error> {
+        self.inner.unlink_file(path).await
+    }
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        self.inner.read_link(path).await
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.inner.get_filestat().await
+    }
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        self.inner.get_path_filestat(path, follow_symlinks).await
+    }
+    async fn rename(
+        &self,
+        path: &str,
+        dest_dir: &dyn WasiDir,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        // `dest_dir` downcasts to the directory it wraps, if it's a
+        // `TraceDir`, so it can be passed on as is.
+        self.inner.rename(path, dest_dir, dest_path).await
+    }
+    async fn hard_link(
+        &self,
+        path: &str,
+        target_dir: &dyn WasiDir,
+        target_path: &str,
+    ) -> Result<(), Error> {
+        self.inner.hard_link(path, target_dir, target_path).await
+    }
+    async fn set_times(
+        &self,
+        path: &str,
+        atime: Option<SystemTimeSpec>,
+        mtime: Option<SystemTimeSpec>,
+        follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        self.inner
+            .set_times(path, atime, mtime, follow_symlinks)
+            .await
+    }
+}
+
+/// An error recorded in a trace. Errors are recorded as the `ErrorKind` or
+/// OS error code they carry, which is what the guest observes.
+enum RecordedError {
+    Kind(ErrorKind),
+    Os(i32),
+    Other(String),
+}
+
+/// Every `ErrorKind`, indexed by its code in a trace.
+const ERROR_KINDS: &[ErrorKind] = &[
+    ErrorKind::Noent,
+    ErrorKind::TooBig,
+    ErrorKind::Badf,
+    ErrorKind::Exist,
+    ErrorKind::Ilseq,
+    ErrorKind::Inval,
+    ErrorKind::Io,
+    ErrorKind::Isdir,
+    ErrorKind::Loop,
+    ErrorKind::Nametoolong,
+    ErrorKind::Nospc,
+    ErrorKind::Notdir,
+    ErrorKind::Notempty,
+    ErrorKind::Notsup,
+    ErrorKind::Overflow,
+    ErrorKind::Perm,
+    ErrorKind::Range,
+    ErrorKind::Spipe,
+    ErrorKind::Xdev,
+    ErrorKind::NotCapable,
+    ErrorKind::Mfile,
+];
+
+impl RecordedError {
+    fn new(e: &Error) -> Self {
+        if let Some(kind) = e.downcast_ref::<ErrorKind>() {
+            RecordedError::Kind(*kind)
+        } else if let Some(code) = e.downcast_ref::<io::Error>().and_then(|e| e.raw_os_error()) {
+            RecordedError::Os(code)
+        } else {
+            RecordedError::Other(format!("{:#}", e))
+        }
+    }
+
+    fn into_error(self) -> Error {
+        match self {
+            RecordedError::Kind(kind) => kind.into(),
+            RecordedError::Os(code) => io::Error::from_raw_os_error(code).into(),
+            RecordedError::Other(msg) => Error::io().context(msg),
+        }
+    }
+}
+
+/// An entry of a trace.
+enum Event {
+    Args(Vec<String>),
+    Env(Vec<String>),
+    SystemClockResolution(Duration),
+    /// The time since the Unix epoch.
+    SystemClockNow(Duration),
+    MonotonicClockResolution(Duration),
+    /// The time since the creation of the context.
+    MonotonicClockNow(Duration),
+    Random(Vec<u8>),
+    Read(Result<Vec<u8>, RecordedError>),
+    ReadyBytes(Result<u64, RecordedError>),
+    Poll(Result<Vec<PollStatus>, RecordedError>),
+}
+
+/// The status of a read or write subscription after a `poll_oneoff`, with
+/// the bits of its `RwEventFlags`.
+type PollStatus = Option<Result<(u64, u32), RecordedError>>;
+
+impl Event {
+    fn describe(&self) -> &'static str {
+        match self {
+            Event::Args(_) => "the arguments",
+            Event::Env(_) => "the environment",
+            Event::SystemClockResolution(_) => "the system clock's resolution",
+            Event::SystemClockNow(_) => "a system clock reading",
+            Event::MonotonicClockResolution(_) => "the monotonic clock's resolution",
+            Event::MonotonicClockNow(_) => "a monotonic clock reading",
+            Event::Random(_) => "random bytes",
+            Event::Read(_) => "a read",
+            Event::ReadyBytes(_) => "a count of ready bytes",
+            Event::Poll(_) => "a poll_oneoff",
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Event::Args(args) => {
+                out.push(0);
+                encode_strings(out, args);
+            }
+            Event::Env(env) => {
+                out.push(1);
+                encode_strings(out, env);
+            }
+            Event::SystemClockResolution(d) => {
+                out.push(2);
+                encode_duration(out, *d);
+            }
+            Event::SystemClockNow(d) => {
+                out.push(3);
+                encode_duration(out, *d);
+            }
+            Event::MonotonicClockResolution(d) => {
+                out.push(4);
+                encode_duration(out, *d);
+            }
+            Event::MonotonicClockNow(d) => {
+                out.push(5);
+                encode_duration(out, *d);
+            }
+            Event::Random(bytes) => {
+                out.push(6);
+                encode_bytes(out, bytes);
+            }
+            Event::Read(result) => {
+                out.push(7);
+                encode_result(out, result, |out, data| encode_bytes(out, data));
+            }
+            Event::ReadyBytes(result) => {
+                out.push(8);
+                encode_result(out, result, |out, n| {
+                    out.extend_from_slice(&n.to_le_bytes())
+                });
+            }
+            Event::Poll(result) => {
+                out.push(9);
+                encode_result(out, result, |out, statuses| {
+                    out.extend_from_slice(&(statuses.len() as u32).to_le_bytes());
+                    for status in statuses {
+                        match status {
+                            None => out.push(0),
+                            Some(result) => {
+                                out.push(1);
+                                encode_result(out, result, |out, (size, flags)| {
+                                    out.extend_from_slice(&size.to_le_bytes());
+                                    out.extend_from_slice(&flags.to_le_bytes());
+                                });
+                            }
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Decodes the next event of a trace, or returns `None` at its end.
+    fn decode(input: &mut impl Read) -> Result<Option<Event>, Error> {
+        let mut tag = [0];
+        if input.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let event = match tag[0] {
+            0 => Event::Args(decode_strings(input)?),
+            1 => Event::Env(decode_strings(input)?),
+            2 => Event::SystemClockResolution(decode_duration(input)?),
+            3 => Event::SystemClockNow(decode_duration(input)?),
+            4 => Event::MonotonicClockResolution(decode_duration(input)?),
+            5 => Event::MonotonicClockNow(decode_duration(input)?),
+            6 => Event::Random(decode_bytes(input)?),
+            7 => Event::Read(decode_result(input, decode_bytes)?),
+            8 => Event::ReadyBytes(decode_result(input, decode_u64)?),
+            9 => Event::Poll(decode_result(input, |input| {
+                (0..decode_u32(input)?)
+                    .map(|_| match decode_u8(input)? {
+                        0 => Ok(None),
+                        _ => Ok(Some(decode_result(input, |input| {
+                            Ok((decode_u64(input)?, decode_u32(input)?))
+                        })?)),
+                    })
+                    .collect()
+            })?),
+            tag => return Err(corrupt(format!("unknown event {}", tag))),
+        };
+        Ok(Some(event))
+    }
+}
+
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn encode_strings(out: &mut Vec<u8>, strings: &[String]) {
+    out.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    for s in strings {
+        encode_bytes(out, s.as_bytes());
+    }
+}
+
+fn encode_duration(out: &mut Vec<u8>, d: Duration) {
+    out.extend_from_slice(&d.as_secs().to_le_bytes());
+    out.extend_from_slice(&d.subsec_nanos().to_le_bytes());
+}
+
+fn encode_result<T>(
+    out: &mut Vec<u8>,
+    result: &Result<T, RecordedError>,
+    encode_ok: impl FnOnce(&mut Vec<u8>, &T),
+) {
+    match result {
+        Ok(value) => {
+            out.push(0);
+            encode_ok(out, value);
+        }
+        Err(RecordedError::Kind(kind)) => {
+            out.push(1);
+            let code = ERROR_KINDS.iter().position(|k| k == kind).unwrap();
+            out.push(code as u8);
+        }
+        Err(RecordedError::Os(code)) => {
+            out.push(2);
+            out.extend_from_slice(&code.to_le_bytes());
+        }
+        Err(RecordedError::Other(msg)) => {
+            out.push(3);
+            encode_bytes(out, msg.as_bytes());
+        }
+    }
+}
+
+fn decode_u8(input: &mut impl Read) -> Result<u8, Error> {
+    let mut bytes = [0; 1];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn decode_u32(input: &mut impl Read) -> Result<u32, Error> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn decode_u64(input: &mut impl Read) -> Result<u64, Error> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn decode_bytes(input: &mut impl Read) -> Result<Vec<u8>, Error> {
+    let len = decode_u32(input)?;
+    let mut bytes = Vec::new();
+    input.take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(corrupt("truncated"));
+    }
+    Ok(bytes)
+}
+
+fn decode_string(input: &mut impl Read) -> Result<String, Error> {
+    String::from_utf8(decode_bytes(input)?).map_err(|_| corrupt("invalid UTF-8"))
+}
+
+fn decode_strings(input: &mut impl Read) -> Result<Vec<String>, Error> {
+    (0..decode_u32(input)?)
+        .map(|_| decode_string(input))
+        .collect()
+}
+
+fn decode_duration(input: &mut impl Read) -> Result<Duration, Error> {
+    let secs = decode_u64(input)?;
+    let nanos = decode_u32(input)?;
+    Ok(Duration::new(secs, nanos))
+}
+
+fn decode_result<T, R: Read>(
+    input: &mut R,
+    decode_ok: impl FnOnce(&mut R) -> Result<T, Error>,
+) -> Result<Result<T, RecordedError>, Error> {
+    Ok(match decode_u8(input)? {
+        0 => Ok(decode_ok(input)?),
+        1 => {
+            let code = decode_u8(input)?;
+            let kind = ERROR_KINDS
+                .get(usize::from(code))
+                .ok_or_else(|| corrupt(format!("unknown error kind {}", code)))?;
+            Err(RecordedError::Kind(*kind))
+        }
+        2 => Err(RecordedError::Os(decode_u32(input)? as i32)),
+        3 => Err(RecordedError::Other(decode_string(input)?)),
+        tag => return Err(corrupt(format!("unknown result {}", tag))),
+    })
+}
+
+fn corrupt(msg: impl Into<String>) -> Error {
+    Error::invalid_argument().context(format!("corrupt WASI trace: {}", msg.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::file::{FileCaps, FileEntryExt, TableFileExt};
+    use crate::pipe::ReadPipe;
+    use crate::random::Deterministic;
+    use crate::Table;
+    use std::future::Future;
+    use std::task::{Context, Poll as TaskPoll, RawWaker, RawWakerVTable, Waker};
+
+    /// Runs a future which never waits, as every future here completes
+    /// immediately.
+    fn run<F: Future>(future: F) -> F::Output {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        let waker = unsafe { Waker::from_raw(clone(std::ptr::null())) };
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            TaskPoll::Ready(output) => output,
+            TaskPoll::Pending => panic!("future waited"),
+        }
+    }
+
+    /// A clock which advances by a second each time it's read.
+    struct TickingClock {
+        start: Instant,
+        ticks: Mutex<u64>,
+    }
+
+    impl TickingClock {
+        fn tick(&self) -> Duration {
+            let mut ticks = self.ticks.lock().unwrap();
+            *ticks += 1;
+            Duration::from_secs(*ticks)
+        }
+    }
+
+    impl WasiSystemClock for TickingClock {
+        fn resolution(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+        fn now(&self, _precision: Duration) -> SystemTime {
+            SystemTime::from_std(UNIX_EPOCH + self.tick())
+        }
+    }
+
+    impl WasiMonotonicClock for TickingClock {
+        fn resolution(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+        fn now(&self, _precision: Duration) -> Instant {
+            self.start + self.tick()
+        }
+    }
+
+    /// A scheduler which finds everything ready to read or write 5 bytes.
+    struct ReadySched;
+
+    #[wiggle::async_trait]
+    impl WasiSched for ReadySched {
+        async fn poll_oneoff<'a>(&self, poll: &mut Poll<'a>) -> Result<(), Error> {
+            for sub in poll.rw_subscriptions() {
+                if let Subscription::Read(rw) | Subscription::Write(rw) = sub {
+                    rw.complete(5, RwEventFlags::empty());
+                }
+            }
+            Ok(())
+        }
+        async fn sched_yield(&self) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn sleep(&self, _duration: Duration) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn ctx(seed: u8, args: &[&str], stdin: &str) -> WasiCtx {
+        let start = Instant::from_std(std::time::Instant::now());
+        let mut ctx = WasiCtx::new(
+            Box::new(Deterministic::new(vec![seed])),
+            WasiClocks {
+                system: Box::new(TickingClock {
+                    start,
+                    ticks: Mutex::new(u64::from(seed)),
+                }),
+                monotonic: Box::new(TickingClock {
+                    start,
+                    ticks: Mutex::new(u64::from(seed)),
+                }),
+                creation_time: start,
+            },
+            Box::new(ReadySched),
+            Table::new(),
+        );
+        for arg in args {
+            ctx.push_arg(arg).unwrap();
+        }
+        ctx.push_env("SEED", &seed.to_string()).unwrap();
+        ctx.set_stdin(Box::new(ReadPipe::from(stdin)));
+        ctx
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Observed {
+        args: Vec<String>,
+        env: Vec<String>,
+        system: Duration,
+        monotonic: Duration,
+        random: [u8; 6],
+        stdin: Vec<u8>,
+        ready: Vec<Option<u64>>,
+    }
+
+    /// Observes everything nondeterministic that a guest could observe.
+    fn observe(ctx: &mut WasiCtx) -> Result<Observed, Error> {
+        let precision = Duration::from_secs(1);
+        let system = ctx.clocks.system.now(precision).into_std();
+        let monotonic = ctx.clocks.monotonic.now(precision);
+        let mut random = [0; 6];
+        ctx.random.fill_bytes(&mut random);
+        let stdin = ctx.table.get_file(0)?.get_cap(FileCaps::READ)?;
+        let (mut a, mut b) = ([0; 3], [0; 8]);
+        let n =
+            run(stdin
+                .read_vectored(&mut [io::IoSliceMut::new(&mut a), io::IoSliceMut::new(&mut b)]))?;
+        let mut poll = Poll::new();
+        poll.subscribe_read(stdin, 0.into());
+        run(ctx.sched.poll_oneoff(&mut poll))?;
+        let ready = poll
+            .rw_subscriptions()
+            .map(|sub| match sub {
+                Subscription::Read(rw) => rw.result().map(|r| r.unwrap().0),
+                _ => unreachable!(),
+            })
+            .collect();
+        Ok(Observed {
+            args: ctx.args.elems().to_vec(),
+            env: ctx.env.elems().to_vec(),
+            system: system.duration_since(UNIX_EPOCH).unwrap(),
+            monotonic: monotonic
+                .checked_duration_since(ctx.clocks.creation_time)
+                .unwrap(),
+            random,
+            stdin: a.iter().chain(&b).take(n as usize).copied().collect(),
+            ready,
+        })
+    }
+
+    fn record() -> (Observed, Vec<u8>) {
+        let trace = SharedBuf::default();
+        let recorder = Recorder::new(trace.clone());
+        let mut ctx = recorder.record(ctx(1, &["prog", "arg"], "hello, world"));
+        let observed = observe(&mut ctx).unwrap();
+        recorder.flush().unwrap();
+        let trace = trace.0.lock().unwrap().clone();
+        (observed, trace)
+    }
+
+    #[test]
+    fn replay() {
+        let (recorded, trace) = record();
+        assert_eq!(recorded.stdin, b"hello, worl");
+        assert_eq!(recorded.ready, vec![Some(5)]);
+
+        let replayer = Replayer::new(io::Cursor::new(trace)).unwrap();
+        let mut ctx = replayer.replay(ctx(2, &["other"], "goodbye")).unwrap();
+        assert_eq!(observe(&mut ctx).unwrap(), recorded);
+    }
+
+    #[test]
+    fn diverge() {
+        let (_, trace) = record();
+        let replayer = Replayer::new(io::Cursor::new(trace)).unwrap();
+        let ctx = replayer.replay(ctx(2, &[], "")).unwrap();
+        let stdin = ctx
+            .table
+            .get_file(0)
+            .unwrap()
+            .get_cap(FileCaps::READ)
+            .unwrap();
+        let err = run(stdin.read_vectored(&mut [io::IoSliceMut::new(&mut [0; 4])])).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("expected a read, found a system clock reading"),
+            "{:?}",
+            err
+        );
+
+        let err = Replayer::new(&b"not a trace"[..]).err().unwrap();
+        assert!(err.to_string().contains("not a WASI trace"), "{:?}", err);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::file::{FileCaps, FileEntryExt, TableFileExt};
+use crate::file::{FileCaps, FileEntryExt, RiFlags, TableFileExt};
+use crate::policy::Rights;
 use crate::sched::{
     subscription::{RwEventFlags, SubscriptionResult},
     Poll, Userdata,
@@ -299,6 +300,18 @@ convert_flags!(
     EXCL,
     TRUNC
 );
+convert_flags!(
+    types::Riflags,
+    snapshot1_types::Riflags,
+    RECV_PEEK,
+    RECV_WAITALL
+);
+convert_flags!(
+    snapshot1_types::Roflags,
+    types::Roflags,
+    RECV_DATA_TRUNCATED
+);
+convert_flags!(types::Sdflags, snapshot1_types::Sdflags, RD, WR);
 convert_flags_bidirectional!(
     types::Rights,
     snapshot1_types::Rights,
@@ -462,6 +475,7 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         fd: types::Fd,
         iovs: &types::IovecArray<'a>,
     ) -> Result<types::Size, Error> {
+        self.check("fd_read", Some(u32::from(fd)), FileCaps::READ)?;
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
 
@@ -489,6 +503,11 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         iovs: &types::IovecArray<'a>,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
+        self.check(
+            "fd_pread",
+            Some(u32::from(fd)),
+            FileCaps::READ | FileCaps::SEEK,
+        )?;
         let table = self.table();
         let f = table
             .get_file(u32::from(fd))?
@@ -517,6 +536,7 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         fd: types::Fd,
         ciovs: &types::CiovecArray<'a>,
     ) -> Result<types::Size, Error> {
+        self.check("fd_write", Some(u32::from(fd)), FileCaps::WRITE)?;
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
 
@@ -544,6 +564,11 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         ciovs: &types::CiovecArray<'a>,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
+        self.check(
+            "fd_pwrite",
+            Some(u32::from(fd)),
+            FileCaps::WRITE | FileCaps::SEEK,
+        )?;
         let table = self.table();
         let f = table
             .get_file(u32::from(fd))?
@@ -754,6 +779,7 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         events: &GuestPtr<'a, types::Event>,
         nsubscriptions: types::Size,
     ) -> Result<types::Size, Error> {
+        self.check("poll_oneoff", None, Rights::none())?;
         if nsubscriptions == 0 {
             return Err(Error::invalid_argument().context("nsubscriptions must be nonzero"));
         }
@@ -933,6 +959,7 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
     }
 
     async fn proc_raise(&mut self, _sig: types::Signal) -> Result<(), Error> {
+        self.check("proc_raise", None, Rights::none())?;
         Err(Error::trap("proc_raise unsupported"))
     }
 
@@ -948,26 +975,74 @@ impl wasi_unstable::WasiUnstable for WasiCtx {
         Snapshot1::random_get(self, buf, buf_len).await
     }
 
+    // Like `fd_read` and `fd_write`, these are copied from the Snapshot1 code since they deal
+    // with iovecs.
+
     async fn sock_recv<'a>(
         &mut self,
-        _fd: types::Fd,
-        _ri_data: &types::IovecArray<'a>,
-        _ri_flags: types::Riflags,
+        fd: types::Fd,
+        ri_data: &types::IovecArray<'a>,
+        ri_flags: types::Riflags,
     ) -> Result<(types::Size, types::Roflags), Error> {
-        Err(Error::trap("sock_recv unsupported"))
+        self.check("sock_recv", Some(u32::from(fd)), FileCaps::READ)?;
+        let table = self.table();
+        let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
+
+        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = ri_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Iovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let mut ioslices: Vec<IoSliceMut> = guest_slices
+            .iter_mut()
+            .map(|s| IoSliceMut::new(&mut *s))
+            .collect();
+
+        let ri_flags = RiFlags::from(snapshot1_types::Riflags::from(ri_flags));
+        let (bytes_read, roflags) = f.sock_recv(&mut ioslices, ri_flags).await?;
+        Ok((
+            types::Size::try_from(bytes_read)?,
+            types::Roflags::from(snapshot1_types::Roflags::from(roflags)),
+        ))
     }
 
     async fn sock_send<'a>(
         &mut self,
-        _fd: types::Fd,
-        _si_data: &types::CiovecArray<'a>,
-        _si_flags: types::Siflags,
+        fd: types::Fd,
+        si_data: &types::CiovecArray<'a>,
+        si_flags: types::Siflags,
     ) -> Result<types::Size, Error> {
-        Err(Error::trap("sock_send unsupported"))
+        self.check("sock_send", Some(u32::from(fd)), FileCaps::WRITE)?;
+        if si_flags != 0 {
+            return Err(Error::invalid_argument().context("sock_send flags must be zero"));
+        }
+        let table = self.table();
+        let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
+
+        let guest_slices: Vec<wiggle::GuestSlice<u8>> = si_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Ciovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let ioslices: Vec<IoSlice> = guest_slices
+            .iter()
+            .map(|s| IoSlice::new(s.deref()))
+            .collect();
+        let bytes_written = f.sock_send(&ioslices).await?;
+
+        Ok(types::Size::try_from(bytes_written)?)
     }
 
-    async fn sock_shutdown(&mut self, _fd: types::Fd, _how: types::Sdflags) -> Result<(), Error> {
-        Err(Error::trap("sock_shutdown unsupported"))
+    async fn sock_shutdown(&mut self, fd: types::Fd, how: types::Sdflags) -> Result<(), Error> {
+        Snapshot1::sock_shutdown(self, fd.into(), how.into()).await
     }
 }
 
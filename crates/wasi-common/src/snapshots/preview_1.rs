@@ -2,8 +2,9 @@ use crate::{
     dir::{DirCaps, DirEntry, DirEntryExt, DirFdStat, ReaddirCursor, ReaddirEntity, TableDirExt},
     file::{
         Advice, FdFlags, FdStat, FileCaps, FileEntry, FileEntryExt, FileType, Filestat, OFlags,
-        TableFileExt, WasiFile,
+        RiFlags, RoFlags, SdFlags, TableFileExt, WasiFile,
     },
+    policy::Rights,
     sched::{
         subscription::{RwEventFlags, SubscriptionResult},
         Poll, Userdata,
@@ -77,12 +78,19 @@ impl From<ErrorKind> for types::Errno {
             ErrorKind::Ilseq => Errno::Ilseq,
             ErrorKind::Inval => Errno::Inval,
             ErrorKind::Io => Errno::Io,
+            ErrorKind::Isdir => Errno::Isdir,
+            ErrorKind::Loop => Errno::Loop,
+            ErrorKind::Mfile => Errno::Mfile,
             ErrorKind::Nametoolong => Errno::Nametoolong,
+            ErrorKind::Nospc => Errno::Nospc,
             ErrorKind::Notdir => Errno::Notdir,
+            ErrorKind::Notempty => Errno::Notempty,
             ErrorKind::Notsup => Errno::Notsup,
             ErrorKind::Overflow => Errno::Overflow,
+            ErrorKind::Perm => Errno::Perm,
             ErrorKind::Range => Errno::Range,
             ErrorKind::Spipe => Errno::Spipe,
+            ErrorKind::Xdev => Errno::Xdev,
             ErrorKind::NotCapable => Errno::Notcapable,
         }
     }
@@ -140,6 +148,11 @@ impl TryFrom<std::io::Error> for types::Errno {
                 Some(Error::OVERFLOW) => Some(types::Errno::Overflow),
                 Some(Error::ILSEQ) => Some(types::Errno::Ilseq),
                 Some(Error::NOTSUP) => Some(types::Errno::Notsup),
+                Some(Error::AGAIN) => Some(types::Errno::Again),
+                Some(Error::NOTSOCK) => Some(types::Errno::Notsock),
+                Some(Error::CONNRESET) => Some(types::Errno::Connreset),
+                Some(Error::CONNABORTED) => Some(types::Errno::Connaborted),
+                Some(Error::NOTCONN) => Some(types::Errno::Notconn),
                 _ => None,
             }
         }
@@ -182,6 +195,11 @@ impl TryFrom<std::io::Error> for types::Errno {
                 std::io::ErrorKind::PermissionDenied => Ok(types::Errno::Perm),
                 std::io::ErrorKind::AlreadyExists => Ok(types::Errno::Exist),
                 std::io::ErrorKind::InvalidInput => Ok(types::Errno::Ilseq),
+                std::io::ErrorKind::WouldBlock => Ok(types::Errno::Again),
+                std::io::ErrorKind::ConnectionReset => Ok(types::Errno::Connreset),
+                std::io::ErrorKind::ConnectionAborted => Ok(types::Errno::Connaborted),
+                std::io::ErrorKind::NotConnected => Ok(types::Errno::Notconn),
+                std::io::ErrorKind::BrokenPipe => Ok(types::Errno::Pipe),
                 _ => Err(anyhow::anyhow!(err).context(format!("Unknown OS error"))),
             },
         }
@@ -195,10 +213,12 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         argv: &GuestPtr<'b, GuestPtr<'b, u8>>,
         argv_buf: &GuestPtr<'b, u8>,
     ) -> Result<(), Error> {
+        self.check("args_get", None, Rights::none())?;
         self.args.write_to_guest(argv_buf, argv)
     }
 
     async fn args_sizes_get(&mut self) -> Result<(types::Size, types::Size), Error> {
+        self.check("args_sizes_get", None, Rights::none())?;
         Ok((self.args.number_elements(), self.args.cumulative_size()))
     }
 
@@ -207,14 +227,17 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         environ: &GuestPtr<'b, GuestPtr<'b, u8>>,
         environ_buf: &GuestPtr<'b, u8>,
     ) -> Result<(), Error> {
+        self.check("environ_get", None, Rights::none())?;
         self.env.write_to_guest(environ_buf, environ)
     }
 
     async fn environ_sizes_get(&mut self) -> Result<(types::Size, types::Size), Error> {
+        self.check("environ_sizes_get", None, Rights::none())?;
         Ok((self.env.number_elements(), self.env.cumulative_size()))
     }
 
     async fn clock_res_get(&mut self, id: types::Clockid) -> Result<types::Timestamp, Error> {
+        self.check("clock_res_get", None, Rights::none())?;
         let resolution = match id {
             types::Clockid::Realtime => Ok(self.clocks.system.resolution()),
             types::Clockid::Monotonic => Ok(self.clocks.monotonic.resolution()),
@@ -230,6 +253,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         id: types::Clockid,
         precision: types::Timestamp,
     ) -> Result<types::Timestamp, Error> {
+        self.check("clock_time_get", None, Rights::none())?;
         let precision = Duration::from_nanos(precision);
         match id {
             types::Clockid::Realtime => {
@@ -257,6 +281,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         len: types::Filesize,
         advice: types::Advice,
     ) -> Result<(), Error> {
+        self.check("fd_advise", Some(u32::from(fd)), FileCaps::ADVISE)?;
         self.table()
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::ADVISE)?
@@ -271,6 +296,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         offset: types::Filesize,
         len: types::Filesize,
     ) -> Result<(), Error> {
+        self.check("fd_allocate", Some(u32::from(fd)), FileCaps::ALLOCATE)?;
         self.table()
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::ALLOCATE)?
@@ -280,6 +306,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_close(&mut self, fd: types::Fd) -> Result<(), Error> {
+        self.check("fd_close", Some(u32::from(fd)), Rights::none())?;
         let table = self.table();
         let fd = u32::from(fd);
 
@@ -287,16 +314,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         if !table.contains_key(fd) {
             return Err(Error::badf().context("key not in table"));
         }
-        // fd_close must close either a File or a Dir handle
-        if table.is::<FileEntry>(fd) {
-            let _ = table.delete(fd);
-        } else if table.is::<DirEntry>(fd) {
-            // We cannot close preopened directories
-            let dir_entry: &DirEntry = table.get(fd).unwrap();
-            if dir_entry.preopen_path().is_some() {
-                return Err(Error::not_supported().context("cannot close propened directory"));
-            }
-            drop(dir_entry);
+        // fd_close must close either a File or a Dir handle. Preopened directories can be closed
+        // like any other, e.g. by a sandbox giving up access it only needed during startup.
+        if table.is::<FileEntry>(fd) || table.is::<DirEntry>(fd) {
             let _ = table.delete(fd);
         } else {
             return Err(Error::badf().context("key does not refer to file or directory"));
@@ -306,6 +326,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_datasync(&mut self, fd: types::Fd) -> Result<(), Error> {
+        self.check("fd_datasync", Some(u32::from(fd)), FileCaps::DATASYNC)?;
         self.table()
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::DATASYNC)?
@@ -315,6 +336,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_fdstat_get(&mut self, fd: types::Fd) -> Result<types::Fdstat, Error> {
+        self.check("fd_fdstat_get", Some(u32::from(fd)), Rights::none())?;
         let table = self.table();
         let fd = u32::from(fd);
         if table.is::<FileEntry>(fd) {
@@ -335,6 +357,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         flags: types::Fdflags,
     ) -> Result<(), Error> {
+        self.check(
+            "fd_fdstat_set_flags",
+            Some(u32::from(fd)),
+            FileCaps::FDSTAT_SET_FLAGS,
+        )?;
         self.table()
             .get_file_mut(u32::from(fd))?
             .get_cap_mut(FileCaps::FDSTAT_SET_FLAGS)?
@@ -348,6 +375,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fs_rights_base: types::Rights,
         fs_rights_inheriting: types::Rights,
     ) -> Result<(), Error> {
+        self.check("fd_fdstat_set_rights", Some(u32::from(fd)), Rights::none())?;
         let table = self.table();
         let fd = u32::from(fd);
         if table.is::<FileEntry>(fd) {
@@ -365,6 +393,14 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_filestat_get(&mut self, fd: types::Fd) -> Result<types::Filestat, Error> {
+        self.check(
+            "fd_filestat_get",
+            Some(u32::from(fd)),
+            Rights {
+                dir: DirCaps::FILESTAT_GET,
+                file: FileCaps::FILESTAT_GET,
+            },
+        )?;
         let table = self.table();
         let fd = u32::from(fd);
         if table.is::<FileEntry>(fd) {
@@ -391,6 +427,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         size: types::Filesize,
     ) -> Result<(), Error> {
+        self.check(
+            "fd_filestat_set_size",
+            Some(u32::from(fd)),
+            FileCaps::FILESTAT_SET_SIZE,
+        )?;
         self.table()
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::FILESTAT_SET_SIZE)?
@@ -406,6 +447,14 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         mtim: types::Timestamp,
         fst_flags: types::Fstflags,
     ) -> Result<(), Error> {
+        self.check(
+            "fd_filestat_set_times",
+            Some(u32::from(fd)),
+            Rights {
+                dir: DirCaps::FILESTAT_SET_TIMES,
+                file: FileCaps::FILESTAT_SET_TIMES,
+            },
+        )?;
         let fd = u32::from(fd);
         let table = self.table();
         // Validate flags
@@ -441,6 +490,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         iovs: &types::IovecArray<'a>,
     ) -> Result<types::Size, Error> {
+        self.check("fd_read", Some(u32::from(fd)), FileCaps::READ)?;
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
 
@@ -468,6 +518,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         iovs: &types::IovecArray<'a>,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
+        self.check(
+            "fd_pread",
+            Some(u32::from(fd)),
+            FileCaps::READ | FileCaps::SEEK,
+        )?;
         let table = self.table();
         let f = table
             .get_file(u32::from(fd))?
@@ -496,6 +551,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         ciovs: &types::CiovecArray<'a>,
     ) -> Result<types::Size, Error> {
+        self.check("fd_write", Some(u32::from(fd)), FileCaps::WRITE)?;
         let table = self.table();
         let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
 
@@ -523,6 +579,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         ciovs: &types::CiovecArray<'a>,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
+        self.check(
+            "fd_pwrite",
+            Some(u32::from(fd)),
+            FileCaps::WRITE | FileCaps::SEEK,
+        )?;
         let table = self.table();
         let f = table
             .get_file(u32::from(fd))?
@@ -547,6 +608,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_prestat_get(&mut self, fd: types::Fd) -> Result<types::Prestat, Error> {
+        self.check("fd_prestat_get", Some(u32::from(fd)), Rights::none())?;
         let table = self.table();
         let dir_entry: &DirEntry = table.get(u32::from(fd)).map_err(|_| Error::badf())?;
         if let Some(ref preopen) = dir_entry.preopen_path() {
@@ -564,6 +626,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         path: &GuestPtr<'a, u8>,
         path_max_len: types::Size,
     ) -> Result<(), Error> {
+        self.check("fd_prestat_dir_name", Some(u32::from(fd)), Rights::none())?;
         let table = self.table();
         let dir_entry: &DirEntry = table.get(u32::from(fd)).map_err(|_| Error::not_dir())?;
         if let Some(ref preopen) = dir_entry.preopen_path() {
@@ -583,15 +646,19 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         }
     }
     async fn fd_renumber(&mut self, from: types::Fd, to: types::Fd) -> Result<(), Error> {
-        let table = self.table();
         let from = u32::from(from);
         let to = u32::from(to);
+        self.check("fd_renumber", Some(from), Rights::none())?;
+        self.check("fd_renumber", Some(to), Rights::none())?;
+        let table = self.table();
         if !table.contains_key(from) {
             return Err(Error::badf());
         }
-        if table.is_preopen(from) || table.is_preopen(to) {
-            return Err(Error::not_supported().context("cannot renumber a preopen"));
+        if from == to {
+            return Ok(());
         }
+        // Like `dup2`, this closes whatever `to` referred to. A preopen keeps its path wherever
+        // it's renumbered to, so `fd_prestat_get` still describes it.
         let from_entry = table
             .delete(from)
             .expect("we checked that table contains from");
@@ -612,6 +679,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         } else {
             FileCaps::TELL | FileCaps::SEEK
         };
+        self.check("fd_seek", Some(u32::from(fd)), required_caps)?;
 
         let whence = match whence {
             types::Whence::Cur => SeekFrom::Current(offset),
@@ -628,6 +696,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_sync(&mut self, fd: types::Fd) -> Result<(), Error> {
+        self.check("fd_sync", Some(u32::from(fd)), FileCaps::SYNC)?;
         self.table()
             .get_file(u32::from(fd))?
             .get_cap(FileCaps::SYNC)?
@@ -637,6 +706,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn fd_tell(&mut self, fd: types::Fd) -> Result<types::Filesize, Error> {
+        self.check("fd_tell", Some(u32::from(fd)), FileCaps::TELL)?;
         // XXX should this be stream_position?
         let offset = self
             .table()
@@ -654,6 +724,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         buf_len: types::Size,
         cookie: types::Dircookie,
     ) -> Result<types::Size, Error> {
+        self.check("fd_readdir", Some(u32::from(fd)), DirCaps::READDIR)?;
         let mut bufused = 0;
         let mut buf = buf.clone();
         for entity in self
@@ -706,10 +777,17 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dirfd: types::Fd,
         path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_create_directory",
+            u32::from(dirfd),
+            &path,
+            DirCaps::CREATE_DIRECTORY,
+        )?;
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::CREATE_DIRECTORY)?
-            .create_dir(path.as_str()?.deref())
+            .create_dir(&path)
             .await
     }
 
@@ -719,14 +797,18 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         flags: types::Lookupflags,
         path: &GuestPtr<'a, str>,
     ) -> Result<types::Filestat, Error> {
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_filestat_get",
+            u32::from(dirfd),
+            &path,
+            DirCaps::PATH_FILESTAT_GET,
+        )?;
         let filestat = self
             .table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::PATH_FILESTAT_GET)?
-            .get_path_filestat(
-                path.as_str()?.deref(),
-                flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
-            )
+            .get_path_filestat(&path, flags.contains(types::Lookupflags::SYMLINK_FOLLOW))
             .await?;
         Ok(types::Filestat::from(filestat))
     }
@@ -747,11 +829,18 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
         let atim = systimespec(set_atim, atim, set_atim_now).context("atim")?;
         let mtim = systimespec(set_mtim, mtim, set_mtim_now).context("mtim")?;
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_filestat_set_times",
+            u32::from(dirfd),
+            &path,
+            DirCaps::PATH_FILESTAT_SET_TIMES,
+        )?;
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::PATH_FILESTAT_SET_TIMES)?
             .set_times(
-                path.as_str()?.deref(),
+                &path,
                 atim,
                 mtim,
                 flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
@@ -767,6 +856,20 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         target_fd: types::Fd,
         target_path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        let src_path = src_path.as_str()?;
+        let src_path = self.check_path(
+            "path_link",
+            u32::from(src_fd),
+            &src_path,
+            DirCaps::LINK_SOURCE,
+        )?;
+        let target_path = target_path.as_str()?;
+        let target_path = self.check_path(
+            "path_link",
+            u32::from(target_fd),
+            &target_path,
+            DirCaps::LINK_TARGET,
+        )?;
         let table = self.table();
         let src_dir = table
             .get_dir(u32::from(src_fd))?
@@ -781,11 +884,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         }
 
         src_dir
-            .hard_link(
-                src_path.as_str()?.deref(),
-                target_dir.deref(),
-                target_path.as_str()?.deref(),
-            )
+            .hard_link(&src_path, target_dir.deref(), &target_path)
             .await
     }
 
@@ -799,6 +898,21 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fs_rights_inheriting: types::Rights,
         fdflags: types::Fdflags,
     ) -> Result<types::Fd, Error> {
+        let oflags = OFlags::from(&oflags);
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_open",
+            u32::from(dirfd),
+            &path,
+            Rights {
+                dir: if oflags.contains(OFlags::CREATE) {
+                    DirCaps::OPEN | DirCaps::CREATE_FILE
+                } else {
+                    DirCaps::OPEN
+                },
+                file: FileCaps::from(&fs_rights_base),
+            },
+        )?;
         let table = self.table();
         let dirfd = u32::from(dirfd);
         if table.is::<FileEntry>(dirfd) {
@@ -808,9 +922,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
         let symlink_follow = dirflags.contains(types::Lookupflags::SYMLINK_FOLLOW);
 
-        let oflags = OFlags::from(&oflags);
         let fdflags = FdFlags::from(fdflags);
-        let path = path.as_str()?;
         if oflags.contains(OFlags::DIRECTORY) {
             if oflags.contains(OFlags::CREATE)
                 || oflags.contains(OFlags::EXCLUSIVE)
@@ -821,9 +933,9 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             let dir_caps = dir_entry.child_dir_caps(DirCaps::from(&fs_rights_base));
             let file_caps = dir_entry.child_file_caps(FileCaps::from(&fs_rights_inheriting));
             let dir = dir_entry.get_cap(DirCaps::OPEN)?;
-            let child_dir = dir.open_dir(symlink_follow, path.deref()).await?;
+            let child_dir = dir.open_dir(symlink_follow, &path).await?;
             drop(dir);
-            let fd = table.push(Box::new(DirEntry::new(
+            let fd = table.push_limited(Box::new(DirEntry::new(
                 dir_caps, file_caps, None, child_dir,
             )))?;
             Ok(types::Fd::from(fd))
@@ -840,10 +952,10 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
                 || file_caps.contains(FileCaps::ALLOCATE)
                 || file_caps.contains(FileCaps::FILESTAT_SET_SIZE);
             let file = dir
-                .open_file(symlink_follow, path.deref(), oflags, read, write, fdflags)
+                .open_file(symlink_follow, &path, oflags, read, write, fdflags)
                 .await?;
             drop(dir);
-            let fd = table.push(Box::new(FileEntry::new(file_caps, file)))?;
+            let fd = table.push_limited(Box::new(FileEntry::new(file_caps, file)))?;
             Ok(types::Fd::from(fd))
         }
     }
@@ -855,15 +967,21 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         buf: &GuestPtr<'a, u8>,
         buf_len: types::Size,
     ) -> Result<types::Size, Error> {
-        let link = self
-            .table()
-            .get_dir(u32::from(dirfd))?
-            .get_cap(DirCaps::READLINK)?
-            .read_link(path.as_str()?.deref())
-            .await?
-            .into_os_string()
-            .into_string()
-            .map_err(|_| Error::illegal_byte_sequence().context("link contents"))?;
+        // The path is borrowed from guest memory, so it needs to be released
+        // before `buf` is borrowed mutably.
+        let link = {
+            let path = path.as_str()?;
+            let path =
+                self.check_path("path_readlink", u32::from(dirfd), &path, DirCaps::READLINK)?;
+            self.table()
+                .get_dir(u32::from(dirfd))?
+                .get_cap(DirCaps::READLINK)?
+                .read_link(&path)
+                .await?
+        }
+        .into_os_string()
+        .into_string()
+        .map_err(|_| Error::illegal_byte_sequence().context("link contents"))?;
         let link_bytes = link.as_bytes();
         let link_len = link_bytes.len();
         if link_len > buf_len as usize {
@@ -879,10 +997,17 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dirfd: types::Fd,
         path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_remove_directory",
+            u32::from(dirfd),
+            &path,
+            DirCaps::REMOVE_DIRECTORY,
+        )?;
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::REMOVE_DIRECTORY)?
-            .remove_dir(path.as_str()?.deref())
+            .remove_dir(&path)
             .await
     }
 
@@ -893,6 +1018,20 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dest_fd: types::Fd,
         dest_path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        let src_path = src_path.as_str()?;
+        let src_path = self.check_path(
+            "path_rename",
+            u32::from(src_fd),
+            &src_path,
+            DirCaps::RENAME_SOURCE,
+        )?;
+        let dest_path = dest_path.as_str()?;
+        let dest_path = self.check_path(
+            "path_rename",
+            u32::from(dest_fd),
+            &dest_path,
+            DirCaps::RENAME_TARGET,
+        )?;
         let table = self.table();
         let src_dir = table
             .get_dir(u32::from(src_fd))?
@@ -901,11 +1040,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .get_dir(u32::from(dest_fd))?
             .get_cap(DirCaps::RENAME_TARGET)?;
         src_dir
-            .rename(
-                src_path.as_str()?.deref(),
-                dest_dir.deref(),
-                dest_path.as_str()?.deref(),
-            )
+            .rename(&src_path, dest_dir.deref(), &dest_path)
             .await
     }
 
@@ -915,10 +1050,19 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dirfd: types::Fd,
         dest_path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        // The source of a symlink is just its contents, which aren't
+        // resolved until the link is followed, so only the link is checked.
+        let dest_path = dest_path.as_str()?;
+        let dest_path = self.check_path(
+            "path_symlink",
+            u32::from(dirfd),
+            &dest_path,
+            DirCaps::SYMLINK,
+        )?;
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::SYMLINK)?
-            .symlink(src_path.as_str()?.deref(), dest_path.as_str()?.deref())
+            .symlink(src_path.as_str()?.deref(), &dest_path)
             .await
     }
 
@@ -927,10 +1071,17 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         dirfd: types::Fd,
         path: &GuestPtr<'a, str>,
     ) -> Result<(), Error> {
+        let path = path.as_str()?;
+        let path = self.check_path(
+            "path_unlink_file",
+            u32::from(dirfd),
+            &path,
+            DirCaps::UNLINK_FILE,
+        )?;
         self.table()
             .get_dir(u32::from(dirfd))?
             .get_cap(DirCaps::UNLINK_FILE)?
-            .unlink_file(path.as_str()?.deref())
+            .unlink_file(&path)
             .await
     }
 
@@ -940,6 +1091,7 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         events: &GuestPtr<'a, types::Event>,
         nsubscriptions: types::Size,
     ) -> Result<types::Size, Error> {
+        self.check("poll_oneoff", None, Rights::none())?;
         if nsubscriptions == 0 {
             return Err(Error::invalid_argument().context("nsubscriptions must be nonzero"));
         }
@@ -1122,6 +1274,8 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn proc_exit(&mut self, status: types::Exitcode) -> wiggle::Trap {
+        // The guest can't carry on after exiting, so a denial can't stop it.
+        let _ = self.check("proc_exit", None, Rights::none());
         // Check that the status is within WASI's range.
         if status < 126 {
             wiggle::Trap::I32Exit(status as i32)
@@ -1131,10 +1285,12 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     }
 
     async fn proc_raise(&mut self, _sig: types::Signal) -> Result<(), Error> {
+        self.check("proc_raise", None, Rights::none())?;
         Err(Error::trap("proc_raise unsupported"))
     }
 
     async fn sched_yield(&mut self) -> Result<(), Error> {
+        self.check("sched_yield", None, Rights::none())?;
         self.sched.sched_yield().await
     }
 
@@ -1143,31 +1299,104 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         buf: &GuestPtr<'a, u8>,
         buf_len: types::Size,
     ) -> Result<(), Error> {
+        self.check("random_get", None, Rights::none())?;
         let mut buf = buf.as_array(buf_len).as_slice_mut()?;
         self.random.try_fill_bytes(buf.deref_mut())?;
         Ok(())
     }
 
+    async fn sock_accept(
+        &mut self,
+        fd: types::Fd,
+        flags: types::Fdflags,
+    ) -> Result<types::Fd, Error> {
+        self.check("sock_accept", Some(u32::from(fd)), FileCaps::READ)?;
+        let table = self.table();
+        let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
+        let file = f.sock_accept(FdFlags::from(flags)).await?;
+        let file_caps = FileCaps::READ
+            | FileCaps::WRITE
+            | FileCaps::FDSTAT_SET_FLAGS
+            | FileCaps::FILESTAT_GET
+            | FileCaps::POLL_READWRITE
+            | FileCaps::SOCK_SHUTDOWN;
+        let fd = table.push_limited(Box::new(FileEntry::new(file_caps, file)))?;
+        Ok(types::Fd::from(fd))
+    }
+
     async fn sock_recv<'a>(
         &mut self,
-        _fd: types::Fd,
-        _ri_data: &types::IovecArray<'a>,
-        _ri_flags: types::Riflags,
+        fd: types::Fd,
+        ri_data: &types::IovecArray<'a>,
+        ri_flags: types::Riflags,
     ) -> Result<(types::Size, types::Roflags), Error> {
-        Err(Error::trap("sock_recv unsupported"))
+        self.check("sock_recv", Some(u32::from(fd)), FileCaps::READ)?;
+        let table = self.table();
+        let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::READ)?;
+
+        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = ri_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Iovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let mut ioslices: Vec<IoSliceMut> = guest_slices
+            .iter_mut()
+            .map(|s| IoSliceMut::new(&mut *s))
+            .collect();
+
+        let (bytes_read, roflags) = f.sock_recv(&mut ioslices, RiFlags::from(ri_flags)).await?;
+        Ok((
+            types::Size::try_from(bytes_read)?,
+            types::Roflags::from(roflags),
+        ))
     }
 
     async fn sock_send<'a>(
         &mut self,
-        _fd: types::Fd,
-        _si_data: &types::CiovecArray<'a>,
-        _si_flags: types::Siflags,
+        fd: types::Fd,
+        si_data: &types::CiovecArray<'a>,
+        si_flags: types::Siflags,
     ) -> Result<types::Size, Error> {
-        Err(Error::trap("sock_send unsupported"))
+        self.check("sock_send", Some(u32::from(fd)), FileCaps::WRITE)?;
+        if si_flags != 0 {
+            return Err(Error::invalid_argument().context("sock_send flags must be zero"));
+        }
+        let table = self.table();
+        let f = table.get_file(u32::from(fd))?.get_cap(FileCaps::WRITE)?;
+
+        let guest_slices: Vec<wiggle::GuestSlice<u8>> = si_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Ciovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let ioslices: Vec<IoSlice> = guest_slices
+            .iter()
+            .map(|s| IoSlice::new(s.deref()))
+            .collect();
+        let bytes_written = f.sock_send(&ioslices).await?;
+
+        Ok(types::Size::try_from(bytes_written)?)
     }
 
-    async fn sock_shutdown(&mut self, _fd: types::Fd, _how: types::Sdflags) -> Result<(), Error> {
-        Err(Error::trap("sock_shutdown unsupported"))
+    async fn sock_shutdown(&mut self, fd: types::Fd, how: types::Sdflags) -> Result<(), Error> {
+        self.check(
+            "sock_shutdown",
+            Some(u32::from(fd)),
+            FileCaps::SOCK_SHUTDOWN,
+        )?;
+        let table = self.table();
+        let f = table
+            .get_file(u32::from(fd))?
+            .get_cap(FileCaps::SOCK_SHUTDOWN)?;
+        f.sock_shutdown(SdFlags::from(how)).await
     }
 }
 
@@ -1251,6 +1480,9 @@ impl From<&FileCaps> for types::Rights {
         if caps.contains(FileCaps::POLL_READWRITE) {
             rights = rights | types::Rights::POLL_FD_READWRITE;
         }
+        if caps.contains(FileCaps::SOCK_SHUTDOWN) {
+            rights = rights | types::Rights::SOCK_SHUTDOWN;
+        }
         rights
     }
 }
@@ -1298,6 +1530,9 @@ impl From<&types::Rights> for FileCaps {
         if rights.contains(types::Rights::POLL_FD_READWRITE) {
             caps = caps | FileCaps::POLL_READWRITE;
         }
+        if rights.contains(types::Rights::SOCK_SHUTDOWN) {
+            caps = caps | FileCaps::SOCK_SHUTDOWN;
+        }
         caps
     }
 }
@@ -1462,6 +1697,9 @@ convert_flags_bidirectional!(
     RSYNC,
     SYNC
 );
+convert_flags!(types::Riflags, RiFlags, RECV_PEEK, RECV_WAITALL);
+convert_flags!(RoFlags, types::Roflags, RECV_DATA_TRUNCATED);
+convert_flags!(types::Sdflags, SdFlags, RD, WR);
 
 impl From<&types::Oflags> for OFlags {
     fn from(oflags: &types::Oflags) -> OFlags {
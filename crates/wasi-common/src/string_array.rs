@@ -38,6 +38,10 @@ impl StringArray {
         self.elems.len() as u32
     }
 
+    pub(crate) fn elems(&self) -> &[String] {
+        &self.elems
+    }
+
     pub fn cumulative_size(&self) -> u32 {
         self.elems
             .iter()
@@ -12,6 +12,7 @@ use std::collections::HashMap;
 pub struct Table {
     map: HashMap<u32, Box<dyn Any + Send + Sync>>,
     next_key: u32,
+    limit: usize,
 }
 
 impl Table {
@@ -20,9 +21,18 @@ impl Table {
         Table {
             map: HashMap::new(),
             next_key: 3, // 0, 1 and 2 are reserved for stdio
+            limit: u32::MAX as usize,
         }
     }
 
+    /// Limit the number of resources in the table. Once it holds `limit` resources,
+    /// `push_limited` fails with `Mfile` until some are deleted. Every resource counts toward the
+    /// limit, but those inserted with `push` or `insert_at` are never refused, so the host can
+    /// still add stdio and preopens after setting it.
+    pub fn set_limit(&mut self, limit: u32) {
+        self.limit = limit as usize;
+    }
+
     /// Insert a resource at a certain index.
     pub fn insert_at(&mut self, key: u32, a: Box<dyn Any + Send + Sync>) {
         self.map.insert(key, a);
@@ -46,6 +56,20 @@ impl Table {
         }
     }
 
+    /// Insert a resource at the next available index, unless the table is at its limit. This is
+    /// for the resources a guest opens, such as files and accepted connections.
+    pub fn push_limited(&mut self, a: Box<dyn Any + Send + Sync>) -> Result<u32, Error> {
+        if self.map.len() >= self.limit {
+            return Err(Error::too_many_open_files().context("table is at its limit"));
+        }
+        self.push(a)
+    }
+
+    /// Returns the indices of every resource in the table.
+    pub(crate) fn keys(&self) -> Vec<u32> {
+        self.map.keys().copied().collect()
+    }
+
     /// Check if the table has a resource at the given index.
     pub fn contains_key(&self, key: u32) -> bool {
         self.map.contains_key(&key)
@@ -90,3 +114,33 @@ impl Table {
         self.map.remove(&key)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn limit() {
+        let mut table = Table::new();
+        table.insert_at(0, Box::new(()));
+        table.set_limit(3);
+        assert_eq!(table.push_limited(Box::new(())).unwrap(), 3);
+        assert_eq!(table.push_limited(Box::new(())).unwrap(), 4);
+        let err = table.push_limited(Box::new(())).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&ErrorKind::Mfile));
+
+        table.delete(3);
+        assert_eq!(table.push_limited(Box::new(())).unwrap(), 5);
+
+        // The host's own insertions aren't refused, but they count.
+        assert_eq!(table.push(Box::new(())).unwrap(), 6);
+        table.insert_at(1, Box::new(()));
+        table.delete(4);
+        table.delete(5);
+        let err = table.push_limited(Box::new(())).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&ErrorKind::Mfile));
+        table.delete(6);
+        assert_eq!(table.push_limited(Box::new(())).unwrap(), 7);
+    }
+}
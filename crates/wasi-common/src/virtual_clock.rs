@@ -0,0 +1,379 @@
+//! A clock which only moves when the host, or the guest's waiting, moves it,
+//! for running time-dependent guests instantly and deterministically.
+//!
+//! A `VirtualClock` is installed in a `WasiCtx` with
+//! [`VirtualClock::install`], which makes it the context's system and
+//! monotonic clock, and wraps the context's scheduler so that waiting on a
+//! clock advances the virtual time rather than blocking: `sched_yield` and
+//! relative sleeps return at once, and a `poll_oneoff` which finds nothing
+//! ready skips ahead to its earliest deadline. A `poll_oneoff` without a
+//! deadline still waits for its files, as no amount of time would make them
+//! ready.
+//!
+//! The host moves the clock with [`VirtualClock::advance`], and may tie it to
+//! the work the guest has done by calling [`VirtualClock::set_fuel_consumed`]
+//! with a `Store`'s `fuel_consumed`, for example from its own hostcalls.
+
+use crate::clocks::{WasiClocks, WasiMonotonicClock, WasiSystemClock};
+use crate::sched::{Poll, Subscription, SubscriptionResult, Userdata, WasiSched};
+use crate::{Error, WasiCtx};
+use cap_std::time::{Duration, Instant, SystemTime};
+use std::sync::{Arc, Mutex};
+
+/// A virtual system and monotonic clock. Clones share the same time.
+#[derive(Clone)]
+pub struct VirtualClock(Arc<Mutex<State>>);
+
+struct State {
+    /// The monotonic time at which the clock was created, which the guest
+    /// measures monotonic time from.
+    origin: Instant,
+    /// The time the clock has been advanced by, not counting fuel.
+    advanced: Duration,
+    /// The system time which was last set, and the elapsed time when it was.
+    /// The elapsed time never decreases, so the system time is this plus
+    /// however much has elapsed since.
+    system_time: SystemTime,
+    system_time_set_at: Duration,
+    fuel: u64,
+    per_fuel: Duration,
+}
+
+impl State {
+    fn elapsed(&self) -> Duration {
+        let fuel_nanos = self.per_fuel.as_nanos() * u128::from(self.fuel);
+        self.advanced + Duration::from_nanos(fuel_nanos.min(u128::from(u64::MAX)) as u64)
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.system_time + (self.elapsed() - self.system_time_set_at)
+    }
+}
+
+impl VirtualClock {
+    /// Creates a clock which reads `system_time` as the system time, and no
+    /// time elapsed since the guest started as the monotonic time.
+    pub fn new(system_time: SystemTime) -> Self {
+        VirtualClock(Arc::new(Mutex::new(State {
+            origin: Instant::from_std(std::time::Instant::now()),
+            advanced: Duration::from_secs(0),
+            system_time,
+            system_time_set_at: Duration::from_secs(0),
+            fuel: 0,
+            per_fuel: Duration::from_secs(0),
+        })))
+    }
+
+    /// Returns the monotonic time which has elapsed since the clock was
+    /// created.
+    pub fn elapsed(&self) -> Duration {
+        self.0.lock().unwrap().elapsed()
+    }
+
+    /// Moves both clocks forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.0.lock().unwrap().advanced += duration;
+    }
+
+    /// Moves both clocks forward until the monotonic clock reads `deadline`.
+    /// Does nothing if it's already past it.
+    pub fn advance_to(&self, deadline: Instant) {
+        let mut state = self.0.lock().unwrap();
+        let now = state.origin + state.elapsed();
+        if let Some(duration) = deadline.checked_duration_since(now) {
+            state.advanced += duration;
+        }
+    }
+
+    /// Sets the system clock to `time`, which may be earlier than it reads
+    /// now. The monotonic clock is unaffected.
+    pub fn set_system_time(&self, time: SystemTime) {
+        let mut state = self.0.lock().unwrap();
+        state.system_time = time;
+        state.system_time_set_at = state.elapsed();
+    }
+
+    /// Makes each unit of fuel which the guest consumes advance both clocks
+    /// by `per_fuel`, in addition to any other advances.
+    pub fn set_fuel_rate(&self, per_fuel: Duration) {
+        self.0.lock().unwrap().per_fuel = per_fuel;
+    }
+
+    /// Updates the fuel the guest has consumed so far, which advances both
+    /// clocks at the rate of `set_fuel_rate`. Fuel is only counted once, so
+    /// this does nothing if `fuel` is less than a previous call's.
+    pub fn set_fuel_consumed(&self, fuel: u64) {
+        let mut state = self.0.lock().unwrap();
+        state.fuel = state.fuel.max(fuel);
+    }
+
+    /// Replaces the clocks of `ctx` with this clock, and wraps its scheduler
+    /// to advance this clock instead of waiting for time to pass.
+    pub fn install(&self, mut ctx: WasiCtx) -> WasiCtx {
+        ctx.clocks = WasiClocks {
+            system: Box::new(self.clone()),
+            monotonic: Box::new(self.clone()),
+            creation_time: self.0.lock().unwrap().origin,
+        };
+        ctx.sched = Box::new(VirtualSched {
+            inner: ctx.sched,
+            clock: self.clone(),
+        });
+        ctx
+    }
+}
+
+impl WasiSystemClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+    fn now(&self, _precision: Duration) -> SystemTime {
+        self.0.lock().unwrap().system_time()
+    }
+}
+
+impl WasiMonotonicClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+    fn now(&self, _precision: Duration) -> Instant {
+        let state = self.0.lock().unwrap();
+        state.origin + state.elapsed()
+    }
+}
+
+struct VirtualSched {
+    inner: Box<dyn WasiSched>,
+    clock: VirtualClock,
+}
+
+#[wiggle::async_trait]
+impl WasiSched for VirtualSched {
+    async fn poll_oneoff<'a>(&self, poll: &mut Poll<'a>) -> Result<(), Error> {
+        let deadline = match poll.earliest_clock_deadline() {
+            Some(sub) => sub.deadline,
+            // Nothing but the files can end the wait, so wait for them.
+            None => return self.inner.poll_oneoff(poll).await,
+        };
+
+        // Find which files are ready without waiting, by polling them
+        // alongside a deadline which has already passed.
+        let mut probe = Poll::new();
+        for (i, sub) in poll.rw_subscriptions().enumerate() {
+            match sub {
+                Subscription::Read(rw) => probe.subscribe_read(rw.file, Userdata::from(i as u64)),
+                Subscription::Write(rw) => probe.subscribe_write(rw.file, Userdata::from(i as u64)),
+                Subscription::MonotonicClock(_) => unreachable!(),
+            }
+        }
+        if !probe.is_empty() {
+            let now = WasiMonotonicClock::now(&self.clock, Duration::from_secs(0));
+            probe.subscribe_monotonic_clock(
+                &self.clock,
+                now,
+                Duration::from_secs(0),
+                Userdata::from(u64::MAX),
+            );
+            self.inner.poll_oneoff(&mut probe).await?;
+            let mut ready = false;
+            for (result, ud) in probe.results() {
+                let result = match result {
+                    SubscriptionResult::Read(r) | SubscriptionResult::Write(r) => r,
+                    SubscriptionResult::MonotonicClock(_) => continue,
+                };
+                match poll.rw_subscriptions().nth(u64::from(ud) as usize) {
+                    Some(Subscription::Read(rw)) | Some(Subscription::Write(rw)) => match result {
+                        Ok((size, flags)) => rw.complete(size, flags),
+                        Err(e) => rw.error(e),
+                    },
+                    _ => unreachable!(),
+                }
+                ready = true;
+            }
+            if ready {
+                return Ok(());
+            }
+        }
+
+        // Nothing is ready, so skip ahead to the deadline.
+        self.clock.advance_to(deadline);
+        Ok(())
+    }
+    async fn sched_yield(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn sleep(&self, duration: Duration) -> Result<(), Error> {
+        self.clock.advance(duration);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipe::ReadPipe;
+    use crate::random::Deterministic;
+    use crate::sched::RwEventFlags;
+    use crate::{ErrorExt, Table};
+    use std::future::Future;
+    use std::task::{Context, Poll as TaskPoll, RawWaker, RawWakerVTable, Waker};
+    use std::time::UNIX_EPOCH;
+
+    /// Runs a future which never waits, as every future here completes
+    /// immediately.
+    fn run<F: Future>(future: F) -> F::Output {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        let waker = unsafe { Waker::from_raw(clone(std::ptr::null())) };
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            TaskPoll::Ready(output) => output,
+            TaskPoll::Pending => panic!("future waited"),
+        }
+    }
+
+    /// A scheduler which finds the files with a nonzero index ready, and
+    /// fails if it would have to wait for time to pass.
+    struct ProbeSched;
+
+    #[wiggle::async_trait]
+    impl WasiSched for ProbeSched {
+        async fn poll_oneoff<'a>(&self, poll: &mut Poll<'a>) -> Result<(), Error> {
+            if let Some(sub) = poll.earliest_clock_deadline() {
+                let duration = sub.duration_until().unwrap_or(Duration::from_secs(0));
+                assert_eq!(duration, Duration::from_secs(0), "waited for time to pass");
+            }
+            for (i, sub) in poll.rw_subscriptions().enumerate() {
+                if let Subscription::Read(rw) | Subscription::Write(rw) = sub {
+                    if i > 0 {
+                        rw.complete(5, RwEventFlags::empty());
+                    }
+                }
+            }
+            Ok(())
+        }
+        async fn sched_yield(&self) -> Result<(), Error> {
+            Err(Error::not_supported().context("yielded"))
+        }
+        async fn sleep(&self, _duration: Duration) -> Result<(), Error> {
+            Err(Error::not_supported().context("slept"))
+        }
+    }
+
+    fn ctx(clock: &VirtualClock) -> WasiCtx {
+        let clocks = WasiClocks {
+            system: Box::new(clock.clone()),
+            monotonic: Box::new(clock.clone()),
+            creation_time: Instant::from_std(std::time::Instant::now()),
+        };
+        clock.install(WasiCtx::new(
+            Box::new(Deterministic::new(vec![0])),
+            clocks,
+            Box::new(ProbeSched),
+            Table::new(),
+        ))
+    }
+
+    #[test]
+    fn advance() {
+        let start = SystemTime::from_std(UNIX_EPOCH + std::time::Duration::from_secs(1000));
+        let clock = VirtualClock::new(start);
+        let ctx = ctx(&clock);
+        let monotonic = || {
+            ctx.clocks
+                .monotonic
+                .now(Duration::from_secs(0))
+                .duration_since(ctx.clocks.creation_time)
+        };
+        let system = || ctx.clocks.system.now(Duration::from_secs(0));
+        assert_eq!(monotonic(), Duration::from_secs(0));
+        assert_eq!(system(), start);
+
+        clock.advance(Duration::from_secs(2));
+        run(ctx.sched.sleep(Duration::from_secs(3))).unwrap();
+        assert_eq!(monotonic(), Duration::from_secs(5));
+        assert_eq!(system(), start + Duration::from_secs(5));
+
+        clock.set_fuel_rate(Duration::from_millis(1));
+        clock.set_fuel_consumed(2000);
+        clock.set_fuel_consumed(1000);
+        assert_eq!(monotonic(), Duration::from_secs(7));
+
+        // Setting the system time leaves monotonic time alone.
+        clock.set_system_time(start);
+        assert_eq!(monotonic(), Duration::from_secs(7));
+        assert_eq!(system(), start);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(system(), start + Duration::from_secs(1));
+
+        // The system time may be set to before the clock was created, or
+        // before as much time as has elapsed.
+        let epoch = SystemTime::from_std(UNIX_EPOCH);
+        clock.set_system_time(epoch);
+        assert_eq!(system(), epoch);
+        clock.set_fuel_consumed(3000);
+        assert_eq!(system(), epoch + Duration::from_secs(1));
+        assert_eq!(monotonic(), Duration::from_secs(9));
+    }
+
+    #[test]
+    fn poll_oneoff() {
+        let clock = VirtualClock::new(SystemTime::from_std(UNIX_EPOCH));
+        let ctx = ctx(&clock);
+        let deadline = |secs| {
+            ctx.clocks
+                .monotonic
+                .now(Duration::from_secs(0))
+                .checked_add(Duration::from_secs(secs))
+                .unwrap()
+        };
+        let idle = ReadPipe::from("");
+        let ready = ReadPipe::from("hello");
+
+        // With nothing ready, the clock skips ahead to the earliest deadline.
+        let mut poll = Poll::new();
+        poll.subscribe_read(&idle, Userdata::from(1));
+        poll.subscribe_monotonic_clock(
+            &*ctx.clocks.monotonic,
+            deadline(20),
+            Duration::from_secs(0),
+            Userdata::from(2),
+        );
+        poll.subscribe_monotonic_clock(
+            &*ctx.clocks.monotonic,
+            deadline(10),
+            Duration::from_secs(0),
+            Userdata::from(3),
+        );
+        run(ctx.sched.poll_oneoff(&mut poll)).unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+        let results = poll.results();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            results[0],
+            (SubscriptionResult::MonotonicClock(Ok(())), ud) if ud == Userdata::from(3)
+        ));
+
+        // With a file ready, time stands still.
+        let mut poll = Poll::new();
+        poll.subscribe_read(&idle, Userdata::from(1));
+        poll.subscribe_read(&ready, Userdata::from(2));
+        poll.subscribe_monotonic_clock(
+            &*ctx.clocks.monotonic,
+            deadline(10),
+            Duration::from_secs(0),
+            Userdata::from(3),
+        );
+        run(ctx.sched.poll_oneoff(&mut poll)).unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+        let results = poll.results();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            results[0],
+            (SubscriptionResult::Read(Ok((5, _))), ud) if ud == Userdata::from(2)
+        ));
+    }
+}
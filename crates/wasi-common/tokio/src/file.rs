@@ -6,7 +6,7 @@ use io_lifetimes::{AsHandle, BorrowedHandle};
 use std::any::Any;
 use std::io;
 use wasi_common::{
-    file::{Advice, FdFlags, FileType, Filestat, WasiFile},
+    file::{Advice, FdFlags, FileType, Filestat, RiFlags, RoFlags, SdFlags, WasiFile},
     Error,
 };
 
@@ -39,8 +39,21 @@ pub fn stderr() -> Stderr {
     Stderr(wasi_cap_std_sync::stdio::stderr())
 }
 
+// Implements `WasiFile` for a wrapper around a type from
+// `wasi_cap_std_sync`, delegating to the wrapped type. The `@impl` form takes
+// any additional methods of the impl, and leaves out the `AsHandle` impl so
+// that sockets can provide `AsSocket` on windows instead.
 macro_rules! wasi_file_impl {
     ($ty:ty) => {
+        wasi_file_impl!(@impl $ty, {});
+        #[cfg(windows)]
+        impl AsHandle for $ty {
+            fn as_handle(&self) -> BorrowedHandle<'_> {
+                self.0.as_handle()
+            }
+        }
+    };
+    (@impl $ty:ty, { $($methods:tt)* }) => {
         #[wiggle::async_trait]
         impl WasiFile for $ty {
             fn as_any(&self) -> &dyn Any {
@@ -112,6 +125,19 @@ macro_rules! wasi_file_impl {
             async fn num_ready_bytes(&self) -> Result<u64, Error> {
                 block_on_dummy_executor(|| self.0.num_ready_bytes())
             }
+            async fn sock_recv<'a>(
+                &self,
+                ri_data: &mut [io::IoSliceMut<'a>],
+                ri_flags: RiFlags,
+            ) -> Result<(u64, RoFlags), Error> {
+                block_on_dummy_executor(move || self.0.sock_recv(ri_data, ri_flags))
+            }
+            async fn sock_send<'a>(&self, si_data: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+                block_on_dummy_executor(move || self.0.sock_send(si_data))
+            }
+            async fn sock_shutdown(&self, how: SdFlags) -> Result<(), Error> {
+                block_on_dummy_executor(move || self.0.sock_shutdown(how))
+            }
 
             #[cfg(not(windows))]
             async fn readable(&self) -> Result<(), Error> {
@@ -172,12 +198,8 @@ macro_rules! wasi_file_impl {
                 use wasi_common::ErrorExt;
                 Err(Error::badf())
             }
-        }
-        #[cfg(windows)]
-        impl AsHandle for $ty {
-            fn as_handle(&self) -> BorrowedHandle<'_> {
-                self.0.as_handle()
-            }
+
+            $($methods)*
         }
     };
 }
@@ -1,12 +1,15 @@
 mod dir;
+#[macro_use]
 mod file;
+pub mod net;
+pub mod pipe;
 pub mod sched;
 pub mod stdio;
 
 use std::future::Future;
 use std::path::Path;
 pub use wasi_cap_std_sync::{clocks_ctx, random_ctx};
-use wasi_common::{Error, Table, WasiCtx, WasiFile};
+use wasi_common::{file::FileCaps, Error, Table, WasiCtx, WasiFile};
 
 pub use dir::Dir;
 pub use file::File;
@@ -89,6 +92,26 @@ impl WasiCtxBuilder {
         self.0.push_preopened_dir(dir, guest_path)?;
         Ok(self)
     }
+    /// Inserts `socket` into the table at `fd`. Guests can't discover
+    /// sockets like preopened directories, so `fd` has to be agreed upon
+    /// with the guest, and should come after any preopened directories.
+    pub fn preopened_socket(mut self, fd: u32, socket: impl Into<net::Socket>) -> Self {
+        let socket = net::wasi_file_from_socket(socket.into());
+        let caps = FileCaps::FDSTAT_SET_FLAGS
+            | FileCaps::FILESTAT_GET
+            | FileCaps::READ
+            | FileCaps::POLL_READWRITE;
+        self.0.insert_file(fd, socket, caps);
+        self
+    }
+    /// Limits the number of descriptors the guest can have open, counting
+    /// stdio and preopens. Once it has that many, `path_open` and
+    /// `sock_accept` fail with `EMFILE`; the preopens themselves are always
+    /// added, whether they come before or after this.
+    pub fn fd_limit(mut self, limit: u32) -> Self {
+        self.0.table().set_limit(limit);
+        self
+    }
     pub fn build(self) -> WasiCtx {
         self.0
     }
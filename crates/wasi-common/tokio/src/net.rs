@@ -0,0 +1,77 @@
+use crate::block_on_dummy_executor;
+#[cfg(not(windows))]
+use io_lifetimes::AsFd;
+#[cfg(windows)]
+use io_lifetimes::{AsSocket, BorrowedSocket};
+use std::any::Any;
+use std::io;
+use wasi_common::{
+    file::{Advice, FdFlags, FileType, Filestat, RiFlags, RoFlags, SdFlags, WasiFile},
+    Error,
+};
+
+/// A socket which can be inserted into a `WasiCtx`, typically with
+/// `WasiCtxBuilder::preopened_socket`.
+pub use wasi_cap_std_sync::net::Socket;
+
+pub(crate) fn wasi_file_from_socket(socket: Socket) -> Box<dyn WasiFile> {
+    match socket {
+        Socket::TcpListener(listener) => Box::new(TcpListener::from_cap_std(listener)),
+        Socket::TcpStream(stream) => Box::new(TcpStream::from_cap_std(stream)),
+        #[cfg(unix)]
+        Socket::UnixListener(listener) => Box::new(UnixListener::from_cap_std(listener)),
+        #[cfg(unix)]
+        Socket::UnixStream(stream) => Box::new(UnixStream::from_cap_std(stream)),
+    }
+}
+
+macro_rules! wasi_socket_impl {
+    ($ty:ident, $inner:ident, $cap_std:ty, { $($methods:tt)* }) => {
+        pub struct $ty(wasi_cap_std_sync::net::$inner);
+
+        impl $ty {
+            pub fn from_cap_std(socket: $cap_std) -> Self {
+                $ty(wasi_cap_std_sync::net::$inner::from_cap_std(socket))
+            }
+        }
+
+        wasi_file_impl!(@impl $ty, { $($methods)* });
+
+        #[cfg(windows)]
+        impl AsSocket for $ty {
+            fn as_socket(&self) -> BorrowedSocket<'_> {
+                self.0.as_socket()
+            }
+        }
+    };
+}
+
+macro_rules! wasi_listener_impl {
+    ($ty:ident, $cap_std:ty, $stream:ident) => {
+        wasi_socket_impl!($ty, $ty, $cap_std, {
+            async fn sock_accept(&self, fdflags: FdFlags) -> Result<Box<dyn WasiFile>, Error> {
+                // Accept with the listener from `wasi_cap_std_sync`, but wrap
+                // the connection in this crate's type so that polling it goes
+                // through tokio.
+                let stream = tokio::task::block_in_place(|| self.0.accept(fdflags))?;
+                Ok(Box::new($stream(stream)))
+            }
+        });
+    };
+}
+
+wasi_listener_impl!(TcpListener, cap_std::net::TcpListener, TcpStream);
+wasi_socket_impl!(TcpStream, TcpStream, cap_std::net::TcpStream, {});
+#[cfg(unix)]
+wasi_listener_impl!(
+    UnixListener,
+    cap_std::os::unix::net::UnixListener,
+    UnixStream
+);
+#[cfg(unix)]
+wasi_socket_impl!(
+    UnixStream,
+    UnixStream,
+    cap_std::os::unix::net::UnixStream,
+    {}
+);
@@ -0,0 +1,293 @@
+//! Virtual pipes backed by tokio's `AsyncRead` and `AsyncWrite`.
+//!
+//! Unlike the pipes of `wasi_common::pipe`, which block on a `Read` or
+//! `Write`, these can be waited on by `poll_oneoff`: a read subscription on
+//! an `AsyncReadPipe` is ready once data has arrived, or the stream has
+//! ended. This lets a host stream data into a long-running guest through a
+//! bounded in-memory channel:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), anyhow::Error> {
+//! use tokio::io::AsyncWriteExt;
+//! let (mut input, stdin) = wasi_tokio::pipe::read_channel(4096);
+//! let ctx = wasi_tokio::WasiCtxBuilder::new()
+//!     .stdin(Box::new(stdin))
+//!     .build();
+//! // Run an instance using `ctx` in another task, and then:
+//! input.write_all(b"hello\n").await?;
+//! // The guest sees the end of its stdin once `input` is dropped.
+//! drop(input);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::any::Any;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::Mutex;
+use wasi_common::{
+    file::{Advice, FdFlags, FileType, Filestat, WasiFile},
+    Error, ErrorExt, SystemTimeSpec,
+};
+
+/// Creates a pipe for the host to write into, and the guest to read from,
+/// which buffers up to `capacity` bytes. Returns the host's end and the
+/// guest's end, in that order.
+pub fn read_channel(capacity: usize) -> (DuplexStream, AsyncReadPipe<DuplexStream>) {
+    let (host, guest) = tokio::io::duplex(capacity);
+    (host, AsyncReadPipe::new(guest))
+}
+
+/// Creates a pipe for the guest to write into, and the host to read from,
+/// which buffers up to `capacity` bytes. Returns the guest's end and the
+/// host's end, in that order.
+pub fn write_channel(capacity: usize) -> (AsyncWritePipe<DuplexStream>, DuplexStream) {
+    let (guest, host) = tokio::io::duplex(capacity);
+    (AsyncWritePipe::new(guest), host)
+}
+
+/// The read end of a virtual pipe, whose data comes from an `AsyncRead`.
+///
+/// Reads wait until some data is available, and return as much of it as
+/// fits, like reads of a pipe in blocking mode. Clones share the reader.
+pub struct AsyncReadPipe<R> {
+    reader: Arc<Mutex<BufReader<R>>>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReadPipe<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncReadPipe {
+            reader: Arc::new(Mutex::new(BufReader::new(reader))),
+        }
+    }
+}
+
+impl<R> Clone for AsyncReadPipe<R> {
+    fn clone(&self) -> Self {
+        AsyncReadPipe {
+            reader: self.reader.clone(),
+        }
+    }
+}
+
+#[wiggle::async_trait]
+impl<R: AsyncRead + Unpin + Send + 'static> WasiFile for AsyncReadPipe<R> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn datasync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+    async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+        Ok(FdFlags::empty())
+    }
+    async fn set_fdflags(&mut self, _fdflags: FdFlags) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(Filestat {
+            device_id: 0,
+            inode: 0,
+            filetype: self.get_filetype().await?,
+            nlink: 0,
+            size: 0,
+            atim: None,
+            mtim: None,
+            ctim: None,
+        })
+    }
+    async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn advise(&self, _offset: u64, _len: u64, _advice: Advice) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn allocate(&self, _offset: u64, _len: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn read_vectored<'a>(&self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        let mut reader = self.reader.lock().await;
+        let data = reader.fill_buf().await?;
+        let mut n = 0;
+        for buf in bufs.iter_mut() {
+            let len = buf.len().min(data.len() - n);
+            buf[..len].copy_from_slice(&data[n..n + len]);
+            n += len;
+        }
+        reader.consume(n);
+        Ok(n as u64)
+    }
+    async fn read_vectored_at<'a>(
+        &self,
+        _bufs: &mut [io::IoSliceMut<'a>],
+        _offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn write_vectored<'a>(&self, _bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn write_vectored_at<'a>(
+        &self,
+        _bufs: &[io::IoSlice<'a>],
+        _offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn seek(&self, _pos: std::io::SeekFrom) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn peek(&self, buf: &mut [u8]) -> Result<u64, Error> {
+        let mut reader = self.reader.lock().await;
+        let data = reader.fill_buf().await?;
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n as u64)
+    }
+    async fn set_times(
+        &self,
+        _atime: Option<SystemTimeSpec>,
+        _mtime: Option<SystemTimeSpec>,
+    ) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        Ok(self.reader.lock().await.buffer().len() as u64)
+    }
+    async fn readable(&self) -> Result<(), Error> {
+        // Waits for data to arrive, and buffers it for the next read. At the
+        // end of the stream this returns straight away with nothing
+        // buffered, and the next read returns 0.
+        self.reader.lock().await.fill_buf().await?;
+        Ok(())
+    }
+    async fn writable(&self) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+}
+
+/// The write end of a virtual pipe, whose data goes to an `AsyncWrite`.
+///
+/// Writes wait until all of their data has been written and flushed, so a
+/// write to a full channel waits for the host to read from it. Since an
+/// `AsyncWrite` can't tell whether it has room without being written to,
+/// the pipe is always ready for writing as far as `poll_oneoff` is
+/// concerned. Clones share the writer.
+pub struct AsyncWritePipe<W> {
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWritePipe<W> {
+    pub fn new(writer: W) -> Self {
+        AsyncWritePipe {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+}
+
+impl<W> Clone for AsyncWritePipe<W> {
+    fn clone(&self) -> Self {
+        AsyncWritePipe {
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+#[wiggle::async_trait]
+impl<W: AsyncWrite + Unpin + Send + 'static> WasiFile for AsyncWritePipe<W> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn datasync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn get_filetype(&self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+    async fn get_fdflags(&self) -> Result<FdFlags, Error> {
+        Ok(FdFlags::APPEND)
+    }
+    async fn set_fdflags(&mut self, _fdflags: FdFlags) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(Filestat {
+            device_id: 0,
+            inode: 0,
+            filetype: self.get_filetype().await?,
+            nlink: 0,
+            size: 0,
+            atim: None,
+            mtim: None,
+            ctim: None,
+        })
+    }
+    async fn set_filestat_size(&self, _size: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn advise(&self, _offset: u64, _len: u64, _advice: Advice) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn allocate(&self, _offset: u64, _len: u64) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn read_vectored<'a>(&self, _bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn read_vectored_at<'a>(
+        &self,
+        _bufs: &mut [io::IoSliceMut<'a>],
+        _offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn write_vectored<'a>(&self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        let mut writer = self.writer.lock().await;
+        let mut n = 0;
+        for buf in bufs {
+            writer.write_all(buf).await?;
+            n += buf.len();
+        }
+        writer.flush().await?;
+        Ok(n as u64)
+    }
+    async fn write_vectored_at<'a>(
+        &self,
+        _bufs: &[io::IoSlice<'a>],
+        _offset: u64,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn seek(&self, _pos: std::io::SeekFrom) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn peek(&self, _buf: &mut [u8]) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+    async fn set_times(
+        &self,
+        _atime: Option<SystemTimeSpec>,
+        _mtime: Option<SystemTimeSpec>,
+    ) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        Ok(0)
+    }
+    async fn readable(&self) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+    async fn writable(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
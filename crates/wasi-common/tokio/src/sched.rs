@@ -1,7 +1,9 @@
+// Waits on the `readable` and `writable` futures of each file. This is how
+// all polling is done on unix, and on windows when only files without
+// handles, such as the pipes of `crate::pipe`, are polled.
+mod ready;
 #[cfg(unix)]
-mod unix;
-#[cfg(unix)]
-pub use unix::poll_oneoff;
+pub use ready::poll_oneoff;
 
 #[cfg(windows)]
 mod windows;
@@ -1,10 +1,30 @@
 use crate::block_on_dummy_executor;
-use io_lifetimes::AsHandle;
-use std::os::windows::io::{AsRawHandle, RawHandle};
+use io_lifetimes::{AsHandle, AsSocket};
+use std::os::windows::io::{AsRawHandle, AsRawSocket, RawHandle};
 use wasi_cap_std_sync::sched::windows::poll_oneoff_;
-use wasi_common::{file::WasiFile, sched::Poll, Error};
+use wasi_common::{
+    file::WasiFile,
+    sched::{subscription::Subscription, Poll},
+    Error,
+};
 
 pub async fn poll_oneoff<'a>(poll: &mut Poll<'a>) -> Result<(), Error> {
+    // Files without handles, such as pipes, can't be waited on by the
+    // blocking implementation, but their readiness futures can be awaited
+    // like they are on unix, as long as nothing else is polled alongside.
+    let mut handleless = None;
+    for s in poll.rw_subscriptions() {
+        let file = match s {
+            Subscription::Read(f) | Subscription::Write(f) => f.file,
+            Subscription::MonotonicClock { .. } => unreachable!(),
+        };
+        let has_handle = wasi_file_is_stdin(file) || wasi_file_raw_handle(file).is_some();
+        handleless = Some(handleless.unwrap_or(true) && !has_handle);
+    }
+    if handleless == Some(true) {
+        return super::ready::poll_oneoff(poll).await;
+    }
+
     // Tokio doesn't provide us the AsyncFd primitive on Windows, so instead
     // we use the blocking poll_oneoff implementation from the wasi-cap-std-crate.
     // We provide a function specific to this crate's WasiFile types for downcasting
@@ -46,6 +66,20 @@ fn wasi_file_raw_handle(f: &dyn WasiFile) -> Option<RawHandle> {
                 .as_handle()
                 .as_raw_handle(),
         )
+    } else if a.is::<crate::net::TcpListener>() {
+        Some(
+            a.downcast_ref::<crate::net::TcpListener>()
+                .unwrap()
+                .as_socket()
+                .as_raw_socket() as RawHandle,
+        )
+    } else if a.is::<crate::net::TcpStream>() {
+        Some(
+            a.downcast_ref::<crate::net::TcpStream>()
+                .unwrap()
+                .as_socket()
+                .as_raw_socket() as RawHandle,
+        )
     } else {
         None
     }
@@ -0,0 +1,68 @@
+use anyhow::Error;
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use wasi_common::{
+    file::{FdFlags, RiFlags, SdFlags},
+    sched::{Poll, RwEventFlags, SubscriptionResult, Userdata},
+    WasiFile,
+};
+use wasi_tokio::{net, sched::poll_oneoff};
+
+/// Waits for `listener` to poll as readable, accepts the connection, and
+/// exchanges a message with the client before shutting down writing.
+async fn serve_one(listener: &dyn WasiFile) -> Result<(), Error> {
+    let mut poll = Poll::new();
+    poll.subscribe_read(listener, Userdata::from(123));
+    poll_oneoff(&mut poll).await?;
+    match poll.results().get(0).expect("at least one event") {
+        (SubscriptionResult::Read(Ok((1, flags))), ud) => {
+            assert_eq!(*flags, RwEventFlags::empty());
+            assert_eq!(*ud, Userdata::from(123));
+        }
+        other => panic!("expected (Read(Ok(1, empty), 123), got: {:?}", other),
+    }
+
+    let stream = listener.sock_accept(FdFlags::empty()).await?;
+    let mut buf = [0; 4];
+    let (n, _) = stream
+        .sock_recv(&mut [IoSliceMut::new(&mut buf)], RiFlags::RECV_WAITALL)
+        .await?;
+    assert_eq!(n, 4);
+    assert_eq!(&buf, b"ping");
+    assert_eq!(stream.sock_send(&[IoSlice::new(b"pong")]).await?, 4);
+    stream.sock_shutdown(SdFlags::WR).await?;
+    Ok(())
+}
+
+/// The client side of `serve_one`.
+fn client(mut stream: impl Read + Write) {
+    stream.write_all(b"ping").unwrap();
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).unwrap();
+    assert_eq!(reply, b"pong");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp_accept() -> Result<(), Error> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let listener = net::TcpListener::from_cap_std(cap_std::net::TcpListener::from_std(listener));
+    let client = std::thread::spawn(move || client(std::net::TcpStream::connect(addr).unwrap()));
+    serve_one(&listener).await?;
+    client.join().unwrap();
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn unix_accept() -> Result<(), Error> {
+    let tempdir = tempfile::Builder::new().prefix("wasi-tokio").tempdir()?;
+    let path = tempdir.path().join("socket");
+    let listener = std::os::unix::net::UnixListener::bind(&path)?;
+    let listener =
+        net::UnixListener::from_cap_std(cap_std::os::unix::net::UnixListener::from_std(listener));
+    let client =
+        std::thread::spawn(move || client(std::os::unix::net::UnixStream::connect(path).unwrap()));
+    serve_one(&listener).await?;
+    client.join().unwrap();
+    Ok(())
+}
@@ -1,12 +1,13 @@
 use anyhow::{Context, Error};
 use cap_std::time::Duration;
 use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use wasi_common::{
     file::{FdFlags, OFlags},
     sched::{Poll, RwEventFlags, SubscriptionResult, Userdata},
     WasiDir, WasiFile,
 };
-use wasi_tokio::{clocks_ctx, sched::poll_oneoff, Dir};
+use wasi_tokio::{clocks_ctx, pipe, sched::poll_oneoff, Dir};
 
 const TIMEOUT: Duration = Duration::from_millis(200); // Required for slow execution in CI
 
@@ -160,3 +161,102 @@ async fn stdio_readable() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pipe_readable_when_written() -> Result<(), Error> {
+    let clocks = clocks_ctx();
+    let (mut input, stdin) = pipe::read_channel(16);
+
+    // Nothing has been written yet, so only the clock fires.
+    let mut poll = Poll::new();
+    poll.subscribe_read(&stdin, Userdata::from(123));
+    poll.subscribe_monotonic_clock(
+        &*clocks.monotonic,
+        clocks
+            .monotonic
+            .now(clocks.monotonic.resolution())
+            .checked_add(Duration::from_millis(10))
+            .unwrap(),
+        clocks.monotonic.resolution(),
+        Userdata::from(0),
+    );
+    poll_oneoff(&mut poll).await?;
+    match poll.results().get(0).expect("at least one event") {
+        (SubscriptionResult::MonotonicClock(Ok(())), ud) => assert_eq!(*ud, Userdata::from(0)),
+        e => panic!("expected (MonotonicClock(Ok(())), 0), got: {:?}", e),
+    }
+
+    // A poll without a deadline wakes when the host writes.
+    let writer = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        input.write_all(b"hello").await?;
+        Ok::<_, std::io::Error>(input)
+    });
+    let mut poll = Poll::new();
+    poll.subscribe_read(&stdin, Userdata::from(123));
+    poll_oneoff(&mut poll).await?;
+    match poll.results().get(0).expect("at least one event") {
+        (SubscriptionResult::Read(Ok((5, flags))), ud) => {
+            assert_eq!(*flags, RwEventFlags::empty());
+            assert_eq!(*ud, Userdata::from(123));
+        }
+        e => panic!("expected (Read(Ok(5, empty)), 123), got: {:?}", e),
+    }
+
+    let mut buf = [0; 8];
+    let n = stdin
+        .read_vectored(&mut [std::io::IoSliceMut::new(&mut buf)])
+        .await
+        .context("read from pipe")?;
+    assert_eq!(&buf[..n as usize], b"hello");
+
+    // Once the host's end is dropped, the pipe is readable at its end.
+    drop(writer.await??);
+    let mut poll = Poll::new();
+    poll.subscribe_read(&stdin, Userdata::from(123));
+    poll_oneoff(&mut poll).await?;
+    match poll.results().get(0).expect("at least one event") {
+        (SubscriptionResult::Read(Ok((0, _))), _) => {}
+        e => panic!("expected (Read(Ok(0, empty)), 123), got: {:?}", e),
+    }
+    let n = stdin
+        .read_vectored(&mut [std::io::IoSliceMut::new(&mut buf)])
+        .await
+        .context("read from pipe at end")?;
+    assert_eq!(n, 0);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pipe_writable() -> Result<(), Error> {
+    let (stdout, mut output) = pipe::write_channel(4);
+
+    let mut poll = Poll::new();
+    poll.subscribe_write(&stdout, Userdata::from(123));
+    poll_oneoff(&mut poll).await?;
+    match poll.results().get(0).expect("at least one event") {
+        (SubscriptionResult::Write(Ok((0, _))), ud) => assert_eq!(*ud, Userdata::from(123)),
+        e => panic!("expected (Write(Ok(0, empty)), 123), got: {:?}", e),
+    }
+
+    // The write is larger than the channel, so it completes as the host
+    // reads.
+    let reader = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        output.read_to_end(&mut buf).await?;
+        Ok::<_, std::io::Error>(buf)
+    });
+    let n = stdout
+        .write_vectored(&[
+            std::io::IoSlice::new(b"hello "),
+            std::io::IoSlice::new(b"world"),
+        ])
+        .await
+        .context("write to pipe")?;
+    assert_eq!(n, 11);
+    drop(stdout);
+    assert_eq!(reader.await??, b"hello world");
+
+    Ok(())
+}
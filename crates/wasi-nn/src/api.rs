@@ -1,13 +1,14 @@
 //! Define the Rust interface a backend must implement in order to be used by
 //! this crate. the `Box<dyn ...>` types returned by these interfaces allow
-//! implementations to maintain backend-specific state between calls.
+//! implementations to maintain backend-specific state between calls. Backends
+//! are registered with [crate::WasiNnCtx::register_backend].
 
 use crate::witx::types::{ExecutionTarget, GraphBuilderArray, Tensor};
 use thiserror::Error;
 use wiggle::GuestError;
 
 /// A [Backend] contains the necessary state to load [BackendGraph]s.
-pub(crate) trait Backend {
+pub trait Backend {
     fn name(&self) -> &str;
     fn load(
         &mut self,
@@ -18,13 +19,13 @@ pub(crate) trait Backend {
 
 /// A [BackendGraph] can create [BackendExecutionContext]s; this is the backing
 /// implementation for a [crate::witx::types::Graph].
-pub(crate) trait BackendGraph {
+pub trait BackendGraph {
     fn init_execution_context(&mut self) -> Result<Box<dyn BackendExecutionContext>, BackendError>;
 }
 
 /// A [BackendExecutionContext] performs the actual inference; this is the
 /// backing implementation for a [crate::witx::types::GraphExecutionContext].
-pub(crate) trait BackendExecutionContext {
+pub trait BackendExecutionContext {
     fn set_input(&mut self, index: u32, tensor: &Tensor<'_>) -> Result<(), BackendError>;
     fn compute(&mut self) -> Result<(), BackendError>;
     fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError>;
@@ -1,6 +1,8 @@
 //! Implements the base structure (i.e. [WasiNnCtx]) that will provide the
 //! implementation of the wasi-nn API.
 use crate::api::{Backend, BackendError, BackendExecutionContext, BackendGraph};
+use crate::onnx::OnnxBackend;
+#[cfg(feature = "openvino")]
 use crate::openvino::OpenvinoBackend;
 use crate::r#impl::UsageError;
 use crate::witx::types::{Graph, GraphEncoding, GraphExecutionContext};
@@ -18,20 +20,27 @@ pub struct Ctx {
 }
 
 impl Ctx {
-    /// Make a new context from the default state.
+    /// Make a new context from the default state; this registers every
+    /// backend compiled into this crate.
     pub fn new() -> WasiNnResult<Self> {
-        let mut backends = HashMap::new();
-        backends.insert(
-            // This is necessary because Wiggle's variant types do not derive
-            // `Hash` and `Eq`.
-            GraphEncoding::Openvino.into(),
-            Box::new(OpenvinoBackend::default()) as Box<dyn Backend>,
-        );
-        Ok(Self {
-            backends,
+        let mut ctx = Self {
+            backends: HashMap::new(),
             graphs: Table::default(),
             executions: Table::default(),
-        })
+        };
+        #[cfg(feature = "openvino")]
+        ctx.register(GraphEncoding::Openvino, OpenvinoBackend::default());
+        ctx.register(GraphEncoding::Onnx, OnnxBackend);
+        Ok(ctx)
+    }
+
+    /// Use `backend` to load graphs of the given `encoding`, replacing any
+    /// backend previously registered for it.
+    pub fn register(&mut self, encoding: GraphEncoding, backend: impl Backend + 'static) {
+        // This is necessary because Wiggle's variant types do not derive
+        // `Hash` and `Eq`.
+        let encoding: u8 = encoding.into();
+        self.backends.insert(encoding, Box::new(backend));
     }
 }
 
@@ -47,6 +56,12 @@ impl WasiNnCtx {
             ctx: RefCell::new(Ctx::new()?),
         })
     }
+
+    /// Use `backend` to load graphs of the given `encoding`; see
+    /// [Ctx::register].
+    pub fn register_backend(&self, encoding: GraphEncoding, backend: impl Backend + 'static) {
+        self.ctx.borrow_mut().register(encoding, backend);
+    }
 }
 
 /// Possible errors while interacting with [WasiNnCtx].
@@ -106,4 +121,28 @@ mod test {
     fn instantiate() {
         WasiNnCtx::new().unwrap();
     }
+
+    #[test]
+    fn register() {
+        let ctx = WasiNnCtx::new().unwrap();
+        let onnx: u8 = GraphEncoding::Onnx.into();
+        assert_eq!(ctx.ctx.borrow().backends[&onnx].name(), "onnx");
+
+        ctx.register_backend(GraphEncoding::Onnx, Named);
+        assert_eq!(ctx.ctx.borrow().backends[&onnx].name(), "named");
+    }
+
+    struct Named;
+    impl Backend for Named {
+        fn name(&self) -> &str {
+            "named"
+        }
+        fn load(
+            &mut self,
+            _builders: &crate::witx::types::GraphBuilderArray<'_>,
+            _target: crate::witx::types::ExecutionTarget,
+        ) -> Result<Box<dyn BackendGraph>, BackendError> {
+            Err(BackendError::InvalidNumberOfBuilders(0, 0))
+        }
+    }
 }
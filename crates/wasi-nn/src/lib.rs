@@ -1,8 +1,12 @@
 mod api;
 mod ctx;
 mod r#impl;
+mod onnx;
+#[cfg(feature = "openvino")]
 mod openvino;
 mod witx;
 
+pub use api::{Backend, BackendError, BackendExecutionContext, BackendGraph};
 pub use ctx::WasiNnCtx;
+pub use witx::types::{ExecutionTarget, GraphBuilderArray, GraphEncoding, Tensor, TensorType};
 pub use witx::wasi_ephemeral_nn::add_to_linker;
@@ -0,0 +1,117 @@
+//! Implements a pure-Rust, CPU-only backend for small ONNX models. It needs no
+//! native libraries, so wasi-nn guests can be run anywhere Wasmtime builds;
+//! only the operators in [ops] and `float` tensors are supported.
+use crate::api::{Backend, BackendError, BackendExecutionContext, BackendGraph};
+use crate::witx::types::{ExecutionTarget, GraphBuilderArray, Tensor, TensorType};
+use anyhow::{anyhow, bail};
+use model::Model;
+use ops::Array;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+mod model;
+mod ops;
+
+#[derive(Default)]
+pub(crate) struct OnnxBackend;
+
+impl Backend for OnnxBackend {
+    fn name(&self) -> &str {
+        "onnx"
+    }
+
+    fn load(
+        &mut self,
+        builders: &GraphBuilderArray<'_>,
+        target: ExecutionTarget,
+    ) -> Result<Box<dyn BackendGraph>, BackendError> {
+        if builders.len() != 1 {
+            return Err(BackendError::InvalidNumberOfBuilders(1, builders.len()));
+        }
+        if target != ExecutionTarget::Cpu {
+            return Err(anyhow!("the ONNX backend only supports CPU execution targets").into());
+        }
+
+        // The single builder is the serialized `ModelProto`.
+        let builders = builders.as_ptr();
+        let bytes = builders.read()?.as_slice()?;
+        let model = Model::parse(&bytes)?;
+        Ok(Box::new(OnnxGraph(Arc::new(model))))
+    }
+}
+
+struct OnnxGraph(Arc<Model>);
+
+impl BackendGraph for OnnxGraph {
+    fn init_execution_context(&mut self) -> Result<Box<dyn BackendExecutionContext>, BackendError> {
+        Ok(Box::new(OnnxExecutionContext {
+            model: self.0.clone(),
+            inputs: vec![None; self.0.inputs.len()],
+            outputs: Vec::new(),
+        }))
+    }
+}
+
+struct OnnxExecutionContext {
+    model: Arc<Model>,
+    inputs: Vec<Option<Array>>,
+    outputs: Vec<Array>,
+}
+
+impl BackendExecutionContext for OnnxExecutionContext {
+    fn set_input(&mut self, index: u32, tensor: &Tensor<'_>) -> Result<(), BackendError> {
+        let slot = self
+            .inputs
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow!("the model has no input {}", index))?;
+        if tensor.type_ != TensorType::F32 {
+            return Err(anyhow!("the ONNX backend only supports f32 tensors").into());
+        }
+
+        let shape = tensor
+            .dimensions
+            .as_slice()?
+            .iter()
+            .map(|d| *d as usize)
+            .collect::<Vec<_>>();
+        let data = tensor
+            .data
+            .as_slice()?
+            .chunks(4)
+            .map(|b| Ok(f32::from_le_bytes(b.try_into()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        slot.replace(Array::new(shape, data)?);
+        Ok(())
+    }
+
+    fn compute(&mut self) -> Result<(), BackendError> {
+        let inputs = self
+            .inputs
+            .iter()
+            .zip(&self.model.inputs)
+            .map(|(input, name)| match input {
+                Some(array) => Ok(array.clone()),
+                None => bail!("input `{}` has not been set", name),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.outputs = self.model.run(inputs)?;
+        Ok(())
+    }
+
+    fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError> {
+        let output = self
+            .outputs
+            .get(index as usize)
+            .ok_or_else(|| anyhow!("no output {}; has compute been called?", index))?;
+        let size = output.data.len() * 4;
+        if size > destination.len() {
+            return Err(BackendError::NotEnoughMemory(size));
+        }
+
+        // Copy the tensor data into the destination buffer.
+        for (chunk, value) in destination.chunks_mut(4).zip(&output.data) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        Ok(size as u32)
+    }
+}
@@ -0,0 +1,519 @@
+//! Decodes the parts of an ONNX model, a `ModelProto` protobuf message, which
+//! the reference backend needs to run it.
+//!
+//! Only the fields listed here are read; everything else, including the
+//! model's metadata and the types of its inputs and outputs, is skipped.
+
+use super::ops::Array;
+use anyhow::{anyhow, bail, Context, Result};
+use std::convert::{TryFrom, TryInto};
+
+/// A model, ready to run.
+#[derive(Debug)]
+pub struct Model {
+    /// The version of the default operator set the model was exported with.
+    pub opset: i64,
+    pub nodes: Vec<Node>,
+    /// Constant tensors, such as weights, by name.
+    pub initializers: Vec<(String, Array)>,
+    /// The names of the tensors the guest provides, in order.
+    pub inputs: Vec<String>,
+    /// The names of the tensors the guest retrieves, in order.
+    pub outputs: Vec<String>,
+}
+
+/// An operation, which computes its `outputs` from its `inputs`.
+#[derive(Debug)]
+pub struct Node {
+    pub op_type: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub attributes: Vec<(String, Attribute)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Attribute {
+    Float(f32),
+    Int(i64),
+    String(Vec<u8>),
+    Tensor(Array),
+    Floats(Vec<f32>),
+    Ints(Vec<i64>),
+}
+
+impl Node {
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+    }
+
+    pub fn int(&self, name: &str, default: i64) -> Result<i64> {
+        match self.attribute(name) {
+            None => Ok(default),
+            Some(Attribute::Int(i)) => Ok(*i),
+            Some(a) => bail!(
+                "attribute {} of {} is not an int: {:?}",
+                name,
+                self.op_type,
+                a
+            ),
+        }
+    }
+
+    pub fn float(&self, name: &str, default: f32) -> Result<f32> {
+        match self.attribute(name) {
+            None => Ok(default),
+            Some(Attribute::Float(f)) => Ok(*f),
+            Some(a) => bail!(
+                "attribute {} of {} is not a float: {:?}",
+                name,
+                self.op_type,
+                a
+            ),
+        }
+    }
+
+    pub fn ints(&self, name: &str) -> Result<Option<&[i64]>> {
+        match self.attribute(name) {
+            None => Ok(None),
+            Some(Attribute::Ints(i)) => Ok(Some(i)),
+            Some(a) => bail!(
+                "attribute {} of {} is not ints: {:?}",
+                name,
+                self.op_type,
+                a
+            ),
+        }
+    }
+}
+
+impl Model {
+    /// Decodes a serialized `ModelProto`.
+    pub fn parse(bytes: &[u8]) -> Result<Model> {
+        let mut opset = None;
+        let mut graph = None;
+        for field in Fields(bytes) {
+            match field? {
+                (7, Value::Bytes(b)) => graph = Some(b),
+                (8, Value::Bytes(b)) => {
+                    let (domain, version) = parse_opset(b)?;
+                    if domain.is_empty() || domain == "ai.onnx" {
+                        opset = Some(version);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let graph = graph.ok_or_else(|| anyhow!("model has no graph"))?;
+
+        let mut model = Model {
+            opset: opset.ok_or_else(|| anyhow!("model does not import the default opset"))?,
+            nodes: Vec::new(),
+            initializers: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+        let mut inputs = Vec::new();
+        for field in Fields(graph) {
+            match field? {
+                (1, Value::Bytes(b)) => model.nodes.push(parse_node(b)?),
+                (5, Value::Bytes(b)) => model.initializers.push(parse_tensor(b)?),
+                (11, Value::Bytes(b)) => inputs.push(parse_value_info(b)?),
+                (12, Value::Bytes(b)) => model.outputs.push(parse_value_info(b)?),
+                _ => {}
+            }
+        }
+        // Older models list their initializers among their inputs too.
+        model.inputs = inputs
+            .into_iter()
+            .filter(|i| !model.initializers.iter().any(|(n, _)| n == i))
+            .collect();
+        Ok(model)
+    }
+}
+
+fn parse_opset(bytes: &[u8]) -> Result<(String, i64)> {
+    let mut domain = String::new();
+    let mut version = 0;
+    for field in Fields(bytes) {
+        match field? {
+            (1, Value::Bytes(b)) => domain = string(b)?,
+            (2, Value::Varint(v)) => version = v as i64,
+            _ => {}
+        }
+    }
+    Ok((domain, version))
+}
+
+fn parse_value_info(bytes: &[u8]) -> Result<String> {
+    for field in Fields(bytes) {
+        if let (1, Value::Bytes(b)) = field? {
+            return string(b);
+        }
+    }
+    bail!("value has no name")
+}
+
+fn parse_node(bytes: &[u8]) -> Result<Node> {
+    let mut node = Node {
+        op_type: String::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        attributes: Vec::new(),
+    };
+    let mut domain = String::new();
+    for field in Fields(bytes) {
+        match field? {
+            (1, Value::Bytes(b)) => node.inputs.push(string(b)?),
+            (2, Value::Bytes(b)) => node.outputs.push(string(b)?),
+            (4, Value::Bytes(b)) => node.op_type = string(b)?,
+            (5, Value::Bytes(b)) => node.attributes.push(parse_attribute(b)?),
+            (7, Value::Bytes(b)) => domain = string(b)?,
+            _ => {}
+        }
+    }
+    if !domain.is_empty() && domain != "ai.onnx" {
+        bail!(
+            "operator {} of domain {} is not supported",
+            node.op_type,
+            domain
+        );
+    }
+    Ok(node)
+}
+
+fn parse_attribute(bytes: &[u8]) -> Result<(String, Attribute)> {
+    let mut name = String::new();
+    let mut ty = 0;
+    let mut float = None;
+    let mut int = None;
+    let mut string_ = None;
+    let mut tensor = None;
+    let mut floats = Vec::new();
+    let mut ints = Vec::new();
+    for field in Fields(bytes) {
+        match field? {
+            (1, Value::Bytes(b)) => name = string(b)?,
+            (2, Value::Fixed32(f)) => float = Some(f32::from_bits(f)),
+            (3, Value::Varint(i)) => int = Some(i as i64),
+            (4, Value::Bytes(b)) => string_ = Some(b.to_vec()),
+            (5, Value::Bytes(b)) => tensor = Some(parse_tensor(b)?.1),
+            (7, Value::Fixed32(f)) => floats.push(f32::from_bits(f)),
+            (7, Value::Bytes(b)) => floats.extend(packed_fixed32(b)?.map(f32::from_bits)),
+            (8, Value::Varint(i)) => ints.push(i as i64),
+            (8, Value::Bytes(b)) => {
+                for i in packed_varints(b) {
+                    ints.push(i? as i64);
+                }
+            }
+            (20, Value::Varint(t)) => ty = t,
+            _ => {}
+        }
+    }
+    // The type is authoritative if it's there; older models leave it out.
+    let attribute = match (ty, float, int, string_, tensor) {
+        (1, Some(f), ..) | (0, Some(f), ..) => Attribute::Float(f),
+        (2, _, Some(i), ..) | (0, _, Some(i), ..) => Attribute::Int(i),
+        (3, _, _, Some(s), _) | (0, _, _, Some(s), _) => Attribute::String(s),
+        (4, _, _, _, Some(t)) | (0, _, _, _, Some(t)) => Attribute::Tensor(t),
+        (6, ..) => Attribute::Floats(floats),
+        (7, ..) => Attribute::Ints(ints),
+        (0, ..) if !floats.is_empty() => Attribute::Floats(floats),
+        (0, ..) if !ints.is_empty() => Attribute::Ints(ints),
+        _ => bail!("attribute {} has an unsupported type {}", name, ty),
+    };
+    Ok((name, attribute))
+}
+
+// The `TensorProto.DataType`s which can be read.
+const FLOAT: u64 = 1;
+const INT32: u64 = 6;
+const INT64: u64 = 7;
+const DOUBLE: u64 = 11;
+
+/// Decodes a `TensorProto`. Integer and double tensors are converted to
+/// `f32`, which is exact for the shapes and axes they usually hold.
+fn parse_tensor(bytes: &[u8]) -> Result<(String, Array)> {
+    let mut name = String::new();
+    let mut shape = Vec::new();
+    let mut data_type = 0;
+    let mut data = Vec::new();
+    let mut raw = None;
+    for field in Fields(bytes) {
+        match field? {
+            (1, Value::Varint(d)) => shape.push(d as usize),
+            (1, Value::Bytes(b)) => {
+                for d in packed_varints(b) {
+                    shape.push(d? as usize);
+                }
+            }
+            (2, Value::Varint(t)) => data_type = t,
+            (4, Value::Fixed32(f)) => data.push(f32::from_bits(f)),
+            (4, Value::Bytes(b)) => data.extend(packed_fixed32(b)?.map(f32::from_bits)),
+            (5, Value::Varint(i)) | (7, Value::Varint(i)) => data.push(i as i64 as f32),
+            (5, Value::Bytes(b)) | (7, Value::Bytes(b)) => {
+                for i in packed_varints(b) {
+                    data.push(i? as i64 as f32);
+                }
+            }
+            (8, Value::Bytes(b)) => name = string(b)?,
+            (9, Value::Bytes(b)) => raw = Some(b),
+            (10, Value::Fixed64(f)) => data.push(f64::from_bits(f) as f32),
+            (10, Value::Bytes(b)) => {
+                data.extend(packed_fixed64(b)?.map(|f| f64::from_bits(f) as f32))
+            }
+            _ => {}
+        }
+    }
+    if let Some(raw) = raw {
+        data = match data_type {
+            FLOAT => raw
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+            INT32 => raw
+                .chunks_exact(4)
+                .map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32)
+                .collect(),
+            INT64 => raw
+                .chunks_exact(8)
+                .map(|c| i64::from_le_bytes(<[u8; 8]>::try_from(c).unwrap()) as f32)
+                .collect(),
+            DOUBLE => raw
+                .chunks_exact(8)
+                .map(|c| f64::from_le_bytes(<[u8; 8]>::try_from(c).unwrap()) as f32)
+                .collect(),
+            t => bail!("tensor {} has an unsupported data type {}", name, t),
+        };
+    } else if !matches!(data_type, FLOAT | INT32 | INT64 | DOUBLE) {
+        bail!("tensor {} has an unsupported data type {}", name, data_type);
+    }
+    let array = Array::new(shape, data).with_context(|| format!("tensor {}", name))?;
+    Ok((name, array))
+}
+
+fn string(bytes: &[u8]) -> Result<String> {
+    Ok(std::str::from_utf8(bytes)
+        .context("string is not UTF-8")?
+        .to_owned())
+}
+
+/// A field of a protobuf message, by wire type. Groups are not supported.
+#[derive(Debug)]
+enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+/// Iterates over the fields of a protobuf message.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("message is truncated");
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn field(&mut self) -> Result<(u32, Value<'a>)> {
+        let key = varint(&mut self.0)?;
+        let value = match key & 7 {
+            0 => Value::Varint(varint(&mut self.0)?),
+            1 => Value::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            2 => {
+                let len = varint(&mut self.0)?;
+                Value::Bytes(self.take(usize::try_from(len)?)?)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+            t => bail!("unsupported wire type {}", t),
+        };
+        Ok(((key >> 3) as u32, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u32, Value<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let field = self.field();
+        if field.is_err() {
+            // Stop after an error, rather than reading garbage.
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+fn varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("message is truncated"))?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("varint is too long")
+}
+
+fn packed_varints(mut bytes: &[u8]) -> impl Iterator<Item = Result<u64>> + '_ {
+    std::iter::from_fn(move || {
+        if bytes.is_empty() {
+            None
+        } else {
+            Some(varint(&mut bytes))
+        }
+    })
+}
+
+fn packed_fixed32(bytes: &[u8]) -> Result<impl Iterator<Item = u32> + '_> {
+    if bytes.len() % 4 != 0 {
+        bail!("packed field is truncated");
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+}
+
+fn packed_fixed64(bytes: &[u8]) -> Result<impl Iterator<Item = u64> + '_> {
+    if bytes.len() % 8 != 0 {
+        bail!("packed field is truncated");
+    }
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(<[u8; 8]>::try_from(c).unwrap())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Just enough of a protobuf encoder to write a model.
+
+    fn key(field: u64, wire_type: u64, out: &mut Vec<u8>) {
+        encode_varint(field << 3 | wire_type, out);
+    }
+
+    fn encode_varint(mut v: u64, out: &mut Vec<u8>) {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn bytes(field: u64, b: &[u8], out: &mut Vec<u8>) {
+        key(field, 2, out);
+        encode_varint(b.len() as u64, out);
+        out.extend_from_slice(b);
+    }
+
+    fn int(field: u64, v: u64, out: &mut Vec<u8>) {
+        key(field, 0, out);
+        encode_varint(v, out);
+    }
+
+    fn tensor(name: &str, shape: &[u64], data: &[f32]) -> Vec<u8> {
+        let mut t = Vec::new();
+        for d in shape {
+            int(1, *d, &mut t);
+        }
+        int(2, FLOAT, &mut t);
+        bytes(8, name.as_bytes(), &mut t);
+        let raw: Vec<u8> = data.iter().flat_map(|f| f.to_le_bytes().to_vec()).collect();
+        bytes(9, &raw, &mut t);
+        t
+    }
+
+    fn node(op_type: &str, inputs: &[&str], outputs: &[&str], attrs: &[Vec<u8>]) -> Vec<u8> {
+        let mut n = Vec::new();
+        for i in inputs {
+            bytes(1, i.as_bytes(), &mut n);
+        }
+        for o in outputs {
+            bytes(2, o.as_bytes(), &mut n);
+        }
+        bytes(4, op_type.as_bytes(), &mut n);
+        for a in attrs {
+            bytes(5, a, &mut n);
+        }
+        n
+    }
+
+    fn value_info(name: &str) -> Vec<u8> {
+        let mut v = Vec::new();
+        bytes(1, name.as_bytes(), &mut v);
+        v
+    }
+
+    /// A single layer perceptron: `y = relu(x * W^T + b)`.
+    fn perceptron() -> Vec<u8> {
+        let mut trans_b = Vec::new();
+        bytes(1, b"transB", &mut trans_b);
+        int(3, 1, &mut trans_b);
+        int(20, 2, &mut trans_b);
+
+        let mut graph = Vec::new();
+        bytes(
+            1,
+            &node("Gemm", &["x", "W", "b"], &["h"], &[trans_b]),
+            &mut graph,
+        );
+        bytes(1, &node("Relu", &["h"], &["y"], &[]), &mut graph);
+        bytes(2, b"perceptron", &mut graph);
+        bytes(
+            5,
+            &tensor("W", &[2, 3], &[1., 0., -1., 0., 1., 1.]),
+            &mut graph,
+        );
+        bytes(5, &tensor("b", &[2], &[0.5, -10.]), &mut graph);
+        // Like older exporters, list the initializers among the inputs.
+        bytes(11, &value_info("x"), &mut graph);
+        bytes(11, &value_info("W"), &mut graph);
+        bytes(11, &value_info("b"), &mut graph);
+        bytes(12, &value_info("y"), &mut graph);
+
+        let mut opset = Vec::new();
+        int(2, 13, &mut opset);
+
+        let mut model = Vec::new();
+        int(1, 7, &mut model);
+        bytes(2, b"test", &mut model);
+        bytes(7, &graph, &mut model);
+        bytes(8, &opset, &mut model);
+        model
+    }
+
+    #[test]
+    fn parse_and_run() {
+        let model = Model::parse(&perceptron()).unwrap();
+        assert_eq!(model.opset, 13);
+        assert_eq!(model.inputs, ["x"]);
+        assert_eq!(model.outputs, ["y"]);
+        assert_eq!(model.nodes.len(), 2);
+
+        let x = Array::new(vec![2, 3], vec![1., 2., 3., 4., 5., 6.]).unwrap();
+        let y = model.run(vec![x]).unwrap();
+        assert_eq!(y, [Array::new(vec![2, 2], vec![0., 0., 0., 1.]).unwrap()]);
+    }
+
+    #[test]
+    fn truncated() {
+        let model = perceptron();
+        let err = Model::parse(&model[..model.len() - 3]).unwrap_err();
+        assert_eq!(err.to_string(), "message is truncated");
+    }
+}
@@ -0,0 +1,484 @@
+//! Evaluates the graph of a [`Model`] on the CPU, one operator at a time.
+//!
+//! All tensors are dense arrays of `f32`. The operators supported are enough
+//! for small multilayer perceptrons and similar models: elementwise
+//! arithmetic with broadcasting, `MatMul`, `Gemm`, common activations,
+//! `Softmax`, and the reshaping operators which usually surround them.
+
+use super::model::{Attribute, Model, Node};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::collections::HashMap;
+
+/// A dense, row-major tensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Array {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+impl Array {
+    pub fn new(shape: Vec<usize>, data: Vec<f32>) -> Result<Array> {
+        let len: usize = shape.iter().product();
+        ensure!(
+            len == data.len(),
+            "shape {:?} needs {} elements, but there are {}",
+            shape,
+            len,
+            data.len()
+        );
+        Ok(Array { shape, data })
+    }
+
+    fn map(&self, f: impl Fn(f32) -> f32) -> Array {
+        Array {
+            shape: self.shape.clone(),
+            data: self.data.iter().map(|x| f(*x)).collect(),
+        }
+    }
+
+    /// Returns the values of a tensor of integers, such as a shape.
+    fn ints(&self) -> Vec<i64> {
+        self.data.iter().map(|x| *x as i64).collect()
+    }
+}
+
+impl Model {
+    /// Runs the model on `inputs`, given in the order of `Model::inputs`,
+    /// and returns its outputs in the order of `Model::outputs`.
+    pub fn run(&self, inputs: Vec<Array>) -> Result<Vec<Array>> {
+        ensure!(
+            inputs.len() == self.inputs.len(),
+            "the model has {} inputs, but {} were given",
+            self.inputs.len(),
+            inputs.len()
+        );
+        let mut values: HashMap<&str, Array> = self
+            .initializers
+            .iter()
+            .map(|(name, array)| (name.as_str(), array.clone()))
+            .collect();
+        for (name, array) in self.inputs.iter().zip(inputs) {
+            values.insert(name, array);
+        }
+
+        // Nodes are topologically sorted, so each one's inputs are ready by
+        // the time it runs.
+        for node in &self.nodes {
+            let inputs = node
+                .inputs
+                .iter()
+                .map(|name| match name.as_str() {
+                    // An empty name is an optional input which was left out.
+                    "" => Ok(None),
+                    name => values
+                        .get(name)
+                        .map(Some)
+                        .ok_or_else(|| anyhow!("{} has no value", name)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let outputs = eval(node, &inputs, self.opset)
+                .with_context(|| format!("failed to evaluate {}", node.op_type))?;
+            for (name, output) in node.outputs.iter().zip(outputs) {
+                values.insert(name, output);
+            }
+        }
+
+        self.outputs
+            .iter()
+            .map(|name| {
+                values
+                    .remove(name.as_str())
+                    .ok_or_else(|| anyhow!("output {} was not computed", name))
+            })
+            .collect()
+    }
+}
+
+fn eval(node: &Node, inputs: &[Option<&Array>], opset: i64) -> Result<Vec<Array>> {
+    let input = |i: usize| -> Result<&Array> {
+        inputs
+            .get(i)
+            .copied()
+            .flatten()
+            .ok_or_else(|| anyhow!("input {} is missing", i))
+    };
+    let output = match node.op_type.as_str() {
+        "Add" => broadcast(input(0)?, input(1)?, |a, b| a + b)?,
+        "Sub" => broadcast(input(0)?, input(1)?, |a, b| a - b)?,
+        "Mul" => broadcast(input(0)?, input(1)?, |a, b| a * b)?,
+        "Div" => broadcast(input(0)?, input(1)?, |a, b| a / b)?,
+        "Relu" => input(0)?.map(|x| x.max(0.0)),
+        "Sigmoid" => input(0)?.map(|x| 1.0 / (1.0 + (-x).exp())),
+        "Tanh" => input(0)?.map(f32::tanh),
+        "Exp" => input(0)?.map(f32::exp),
+        "Neg" => input(0)?.map(|x| -x),
+        "Identity" | "Dropout" => input(0)?.clone(),
+        "MatMul" => matmul(input(0)?, input(1)?)?,
+        "Gemm" => gemm(node, input(0)?, input(1)?, inputs.get(2).copied().flatten())?,
+        "Softmax" => softmax(node, input(0)?, opset)?,
+        "Flatten" => {
+            let x = input(0)?;
+            // Unlike other axes, this can be one past the last.
+            let rank = x.shape.len() as i64;
+            let axis = match node.int("axis", 1)? {
+                a if a < 0 => a + rank,
+                a => a,
+            };
+            ensure!(0 <= axis && axis <= rank, "axis {} is out of range", axis);
+            let axis = axis as usize;
+            let shape = vec![
+                x.shape[..axis].iter().product(),
+                x.shape[axis..].iter().product(),
+            ];
+            Array::new(shape, x.data.clone())?
+        }
+        "Reshape" => reshape(input(0)?, &input(1)?.ints())?,
+        "Transpose" => transpose(node, input(0)?)?,
+        "Constant" => match node.attribute("value") {
+            Some(Attribute::Tensor(t)) => t.clone(),
+            _ => bail!("only tensor constants are supported"),
+        },
+        op => bail!("operator {} is not supported", op),
+    };
+    Ok(vec![output])
+}
+
+/// Resolves a possibly negative `axis` of a tensor of rank `rank`.
+fn axis(axis: i64, rank: usize) -> Result<usize> {
+    let resolved = if axis < 0 { axis + rank as i64 } else { axis };
+    ensure!(
+        0 <= resolved && resolved < rank as i64,
+        "axis {} is out of range for rank {}",
+        axis,
+        rank
+    );
+    Ok(resolved as usize)
+}
+
+/// Returns the strides of a row-major array of `shape`.
+fn strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for i in (0..shape.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+    strides
+}
+
+/// Applies `f` elementwise, broadcasting like numpy.
+fn broadcast(a: &Array, b: &Array, f: impl Fn(f32, f32) -> f32) -> Result<Array> {
+    let rank = a.shape.len().max(b.shape.len());
+    // Pads a shape with leading 1s to `rank`.
+    let pad = |shape: &[usize]| {
+        let mut padded = vec![1; rank - shape.len()];
+        padded.extend_from_slice(shape);
+        padded
+    };
+    let (a_shape, b_shape) = (pad(&a.shape), pad(&b.shape));
+    let mut shape = Vec::with_capacity(rank);
+    for (&x, &y) in a_shape.iter().zip(&b_shape) {
+        ensure!(
+            x == y || x == 1 || y == 1,
+            "shapes {:?} and {:?} can't be broadcast",
+            a.shape,
+            b.shape
+        );
+        shape.push(x.max(y));
+    }
+    // A broadcast dimension has a stride of 0, so it repeats.
+    let stride = |shape: &[usize]| {
+        strides(shape)
+            .into_iter()
+            .zip(shape)
+            .map(|(s, &d)| if d == 1 { 0 } else { s })
+            .collect::<Vec<_>>()
+    };
+    let (a_strides, b_strides) = (stride(&a_shape), stride(&b_shape));
+    let out_strides = strides(&shape);
+    let len = shape.iter().product();
+    let mut data = Vec::with_capacity(len);
+    for i in 0..len {
+        let (mut ai, mut bi) = (0, 0);
+        for d in 0..rank {
+            let index = i / out_strides[d] % shape[d];
+            ai += index * a_strides[d];
+            bi += index * b_strides[d];
+        }
+        data.push(f(a.data[ai], b.data[bi]));
+    }
+    Array::new(shape, data)
+}
+
+/// Multiplies matrices, or stacks of matrices, of shapes `[.., m, k]` and
+/// `[.., k, n]`. A rank 2 operand is multiplied with every matrix of the
+/// other.
+fn matmul(a: &Array, b: &Array) -> Result<Array> {
+    ensure!(
+        a.shape.len() >= 2 && b.shape.len() >= 2,
+        "operands of rank 1 are not supported"
+    );
+    let (m, k) = (a.shape[a.shape.len() - 2], a.shape[a.shape.len() - 1]);
+    let (k2, n) = (b.shape[b.shape.len() - 2], b.shape[b.shape.len() - 1]);
+    ensure!(
+        k == k2,
+        "shapes {:?} and {:?} can't be multiplied",
+        a.shape,
+        b.shape
+    );
+    let a_batch = &a.shape[..a.shape.len() - 2];
+    let b_batch = &b.shape[..b.shape.len() - 2];
+    let batch = match (a_batch.is_empty(), b_batch.is_empty()) {
+        (_, true) => a_batch,
+        (true, false) => b_batch,
+        (false, false) if a_batch == b_batch => a_batch,
+        _ => bail!("shapes {:?} and {:?} can't be multiplied", a.shape, b.shape),
+    };
+    let count: usize = batch.iter().product();
+    let mut data = vec![0.0; count * m * n];
+    for batch_index in 0..count {
+        let a_offset = if a_batch.is_empty() {
+            0
+        } else {
+            batch_index * m * k
+        };
+        let b_offset = if b_batch.is_empty() {
+            0
+        } else {
+            batch_index * k * n
+        };
+        let out = &mut data[batch_index * m * n..][..m * n];
+        for i in 0..m {
+            for p in 0..k {
+                let x = a.data[a_offset + i * k + p];
+                for j in 0..n {
+                    out[i * n + j] += x * b.data[b_offset + p * n + j];
+                }
+            }
+        }
+    }
+    let mut shape = batch.to_vec();
+    shape.extend_from_slice(&[m, n]);
+    Array::new(shape, data)
+}
+
+/// Computes `alpha * A' * B' + beta * C`, where `A'` and `B'` are `A` and
+/// `B`, transposed if `transA` and `transB` are set.
+fn gemm(node: &Node, a: &Array, b: &Array, c: Option<&Array>) -> Result<Array> {
+    let alpha = node.float("alpha", 1.0)?;
+    let beta = node.float("beta", 1.0)?;
+    let trans = |array: &Array, attr: &str| -> Result<Array> {
+        ensure!(array.shape.len() == 2, "operands must be matrices");
+        Ok(if node.int(attr, 0)? != 0 {
+            transpose_axes(array, &[1, 0])
+        } else {
+            array.clone()
+        })
+    };
+    let product = matmul(&trans(a, "transA")?, &trans(b, "transB")?)?;
+    let product = product.map(|x| alpha * x);
+    match c {
+        Some(c) => broadcast(&product, c, |x, y| x + beta * y),
+        None => Ok(product),
+    }
+}
+
+fn softmax(node: &Node, x: &Array, opset: i64) -> Result<Array> {
+    let rank = x.shape.len();
+    let mut out = x.clone();
+    if opset < 13 {
+        // Before opset 13, softmax is taken over everything from `axis` on,
+        // as though the input were flattened to a matrix there.
+        let axis = axis(node.int("axis", 1)?, rank)?;
+        let row: usize = x.shape[axis..].iter().product();
+        for chunk in out.data.chunks_mut(row.max(1)) {
+            softmax_in_place(chunk, 1);
+        }
+    } else {
+        let axis = axis(node.int("axis", -1)?, rank)?;
+        let stride = strides(&x.shape)[axis];
+        let len = x.shape[axis];
+        for chunk in out.data.chunks_mut((stride * len).max(1)) {
+            for offset in 0..stride {
+                softmax_in_place(&mut chunk[offset..], stride);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Replaces every `stride`th element of `data` with its softmax.
+fn softmax_in_place(data: &mut [f32], stride: usize) {
+    let max = data
+        .iter()
+        .step_by(stride)
+        .fold(f32::NEG_INFINITY, |m, x| m.max(*x));
+    let mut sum = 0.0;
+    for x in data.iter_mut().step_by(stride) {
+        *x = (*x - max).exp();
+        sum += *x;
+    }
+    for x in data.iter_mut().step_by(stride) {
+        *x /= sum;
+    }
+}
+
+fn reshape(x: &Array, shape: &[i64]) -> Result<Array> {
+    let mut resolved = Vec::with_capacity(shape.len());
+    let mut inferred = None;
+    for (i, &d) in shape.iter().enumerate() {
+        match d {
+            0 => resolved.push(
+                *x.shape
+                    .get(i)
+                    .ok_or_else(|| anyhow!("dimension {} can't be copied", i))?,
+            ),
+            -1 if inferred.is_none() => {
+                inferred = Some(i);
+                resolved.push(1);
+            }
+            d if d > 0 => resolved.push(d as usize),
+            d => bail!("invalid dimension {} in shape {:?}", d, shape),
+        }
+    }
+    if let Some(i) = inferred {
+        let known: usize = resolved.iter().product();
+        ensure!(
+            known != 0 && x.data.len() % known == 0,
+            "can't reshape {:?} to {:?}",
+            x.shape,
+            shape
+        );
+        resolved[i] = x.data.len() / known;
+    }
+    Array::new(resolved, x.data.clone())
+}
+
+fn transpose(node: &Node, x: &Array) -> Result<Array> {
+    let rank = x.shape.len();
+    let perm = match node.ints("perm")? {
+        Some(perm) => perm
+            .iter()
+            .map(|&p| axis(p, rank))
+            .collect::<Result<Vec<_>>>()?,
+        None => (0..rank).rev().collect(),
+    };
+    let mut sorted = perm.clone();
+    sorted.sort_unstable();
+    ensure!(
+        sorted == (0..rank).collect::<Vec<_>>(),
+        "{:?} is not a permutation",
+        perm
+    );
+    Ok(transpose_axes(x, &perm))
+}
+
+/// Permutes the axes of `x`, so that axis `i` of the result is axis
+/// `perm[i]` of `x`.
+fn transpose_axes(x: &Array, perm: &[usize]) -> Array {
+    let in_strides = strides(&x.shape);
+    let shape: Vec<usize> = perm.iter().map(|&p| x.shape[p]).collect();
+    let out_strides = strides(&shape);
+    let data = (0..x.data.len())
+        .map(|i| {
+            let index: usize = (0..shape.len())
+                .map(|d| (i / out_strides[d] % shape[d]) * in_strides[perm[d]])
+                .sum();
+            x.data[index]
+        })
+        .collect();
+    Array { shape, data }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn array(shape: &[usize], data: &[f32]) -> Array {
+        Array::new(shape.to_vec(), data.to_vec()).unwrap()
+    }
+
+    fn node(op_type: &str, attributes: Vec<(&str, Attribute)>) -> Node {
+        Node {
+            op_type: op_type.to_owned(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            attributes: attributes
+                .into_iter()
+                .map(|(n, a)| (n.to_owned(), a))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn broadcasting() {
+        let a = array(&[2, 3], &[1., 2., 3., 4., 5., 6.]);
+        let row = array(&[3], &[10., 20., 30.]);
+        let column = array(&[2, 1], &[100., 200.]);
+        assert_eq!(
+            broadcast(&a, &row, |x, y| x + y).unwrap(),
+            array(&[2, 3], &[11., 22., 33., 14., 25., 36.])
+        );
+        assert_eq!(
+            broadcast(&column, &a, |x, y| x - y).unwrap(),
+            array(&[2, 3], &[99., 98., 97., 196., 195., 194.])
+        );
+        assert!(broadcast(&a, &array(&[2], &[1., 2.]), |x, y| x + y).is_err());
+    }
+
+    #[test]
+    fn matrices() {
+        let a = array(&[2, 3], &[1., 2., 3., 4., 5., 6.]);
+        let b = array(&[3, 2], &[7., 8., 9., 10., 11., 12.]);
+        assert_eq!(
+            matmul(&a, &b).unwrap(),
+            array(&[2, 2], &[58., 64., 139., 154.])
+        );
+
+        // Y = 2 * A * B^T + C
+        let gemm_node = node(
+            "Gemm",
+            vec![
+                ("alpha", Attribute::Float(2.)),
+                ("transB", Attribute::Int(1)),
+            ],
+        );
+        let b_t = transpose_axes(&b, &[1, 0]);
+        let c = array(&[2], &[1., -1.]);
+        assert_eq!(
+            gemm(&gemm_node, &a, &b_t, Some(&c)).unwrap(),
+            array(&[2, 2], &[117., 127., 279., 307.])
+        );
+    }
+
+    #[test]
+    fn softmax_axes() {
+        let x = array(&[2, 2], &[0., 0., 1., 1.]);
+        let opset_13 = softmax(&node("Softmax", vec![("axis", Attribute::Int(0))]), &x, 13);
+        let e = 1f32.exp();
+        let expected = [1. / (1. + e), 1. / (1. + e), e / (1. + e), e / (1. + e)];
+        for (x, y) in opset_13.unwrap().data.iter().zip(&expected) {
+            assert!((x - y).abs() < 1e-6);
+        }
+
+        // Before opset 13, axis 0 means the whole tensor.
+        let opset_11 = softmax(&node("Softmax", vec![("axis", Attribute::Int(0))]), &x, 11);
+        let sum = 2. + 2. * e;
+        let expected = [1. / sum, 1. / sum, e / sum, e / sum];
+        for (x, y) in opset_11.unwrap().data.iter().zip(&expected) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn reshaping() {
+        let x = array(&[2, 3, 4], &[0.; 24]);
+        assert_eq!(reshape(&x, &[0, -1]).unwrap().shape, [2, 12]);
+        assert_eq!(reshape(&x, &[4, 0, 2]).unwrap().shape, [4, 3, 2]);
+        assert!(reshape(&x, &[5, -1]).is_err());
+
+        let x = array(&[2, 3], &[1., 2., 3., 4., 5., 6.]);
+        assert_eq!(
+            transpose(&node("Transpose", vec![]), &x).unwrap(),
+            array(&[3, 2], &[1., 4., 2., 5., 3., 6.])
+        );
+    }
+}
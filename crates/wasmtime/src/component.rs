@@ -0,0 +1,293 @@
+//! Canonical ABI support for exchanging high-level values with wasm.
+//!
+//! Core wasm functions can only pass integers, floats and references across
+//! the host boundary. This module implements the lifting and lowering rules of
+//! the component model's canonical ABI so that values such as strings, lists,
+//! records, variants and options can be exchanged between the host and a core
+//! wasm module's linear memory as plain Rust types.
+//!
+//! The main entry points are:
+//!
+//! * [`ComponentType`], [`Lift`] and [`Lower`] - traits describing how a Rust
+//!   type is represented in the canonical ABI. These are implemented for
+//!   primitives, `String`, `Vec<T>`, `Option<T>`, `Result<T, E>` and tuples,
+//!   and can be derived for structs (records) and enums (variants).
+//!
+//! * [`TypedFunc`] - a wasm export which is called with lowered arguments and
+//!   whose results are lifted back into Rust values.
+//!
+//! * [`Func::wrap_component`](crate::Func::wrap_component) and
+//!   [`Linker::func_wrap_component`](crate::Linker::func_wrap_component) -
+//!   host functions whose arguments are lifted out of the calling instance and
+//!   whose results are lowered back into it.
+//!
+//! Values which live in linear memory require the module to export its memory
+//! and, for values which the host writes into it, a `cabi_realloc` function
+//! with the signature `(func (param i32 i32 i32 i32) (result i32))` taking the
+//! original pointer, original size, alignment and new size of an allocation.
+//!
+//! Only the UTF-8 string encoding and 32-bit memories are supported.
+//!
+//! # Deriving
+//!
+//! ```
+//! use wasmtime::component::{ComponentType, Lift, Lower};
+//!
+//! #[derive(ComponentType, Lift, Lower)]
+//! struct Person {
+//!     name: String,
+//!     age: u32,
+//! }
+//!
+//! #[derive(ComponentType, Lift, Lower)]
+//! enum Shape {
+//!     Circle(f32),
+//!     Rectangle((f32, f32)),
+//!     Empty,
+//! }
+//! ```
+//!
+//! Structs are represented as records of their fields, in declaration order.
+//! Enums are represented as variants whose cases are the enum's variants, in
+//! declaration order, each of which may carry at most one unnamed payload.
+
+use crate::{AsContextMut, Caller, Extern, Instance, Memory, StoreContext, StoreContextMut};
+use crate::{Func, TypedFunc as CoreTypedFunc};
+use anyhow::{bail, Result};
+
+mod func;
+mod typed;
+
+pub use self::func::TypedFunc;
+pub use self::typed::{ComponentType, Lift, Lower};
+pub use wasmtime_component_macro::{ComponentType, Lift, Lower};
+
+/// The maximum number of core wasm parameters a function's arguments are
+/// flattened into before they're passed indirectly through linear memory.
+pub const MAX_FLAT_PARAMS: usize = 16;
+
+/// The maximum number of core wasm results a function's return value is
+/// flattened into before it's returned indirectly through linear memory.
+pub const MAX_FLAT_RESULTS: usize = 1;
+
+/// The name of the memory export used by [`Options::from_instance`].
+pub const MEMORY_EXPORT: &str = "memory";
+
+/// The name of the allocation function export used by
+/// [`Options::from_instance`].
+pub const REALLOC_EXPORT: &str = "cabi_realloc";
+
+/// The canonical ABI options used when lifting and lowering values, namely
+/// which linear memory values live in and how to allocate space within it.
+#[derive(Clone)]
+pub struct Options {
+    memory: Option<Memory>,
+    realloc: Option<CoreTypedFunc<(i32, i32, i32, i32), i32>>,
+}
+
+impl Options {
+    /// Creates a new set of options with no memory or allocation function.
+    ///
+    /// Such options can only be used to lift and lower values which are
+    /// passed entirely in core wasm values, such as integers or records of
+    /// integers.
+    pub fn new() -> Options {
+        Options {
+            memory: None,
+            realloc: None,
+        }
+    }
+
+    /// Configures the memory that lists and strings are read from and written
+    /// into.
+    pub fn memory(mut self, memory: Memory) -> Options {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Configures the function used to allocate memory for values being
+    /// lowered into wasm.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `realloc` does not have the type
+    /// `(func (param i32 i32 i32 i32) (result i32))`.
+    pub fn realloc(mut self, store: impl AsContextMut, realloc: Func) -> Result<Options> {
+        self.realloc = Some(realloc.typed(store)?);
+        Ok(self)
+    }
+
+    /// Creates options from the `memory` and `cabi_realloc` exports of
+    /// `instance`, if they're present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `cabi_realloc` is exported with the wrong type.
+    pub fn from_instance(mut store: impl AsContextMut, instance: &Instance) -> Result<Options> {
+        let mut options = Options::new();
+        if let Some(memory) = instance.get_memory(&mut store, MEMORY_EXPORT) {
+            options = options.memory(memory);
+        }
+        if let Some(realloc) = instance.get_func(&mut store, REALLOC_EXPORT) {
+            options = options.realloc(&mut store, realloc)?;
+        }
+        Ok(options)
+    }
+
+    /// Creates options from the `memory` and `cabi_realloc` exports of the
+    /// instance calling a host function, if they're present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `cabi_realloc` is exported with the wrong type.
+    pub fn from_caller<T>(caller: &mut Caller<'_, T>) -> Result<Options> {
+        let mut options = Options::new();
+        if let Some(Extern::Memory(memory)) = caller.get_export(MEMORY_EXPORT) {
+            options = options.memory(memory);
+        }
+        if let Some(Extern::Func(realloc)) = caller.get_export(REALLOC_EXPORT) {
+            options = options.realloc(&mut *caller, realloc)?;
+        }
+        Ok(options)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
+
+/// Context used when lifting values out of wasm into Rust.
+pub struct LiftContext<'a> {
+    memory: Option<&'a [u8]>,
+}
+
+impl<'a> LiftContext<'a> {
+    /// Creates a new context reading from the memory configured in `options`.
+    pub fn new<T: 'a>(store: StoreContext<'a, T>, options: &Options) -> LiftContext<'a> {
+        LiftContext {
+            memory: options.memory.map(|memory| memory.data(store)),
+        }
+    }
+
+    /// Returns the contents of the configured linear memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no memory was configured.
+    pub fn memory(&self) -> Result<&'a [u8]> {
+        match self.memory {
+            Some(memory) => Ok(memory),
+            None => bail!("the canonical ABI requires a memory to lift this value"),
+        }
+    }
+
+    /// Returns the `size` bytes at `ptr` in linear memory, checking that `ptr`
+    /// is aligned to `align`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pointer is misaligned or the range is out of
+    /// bounds.
+    pub fn bytes(&self, ptr: usize, size: usize, align: u32) -> Result<&'a [u8]> {
+        let memory = self.memory()?;
+        check_range(memory.len(), ptr, size, align)?;
+        Ok(&memory[ptr..][..size])
+    }
+}
+
+/// Context used when lowering Rust values into wasm.
+pub struct LowerContext<'a, T> {
+    store: StoreContextMut<'a, T>,
+    options: &'a Options,
+}
+
+impl<'a, T> LowerContext<'a, T> {
+    /// Creates a new context writing into the memory configured in `options`.
+    pub fn new(store: StoreContextMut<'a, T>, options: &'a Options) -> LowerContext<'a, T> {
+        LowerContext { store, options }
+    }
+
+    /// Calls the configured `cabi_realloc` function, returning a pointer to
+    /// `new_size` bytes aligned to `align`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no allocation function or memory was configured,
+    /// if the allocation function traps, or if the returned pointer is
+    /// misaligned or out of bounds.
+    pub fn realloc(
+        &mut self,
+        old: usize,
+        old_size: usize,
+        align: u32,
+        new_size: usize,
+    ) -> Result<usize> {
+        let realloc = match &self.options.realloc {
+            Some(realloc) => realloc,
+            None => bail!(
+                "the canonical ABI requires `{}` to lower this value",
+                REALLOC_EXPORT
+            ),
+        };
+        let ptr = realloc.call(
+            &mut self.store,
+            (old as i32, old_size as i32, align as i32, new_size as i32),
+        )? as u32 as usize;
+        let len = self.as_slice_mut()?.len();
+        check_range(len, ptr, new_size, align)?;
+        Ok(ptr)
+    }
+
+    /// Returns the contents of the configured linear memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no memory was configured.
+    pub fn as_slice_mut(&mut self) -> Result<&mut [u8]> {
+        match self.options.memory {
+            Some(memory) => Ok(memory.data_mut(&mut self.store)),
+            None => bail!("the canonical ABI requires a memory to lower this value"),
+        }
+    }
+
+    /// Returns the `size` bytes at `ptr` in linear memory, checking that `ptr`
+    /// is aligned to `align`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pointer is misaligned or the range is out of
+    /// bounds.
+    pub fn bytes_mut(&mut self, ptr: usize, size: usize, align: u32) -> Result<&mut [u8]> {
+        let memory = self.as_slice_mut()?;
+        check_range(memory.len(), ptr, size, align)?;
+        Ok(&mut memory[ptr..][..size])
+    }
+}
+
+fn check_range(len: usize, ptr: usize, size: usize, align: u32) -> Result<()> {
+    if ptr % (align as usize) != 0 {
+        bail!("pointer {:#x} is not aligned to {}", ptr, align);
+    }
+    match ptr.checked_add(size) {
+        Some(end) if end <= len => Ok(()),
+        _ => bail!(
+            "range {:#x}+{:#x} is out of bounds of linear memory of size {:#x}",
+            ptr,
+            size,
+            len
+        ),
+    }
+}
+
+// Support for the derive macros, not public API.
+#[doc(hidden)]
+pub mod __internal {
+    pub use super::typed::{
+        align_to, lift_payload, lift_variant, load_variant, lower_variant, next_field,
+        record_align, record_size, store_variant, variant_align, variant_flatten, variant_size,
+    };
+    pub use crate::ValType;
+    pub use anyhow::{bail, Result};
+}
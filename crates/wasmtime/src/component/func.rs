@@ -0,0 +1,237 @@
+use super::typed::{flat_count, flat_types};
+use super::{Lift, LiftContext, Lower, LowerContext, Options, MAX_FLAT_PARAMS, MAX_FLAT_RESULTS};
+use crate::{AsContext, AsContextMut, Caller, Func, FuncType, Linker, Val, ValType};
+use anyhow::{bail, Result};
+use std::marker;
+
+/// A WebAssembly function whose arguments and results are exchanged using the
+/// canonical ABI.
+///
+/// Calling a `TypedFunc` lowers `Params` into the core wasm parameters of the
+/// underlying function, allocating space in linear memory for strings and
+/// lists as necessary, and then lifts its core wasm results into `Return`.
+pub struct TypedFunc<Params, Return> {
+    _a: marker::PhantomData<fn(Params) -> Return>,
+    func: Func,
+    options: Options,
+}
+
+impl<Params, Return> Clone for TypedFunc<Params, Return> {
+    fn clone(&self) -> TypedFunc<Params, Return> {
+        TypedFunc {
+            _a: marker::PhantomData,
+            func: self.func,
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<Params, Return> TypedFunc<Params, Return>
+where
+    Params: Lower,
+    Return: Lift,
+{
+    /// Creates a new `TypedFunc` calling `func` using the canonical ABI
+    /// `options` specified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the core wasm type of `func` isn't the type that
+    /// `Params` and `Return` are flattened into.
+    pub fn new(store: impl AsContext, func: Func, options: Options) -> Result<Self> {
+        let expected = export_type::<Params, Return>();
+        let actual = func.ty(&store);
+        if actual != expected {
+            bail!(
+                "type mismatch with canonical ABI signature: expected {:?}, found {:?}",
+                expected,
+                actual
+            );
+        }
+        Ok(TypedFunc {
+            _a: marker::PhantomData,
+            func,
+            options,
+        })
+    }
+
+    /// Returns the underlying core wasm [`Func`].
+    pub fn func(&self) -> &Func {
+        &self.func
+    }
+
+    /// Invokes this function with the `params` given, returning the lifted
+    /// result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the function traps, or if any value can't be lifted
+    /// or lowered, for example because a string isn't valid UTF-8 or a pointer
+    /// is out of bounds.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if it is called when the underlying [`Func`] is
+    /// connected to an asynchronous store.
+    pub fn call(&self, mut store: impl AsContextMut, params: Params) -> Result<Return> {
+        let mut store = store.as_context_mut();
+        let mut args = Vec::new();
+        let mut cx = LowerContext::new(store.as_context_mut(), &self.options);
+        if flat_count::<Params>() <= MAX_FLAT_PARAMS {
+            params.lower(&mut cx, &mut args)?;
+        } else {
+            let ptr = cx.realloc(0, 0, Params::ALIGN32, Params::SIZE32)?;
+            params.store(&mut cx, ptr)?;
+            args.push(Val::I32(ptr as i32));
+        }
+
+        let results = self.func.call(&mut store, &args)?;
+
+        let cx = LiftContext::new(store.as_context(), &self.options);
+        if flat_count::<Return>() <= MAX_FLAT_RESULTS {
+            Return::lift(&cx, &mut results.iter())
+        } else {
+            let ptr = results[0].unwrap_i32() as u32 as usize;
+            Return::load(&cx, cx.bytes(ptr, Return::SIZE32, Return::ALIGN32)?)
+        }
+    }
+}
+
+/// The core wasm type of a wasm export taking `Params` and returning `Return`.
+fn export_type<Params: Lower, Return: Lift>() -> FuncType {
+    let mut params = flat_types::<Params>();
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![ValType::I32];
+    }
+    let mut results = flat_types::<Return>();
+    if results.len() > MAX_FLAT_RESULTS {
+        results = vec![ValType::I32];
+    }
+    FuncType::new(params, results)
+}
+
+/// The core wasm type of a host function taking `Params` and returning
+/// `Return`, which writes results that don't fit in core wasm results through
+/// a trailing return pointer parameter.
+fn import_type<Params: Lift, Return: Lower>() -> FuncType {
+    let mut params = flat_types::<Params>();
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![ValType::I32];
+    }
+    let mut results = flat_types::<Return>();
+    if results.len() > MAX_FLAT_RESULTS {
+        params.push(ValType::I32);
+        results = Vec::new();
+    }
+    FuncType::new(params, results)
+}
+
+fn call_host<T, Params, Return>(
+    mut caller: Caller<'_, T>,
+    args: &[Val],
+    results: &mut [Val],
+    func: &(impl Fn(Caller<'_, T>, Params) -> Result<Return> + Send + Sync + 'static),
+) -> Result<()>
+where
+    Params: Lift,
+    Return: Lower,
+{
+    let options = Options::from_caller(&mut caller)?;
+
+    let params = {
+        let cx = LiftContext::new(caller.as_context(), &options);
+        if flat_count::<Params>() <= MAX_FLAT_PARAMS {
+            Params::lift(&cx, &mut args.iter())?
+        } else {
+            let ptr = args[0].unwrap_i32() as u32 as usize;
+            Params::load(&cx, cx.bytes(ptr, Params::SIZE32, Params::ALIGN32)?)?
+        }
+    };
+
+    let ret = func(caller.sub_caller(), params)?;
+
+    let mut cx = LowerContext::new(caller.as_context_mut(), &options);
+    if flat_count::<Return>() <= MAX_FLAT_RESULTS {
+        let mut flat = Vec::new();
+        ret.lower(&mut cx, &mut flat)?;
+        results.clone_from_slice(&flat);
+    } else {
+        let ptr = args[args.len() - 1].unwrap_i32() as u32 as usize;
+        cx.bytes_mut(ptr, Return::SIZE32, Return::ALIGN32)?;
+        ret.store(&mut cx, ptr)?;
+    }
+    Ok(())
+}
+
+impl Func {
+    /// Creates a new host-defined function whose arguments and results are
+    /// exchanged with the calling instance using the canonical ABI.
+    ///
+    /// When called, `Params` is lifted out of the core wasm arguments and the
+    /// caller's exported memory, `func` is invoked, and its return value is
+    /// lowered back into the caller, allocating space for strings and lists
+    /// with the caller's `cabi_realloc` export. See the
+    /// [`component`](crate::component) module for more information.
+    ///
+    /// The core wasm type of the returned function is determined by how
+    /// `Params` and `Return` are flattened. For example a function taking
+    /// `(String,)` and returning `u32` has the type
+    /// `(func (param i32 i32) (result i32))`, and a function taking `()` and
+    /// returning `String` has the type `(func (param i32))` where the
+    /// parameter is a pointer to where the string's pointer and length are
+    /// written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::<()>::default();
+    /// let greet = Func::wrap_component(&mut store, |_caller: Caller<'_, ()>, (name,): (String,)| {
+    ///     Ok(format!("Hello, {}!", name))
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn wrap_component<T, Params, Return>(
+        store: impl AsContextMut<Data = T>,
+        func: impl Fn(Caller<'_, T>, Params) -> Result<Return> + Send + Sync + 'static,
+    ) -> Func
+    where
+        Params: Lift + 'static,
+        Return: Lower + 'static,
+    {
+        Func::new(
+            store,
+            import_type::<Params, Return>(),
+            move |caller, args, results| Ok(call_host(caller, args, results, &func)?),
+        )
+    }
+}
+
+impl<T> Linker<T> {
+    /// Creates a [`Func::wrap_component`]-style function named in this linker.
+    ///
+    /// For more information see [`Linker::func_wrap`].
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn func_wrap_component<Params, Return>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func: impl Fn(Caller<'_, T>, Params) -> Result<Return> + Send + Sync + 'static,
+    ) -> Result<&mut Self>
+    where
+        Params: Lift + 'static,
+        Return: Lower + 'static,
+    {
+        self.func_new(
+            module,
+            name,
+            import_type::<Params, Return>(),
+            move |caller, args, results| Ok(call_host(caller, args, results, &func)?),
+        )
+    }
+}
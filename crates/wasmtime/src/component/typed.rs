@@ -0,0 +1,802 @@
+use super::{LiftContext, LowerContext};
+use crate::{Val, ValType};
+use anyhow::{bail, Result};
+use std::slice;
+
+/// A Rust type which has a representation in the canonical ABI.
+///
+/// This describes how a value is laid out in linear memory and which core
+/// wasm values it's flattened into when passed as a parameter or result. It's
+/// typically implemented alongside [`Lift`] and [`Lower`] through
+/// `#[derive(ComponentType)]`.
+pub trait ComponentType {
+    /// The size, in bytes, of this type when stored in linear memory.
+    const SIZE32: usize;
+
+    /// The alignment, in bytes, of this type when stored in linear memory.
+    const ALIGN32: u32;
+
+    /// Appends the core wasm types this type is flattened into to `dst`.
+    fn flatten(dst: &mut Vec<ValType>);
+}
+
+/// A type which can be lifted out of wasm into a Rust value.
+pub trait Lift: ComponentType + Sized {
+    /// Lifts a value from its flattened core wasm representation, consuming
+    /// as many values from `src` as [`ComponentType::flatten`] produces.
+    fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self>;
+
+    /// Loads a value from its representation in linear memory, where `bytes`
+    /// is exactly [`ComponentType::SIZE32`] bytes long.
+    fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self>;
+}
+
+/// A Rust value which can be lowered into wasm.
+pub trait Lower: ComponentType {
+    /// Lowers this value into its flattened core wasm representation,
+    /// appending to `dst` the values corresponding to the types produced by
+    /// [`ComponentType::flatten`].
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()>;
+
+    /// Stores this value into linear memory at `offset`, which is aligned to
+    /// [`ComponentType::ALIGN32`].
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()>;
+}
+
+/// Returns the number of core wasm values `T` is flattened into.
+pub(crate) fn flat_count<T: ComponentType + ?Sized>() -> usize {
+    flat_types::<T>().len()
+}
+
+pub(crate) fn flat_types<T: ComponentType + ?Sized>() -> Vec<ValType> {
+    let mut dst = Vec::new();
+    T::flatten(&mut dst);
+    dst
+}
+
+fn next<'a>(src: &mut slice::Iter<'a, Val>) -> Result<&'a Val> {
+    match src.next() {
+        Some(val) => Ok(val),
+        None => bail!("too few core wasm values to lift from"),
+    }
+}
+
+fn next_i32(src: &mut slice::Iter<'_, Val>) -> Result<i32> {
+    match next(src)? {
+        Val::I32(i) => Ok(*i),
+        other => bail!("expected an `i32` value, found `{}`", other.ty()),
+    }
+}
+
+fn load_u32(bytes: &[u8]) -> u32 {
+    let mut le = [0; 4];
+    le.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(le)
+}
+
+macro_rules! primitives {
+    ($($ty:ident $flat:ident $align:tt $from:ident,)*) => {$(
+        impl ComponentType for $ty {
+            const SIZE32: usize = std::mem::size_of::<$ty>();
+            const ALIGN32: u32 = $align;
+
+            fn flatten(dst: &mut Vec<ValType>) {
+                dst.push(ValType::$flat);
+            }
+        }
+
+        impl Lift for $ty {
+            fn lift(_cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+                match next(src)? {
+                    Val::$flat(v) => Ok(primitives!(@lift $from $ty *v)),
+                    other => bail!(
+                        "expected `{}` value, found `{}`",
+                        ValType::$flat,
+                        other.ty()
+                    ),
+                }
+            }
+
+            fn load(_cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+                let mut le = [0; std::mem::size_of::<$ty>()];
+                le.copy_from_slice(bytes);
+                Ok($ty::from_le_bytes(le))
+            }
+        }
+
+        impl Lower for $ty {
+            fn lower<T>(&self, _cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+                dst.push(Val::$flat(primitives!(@lower $from $ty *self)));
+                Ok(())
+            }
+
+            fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+                cx.bytes_mut(offset, Self::SIZE32, Self::ALIGN32)?
+                    .copy_from_slice(&self.to_le_bytes());
+                Ok(())
+            }
+        }
+    )*};
+
+    (@lift int $ty:ident $v:expr) => ($v as $ty);
+    (@lift float $ty:ident $v:expr) => ($ty::from_bits($v));
+    (@lower int $ty:ident $v:expr) => ($v as _);
+    (@lower float $ty:ident $v:expr) => ($v.to_bits());
+}
+
+primitives! {
+    u8 I32 1 int,
+    i8 I32 1 int,
+    u16 I32 2 int,
+    i16 I32 2 int,
+    u32 I32 4 int,
+    i32 I32 4 int,
+    u64 I64 8 int,
+    i64 I64 8 int,
+    f32 F32 4 float,
+    f64 F64 8 float,
+}
+
+impl ComponentType for bool {
+    const SIZE32: usize = 1;
+    const ALIGN32: u32 = 1;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        dst.push(ValType::I32);
+    }
+}
+
+impl Lift for bool {
+    fn lift(_cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        Ok(next_i32(src)? != 0)
+    }
+
+    fn load(_cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        Ok(bytes[0] != 0)
+    }
+}
+
+impl Lower for bool {
+    fn lower<T>(&self, _cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        dst.push(Val::I32(*self as i32));
+        Ok(())
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        cx.bytes_mut(offset, 1, 1)?[0] = *self as u8;
+        Ok(())
+    }
+}
+
+impl ComponentType for char {
+    const SIZE32: usize = 4;
+    const ALIGN32: u32 = 4;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        dst.push(ValType::I32);
+    }
+}
+
+fn u32_to_char(bits: u32) -> Result<char> {
+    match std::char::from_u32(bits) {
+        Some(c) => Ok(c),
+        None => bail!("invalid `char` bit pattern: {:#x}", bits),
+    }
+}
+
+impl Lift for char {
+    fn lift(_cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        u32_to_char(next_i32(src)? as u32)
+    }
+
+    fn load(_cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        u32_to_char(load_u32(bytes))
+    }
+}
+
+impl Lower for char {
+    fn lower<T>(&self, _cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        dst.push(Val::I32(*self as i32));
+        Ok(())
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        (*self as u32).store(cx, offset)
+    }
+}
+
+// Strings and lists are both represented as a pointer/length pair, where the
+// length of a string is in bytes and the length of a list is in elements.
+
+fn lift_pointer_pair(src: &mut slice::Iter<'_, Val>) -> Result<(usize, usize)> {
+    let ptr = next_i32(src)? as u32 as usize;
+    let len = next_i32(src)? as u32 as usize;
+    Ok((ptr, len))
+}
+
+fn load_pointer_pair(bytes: &[u8]) -> (usize, usize) {
+    (load_u32(bytes) as usize, load_u32(&bytes[4..]) as usize)
+}
+
+fn store_pointer_pair<T>(
+    cx: &mut LowerContext<'_, T>,
+    offset: usize,
+    ptr: usize,
+    len: usize,
+) -> Result<()> {
+    (ptr as u32).store(cx, offset)?;
+    (len as u32).store(cx, offset + 4)
+}
+
+fn lift_string(cx: &LiftContext<'_>, ptr: usize, len: usize) -> Result<String> {
+    let bytes = cx.bytes(ptr, len, 1)?;
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(s.to_string()),
+        Err(e) => bail!("invalid utf-8 string: {}", e),
+    }
+}
+
+fn lower_string<T>(cx: &mut LowerContext<'_, T>, s: &str) -> Result<(usize, usize)> {
+    let ptr = cx.realloc(0, 0, 1, s.len())?;
+    cx.bytes_mut(ptr, s.len(), 1)?.copy_from_slice(s.as_bytes());
+    Ok((ptr, s.len()))
+}
+
+impl ComponentType for str {
+    const SIZE32: usize = 8;
+    const ALIGN32: u32 = 4;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        dst.push(ValType::I32);
+        dst.push(ValType::I32);
+    }
+}
+
+impl Lower for str {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        let (ptr, len) = lower_string(cx, self)?;
+        dst.push(Val::I32(ptr as i32));
+        dst.push(Val::I32(len as i32));
+        Ok(())
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        let (ptr, len) = lower_string(cx, self)?;
+        store_pointer_pair(cx, offset, ptr, len)
+    }
+}
+
+impl ComponentType for String {
+    const SIZE32: usize = str::SIZE32;
+    const ALIGN32: u32 = str::ALIGN32;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        str::flatten(dst)
+    }
+}
+
+impl Lift for String {
+    fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        let (ptr, len) = lift_pointer_pair(src)?;
+        lift_string(cx, ptr, len)
+    }
+
+    fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        let (ptr, len) = load_pointer_pair(bytes);
+        lift_string(cx, ptr, len)
+    }
+}
+
+impl Lower for String {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        self.as_str().lower(cx, dst)
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        self.as_str().store(cx, offset)
+    }
+}
+
+fn lift_list<E: Lift>(cx: &LiftContext<'_>, ptr: usize, len: usize) -> Result<Vec<E>> {
+    let size = match len.checked_mul(E::SIZE32) {
+        Some(size) => size,
+        None => bail!("list length {} is too large", len),
+    };
+    let bytes = cx.bytes(ptr, size, E::ALIGN32)?;
+    (0..len)
+        .map(|i| E::load(cx, &bytes[i * E::SIZE32..][..E::SIZE32]))
+        .collect()
+}
+
+fn lower_list<T, E: Lower>(cx: &mut LowerContext<'_, T>, list: &[E]) -> Result<(usize, usize)> {
+    let size = match list.len().checked_mul(E::SIZE32) {
+        Some(size) if size <= u32::MAX as usize => size,
+        _ => bail!("list length {} is too large", list.len()),
+    };
+    let ptr = cx.realloc(0, 0, E::ALIGN32, size)?;
+    for (i, elem) in list.iter().enumerate() {
+        elem.store(cx, ptr + i * E::SIZE32)?;
+    }
+    Ok((ptr, list.len()))
+}
+
+impl<E: ComponentType> ComponentType for [E] {
+    const SIZE32: usize = 8;
+    const ALIGN32: u32 = 4;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        dst.push(ValType::I32);
+        dst.push(ValType::I32);
+    }
+}
+
+impl<E: Lower> Lower for [E] {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        let (ptr, len) = lower_list(cx, self)?;
+        dst.push(Val::I32(ptr as i32));
+        dst.push(Val::I32(len as i32));
+        Ok(())
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        let (ptr, len) = lower_list(cx, self)?;
+        store_pointer_pair(cx, offset, ptr, len)
+    }
+}
+
+impl<E: ComponentType> ComponentType for Vec<E> {
+    const SIZE32: usize = <[E]>::SIZE32;
+    const ALIGN32: u32 = <[E]>::ALIGN32;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        <[E]>::flatten(dst)
+    }
+}
+
+impl<E: Lift> Lift for Vec<E> {
+    fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        let (ptr, len) = lift_pointer_pair(src)?;
+        lift_list(cx, ptr, len)
+    }
+
+    fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        let (ptr, len) = load_pointer_pair(bytes);
+        lift_list(cx, ptr, len)
+    }
+}
+
+impl<E: Lower> Lower for Vec<E> {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        self[..].lower(cx, dst)
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        self[..].store(cx, offset)
+    }
+}
+
+impl<A: ComponentType + ?Sized> ComponentType for &A {
+    const SIZE32: usize = A::SIZE32;
+    const ALIGN32: u32 = A::ALIGN32;
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        A::flatten(dst)
+    }
+}
+
+impl<A: Lower + ?Sized> Lower for &A {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        (**self).lower(cx, dst)
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        (**self).store(cx, offset)
+    }
+}
+
+// Records, which tuples and `#[derive]`d structs are represented as, lay out
+// their fields in order with each field aligned to its own alignment.
+
+/// Rounds `offset` up to a multiple of `align`.
+pub const fn align_to(offset: usize, align: u32) -> usize {
+    let align = align as usize;
+    (offset + align - 1) & !(align - 1)
+}
+
+/// Returns the size of a record with fields of the given sizes and
+/// alignments.
+pub const fn record_size(fields: &[(usize, u32)]) -> usize {
+    let mut size = 0;
+    let mut i = 0;
+    while i < fields.len() {
+        size = align_to(size, fields[i].1) + fields[i].0;
+        i += 1;
+    }
+    align_to(size, record_align(fields))
+}
+
+/// Returns the alignment of a record with fields of the given sizes and
+/// alignments.
+pub const fn record_align(fields: &[(usize, u32)]) -> u32 {
+    let mut align = 1;
+    let mut i = 0;
+    while i < fields.len() {
+        if fields[i].1 > align {
+            align = fields[i].1;
+        }
+        i += 1;
+    }
+    align
+}
+
+/// Returns the offset of the next field of type `F` in a record, given the
+/// end of the previous field in `offset`, and advances `offset` past it.
+pub fn next_field<F: ComponentType>(offset: &mut usize) -> usize {
+    let start = align_to(*offset, F::ALIGN32);
+    *offset = start + F::SIZE32;
+    start
+}
+
+impl ComponentType for () {
+    const SIZE32: usize = 0;
+    const ALIGN32: u32 = 1;
+
+    fn flatten(_dst: &mut Vec<ValType>) {}
+}
+
+impl Lift for () {
+    fn lift(_cx: &LiftContext<'_>, _src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        Ok(())
+    }
+
+    fn load(_cx: &LiftContext<'_>, _bytes: &[u8]) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl Lower for () {
+    fn lower<T>(&self, _cx: &mut LowerContext<'_, T>, _dst: &mut Vec<Val>) -> Result<()> {
+        Ok(())
+    }
+
+    fn store<T>(&self, _cx: &mut LowerContext<'_, T>, _offset: usize) -> Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! tuples {
+    ($(($($t:ident)*))*) => ($(
+        #[allow(non_snake_case)]
+        impl<$($t: ComponentType,)*> ComponentType for ($($t,)*) {
+            const SIZE32: usize = record_size(&[$(($t::SIZE32, $t::ALIGN32),)*]);
+            const ALIGN32: u32 = record_align(&[$(($t::SIZE32, $t::ALIGN32),)*]);
+
+            fn flatten(dst: &mut Vec<ValType>) {
+                $($t::flatten(dst);)*
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($t: Lift,)*> Lift for ($($t,)*) {
+            fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+                Ok(($($t::lift(cx, src)?,)*))
+            }
+
+            fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+                let mut offset = 0;
+                $(
+                    let $t = $t::load(cx, &bytes[next_field::<$t>(&mut offset)..][..$t::SIZE32])?;
+                )*
+                Ok(($($t,)*))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($t: Lower,)*> Lower for ($($t,)*) {
+            fn lower<U>(&self, cx: &mut LowerContext<'_, U>, dst: &mut Vec<Val>) -> Result<()> {
+                let ($($t,)*) = self;
+                $($t.lower(cx, dst)?;)*
+                Ok(())
+            }
+
+            fn store<U>(&self, cx: &mut LowerContext<'_, U>, offset: usize) -> Result<()> {
+                let ($($t,)*) = self;
+                let mut field = 0;
+                $($t.store(cx, offset + next_field::<$t>(&mut field))?;)*
+                Ok(())
+            }
+        }
+    )*)
+}
+
+tuples! {
+    (A)
+    (A B)
+    (A B C)
+    (A B C D)
+    (A B C D E)
+    (A B C D E F)
+    (A B C D E F G)
+    (A B C D E F G H)
+    (A B C D E F G H I)
+    (A B C D E F G H I J)
+    (A B C D E F G H I J K)
+    (A B C D E F G H I J K L)
+    (A B C D E F G H I J K L M)
+    (A B C D E F G H I J K L M N)
+    (A B C D E F G H I J K L M N O)
+    (A B C D E F G H I J K L M N O P)
+}
+
+// Variants, which options, results and `#[derive]`d enums are represented
+// as, store a discriminant followed by the payload of the active case. When
+// flattened the payloads of all cases share the same core wasm values, with
+// each value's type widened to fit every case.
+
+const fn discriminant_size(cases: usize) -> usize {
+    if cases <= 0x100 {
+        1
+    } else if cases <= 0x1_0000 {
+        2
+    } else {
+        4
+    }
+}
+
+const fn payload_offset(cases: &[(usize, u32)]) -> usize {
+    align_to(discriminant_size(cases.len()), record_align(cases))
+}
+
+/// Returns the size of a variant whose cases have payloads of the given sizes
+/// and alignments.
+pub const fn variant_size(cases: &[(usize, u32)]) -> usize {
+    let mut payload = 0;
+    let mut i = 0;
+    while i < cases.len() {
+        if cases[i].0 > payload {
+            payload = cases[i].0;
+        }
+        i += 1;
+    }
+    align_to(payload_offset(cases) + payload, variant_align(cases))
+}
+
+/// Returns the alignment of a variant whose cases have payloads of the given
+/// sizes and alignments.
+pub const fn variant_align(cases: &[(usize, u32)]) -> u32 {
+    let discriminant = discriminant_size(cases.len()) as u32;
+    let payload = record_align(cases);
+    if discriminant > payload {
+        discriminant
+    } else {
+        payload
+    }
+}
+
+fn variant_payload_types(cases: &[fn(&mut Vec<ValType>)]) -> Vec<ValType> {
+    let mut joined: Vec<ValType> = Vec::new();
+    for flatten in cases {
+        let mut flat = Vec::new();
+        flatten(&mut flat);
+        for (i, ty) in flat.into_iter().enumerate() {
+            match joined.get_mut(i) {
+                Some(prev) => *prev = join(prev, ty),
+                None => joined.push(ty),
+            }
+        }
+    }
+    joined
+}
+
+fn join(a: &ValType, b: ValType) -> ValType {
+    match (a, &b) {
+        _ if *a == b => b,
+        (ValType::I32, ValType::F32) | (ValType::F32, ValType::I32) => ValType::I32,
+        _ => ValType::I64,
+    }
+}
+
+/// Flattens a variant whose cases' payloads are flattened by `cases`.
+pub fn variant_flatten(cases: &[fn(&mut Vec<ValType>)], dst: &mut Vec<ValType>) {
+    dst.push(ValType::I32);
+    dst.extend(variant_payload_types(cases));
+}
+
+/// Lifts the discriminant of a flattened variant, returning it along with the
+/// core wasm values of its shared payload.
+pub fn lift_variant<'a>(
+    src: &mut slice::Iter<'a, Val>,
+    cases: &[fn(&mut Vec<ValType>)],
+) -> Result<(u32, &'a [Val])> {
+    let discriminant = next_i32(src)? as u32;
+    let len = variant_payload_types(cases).len();
+    let payload = src.as_slice();
+    if payload.len() < len {
+        bail!("too few core wasm values to lift from");
+    }
+    *src = payload[len..].iter();
+    Ok((discriminant, &payload[..len]))
+}
+
+/// Lifts the payload of a variant's active case from the core wasm values
+/// returned by [`lift_variant`].
+pub fn lift_payload<P: Lift>(cx: &LiftContext<'_>, payload: &[Val]) -> Result<P> {
+    let vals = flat_types::<P>()
+        .into_iter()
+        .zip(payload)
+        .map(|(ty, val)| narrow(val, ty))
+        .collect::<Result<Vec<_>>>()?;
+    P::lift(cx, &mut vals.iter())
+}
+
+fn narrow(val: &Val, ty: ValType) -> Result<Val> {
+    Ok(match (val, ty) {
+        (Val::I32(i), ValType::F32) => Val::F32(*i as u32),
+        (Val::I64(i), ValType::I32) => Val::I32(*i as i32),
+        (Val::I64(i), ValType::F32) => Val::F32(*i as u32),
+        (Val::I64(i), ValType::F64) => Val::F64(*i as u64),
+        (val, ty) if val.ty() == ty => val.clone(),
+        (val, ty) => bail!("cannot lift `{}` from a `{}` value", ty, val.ty()),
+    })
+}
+
+/// Lowers a variant with the given `discriminant` and `payload` into the
+/// shared flattened representation of all of its `cases`.
+pub fn lower_variant<T, P: Lower + ?Sized>(
+    cx: &mut LowerContext<'_, T>,
+    discriminant: u32,
+    payload: &P,
+    cases: &[fn(&mut Vec<ValType>)],
+    dst: &mut Vec<Val>,
+) -> Result<()> {
+    dst.push(Val::I32(discriminant as i32));
+    let mut flat = Vec::new();
+    payload.lower(cx, &mut flat)?;
+    let mut flat = flat.into_iter();
+    for ty in variant_payload_types(cases) {
+        dst.push(match flat.next() {
+            Some(val) => widen(val, ty),
+            None => zero(ty),
+        });
+    }
+    Ok(())
+}
+
+fn zero(ty: ValType) -> Val {
+    match ty {
+        ValType::I32 => Val::I32(0),
+        ValType::I64 => Val::I64(0),
+        ValType::F32 => Val::F32(0),
+        ValType::F64 => Val::F64(0),
+        _ => unreachable!("canonical ABI values only flatten to numeric types"),
+    }
+}
+
+fn widen(val: Val, ty: ValType) -> Val {
+    match (val, ty) {
+        (Val::F32(bits), ValType::I32) => Val::I32(bits as i32),
+        (Val::I32(i), ValType::I64) => Val::I64(i as u32 as i64),
+        (Val::F32(bits), ValType::I64) => Val::I64(bits as i64),
+        (Val::F64(bits), ValType::I64) => Val::I64(bits as i64),
+        (val, _) => val,
+    }
+}
+
+/// Loads the discriminant of a variant stored in `bytes`, returning it along
+/// with the bytes of the active case's payload.
+pub fn load_variant<'a>(bytes: &'a [u8], cases: &[(usize, u32)]) -> Result<(u32, &'a [u8])> {
+    let discriminant = match discriminant_size(cases.len()) {
+        1 => u32::from(bytes[0]),
+        2 => u32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+        _ => load_u32(bytes),
+    };
+    match cases.get(discriminant as usize) {
+        Some((size, _)) => Ok((discriminant, &bytes[payload_offset(cases)..][..*size])),
+        None => bail!("invalid variant discriminant: {}", discriminant),
+    }
+}
+
+/// Stores a variant with the given `discriminant` and `payload` into linear
+/// memory at `offset`.
+pub fn store_variant<T, P: Lower + ?Sized>(
+    cx: &mut LowerContext<'_, T>,
+    offset: usize,
+    discriminant: u32,
+    payload: &P,
+    cases: &[(usize, u32)],
+) -> Result<()> {
+    match discriminant_size(cases.len()) {
+        1 => (discriminant as u8).store(cx, offset)?,
+        2 => (discriminant as u16).store(cx, offset)?,
+        _ => discriminant.store(cx, offset)?,
+    }
+    payload.store(cx, offset + payload_offset(cases))
+}
+
+impl<V: ComponentType> ComponentType for Option<V> {
+    const SIZE32: usize = variant_size(&[(0, 1), (V::SIZE32, V::ALIGN32)]);
+    const ALIGN32: u32 = variant_align(&[(0, 1), (V::SIZE32, V::ALIGN32)]);
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        variant_flatten(&[<()>::flatten, V::flatten], dst)
+    }
+}
+
+impl<V: Lift> Lift for Option<V> {
+    fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        match lift_variant(src, &[<()>::flatten, V::flatten])? {
+            (0, _) => Ok(None),
+            (1, payload) => Ok(Some(lift_payload(cx, payload)?)),
+            (n, _) => bail!("invalid option discriminant: {}", n),
+        }
+    }
+
+    fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        match load_variant(bytes, &[(0, 1), (V::SIZE32, V::ALIGN32)])? {
+            (0, _) => Ok(None),
+            (_, payload) => Ok(Some(V::load(cx, payload)?)),
+        }
+    }
+}
+
+impl<V: Lower> Lower for Option<V> {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        let cases = [<()>::flatten, V::flatten];
+        match self {
+            None => lower_variant(cx, 0, &(), &cases, dst),
+            Some(v) => lower_variant(cx, 1, v, &cases, dst),
+        }
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        let cases = [(0, 1), (V::SIZE32, V::ALIGN32)];
+        match self {
+            None => store_variant(cx, offset, 0, &(), &cases),
+            Some(v) => store_variant(cx, offset, 1, v, &cases),
+        }
+    }
+}
+
+impl<O: ComponentType, E: ComponentType> ComponentType for std::result::Result<O, E> {
+    const SIZE32: usize = variant_size(&[(O::SIZE32, O::ALIGN32), (E::SIZE32, E::ALIGN32)]);
+    const ALIGN32: u32 = variant_align(&[(O::SIZE32, O::ALIGN32), (E::SIZE32, E::ALIGN32)]);
+
+    fn flatten(dst: &mut Vec<ValType>) {
+        variant_flatten(&[O::flatten, E::flatten], dst)
+    }
+}
+
+impl<O: Lift, E: Lift> Lift for std::result::Result<O, E> {
+    fn lift(cx: &LiftContext<'_>, src: &mut slice::Iter<'_, Val>) -> Result<Self> {
+        match lift_variant(src, &[O::flatten, E::flatten])? {
+            (0, payload) => Ok(Ok(lift_payload(cx, payload)?)),
+            (1, payload) => Ok(Err(lift_payload(cx, payload)?)),
+            (n, _) => bail!("invalid result discriminant: {}", n),
+        }
+    }
+
+    fn load(cx: &LiftContext<'_>, bytes: &[u8]) -> Result<Self> {
+        match load_variant(bytes, &[(O::SIZE32, O::ALIGN32), (E::SIZE32, E::ALIGN32)])? {
+            (0, payload) => Ok(Ok(O::load(cx, payload)?)),
+            (_, payload) => Ok(Err(E::load(cx, payload)?)),
+        }
+    }
+}
+
+impl<O: Lower, E: Lower> Lower for std::result::Result<O, E> {
+    fn lower<T>(&self, cx: &mut LowerContext<'_, T>, dst: &mut Vec<Val>) -> Result<()> {
+        let cases = [O::flatten, E::flatten];
+        match self {
+            Ok(v) => lower_variant(cx, 0, v, &cases, dst),
+            Err(e) => lower_variant(cx, 1, e, &cases, dst),
+        }
+    }
+
+    fn store<T>(&self, cx: &mut LowerContext<'_, T>, offset: usize) -> Result<()> {
+        let cases = [(O::SIZE32, O::ALIGN32), (E::SIZE32, E::ALIGN32)];
+        match self {
+            Ok(v) => store_variant(cx, offset, 0, v, &cases),
+            Err(e) => store_variant(cx, offset, 1, e, &cases),
+        }
+    }
+}
@@ -11,7 +11,7 @@ use wasmparser::WasmFeatures;
 #[cfg(feature = "cache")]
 use wasmtime_cache::CacheConfig;
 use wasmtime_environ::{CompilerBuilder, Tunables};
-use wasmtime_jit::{JitDumpAgent, NullProfilerAgent, ProfilingAgent, VTuneAgent};
+use wasmtime_jit::{JitDumpAgent, NullProfilerAgent, PerfMapAgent, ProfilingAgent, VTuneAgent};
 use wasmtime_runtime::{
     InstanceAllocator, OnDemandInstanceAllocator, PoolingInstanceAllocator, RuntimeMemoryCreator,
 };
@@ -353,6 +353,12 @@ pub struct Config {
     pub(crate) deserialize_check_wasmtime_version: bool,
     pub(crate) parallel_compilation: bool,
     pub(crate) paged_memory_initialization: bool,
+    pub(crate) memory_init_cow: bool,
+    // Whether the memory reservation tunables were set explicitly, in which
+    // case `Config::target` leaves them alone.
+    static_memory_maximum_size_set: bool,
+    static_memory_guard_size_set: bool,
+    dynamic_memory_reserved_for_growth_set: bool,
 }
 
 impl Config {
@@ -378,6 +384,10 @@ impl Config {
             parallel_compilation: true,
             // Default to paged memory initialization when using uffd on linux
             paged_memory_initialization: cfg!(all(target_os = "linux", feature = "uffd")),
+            memory_init_cow: true,
+            static_memory_maximum_size_set: false,
+            static_memory_guard_size_set: false,
+            dynamic_memory_reserved_for_growth_set: false,
         };
         #[cfg(compiler)]
         {
@@ -400,6 +410,11 @@ impl Config {
     /// Cranelift flags will not be inferred for the given target and any
     /// existing target-specific Cranelift flags will be cleared.
     ///
+    /// For a 32-bit target, the memory reservation settings that haven't been
+    /// configured, such as [`Config::static_memory_maximum_size`], use the
+    /// defaults for 32-bit platforms, since a 32-bit target can't reserve as
+    /// much address space as a 64-bit one.
+    ///
     /// # Errors
     ///
     /// This method will error if the given target triple is not supported.
@@ -407,9 +422,27 @@ impl Config {
     #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
     pub fn target(&mut self, target: &str) -> Result<&mut Self> {
         use std::str::FromStr;
-        self.compiler
-            .target(target_lexicon::Triple::from_str(target).map_err(|e| anyhow::anyhow!(e))?)?;
-
+        use target_lexicon::PointerWidth;
+        let target = target_lexicon::Triple::from_str(target).map_err(|e| anyhow::anyhow!(e))?;
+        let is_32_bit = target.pointer_width() == Ok(PointerWidth::U32);
+        self.compiler.target(target)?;
+
+        if is_32_bit {
+            let defaults = Tunables::default_u32();
+            if !self.static_memory_maximum_size_set {
+                self.tunables.static_memory_bound = defaults.static_memory_bound;
+            }
+            if !self.static_memory_guard_size_set {
+                self.tunables.static_memory_offset_guard_size = cmp::max(
+                    defaults.static_memory_offset_guard_size,
+                    self.tunables.dynamic_memory_offset_guard_size,
+                );
+            }
+            if !self.dynamic_memory_reserved_for_growth_set {
+                self.tunables.dynamic_memory_growth_reserve =
+                    defaults.dynamic_memory_growth_reserve;
+            }
+        }
         Ok(self)
     }
 
@@ -564,6 +597,115 @@ impl Config {
         self
     }
 
+    /// Enables epoch-based interruption.
+    ///
+    /// When executing code in async mode, we sometimes want to
+    /// implement a form of cooperative timeslicing: long-running Wasm
+    /// guest code should periodically yield to the executor
+    /// loop. This yielding could be implemented by using "fuel" (see
+    /// [`consume_fuel`](Config::consume_fuel)). However, fuel
+    /// instrumentation is somewhat expensive: it modifies the
+    /// compiled form of the Wasm code so that it maintains a precise
+    /// instruction count, frequently checking this count against the
+    /// remaining fuel. If one does not need this precise count or
+    /// deterministic interruptions, and only needs a periodic
+    /// interrupt of some form, then it would be better to have a more
+    /// lightweight mechanism.
+    ///
+    /// Epoch-based interruption is that mechanism. There is a global
+    /// "epoch", which is a counter that divides time into arbitrary
+    /// periods (or epochs). This counter lives on the
+    /// [`Engine`](crate::Engine) and can be incremented by calling
+    /// [`Engine::increment_epoch`](crate::Engine::increment_epoch).
+    /// Epoch-based instrumentation works by setting a "deadline
+    /// epoch". The compiled code knows the deadline, and at certain
+    /// points, checks the current epoch against that deadline. It
+    /// will yield if the deadline has been reached.
+    ///
+    /// The idea is that checking an infrequently-changing counter is
+    /// cheaper than counting and frequently storing a precise metric
+    /// (instructions executed) locally. The interruptions are not
+    /// deterministic, but if the embedder increments the epoch in a
+    /// periodic way (say, every regular timer tick by a thread or
+    /// signal handler), then we can ensure that all async code will
+    /// yield to the executor within a bounded time.
+    ///
+    /// Unlike [`Config::interruptable`] a single call to
+    /// [`Engine::increment_epoch`](crate::Engine::increment_epoch)
+    /// affects every [`Store`] of the engine, and each store decides for
+    /// itself whether reaching its deadline traps or yields.
+    ///
+    /// The [`Store`] tracks the deadline, and controls what happens
+    /// when the deadline is reached during execution. Two behaviors
+    /// are possible:
+    ///
+    /// - Trap if code is executing when the epoch deadline is
+    ///   met. See
+    ///   [`Store::epoch_deadline_trap`](crate::Store::epoch_deadline_trap).
+    ///
+    /// - Yield to the executor loop, then resume when the future is
+    ///   next polled. See
+    ///   [`Store::epoch_deadline_async_yield_and_update`](crate::Store::epoch_deadline_async_yield_and_update).
+    ///
+    /// The first is the default; set the second for the timeslicing
+    /// behavior described above.
+    ///
+    /// This feature is available with or without async
+    /// support. However, without async support, only the trapping
+    /// behavior is available. In this mode, epoch-based interruption
+    /// can serve as a simple external-interruption mechanism.
+    ///
+    /// An initial deadline can be set before executing code by
+    /// calling
+    /// [`Store::set_epoch_deadline`](crate::Store::set_epoch_deadline).
+    ///
+    /// ## When to use fuel vs. epochs
+    ///
+    /// In general, epoch-based interruption results in faster
+    /// execution. This difference is sometimes significant: in some
+    /// measurements, up to 2-3x. This is because epoch-based
+    /// interruption does less work: it only watches for a global
+    /// rarely-changing counter to increment, rather than keeping a
+    /// local frequently-changing counter and comparing it to a
+    /// deadline.
+    ///
+    /// Fuel, in contrast, should be used when *deterministic*
+    /// yielding or trapping is needed. For example, if it is required
+    /// that the same function call with the same starting state will
+    /// always either complete or trap with an out-of-fuel error,
+    /// deterministically, then fuel with a fixed bound should be
+    /// used.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// [`Store`]: crate::Store
+    pub fn epoch_interruption(&mut self, enable: bool) -> &mut Self {
+        self.tunables.epoch_interruption = enable;
+        self
+    }
+
+    /// Configures whether compiled code supports guest debugging with
+    /// breakpoints and single-stepping.
+    ///
+    /// When enabled, code is compiled with a check before every wasm
+    /// instruction for whether the [`Store`] has breakpoints set or is
+    /// single-stepping, and if so calls into the store so that its debug
+    /// handler can inspect the locals and operand stack of the stopped
+    /// function. See [`Store::debug_handler`](crate::Store::debug_handler)
+    /// for more information.
+    ///
+    /// This makes wasm execution considerably slower even when no breakpoints
+    /// are set, and much slower again while any breakpoint is set, so it's
+    /// only intended for debugging.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// [`Store`]: crate::Store
+    pub fn guest_debug(&mut self, enable: bool) -> &mut Self {
+        self.tunables.guest_debug = enable;
+        self
+    }
+
     /// Configures the maximum amount of stack space available for
     /// executing WebAssembly code.
     ///
@@ -782,6 +924,64 @@ impl Config {
         self
     }
 
+    /// Configures whether the WebAssembly tail-call [proposal] will be
+    /// enabled for compilation.
+    ///
+    /// When enabled, the `return_call` and `return_call_indirect` instructions
+    /// are accepted and guaranteed not to grow the native stack. Cranelift
+    /// currently only implements these on x86_64, aarch64, riscv64 and arm,
+    /// and [`Engine::new`](crate::Engine::new) fails for other targets.
+    ///
+    /// Note that enabling this also makes every function in a module use the
+    /// default Wasmtime calling convention rather than the internal fast one,
+    /// since a tail call requires caller and callee to agree on a convention.
+    /// A function that tail calls one taking more stack arguments than itself
+    /// makes room for them in its prologue, and every call recomputes the
+    /// stack pointer afterwards, which makes calls slightly more expensive.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/webassembly/tail-call
+    pub fn wasm_tail_call(&mut self, enable: bool) -> &mut Self {
+        self.features.tail_call = enable;
+
+        #[cfg(compiler)]
+        {
+            self.compiler
+                .set(
+                    "enable_growing_tail_calls",
+                    if enable { "true" } else { "false" },
+                )
+                .unwrap();
+        }
+
+        self
+    }
+
+    /// Configures whether the WebAssembly exception handling [proposal] will
+    /// be enabled for compilation.
+    ///
+    /// When enabled, tags and the `try`, `catch`, `catch_all`, `throw`,
+    /// `rethrow` and `delegate` instructions are accepted. An exception that
+    /// propagates out of wasm is reported as a [`Trap`](crate::Trap) from
+    /// which it can be retrieved with [`Trap::exception`](crate::Trap::exception),
+    /// and host functions can throw exceptions into wasm by returning
+    /// [`Trap::throw`](crate::Trap::throw).
+    ///
+    /// Thrown exceptions unwind the native stack straight to the landing pad
+    /// of the innermost `try` handling them, so calls cost nothing extra
+    /// unless they throw. This is currently only implemented for x86_64,
+    /// aarch64 and riscv64 targets other than Windows, and
+    /// [`Engine::new`](crate::Engine::new) fails for other targets.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/webassembly/exception-handling
+    pub fn wasm_exceptions(&mut self, enable: bool) -> &mut Self {
+        self.features.exceptions = enable;
+        self
+    }
+
     /// Configures which compilation strategy will be used for wasm modules.
     ///
     /// This method can be used to configure which compiler is used for wasm
@@ -809,6 +1009,7 @@ impl Config {
     pub fn profiler(&mut self, profile: ProfilingStrategy) -> Result<&mut Self> {
         self.profiler = match profile {
             ProfilingStrategy::JitDump => Arc::new(JitDumpAgent::new()?) as Arc<dyn ProfilingAgent>,
+            ProfilingStrategy::PerfMap => Arc::new(PerfMapAgent::new()?) as Arc<dyn ProfilingAgent>,
             ProfilingStrategy::VTune => Arc::new(VTuneAgent::new()?) as Arc<dyn ProfilingAgent>,
             ProfilingStrategy::None => Arc::new(NullProfilerAgent),
         };
@@ -1008,6 +1209,35 @@ impl Config {
         self
     }
 
+    /// Configures whether copy-on-write memory images are used to initialize
+    /// linear memories.
+    ///
+    /// When enabled, the initial contents of each linear memory of a module
+    /// are written once into an in-memory file when the module is created or
+    /// deserialized. Instantiating the module then maps that file
+    /// copy-on-write into the instance's linear memory rather than copying
+    /// each data segment, so only pages which are actually written by an
+    /// instance are ever copied. With the pooling allocator, memories are
+    /// additionally reset for reuse with a single `madvise` call and keep
+    /// their image mapped for the next instance of the same module.
+    ///
+    /// Images can only be used for modules whose data segments can be
+    /// organized into pages ahead of time (see
+    /// [`Config::paged_memory_initialization`]), so enabling this also causes
+    /// compilation to attempt paged memory initialization. Other modules, and
+    /// memories created with a custom [`MemoryCreator`](crate::MemoryCreator),
+    /// are still initialized by copying data segments.
+    ///
+    /// This requires the `memory-init-cow` feature of this crate, which is
+    /// enabled by default, and is currently only supported on Linux. It has
+    /// no effect when the `uffd` feature is enabled.
+    ///
+    /// By default this option is enabled.
+    pub fn memory_init_cow(&mut self, enable: bool) -> &mut Self {
+        self.memory_init_cow = enable;
+        self
+    }
+
     /// Configures the maximum size, in bytes, where a linear memory is
     /// considered static, above which it'll be considered dynamic.
     ///
@@ -1099,6 +1329,7 @@ impl Config {
     pub fn static_memory_maximum_size(&mut self, max_size: u64) -> &mut Self {
         let max_pages = max_size / u64::from(wasmtime_environ::WASM_PAGE_SIZE);
         self.tunables.static_memory_bound = max_pages;
+        self.static_memory_maximum_size_set = true;
         self
     }
 
@@ -1157,6 +1388,7 @@ impl Config {
         let guard_size = round_up_to_pages(guard_size);
         let guard_size = cmp::max(guard_size, self.tunables.dynamic_memory_offset_guard_size);
         self.tunables.static_memory_offset_guard_size = guard_size;
+        self.static_memory_guard_size_set = true;
         self
     }
 
@@ -1226,6 +1458,7 @@ impl Config {
     /// defaults to 1MB.
     pub fn dynamic_memory_reserved_for_growth(&mut self, reserved: u64) -> &mut Self {
         self.tunables.dynamic_memory_growth_reserve = round_up_to_pages(reserved);
+        self.dynamic_memory_reserved_for_growth_set = true;
         self
     }
 
@@ -1354,6 +1587,10 @@ impl Clone for Config {
             deserialize_check_wasmtime_version: self.deserialize_check_wasmtime_version,
             parallel_compilation: self.parallel_compilation,
             paged_memory_initialization: self.paged_memory_initialization,
+            memory_init_cow: self.memory_init_cow,
+            static_memory_maximum_size_set: self.static_memory_maximum_size_set,
+            static_memory_guard_size_set: self.static_memory_guard_size_set,
+            dynamic_memory_reserved_for_growth_set: self.dynamic_memory_reserved_for_growth_set,
         }
     }
 }
@@ -1386,7 +1623,8 @@ impl fmt::Debug for Config {
                 "guard_before_linear_memory",
                 &self.tunables.guard_before_linear_memory,
             )
-            .field("parallel_compilation", &self.parallel_compilation);
+            .field("parallel_compilation", &self.parallel_compilation)
+            .field("memory_init_cow", &self.memory_init_cow);
         #[cfg(compiler)]
         {
             f.field("compiler", &self.compiler);
@@ -1450,6 +1688,13 @@ pub enum ProfilingStrategy {
 
     /// Collect profiling info using the "ittapi", used with `VTune` on Linux.
     VTune,
+
+    /// Collect function name information in a "perf map" file at
+    /// `/tmp/perf-<pid>.map`, used with `perf` on Linux.
+    ///
+    /// This is lighter weight than `JitDump` and doesn't need a `perf inject`
+    /// step, but doesn't provide source line information.
+    PerfMap,
 }
 
 /// Select how wasm backtrace detailed information is handled.
@@ -1467,3 +1712,39 @@ pub enum WasmBacktraceDetails {
     /// `WASMTIME_BACKTRACE_DETAILS` environment variable.
     Environment,
 }
+
+#[cfg(all(test, feature = "all-arch"))]
+mod tests {
+    use super::Config;
+    use anyhow::Result;
+    use wasmtime_environ::Tunables;
+
+    #[test]
+    fn target_keeps_configured_memory_reservations() -> Result<()> {
+        let host = Tunables::default();
+        let u32_defaults = Tunables::default_u32();
+
+        // A 64-bit target keeps the host's defaults.
+        let mut config = Config::new();
+        config.target("aarch64-unknown-linux-gnu")?;
+        assert_eq!(
+            config.tunables.static_memory_bound,
+            host.static_memory_bound
+        );
+
+        // A 32-bit target uses smaller defaults, but only for the settings
+        // that weren't configured, whether before or after the target.
+        let mut config = Config::new();
+        config
+            .static_memory_maximum_size(1 << 30)
+            .target("armv7-unknown-linux-gnueabihf")?
+            .dynamic_memory_reserved_for_growth(1 << 16);
+        assert_eq!(config.tunables.static_memory_bound, (1 << 30) / 0x1_0000);
+        assert_eq!(
+            config.tunables.static_memory_offset_guard_size,
+            u32_defaults.static_memory_offset_guard_size
+        );
+        assert_eq!(config.tunables.dynamic_memory_growth_reserve, 1 << 16);
+        Ok(())
+    }
+}
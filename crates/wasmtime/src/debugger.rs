@@ -0,0 +1,297 @@
+//! An embedder-facing debugger for WebAssembly guests.
+//!
+//! When [`Config::guest_debug`](crate::Config::guest_debug) is enabled, code is
+//! compiled with a hook before every wasm instruction which calls into the
+//! store whenever it has breakpoints set or is single-stepping. The store's
+//! debug handler, configured with [`Store::debug_handler`], is then invoked
+//! with a [`DebugFrame`] describing the instruction about to execute, and
+//! decides with a [`DebugAction`] how execution continues.
+//!
+//! [`Store::debug_handler`]: crate::Store::debug_handler
+
+use crate::store::StoreOpaque;
+use crate::{
+    AsContext, AsContextMut, Extern, Module, StoreContext, StoreContextMut, Val, ValType,
+    WasmBacktrace,
+};
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use wasmtime_environ::{EntityIndex, FuncIndex, GlobalIndex, MemoryIndex};
+use wasmtime_runtime::{InstanceHandle, VMDebugValue};
+
+/// The reason a store's debug handler was invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// Execution reached a breakpoint added with
+    /// [`Store::add_breakpoint`](crate::Store::add_breakpoint).
+    Breakpoint,
+    /// The store was single-stepping, either because the handler previously
+    /// returned [`DebugAction::Step`] or because of
+    /// [`Store::debug_single_step`](crate::Store::debug_single_step).
+    Step,
+}
+
+/// How execution continues after a store's debug handler returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Run until the next breakpoint is reached.
+    Continue,
+    /// Stop again before the next wasm instruction is executed, in this
+    /// function or any other.
+    Step,
+}
+
+/// The state of a wasm function which is stopped before executing an
+/// instruction, passed to a store's debug handler.
+///
+/// Values of locals and of the operand stack are captured when the handler is
+/// invoked. Note that `funcref` values on the operand stack are reported as
+/// pointer-sized integers since their type isn't known at that point; `funcref`
+/// locals are reported as such.
+pub struct DebugFrame<'a, T> {
+    store: StoreContextMut<'a, T>,
+    instance: &'a InstanceHandle,
+    func_index: u32,
+    offset: u32,
+    locals: Vec<Val>,
+    stack: Vec<Val>,
+}
+
+impl<'a, T> DebugFrame<'a, T> {
+    /// Creates a frame for the function `func_index` of `instance`, stopped
+    /// before the instruction at `offset`.
+    ///
+    /// # Unsafety
+    ///
+    /// `locals` and `stack` must be values passed to the runtime's debug hook
+    /// by wasm code which is still on the stack.
+    pub(crate) unsafe fn new(
+        mut store: StoreContextMut<'a, T>,
+        instance: &'a InstanceHandle,
+        func_index: FuncIndex,
+        offset: u32,
+        locals: &[VMDebugValue],
+        stack: &[VMDebugValue],
+    ) -> DebugFrame<'a, T> {
+        let mut opaque = store.as_context_mut().opaque();
+        let locals = locals.iter().map(|v| read_value(&mut opaque, v)).collect();
+        let stack = stack.iter().map(|v| read_value(&mut opaque, v)).collect();
+        DebugFrame {
+            store,
+            instance,
+            func_index: func_index.as_u32(),
+            offset,
+            locals,
+            stack,
+        }
+    }
+
+    /// Returns the index of the stopped function in the function index space
+    /// of its module.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the offset of the instruction about to execute, relative to the
+    /// start of the function's body in the wasm module.
+    ///
+    /// This is the same kind of offset as
+    /// [`FrameInfo::func_offset`](crate::FrameInfo::func_offset) and the
+    /// `offset` passed to [`Store::add_breakpoint`](crate::Store::add_breakpoint).
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the values of the function's parameters followed by its
+    /// locals.
+    pub fn locals(&self) -> &[Val] {
+        &self.locals
+    }
+
+    /// Returns the values on the wasm operand stack of the function, with the
+    /// top of the stack last.
+    pub fn stack(&self) -> &[Val] {
+        &self.stack
+    }
+
+    /// Returns the value of the global at `index` in the global index space of
+    /// the stopped function's module, or `None` if there's no such global.
+    pub fn global(&mut self, index: u32) -> Option<Val> {
+        if index as usize >= self.instance.module().globals.len() {
+            return None;
+        }
+        let index = EntityIndex::Global(GlobalIndex::from_u32(index));
+        self.lookup(&index)?
+            .into_global()
+            .map(|g| g.get(&mut self.store))
+    }
+
+    /// Returns the memory at `index` in the memory index space of the stopped
+    /// function's module, or `None` if there's no such memory.
+    ///
+    /// The returned extern is either an [`Extern::Memory`] or an
+    /// [`Extern::SharedMemory`].
+    pub fn memory(&mut self, index: u32) -> Option<Extern> {
+        if index as usize >= self.instance.module().memory_plans.len() {
+            return None;
+        }
+        self.lookup(&EntityIndex::Memory(MemoryIndex::from_u32(index)))
+    }
+
+    /// Access the underlying data owned by this `Store`.
+    ///
+    /// Same as [`Store::data`](crate::Store::data)
+    pub fn data(&self) -> &T {
+        self.store.data()
+    }
+
+    /// Access the underlying data owned by this `Store`.
+    ///
+    /// Same as [`Store::data_mut`](crate::Store::data_mut)
+    pub fn data_mut(&mut self) -> &mut T {
+        self.store.data_mut()
+    }
+
+    /// Captures a backtrace of the wasm frames on the stack, starting with the
+    /// stopped function.
+    pub fn backtrace(&self) -> WasmBacktrace {
+        WasmBacktrace::capture(&self.store)
+    }
+
+    fn lookup(&mut self, index: &EntityIndex) -> Option<Extern> {
+        let export = self.instance.lookup_by_declaration(index);
+        let mut store = self.store.as_context_mut().opaque();
+        // Safety: the export was looked up in an instance owned by the store.
+        Some(unsafe { Extern::from_wasmtime_export(export, &mut store) })
+    }
+}
+
+impl<T> AsContext for DebugFrame<'_, T> {
+    type Data = T;
+    fn as_context(&self) -> StoreContext<'_, T> {
+        self.store.as_context()
+    }
+}
+
+impl<T> AsContextMut for DebugFrame<'_, T> {
+    fn as_context_mut(&mut self) -> StoreContextMut<'_, T> {
+        self.store.as_context_mut()
+    }
+}
+
+unsafe fn read_value(store: &mut StoreOpaque, value: &VMDebugValue) -> Val {
+    let ty = match value.ty {
+        0x7f => ValType::I32,
+        0x7e => ValType::I64,
+        0x7d => ValType::F32,
+        0x7c => ValType::F64,
+        0x7b => ValType::V128,
+        0x70 => ValType::FuncRef,
+        0x6f => ValType::ExternRef,
+        ty => panic!("unknown debug value type {:#x}", ty),
+    };
+    Val::read_value_from(store, value.value.as_ptr() as *const u128, ty)
+}
+
+/// A store's breakpoints and single-stepping state.
+#[derive(Default)]
+pub(crate) struct DebugState {
+    breakpoints: Vec<Breakpoint>,
+    single_step: bool,
+}
+
+struct Breakpoint {
+    module: Arc<wasmtime_environ::Module>,
+    func_index: FuncIndex,
+    offset: u32,
+}
+
+impl DebugState {
+    /// Returns whether compiled code needs to call the debug hook.
+    pub(crate) fn hooks_enabled(&self) -> bool {
+        self.single_step || !self.breakpoints.is_empty()
+    }
+
+    pub(crate) fn set_single_step(&mut self, enable: bool) {
+        self.single_step = enable;
+    }
+
+    pub(crate) fn add_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        offset: u32,
+    ) -> Result<()> {
+        if !module.engine().config().tunables.guest_debug {
+            bail!("cannot add a breakpoint to a module compiled without `Config::guest_debug`");
+        }
+        let env_module = module.compiled_module().module();
+        let func_index = FuncIndex::from_u32(func_index);
+        if env_module.defined_func_index(func_index).is_none()
+            || func_index.as_u32() as usize >= env_module.functions.len()
+        {
+            bail!(
+                "function index {} is not a function defined by the module",
+                func_index.as_u32()
+            );
+        }
+        if !self
+            .breakpoints
+            .iter()
+            .any(|b| b.matches(env_module, func_index, offset))
+        {
+            self.breakpoints.push(Breakpoint {
+                module: env_module.clone(),
+                func_index,
+                offset,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        offset: u32,
+    ) -> bool {
+        let env_module = module.compiled_module().module();
+        let func_index = FuncIndex::from_u32(func_index);
+        let len = self.breakpoints.len();
+        self.breakpoints
+            .retain(|b| !b.matches(env_module, func_index, offset));
+        self.breakpoints.len() != len
+    }
+
+    /// Returns why execution should stop before the instruction at `offset`
+    /// of `func_index` within `instance`, if it should.
+    pub(crate) fn event(
+        &self,
+        instance: &InstanceHandle,
+        func_index: FuncIndex,
+        offset: u32,
+    ) -> Option<DebugEvent> {
+        if self.single_step {
+            Some(DebugEvent::Step)
+        } else if self
+            .breakpoints
+            .iter()
+            .any(|b| b.matches(instance.module(), func_index, offset))
+        {
+            Some(DebugEvent::Breakpoint)
+        } else {
+            None
+        }
+    }
+}
+
+impl Breakpoint {
+    fn matches(
+        &self,
+        module: &Arc<wasmtime_environ::Module>,
+        func_index: FuncIndex,
+        offset: u32,
+    ) -> bool {
+        Arc::ptr_eq(&self.module, module) && self.func_index == func_index && self.offset == offset
+    }
+}
@@ -3,6 +3,7 @@ use crate::{Config, Trap};
 use anyhow::Result;
 #[cfg(feature = "parallel-compilation")]
 use rayon::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 #[cfg(feature = "cache")]
 use wasmtime_cache::CacheConfig;
@@ -41,6 +42,7 @@ struct EngineInner {
     compiler: Box<dyn wasmtime_environ::Compiler>,
     allocator: Box<dyn InstanceAllocator>,
     signatures: SignatureRegistry,
+    epoch: AtomicU64,
 }
 
 impl Engine {
@@ -53,6 +55,39 @@ impl Engine {
         wasmtime_runtime::init_traps(crate::module::GlobalModuleRegistry::is_wasm_pc);
         debug_builtins::ensure_exported();
 
+        // Only some backends can compile tail calls, so reject the proposal
+        // up front rather than failing to compile the first `return_call`.
+        #[cfg(compiler)]
+        if config.features.tail_call {
+            use target_lexicon::Architecture;
+            match config.compiler.triple().architecture {
+                Architecture::X86_64
+                | Architecture::Aarch64(_)
+                | Architecture::Riscv64(_)
+                | Architecture::Arm(_) => {}
+                arch => anyhow::bail!("the tail-call proposal is not supported on {}", arch),
+            }
+        }
+
+        // Thrown exceptions are unwound by the runtime, which only knows how to
+        // resume at a landing pad on some targets, so reject the proposal up
+        // front elsewhere.
+        #[cfg(compiler)]
+        if config.features.exceptions {
+            use target_lexicon::{Architecture, OperatingSystem};
+            let triple = config.compiler.triple();
+            match triple.architecture {
+                Architecture::X86_64 | Architecture::Aarch64(_) | Architecture::Riscv64(_) => {}
+                arch => anyhow::bail!("the exceptions proposal is not supported on {}", arch),
+            }
+            if let OperatingSystem::Windows = triple.operating_system {
+                anyhow::bail!(
+                    "the exceptions proposal is not supported on {}",
+                    triple.operating_system
+                );
+            }
+        }
+
         let registry = SignatureRegistry::new();
         let mut config = config.clone();
         let allocator = config.build_allocator()?;
@@ -65,6 +100,7 @@ impl Engine {
                 config,
                 allocator,
                 signatures: registry,
+                epoch: AtomicU64::new(0),
             }),
         })
     }
@@ -119,6 +155,37 @@ impl Engine {
         &self.inner.signatures
     }
 
+    pub(crate) fn epoch_counter(&self) -> &AtomicU64 {
+        &self.inner.epoch
+    }
+
+    pub(crate) fn current_epoch(&self) -> u64 {
+        self.epoch_counter().load(Ordering::Relaxed)
+    }
+
+    /// Increments the epoch.
+    ///
+    /// When using epoch-based interruption, currently-executing Wasm
+    /// code within this engine will trap or yield "soon" when the
+    /// epoch deadline is reached or exceeded. (The configuration, and
+    /// the deadline, are set on the `Store`.) The intent of the
+    /// design is for this method to be called by the embedder at some
+    /// regular cadence, for example by a thread that wakes up at some
+    /// interval, or by a signal handler.
+    ///
+    /// See [`Config::epoch_interruption`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption and pointers
+    /// to the other relevant methods.
+    ///
+    /// ## Signal Safety
+    ///
+    /// This method is signal-safe: it does not make any syscalls, and
+    /// performs only an atomic increment to the epoch value in
+    /// memory.
+    pub fn increment_epoch(&self) {
+        self.inner.epoch.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Ahead-of-time (AOT) compiles a WebAssembly module.
     ///
     /// The `bytes` provided must be in one of two formats:
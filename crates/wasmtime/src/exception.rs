@@ -0,0 +1,237 @@
+use crate::store::{StoreData, StoreOpaque, Stored};
+use crate::{AsContext, AsContextMut, Engine, TagType, Trap, Val, ValType};
+use anyhow::{bail, Result};
+use wasmtime_runtime::{ExportTag, InstanceHandle, VMContext, VMException, VMSharedSignatureIndex};
+
+/// A WebAssembly exception tag.
+///
+/// Every exception thrown by WebAssembly, or by the host through
+/// [`Trap::throw`](crate::Trap::throw), carries a tag which identifies the
+/// kind of exception it is. A `catch` clause only catches exceptions with the
+/// tag it names, and the tag's type describes the values, or payload, carried
+/// by its exceptions.
+///
+/// Tags are either defined by wasm modules, in which case every instantiation
+/// of the module creates distinct tags, or created by the host with
+/// [`Tag::new`]. Like other items a [`Tag`] "belongs" to the store it was
+/// created within, and methods will panic if used with a different store.
+///
+/// This is a part of the [WebAssembly exception handling proposal][proposal].
+///
+/// [proposal]: https://github.com/webassembly/exception-handling
+#[derive(Copy, Clone, Debug)]
+#[repr(transparent)] // here for the C API
+pub struct Tag(Stored<ExportTag>);
+
+impl Tag {
+    /// Creates a new tag of the type `ty` within `store`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ty` has `externref` parameters, which aren't
+    /// supported in exception payloads.
+    pub fn new(mut store: impl AsContextMut, ty: TagType) -> Result<Tag> {
+        let mut store = store.as_context_mut().opaque();
+        if ty.params().any(|ty| ty == ValType::ExternRef) {
+            bail!("exception tags with externref payloads are not supported");
+        }
+        let signature = store.register_host_tag_signature(ty.func_type().as_wasm_func_type());
+        let id = store.exceptions().allocate_tag(signature);
+        Ok(Tag::from_wasmtime_tag(
+            ExportTag { id, signature },
+            &mut store,
+        ))
+    }
+
+    /// Returns the type of this tag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this tag.
+    pub fn ty(&self, store: impl AsContext) -> TagType {
+        let store = store.as_context();
+        self.wasmtime_ty(store.engine(), &store[self.0])
+    }
+
+    fn wasmtime_ty(&self, engine: &Engine, export: &ExportTag) -> TagType {
+        // Signatures should always be registered in the engine's registry of
+        // shared signatures, so we should be able to unwrap safely here.
+        TagType::from_wasm_func_type(
+            engine
+                .signatures()
+                .lookup_type(export.signature)
+                .expect("signature should be registered"),
+        )
+    }
+
+    /// Returns whether `self` and `other` are the same tag.
+    ///
+    /// Tags are compared by identity rather than by type, so for example the
+    /// same tag exported from an instance twice is the same, but tags defined
+    /// by two instantiations of a module are not.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own both tags.
+    pub fn same(&self, other: &Tag, store: impl AsContext) -> bool {
+        let store = store.as_context();
+        store[self.0].id == store[other.0].id
+    }
+
+    pub(crate) fn from_wasmtime_tag(
+        wasmtime_export: ExportTag,
+        store: &mut StoreOpaque<'_>,
+    ) -> Tag {
+        Tag(store.store_data_mut().insert(wasmtime_export))
+    }
+
+    pub(crate) fn sig_index(&self, data: &StoreData) -> VMSharedSignatureIndex {
+        data[self.0].signature
+    }
+
+    pub(crate) fn wasmtime_export(&self, store: &StoreOpaque<'_>) -> ExportTag {
+        store[self.0]
+    }
+
+    pub(crate) fn comes_from_same_store(&self, store: &StoreOpaque<'_>) -> bool {
+        store.store_data().contains(self.0)
+    }
+}
+
+/// An exception thrown by WebAssembly or by the host.
+///
+/// An exception thrown by WebAssembly that isn't caught by WebAssembly results
+/// in a [`Trap`](crate::Trap) when it reaches the host, from which the
+/// exception can be retrieved with [`Trap::exception`](crate::Trap::exception).
+/// Conversely, a host function called from WebAssembly can throw an exception
+/// for WebAssembly to catch by returning the trap created by
+/// [`Trap::throw`](crate::Trap::throw).
+#[derive(Clone, Debug)]
+pub struct Exception {
+    tag: Tag,
+    payload: Vec<Val>,
+}
+
+impl Exception {
+    /// Creates a new exception with the tag `tag` which carries the values
+    /// `payload`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `payload` doesn't match the type of `tag`, or if
+    /// `tag` or any of the values come from a store other than `store`.
+    pub fn new(mut store: impl AsContextMut, tag: Tag, payload: Vec<Val>) -> Result<Exception> {
+        let store = store.as_context_mut().opaque();
+        if !tag.comes_from_same_store(&store)
+            || !payload.iter().all(|v| v.comes_from_same_store(&store))
+        {
+            bail!("cross-`Store` exceptions are not supported");
+        }
+        let ty = tag.wasmtime_ty(store.engine(), &store[tag.0]);
+        if payload.len() != ty.params().len()
+            || payload.iter().zip(ty.params()).any(|(v, ty)| v.ty() != ty)
+        {
+            bail!("exception payload does not match the type of its tag");
+        }
+        Ok(Exception { tag, payload })
+    }
+
+    /// Returns the tag of this exception.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// Returns the values carried by this exception.
+    pub fn payload(&self) -> &[Val] {
+        &self.payload
+    }
+
+    pub(crate) unsafe fn from_wasmtime_exception(
+        exception: VMException,
+        store: &mut StoreOpaque<'_>,
+    ) -> Exception {
+        let signature = store.exceptions().tag_signature(exception.tag);
+        let tag = Tag::from_wasmtime_tag(
+            ExportTag {
+                id: exception.tag,
+                signature,
+            },
+            store,
+        );
+        let ty = tag.wasmtime_ty(store.engine(), &store[tag.0]);
+        let payload = exception
+            .payload
+            .iter()
+            .zip(ty.params())
+            .map(|(raw, ty)| Val::read_value_from(store, raw, ty))
+            .collect();
+        Exception { tag, payload }
+    }
+
+    pub(crate) unsafe fn into_wasmtime_exception(self, store: &mut StoreOpaque<'_>) -> VMException {
+        let tag = self.tag.wasmtime_export(store).id;
+        let payload = self
+            .payload
+            .into_iter()
+            .map(|val| {
+                let mut raw = 0;
+                val.write_value_to(store, &mut raw);
+                raw
+            })
+            .collect();
+        VMException { tag, payload }
+    }
+}
+
+/// Makes the exception carried by `trap`, if any, pending in `store`, so that
+/// it's thrown into the wasm that called the host function returning `trap`
+/// rather than trapping.
+///
+/// Returns whether it did, which it doesn't if `trap` doesn't carry an
+/// exception or if exceptions aren't enabled, in which case wasm has no way of
+/// observing it.
+pub(crate) fn throw_into_wasm(store: &mut StoreOpaque<'_>, trap: &Trap) -> Result<bool, Trap> {
+    let exception = match trap.exception() {
+        Some(exception) if store.engine().config().features.exceptions => exception.clone(),
+        _ => return Ok(false),
+    };
+    if !exception.tag.comes_from_same_store(store) {
+        return Err(Trap::new("cross-`Store` exceptions are not supported"));
+    }
+    unsafe {
+        let exception = exception.into_wasmtime_exception(store);
+        store.exceptions().throw(exception);
+    }
+    Ok(true)
+}
+
+/// Unwinds to the wasm that handles the exception made pending by
+/// `throw_into_wasm` in the store of `caller_vmctx`.
+///
+/// # Safety
+///
+/// Only safe to call from a host function called by wasm with `caller_vmctx`,
+/// when no Rust destructors are on the stack, as for
+/// `wasmtime_runtime::raise_user_trap`.
+pub(crate) unsafe fn raise_pending(caller_vmctx: *mut VMContext) -> ! {
+    let store = InstanceHandle::from_vmctx(caller_vmctx).store();
+    let (_, modules) = (*store).exceptions();
+    wasmtime_runtime::raise_exception(modules)
+}
+
+/// Raises `trap` from a host function called by wasm with `caller_vmctx`,
+/// by unwinding to the wasm that handles its exception if `throw_into_wasm`
+/// made that pending, or as a trap otherwise.
+///
+/// # Safety
+///
+/// The same as `raise_pending`, except that `trap` is dropped here.
+pub(crate) unsafe fn raise_from_host(caller_vmctx: *mut VMContext, trap: Trap) -> ! {
+    let store = InstanceHandle::from_vmctx(caller_vmctx).store();
+    let (exceptions, _) = (*store).exceptions();
+    if exceptions.pending().is_some() {
+        drop(trap);
+        raise_pending(caller_vmctx)
+    }
+    wasmtime_runtime::raise_user_trap(Box::new(trap))
+}
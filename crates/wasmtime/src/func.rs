@@ -1,7 +1,7 @@
 use crate::store::{StoreData, StoreInnermost, StoreOpaque, Stored};
 use crate::{
-    AsContext, AsContextMut, Engine, Extern, FuncType, Instance, InterruptHandle, StoreContext,
-    StoreContextMut, Trap, Val, ValType,
+    AsContext, AsContextMut, Engine, Exception, Extern, FuncType, Instance, InterruptHandle,
+    StoreContext, StoreContextMut, Trap, Val, ValType, WasmBacktrace,
 };
 use anyhow::{bail, Context as _, Result};
 use smallvec::{smallvec, SmallVec};
@@ -872,7 +872,22 @@ impl Func {
         let mut returns: SmallVec<[Val; STACK_RETURNS]> =
             smallvec![Val::null(); ty.results().len()];
 
-        func(caller.sub_caller(), &args, &mut returns)?;
+        if let Err(trap) = func(caller.sub_caller(), &args, &mut returns) {
+            // An exception for wasm to catch is thrown by `stub_fn` once
+            // nothing here needs dropping anymore. Control then returns to
+            // wasm at the landing pad that catches it, so the exit hook runs
+            // as if the call had returned normally.
+            if crate::exception::throw_into_wasm(
+                &mut caller.store.as_context_mut().opaque(),
+                &trap,
+            )? {
+                if let Err(trap) = caller.store.0.exiting_native_hook() {
+                    caller.store.0.exceptions().take_pending();
+                    return Err(trap);
+                }
+            }
+            return Err(trap);
+        }
 
         // Unlike our arguments we need to dynamically check that the return
         // values produced are correct. There could be a bug in `func` that
@@ -1055,8 +1070,25 @@ pub(crate) fn invoke_wasm_and_catch_traps<T>(
             closure,
         );
         exit_wasm(store, exit);
+
+        // Wasm can't unwind through the host, so an exception that unwinds
+        // all the wasm frames since it was entered is left pending and
+        // reported as a trap. Any exception pending alongside a real trap is
+        // discarded.
+        let exception = store.0.exceptions().take_pending();
         store.0.entering_native_hook()?;
-        result.map_err(Trap::from_runtime)
+        match result {
+            Ok(()) => Ok(()),
+            Err(wasmtime_runtime::Trap::Exception { backtrace }) => {
+                let exception = exception.expect("unwound without a pending exception");
+                let exception = Exception::from_wasmtime_exception(
+                    exception,
+                    &mut store.as_context_mut().opaque(),
+                );
+                Err(Trap::new_uncaught_exception(exception, backtrace))
+            }
+            Err(trap) => Err(Trap::from_runtime(trap)),
+        }
     }
 }
 
@@ -1518,7 +1550,7 @@ impl<T> Caller<'_, T> {
         })
     }
 
-    fn sub_caller(&mut self) -> Caller<'_, T> {
+    pub(crate) fn sub_caller(&mut self) -> Caller<'_, T> {
         Caller {
             store: self.store.as_context_mut(),
             caller: self.caller,
@@ -1527,9 +1559,9 @@ impl<T> Caller<'_, T> {
 
     /// Looks up an export from the caller's module by the `name` given.
     ///
-    /// Note that this function is only implemented for the `Extern::Memory`
-    /// and the `Extern::Func` types currently. No other exported structures
-    /// can be acquired through this method.
+    /// Note that this function is only implemented for the `Extern::Memory`,
+    /// `Extern::SharedMemory` and the `Extern::Func` types currently. No other
+    /// exported structures can be acquired through this method.
     ///
     /// Note that when accessing and calling exported functions, one should
     /// adhere to the guidelines of the interface types proposal.  This method
@@ -1567,6 +1599,7 @@ impl<T> Caller<'_, T> {
         {
             Extern::Func(f) => Some(Extern::Func(f)),
             Extern::Memory(f) => Some(Extern::Memory(f)),
+            Extern::SharedMemory(f) => Some(Extern::SharedMemory(f)),
             // Intentionally ignore other Extern items here since this API is
             // supposed to be a temporary stop-gap until interface types.
             _ => None,
@@ -1600,6 +1633,14 @@ impl<T> Caller<'_, T> {
         self.store.interrupt_handle()
     }
 
+    /// Captures a backtrace of the WebAssembly frames that led to this host
+    /// function being called.
+    ///
+    /// Same as [`Store::backtrace`](crate::Store::backtrace).
+    pub fn backtrace(&self) -> WasmBacktrace {
+        WasmBacktrace::capture(self)
+    }
+
     /// Perform garbage collection of `ExternRef`s.
     ///
     /// Same as [`Store::gc`](crate::Store::gc).
@@ -1722,6 +1763,7 @@ macro_rules! impl_into_func {
                     enum CallResult<U> {
                         Ok(U),
                         Trap(Box<dyn Error + Send + Sync>),
+                        Throw,
                         Panic(Box<dyn std::any::Any + Send>),
                     }
 
@@ -1776,7 +1818,13 @@ macro_rules! impl_into_func {
                                 } else {
                                     match ret.into_abi_for_ret(&mut store, retptr) {
                                         Ok(val) => CallResult::Ok(val),
-                                        Err(trap) => CallResult::Trap(trap.into()),
+                                        Err(trap) => {
+                                            match crate::exception::throw_into_wasm(&mut store, &trap) {
+                                                Ok(true) => CallResult::Throw,
+                                                Ok(false) => CallResult::Trap(trap.into()),
+                                                Err(trap) => CallResult::Trap(trap.into()),
+                                            }
+                                        }
                                     }
                                 }
 
@@ -1787,6 +1835,7 @@ macro_rules! impl_into_func {
                     match result {
                         CallResult::Ok(val) => val,
                         CallResult::Trap(trap) => raise_user_trap(trap),
+                        CallResult::Throw => crate::exception::raise_pending(caller_vmctx),
                         CallResult::Panic(panic) => wasmtime_runtime::resume_panic(panic),
                     }
                 }
@@ -5,7 +5,7 @@ use anyhow::{bail, Result};
 use std::marker;
 use std::mem::{self, MaybeUninit};
 use std::ptr;
-use wasmtime_runtime::{VMContext, VMFunctionBody};
+use wasmtime_runtime::{VMContext, VMFunctionBody, VMTrampoline};
 
 /// A statically typed WebAssembly function.
 ///
@@ -132,26 +132,40 @@ where
         // efficient to move in memory. This closure is actually invoked on the
         // other side of a C++ shim, so it can never be inlined enough to make
         // the memory go away, so the size matters here for performance.
-        let mut captures = (
-            self.func.caller_checked_anyfunc(store.0),
-            MaybeUninit::uninit(),
-            params,
-            false,
-        );
+        //
+        // With tail calls, wasm may return with the stack pointer somewhere
+        // other than where it was called with it, which only compiled code
+        // copes with, so go through the function's trampoline.
+        let anyfunc = self.func.caller_checked_anyfunc(store.0);
+        let trampoline = if store.engine().config().features.tail_call {
+            Some(store.0.lookup_trampoline(anyfunc.as_ref()))
+        } else {
+            None
+        };
+        let mut captures = (anyfunc, trampoline, MaybeUninit::uninit(), params, false);
 
         let result = invoke_wasm_and_catch_traps(store, |callee| {
-            let (anyfunc, ret, params, returned) = &mut captures;
+            let (anyfunc, trampoline, ret, params, returned) = &mut captures;
             let anyfunc = anyfunc.as_ref();
-            let result = Params::invoke::<Results>(
-                anyfunc.func_ptr.as_ptr(),
-                anyfunc.vmctx,
-                callee,
-                *params,
-            );
+            let result = match trampoline {
+                Some(trampoline) => Params::invoke_trampoline::<Results>(
+                    *trampoline,
+                    anyfunc.func_ptr.as_ptr(),
+                    anyfunc.vmctx,
+                    callee,
+                    *params,
+                ),
+                None => Params::invoke::<Results>(
+                    anyfunc.func_ptr.as_ptr(),
+                    anyfunc.vmctx,
+                    callee,
+                    *params,
+                ),
+            };
             ptr::write(ret.as_mut_ptr(), result);
             *returned = true
         });
-        let (_, ret, _, returned) = captures;
+        let (_, _, ret, _, returned) = captures;
         debug_assert_eq!(result.is_ok(), returned);
         result?;
         Ok(Results::from_abi(
@@ -313,6 +327,14 @@ pub unsafe trait WasmParams: Send {
         vmctx2: *mut VMContext,
         abi: Self::Abi,
     ) -> R::ResultAbi;
+    #[doc(hidden)]
+    unsafe fn invoke_trampoline<R: WasmResults>(
+        trampoline: VMTrampoline,
+        func: *const VMFunctionBody,
+        vmctx1: *mut VMContext,
+        vmctx2: *mut VMContext,
+        abi: Self::Abi,
+    ) -> R::ResultAbi;
 }
 
 // Forward an impl from `T` to `(T,)` for convenience if there's only one
@@ -338,6 +360,15 @@ where
     ) -> R::ResultAbi {
         <(T,) as WasmParams>::invoke::<R>(func, vmctx1, vmctx2, abi)
     }
+    unsafe fn invoke_trampoline<R: WasmResults>(
+        trampoline: VMTrampoline,
+        func: *const VMFunctionBody,
+        vmctx1: *mut VMContext,
+        vmctx2: *mut VMContext,
+        abi: Self::Abi,
+    ) -> R::ResultAbi {
+        <(T,) as WasmParams>::invoke_trampoline::<R>(trampoline, func, vmctx1, vmctx2, abi)
+    }
 }
 
 macro_rules! impl_wasm_params {
@@ -407,6 +438,26 @@ macro_rules! impl_wasm_params {
                     fnptr(vmctx1, vmctx2, $($t,)* retptr)
                 })
             }
+
+            unsafe fn invoke_trampoline<R: WasmResults>(
+                trampoline: VMTrampoline,
+                func: *const VMFunctionBody,
+                vmctx1: *mut VMContext,
+                vmctx2: *mut VMContext,
+                abi: Self::Abi,
+            ) -> R::ResultAbi {
+                // Trampolines take arguments and return results in 16-byte
+                // slots, of which there are at most 16 either way.
+                let mut values = [0u128; 16];
+                let ($($t,)*) = abi;
+                let mut _i = 0;
+                $(
+                    ptr::write(values.as_mut_ptr().add(_i).cast::<$t::Abi>(), $t);
+                    _i += 1;
+                )*
+                trampoline(vmctx1, vmctx2, func, values.as_mut_ptr());
+                R::read_abi(values.as_ptr())
+            }
         }
     };
 }
@@ -424,6 +475,8 @@ pub unsafe trait WasmResults: WasmParams {
     type ResultAbi: HostAbi;
     #[doc(hidden)]
     unsafe fn from_abi(store: &mut StoreOpaque, abi: Self::ResultAbi) -> Self;
+    #[doc(hidden)]
+    unsafe fn read_abi(values: *const u128) -> Self::ResultAbi;
 }
 
 // Forwards from a bare type `T` to the 1-tuple type `(T,)`
@@ -436,6 +489,10 @@ where
     unsafe fn from_abi(store: &mut StoreOpaque, abi: Self::ResultAbi) -> Self {
         <(T,) as WasmResults>::from_abi(store, abi).0
     }
+
+    unsafe fn read_abi(values: *const u128) -> Self::ResultAbi {
+        <(T,) as WasmResults>::read_abi(values)
+    }
 }
 
 macro_rules! impl_wasm_results {
@@ -450,6 +507,15 @@ macro_rules! impl_wasm_results {
                 let ($($t,)*) = abi;
                 ($($t::from_abi($t, store),)*)
             }
+
+            unsafe fn read_abi(values: *const u128) -> Self::ResultAbi {
+                let mut _i = 0;
+                $(
+                    let $t = ptr::read(values.add(_i).cast::<$t::Abi>());
+                    _i += 1;
+                )*
+                ($($t,)*)
+            }
         }
     };
 }
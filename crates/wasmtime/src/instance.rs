@@ -4,21 +4,25 @@ use crate::store::{InstanceId, StoreData, StoreOpaque, Stored};
 use crate::types::matching;
 use crate::{
     AsContext, AsContextMut, Engine, Export, Extern, ExternType, Func, Global, InstanceType,
-    Memory, Module, StoreContextMut, Table, Trap, TypedFunc,
+    Memory, Module, SharedMemory, StoreContextMut, Table, Trap, TypedFunc,
 };
 use anyhow::{anyhow, bail, Context, Error, Result};
 use std::mem;
 use std::sync::Arc;
 use wasmtime_environ::{
     EntityIndex, EntityType, FuncIndex, GlobalIndex, Initializer, InstanceIndex, MemoryIndex,
-    ModuleIndex, PrimaryMap, TableIndex,
+    ModuleIndex, PrimaryMap, TableIndex, TagIndex,
 };
 use wasmtime_jit::TypeTables;
 use wasmtime_runtime::{
-    Imports, InstanceAllocationRequest, InstantiationError, VMContext, VMFunctionBody,
-    VMFunctionImport, VMGlobalImport, VMMemoryImport, VMTableImport,
+    ExportTag, Imports, InstanceAllocationRequest, InstantiationError, VMFunctionImport,
+    VMGlobalImport, VMMemoryImport, VMTableImport,
 };
 
+mod snapshot;
+
+pub use self::snapshot::InstanceSnapshot;
+
 /// An instantiated WebAssembly module.
 ///
 /// This type represents the instantiation of a [`Module`]. Once instantiated
@@ -400,6 +404,18 @@ impl Instance {
         self.get_export(store, name)?.into_memory()
     }
 
+    /// Looks up an exported [`SharedMemory`] value by name.
+    ///
+    /// Returns `None` if there was no export named `name`, or if there was but
+    /// it wasn't a shared memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn get_shared_memory(&self, store: impl AsContextMut, name: &str) -> Option<SharedMemory> {
+        self.get_export(store, name)?.into_shared_memory()
+    }
+
     /// Looks up an exported [`Global`] value by name.
     ///
     /// Returns `None` if there was no export named `name`, or if there was but
@@ -411,6 +427,58 @@ impl Instance {
     pub fn get_global(&self, store: impl AsContextMut, name: &str) -> Option<Global> {
         self.get_export(store, name)?.into_global()
     }
+
+    /// Captures the current contents of every memory, mutable global and table
+    /// defined by this instance.
+    ///
+    /// The returned [`InstanceSnapshot`] can be applied with
+    /// [`Instance::restore`], for example to pre-initialize fresh instances of
+    /// the same module or to reset this instance to a known state between
+    /// requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this instance was created by a [`Linker`] rather
+    /// than by instantiating a module, or if a table or global holds a value
+    /// which can't be snapshotted: a non-null `externref`, or a `funcref` to a
+    /// function which isn't defined or imported by this instance.
+    ///
+    /// [`Linker`]: crate::Linker
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn snapshot(&self, mut store: impl AsContextMut) -> Result<InstanceSnapshot> {
+        InstanceSnapshot::capture(store.as_context_mut(), self)
+    }
+
+    /// Restores the state captured by [`Instance::snapshot`] into this
+    /// instance.
+    ///
+    /// This instance should be an instance of the same module as the one the
+    /// snapshot was taken from, with equivalent imports. Memories and tables
+    /// are grown to the snapshot's sizes as necessary, and the entire contents
+    /// of memories, mutable globals and tables are overwritten.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot doesn't match the memories, globals and
+    /// tables defined by this instance, if it's corrupt, or if a memory or
+    /// table has grown larger than it was in the snapshot, since they can't be
+    /// shrunk. In these cases the instance is left unmodified.
+    ///
+    /// Also returns an error if growing a memory or table to the snapshot's
+    /// size fails, for example due to a
+    /// [`ResourceLimiter`](crate::ResourceLimiter). In that case the memories
+    /// and tables grown before it keep their new sizes, but the contents of
+    /// the instance are otherwise left unmodified.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn restore(&self, mut store: impl AsContextMut, snapshot: &InstanceSnapshot) -> Result<()> {
+        snapshot.restore(store.as_context_mut(), self)
+    }
 }
 
 struct Instantiator<'a> {
@@ -424,6 +492,7 @@ struct ImportsBuilder<'a> {
     tables: PrimaryMap<TableIndex, VMTableImport>,
     memories: PrimaryMap<MemoryIndex, VMMemoryImport>,
     globals: PrimaryMap<GlobalIndex, VMGlobalImport>,
+    tags: PrimaryMap<TagIndex, ExportTag>,
     instances: PrimaryMap<InstanceIndex, Instance>,
     modules: PrimaryMap<ModuleIndex, Module>,
     initializer: usize,
@@ -570,14 +639,14 @@ impl<'a> Instantiator<'a> {
                     ImportSource::Externs(list) => {
                         let (head, remaining) = list.split_first().unwrap();
                         *list = remaining;
-                        self.cur.push(head.clone(), store);
+                        self.cur.push(head.clone(), store)?;
                     }
                     ImportSource::Definitions(list) => {
                         let (head, remaining) = list.split_first().unwrap();
                         *list = remaining;
                         // This unsafety is encapsulated with
                         // `Instantiator::new`, documented above.
-                        self.cur.push(unsafe { head.to_extern(store) }, store);
+                        self.cur.push(unsafe { head.to_extern(store) }, store)?;
                     }
 
                     // Otherwise if arguments are coming from our outer
@@ -607,6 +676,9 @@ impl<'a> Instantiator<'a> {
                             EntityIndex::Memory(i) => {
                                 self.cur.memories.push(outer.memories[i]);
                             }
+                            EntityIndex::Tag(i) => {
+                                self.cur.tags.push(outer.tags[i]);
+                            }
                             EntityIndex::Module(i) => {
                                 self.cur.modules.push(outer.modules[i].clone());
                             }
@@ -624,7 +696,7 @@ impl<'a> Instantiator<'a> {
             Some(Initializer::AliasInstanceExport { instance, export }) => {
                 let instance = self.cur.instances[*instance];
                 let export = instance._get_export(store, export).unwrap();
-                self.cur.push(export, store);
+                self.cur.push(export, store)?;
             }
 
             // A recursive instantiation of an instance.
@@ -732,12 +804,13 @@ impl<'a> Instantiator<'a> {
                     .allocator()
                     .allocate(InstanceAllocationRequest {
                         module: compiled_module.module().clone(),
-                        finished_functions: compiled_module.finished_functions(),
+                        finished_functions: compiled_module.function_entries(),
                         imports: self.cur.build(),
                         shared_signatures: self.cur.module.signatures().as_module_map().into(),
                         host_state: Box::new(Instance(instance_to_be)),
                         store: Some(store.traitobj),
                         wasm_data: compiled_module.wasm_data(),
+                        memory_images: self.cur.module.memory_images(),
                     })?;
 
             // The instance still has lots of setup, for example
@@ -847,12 +920,18 @@ impl<'a> Instantiator<'a> {
         };
         let vmctx = instance.vmctx_ptr();
         unsafe {
+            // Go through the trampoline rather than calling the function
+            // directly, since with tail calls it may return with the stack
+            // pointer moved.
+            let anyfunc = f.anyfunc.as_ref();
+            let trampoline = store.0.lookup_trampoline(anyfunc);
+            let mut values: [u128; 0] = [];
             super::func::invoke_wasm_and_catch_traps(store, |_default_callee| {
-                mem::transmute::<
-                    *const VMFunctionBody,
-                    unsafe extern "C" fn(*mut VMContext, *mut VMContext),
-                >(f.anyfunc.as_ref().func_ptr.as_ptr())(
-                    f.anyfunc.as_ref().vmctx, vmctx
+                trampoline(
+                    anyfunc.vmctx,
+                    vmctx,
+                    anyfunc.func_ptr.as_ptr(),
+                    values.as_mut_ptr(),
                 )
             })?;
         }
@@ -869,6 +948,7 @@ impl<'a> ImportsBuilder<'a> {
             tables: PrimaryMap::with_capacity(raw.num_imported_tables),
             memories: PrimaryMap::with_capacity(raw.num_imported_memories),
             globals: PrimaryMap::with_capacity(raw.num_imported_globals),
+            tags: PrimaryMap::with_capacity(raw.num_imported_tags),
             instances: PrimaryMap::with_capacity(raw.instances.len()),
             modules: PrimaryMap::with_capacity(raw.modules.len()),
             module: module.clone(),
@@ -876,7 +956,7 @@ impl<'a> ImportsBuilder<'a> {
         }
     }
 
-    fn push(&mut self, item: Extern, store: &mut StoreOpaque<'_>) {
+    fn push(&mut self, item: Extern, store: &mut StoreOpaque<'_>) -> Result<()> {
         match item {
             Extern::Func(i) => {
                 self.functions.push(i.vmimport(store));
@@ -890,13 +970,20 @@ impl<'a> ImportsBuilder<'a> {
             Extern::Memory(i) => {
                 self.memories.push(i.vmimport(store));
             }
+            Extern::SharedMemory(i) => {
+                self.memories.push(i.vmimport(store)?);
+            }
             Extern::Instance(i) => {
                 self.instances.push(i);
             }
             Extern::Module(m) => {
                 self.modules.push(m);
             }
+            Extern::Tag(t) => {
+                self.tags.push(t.wasmtime_export(store));
+            }
         }
+        Ok(())
     }
 
     fn build(&self) -> Imports<'_> {
@@ -905,6 +992,7 @@ impl<'a> ImportsBuilder<'a> {
             globals: self.globals.values().as_slice(),
             memories: self.memories.values().as_slice(),
             functions: self.functions.values().as_slice(),
+            tags: self.tags.values().as_slice(),
         }
     }
 }
@@ -0,0 +1,415 @@
+use super::{Instance, InstanceData};
+use crate::store::StoreOpaque;
+use crate::{AsContextMut, Extern, Func, Global, Mutability, StoreContextMut, Table, Val, ValType};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use wasmtime_environ::WASM_PAGE_SIZE;
+use wasmtime_environ::{EntityIndex, EntityRef, FuncIndex, GlobalIndex, MemoryIndex, TableIndex};
+
+/// The granularity at which memory contents are checked for being all zeros,
+/// and left out of the snapshot if so.
+const CHUNK_SIZE: usize = 4096;
+
+/// A snapshot of the mutable state of an [`Instance`]: the contents of every
+/// memory, mutable global and table that it defines.
+///
+/// Snapshots are created with [`Instance::snapshot`] and applied with
+/// [`Instance::restore`], typically to a fresh instance of the same
+/// [`Module`](crate::Module). They can also be serialized to bytes and
+/// deserialized, possibly in another process, to pre-initialize instances.
+///
+/// Imported memories, globals and tables aren't part of the snapshot since
+/// they're owned by whoever provided them. Immutable globals aren't included
+/// either, since instantiation already determines their values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstanceSnapshot {
+    memories: Vec<MemorySnapshot>,
+    globals: Vec<Option<Value>>,
+    tables: Vec<TableSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct MemorySnapshot {
+    /// The size of the memory, in bytes.
+    size: usize,
+    /// Runs of bytes which aren't all zero, along with their offsets.
+    segments: Vec<(usize, Vec<u8>)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TableSnapshot {
+    element: RefType,
+    elements: Vec<Option<u32>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum RefType {
+    FuncRef,
+    ExternRef,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Value {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    V128([u8; 16]),
+    FuncRef(Option<u32>),
+    NullExternRef,
+}
+
+/// The memories, globals and tables defined by an instance.
+struct DefinedItems {
+    memories: Vec<Extern>,
+    globals: Vec<Global>,
+    tables: Vec<Table>,
+}
+
+impl InstanceSnapshot {
+    /// Serializes this snapshot into a list of bytes which can later be passed
+    /// to [`InstanceSnapshot::deserialize`].
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Deserializes a snapshot previously produced by
+    /// [`InstanceSnapshot::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<InstanceSnapshot> {
+        bincode::deserialize(bytes).context("failed to deserialize instance snapshot")
+    }
+
+    pub(super) fn capture<T>(
+        mut store: StoreContextMut<'_, T>,
+        instance: &Instance,
+    ) -> Result<InstanceSnapshot> {
+        let items = defined_items(&mut store.as_context_mut().opaque(), instance)?;
+
+        let memories = items
+            .memories
+            .iter()
+            .map(|memory| {
+                let data = memory_data(&mut store, memory);
+                // Safety: non-shared memories are borrowed through the store
+                // for the duration of this closure, and shared memories are
+                // only read here, racing with other threads as they would in
+                // wasm.
+                let data = unsafe { &*data };
+                MemorySnapshot {
+                    size: data.len(),
+                    segments: nonzero_segments(data),
+                }
+            })
+            .collect();
+
+        let globals = items
+            .globals
+            .iter()
+            .map(|global| match global.ty(&store).mutability() {
+                Mutability::Const => Ok(None),
+                Mutability::Var => {
+                    let val = global.get(&mut store);
+                    Ok(Some(to_value(&mut store, instance, val)?))
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        let tables = items
+            .tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| {
+                let element = match table.ty(&store).element() {
+                    ValType::FuncRef => RefType::FuncRef,
+                    _ => RefType::ExternRef,
+                };
+                let elements = (0..table.size(&store))
+                    .map(|j| {
+                        let val = table.get(&mut store, j).unwrap();
+                        match to_value(&mut store, instance, val) {
+                            Ok(Value::FuncRef(index)) => Ok(index),
+                            Ok(_) => Ok(None),
+                            Err(e) => Err(e.context(format!("failed to snapshot table {}", i))),
+                        }
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(TableSnapshot { element, elements })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(InstanceSnapshot {
+            memories,
+            globals,
+            tables,
+        })
+    }
+
+    pub(super) fn restore<T>(
+        &self,
+        mut store: StoreContextMut<'_, T>,
+        instance: &Instance,
+    ) -> Result<()> {
+        let items = defined_items(&mut store.as_context_mut().opaque(), instance)?;
+        if items.memories.len() != self.memories.len()
+            || items.globals.len() != self.globals.len()
+            || items.tables.len() != self.tables.len()
+        {
+            bail!("instance snapshot was taken from an instance of a different module");
+        }
+
+        // Validate everything and convert all values up front so a mismatched
+        // or corrupt snapshot doesn't leave the instance partially restored.
+        for (i, (memory, snapshot)) in items.memories.iter().zip(&self.memories).enumerate() {
+            let size = memory_size(&mut store, memory);
+            if size > snapshot.size {
+                bail!(
+                    "memory {} has grown to {:#x} bytes, beyond the snapshot's {:#x} bytes",
+                    i,
+                    size,
+                    snapshot.size
+                );
+            }
+            let in_bounds = snapshot.segments.iter().all(|(offset, bytes)| {
+                offset
+                    .checked_add(bytes.len())
+                    .map_or(false, |end| end <= snapshot.size)
+            });
+            if snapshot.size % WASM_PAGE_SIZE as usize != 0 || !in_bounds {
+                bail!("instance snapshot of memory {} is corrupt", i);
+            }
+        }
+        let mut globals = Vec::new();
+        for (i, (global, snapshot)) in items.globals.iter().zip(&self.globals).enumerate() {
+            let ty = global.ty(&store);
+            let matches = match snapshot {
+                None => ty.mutability() == Mutability::Const,
+                Some(value) => {
+                    ty.mutability() == Mutability::Var && value_type(value) == *ty.content()
+                }
+            };
+            if !matches {
+                bail!("global {} doesn't match the snapshot's type", i);
+            }
+            if let Some(value) = snapshot {
+                globals.push((global, from_value(&mut store, instance, value)?));
+            }
+        }
+        let mut tables = Vec::new();
+        for (i, (table, snapshot)) in items.tables.iter().zip(&self.tables).enumerate() {
+            let element = match table.ty(&store).element() {
+                ValType::FuncRef => RefType::FuncRef,
+                _ => RefType::ExternRef,
+            };
+            if element != snapshot.element {
+                bail!("table {} doesn't match the snapshot's element type", i);
+            }
+            if table.size(&store) as usize > snapshot.elements.len() {
+                bail!("table {} has grown beyond the snapshot's size", i);
+            }
+            let null = match snapshot.element {
+                RefType::FuncRef => Value::FuncRef(None),
+                RefType::ExternRef => Value::NullExternRef,
+            };
+            let null = from_value(&mut store, instance, &null)?;
+            let elements = snapshot
+                .elements
+                .iter()
+                .map(|element| match element {
+                    Some(index) => from_value(&mut store, instance, &Value::FuncRef(Some(*index))),
+                    None => Ok(null.clone()),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            tables.push((table, null, elements));
+        }
+
+        // Then grow everything before writing any contents, since growing is
+        // the only thing left that can fail.
+        for (i, (memory, snapshot)) in items.memories.iter().zip(&self.memories).enumerate() {
+            let size = memory_size(&mut store, memory);
+            if size > snapshot.size {
+                // Only possible if another thread grew a shared memory since
+                // it was checked above.
+                bail!("memory {} has grown beyond the snapshot's size", i);
+            }
+            let delta = (snapshot.size - size) / WASM_PAGE_SIZE as usize;
+            if delta > 0 {
+                let grown = match memory {
+                    Extern::Memory(m) => m.grow(&mut store, delta as u64),
+                    Extern::SharedMemory(m) => m.grow(delta as u64),
+                    _ => unreachable!(),
+                };
+                grown.with_context(|| format!("failed to grow memory {}", i))?;
+            }
+        }
+        for (i, (table, null, elements)) in tables.iter().enumerate() {
+            let delta = elements.len() as u32 - table.size(&store);
+            if delta > 0 {
+                table
+                    .grow(&mut store, delta, null.clone())
+                    .with_context(|| format!("failed to grow table {}", i))?;
+            }
+        }
+
+        for (memory, snapshot) in items.memories.iter().zip(&self.memories) {
+            // Safety: as in `capture`, except shared memories are written to
+            // here.
+            let data = unsafe { &mut *memory_data(&mut store, memory) };
+            for byte in data.iter_mut() {
+                *byte = 0;
+            }
+            for (offset, bytes) in snapshot.segments.iter() {
+                data[*offset..][..bytes.len()].copy_from_slice(bytes);
+            }
+        }
+        for (global, val) in globals {
+            global.set(&mut store, val)?;
+        }
+        for (table, _, elements) in tables {
+            for (j, val) in elements.into_iter().enumerate() {
+                table.set(&mut store, j as u32, val)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn defined_items(store: &mut StoreOpaque<'_>, instance: &Instance) -> Result<DefinedItems> {
+    let id = match &store[instance.0] {
+        InstanceData::Instantiated { id, .. } => *id,
+        InstanceData::Synthetic(_) => bail!("cannot snapshot an instance created by a `Linker`"),
+    };
+    let handle = store.instance(id);
+    let module = handle.module();
+    let memories = (module.num_imported_memories..module.memory_plans.len())
+        .map(|i| handle.lookup_by_declaration(&EntityIndex::Memory(MemoryIndex::new(i))))
+        .collect::<Vec<_>>();
+    let globals = (module.num_imported_globals..module.globals.len())
+        .map(|i| handle.lookup_by_declaration(&EntityIndex::Global(GlobalIndex::new(i))))
+        .collect::<Vec<_>>();
+    let tables = (module.num_imported_tables..module.table_plans.len())
+        .map(|i| handle.lookup_by_declaration(&EntityIndex::Table(TableIndex::new(i))))
+        .collect::<Vec<_>>();
+
+    unsafe {
+        Ok(DefinedItems {
+            memories: memories
+                .into_iter()
+                .map(|m| Extern::from_wasmtime_export(m, store))
+                .collect(),
+            globals: globals
+                .into_iter()
+                .filter_map(|g| Extern::from_wasmtime_export(g, store).into_global())
+                .collect(),
+            tables: tables
+                .into_iter()
+                .filter_map(|t| Extern::from_wasmtime_export(t, store).into_table())
+                .collect(),
+        })
+    }
+}
+
+fn memory_size<T>(store: &mut StoreContextMut<'_, T>, memory: &Extern) -> usize {
+    match memory {
+        Extern::Memory(m) => m.data_size(store),
+        Extern::SharedMemory(m) => m.data_size(),
+        _ => unreachable!(),
+    }
+}
+
+fn memory_data<T>(store: &mut StoreContextMut<'_, T>, memory: &Extern) -> *mut [u8] {
+    match memory {
+        Extern::Memory(m) => m.data_mut(store),
+        Extern::SharedMemory(m) => {
+            let data = m.data();
+            std::ptr::slice_from_raw_parts_mut(data.as_ptr() as *mut u8, data.len())
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn nonzero_segments(data: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut segments: Vec<(usize, Vec<u8>)> = Vec::new();
+    for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+        if chunk.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let offset = i * CHUNK_SIZE;
+        match segments.last_mut() {
+            Some((start, bytes)) if *start + bytes.len() == offset => {
+                bytes.extend_from_slice(chunk)
+            }
+            _ => segments.push((offset, chunk.to_vec())),
+        }
+    }
+    segments
+}
+
+fn value_type(value: &Value) -> ValType {
+    match value {
+        Value::I32(_) => ValType::I32,
+        Value::I64(_) => ValType::I64,
+        Value::F32(_) => ValType::F32,
+        Value::F64(_) => ValType::F64,
+        Value::V128(_) => ValType::V128,
+        Value::FuncRef(_) => ValType::FuncRef,
+        Value::NullExternRef => ValType::ExternRef,
+    }
+}
+
+fn to_value<T>(store: &mut StoreContextMut<'_, T>, instance: &Instance, val: Val) -> Result<Value> {
+    Ok(match val {
+        Val::I32(i) => Value::I32(i),
+        Val::I64(i) => Value::I64(i),
+        Val::F32(bits) => Value::F32(bits),
+        Val::F64(bits) => Value::F64(bits),
+        Val::V128(bits) => Value::V128(bits.to_le_bytes()),
+        Val::FuncRef(None) => Value::FuncRef(None),
+        Val::FuncRef(Some(func)) => {
+            let store = store.as_context_mut().opaque();
+            let id = match &store[instance.0] {
+                InstanceData::Instantiated { id, .. } => *id,
+                InstanceData::Synthetic(_) => unreachable!(),
+            };
+            let anyfunc = func.caller_checked_anyfunc(&store);
+            match store.instance(id).anyfunc_index(anyfunc.as_ptr()) {
+                Some(index) => Value::FuncRef(Some(index.as_u32())),
+                None => bail!("cannot snapshot a reference to a function of another instance"),
+            }
+        }
+        Val::ExternRef(None) => Value::NullExternRef,
+        Val::ExternRef(Some(_)) => bail!("cannot snapshot a non-null `externref`"),
+    })
+}
+
+fn from_value<T>(
+    store: &mut StoreContextMut<'_, T>,
+    instance: &Instance,
+    value: &Value,
+) -> Result<Val> {
+    Ok(match value {
+        Value::I32(i) => Val::I32(*i),
+        Value::I64(i) => Val::I64(*i),
+        Value::F32(bits) => Val::F32(*bits),
+        Value::F64(bits) => Val::F64(*bits),
+        Value::V128(bytes) => Val::V128(u128::from_le_bytes(*bytes)),
+        Value::FuncRef(None) => Val::FuncRef(None),
+        Value::FuncRef(Some(index)) => {
+            let mut store = store.as_context_mut().opaque();
+            let id = match &store[instance.0] {
+                InstanceData::Instantiated { id, .. } => *id,
+                InstanceData::Synthetic(_) => unreachable!(),
+            };
+            let handle = store.instance(id);
+            let index = FuncIndex::from_u32(*index);
+            if index.index() >= handle.module().functions.len() {
+                bail!("instance snapshot refers to a nonexistent function");
+            }
+            let export = handle.lookup_by_declaration(&EntityIndex::Function(index));
+            let func: Option<Func> =
+                unsafe { Extern::from_wasmtime_export(export, &mut store).into_func() };
+            Val::FuncRef(func)
+        }
+        Value::NullExternRef => Val::ExternRef(None),
+    })
+}
@@ -262,6 +262,10 @@
 //!   jitdump runtime profilng format. The profiler can be selected with
 //!   [`Config::profiler`].
 //!
+//! * `component-model` - Enabled by default, this feature enables the
+//!   [`component`] module for exchanging strings, lists, records and variants
+//!   with wasm using the canonical ABI, along with its derive macros.
+//!
 //! * `vtune` - Not enabled by default, this feature compiles in support for
 //!   supporting VTune profiling of JIT code.
 //!
@@ -375,13 +379,16 @@
 mod func;
 
 mod config;
+mod debugger;
 mod engine;
+mod exception;
 mod externals;
 mod instance;
 mod limits;
 mod linker;
 mod memory;
 mod module;
+mod profiling;
 mod r#ref;
 mod signatures;
 mod store;
@@ -390,15 +397,22 @@ mod trap;
 mod types;
 mod values;
 
+#[cfg(feature = "component-model")]
+#[cfg_attr(nightlydoc, doc(cfg(feature = "component-model")))]
+pub mod component;
+
 pub use crate::config::*;
+pub use crate::debugger::{DebugAction, DebugEvent, DebugFrame};
 pub use crate::engine::*;
+pub use crate::exception::{Exception, Tag};
 pub use crate::externals::*;
 pub use crate::func::*;
-pub use crate::instance::{Instance, InstancePre};
+pub use crate::instance::{Instance, InstancePre, InstanceSnapshot};
 pub use crate::limits::*;
 pub use crate::linker::*;
 pub use crate::memory::*;
 pub use crate::module::{FrameInfo, FrameSymbol, Module};
+pub use crate::profiling::{GuestProfileFormat, GuestProfiler};
 pub use crate::r#ref::ExternRef;
 pub use crate::store::{
     AsContext, AsContextMut, InterruptHandle, Store, StoreContext, StoreContextMut,
@@ -1,9 +1,14 @@
 use crate::store::{StoreData, StoreOpaque, Stored};
 use crate::trampoline::generate_memory_export;
-use crate::{AsContext, AsContextMut, MemoryType, StoreContext, StoreContextMut};
+use crate::{AsContext, AsContextMut, Engine, MemoryType, StoreContext, StoreContextMut, Trap};
 use anyhow::{bail, Result};
+use std::cell::UnsafeCell;
 use std::convert::TryFrom;
 use std::slice;
+use std::time::{Duration, Instant};
+use wasmtime_environ::MemoryPlan;
+
+pub use wasmtime_runtime::WaitResult;
 
 /// Error for out of bounds [`Memory`] access.
 #[derive(Debug)]
@@ -163,11 +168,13 @@ impl std::error::Error for MemoryAccessError {}
 ///
 /// ## `Memory` Safety and Threads
 ///
-/// Currently the `wasmtime` crate does not implement the wasm threads proposal,
-/// but it is planned to do so. It may be interesting to readers to see how this
-/// affects memory safety and what was previously just discussed as well.
+/// A [`Memory`] is never shared: memories declared as `shared` by the wasm
+/// threads proposal are instead represented by [`SharedMemory`], which can be
+/// used from multiple threads and stores at once. It may be interesting to
+/// readers to see how this affects memory safety and what was previously just
+/// discussed as well.
 ///
-/// Once threads are added into the mix, all of the above rules still apply.
+/// With shared memories all of the above rules still apply.
 /// There's an additional consideration that all reads and writes can happen
 /// concurrently, though. This effectively means that any borrow into wasm
 /// memory are virtually never safe to have.
@@ -187,11 +194,8 @@ impl std::error::Error for MemoryAccessError {}
 ///
 /// Overall the general rule of thumb for shared memories is that you must
 /// atomically read and write everything. Nothing can be borrowed and everything
-/// must be eagerly copied out. This means that [`Memory::data`] and
-/// [`Memory::data_mut`] won't work in the future (they'll probably return an
-/// error) for shared memories when they're implemented. When possible it's
-/// recommended to use [`Memory::read`] and [`Memory::write`] which will still
-/// be provided.
+/// must be eagerly copied out. This is why [`SharedMemory::data`] only hands
+/// out a slice of `UnsafeCell<u8>` rather than `&[u8]` or `&mut [u8]`.
 #[derive(Copy, Clone, Debug)]
 #[repr(transparent)] // here for the C API
 pub struct Memory(Stored<wasmtime_runtime::ExportMemory>);
@@ -224,8 +228,11 @@ impl Memory {
     }
 
     fn _new(store: &mut StoreOpaque<'_>, ty: MemoryType) -> Result<Memory> {
+        if ty.is_shared() {
+            bail!("shared memories must be created through `SharedMemory`")
+        }
         unsafe {
-            let export = generate_memory_export(store, &ty)?;
+            let export = generate_memory_export(store, &ty, None)?;
             Ok(Memory::from_wasmtime_memory(export, store))
         }
     }
@@ -497,6 +504,220 @@ impl Memory {
     }
 }
 
+/// A WebAssembly linear memory which may be shared between threads.
+///
+/// Shared memories are created from a [`MemoryType`] whose
+/// [`is_shared`](MemoryType::is_shared) flag is set, either with
+/// [`SharedMemory::new`] or by instantiating a module which defines one.
+/// Unlike [`Memory`] a `SharedMemory` isn't owned by any particular
+/// [`Store`](crate::Store): it's a reference-counted handle which can be sent
+/// to other threads and imported into instances in any store that uses the
+/// same [`Engine`].
+///
+/// Shared memories always have a maximum size and their entire maximum is
+/// reserved up front, so growing a shared memory never moves its base
+/// pointer. Growth of a shared memory isn't subject to the
+/// [`ResourceLimiter`](crate::ResourceLimiter) of any store.
+///
+/// See the documentation on [`Memory`] for more information about the safety
+/// of accessing memory which can be concurrently modified.
+#[derive(Clone)]
+pub struct SharedMemory(wasmtime_runtime::SharedMemory, Engine);
+
+impl SharedMemory {
+    /// Creates a new shared WebAssembly memory given the configuration of
+    /// `ty`.
+    ///
+    /// All WebAssembly memory is initialized to zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ty` is not a shared memory type, for example one
+    /// created with [`MemoryType::shared`], or if the memory could not be
+    /// allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut config = Config::new();
+    /// config.wasm_threads(true);
+    /// let engine = Engine::new(&config)?;
+    /// let memory = SharedMemory::new(&engine, MemoryType::shared(1, 2))?;
+    ///
+    /// let module = Module::new(
+    ///     &engine,
+    ///     "(module (memory (import \"\" \"\") 1 2 shared))",
+    /// )?;
+    /// let mut store = Store::new(&engine, ());
+    /// let instance = Instance::new(&mut store, &module, &[memory.clone().into()])?;
+    /// // ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(engine: &Engine, ty: MemoryType) -> Result<SharedMemory> {
+        if !ty.is_shared() {
+            bail!("shared memory must have the `shared` flag enabled on its memory type")
+        }
+        let plan = MemoryPlan::for_memory(ty.wasmtime_memory().clone(), &engine.config().tunables);
+        let memory = wasmtime_runtime::SharedMemory::new(plan)?;
+        Ok(SharedMemory(memory, engine.clone()))
+    }
+
+    /// Returns the underlying type of this memory.
+    pub fn ty(&self) -> MemoryType {
+        MemoryType::from_wasmtime_memory(&self.0.plan().memory)
+    }
+
+    /// Returns the size, in WebAssembly pages, of this wasm memory.
+    pub fn size(&self) -> u64 {
+        (self.data_size() / wasmtime_environ::WASM_PAGE_SIZE as usize) as u64
+    }
+
+    /// Returns the byte length of this memory.
+    ///
+    /// The returned value will be a multiple of the wasm page size, 64k. Note
+    /// that other threads may grow this memory concurrently, so the size may
+    /// have increased by the time this method returns.
+    pub fn data_size(&self) -> usize {
+        self.0.byte_size()
+    }
+
+    /// Returns the contents of this memory as a slice of `UnsafeCell<u8>`.
+    ///
+    /// Any thread may read or write this memory at any time, including
+    /// WebAssembly running on other threads, so the bytes can't be safely
+    /// borrowed. Reads and writes through the returned slice should either be
+    /// atomic or otherwise synchronized with the other users of this memory.
+    ///
+    /// The returned slice covers the size of this memory at the time of the
+    /// call, and stays valid for as long as this `SharedMemory` is alive since
+    /// shared memories never move.
+    pub fn data(&self) -> &[UnsafeCell<u8>] {
+        unsafe {
+            let definition = self.0.vmmemory();
+            slice::from_raw_parts(
+                definition.base as *const UnsafeCell<u8>,
+                definition.current_length,
+            )
+        }
+    }
+
+    /// Grows this WebAssembly memory by `delta` pages.
+    ///
+    /// On success returns the number of pages this memory previously had
+    /// before the growth succeeded. The base pointer of a shared memory never
+    /// changes when it's grown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if memory could not be grown, for example if it
+    /// exceeds the maximum limits of this memory.
+    pub fn grow(&self, delta: u64) -> Result<u64> {
+        match self.0.grow(delta) {
+            Some(size) => {
+                Ok(u64::try_from(size).unwrap() / u64::from(wasmtime_environ::WASM_PAGE_SIZE))
+            }
+            None => bail!("failed to grow memory by `{}`", delta),
+        }
+    }
+
+    /// Equivalent of the WebAssembly `memory.atomic.notify` instruction for
+    /// this shared memory.
+    ///
+    /// Wakes up at most `count` threads which are waiting on the byte offset
+    /// `addr` of this memory, returning how many threads were woken up.
+    ///
+    /// # Errors
+    ///
+    /// Returns a trap if `addr` isn't 4-byte aligned or is out of bounds.
+    pub fn atomic_notify(&self, addr: u64, count: u32) -> Result<u32, Trap> {
+        self.0
+            .atomic_notify(addr, count)
+            .map_err(Trap::from_runtime)
+    }
+
+    /// Equivalent of the WebAssembly `memory.atomic.wait32` instruction for
+    /// this shared memory.
+    ///
+    /// If the 32-bit value at byte offset `addr` equals `expected` then this
+    /// blocks the current thread until it's notified by
+    /// [`SharedMemory::atomic_notify`] or a wasm `memory.atomic.notify`
+    /// instruction, or until `timeout` elapses if one is given.
+    ///
+    /// # Errors
+    ///
+    /// Returns a trap if `addr` isn't 4-byte aligned or is out of bounds.
+    pub fn atomic_wait32(
+        &self,
+        addr: u64,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, Trap> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.0
+            .atomic_wait32(addr, expected, deadline)
+            .map_err(Trap::from_runtime)
+    }
+
+    /// Equivalent of the WebAssembly `memory.atomic.wait64` instruction for
+    /// this shared memory.
+    ///
+    /// Same as [`SharedMemory::atomic_wait32`] except that the 64-bit value at
+    /// `addr` is compared, and `addr` must be 8-byte aligned.
+    pub fn atomic_wait64(
+        &self,
+        addr: u64,
+        expected: u64,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, Trap> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.0
+            .atomic_wait64(addr, expected, deadline)
+            .map_err(Trap::from_runtime)
+    }
+
+    pub(crate) fn engine(&self) -> &Engine {
+        &self.1
+    }
+
+    pub(crate) unsafe fn from_wasmtime_memory(
+        wasmtime_export: wasmtime_runtime::ExportMemory,
+        store: &StoreOpaque,
+    ) -> SharedMemory {
+        let mut handle = wasmtime_runtime::InstanceHandle::from_vmctx(wasmtime_export.vmctx);
+        let idx = handle.memory_index(&*wasmtime_export.definition);
+        let memory = (*handle.get_defined_memory(idx))
+            .as_shared_memory()
+            .expect("exported memory with a shared type is not a shared memory")
+            .clone();
+        SharedMemory(memory, store.engine().clone())
+    }
+
+    /// Creates a host instance in `store` which defines this memory, returning
+    /// an import that refers to it.
+    pub(crate) fn vmimport(
+        &self,
+        store: &mut StoreOpaque<'_>,
+    ) -> Result<wasmtime_runtime::VMMemoryImport> {
+        let export = generate_memory_export(store, &self.ty(), Some(&self.0))?;
+        Ok(wasmtime_runtime::VMMemoryImport {
+            from: export.definition,
+            vmctx: export.vmctx,
+        })
+    }
+}
+
+impl std::fmt::Debug for SharedMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedMemory")
+            .field("ty", &self.ty())
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
 /// A linear memory. This trait provides an interface for raw memory buffers
 /// which are used by wasmtime, e.g. inside ['Memory']. Such buffers are in
 /// principle not thread safe. By implementing this trait together with
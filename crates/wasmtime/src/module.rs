@@ -11,6 +11,7 @@ use std::sync::Arc;
 use wasmparser::Validator;
 use wasmtime_environ::{ModuleEnvironment, ModuleIndex, PrimaryMap};
 use wasmtime_jit::{CompilationArtifacts, CompiledModule, CompiledModuleInfo, TypeTables};
+use wasmtime_runtime::ModuleMemoryImages;
 
 mod registry;
 mod serialization;
@@ -107,6 +108,9 @@ struct ModuleInner {
     types: Arc<TypeTables>,
     /// Registered shared signature for the module.
     signatures: Arc<SignatureCollection>,
+    /// Copy-on-write images of the initial contents of this module's linear
+    /// memories, if enabled and supported for this module.
+    memory_images: Option<ModuleMemoryImages>,
 }
 
 impl Module {
@@ -383,8 +387,9 @@ impl Module {
             )?;
 
             // If configured, attempt to use paged memory initialization
-            // instead of the default mode of memory initialization
-            if engine.config().paged_memory_initialization {
+            // instead of the default mode of memory initialization. This is
+            // also required for copy-on-write memory images.
+            if engine.config().paged_memory_initialization || engine.config().memory_init_cow {
                 translation.try_paged_init();
             }
 
@@ -453,6 +458,36 @@ impl Module {
         module.into_module(engine)
     }
 
+    /// Same as [`deserialize`], except that the contents of `path` are read to
+    /// deserialize into a [`Module`].
+    ///
+    /// This method is provided because it can be faster than [`deserialize`]
+    /// since the data doesn't need to be copied around. The file is mapped
+    /// into memory and the compiled code within it is made executable where it
+    /// sits, so only the parts of the file which are actually used are read
+    /// in by the OS. Metadata such as trap information and stack maps is also
+    /// read lazily from the mapping as it's needed.
+    ///
+    /// Note that on hosts whose page size is larger than 4KiB the compiled
+    /// code is still copied into separate executable memory.
+    ///
+    /// [`deserialize`]: Module::deserialize
+    ///
+    /// # Unsafety
+    ///
+    /// All of the reasons that [`deserialize`] is `unsafe` applies to this
+    /// function as well. Arbitrary data loaded from a file may trick Wasmtime
+    /// into arbitrary execution, for example. Additionally the file must not
+    /// be modified while the returned [`Module`], or any instance of it, is
+    /// still alive.
+    pub unsafe fn deserialize_file(engine: &Engine, path: impl AsRef<Path>) -> Result<Module> {
+        let module = SerializedModule::from_file(
+            path.as_ref(),
+            engine.config().deserialize_check_wasmtime_version,
+        )?;
+        module.into_module(engine)
+    }
+
     fn from_parts(
         engine: &Engine,
         mut modules: Vec<Arc<CompiledModule>>,
@@ -466,7 +501,7 @@ impl Module {
         let signatures = Arc::new(SignatureCollection::new_for_module(
             engine.signatures(),
             &types.wasm_signatures,
-            modules.iter().flat_map(|m| m.trampolines().iter().cloned()),
+            modules.iter().flat_map(|m| m.trampolines()),
         ));
 
         let module = modules.remove(main_module);
@@ -486,6 +521,8 @@ impl Module {
             })
             .collect::<Result<Vec<_>>>()?;
 
+        let images = memory_images(engine, &module)?;
+
         return Ok(Self {
             inner: Arc::new(ModuleInner {
                 engine: engine.clone(),
@@ -494,6 +531,7 @@ impl Module {
                 artifact_upvars: modules,
                 module_upvars,
                 signatures,
+                memory_images: images,
             }),
         });
 
@@ -506,11 +544,13 @@ impl Module {
             module_upvars: &[serialization::SerializedModuleUpvar],
             signatures: &Arc<SignatureCollection>,
         ) -> Result<Module> {
+            let module = artifacts[module_index].clone();
             Ok(Module {
                 inner: Arc::new(ModuleInner {
                     engine: engine.clone(),
                     types: types.clone(),
-                    module: artifacts[module_index].clone(),
+                    memory_images: memory_images(engine, &module)?,
+                    module,
                     artifact_upvars: artifact_upvars
                         .iter()
                         .map(|i| artifacts[*i].clone())
@@ -636,6 +676,10 @@ impl Module {
                     })
                     .collect(),
                 signatures: self.inner.signatures.clone(),
+                // Submodules are created during instantiation, so rather than
+                // creating images for them each time they're always
+                // initialized by copying data segments.
+                memory_images: None,
             }),
         }
     }
@@ -656,6 +700,10 @@ impl Module {
         &self.inner.signatures
     }
 
+    pub(crate) fn memory_images(&self) -> Option<&ModuleMemoryImages> {
+        self.inner.memory_images.as_ref()
+    }
+
     /// Looks up the module upvar value at the `index` specified.
     ///
     /// Note that this panics if `index` is out of bounds since this should
@@ -912,8 +960,17 @@ impl std::hash::Hash for HashedEngineCompileEnv<'_> {
         let config = self.0.config();
         config.tunables.hash(hasher);
         config.features.hash(hasher);
+        (config.paged_memory_initialization || config.memory_init_cow).hash(hasher);
 
         // Catch accidental bugs of reusing across crate versions.
         env!("CARGO_PKG_VERSION").hash(hasher);
     }
 }
+
+/// Creates the copy-on-write memory images for `module`, if they're enabled.
+fn memory_images(engine: &Engine, module: &CompiledModule) -> Result<Option<ModuleMemoryImages>> {
+    if !engine.config().memory_init_cow {
+        return Ok(None);
+    }
+    ModuleMemoryImages::new(module.module(), module.wasm_data())
+}
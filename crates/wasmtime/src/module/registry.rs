@@ -92,6 +92,18 @@ impl ModuleRegistry {
         GLOBAL_MODULES.write().unwrap().register(start, end, module);
     }
 
+    /// Fetches frame information about a program counter in a backtrace.
+    ///
+    /// Returns an object if this `pc` is known to a module registered with
+    /// this store, or returns `None` otherwise. The boolean returned indicates
+    /// whether the original module has unparsed debug information due to the
+    /// compiler's configuration.
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<(FrameInfo, bool)> {
+        let module = self.module(pc)?;
+        let info = FrameInfo::new(&module.module, pc - module.start)?;
+        Some((info, module.module.has_unparsed_debuginfo()))
+    }
+
     /// Looks up a trampoline from an anyfunc.
     pub fn lookup_trampoline(&self, anyfunc: &VMCallerCheckedAnyfunc) -> Option<VMTrampoline> {
         let module = self.module(anyfunc.func_ptr.as_ptr() as usize)?;
@@ -115,10 +127,10 @@ struct RegisteredModule {
 }
 
 impl ModuleInfo for RegisteredModule {
-    fn lookup_stack_map(&self, pc: usize) -> Option<&StackMap> {
+    fn lookup_stack_map(&self, pc: usize) -> Option<StackMap> {
         let text_offset = pc - self.start;
-        let (index, func_offset) = self.module.func_by_text_offset(text_offset)?;
-        let info = self.module.func_info(index);
+        let (_index, func_offset) = self.module.func_by_text_offset(text_offset)?;
+        let func_start = text_offset - func_offset as usize;
 
         // Do a binary search to find the stack map for the given offset.
         //
@@ -161,25 +173,18 @@ impl ModuleInfo for RegisteredModule {
         //    (which would not have been updated to point to the moved objects)
         //    or reload from the stack slots (which would have been updated to
         //    point to the moved objects).
+        //
+        // Given all that, the stack map used is the one for the closest GC
+        // safepoint at or before the pc within the same function. If there's
+        // no such safepoint then there are no live refs at this pc.
+        wasmtime_environ::lookup_stack_map(self.module.stack_map_data(), func_start, text_offset)
+    }
 
-        let index = match info
-            .stack_maps
-            .binary_search_by_key(&func_offset, |i| i.code_offset)
-        {
-            // Exact hit.
-            Ok(i) => i,
-
-            // `Err(0)` means that the associated stack map would have been the
-            // first element in the array if this pc had an associated stack
-            // map, but this pc does not have an associated stack map. This can
-            // only happen inside a Wasm frame if there are no live refs at this
-            // pc.
-            Err(0) => return None,
-
-            Err(i) => i - 1,
-        };
-
-        Some(&info.stack_maps[index].stack_map)
+    fn lookup_exception_handler(&self, pc: usize) -> Option<(usize, usize)> {
+        let text_offset = pc - self.start;
+        let (landing_pad, sp_offset) =
+            wasmtime_environ::lookup_exception_handler(self.module.exception_data(), text_offset)?;
+        Some((self.start + landing_pad, sp_offset))
     }
 }
 
@@ -309,9 +314,40 @@ impl GlobalRegisteredModule {
     /// Returns an object if this `pc` is known to this module, or returns `None`
     /// if no information can be found.
     pub fn lookup_frame_info(&self, text_offset: usize) -> Option<FrameInfo> {
-        let (index, _func_offset) = self.module.func_by_text_offset(text_offset)?;
-        let info = self.module.func_info(index);
-        let instr = wasmtime_environ::lookup_file_pos(self.module.address_map_data(), text_offset);
+        FrameInfo::new(&self.module, text_offset)
+    }
+}
+
+/// Description of a frame in a backtrace for a [`Trap`] or a
+/// [`WasmBacktrace`].
+///
+/// Whenever a WebAssembly trap occurs an instance of [`Trap`] is created. Each
+/// [`Trap`] has a backtrace of the WebAssembly frames that led to the trap, and
+/// each frame is described by this structure. The same frames can also be
+/// captured at any time while wasm is executing with [`WasmBacktrace`].
+///
+/// [`Trap`]: crate::Trap
+/// [`WasmBacktrace`]: crate::WasmBacktrace
+#[derive(Debug)]
+pub struct FrameInfo {
+    module_name: Option<String>,
+    func_index: u32,
+    func_name: Option<String>,
+    func_start: FilePos,
+    instr: FilePos,
+    symbols: Vec<FrameSymbol>,
+}
+
+impl FrameInfo {
+    /// Creates the frame information for the instruction at `text_offset`
+    /// within the text section of `module`.
+    ///
+    /// Returns `None` if `text_offset` doesn't lie within any function of
+    /// `module`.
+    pub(crate) fn new(module: &CompiledModule, text_offset: usize) -> Option<FrameInfo> {
+        let (index, _func_offset) = module.func_by_text_offset(text_offset)?;
+        let info = module.func_info(index);
+        let instr = wasmtime_environ::lookup_file_pos(module.address_map_data(), text_offset);
 
         // In debug mode for now assert that we found a mapping for `pc` within
         // the function, because otherwise something is buggy along the way and
@@ -335,7 +371,7 @@ impl GlobalRegisteredModule {
         // custom section contents.
         let mut symbols = Vec::new();
 
-        if let Some(s) = &self.module.symbolize_context().ok().and_then(|c| c) {
+        if let Some(s) = &module.symbolize_context().ok().and_then(|c| c) {
             if let Some(offset) = instr.file_offset() {
                 let to_lookup = u64::from(offset) - s.code_section_offset();
                 if let Ok(mut frames) = s.addr2line().find_frames(to_lookup) {
@@ -359,7 +395,7 @@ impl GlobalRegisteredModule {
             }
         }
 
-        let module = self.module.module();
+        let module = module.module();
         let index = module.func_index(index);
 
         Some(FrameInfo {
@@ -371,26 +407,7 @@ impl GlobalRegisteredModule {
             symbols,
         })
     }
-}
 
-/// Description of a frame in a backtrace for a [`Trap`].
-///
-/// Whenever a WebAssembly trap occurs an instance of [`Trap`] is created. Each
-/// [`Trap`] has a backtrace of the WebAssembly frames that led to the trap, and
-/// each frame is described by this structure.
-///
-/// [`Trap`]: crate::Trap
-#[derive(Debug)]
-pub struct FrameInfo {
-    module_name: Option<String>,
-    func_index: u32,
-    func_name: Option<String>,
-    func_start: FilePos,
-    instr: FilePos,
-    symbols: Vec<FrameSymbol>,
-}
-
-impl FrameInfo {
     /// Returns the WebAssembly function index for this frame.
     ///
     /// This function index is the index in the function index space of the
@@ -1,17 +1,42 @@
 //! Implements module serialization.
+//!
+//! A serialized module has the following layout:
+//!
+//! * The `HEADER` magic bytes.
+//! * A single byte with the length of the Wasmtime version string, followed
+//!   by the version string itself.
+//! * A 64-bit little-endian length of the bincode-encoded `SerializedModule`
+//!   metadata, followed by the metadata itself.
+//! * The ELF image of each compilation artifact, each of which starts at an
+//!   offset that's a multiple of `ARTIFACT_ALIGN`.
+//!
+//! Keeping the ELF images out of the bincode-encoded metadata means that they
+//! can be used where they sit when a serialized module is mapped into memory,
+//! rather than having to be decoded and copied.
 
 use crate::{Engine, Module};
 use anyhow::{anyhow, bail, Context, Result};
 use bincode::Options;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use wasmtime_environ::{Compiler, FlagValue, Tunables};
 use wasmtime_jit::{CompilationArtifacts, CompiledModule, TypeTables};
+use wasmtime_runtime::MmapVec;
 
 const HEADER: &[u8] = b"\0wasmtime-aot";
 
+/// Alignment of each compilation artifact within a serialized module.
+///
+/// Compilation page-aligns the text section within each artifact, so aligning
+/// the artifacts themselves to a page means that the text section is
+/// page-aligned when the serialized module is mapped into memory. It can then
+/// be made executable in place on hosts with 4KiB pages.
+const ARTIFACT_ALIGN: usize = 0x1000;
+
 fn bincode_options() -> impl Options {
     // Use a variable-length integer encoding instead of fixed length. The
     // module shown on #2318 gets compressed from ~160MB to ~110MB simply using
@@ -23,6 +48,11 @@ fn bincode_options() -> impl Options {
     bincode::DefaultOptions::new().with_varint_encoding()
 }
 
+/// Rounds `offset` up to the next multiple of `ARTIFACT_ALIGN`.
+fn align(offset: usize) -> usize {
+    (offset + ARTIFACT_ALIGN - 1) & !(ARTIFACT_ALIGN - 1)
+}
+
 // This exists because `wasmparser::WasmFeatures` isn't serializable
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct WasmFeatures {
@@ -78,6 +108,13 @@ enum MyCow<'a, T> {
 }
 
 impl<'a, T> MyCow<'a, T> {
+    fn as_ref(&self) -> &T {
+        match self {
+            MyCow::Owned(val) => val,
+            MyCow::Borrowed(val) => val,
+        }
+    }
+
     fn unwrap_owned(self) -> T {
         match self {
             MyCow::Owned(val) => val,
@@ -156,7 +193,11 @@ pub struct SerializedModule<'a> {
     isa_flags: BTreeMap<String, FlagValue>,
     tunables: Tunables,
     features: WasmFeatures,
+    // The ELF images of the artifacts are written after the metadata, see the
+    // module documentation for more information.
+    #[serde(skip)]
     artifacts: Vec<MyCow<'a, CompilationArtifacts>>,
+    artifact_lens: Vec<u64>,
     module_upvars: Vec<SerializedModuleUpvar>,
     types: MyCow<'a, TypeTables>,
 }
@@ -215,6 +256,10 @@ impl<'a> SerializedModule<'a> {
             isa_flags: engine.compiler().isa_flags(),
             tunables: engine.config().tunables.clone(),
             features: (&engine.config().features).into(),
+            artifact_lens: artifacts
+                .iter()
+                .map(|a| a.as_ref().obj().len() as u64)
+                .collect(),
             artifacts,
             module_upvars,
             types,
@@ -246,7 +291,7 @@ impl<'a> SerializedModule<'a> {
         self.check_features(&engine.config().features)?;
 
         let modules = engine.run_maybe_parallel(self.artifacts, |i| {
-            CompiledModule::from_artifacts(i.unwrap_owned(), None, &*engine.config().profiler)
+            CompiledModule::from_artifacts_in_place(i.unwrap_owned(), &*engine.config().profiler)
         })?;
 
         assert!(!modules.is_empty());
@@ -280,40 +325,92 @@ impl<'a> SerializedModule<'a> {
 
         bytes.write_all(version.as_bytes())?;
 
-        bincode_options().serialize_into(&mut bytes, self)?;
+        let metadata = bincode_options().serialize(self)?;
+        bytes.write_all(&(metadata.len() as u64).to_le_bytes())?;
+        bytes.write_all(&metadata)?;
+
+        for artifact in self.artifacts.iter() {
+            bytes.resize(align(bytes.len()), 0);
+            bytes.write_all(artifact.as_ref().obj())?;
+        }
 
         Ok(bytes)
     }
 
     pub fn from_bytes(bytes: &[u8], check_version: bool) -> Result<Self> {
-        if !bytes.starts_with(HEADER) {
-            bail!("bytes are not a compatible serialized wasmtime module");
-        }
+        Self::from_mmap(MmapVec::from_slice(bytes)?, check_version)
+    }
 
-        let bytes = &bytes[HEADER.len()..];
+    pub fn from_file(path: &Path, check_version: bool) -> Result<Self> {
+        Self::from_mmap(MmapVec::from_file(path)?, check_version)
+    }
 
-        if bytes.is_empty() {
-            bail!("serialized data data is empty");
-        }
+    pub fn from_mmap(mut mmap: MmapVec, check_version: bool) -> Result<Self> {
+        let (mut module, metadata_end) = {
+            let bytes = &mmap[..];
+            if !bytes.starts_with(HEADER) {
+                bail!("bytes are not a compatible serialized wasmtime module");
+            }
 
-        let version_len = bytes[0] as usize;
-        if bytes.len() < version_len + 1 {
-            bail!("serialized data is malformed");
-        }
+            let bytes = &bytes[HEADER.len()..];
+
+            if bytes.is_empty() {
+                bail!("serialized data data is empty");
+            }
+
+            let version_len = bytes[0] as usize;
+            if bytes.len() < version_len + 1 {
+                bail!("serialized data is malformed");
+            }
 
-        if check_version {
-            let version = std::str::from_utf8(&bytes[1..1 + version_len])?;
-            if version != env!("CARGO_PKG_VERSION") {
-                bail!(
-                    "Module was compiled with incompatible Wasmtime version '{}'",
-                    version
-                );
+            if check_version {
+                let version = std::str::from_utf8(&bytes[1..1 + version_len])?;
+                if version != env!("CARGO_PKG_VERSION") {
+                    bail!(
+                        "Module was compiled with incompatible Wasmtime version '{}'",
+                        version
+                    );
+                }
+            }
+
+            let bytes = &bytes[1 + version_len..];
+            if bytes.len() < 8 {
+                bail!("serialized data is malformed");
+            }
+            let mut len = [0; 8];
+            len.copy_from_slice(&bytes[..8]);
+            let metadata_len = usize::try_from(u64::from_le_bytes(len))
+                .ok()
+                .filter(|len| *len <= bytes.len() - 8)
+                .ok_or_else(|| anyhow!("serialized data is malformed"))?;
+
+            let module = bincode_options()
+                .deserialize::<SerializedModule<'_>>(&bytes[8..8 + metadata_len])
+                .context("deserialize compilation artifacts")?;
+            (module, HEADER.len() + 1 + version_len + 8 + metadata_len)
+        };
+
+        // Split each artifact's ELF image off of `mmap` so it can be used in
+        // place, skipping over the padding before each one.
+        let mut rest = mmap.split_off(metadata_end);
+        let mut pos = metadata_end;
+        let mut artifacts = Vec::with_capacity(module.artifact_lens.len());
+        for len in module.artifact_lens.iter() {
+            let start = align(pos);
+            let len = usize::try_from(*len).ok();
+            match len {
+                Some(len) if start - pos <= rest.len() && len <= rest.len() - (start - pos) => {
+                    let mut artifact = rest.split_off(start - pos);
+                    rest = artifact.split_off(len);
+                    artifacts.push(MyCow::Owned(CompilationArtifacts::from_mmap(artifact)));
+                    pos = start + len;
+                }
+                _ => bail!("serialized data is malformed"),
             }
         }
+        module.artifacts = artifacts;
 
-        Ok(bincode_options()
-            .deserialize::<SerializedModule<'_>>(&bytes[1 + version_len..])
-            .context("deserialize compilation artifacts")?)
+        Ok(module)
     }
 
     fn check_triple(&self, other: &target_lexicon::Triple) -> Result<()> {
@@ -427,6 +524,8 @@ impl<'a> SerializedModule<'a> {
             parse_wasm_debuginfo,
             interruptable,
             consume_fuel,
+            epoch_interruption,
+            guest_debug,
             static_memory_bound_is_maximum,
             guard_before_linear_memory,
 
@@ -461,6 +560,12 @@ impl<'a> SerializedModule<'a> {
         )?;
         Self::check_bool(interruptable, other.interruptable, "interruption support")?;
         Self::check_bool(consume_fuel, other.consume_fuel, "fuel support")?;
+        Self::check_bool(
+            epoch_interruption,
+            other.epoch_interruption,
+            "epoch interruption",
+        )?;
+        Self::check_bool(guest_debug, other.guest_debug, "guest debugging")?;
         Self::check_bool(
             static_memory_bound_is_maximum,
             other.static_memory_bound_is_maximum,
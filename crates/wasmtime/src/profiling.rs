@@ -0,0 +1,545 @@
+//! A sampling profiler for WebAssembly guests.
+//!
+//! Unlike the profiling agents configured through
+//! [`Config::profiler`](crate::Config::profiler), which describe jit code to
+//! external tools such as `perf` or VTune, the [`GuestProfiler`] lives inside
+//! the embedding. It's fed samples of the wasm stack of a store and then
+//! writes out a profile that can be inspected with the Firefox Profiler or
+//! `pprof`, so it works on any host that can run wasm.
+
+use crate::trap::demangle_function_name;
+use crate::{AsContext, FrameInfo, WasmBacktrace};
+use anyhow::Result;
+use indexmap::IndexSet;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The file format written by [`GuestProfiler::finish`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestProfileFormat {
+    /// The JSON "Gecko profile" format which can be loaded into the Firefox
+    /// Profiler at <https://profiler.firefox.com/>.
+    Firefox,
+    /// The protobuf format used by [pprof](https://github.com/google/pprof),
+    /// written uncompressed.
+    Pprof,
+}
+
+/// A sampling profiler of the WebAssembly code executing in a [`Store`].
+///
+/// The profiler doesn't interrupt wasm by itself; instead
+/// [`GuestProfiler::sample`] must be called periodically while wasm is
+/// executing. Each call captures a [`WasmBacktrace`] of the store, whose frames
+/// are described using the module's name section and DWARF debug information
+/// when available. The most common ways to drive sampling are:
+///
+/// * From an epoch deadline callback (see
+///   [`Store::epoch_deadline_callback`]), with a separate thread calling
+///   [`Engine::increment_epoch`] on a timer.
+/// * From host functions called by wasm, or when an async store yields due
+///   to fuel or epochs.
+///
+/// Once execution has finished [`GuestProfiler::finish`] writes all the
+/// collected samples out in one of the [`GuestProfileFormat`]s.
+///
+/// # Example
+///
+/// ```
+/// # use wasmtime::*;
+/// # use std::time::Duration;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut config = Config::new();
+/// config.epoch_interruption(true);
+/// let engine = Engine::new(&config)?;
+/// let module = Module::new(&engine, r#"(module (func (export "run")))"#)?;
+///
+/// let interval = Duration::from_millis(1);
+/// let profiler = GuestProfiler::new("example", interval);
+/// let mut store = Store::new(&engine, Some(profiler));
+/// store.set_epoch_deadline(1);
+/// store.epoch_deadline_callback(|mut cx| {
+///     // The profiler needs to look at the store while sampling, so take it
+///     // out of the store's data for the duration of the sample.
+///     let mut profiler = cx.data_mut().take().unwrap();
+///     profiler.sample(&cx);
+///     *cx.data_mut() = Some(profiler);
+///     Ok(1)
+/// });
+///
+/// // Meanwhile another thread would call `engine.increment_epoch()` every
+/// // `interval` to trigger the callback above.
+/// let instance = Instance::new(&mut store, &module, &[])?;
+/// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+/// run.call(&mut store, ())?;
+///
+/// let mut profile = Vec::new();
+/// store
+///     .into_data()
+///     .unwrap()
+///     .finish(GuestProfileFormat::Firefox, &mut profile)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Store`]: crate::Store
+/// [`Store::epoch_deadline_callback`]: crate::Store::epoch_deadline_callback
+/// [`Engine::increment_epoch`]: crate::Engine::increment_epoch
+#[derive(Debug)]
+pub struct GuestProfiler {
+    name: String,
+    interval: Duration,
+    start: Instant,
+    start_time: SystemTime,
+    functions: IndexSet<ProfileFunction>,
+    frames: IndexSet<ProfileFrame>,
+    /// Stacks are stored as a tree: each entry is a frame and the index of
+    /// the stack it was called from, if any.
+    stacks: IndexSet<(Option<usize>, usize)>,
+    samples: Vec<(usize, Duration)>,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct ProfileFunction {
+    name: String,
+    module: Option<String>,
+    file: Option<String>,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct ProfileFrame {
+    function: usize,
+    module_offset: usize,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl GuestProfiler {
+    /// Creates a new profiler with no samples.
+    ///
+    /// The `name` is used to label the profile, for example with the name of
+    /// the module being run. The `interval` is the expected time between
+    /// calls to [`GuestProfiler::sample`] and is recorded in the profile.
+    pub fn new(name: &str, interval: Duration) -> GuestProfiler {
+        GuestProfiler {
+            name: name.to_string(),
+            interval,
+            start: Instant::now(),
+            start_time: SystemTime::now(),
+            functions: IndexSet::new(),
+            frames: IndexSet::new(),
+            stacks: IndexSet::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Adds a sample of the WebAssembly frames currently executing within
+    /// `store` to the profile.
+    ///
+    /// Samples taken while no wasm is executing within `store` are ignored.
+    pub fn sample(&mut self, store: impl AsContext) {
+        let backtrace = WasmBacktrace::capture(store);
+        let time = self.start.elapsed();
+
+        // The backtrace lists the most recently called frame first, while
+        // stacks are built up from the outermost frame inwards.
+        let mut stack = None;
+        for frame in backtrace.frames().iter().rev() {
+            for frame in self.frames_of(frame).into_iter().rev() {
+                let frame = self.frames.insert_full(frame).0;
+                stack = Some(self.stacks.insert_full((stack, frame)).0);
+            }
+        }
+        if let Some(stack) = stack {
+            self.samples.push((stack, time));
+        }
+    }
+
+    /// Returns the profile frames for `frame`, innermost first.
+    ///
+    /// A single wasm frame expands into several profile frames if its
+    /// debug information says that functions were inlined into it.
+    fn frames_of(&mut self, frame: &FrameInfo) -> Vec<ProfileFrame> {
+        let module = frame.module_name().map(|s| s.to_string());
+        let module_offset = frame.module_offset();
+        let raw_func_name = || match frame.func_name() {
+            Some(name) => demangle(name),
+            None => format!("<wasm function {}>", frame.func_index()),
+        };
+
+        if frame.symbols().is_empty() {
+            let function = ProfileFunction {
+                name: raw_func_name(),
+                module,
+                file: None,
+            };
+            return vec![ProfileFrame {
+                function: self.functions.insert_full(function).0,
+                module_offset,
+                line: None,
+                column: None,
+            }];
+        }
+
+        let mut frames = Vec::new();
+        for (i, symbol) in frame.symbols().iter().enumerate() {
+            let name = match symbol.name() {
+                Some(name) => demangle(name),
+                None if i == 0 => raw_func_name(),
+                None => "<inlined function>".to_string(),
+            };
+            let function = ProfileFunction {
+                name,
+                module: module.clone(),
+                file: symbol.file().map(|s| s.to_string()),
+            };
+            frames.push(ProfileFrame {
+                function: self.functions.insert_full(function).0,
+                module_offset,
+                line: symbol.line(),
+                column: symbol.column(),
+            });
+        }
+        frames
+    }
+
+    /// Writes out the profile in the given `format` to `output`.
+    pub fn finish(self, format: GuestProfileFormat, mut output: impl Write) -> Result<()> {
+        let bytes = match format {
+            GuestProfileFormat::Firefox => self.firefox_profile().into_bytes(),
+            GuestProfileFormat::Pprof => self.pprof_profile(),
+        };
+        output.write_all(&bytes)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Encodes this profile in the Gecko profile format, the format that
+    /// Firefox itself emits and which the Firefox Profiler upgrades on load.
+    fn firefox_profile(&self) -> String {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        let start_time = self
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut strings = StringTable::default();
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let function = &self.functions[frame.function];
+                let location = match &function.module {
+                    Some(module) => format!("{} ({})", function.name, module),
+                    None => function.name.clone(),
+                };
+                format!(
+                    "[{},false,0,null,{},{},0,0]",
+                    strings.intern(&location),
+                    json_option(frame.line),
+                    json_option(frame.column),
+                )
+            })
+            .collect::<Vec<_>>();
+        let stacks = self
+            .stacks
+            .iter()
+            .map(|(prefix, frame)| format!("[{},{}]", json_option(*prefix), frame))
+            .collect::<Vec<_>>();
+        let samples = self
+            .samples
+            .iter()
+            .map(|(stack, time)| format!("[{},{},0]", stack, millis(*time)))
+            .collect::<Vec<_>>();
+        let strings = strings
+            .strings
+            .iter()
+            .map(|s| json_string(s))
+            .collect::<Vec<_>>();
+        let name = json_string(&self.name);
+
+        format!(
+            concat!(
+                "{{",
+                "\"meta\":{{",
+                "\"version\":24,",
+                "\"startTime\":{start_time},",
+                "\"shutdownTime\":null,",
+                "\"interval\":{interval},",
+                "\"stackwalk\":1,",
+                "\"debug\":0,",
+                "\"gcpoison\":0,",
+                "\"asyncstack\":0,",
+                "\"processType\":0,",
+                "\"product\":{name},",
+                "\"categories\":[",
+                "{{\"name\":\"WebAssembly\",\"color\":\"blue\",\"subcategories\":[\"Other\"]}}",
+                "],",
+                "\"markerSchema\":[]",
+                "}},",
+                "\"libs\":[],",
+                "\"pausedRanges\":[],",
+                "\"processes\":[],",
+                "\"threads\":[{{",
+                "\"name\":{name},",
+                "\"processType\":\"default\",",
+                "\"processName\":{name},",
+                "\"tid\":0,",
+                "\"pid\":0,",
+                "\"registerTime\":0,",
+                "\"unregisterTime\":null,",
+                "\"markers\":{{",
+                "\"schema\":{{\"name\":0,\"startTime\":1,\"endTime\":2,\"phase\":3,\"category\":4,\"data\":5}},",
+                "\"data\":[]",
+                "}},",
+                "\"samples\":{{",
+                "\"schema\":{{\"stack\":0,\"time\":1,\"eventDelay\":2}},",
+                "\"data\":[{samples}]",
+                "}},",
+                "\"frameTable\":{{",
+                "\"schema\":{{\"location\":0,\"relevantForJS\":1,\"innerWindowID\":2,",
+                "\"implementation\":3,\"line\":4,\"column\":5,\"category\":6,\"subcategory\":7}},",
+                "\"data\":[{frames}]",
+                "}},",
+                "\"stackTable\":{{",
+                "\"schema\":{{\"prefix\":0,\"frame\":1}},",
+                "\"data\":[{stacks}]",
+                "}},",
+                "\"stringTable\":[{strings}]",
+                "}}]",
+                "}}",
+            ),
+            start_time = millis(start_time),
+            interval = millis(self.interval),
+            name = name,
+            samples = samples.join(","),
+            frames = frames.join(","),
+            stacks = stacks.join(","),
+            strings = strings.join(","),
+        )
+    }
+
+    /// Encodes this profile as a pprof `Profile` protobuf message.
+    ///
+    /// Every profile frame becomes a location whose address is the frame's
+    /// offset within the original wasm module, and samples with identical
+    /// stacks are merged together.
+    fn pprof_profile(&self) -> Vec<u8> {
+        // Field numbers from pprof's `profile.proto`.
+        const PROFILE_SAMPLE_TYPE: u32 = 1;
+        const PROFILE_SAMPLE: u32 = 2;
+        const PROFILE_LOCATION: u32 = 4;
+        const PROFILE_FUNCTION: u32 = 5;
+        const PROFILE_STRING_TABLE: u32 = 6;
+        const PROFILE_TIME_NANOS: u32 = 9;
+        const PROFILE_DURATION_NANOS: u32 = 10;
+        const PROFILE_PERIOD_TYPE: u32 = 11;
+        const PROFILE_PERIOD: u32 = 12;
+        const VALUE_TYPE_TYPE: u32 = 1;
+        const VALUE_TYPE_UNIT: u32 = 2;
+        const SAMPLE_LOCATION_ID: u32 = 1;
+        const SAMPLE_VALUE: u32 = 2;
+        const LOCATION_ID: u32 = 1;
+        const LOCATION_ADDRESS: u32 = 3;
+        const LOCATION_LINE: u32 = 4;
+        const LINE_FUNCTION_ID: u32 = 1;
+        const LINE_LINE: u32 = 2;
+        const FUNCTION_ID: u32 = 1;
+        const FUNCTION_NAME: u32 = 2;
+        const FUNCTION_SYSTEM_NAME: u32 = 3;
+        const FUNCTION_FILENAME: u32 = 4;
+
+        let nanos = |d: Duration| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        let interval = nanos(self.interval);
+        let mut strings = StringTable::default();
+        let mut profile = ProtoWriter::default();
+
+        let samples_str = strings.intern("samples");
+        let count_str = strings.intern("count");
+        let wall_str = strings.intern("wall");
+        let nanoseconds_str = strings.intern("nanoseconds");
+        for (ty, unit) in [(samples_str, count_str), (wall_str, nanoseconds_str)].iter() {
+            profile.message(PROFILE_SAMPLE_TYPE, |w| {
+                w.uint64(VALUE_TYPE_TYPE, *ty);
+                w.uint64(VALUE_TYPE_UNIT, *unit);
+            });
+        }
+
+        let mut counts = HashMap::new();
+        for (stack, _time) in self.samples.iter() {
+            *counts.entry(*stack).or_insert(0u64) += 1;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort();
+        for (stack, count) in counts {
+            // Locations are listed from the innermost frame outwards, and ids
+            // are one-based since zero is reserved.
+            let mut locations = Vec::new();
+            let mut next = Some(stack);
+            while let Some(stack) = next {
+                let (prefix, frame) = self.stacks[stack];
+                locations.push(frame as u64 + 1);
+                next = prefix;
+            }
+            profile.message(PROFILE_SAMPLE, |w| {
+                w.packed(SAMPLE_LOCATION_ID, &locations);
+                w.packed(SAMPLE_VALUE, &[count, count.saturating_mul(interval)]);
+            });
+        }
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            profile.message(PROFILE_LOCATION, |w| {
+                w.uint64(LOCATION_ID, i as u64 + 1);
+                w.uint64(LOCATION_ADDRESS, frame.module_offset as u64);
+                w.message(LOCATION_LINE, |w| {
+                    w.uint64(LINE_FUNCTION_ID, frame.function as u64 + 1);
+                    w.uint64(LINE_LINE, u64::from(frame.line.unwrap_or(0)));
+                });
+            });
+        }
+
+        for (i, function) in self.functions.iter().enumerate() {
+            let name = strings.intern(&function.name);
+            let filename = match (&function.file, &function.module) {
+                (Some(file), _) => strings.intern(file),
+                (None, Some(module)) => strings.intern(module),
+                (None, None) => 0,
+            };
+            profile.message(PROFILE_FUNCTION, |w| {
+                w.uint64(FUNCTION_ID, i as u64 + 1);
+                w.uint64(FUNCTION_NAME, name);
+                w.uint64(FUNCTION_SYSTEM_NAME, name);
+                w.uint64(FUNCTION_FILENAME, filename);
+            });
+        }
+
+        for s in strings.strings.iter() {
+            profile.bytes(PROFILE_STRING_TABLE, s.as_bytes());
+        }
+
+        let start_time = self
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        profile.uint64(PROFILE_TIME_NANOS, nanos(start_time));
+        profile.uint64(PROFILE_DURATION_NANOS, nanos(self.start.elapsed()));
+        profile.message(PROFILE_PERIOD_TYPE, |w| {
+            w.uint64(VALUE_TYPE_TYPE, wall_str);
+            w.uint64(VALUE_TYPE_UNIT, nanoseconds_str);
+        });
+        profile.uint64(PROFILE_PERIOD, interval);
+
+        profile.0
+    }
+}
+
+fn demangle(name: &str) -> String {
+    let mut demangled = String::new();
+    match demangle_function_name(&mut demangled, name) {
+        Ok(()) => demangled,
+        Err(_) => name.to_string(),
+    }
+}
+
+/// A table of interned strings, where the empty string is always index 0 as
+/// required by pprof.
+struct StringTable {
+    strings: IndexSet<String>,
+}
+
+impl Default for StringTable {
+    fn default() -> StringTable {
+        let mut strings = IndexSet::new();
+        strings.insert(String::new());
+        StringTable { strings }
+    }
+}
+
+impl StringTable {
+    fn intern(&mut self, s: &str) -> u64 {
+        match self.strings.get_index_of(s) {
+            Some(i) => i as u64,
+            None => self.strings.insert_full(s.to_string()).0 as u64,
+        }
+    }
+}
+
+fn json_option(value: Option<impl std::fmt::Display>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// A minimal encoder for the subset of the protobuf wire format used by
+/// pprof profiles.
+#[derive(Default)]
+struct ProtoWriter(Vec<u8>);
+
+impl ProtoWriter {
+    const VARINT: u32 = 0;
+    const LENGTH_DELIMITED: u32 = 2;
+
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.0.push(byte);
+                return;
+            }
+            self.0.push(byte | 0x80);
+        }
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        self.varint(u64::from(field << 3 | wire_type));
+    }
+
+    fn uint64(&mut self, field: u32, value: u64) {
+        // Zero is the default value of a field, so it needn't be written.
+        if value != 0 {
+            self.key(field, Self::VARINT);
+            self.varint(value);
+        }
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.key(field, Self::LENGTH_DELIMITED);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn packed(&mut self, field: u32, values: &[u64]) {
+        let mut packed = ProtoWriter::default();
+        for value in values {
+            packed.varint(*value);
+        }
+        self.bytes(field, &packed.0);
+    }
+
+    fn message(&mut self, field: u32, encode: impl FnOnce(&mut ProtoWriter)) {
+        let mut message = ProtoWriter::default();
+        encode(&mut message);
+        self.bytes(field, &message.0);
+    }
+}
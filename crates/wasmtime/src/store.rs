@@ -1,4 +1,5 @@
-use crate::{module::ModuleRegistry, Engine, Module, Trap};
+use crate::debugger::{DebugAction, DebugEvent, DebugFrame, DebugState};
+use crate::{module::ModuleRegistry, Engine, Module, Trap, WasmBacktrace};
 use anyhow::{bail, Result};
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
@@ -11,12 +12,14 @@ use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
 use std::ptr;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use wasmtime_environ::{FuncIndex, WasmFuncType};
 use wasmtime_runtime::{
-    InstanceAllocationRequest, InstanceAllocator, InstanceHandle, ModuleInfo,
-    OnDemandInstanceAllocator, SignalHandler, VMCallerCheckedAnyfunc, VMContext, VMExternRef,
-    VMExternRefActivationsTable, VMInterrupts, VMSharedSignatureIndex, VMTrampoline,
+    ExceptionState, InstanceAllocationRequest, InstanceAllocator, InstanceHandle, ModuleInfo,
+    OnDemandInstanceAllocator, SignalHandler, VMCallerCheckedAnyfunc, VMContext, VMDebugValue,
+    VMExternRef, VMExternRefActivationsTable, VMInterrupts, VMSharedSignatureIndex, VMTrampoline,
 };
 
 mod context;
@@ -106,6 +109,11 @@ pub struct StoreInner<T> {
     limiter: Option<Box<dyn FnMut(&mut T) -> &mut (dyn crate::ResourceLimiter) + Send + Sync>>,
     entering_native_hook: Option<Box<dyn FnMut(&mut T) -> Result<(), crate::Trap> + Send + Sync>>,
     exiting_native_hook: Option<Box<dyn FnMut(&mut T) -> Result<(), crate::Trap> + Send + Sync>>,
+    epoch_deadline_callback:
+        Option<Box<dyn FnMut(StoreContextMut<'_, T>) -> Result<u64, Trap> + Send + Sync>>,
+    debug_handler: Option<
+        Box<dyn FnMut(DebugFrame<'_, T>, DebugEvent) -> Result<DebugAction, Trap> + Send + Sync>,
+    >,
     // for comments about `ManuallyDrop`, see `Store::into_data`
     data: ManuallyDrop<T>,
 }
@@ -146,6 +154,12 @@ pub struct StoreInnermost {
     #[cfg(feature = "async")]
     async_state: AsyncState,
     out_of_gas_behavior: OutOfGas,
+    epoch_deadline_behavior: EpochDeadline,
+    debug: DebugState,
+    exceptions: ExceptionState,
+    /// Signatures of tags created by the host, which stay registered with the
+    /// engine for as long as this store lives.
+    host_tag_signatures: Vec<VMSharedSignatureIndex>,
     store_data: StoreData,
     default_callee: InstanceHandle,
 }
@@ -183,6 +197,21 @@ enum OutOfGas {
     },
 }
 
+/// What to do when the engine epoch reaches the deadline for a Store
+/// during execution of a function using that store.
+#[derive(Copy, Clone)]
+enum EpochDeadline {
+    /// Return early with a trap.
+    Trap,
+    /// Extend the deadline by the specified number of ticks after
+    /// yielding to the async executor loop.
+    #[cfg(feature = "async")]
+    YieldAndExtendDeadline { delta: u64 },
+    /// Call the store's epoch deadline callback, which either returns a
+    /// trap or the number of ticks to extend the deadline by.
+    Callback,
+}
+
 impl<T> Store<T> {
     /// Creates a new [`Store`] to be associated with the given [`Engine`] and
     /// `data` provided.
@@ -212,6 +241,7 @@ impl<T> Store<T> {
                     module: Arc::new(wasmtime_environ::Module::default()),
                     store: None,
                     wasm_data: &[],
+                    memory_images: None,
                 })
                 .expect("failed to allocate default callee")
         };
@@ -238,12 +268,18 @@ impl<T> Store<T> {
                     current_poll_cx: UnsafeCell::new(ptr::null_mut()),
                 },
                 out_of_gas_behavior: OutOfGas::Trap,
+                epoch_deadline_behavior: EpochDeadline::Trap,
+                debug: DebugState::default(),
+                exceptions: ExceptionState::default(),
+                host_tag_signatures: Vec::new(),
                 store_data: StoreData::new(),
                 default_callee,
             },
             limiter: None,
             entering_native_hook: None,
             exiting_native_hook: None,
+            epoch_deadline_callback: None,
+            debug_handler: None,
             data: ManuallyDrop::new(data),
         });
 
@@ -468,6 +504,17 @@ impl<T> Store<T> {
         self.inner.interrupt_handle()
     }
 
+    /// Captures a backtrace of the WebAssembly frames currently executing
+    /// within this store.
+    ///
+    /// This is most useful from within host functions, where it can be used
+    /// to inspect which wasm functions led to the host being called without
+    /// needing a trap to happen. See [`WasmBacktrace::capture`] for more
+    /// information.
+    pub fn backtrace(&self) -> WasmBacktrace {
+        WasmBacktrace::capture(self)
+    }
+
     /// Perform garbage collection of `ExternRef`s.
     ///
     /// Note that it is not required to actively call this function. GC will
@@ -563,6 +610,224 @@ impl<T> Store<T> {
         self.inner
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Sets the epoch deadline to a certain number of ticks in the future.
+    ///
+    /// When the Wasm guest code is compiled with epoch-interruption
+    /// instrumentation
+    /// ([`Config::epoch_interruption()`](crate::Config::epoch_interruption)),
+    /// and when the `Engine`'s epoch is incremented
+    /// ([`Engine::increment_epoch()`](crate::Engine::increment_epoch))
+    /// past a deadline, execution can be configured to either trap or
+    /// yield and then continue.
+    ///
+    /// This deadline is always set relative to the current epoch:
+    /// `delta_beyond_current` ticks in the future. The deadline can
+    /// be set explicitly via this method, or refilled automatically
+    /// on a yield if configured via
+    /// [`epoch_deadline_async_yield_and_update()`](Store::epoch_deadline_async_yield_and_update). After
+    /// this method is invoked, the deadline is reached when
+    /// [`Engine::increment_epoch()`] has been invoked at least
+    /// `ticks_beyond_current` times.
+    ///
+    /// Note that a [`Store`] starts with a deadline of zero, so wasm
+    /// compiled with epoch interruption will immediately reach its
+    /// deadline unless this method is called first.
+    ///
+    /// See documentation on
+    /// [`Config::epoch_interruption()`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption.
+    ///
+    /// [`Engine::increment_epoch()`]: crate::Engine::increment_epoch
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.inner.set_epoch_deadline(ticks_beyond_current);
+    }
+
+    /// Configures epoch-deadline expiration to trap.
+    ///
+    /// When epoch-interruption-instrumented code is executed on this
+    /// store and the epoch deadline is reached before completion,
+    /// with the store configured in this way, execution will
+    /// terminate with a trap as soon as an epoch check in the
+    /// instrumented code is reached.
+    ///
+    /// This behavior is the default if the store is not otherwise
+    /// configured via
+    /// [`epoch_deadline_trap()`](Store::epoch_deadline_trap) or
+    /// [`epoch_deadline_async_yield_and_update()`](Store::epoch_deadline_async_yield_and_update).
+    ///
+    /// This setting is intended to allow for coarse-grained
+    /// interruption, but not a deterministic deadline of a fixed,
+    /// finite interval. For deterministic interruption, see the
+    /// "fuel" mechanism instead.
+    ///
+    /// See documentation on
+    /// [`Config::epoch_interruption()`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption.
+    pub fn epoch_deadline_trap(&mut self) {
+        self.inner.epoch_deadline_trap();
+    }
+
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    /// Configures epoch-deadline expiration to yield to the async
+    /// caller and the update the deadline.
+    ///
+    /// When epoch-interruption-instrumented code is executed on this
+    /// store and the epoch deadline is reached before completion,
+    /// with the store configured in this way, execution will yield
+    /// (the future will return `Pending` but re-awake itself for
+    /// later execution) and, upon resuming, the store will be
+    /// configured with an epoch deadline equal to the current epoch
+    /// plus `delta` ticks.
+    ///
+    /// This setting is intended to allow for cooperative timeslicing
+    /// of multiple CPU-bound Wasm guests in different stores, all
+    /// executing under the control of an async executor. To drive
+    /// this, stores should be configured to "yield and update"
+    /// automatically with this function, and some external driver (a
+    /// thread that wakes up periodically, or a timer
+    /// signal/interrupt) should call
+    /// [`Engine::increment_epoch()`](crate::Engine::increment_epoch).
+    ///
+    /// See documentation on
+    /// [`Config::epoch_interruption()`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if it is not called on a store associated with an [async
+    /// config](crate::Config::async_support).
+    #[cfg(feature = "async")]
+    pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
+        self.inner.epoch_deadline_async_yield_and_update(delta);
+    }
+
+    /// Configures epoch-deadline expiration to invoke a custom callback
+    /// function.
+    ///
+    /// When epoch-interruption-instrumented code is executed on this store
+    /// and the epoch deadline is reached before completion, the provided
+    /// callback function is invoked with the store. The callback may either
+    /// return a [`Trap`] to terminate execution, or the number of ticks to
+    /// extend the deadline by, after which execution resumes.
+    ///
+    /// This can be used to run periodic work while wasm is executing, for
+    /// example sampling the wasm stack with a
+    /// [`GuestProfiler`](crate::GuestProfiler) whenever a timer thread calls
+    /// [`Engine::increment_epoch()`](crate::Engine::increment_epoch).
+    ///
+    /// This setting is overridden by later calls to
+    /// [`epoch_deadline_trap()`](Store::epoch_deadline_trap) or
+    /// [`epoch_deadline_async_yield_and_update()`](Store::epoch_deadline_async_yield_and_update).
+    ///
+    /// See documentation on
+    /// [`Config::epoch_interruption()`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption.
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>) -> Result<u64, Trap> + Send + Sync + 'static,
+    ) {
+        self.inner.epoch_deadline_callback(Box::new(callback));
+    }
+
+    /// Configures the function invoked when wasm execution stops at a
+    /// breakpoint or while single-stepping.
+    ///
+    /// Stopping requires modules to be compiled with
+    /// [`Config::guest_debug`](crate::Config::guest_debug) enabled. Execution
+    /// stops before any wasm instruction at a location added with
+    /// [`Store::add_breakpoint`], and before every instruction while the store
+    /// is single-stepping. The `handler` is then passed a [`DebugFrame`],
+    /// through which the locals and operand stack of the stopped function can
+    /// be read along with the rest of the store, and the [`DebugEvent`] which
+    /// caused it to stop.
+    ///
+    /// The handler returns the [`DebugAction`] to continue execution with,
+    /// which may start or stop single-stepping. Breakpoints may be added and
+    /// removed from within the handler. If the handler returns a [`Trap`] then
+    /// wasm execution is aborted with that trap.
+    ///
+    /// While the handler runs it's removed from the store, so any wasm it calls
+    /// runs without stopping.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut config = Config::new();
+    /// config.guest_debug(true);
+    /// let engine = Engine::new(&config)?;
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"
+    ///         (module
+    ///             (func (export "add") (param i32 i32) (result i32)
+    ///                 local.get 0
+    ///                 local.get 1
+    ///                 i32.add))
+    ///     "#,
+    /// )?;
+    ///
+    /// let mut store = Store::new(&engine, Vec::new());
+    /// store.debug_handler(|mut frame, _event| {
+    ///     let stack = frame.stack().to_vec();
+    ///     frame.data_mut().push(stack);
+    ///     Ok(DebugAction::Continue)
+    /// });
+    /// // Stop before the `i32.add`, at offset 5 within the function's body.
+    /// store.add_breakpoint(&module, 0, 5)?;
+    ///
+    /// let instance = Instance::new(&mut store, &module, &[])?;
+    /// let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    /// assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    /// assert_eq!(store.data()[0][1].unwrap_i32(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn debug_handler(
+        &mut self,
+        handler: impl FnMut(DebugFrame<'_, T>, DebugEvent) -> Result<DebugAction, Trap>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.inner.debug_handler = Some(Box::new(handler));
+    }
+
+    /// Adds a breakpoint before the instruction at `offset` within the body of
+    /// the function `func_index` of `module`.
+    ///
+    /// The function index is in the function index space of the module, and
+    /// the offset is relative to the start of the function's body in the wasm
+    /// binary, as with [`FrameInfo::func_offset`](crate::FrameInfo::func_offset).
+    /// Breakpoints at offsets which aren't the start of an instruction are
+    /// never reached. See [`Store::debug_handler`] for what happens when a
+    /// breakpoint is reached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `module` wasn't compiled with
+    /// [`Config::guest_debug`](crate::Config::guest_debug) enabled, belongs
+    /// to a different engine, or doesn't define the function `func_index`.
+    pub fn add_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> Result<()> {
+        self.inner.add_breakpoint(module, func_index, offset)
+    }
+
+    /// Removes a breakpoint previously added with [`Store::add_breakpoint`],
+    /// returning whether it existed.
+    pub fn remove_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> bool {
+        self.inner.remove_breakpoint(module, func_index, offset)
+    }
+
+    /// Configures whether this store is single-stepping, in which case wasm
+    /// execution stops before the next instruction executed.
+    ///
+    /// This can be used to stop at the very start of execution. See
+    /// [`Store::debug_handler`] for more information.
+    pub fn debug_single_step(&mut self, enable: bool) {
+        self.inner.debug_single_step(enable)
+    }
 }
 
 impl<'a, T> StoreContext<'a, T> {
@@ -589,6 +854,14 @@ impl<'a, T> StoreContext<'a, T> {
         self.0.data()
     }
 
+    /// Captures a backtrace of the WebAssembly frames currently executing
+    /// within this store.
+    ///
+    /// For more information see [`Store::backtrace`].
+    pub fn backtrace(&self) -> WasmBacktrace {
+        WasmBacktrace::capture(self)
+    }
+
     /// Returns the fuel consumed by this store.
     ///
     /// For more information see [`Store::fuel_consumed`].
@@ -631,6 +904,14 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.gc()
     }
 
+    /// Captures a backtrace of the WebAssembly frames currently executing
+    /// within this store.
+    ///
+    /// For more information see [`Store::backtrace`].
+    pub fn backtrace(&self) -> WasmBacktrace {
+        WasmBacktrace::capture(self)
+    }
+
     /// Returns the fuel consumed by this store.
     ///
     /// For more information see [`Store::fuel_consumed`].
@@ -660,6 +941,77 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Sets the epoch deadline to a certain number of ticks in the future.
+    ///
+    /// For more information see [`Store::set_epoch_deadline`].
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.0.set_epoch_deadline(ticks_beyond_current);
+    }
+
+    /// Configures epoch-deadline expiration to trap.
+    ///
+    /// For more information see [`Store::epoch_deadline_trap`].
+    pub fn epoch_deadline_trap(&mut self) {
+        self.0.epoch_deadline_trap();
+    }
+
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    /// Configures epoch-deadline expiration to yield to the async
+    /// caller and the update the deadline.
+    ///
+    /// For more information see
+    /// [`Store::epoch_deadline_async_yield_and_update`].
+    #[cfg(feature = "async")]
+    pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
+        self.0.epoch_deadline_async_yield_and_update(delta);
+    }
+
+    /// Configures epoch-deadline expiration to invoke a custom callback
+    /// function.
+    ///
+    /// For more information see [`Store::epoch_deadline_callback`].
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>) -> Result<u64, Trap> + Send + Sync + 'static,
+    ) {
+        self.0.epoch_deadline_callback(Box::new(callback));
+    }
+
+    /// Configures the function invoked when wasm execution stops at a
+    /// breakpoint or while single-stepping.
+    ///
+    /// For more information see [`Store::debug_handler`].
+    pub fn debug_handler(
+        &mut self,
+        handler: impl FnMut(DebugFrame<'_, T>, DebugEvent) -> Result<DebugAction, Trap>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.0.debug_handler = Some(Box::new(handler));
+    }
+
+    /// Adds a breakpoint to a function of `module`.
+    ///
+    /// For more information see [`Store::add_breakpoint`].
+    pub fn add_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> Result<()> {
+        self.0.add_breakpoint(module, func_index, offset)
+    }
+
+    /// Removes a breakpoint from a function of `module`.
+    ///
+    /// For more information see [`Store::remove_breakpoint`].
+    pub fn remove_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> bool {
+        self.0.remove_breakpoint(module, func_index, offset)
+    }
+
+    /// Configures whether this store is single-stepping.
+    ///
+    /// For more information see [`Store::debug_single_step`].
+    pub fn debug_single_step(&mut self, enable: bool) {
+        self.0.debug_single_step(enable)
+    }
 }
 
 impl<T> StoreInner<T> {
@@ -693,6 +1045,14 @@ impl<T> StoreInner<T> {
             Ok(())
         }
     }
+
+    fn epoch_deadline_callback(
+        &mut self,
+        callback: Box<dyn FnMut(StoreContextMut<'_, T>) -> Result<u64, Trap> + Send + Sync>,
+    ) {
+        self.epoch_deadline_behavior = EpochDeadline::Callback;
+        self.epoch_deadline_callback = Some(callback);
+    }
 }
 
 impl StoreInnermost {
@@ -761,6 +1121,11 @@ impl StoreInnermost {
         }
     }
 
+    #[inline]
+    pub(crate) fn modules(&self) -> &ModuleRegistry {
+        &self.modules
+    }
+
     #[inline]
     pub(crate) fn modules_mut(&mut self) -> &mut ModuleRegistry {
         &mut self.modules
@@ -857,6 +1222,18 @@ impl StoreInnermost {
     /// and when we come back we'll continue with `fuel_to_inject` more fuel.
     #[cfg(feature = "async")]
     fn out_of_gas_yield(&mut self, fuel_to_inject: u64) -> Result<(), Trap> {
+        self.async_yield_impl()?;
+        self.add_fuel(fuel_to_inject).unwrap();
+        Ok(())
+    }
+
+    /// Yields execution to the caller once, returning when the future
+    /// representing the current wasm execution is polled again.
+    ///
+    /// This only works on async futures and stores, and assumes that we're
+    /// executing on a fiber.
+    #[cfg(feature = "async")]
+    fn async_yield_impl(&mut self) -> Result<(), Trap> {
         // Small future that yields once and then returns ()
         #[derive(Default)]
         struct Yield {
@@ -881,19 +1258,13 @@ impl StoreInnermost {
         }
 
         let mut future = Yield::default();
-        let result = unsafe { self.async_cx().block_on(Pin::new_unchecked(&mut future)) };
-        match result {
-            // If this finished successfully then we were resumed normally via a
-            // `poll`, so inject some more fuel and keep going.
-            Ok(()) => {
-                self.add_fuel(fuel_to_inject).unwrap();
-                Ok(())
-            }
-            // If the future was dropped while we were yielded, then we need to
-            // clean up this fiber. Do so by raising a trap which will abort all
-            // wasm and get caught on the other side to clean things up.
-            Err(trap) => Err(trap),
-        }
+
+        // If this finished successfully then we were resumed normally via a
+        // `poll`, so keep going. If the future was dropped while we were
+        // yielded, then we need to clean up this fiber. Do so by raising a trap
+        // which will abort all wasm and get caught on the other side to clean
+        // things up.
+        unsafe { self.async_cx().block_on(Pin::new_unchecked(&mut future)) }
     }
 
     fn add_fuel(&mut self, fuel: u64) -> Result<()> {
@@ -941,6 +1312,83 @@ impl StoreInnermost {
         &*self.interrupts as *const VMInterrupts as *mut VMInterrupts
     }
 
+    #[inline]
+    pub fn exceptions(&mut self) -> &mut ExceptionState {
+        &mut self.exceptions
+    }
+
+    pub(crate) fn register_host_tag_signature(
+        &mut self,
+        ty: &WasmFuncType,
+    ) -> VMSharedSignatureIndex {
+        let signature = self.engine.signatures().register(ty);
+        self.host_tag_signatures.push(signature);
+        signature
+    }
+
+    #[inline]
+    pub fn epoch_ptr(&self) -> *const AtomicU64 {
+        self.engine.epoch_counter() as *const AtomicU64
+    }
+
+    fn set_epoch_deadline(&mut self, delta: u64) {
+        // Set a new deadline based on the "epoch deadline delta".
+        //
+        // Safety: this is safe because the epoch deadline in the
+        // `VMInterrupts` is accessed only here and by Wasm guest code
+        // running in this store, and we have a `&mut self` here.
+        //
+        // Also, note that when this update is performed while Wasm is
+        // on the stack, the Wasm will reload the new value once we
+        // return into it.
+        let epoch_deadline = unsafe { &mut *self.interrupts.epoch_deadline.get() };
+        *epoch_deadline = self.engine.current_epoch().saturating_add(delta);
+    }
+
+    fn epoch_deadline(&self) -> u64 {
+        unsafe { *self.interrupts.epoch_deadline.get() }
+    }
+
+    fn epoch_deadline_trap(&mut self) {
+        self.epoch_deadline_behavior = EpochDeadline::Trap;
+    }
+
+    fn add_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> Result<()> {
+        if !Engine::same(module.engine(), &self.engine) {
+            bail!("cannot add a breakpoint to a module from a different engine");
+        }
+        self.debug.add_breakpoint(module, func_index, offset)?;
+        self.update_debug_hooks();
+        Ok(())
+    }
+
+    fn remove_breakpoint(&mut self, module: &Module, func_index: u32, offset: u32) -> bool {
+        let removed = self.debug.remove_breakpoint(module, func_index, offset);
+        self.update_debug_hooks();
+        removed
+    }
+
+    fn debug_single_step(&mut self, enable: bool) {
+        self.debug.set_single_step(enable);
+        self.update_debug_hooks();
+    }
+
+    fn update_debug_hooks(&mut self) {
+        // Safety: as with the epoch deadline, this field of `VMInterrupts` is
+        // only accessed here and by wasm running in this store.
+        let debug_hooks = unsafe { &mut *self.interrupts.debug_hooks.get() };
+        *debug_hooks = self.debug.hooks_enabled() as u32;
+    }
+
+    #[cfg(feature = "async")]
+    fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
+        assert!(
+            self.async_support(),
+            "cannot use `epoch_deadline_async_yield_and_update` without enabling async support in the config"
+        );
+        self.epoch_deadline_behavior = EpochDeadline::YieldAndExtendDeadline { delta };
+    }
+
     pub unsafe fn insert_vmexternref(&mut self, r: VMExternRef) {
         self.externref_activations_table
             .insert_with_gc(r, &self.modules)
@@ -1242,6 +1690,15 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
         <StoreInnermost>::vminterrupts(self)
     }
 
+    fn exceptions(&mut self) -> (&mut ExceptionState, &dyn wasmtime_runtime::ModuleInfoLookup) {
+        let inner = &mut self.inner;
+        (&mut inner.exceptions, &inner.modules)
+    }
+
+    fn epoch_ptr(&self) -> *const AtomicU64 {
+        <StoreInnermost>::epoch_ptr(self)
+    }
+
     fn externref_activations_table(
         &mut self,
     ) -> (
@@ -1287,6 +1744,80 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
 
         impl std::error::Error for OutOfGasError {}
     }
+
+    fn new_epoch(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        match self.epoch_deadline_behavior {
+            EpochDeadline::Trap => Err(Box::new(Trap::new_wasm(
+                None,
+                wasmtime_environ::TrapCode::Interrupt,
+                backtrace::Backtrace::new_unresolved(),
+            ))),
+            #[cfg(feature = "async")]
+            EpochDeadline::YieldAndExtendDeadline { delta } => {
+                // Do the async yield. May return a trap if future was
+                // canceled while we're yielded.
+                StoreContextMut(self).opaque().async_yield_impl()?;
+                // Set a new deadline.
+                self.set_epoch_deadline(delta);
+                Ok(self.epoch_deadline())
+            }
+            EpochDeadline::Callback => {
+                // Take the callback out of the store while it runs so it can
+                // be handed the store itself, and then put it back unless it
+                // configured a new callback in the meantime.
+                let mut callback = self.epoch_deadline_callback.take().unwrap();
+                let result = callback(StoreContextMut(self));
+                if self.epoch_deadline_callback.is_none() {
+                    self.epoch_deadline_callback = Some(callback);
+                }
+                let delta = result?;
+                self.set_epoch_deadline(delta);
+                Ok(self.epoch_deadline())
+            }
+        }
+    }
+
+    fn debug_hook(
+        &mut self,
+        vmctx: *mut VMContext,
+        func_index: FuncIndex,
+        offset: u32,
+        locals: &[VMDebugValue],
+        stack: &[VMDebugValue],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let instance = unsafe { InstanceHandle::from_vmctx(vmctx) };
+        let event = match self.debug.event(&instance, func_index, offset) {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+        // As with the epoch deadline callback the handler is taken out of the
+        // store while it runs, which also means that wasm called by the
+        // handler doesn't stop.
+        let mut handler = match self.debug_handler.take() {
+            Some(handler) => handler,
+            None => return Ok(()),
+        };
+        let frame = unsafe {
+            DebugFrame::new(
+                StoreContextMut(self),
+                &instance,
+                func_index,
+                offset,
+                locals,
+                stack,
+            )
+        };
+        let result = handler(frame, event);
+        if self.debug_handler.is_none() {
+            self.debug_handler = Some(handler);
+        }
+        let single_step = match result? {
+            DebugAction::Continue => false,
+            DebugAction::Step => true,
+        };
+        self.debug_single_step(single_step);
+        Ok(())
+    }
 }
 
 impl<T: Default> Default for Store<T> {
@@ -1331,6 +1862,9 @@ impl Drop for StoreInnermost {
                 }
             }
             ondemand.deallocate(&self.default_callee);
+            for signature in self.host_tag_signatures.iter() {
+                self.engine.signatures().unregister(*signature);
+            }
         }
     }
 }
@@ -21,7 +21,7 @@ use wasmtime_environ::{
 };
 use wasmtime_runtime::{
     Imports, InstanceAllocationRequest, InstanceAllocator, OnDemandInstanceAllocator,
-    VMFunctionBody, VMFunctionImport, VMSharedSignatureIndex,
+    RuntimeMemoryCreator, SharedMemory, VMFunctionBody, VMFunctionImport, VMSharedSignatureIndex,
 };
 
 fn create_handle(
@@ -31,17 +31,37 @@ fn create_handle(
     host_state: Box<dyn Any + Send + Sync>,
     func_imports: &[VMFunctionImport],
     shared_signature_id: Option<VMSharedSignatureIndex>,
+) -> Result<InstanceId> {
+    let mem_creator = store.engine().config().mem_creator.clone();
+    create_handle_with_mem_creator(
+        module,
+        store,
+        finished_functions,
+        host_state,
+        func_imports,
+        shared_signature_id,
+        mem_creator,
+    )
+}
+
+fn create_handle_with_mem_creator(
+    module: Module,
+    store: &mut StoreOpaque<'_>,
+    finished_functions: PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
+    host_state: Box<dyn Any + Send + Sync>,
+    func_imports: &[VMFunctionImport],
+    shared_signature_id: Option<VMSharedSignatureIndex>,
+    mem_creator: Option<Arc<dyn RuntimeMemoryCreator>>,
 ) -> Result<InstanceId> {
     let mut imports = Imports::default();
     imports.functions = func_imports;
 
     unsafe {
-        let config = store.engine().config();
         // Use the on-demand allocator when creating handles associated with host objects
         // The configured instance allocator should only be used when creating module instances
         // as we don't want host objects to count towards instance limits.
-        let handle = OnDemandInstanceAllocator::new(config.mem_creator.clone(), 0).allocate(
-            InstanceAllocationRequest {
+        let handle =
+            OnDemandInstanceAllocator::new(mem_creator, 0).allocate(InstanceAllocationRequest {
                 module: Arc::new(module),
                 finished_functions: &finished_functions,
                 imports,
@@ -49,8 +69,8 @@ fn create_handle(
                 host_state,
                 store: Some(store.traitobj),
                 wasm_data: &[],
-            },
-        )?;
+                memory_images: None,
+            })?;
 
         Ok(store.add_instance(handle, true))
     }
@@ -72,8 +92,9 @@ pub fn generate_global_export(
 pub fn generate_memory_export(
     store: &mut StoreOpaque<'_>,
     m: &MemoryType,
+    preallocation: Option<&SharedMemory>,
 ) -> Result<wasmtime_runtime::ExportMemory> {
-    let instance = create_memory(store, m)?;
+    let instance = create_memory(store, m, preallocation)?;
     let idx = EntityIndex::Memory(MemoryIndex::from_u32(0));
     match store.instance(instance).lookup_by_declaration(&idx) {
         wasmtime_runtime::Export::Memory(m) => Ok(m),
@@ -47,8 +47,8 @@ unsafe extern "C" fn stub_fn(
         // then we smuggle the trap through `Box<dyn Error>` through to the
         // call-site, which gets unwrapped in `Trap::from_runtime` later on as we
         // convert from the internal `Trap` type to our own `Trap` type in this
-        // crate.
-        Ok(Err(trap)) => wasmtime_runtime::raise_user_trap(Box::new(trap)),
+        // crate. Unless the trap threw an exception for wasm to catch, that is.
+        Ok(Err(trap)) => crate::exception::raise_from_host(caller_vmctx, trap),
 
         // And finally if the imported function panicked, then we trigger the
         // form of unwinding that's safe to jump over wasm code on all
@@ -90,8 +90,8 @@ pub fn create_function(
     let (wasm_i, wasm_trampoline) = trampolines.next().unwrap();
     assert_eq!(wasm_i.as_u32(), 1);
     assert!(trampolines.next().is_none());
-    let host_trampoline = host_trampoline.as_ptr();
-    let wasm_trampoline = wasm_trampoline as *mut [_];
+    let host_trampoline = unsafe { (*VMFunctionBody::entry(host_trampoline)).as_ptr() };
+    let wasm_trampoline = unsafe { VMFunctionBody::entry(wasm_trampoline) };
     drop(trampolines);
 
     code_memory.publish();
@@ -135,6 +135,7 @@ pub unsafe fn create_raw_function(
             host_state,
             store: None,
             wasm_data: &[],
+            memory_images: None,
         })?,
     )
 }
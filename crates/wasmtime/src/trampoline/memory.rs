@@ -1,14 +1,20 @@
 use crate::memory::{LinearMemory, MemoryCreator};
 use crate::store::{InstanceId, StoreOpaque};
-use crate::trampoline::create_handle;
+use crate::trampoline::{create_handle, create_handle_with_mem_creator};
 use crate::MemoryType;
 use anyhow::{anyhow, Result};
 use std::convert::TryFrom;
 use std::sync::Arc;
 use wasmtime_environ::{EntityIndex, MemoryPlan, MemoryStyle, Module, PrimaryMap, WASM_PAGE_SIZE};
-use wasmtime_runtime::{RuntimeLinearMemory, RuntimeMemoryCreator, VMMemoryDefinition};
+use wasmtime_runtime::{
+    MemoryImage, RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory, VMMemoryDefinition,
+};
 
-pub fn create_memory(store: &mut StoreOpaque<'_>, memory: &MemoryType) -> Result<InstanceId> {
+pub fn create_memory(
+    store: &mut StoreOpaque<'_>,
+    memory: &MemoryType,
+    preallocation: Option<&SharedMemory>,
+) -> Result<InstanceId> {
     let mut module = Module::new();
 
     let memory_plan = wasmtime_environ::MemoryPlan::for_memory(
@@ -20,7 +26,44 @@ pub fn create_memory(store: &mut StoreOpaque<'_>, memory: &MemoryType) -> Result
         .exports
         .insert(String::new(), EntityIndex::Memory(memory_id));
 
-    create_handle(module, store, PrimaryMap::new(), Box::new(()), &[], None)
+    match preallocation {
+        // Wrap an existing shared memory in a host instance of this store
+        // rather than creating a fresh one.
+        Some(shared) => create_handle_with_mem_creator(
+            module,
+            store,
+            PrimaryMap::new(),
+            Box::new(()),
+            &[],
+            None,
+            Some(Arc::new(PreallocatedSharedMemory(shared.clone()))),
+        ),
+        None => create_handle(module, store, PrimaryMap::new(), Box::new(()), &[], None),
+    }
+}
+
+/// A memory creator which hands out an already-existing shared memory.
+struct PreallocatedSharedMemory(SharedMemory);
+
+impl RuntimeMemoryCreator for PreallocatedSharedMemory {
+    fn new_memory(
+        &self,
+        _plan: &MemoryPlan,
+        _minimum: usize,
+        _maximum: Option<usize>,
+        _memory_image: Option<&Arc<MemoryImage>>,
+    ) -> Result<Box<dyn RuntimeLinearMemory>> {
+        unreachable!("only shared memories are created from a preallocation")
+    }
+
+    fn new_shared_memory(
+        &self,
+        _plan: &MemoryPlan,
+        _minimum: usize,
+        _maximum: Option<usize>,
+    ) -> Result<SharedMemory> {
+        Ok(self.0.clone())
+    }
 }
 
 struct LinearMemoryProxy {
@@ -57,6 +100,9 @@ impl RuntimeMemoryCreator for MemoryCreatorProxy {
         plan: &MemoryPlan,
         minimum: usize,
         maximum: Option<usize>,
+        // Memories created by the embedder are always initialized by copying
+        // data segments into them.
+        _memory_image: Option<&Arc<MemoryImage>>,
     ) -> Result<Box<dyn RuntimeLinearMemory>> {
         let ty = MemoryType::from_wasmtime_memory(&plan.memory);
         let reserved_size_in_bytes = match plan.style {
@@ -1,5 +1,6 @@
 use crate::module::GlobalModuleRegistry;
-use crate::FrameInfo;
+use crate::store::AsContext;
+use crate::{Exception, FrameInfo};
 use backtrace::Backtrace;
 use std::fmt;
 use std::sync::Arc;
@@ -26,6 +27,9 @@ enum TrapReason {
 
     /// A specific code for a trap triggered while executing WASM.
     InstructionTrap(TrapCode),
+
+    /// An exception thrown by wasm or by the host that wasn't caught.
+    Exception(Exception),
 }
 
 impl fmt::Display for TrapReason {
@@ -35,6 +39,7 @@ impl fmt::Display for TrapReason {
             TrapReason::I32Exit(status) => write!(f, "Exited with i32 exit status {}", status),
             TrapReason::Error(e) => write!(f, "{}", e),
             TrapReason::InstructionTrap(code) => write!(f, "wasm trap: {}", code),
+            TrapReason::Exception(_) => write!(f, "uncaught wasm exception"),
         }
     }
 }
@@ -128,9 +133,8 @@ impl fmt::Display for TrapCode {
 
 struct TrapInner {
     reason: TrapReason,
-    wasm_trace: Vec<FrameInfo>,
+    wasm_trace: WasmBacktrace,
     native_trace: Backtrace,
-    hint_wasm_backtrace_details_env: bool,
 }
 
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
@@ -161,6 +165,23 @@ impl Trap {
         )
     }
 
+    /// Creates a new `Trap` which throws `exception`.
+    ///
+    /// When returned from a host function called by WebAssembly in an engine
+    /// with [exceptions enabled](crate::Config::wasm_exceptions), the exception
+    /// is thrown to the calling WebAssembly instead of trapping, so that it
+    /// may be caught by a `catch` clause there. Otherwise this behaves like
+    /// any other trap, and the exception is available from
+    /// [`Trap::exception`].
+    #[cold] // see Trap::new
+    pub fn throw(exception: Exception) -> Self {
+        Trap::new_with_trace(
+            None,
+            TrapReason::Exception(exception),
+            Backtrace::new_unresolved(),
+        )
+    }
+
     #[cold] // see Trap::new
     pub(crate) fn from_runtime(runtime_trap: wasmtime_runtime::Trap) -> Self {
         match runtime_trap {
@@ -188,9 +209,17 @@ impl Trap {
                 let reason = TrapReason::Message("out of memory".to_string());
                 Trap::new_with_trace(None, reason, backtrace)
             }
+            // The exception itself is in the store, so these are converted by
+            // `invoke_wasm_and_catch_traps` instead.
+            wasmtime_runtime::Trap::Exception { .. } => unreachable!(),
         }
     }
 
+    #[cold] // see Trap::new
+    pub(crate) fn new_uncaught_exception(exception: Exception, backtrace: Backtrace) -> Self {
+        Trap::new_with_trace(None, TrapReason::Exception(exception), backtrace)
+    }
+
     #[cold] // see Trap::new
     pub(crate) fn new_wasm(
         trap_pc: Option<usize>,
@@ -214,49 +243,14 @@ impl Trap {
     ///   occurred, and this will iterate over the frames to find frames that
     ///   lie in wasm jit code.
     fn new_with_trace(trap_pc: Option<usize>, reason: TrapReason, native_trace: Backtrace) -> Self {
-        let mut wasm_trace = Vec::new();
-        let mut hint_wasm_backtrace_details_env = false;
-
-        GlobalModuleRegistry::with(|registry| {
-            for frame in native_trace.frames() {
-                let pc = frame.ip() as usize;
-                if pc == 0 {
-                    continue;
-                }
-                // Note that we need to be careful about the pc we pass in
-                // here to lookup frame information. This program counter is
-                // used to translate back to an original source location in
-                // the origin wasm module. If this pc is the exact pc that
-                // the trap happened at, then we look up that pc precisely.
-                // Otherwise backtrace information typically points at the
-                // pc *after* the call instruction (because otherwise it's
-                // likely a call instruction on the stack). In that case we
-                // want to lookup information for the previous instruction
-                // (the call instruction) so we subtract one as the lookup.
-                let pc_to_lookup = if Some(pc) == trap_pc { pc } else { pc - 1 };
-                if let Some((info, has_unparsed_debuginfo, wasm_backtrace_details_env_used)) =
-                    registry.lookup_frame_info(pc_to_lookup)
-                {
-                    wasm_trace.push(info);
-
-                    // If this frame has unparsed debug information and the
-                    // store's configuration indicates that we were
-                    // respecting the environment variable of whether to
-                    // do this then we will print out a helpful note in
-                    // `Display` to indicate that more detailed information
-                    // in a trap may be available.
-                    if has_unparsed_debuginfo && wasm_backtrace_details_env_used {
-                        hint_wasm_backtrace_details_env = true;
-                    }
-                }
-            }
+        let wasm_trace = GlobalModuleRegistry::with(|registry| {
+            WasmBacktrace::from_native(&native_trace, trap_pc, |pc| registry.lookup_frame_info(pc))
         });
         Trap {
             inner: Arc::new(TrapInner {
                 reason,
                 wasm_trace,
                 native_trace,
-                hint_wasm_backtrace_details_env,
             }),
         }
     }
@@ -270,6 +264,15 @@ impl Trap {
         }
     }
 
+    /// If the trap was the result of an exception that wasn't caught, return
+    /// the exception, otherwise return `None`.
+    pub fn exception(&self) -> Option<&Exception> {
+        match &self.inner.reason {
+            TrapReason::Exception(exception) => Some(exception),
+            _ => None,
+        }
+    }
+
     /// Displays the error reason for this trap.
     ///
     /// In particular, it differs from this struct's `Display` by *only*
@@ -283,6 +286,15 @@ impl Trap {
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
     pub fn trace(&self) -> &[FrameInfo] {
+        self.inner.wasm_trace.frames()
+    }
+
+    /// Returns the backtrace of WebAssembly frames that led to this trap.
+    ///
+    /// This contains the same frames as [`Trap::trace`], and its `Display`
+    /// implementation is what's used to render the backtrace when displaying
+    /// this trap.
+    pub fn backtrace(&self) -> &WasmBacktrace {
         &self.inner.wasm_trace
     }
 
@@ -300,7 +312,7 @@ impl fmt::Debug for Trap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Trap")
             .field("reason", &self.inner.reason)
-            .field("wasm_trace", &self.inner.wasm_trace)
+            .field("wasm_trace", &self.trace())
             .field("native_trace", &self.inner.native_trace)
             .finish()
     }
@@ -309,25 +321,188 @@ impl fmt::Debug for Trap {
 impl fmt::Display for Trap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.inner.reason)?;
-        let trace = self.trace();
-        if trace.is_empty() {
+        if self.trace().is_empty() {
             return Ok(());
         }
-        writeln!(f, "\nwasm backtrace:")?;
-        for (i, frame) in self.trace().iter().enumerate() {
+        write!(f, "\n{}", self.inner.wasm_trace)
+    }
+}
+
+impl std::error::Error for Trap {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.inner.reason {
+            TrapReason::Error(e) => e.source(),
+            TrapReason::I32Exit(_)
+            | TrapReason::Message(_)
+            | TrapReason::InstructionTrap(_)
+            | TrapReason::Exception(_) => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Trap {
+    fn from(e: anyhow::Error) -> Trap {
+        Box::<dyn std::error::Error + Send + Sync>::from(e).into()
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Trap {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Trap {
+        // If the top-level error is already a trap, don't be redundant and just return it.
+        if let Some(trap) = e.downcast_ref::<Trap>() {
+            trap.clone()
+        } else {
+            let reason = TrapReason::Error(e.into());
+            Trap::new_with_trace(None, reason, Backtrace::new_unresolved())
+        }
+    }
+}
+
+/// A captured backtrace of the WebAssembly frames on the stack.
+///
+/// A `WasmBacktrace` can be captured at any point while WebAssembly is
+/// executing, most commonly from within a host function through
+/// [`Caller::backtrace`](crate::Caller::backtrace), without a trap having
+/// happened. Each frame is described by a [`FrameInfo`], which provides the
+/// function index, module name, wasm offset, and any symbols found in the
+/// module's DWARF debug information.
+///
+/// Frames are listed from the most recently called function to the oldest.
+/// The same type is also used to describe the frames that led to a [`Trap`],
+/// see [`Trap::backtrace`].
+///
+/// Capturing a backtrace walks the native stack, so it is relatively expensive
+/// and is intended for diagnostics such as profiling and logging rather than
+/// for use on every call.
+#[derive(Debug)]
+pub struct WasmBacktrace {
+    wasm_trace: Vec<FrameInfo>,
+    hint_wasm_backtrace_details_env: bool,
+}
+
+impl WasmBacktrace {
+    /// Captures a backtrace of the WebAssembly frames currently on the stack
+    /// which belong to modules instantiated within `store`.
+    ///
+    /// If no WebAssembly is currently executing within `store`, for example
+    /// if this is called outside of any host function, then the returned
+    /// backtrace has no frames.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"
+    ///         (module
+    ///             (import "" "log" (func $log))
+    ///             (func (export "run") call $log)
+    ///         )
+    ///     "#,
+    /// )?;
+    /// let mut store = Store::new(&engine, ());
+    /// let log = Func::wrap(&mut store, |caller: Caller<'_, ()>| {
+    ///     let backtrace = WasmBacktrace::capture(&caller);
+    ///     assert_eq!(backtrace.frames().len(), 1);
+    ///     assert_eq!(backtrace.frames()[0].func_index(), 1);
+    /// });
+    /// let instance = Instance::new(&mut store, &module, &[log.into()])?;
+    /// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    /// run.call(&mut store, ())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture(store: impl AsContext) -> WasmBacktrace {
+        let store = store.as_context();
+        let wasm_backtrace_details_env_used =
+            store.engine().config().wasm_backtrace_details_env_used;
+        let modules = store.0.modules();
+        WasmBacktrace::from_native(&Backtrace::new_unresolved(), None, |pc| {
+            let (info, has_unparsed_debuginfo) = modules.lookup_frame_info(pc)?;
+            Some((
+                info,
+                has_unparsed_debuginfo,
+                wasm_backtrace_details_env_used,
+            ))
+        })
+    }
+
+    /// Translates the frames of `native_trace` which lie in wasm jit code into
+    /// a `WasmBacktrace`.
+    ///
+    /// * `trap_pc` - this is the precise program counter, if available, that
+    ///   wasm trapped at. This is used to ensure we assign the correct source
+    ///   to every frame.
+    ///
+    /// * `lookup_frame_info` - this looks up the frame information for a
+    ///   program counter, along with whether the owning module has unparsed
+    ///   debug information and whether the engine respects the
+    ///   `WASMTIME_BACKTRACE_DETAILS` environment variable.
+    fn from_native(
+        native_trace: &Backtrace,
+        trap_pc: Option<usize>,
+        lookup_frame_info: impl Fn(usize) -> Option<(FrameInfo, bool, bool)>,
+    ) -> WasmBacktrace {
+        let mut wasm_trace = Vec::new();
+        let mut hint_wasm_backtrace_details_env = false;
+
+        for frame in native_trace.frames() {
+            let pc = frame.ip() as usize;
+            if pc == 0 {
+                continue;
+            }
+            // Note that we need to be careful about the pc we pass in here to
+            // lookup frame information. This program counter is used to
+            // translate back to an original source location in the origin
+            // wasm module. If this pc is the exact pc that the trap happened
+            // at, then we look up that pc precisely. Otherwise backtrace
+            // information typically points at the pc *after* the call
+            // instruction (because otherwise it's likely a call instruction on
+            // the stack). In that case we want to lookup information for the
+            // previous instruction (the call instruction) so we subtract one as
+            // the lookup.
+            let pc_to_lookup = if Some(pc) == trap_pc { pc } else { pc - 1 };
+            if let Some((info, has_unparsed_debuginfo, wasm_backtrace_details_env_used)) =
+                lookup_frame_info(pc_to_lookup)
+            {
+                wasm_trace.push(info);
+
+                // If this frame has unparsed debug information and the
+                // store's configuration indicates that we were respecting the
+                // environment variable of whether to do this then we will
+                // print out a helpful note in `Display` to indicate that more
+                // detailed information may be available.
+                if has_unparsed_debuginfo && wasm_backtrace_details_env_used {
+                    hint_wasm_backtrace_details_env = true;
+                }
+            }
+        }
+
+        WasmBacktrace {
+            wasm_trace,
+            hint_wasm_backtrace_details_env,
+        }
+    }
+
+    /// Returns the list of WebAssembly frames in this backtrace, starting
+    /// with the most recently called function.
+    pub fn frames(&self) -> &[FrameInfo] {
+        &self.wasm_trace
+    }
+}
+
+impl fmt::Display for WasmBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "wasm backtrace:")?;
+        for (i, frame) in self.frames().iter().enumerate() {
             let name = frame.module_name().unwrap_or("<unknown>");
             write!(f, "  {:>3}: {:#6x} - ", i, frame.module_offset())?;
 
-            let demangle =
-                |f: &mut fmt::Formatter<'_>, name: &str| match rustc_demangle::try_demangle(name) {
-                    Ok(name) => write!(f, "{}", name),
-                    Err(_) => match cpp_demangle::Symbol::new(name) {
-                        Ok(name) => write!(f, "{}", name),
-                        Err(_) => write!(f, "{}", name),
-                    },
-                };
             let write_raw_func_name = |f: &mut fmt::Formatter<'_>| match frame.func_name() {
-                Some(name) => demangle(f, name),
+                Some(name) => demangle_function_name(f, name),
                 None => write!(f, "<wasm function {}>", frame.func_index()),
             };
             if frame.symbols().is_empty() {
@@ -342,7 +517,7 @@ impl fmt::Display for Trap {
                         // ...
                     }
                     match symbol.name() {
-                        Some(name) => demangle(f, name)?,
+                        Some(name) => demangle_function_name(f, name)?,
                         None if i == 0 => write_raw_func_name(f)?,
                         None => write!(f, "<inlined function>")?,
                     }
@@ -360,38 +535,20 @@ impl fmt::Display for Trap {
                 }
             }
         }
-        if self.inner.hint_wasm_backtrace_details_env {
+        if self.hint_wasm_backtrace_details_env {
             writeln!(f, "note: using the `WASMTIME_BACKTRACE_DETAILS=1` environment variable to may show more debugging information")?;
         }
         Ok(())
     }
 }
 
-impl std::error::Error for Trap {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match &self.inner.reason {
-            TrapReason::Error(e) => e.source(),
-            TrapReason::I32Exit(_) | TrapReason::Message(_) | TrapReason::InstructionTrap(_) => {
-                None
-            }
-        }
-    }
-}
-
-impl From<anyhow::Error> for Trap {
-    fn from(e: anyhow::Error) -> Trap {
-        Box::<dyn std::error::Error + Send + Sync>::from(e).into()
-    }
-}
-
-impl From<Box<dyn std::error::Error + Send + Sync>> for Trap {
-    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Trap {
-        // If the top-level error is already a trap, don't be redundant and just return it.
-        if let Some(trap) = e.downcast_ref::<Trap>() {
-            trap.clone()
-        } else {
-            let reason = TrapReason::Error(e.into());
-            Trap::new_with_trace(None, reason, Backtrace::new_unresolved())
-        }
+/// Writes `name` to `f`, demangled as a Rust or C++ symbol if possible.
+pub(crate) fn demangle_function_name(f: &mut impl fmt::Write, name: &str) -> fmt::Result {
+    match rustc_demangle::try_demangle(name) {
+        Ok(name) => write!(f, "{}", name),
+        Err(_) => match cpp_demangle::Symbol::new(name) {
+            Ok(name) => write!(f, "{}", name),
+            Err(_) => write!(f, "{}", name),
+        },
     }
 }
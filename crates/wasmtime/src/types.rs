@@ -117,6 +117,8 @@ pub enum ExternType {
     Instance(InstanceType),
     /// This external type is the type of a WebAssembly module.
     Module(ModuleType),
+    /// This external type is the type of a WebAssembly exception tag.
+    Tag(TagType),
 }
 
 macro_rules! accessors {
@@ -151,6 +153,7 @@ impl ExternType {
         (Memory(MemoryType) memory unwrap_memory)
         (Module(ModuleType) module unwrap_module)
         (Instance(InstanceType) instance unwrap_instance)
+        (Tag(TagType) tag unwrap_tag)
     }
 
     pub(crate) fn from_wasmtime(types: &TypeTables, ty: &EntityType) -> ExternType {
@@ -169,7 +172,9 @@ impl ExternType {
                 let ty = &types.instance_signatures[*ty];
                 InstanceType::from_wasmtime(types, ty).into()
             }
-            EntityType::Tag(_) => unimplemented!("wasm tag support"),
+            EntityType::Tag(tag) => {
+                TagType::from_wasm_func_type(types.wasm_signatures[tag.ty].clone()).into()
+            }
         }
     }
 }
@@ -210,6 +215,12 @@ impl From<InstanceType> for ExternType {
     }
 }
 
+impl From<TagType> for ExternType {
+    fn from(ty: TagType) -> ExternType {
+        ExternType::Tag(ty)
+    }
+}
+
 /// A descriptor for a function in a WebAssembly module.
 ///
 /// WebAssembly functions can have 0 or more parameters and results.
@@ -399,6 +410,26 @@ impl MemoryType {
         }
     }
 
+    /// Creates a new descriptor for a 32-bit shared WebAssembly memory given
+    /// the specified limits of the memory.
+    ///
+    /// The `minimum` and `maximum` values here are specified in units of
+    /// WebAssembly pages, which are 64k. Shared memories must always have a
+    /// maximum size.
+    ///
+    /// Note that shared memories are part of the threads proposal for
+    /// WebAssembly which is not standardized yet.
+    pub fn shared(minimum: u32, maximum: u32) -> MemoryType {
+        MemoryType {
+            ty: Memory {
+                memory64: false,
+                shared: true,
+                minimum: minimum.into(),
+                maximum: Some(maximum.into()),
+            },
+        }
+    }
+
     /// Returns whether this is a 64-bit memory or not.
     ///
     /// Note that 64-bit memories are part of the memory64 proposal for
@@ -407,6 +438,14 @@ impl MemoryType {
         self.ty.memory64
     }
 
+    /// Returns whether this is a shared memory or not.
+    ///
+    /// Note that shared memories are part of the threads proposal for
+    /// WebAssembly which is not standardized yet.
+    pub fn is_shared(&self) -> bool {
+        self.ty.shared
+    }
+
     /// Returns minimum number of WebAssembly pages this memory must have.
     ///
     /// Note that the return value, while a `u64`, will always fit into a `u32`
@@ -435,6 +474,47 @@ impl MemoryType {
     }
 }
 
+// Tag Types
+
+/// A descriptor for a WebAssembly exception tag.
+///
+/// A tag is described by a function type without results, whose parameters
+/// are the values carried by the exceptions it tags.
+///
+/// This is a part of the [WebAssembly exception handling proposal][proposal].
+///
+/// [proposal]: https://github.com/webassembly/exception-handling
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct TagType {
+    ty: FuncType,
+}
+
+impl TagType {
+    /// Creates a new tag descriptor for exceptions carrying values of the
+    /// given types.
+    pub fn new(params: impl IntoIterator<Item = ValType>) -> TagType {
+        TagType {
+            ty: FuncType::new(params, None),
+        }
+    }
+
+    /// Returns the types of the values carried by exceptions with this tag.
+    pub fn params(&self) -> impl ExactSizeIterator<Item = ValType> + '_ {
+        self.ty.params()
+    }
+
+    /// Returns the function type describing this tag.
+    pub fn func_type(&self) -> &FuncType {
+        &self.ty
+    }
+
+    pub(crate) fn from_wasm_func_type(sig: WasmFuncType) -> TagType {
+        TagType {
+            ty: FuncType::from_wasm_func_type(sig),
+        }
+    }
+}
+
 // Module Types
 
 /// A descriptor for a WebAssembly module type.
@@ -76,6 +76,15 @@ impl MatchCx<'_> {
         self.vmshared_signature_index(expected, actual.sig_index(self.store.store_data()))
     }
 
+    pub fn tag(&self, expected: SignatureIndex, actual: &crate::Tag) -> Result<()> {
+        let actual = actual.sig_index(self.store.store_data());
+        if self.signatures.shared_signature(expected) == Some(actual) {
+            Ok(())
+        } else {
+            bail!("tag types incompatible")
+        }
+    }
+
     pub(crate) fn host_func(
         &self,
         expected: SignatureIndex,
@@ -298,7 +307,18 @@ impl MatchCx<'_> {
                 }
                 _ => bail!("expected module, but found {}", actual_desc),
             },
-            EntityType::Tag(_) => unimplemented!(),
+            EntityType::Tag(expected) => match actual_ty {
+                EntityType::Tag(actual) => {
+                    if self.types.wasm_signatures[expected.ty]
+                        == actual_types.wasm_signatures[actual.ty]
+                    {
+                        Ok(())
+                    } else {
+                        bail!("tag types incompatible")
+                    }
+                }
+                _ => bail!("expected tag, but found {}", actual_desc),
+            },
         }
     }
 
@@ -315,6 +335,9 @@ impl MatchCx<'_> {
             },
             EntityType::Memory(expected) => match actual {
                 Extern::Memory(actual) => self.memory(expected, actual),
+                Extern::SharedMemory(actual) => {
+                    self.memory_ty(expected, actual.ty().wasmtime_memory())
+                }
                 _ => bail!("expected memory, but found {}", actual.desc()),
             },
             EntityType::Function(expected) => match actual {
@@ -329,7 +352,10 @@ impl MatchCx<'_> {
                 Extern::Module(actual) => self.module(*expected, actual),
                 _ => bail!("expected module, but found {}", actual.desc()),
             },
-            EntityType::Tag(_) => unimplemented!(),
+            EntityType::Tag(expected) => match actual {
+                Extern::Tag(actual) => self.tag(expected.ty, actual),
+                _ => bail!("expected tag, but found {}", actual.desc()),
+            },
         }
     }
 
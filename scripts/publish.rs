@@ -53,12 +53,15 @@ const CRATES_TO_PUBLISH: &[&str] = &[
     "wasmtime-lightbeam",
     "wasmtime-jit",
     "wasmtime-cache",
+    "wasmtime-component-macro",
     "wasmtime",
     // wasi-common/wiggle
     "wiggle",
     "wasi-common",
     "wasi-cap-std-sync",
     "wasi-tokio",
+    "wasi-memfs",
+    "wasi-overlay",
     // other mic wasmtime crates
     "wasmtime-wasi",
     "wasmtime-wasi-nn",
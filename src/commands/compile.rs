@@ -271,4 +271,117 @@ mod test {
 
         Ok(())
     }
+
+    #[cfg(feature = "all-arch")]
+    #[test]
+    fn test_riscv64_cross_compile() -> Result<()> {
+        let (mut input, input_path) = NamedTempFile::new()?.into_parts();
+        input.write_all(
+            r#"
+            (module
+              (type $t (func (param i64) (result i64)))
+              (memory 1)
+              (table 1 funcref)
+              (elem (i32.const 0) $double)
+              (global $g (mut f64) (f64.const 1.5))
+              (func $double (type $t) local.get 0 i64.const 1 i64.shl)
+              (func (export "f") (param i32 i64) (result i64)
+                (i32.store8 (local.get 0) (i32.const 7))
+                (global.set $g (f64.mul (global.get $g) (f64.convert_i32_u (local.get 0))))
+                (i64.add
+                  (call $double (i64.load (local.get 0)))
+                  (call_indirect (type $t) (local.get 1) (i32.const 0))))
+              (func (export "g") (param i32) (result i32)
+                (i32.div_s (local.get 0) (memory.grow (i32.const 1)))))
+            "#
+            .as_bytes(),
+        )?;
+        drop(input);
+
+        let output_path = NamedTempFile::new()?.into_temp_path();
+
+        let command = CompileCommand::from_iter_safe(vec![
+            "compile",
+            "--disable-logging",
+            "--target",
+            "riscv64gc-unknown-linux-gnu",
+            "--cranelift-enable",
+            "has_zbb",
+            "-o",
+            output_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        // The artifact embeds a RISC-V ELF object (`e_machine` is `EM_RISCV`).
+        let contents = std::fs::read(output_path)?;
+        let elf = contents
+            .windows(4)
+            .position(|w| w == b"\x7fELF")
+            .expect("no ELF object in the compiled artifact");
+        assert_eq!(&contents[elf + 18..elf + 20], &243u16.to_le_bytes());
+
+        // ... which can't be loaded on this host.
+        let engine = Engine::default();
+        assert!(unsafe { Module::deserialize(&engine, contents) }.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "all-arch")]
+    #[test]
+    fn test_arm32_cross_compile() -> Result<()> {
+        let (mut input, input_path) = NamedTempFile::new()?.into_parts();
+        input.write_all(
+            r#"
+            (module
+              (type $t (func (param i64) (result i64)))
+              (memory 1)
+              (table 1 funcref)
+              (elem (i32.const 0) $double)
+              (global $g (mut f64) (f64.const 1.5))
+              (func $double (type $t) local.get 0 i64.const 1 i64.shl)
+              (func (export "f") (param i32 i64) (result i64)
+                (f32.store (local.get 0) (f32.sqrt (f32.load offset=4 (local.get 0))))
+                (global.set $g (f64.mul (global.get $g) (f64.convert_i64_s (local.get 1))))
+                (i64.add
+                  (call $double (i64.div_u (i64.load (local.get 0)) (local.get 1)))
+                  (call_indirect (type $t) (local.get 1) (i32.const 0))))
+              (func (export "g") (param i32) (result i32)
+                (i32.div_s (local.get 0) (memory.grow (i32.const 1)))))
+            "#
+            .as_bytes(),
+        )?;
+        drop(input);
+
+        let output_path = NamedTempFile::new()?.into_temp_path();
+
+        let command = CompileCommand::from_iter_safe(vec![
+            "compile",
+            "--disable-logging",
+            "--target",
+            "armv7-unknown-linux-gnueabihf",
+            "-o",
+            output_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        // The artifact embeds a 32-bit ARM ELF object (`ELFCLASS32`, `EM_ARM`).
+        let contents = std::fs::read(output_path)?;
+        let elf = contents
+            .windows(4)
+            .position(|w| w == b"\x7fELF")
+            .expect("no ELF object in the compiled artifact");
+        assert_eq!(contents[elf + 4], 1);
+        assert_eq!(&contents[elf + 18..elf + 20], &40u16.to_le_bytes());
+
+        // ... which can't be loaded on this host.
+        let engine = Engine::default();
+        assert!(unsafe { Module::deserialize(&engine, contents) }.is_err());
+
+        Ok(())
+    }
 }
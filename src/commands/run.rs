@@ -1,17 +1,26 @@
 //! The module that implements the `wasmtime run` command.
 
+use crate::debug_server::DebugServer;
 use crate::{CommonOptions, WasiModules};
 use anyhow::{anyhow, bail, Context as _, Result};
+use cap_std::net::TcpListener;
 use std::thread;
 use std::time::Duration;
 use std::{
     ffi::{OsStr, OsString},
-    path::{Component, PathBuf},
+    fs::File,
+    path::{Path, PathBuf},
     process,
 };
 use structopt::{clap::AppSettings, StructOpt};
-use wasmtime::{Engine, Func, Linker, Module, Store, Trap, Val, ValType};
-use wasmtime_wasi::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use wasmtime::{
+    DebugAction, Engine, Func, GuestProfileFormat, GuestProfiler, Linker, Module, Store,
+    StoreLimits, StoreLimitsBuilder, Trap, Val, ValType, WasmBacktraceDetails,
+};
+use wasmtime_wasi::policy::JsonAuditLog;
+use wasmtime_wasi::replay::{Recorder, Replayer};
+use wasmtime_wasi::sync::manifest::{MappedDir, Preload};
+use wasmtime_wasi::sync::{Manifest, WasiCtxBuilder};
 
 #[cfg(feature = "wasi-nn")]
 use wasmtime_wasi_nn::WasiNnCtx;
@@ -62,6 +71,32 @@ fn parse_preloads(s: &str) -> Result<(String, PathBuf)> {
     Ok((parts[0].into(), parts[1].into()))
 }
 
+/// A profiler requested with `--profile`.
+enum Profile {
+    /// Sample the guest's stack every `interval`, writing the profile to
+    /// `path` when execution finishes.
+    Guest { path: PathBuf, interval: Duration },
+}
+
+fn parse_profile(s: &str) -> Result<Profile> {
+    let parts: Vec<&str> = s.split(',').collect();
+    match parts.as_slice() {
+        ["guest"] => Ok(Profile::Guest {
+            path: "wasmtime-guest-profile.json".into(),
+            interval: Duration::from_millis(10),
+        }),
+        ["guest", path] => Ok(Profile::Guest {
+            path: path.into(),
+            interval: Duration::from_millis(10),
+        }),
+        ["guest", path, interval] => Ok(Profile::Guest {
+            path: path.into(),
+            interval: parse_dur(interval)?,
+        }),
+        _ => bail!("must be of the form `guest[,PATH[,INTERVAL]]`"),
+    }
+}
+
 lazy_static::lazy_static! {
     static ref AFTER_HELP: String = {
         crate::FLAG_EXPLANATIONS.to_string()
@@ -95,14 +130,31 @@ pub struct RunCommand {
     #[structopt(long = "mapdir", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR", parse(try_from_str = parse_map_dirs))]
     map_dirs: Vec<(String, String)>,
 
+    /// Grant access to a TCP listener bound to the given address. Listeners
+    /// are passed to the module as file descriptors following any
+    /// preopened directories, in the order they're given. With port 0 the
+    /// system picks a free port, and the bound address is printed to stderr.
+    #[structopt(long = "tcplisten", number_of_values = 1, value_name = "HOST:PORT")]
+    tcplisten: Vec<String>,
+
     /// The path of the WebAssembly module to run
     #[structopt(
         index = 1,
-        required = true,
+        required_unless = "manifest",
         value_name = "MODULE",
         parse(try_from_os_str = parse_module),
     )]
-    module: PathBuf,
+    module: Option<PathBuf>,
+
+    /// Run the module as declared by the TOML manifest at PATH: its
+    /// arguments, environment, directories, preloads, limits, fuel, timeout
+    /// and WASI modules.
+    ///
+    /// Options given on the command line are added to the manifest's, or
+    /// replace them where there can only be one. A MODULE given on the
+    /// command line replaces the manifest's.
+    #[structopt(long = "manifest", value_name = "PATH")]
+    manifest: Option<PathBuf>,
 
     /// Load the given WebAssembly module before the main module
     #[structopt(
@@ -121,6 +173,52 @@ pub struct RunCommand {
     )]
     wasm_timeout: Option<Duration>,
 
+    /// Profile the guest module while it runs, e.g. `--profile=guest,out.json`.
+    ///
+    /// The guest's stack is sampled every INTERVAL (10ms by default) and the
+    /// profile is written to PATH once execution finishes. Paths ending in
+    /// `.pb` or `.pprof` are written in pprof's protobuf format, and all
+    /// others in a JSON format that can be loaded into the Firefox Profiler.
+    /// The module's DWARF info is parsed to name its frames, so functions
+    /// missing from the name section still show up with source locations.
+    #[structopt(
+        long = "profile",
+        value_name = "guest[,PATH[,INTERVAL]]",
+        parse(try_from_str = parse_profile),
+    )]
+    profile: Option<Profile>,
+
+    /// Wait for a debugger to connect to ADDR, e.g. `127.0.0.1:1234`, before
+    /// running the main module.
+    ///
+    /// The server speaks the GDB remote serial protocol with the WebAssembly
+    /// extensions supported by LLDB, which can attach with
+    /// `process connect --plugin wasm connect://ADDR`. Execution stops before
+    /// the main module's first instruction.
+    #[structopt(long = "debug-server", value_name = "ADDR")]
+    debug_server: Option<String>,
+
+    /// Record everything nondeterministic the module observes through WASI,
+    /// such as its arguments, clocks, random numbers and the data it reads,
+    /// to a trace at PATH.
+    #[structopt(long = "record", value_name = "PATH", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay a trace written by `--record` to the module, so that it sees
+    /// the same arguments, clocks, random numbers and data as it did then.
+    ///
+    /// Writes, and the metadata of files and directories, aren't part of the
+    /// trace, so the same directories should be mapped as when it was
+    /// recorded.
+    #[structopt(long = "replay", value_name = "PATH")]
+    replay: Option<PathBuf>,
+
+    /// Write every WASI call the module makes to an audit log at PATH, as a
+    /// line of JSON with the call's name, descriptor, path and the rights it
+    /// requires.
+    #[structopt(long = "wasi-audit-log", value_name = "PATH")]
+    wasi_audit_log: Option<PathBuf>,
+
     // NOTE: this must come last for trailing varargs
     /// The arguments to pass to the module
     #[structopt(value_name = "ARGS")]
@@ -132,16 +230,47 @@ impl RunCommand {
     pub fn execute(&self) -> Result<()> {
         self.common.init_logging();
 
+        let manifest = self.manifest()?;
+        let module = manifest
+            .module
+            .as_deref()
+            .ok_or_else(|| anyhow!("no module to run; the manifest doesn't name one"))?;
+        let wasi_modules = match (self.common.wasi_modules, &manifest.wasi_modules) {
+            (Some(modules), _) => modules,
+            (None, Some(modules)) => crate::parse_wasi_modules(modules)
+                .context("invalid `wasi-modules` in the manifest")?,
+            (None, None) => WasiModules::default(),
+        };
+
         let mut config = self.common.config(None)?;
-        if self.wasm_timeout.is_some() {
+        if manifest.timeout.is_some() {
             config.interruptable(true);
         }
+        if manifest.fuel.is_some() {
+            config.consume_fuel(true);
+        }
+        if self.profile.is_some() {
+            config.epoch_interruption(true);
+            config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+        }
+        if self.debug_server.is_some() {
+            config.guest_debug(true);
+        }
         let engine = Engine::new(&config)?;
         let mut store = Store::new(&engine, Host::default());
+        if let Some(fuel) = manifest.fuel {
+            store.add_fuel(fuel)?;
+        }
+        if !manifest.limits.is_empty() {
+            store.data_mut().limits = store_limits(&manifest);
+            store.limiter(|host| &mut host.limits);
+        }
+        self.setup_profiling(&mut store, module);
 
         // Make wasi available by default.
-        let preopen_dirs = self.compute_preopen_dirs()?;
-        let argv = self.compute_argv();
+        let preopen_sockets = self.compute_preopen_sockets()?;
+        let trace = self.open_trace()?;
+        let audit_log = self.open_audit_log()?;
 
         let mut linker = Linker::new(&engine);
         linker.allow_unknown_exports(self.allow_unknown_exports);
@@ -149,14 +278,15 @@ impl RunCommand {
         populate_with_wasi(
             &mut store,
             &mut linker,
-            preopen_dirs,
-            &argv,
-            &self.vars,
-            &self.common.wasi_modules.unwrap_or(WasiModules::default()),
+            &manifest,
+            preopen_sockets,
+            trace.as_ref(),
+            audit_log,
+            &wasi_modules,
         )?;
 
         // Load the preload wasm modules.
-        for (name, path) in self.preloads.iter() {
+        for Preload { name, path } in manifest.preloads.iter() {
             // Read the wasm module binary either as `*.wat` or a raw binary
             let module = Module::from_file(&engine, path)?;
 
@@ -169,10 +299,28 @@ impl RunCommand {
         }
 
         // Load the main wasm module.
-        match self
-            .load_main_module(&mut store, &mut linker)
-            .with_context(|| format!("failed to run main module `{}`", self.module.display()))
-        {
+        let result = self
+            .load_main_module(&mut store, &mut linker, &manifest)
+            .with_context(|| format!("failed to run main module `{}`", module.display()));
+
+        // Write out the profile before possibly exiting below, since the
+        // profile of a trapping guest is just as interesting.
+        if let Err(e) = self.finish_profiling(&mut store) {
+            eprintln!("warning: failed to write guest profile: {:?}", e);
+        }
+        if let Some(Trace::Record(recorder)) = &trace {
+            if let Err(e) = recorder.flush() {
+                eprintln!("warning: failed to write WASI trace: {:?}", e);
+            }
+        }
+        if let Some(server) = store.data_mut().debug_server.take() {
+            server.exit(match &result {
+                Ok(()) => Some(0),
+                Err(e) => e.downcast_ref::<Trap>().and_then(Trap::i32_exit_status),
+            });
+        }
+
+        match result {
             Ok(()) => (),
             Err(e) => {
                 // If the program exited because of a non-zero exit status, print
@@ -209,53 +357,161 @@ impl RunCommand {
         Ok(())
     }
 
-    fn compute_preopen_dirs(&self) -> Result<Vec<(String, Dir)>> {
-        let mut preopen_dirs = Vec::new();
-
+    /// Merges the options given on the command line into the manifest, or
+    /// into an empty one if there's no `--manifest`.
+    fn manifest(&self) -> Result<Manifest> {
+        let mut manifest = match &self.manifest {
+            Some(path) => Manifest::from_file(path)?,
+            None => Manifest::default(),
+        };
+        if let Some(module) = &self.module {
+            manifest.module = Some(module.clone());
+        }
+        manifest.args.extend(self.module_args.iter().cloned());
+        manifest.env.extend(self.vars.iter().cloned());
         for dir in self.dirs.iter() {
-            preopen_dirs.push((
-                dir.clone(),
-                Dir::open_ambient_dir(dir, ambient_authority())
-                    .with_context(|| format!("failed to open directory '{}'", dir))?,
-            ));
+            manifest.dirs.push(MappedDir {
+                guest: Some(dir.clone()),
+                host: dir.into(),
+                access: Default::default(),
+            });
         }
-
         for (guest, host) in self.map_dirs.iter() {
-            preopen_dirs.push((
-                guest.clone(),
-                Dir::open_ambient_dir(host, ambient_authority())
-                    .with_context(|| format!("failed to open directory '{}'", host))?,
-            ));
+            manifest.dirs.push(MappedDir {
+                guest: Some(guest.clone()),
+                host: host.into(),
+                access: Default::default(),
+            });
+        }
+        for (name, path) in self.preloads.iter() {
+            manifest.preloads.push(Preload {
+                name: name.clone(),
+                path: path.clone(),
+            });
         }
+        if let Some(timeout) = self.wasm_timeout {
+            manifest.timeout = Some(timeout);
+        }
+        Ok(manifest)
+    }
 
-        Ok(preopen_dirs)
+    fn setup_profiling(&self, store: &mut Store<Host>, module: &Path) {
+        let interval = match &self.profile {
+            Some(Profile::Guest { interval, .. }) => *interval,
+            None => return,
+        };
+        let name = module.file_name().and_then(OsStr::to_str).unwrap_or("");
+        store.data_mut().guest_profiler = Some(GuestProfiler::new(name, interval));
+
+        // Sample the guest every time the epoch is incremented, which a
+        // background thread does once per interval.
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|mut cx| {
+            if let Some(mut profiler) = cx.data_mut().guest_profiler.take() {
+                profiler.sample(&cx);
+                cx.data_mut().guest_profiler = Some(profiler);
+            }
+            Ok(1)
+        });
+        let engine = store.engine().clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            engine.increment_epoch();
+        });
     }
 
-    fn compute_argv(&self) -> Vec<String> {
-        let mut result = Vec::new();
+    fn finish_profiling(&self, store: &mut Store<Host>) -> Result<()> {
+        let path = match &self.profile {
+            Some(Profile::Guest { path, .. }) => path,
+            None => return Ok(()),
+        };
+        let profiler = match store.data_mut().guest_profiler.take() {
+            Some(profiler) => profiler,
+            None => return Ok(()),
+        };
+        let format = match path.extension().and_then(OsStr::to_str) {
+            Some("pb") | Some("pprof") => GuestProfileFormat::Pprof,
+            _ => GuestProfileFormat::Firefox,
+        };
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        profiler.finish(format, std::io::BufWriter::new(file))
+    }
 
-        // Add argv[0], which is the program name. Only include the base name of the
-        // main wasm module, to avoid leaking path information.
-        result.push(
-            self.module
-                .components()
-                .next_back()
-                .map(Component::as_os_str)
-                .and_then(OsStr::to_str)
-                .unwrap_or("")
-                .to_owned(),
-        );
+    fn setup_debugging(&self, store: &mut Store<Host>, path: &Path, module: &Module) -> Result<()> {
+        let addr = match &self.debug_server {
+            Some(addr) => addr,
+            None => return Ok(()),
+        };
+        let bytes = wat::parse_file(path)?;
+        let name = path.file_name().and_then(OsStr::to_str).unwrap_or("");
+        let server = DebugServer::listen(addr, name, module.clone(), bytes)?;
+        store.data_mut().debug_server = Some(server);
+        store.debug_handler(|mut frame, _event| {
+            let mut server = match frame.data_mut().debug_server.take() {
+                Some(server) => server,
+                None => return Ok(DebugAction::Continue),
+            };
+            let action = server.stop(&mut frame);
+            frame.data_mut().debug_server = Some(server);
+            action
+        });
+
+        // Stop before the first instruction so the debugger can set
+        // breakpoints.
+        store.debug_single_step(true);
+        Ok(())
+    }
 
-        // Add the remaining arguments.
-        for arg in self.module_args.iter() {
-            result.push(arg.clone());
+    fn open_trace(&self) -> Result<Option<Trace>> {
+        if let Some(path) = &self.record {
+            let file = File::create(path)
+                .with_context(|| format!("failed to create trace `{}`", path.display()))?;
+            return Ok(Some(Trace::Record(Recorder::new(file))));
+        }
+        if let Some(path) = &self.replay {
+            let file = File::open(path)
+                .with_context(|| format!("failed to open trace `{}`", path.display()))?;
+            let replayer = Replayer::new(file)
+                .with_context(|| format!("failed to read trace `{}`", path.display()))?;
+            return Ok(Some(Trace::Replay(replayer)));
         }
+        Ok(None)
+    }
 
-        result
+    fn open_audit_log(&self) -> Result<Option<JsonAuditLog>> {
+        let path = match &self.wasi_audit_log {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let file = File::create(path)
+            .with_context(|| format!("failed to create audit log `{}`", path.display()))?;
+        Ok(Some(JsonAuditLog::new(file)))
     }
 
-    fn load_main_module(&self, store: &mut Store<Host>, linker: &mut Linker<Host>) -> Result<()> {
-        if let Some(timeout) = self.wasm_timeout {
+    fn compute_preopen_sockets(&self) -> Result<Vec<TcpListener>> {
+        let mut listeners = Vec::new();
+
+        for address in self.tcplisten.iter() {
+            let listener = std::net::TcpListener::bind(address)
+                .with_context(|| format!("failed to bind to address '{}'", address))?;
+            if address.ends_with(":0") {
+                eprintln!("listening on {}", listener.local_addr()?);
+            }
+            listeners.push(TcpListener::from_std(listener));
+        }
+
+        Ok(listeners)
+    }
+
+    fn load_main_module(
+        &self,
+        store: &mut Store<Host>,
+        linker: &mut Linker<Host>,
+        manifest: &Manifest,
+    ) -> Result<()> {
+        let path = manifest.module.as_deref().unwrap();
+        if let Some(timeout) = manifest.timeout {
             let handle = store.interrupt_handle()?;
             thread::spawn(move || {
                 thread::sleep(timeout);
@@ -265,17 +521,18 @@ impl RunCommand {
 
         // Read the wasm module binary either as `*.wat` or a raw binary.
         // Use "" as a default module name.
-        let module = Module::from_file(linker.engine(), &self.module)?;
+        let module = Module::from_file(linker.engine(), path)?;
+        self.setup_debugging(store, path, &module)?;
         linker
             .module(&mut *store, "", &module)
-            .context(format!("failed to instantiate {:?}", self.module))?;
+            .context(format!("failed to instantiate {:?}", path))?;
 
         // If a function to invoke was given, invoke it.
         if let Some(name) = self.invoke.as_ref() {
-            self.invoke_export(store, linker, name)
+            self.invoke_export(store, linker, name, &manifest.args)
         } else {
             let func = linker.get_default(&mut *store, "")?;
-            self.invoke_func(store, func, None)
+            self.invoke_func(store, func, None, &manifest.args)
         }
     }
 
@@ -284,6 +541,7 @@ impl RunCommand {
         store: &mut Store<Host>,
         linker: &Linker<Host>,
         name: &str,
+        args: &[String],
     ) -> Result<()> {
         let func = match linker
             .get(&mut *store, "", Some(name))
@@ -293,10 +551,16 @@ impl RunCommand {
             Some(func) => func,
             None => bail!("export of `{}` wasn't a function", name),
         };
-        self.invoke_func(store, func, Some(name))
+        self.invoke_func(store, func, Some(name), args)
     }
 
-    fn invoke_func(&self, store: &mut Store<Host>, func: Func, name: Option<&str>) -> Result<()> {
+    fn invoke_func(
+        &self,
+        store: &mut Store<Host>,
+        func: Func,
+        name: Option<&str>,
+        args: &[String],
+    ) -> Result<()> {
         let ty = func.ty(&store);
         if ty.params().len() > 0 {
             eprintln!(
@@ -304,7 +568,7 @@ impl RunCommand {
                  is experimental and may break in the future"
             );
         }
-        let mut args = self.module_args.iter();
+        let mut args = args.iter();
         let mut values = Vec::new();
         for ty in ty.params() {
             let val = match args.next() {
@@ -361,34 +625,78 @@ impl RunCommand {
     }
 }
 
+/// A trace of WASI nondeterminism requested with `--record` or `--replay`.
+enum Trace {
+    Record(Recorder),
+    Replay(Replayer),
+}
+
 #[derive(Default)]
 struct Host {
     wasi: Option<wasmtime_wasi::WasiCtx>,
+    guest_profiler: Option<GuestProfiler>,
+    debug_server: Option<DebugServer>,
+    limits: StoreLimits,
     #[cfg(feature = "wasi-nn")]
     wasi_nn: Option<WasiNnCtx>,
     #[cfg(feature = "wasi-crypto")]
     wasi_crypto: Option<WasiCryptoCtx>,
 }
 
+fn store_limits(manifest: &Manifest) -> StoreLimits {
+    let limits = &manifest.limits;
+    let mut builder = StoreLimitsBuilder::new();
+    if let Some(size) = limits.memory_size {
+        builder = builder.memory_size(size);
+    }
+    if let Some(elements) = limits.table_elements {
+        builder = builder.table_elements(elements);
+    }
+    if let Some(instances) = limits.instances {
+        builder = builder.instances(instances);
+    }
+    if let Some(tables) = limits.tables {
+        builder = builder.tables(tables);
+    }
+    if let Some(memories) = limits.memories {
+        builder = builder.memories(memories);
+    }
+    builder.build()
+}
+
 /// Populates the given `Linker` with WASI APIs.
 fn populate_with_wasi(
     store: &mut Store<Host>,
     linker: &mut Linker<Host>,
-    preopen_dirs: Vec<(String, Dir)>,
-    argv: &[String],
-    vars: &[(String, String)],
+    manifest: &Manifest,
+    preopen_sockets: Vec<TcpListener>,
+    trace: Option<&Trace>,
+    audit_log: Option<JsonAuditLog>,
     wasi_modules: &WasiModules,
 ) -> Result<()> {
     if wasi_modules.wasi_common {
         wasmtime_wasi::add_to_linker(linker, |host| host.wasi.as_mut().unwrap())?;
 
         let mut builder = WasiCtxBuilder::new();
-        builder = builder.inherit_stdio().args(argv)?.envs(vars)?;
-
-        for (name, dir) in preopen_dirs.into_iter() {
-            builder = builder.preopened_dir(dir, name)?;
+        builder = builder.inherit_stdio().manifest(manifest)?;
+
+        // wasi-libc looks for preopened directories from fd 3 until it
+        // finds a descriptor which isn't one, so sockets go after them.
+        let mut fd = 3 + manifest.dirs.len() as u32;
+        for listener in preopen_sockets.into_iter() {
+            builder = builder.preopened_socket(fd, listener);
+            fd += 1;
+        }
+        let mut wasi = builder.build();
+        match trace {
+            Some(Trace::Record(recorder)) => wasi = recorder.record(wasi),
+            Some(Trace::Replay(replayer)) => wasi = replayer.replay(wasi)?,
+            None => {}
+        }
+        if let Some(audit_log) = audit_log {
+            wasi.set_policy(Box::new(audit_log));
         }
-        store.data_mut().wasi = Some(builder.build());
+        store.data_mut().wasi = Some(wasi);
     }
 
     if wasi_modules.wasi_nn {
@@ -0,0 +1,432 @@
+//! A GDB remote serial protocol server for debugging wasm guests, used by
+//! `wasmtime run --debug-server`.
+//!
+//! This speaks the dialect of the protocol understood by LLDB's WebAssembly
+//! support: the program counter is a 64-bit address whose top two bits select
+//! the address space (`1` for code, `0` for linear memory), followed by a
+//! module id and a 32-bit offset. Code addresses are offsets into the wasm
+//! binary of the main module, which is the only module that can be debugged.
+//! Wasm-specific `qWasmCallStack`, `qWasmLocal`, `qWasmGlobal` and
+//! `qWasmStackValue` packets are used to inspect the stopped guest.
+
+use anyhow::{Context as _, Result};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Range;
+use wasmparser::{ImportSectionEntryType, Parser, Payload};
+use wasmtime::{AsContext, AsContextMut, DebugAction, DebugFrame, Extern, Module, Trap, Val};
+
+/// The address space of the wasm binary's bytes.
+const CODE_SPACE: u64 = 1 << 62;
+
+/// The triple reported to debuggers, hex-encoded as they expect.
+const TRIPLE: &str = "wasm32-unknown-unknown-wasm";
+
+/// A connection to a debugger attached to the main module.
+pub struct DebugServer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    module: Module,
+    name: String,
+    bytes: Vec<u8>,
+    /// The index and body range within `bytes` of each defined function.
+    funcs: Vec<(u32, Range<usize>)>,
+    /// Breakpoints set by the debugger, as function indices and offsets.
+    breakpoints: Vec<(u32, u32)>,
+    no_ack: bool,
+    /// Whether the debugger resumed execution and is waiting for it to stop.
+    running: bool,
+    detached: bool,
+}
+
+/// How the debugger asked to resume execution.
+enum Resume {
+    Continue,
+    Step,
+    Kill,
+    Detach,
+}
+
+impl DebugServer {
+    /// Waits for a debugger to connect to `addr` to debug `module`, which was
+    /// compiled from the wasm binary `bytes`.
+    pub fn listen(addr: &str, name: &str, module: Module, bytes: Vec<u8>) -> Result<DebugServer> {
+        let funcs = function_bodies(&bytes)?;
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("failed to listen on `{}`", addr))?;
+        eprintln!(
+            "waiting for a debugger to connect to {}",
+            listener.local_addr()?
+        );
+        let (stream, _) = listener.accept()?;
+        Ok(DebugServer {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            module,
+            name: name.to_string(),
+            bytes,
+            funcs,
+            breakpoints: Vec::new(),
+            no_ack: false,
+            running: false,
+            detached: false,
+        })
+    }
+
+    /// Reports that the guest stopped at `frame` and serves the debugger's
+    /// requests until it resumes execution.
+    pub fn stop<T>(&mut self, frame: &mut DebugFrame<'_, T>) -> Result<DebugAction, Trap> {
+        if self.detached {
+            return Ok(DebugAction::Continue);
+        }
+        let resume = match self.serve(frame) {
+            Ok(resume) => resume,
+            Err(e) => {
+                eprintln!("warning: lost connection to the debugger: {}", e);
+                Resume::Detach
+            }
+        };
+        match resume {
+            Resume::Continue => Ok(DebugAction::Continue),
+            Resume::Step => Ok(DebugAction::Step),
+            Resume::Kill => Err(Trap::new("execution was killed by the debugger")),
+            Resume::Detach => {
+                self.detached = true;
+                for (func_index, offset) in self.breakpoints.drain(..) {
+                    frame
+                        .as_context_mut()
+                        .remove_breakpoint(&self.module, func_index, offset);
+                }
+                Ok(DebugAction::Continue)
+            }
+        }
+    }
+
+    /// Tells the debugger that the guest exited with `status`, or that it
+    /// trapped if `status` is `None`.
+    pub fn exit(mut self, status: Option<i32>) {
+        if self.detached {
+            return;
+        }
+        let reply = match status {
+            Some(status) => format!("W{:02x}", status as u8),
+            None => format!("X{:02x}", libc::SIGABRT),
+        };
+        if let Err(e) = self.send(&reply) {
+            eprintln!("warning: failed to notify the debugger of exit: {}", e);
+        }
+    }
+
+    fn serve<T>(&mut self, frame: &mut DebugFrame<'_, T>) -> io::Result<Resume> {
+        if self.running {
+            self.running = false;
+            self.send("T05thread:1;")?;
+        }
+        loop {
+            let packet = match self.recv()? {
+                Some(packet) => packet,
+                None => return Ok(Resume::Detach),
+            };
+            let resume = match packet.as_str() {
+                "c" => Resume::Continue,
+                "s" => Resume::Step,
+                "k" => Resume::Kill,
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(Resume::Detach);
+                }
+                p if p.starts_with("vCont;c") => Resume::Continue,
+                p if p.starts_with("vCont;s") => Resume::Step,
+                p if p.starts_with("vKill") => Resume::Kill,
+                p => {
+                    let reply = self.handle(frame, p);
+                    self.send(&reply)?;
+                    if p == "QStartNoAckMode" {
+                        self.no_ack = true;
+                    }
+                    continue;
+                }
+            };
+            self.running = true;
+            return Ok(resume);
+        }
+    }
+
+    /// Returns the reply to a packet which doesn't resume execution.
+    fn handle<T>(&mut self, frame: &mut DebugFrame<'_, T>, packet: &str) -> String {
+        let pc = self.pc(frame.func_index(), frame.offset());
+        let (name, args) = match packet.find(|c| c == ':' || c == ',') {
+            Some(i) if !packet.starts_with(|c| c == 'm' || c == 'Z' || c == 'z') => {
+                (&packet[..i], &packet[i + 1..])
+            }
+            _ => (packet, ""),
+        };
+        let reply = match name {
+            "?" => Some("T05thread:1;".to_string()),
+            "qSupported" => Some("PacketSize=1000;QStartNoAckMode+;qXfer:libraries:read+".into()),
+            "QStartNoAckMode" => Some("OK".into()),
+            "qHostInfo" => Some(format!(
+                "triple:{};endian:little;ptrsize:4;",
+                hex(TRIPLE.as_bytes())
+            )),
+            "qProcessInfo" => Some(format!(
+                "pid:1;parent-pid:1;vendor:wasmtime;ostype:wasi;arch:wasm32;\
+                 triple:{};endian:little;ptrsize:4;",
+                hex(TRIPLE.as_bytes())
+            )),
+            "qAttached" => Some("1".into()),
+            "qfThreadInfo" => Some("m1".into()),
+            "qsThreadInfo" => Some("l".into()),
+            "qC" => Some("QC1".into()),
+            "qRegisterInfo0" => Some(
+                "name:pc;alt-name:pc;bitsize:64;offset:0;encoding:uint;format:hex;\
+                 set:General Purpose Registers;gcc:16;dwarf:16;generic:pc;"
+                    .into(),
+            ),
+            "p0" | "g" => Some(hex(&pc.to_le_bytes())),
+            "vCont?" => Some("vCont;c;C;s;S".into()),
+            "qXfer" => self.libraries(args),
+            "qWasmCallStack" => Some(
+                frame
+                    .backtrace()
+                    .frames()
+                    .iter()
+                    .map(|f| hex(&(CODE_SPACE | f.module_offset() as u64).to_le_bytes()))
+                    .collect(),
+            ),
+            "qWasmLocal" | "qWasmStackValue" | "qWasmGlobal" => {
+                let mut args = args.split(';').map(|s| s.parse::<u32>().ok());
+                match (args.next().flatten(), args.next().flatten()) {
+                    // Only the innermost frame can be inspected.
+                    (Some(0), Some(index)) => {
+                        let value = match name {
+                            "qWasmLocal" => frame.locals().get(index as usize).cloned(),
+                            "qWasmStackValue" => frame.stack().get(index as usize).cloned(),
+                            _ => frame.global(index),
+                        };
+                        value.as_ref().and_then(value_bytes).map(|b| hex(&b))
+                    }
+                    _ => None,
+                }
+            }
+            "qMemoryRegionInfo" => u64::from_str_radix(args, 16)
+                .ok()
+                .and_then(|addr| self.region(frame, addr)),
+            _ if packet.starts_with('H') => Some("OK".into()),
+            _ if packet.starts_with("qRegisterInfo") => Some("E45".into()),
+            _ if packet.starts_with('m') => {
+                parse_range(&packet[1..]).and_then(|(addr, len)| self.read(frame, addr, len))
+            }
+            _ if packet.starts_with("Z0,") || packet.starts_with("z0,") => {
+                let insert = packet.starts_with('Z');
+                parse_range(&packet[3..])
+                    .and_then(|(addr, _)| self.location(addr))
+                    .and_then(|(func_index, offset)| {
+                        self.set_breakpoint(frame, insert, func_index, offset)
+                    })
+            }
+            // An empty reply tells the debugger that the packet is unsupported.
+            _ => return String::new(),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn set_breakpoint<T>(
+        &mut self,
+        frame: &mut DebugFrame<'_, T>,
+        insert: bool,
+        func_index: u32,
+        offset: u32,
+    ) -> Option<String> {
+        let mut store = frame.as_context_mut();
+        if insert {
+            store
+                .add_breakpoint(&self.module, func_index, offset)
+                .ok()?;
+            self.breakpoints.push((func_index, offset));
+        } else {
+            store.remove_breakpoint(&self.module, func_index, offset);
+            self.breakpoints.retain(|b| *b != (func_index, offset));
+        }
+        Some("OK".into())
+    }
+
+    /// Replies to `qXfer:libraries:read::OFFSET,LENGTH`, describing the main
+    /// module as the only loaded library.
+    fn libraries(&self, args: &str) -> Option<String> {
+        let (offset, len) = parse_range(args.strip_prefix("libraries:read::")?)?;
+        let xml = format!(
+            "<library-list><library name=\"{}\"><section address=\"{:#x}\"/></library>\
+             </library-list>",
+            self.name
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('"', "&quot;"),
+            CODE_SPACE
+        );
+        let start = (offset as usize).min(xml.len());
+        let end = start.saturating_add(len).min(xml.len());
+        let more = if end < xml.len() { 'm' } else { 'l' };
+        Some(format!("{}{}", more, &xml[start..end]))
+    }
+
+    fn region<T>(&self, frame: &mut DebugFrame<'_, T>, addr: u64) -> Option<String> {
+        let (start, size, permissions) = if addr >> 62 == 1 {
+            (CODE_SPACE, self.bytes.len() as u64, "rx")
+        } else {
+            (0, memory(frame)?.len() as u64, "rw")
+        };
+        if addr >= start + size {
+            return None;
+        }
+        Some(format!(
+            "start:{:x};size:{:x};permissions:{};",
+            start, size, permissions
+        ))
+    }
+
+    fn read<T>(&self, frame: &mut DebugFrame<'_, T>, addr: u64, len: usize) -> Option<String> {
+        if addr >> 32 & 0x3fff_ffff != 0 {
+            return None;
+        }
+        let offset = addr as u32 as usize;
+        let bytes = if addr >> 62 == 1 {
+            self.bytes.get(offset..)?.to_vec()
+        } else {
+            memory(frame)?.get(offset..)?.to_vec()
+        };
+        if bytes.is_empty() && len > 0 {
+            return None;
+        }
+        Some(hex(&bytes[..len.min(bytes.len())]))
+    }
+
+    /// Returns the code address of `offset` in the function `func_index`.
+    fn pc(&self, func_index: u32, offset: u32) -> u64 {
+        let start = self
+            .funcs
+            .iter()
+            .find(|(index, _)| *index == func_index)
+            .map_or(0, |(_, body)| body.start);
+        CODE_SPACE | (start as u64 + u64::from(offset))
+    }
+
+    /// Returns the function index and offset of the code address `addr`.
+    fn location(&self, addr: u64) -> Option<(u32, u32)> {
+        if addr & !0xffff_ffff != CODE_SPACE {
+            return None;
+        }
+        let offset = addr as u32 as usize;
+        self.funcs
+            .iter()
+            .find(|(_, body)| body.contains(&offset))
+            .map(|(index, body)| (*index, (offset - body.start) as u32))
+    }
+
+    fn recv(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Acks, and interrupt requests, are ignored since execution is
+            // already stopped.
+            let mut byte = [0];
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut packet = Vec::new();
+            self.reader.read_until(b'#', &mut packet)?;
+            if packet.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                == Some(checksum_of(&packet));
+            if !self.no_ack {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid || self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&packet).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(
+            self.writer,
+            "${}#{:02x}",
+            data,
+            checksum_of(data.as_bytes())
+        )?;
+        self.writer.flush()
+    }
+}
+
+/// Returns the index and body range of each function defined in `wasm`.
+fn function_bodies(wasm: &[u8]) -> Result<Vec<(u32, Range<usize>)>> {
+    let mut funcs = Vec::new();
+    let mut index = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    if let ImportSectionEntryType::Function(_) = import?.ty {
+                        index += 1;
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let range = body.range();
+                funcs.push((index, range.start..range.end));
+                index += 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(funcs)
+}
+
+/// Returns a copy of the stopped module's first linear memory.
+fn memory<T>(frame: &mut DebugFrame<'_, T>) -> Option<Vec<u8>> {
+    match frame.memory(0)? {
+        Extern::Memory(memory) => Some(memory.data(frame.as_context()).to_vec()),
+        Extern::SharedMemory(memory) => {
+            Some(memory.data().iter().map(|b| unsafe { *b.get() }).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the little-endian bytes of a value, or `None` for references.
+fn value_bytes(val: &Val) -> Option<Vec<u8>> {
+    Some(match val {
+        Val::I32(i) => i.to_le_bytes().to_vec(),
+        Val::I64(i) => i.to_le_bytes().to_vec(),
+        Val::F32(f) => f.to_le_bytes().to_vec(),
+        Val::F64(f) => f.to_le_bytes().to_vec(),
+        Val::V128(v) => v.to_le_bytes().to_vec(),
+        Val::ExternRef(_) | Val::FuncRef(_) => return None,
+    })
+}
+
+/// Parses the `ADDR,LENGTH` arguments of a packet, both in hex.
+fn parse_range(args: &str) -> Option<(u64, usize)> {
+    let mut parts = args.splitn(2, ',');
+    let addr = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let len = parts.next()?;
+    let len = len.split(|c| c == ',' || c == ';').next()?;
+    Some((addr, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -42,6 +42,11 @@ const SUPPORTED_WASM_FEATURES: &[(&str, &str)] = &[
     ("simd", "enables support for proposed SIMD instructions"),
     ("threads", "enables support for WebAssembly threads"),
     ("memory64", "enables support for 64-bit memories"),
+    ("tail-call", "enables support for the tail-call proposal"),
+    (
+        "exceptions",
+        "enables support for the exception handling proposal",
+    ),
 ];
 
 const SUPPORTED_WASI_MODULES: &[(&str, &str)] = &[
@@ -94,6 +99,7 @@ lazy_static::lazy_static! {
 }
 
 pub mod commands;
+mod debug_server;
 mod obj;
 
 use anyhow::{bail, Result};
@@ -114,15 +120,23 @@ fn pick_compilation_strategy(cranelift: bool, lightbeam: bool) -> Result<Strateg
     })
 }
 
-fn pick_profiling_strategy(jitdump: bool, vtune: bool) -> Result<ProfilingStrategy> {
-    Ok(match (jitdump, vtune) {
-        (true, false) => ProfilingStrategy::JitDump,
-        (false, true) => ProfilingStrategy::VTune,
-        (true, true) => {
-            println!("Can't enable --jitdump and --vtune at the same time. Profiling not enabled.");
+fn pick_profiling_strategy(
+    jitdump: bool,
+    vtune: bool,
+    perfmap: bool,
+) -> Result<ProfilingStrategy> {
+    Ok(match (jitdump, vtune, perfmap) {
+        (true, false, false) => ProfilingStrategy::JitDump,
+        (false, true, false) => ProfilingStrategy::VTune,
+        (false, false, true) => ProfilingStrategy::PerfMap,
+        (false, false, false) => ProfilingStrategy::None,
+        _ => {
+            println!(
+                "Can't enable more than one of --jitdump, --vtune and --perfmap \
+                 at the same time. Profiling not enabled."
+            );
             ProfilingStrategy::None
         }
-        _ => ProfilingStrategy::None,
     })
 }
 
@@ -224,13 +238,17 @@ struct CommonOptions {
     lightbeam: bool,
 
     /// Generate jitdump file (supported on --features=profiling build)
-    #[structopt(long, conflicts_with = "vtune")]
+    #[structopt(long, conflicts_with_all = &["vtune", "perfmap"])]
     jitdump: bool,
 
     /// Generate vtune (supported on --features=vtune build)
-    #[structopt(long, conflicts_with = "jitdump")]
+    #[structopt(long, conflicts_with_all = &["jitdump", "perfmap"])]
     vtune: bool,
 
+    /// Generate a `/tmp/perf-<pid>.map` file for `perf` (supported on Linux)
+    #[structopt(long, conflicts_with_all = &["jitdump", "vtune"])]
+    perfmap: bool,
+
     /// Run optimization passes on translated functions, on by default
     #[structopt(short = "O", long)]
     optimize: bool,
@@ -310,7 +328,7 @@ impl CommonOptions {
             .cranelift_debug_verifier(self.enable_cranelift_debug_verifier)
             .debug_info(self.debug_info)
             .cranelift_opt_level(self.opt_level())
-            .profiler(pick_profiling_strategy(self.jitdump, self.vtune)?)?
+            .profiler(pick_profiling_strategy(self.jitdump, self.vtune, self.perfmap)?)?
             .cranelift_nan_canonicalization(self.enable_cranelift_nan_canonicalization);
 
         self.enable_wasm_features(&mut config);
@@ -366,6 +384,8 @@ impl CommonOptions {
             .wasm_threads(features.threads || self.enable_threads || self.enable_all)
             .wasm_multi_memory(features.multi_memory || self.enable_multi_memory || self.enable_all)
             .wasm_memory64(features.memory64 || self.enable_all)
+            .wasm_tail_call(features.tail_call || self.enable_all)
+            .wasm_exceptions(features.exceptions || self.enable_all)
             .wasm_module_linking(
                 features.module_linking || self.enable_module_linking || self.enable_all,
             );
@@ -437,10 +457,10 @@ fn parse_wasm_features(features: &str) -> Result<wasmparser::WasmFeatures> {
         module_linking: all.unwrap_or(values["module-linking"].unwrap_or(false)),
         simd: all.unwrap_or(values["simd"].unwrap_or(false)),
         threads: all.unwrap_or(values["threads"].unwrap_or(false)),
-        tail_call: false,
+        tail_call: all.unwrap_or(values["tail-call"].unwrap_or(false)),
         deterministic_only: false,
         multi_memory: all.unwrap_or(values["multi-memory"].unwrap_or(false)),
-        exceptions: false,
+        exceptions: all.unwrap_or(values["exceptions"].unwrap_or(false)),
         memory64: all.unwrap_or(values["memory64"].unwrap_or(false)),
     })
 }
@@ -561,10 +581,10 @@ mod test {
         assert!(module_linking);
         assert!(simd);
         assert!(threads);
-        assert!(!tail_call); // Not supported
+        assert!(tail_call);
         assert!(!deterministic_only); // Not supported
         assert!(multi_memory);
-        assert!(!exceptions); // Not supported
+        assert!(exceptions);
         assert!(memory64);
 
         Ok(())
@@ -630,10 +650,10 @@ mod test {
         assert!(!module_linking);
         assert!(simd);
         assert!(!threads);
-        assert!(!tail_call); // Not supported
+        assert!(!tail_call);
         assert!(!deterministic_only); // Not supported
         assert!(multi_memory);
-        assert!(!exceptions); // Not supported
+        assert!(!exceptions);
         assert!(memory64);
 
         Ok(())
@@ -680,6 +700,8 @@ mod test {
     feature_test!(test_threads_feature, threads, "threads");
     feature_test!(test_multi_memory_feature, multi_memory, "multi-memory");
     feature_test!(test_memory64_feature, memory64, "memory64");
+    feature_test!(test_tail_call_feature, tail_call, "tail-call");
+    feature_test!(test_exceptions_feature, exceptions, "exceptions");
 
     #[test]
     fn test_default_modules() {
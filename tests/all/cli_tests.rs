@@ -4,8 +4,8 @@ use std::path::Path;
 use std::process::{Command, Output};
 use tempfile::NamedTempFile;
 
-// Run the wasmtime CLI with the provided args and return the `Output`.
-fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+// Get a `Command` which runs the wasmtime CLI with the provided args.
+fn get_wasmtime_command(args: &[&str]) -> Result<Command> {
     let runner = std::env::vars()
         .filter(|(k, _v)| k.starts_with("CARGO_TARGET") && k.ends_with("RUNNER"))
         .next();
@@ -28,7 +28,13 @@ fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
     } else {
         Command::new(&me)
     };
-    cmd.args(args).output().map_err(Into::into)
+    cmd.args(args);
+    Ok(cmd)
+}
+
+// Run the wasmtime CLI with the provided args and return the `Output`.
+fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+    get_wasmtime_command(args)?.output().map_err(Into::into)
 }
 
 // Run the wasmtime CLI with the provided args and, if it succeeds, return
@@ -203,6 +209,72 @@ fn timeout_in_invoke() -> Result<()> {
     Ok(())
 }
 
+// Run a module, with its arguments and timeout, from a manifest.
+#[test]
+fn manifest() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/simple.wat")?;
+    let mut manifest = NamedTempFile::new()?;
+    write!(
+        manifest,
+        "module = {:?}\nargs = [\"4\"]\n",
+        wasm.path().to_str().unwrap()
+    )?;
+    run_wasmtime(&[
+        "run",
+        "--manifest",
+        manifest.path().to_str().unwrap(),
+        "--invoke",
+        "simple",
+        "--disable-cache",
+    ])?;
+
+    let wasm = build_wasm("tests/all/cli_tests/iloop-start.wat")?;
+    let mut manifest = NamedTempFile::new()?;
+    write!(
+        manifest,
+        "module = {:?}\ntimeout = \"1ms\"\n",
+        wasm.path().to_str().unwrap()
+    )?;
+    let output = run_wasmtime_for_output(&[
+        "run",
+        "--manifest",
+        manifest.path().to_str().unwrap(),
+        "--disable-cache",
+    ])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("wasm trap: interrupt"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
+// Open files until the manifest's descriptor limit is reached.
+#[test]
+fn manifest_fd_limit() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/fd_limit.wat")?;
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("f"), "")?;
+    let mut manifest = NamedTempFile::new()?;
+    write!(
+        manifest,
+        "module = {:?}\nfd-limit = 6\n[[dir]]\nhost = {:?}\n",
+        wasm.path().to_str().unwrap(),
+        dir.path().to_str().unwrap()
+    )?;
+    let stdout = run_wasmtime(&[
+        "run",
+        "--manifest",
+        manifest.path().to_str().unwrap(),
+        "--disable-cache",
+    ])?;
+    // Stdio and the preopen count toward the limit, leaving room for two.
+    assert_eq!(stdout, "2");
+    Ok(())
+}
+
 // Exit with a valid non-zero exit code, snapshot0 edition.
 #[test]
 fn exit2_wasi_snapshot0() -> Result<()> {
@@ -381,3 +453,45 @@ fn exit_with_saved_fprs() -> Result<()> {
     assert!(output.stdout.is_empty());
     Ok(())
 }
+
+// Serve a single connection from the host on a `--tcplisten` socket.
+#[test]
+fn tcplisten_echo() -> Result<()> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::Stdio;
+
+    let wasm = build_wasm("tests/all/cli_tests/tcp_echo.wat")?;
+    let mut child = get_wasmtime_command(&[
+        "run",
+        "--disable-cache",
+        "--tcplisten",
+        "127.0.0.1:0",
+        wasm.path().to_str().unwrap(),
+    ])?
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+    // The system picks the port, and wasmtime reports it.
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line)?;
+    let addr = match line.trim().strip_prefix("listening on ") {
+        Some(addr) => addr.to_string(),
+        None => bail!("unexpected stderr: {}", line),
+    };
+
+    let mut stream = std::net::TcpStream::connect(&addr)?;
+    stream.write_all(b"ping")?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    assert_eq!(reply, b"pong");
+    drop(stream);
+
+    let output = child.wait_with_output()?;
+    let mut rest = String::new();
+    stderr.read_to_string(&mut rest)?;
+    assert!(output.status.success(), "wasmtime failed: {}", rest);
+    assert_eq!(output.stdout, b"ping");
+    Ok(())
+}
@@ -0,0 +1,46 @@
+(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_close"
+    (func $__wasi_fd_close (param i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $__wasi_path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+
+  ;; Opens "f" in the directory preopened at fd 3 with `fd_read` rights.
+  (func $open (result i32)
+    (call $__wasi_path_open
+      (i32.const 3) (i32.const 0) (i32.const 100) (i32.const 1) (i32.const 0)
+      (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 0)))
+
+  ;; Opens "f" until `path_open` fails, which must be with `EMFILE`, and
+  ;; prints how many opens succeeded. Closing a descriptor then makes room
+  ;; for one more.
+  (func $_start
+    (local $count i32)
+    (local $errno i32)
+    (loop $again
+      (local.set $errno (call $open))
+      (if (i32.eqz (local.get $errno))
+        (then
+          (local.set $count (i32.add (local.get $count) (i32.const 1)))
+          (br $again))))
+    ;; ERRNO_MFILE
+    (if (i32.ne (local.get $errno) (i32.const 33)) (then unreachable))
+
+    (if (call $__wasi_fd_close (i32.load (i32.const 0))) (then unreachable))
+    (if (call $open) (then unreachable))
+    (if (i32.ne (call $open) (i32.const 33)) (then unreachable))
+
+    ;; Print the count as a digit: iovec { buf: 200, len: 1 } at 8.
+    (i32.store8 (i32.const 200) (i32.add (i32.const 48) (local.get $count)))
+    (i32.store (i32.const 8) (i32.const 200))
+    (i32.store (i32.const 12) (i32.const 1))
+    (if (call $__wasi_fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16))
+      (then unreachable))
+  )
+
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+  (data (i32.const 100) "f")
+)
@@ -0,0 +1,60 @@
+(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_accept"
+    (func $__wasi_sock_accept (param i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_recv"
+    (func $__wasi_sock_recv (param i32 i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_send"
+    (func $__wasi_sock_send (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_shutdown"
+    (func $__wasi_sock_shutdown (param i32 i32) (result i32)))
+
+  ;; Accepts a connection on the listener at fd 3, echoes the 4 bytes it
+  ;; receives to stdout, replies "pong", shuts down writing, and then waits
+  ;; for the peer to close its side. Any failure traps.
+  (func $_start
+    (local $fd i32)
+    (call $check (call $__wasi_sock_accept (i32.const 3) (i32.const 0) (i32.const 0)))
+    (local.set $fd (i32.load (i32.const 0)))
+
+    ;; iovec { buf: 100, len: 4 } at 8, received with RECV_WAITALL.
+    (i32.store (i32.const 8) (i32.const 100))
+    (i32.store (i32.const 12) (i32.const 4))
+    (call $check
+      (call $__wasi_sock_recv
+        (local.get $fd) (i32.const 8) (i32.const 1) (i32.const 2)
+        (i32.const 16) (i32.const 20)))
+    (call $expect_size (i32.const 4))
+    (call $check
+      (call $__wasi_fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16)))
+
+    ;; ciovec { buf: 200, len: 4 } at 8.
+    (i32.store (i32.const 8) (i32.const 200))
+    (call $check
+      (call $__wasi_sock_send
+        (local.get $fd) (i32.const 8) (i32.const 1) (i32.const 0) (i32.const 16)))
+    (call $expect_size (i32.const 4))
+    ;; SDFLAGS_WR
+    (call $check (call $__wasi_sock_shutdown (local.get $fd) (i32.const 2)))
+
+    ;; The peer closes once it has read everything, which reads as 0 bytes.
+    (i32.store (i32.const 8) (i32.const 100))
+    (call $check
+      (call $__wasi_sock_recv
+        (local.get $fd) (i32.const 8) (i32.const 1) (i32.const 0)
+        (i32.const 16) (i32.const 20)))
+    (call $expect_size (i32.const 0))
+  )
+
+  (func $check (param $errno i32)
+    (if (local.get $errno) (then unreachable)))
+
+  (func $expect_size (param $size i32)
+    (if (i32.ne (i32.load (i32.const 16)) (local.get $size)) (then unreachable)))
+
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+  (data (i32.const 200) "pong")
+)
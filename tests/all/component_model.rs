@@ -0,0 +1,257 @@
+use anyhow::Result;
+use wasmtime::component::{self, ComponentType, Lift, Lower, Options};
+use wasmtime::*;
+
+/// A memory along with a bump allocator satisfying the `cabi_realloc`
+/// interface, allocating from address 1024 onwards.
+const ALLOCATOR: &str = r#"
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 1024))
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+        (local $ret i32)
+        (local.set $ret
+            (i32.and
+                (i32.add (global.get $next) (i32.sub (local.get 2) (i32.const 1)))
+                (i32.sub (i32.const 0) (local.get 2))))
+        (global.set $next (i32.add (local.get $ret) (local.get 3)))
+        (local.get $ret))
+"#;
+
+/// Compiles a module with the fields in `body`, where `(;allocator;)` is
+/// replaced with `ALLOCATOR`.
+fn module(engine: &Engine, body: &str) -> Result<Module> {
+    let body = body.replace("(;allocator;)", ALLOCATOR);
+    Module::new(engine, &format!("(module {})", body))
+}
+
+fn instantiate(store: &mut Store<()>, body: &str, imports: &[Extern]) -> Result<Instance> {
+    let module = module(store.engine(), body)?;
+    Instance::new(store, &module, imports)
+}
+
+#[derive(ComponentType, Lift, Lower, Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(ComponentType, Lift, Lower, Debug, PartialEq)]
+struct Named(String, u8);
+
+#[derive(ComponentType, Lift, Lower, Debug, PartialEq)]
+enum Shape {
+    Circle(f32),
+    Square(u32),
+    Empty,
+}
+
+#[derive(ComponentType, Lift, Lower, Debug, PartialEq)]
+struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+#[test]
+fn layout() {
+    assert_eq!(<Point as ComponentType>::SIZE32, 8);
+    assert_eq!(<Point as ComponentType>::ALIGN32, 4);
+    assert_eq!(<Named as ComponentType>::SIZE32, 12);
+    assert_eq!(<(u8, u64, u16) as ComponentType>::SIZE32, 24);
+    assert_eq!(<(u8, u64, u16) as ComponentType>::ALIGN32, 8);
+    assert_eq!(<Option<String> as ComponentType>::SIZE32, 12);
+    assert_eq!(<Option<u8> as ComponentType>::SIZE32, 2);
+    assert_eq!(<Result<u64, ()> as ComponentType>::SIZE32, 16);
+    assert_eq!(<Shape as ComponentType>::SIZE32, 8);
+    assert_eq!(<Pair<Option<u16>> as ComponentType>::SIZE32, 8);
+
+    let flat = |f: fn(&mut Vec<ValType>)| {
+        let mut dst = Vec::new();
+        f(&mut dst);
+        dst
+    };
+    assert_eq!(
+        flat(<Shape as ComponentType>::flatten),
+        [ValType::I32, ValType::I32]
+    );
+    assert_eq!(
+        flat(<Result<f64, u32> as ComponentType>::flatten),
+        [ValType::I32, ValType::I64]
+    );
+    assert_eq!(
+        flat(<(Named, Option<f32>) as ComponentType>::flatten),
+        [
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::I32,
+            ValType::F32
+        ]
+    );
+}
+
+#[test]
+fn host_lifts_strings_and_lists() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let greet = Func::wrap_component(&mut store, |_, (name,): (String,)| {
+        Ok(name == "wasm" && name.len() == 4)
+    });
+    let sum = Func::wrap_component(&mut store, |_, (points,): (Vec<Point>,)| {
+        Ok(points.iter().map(|p| i64::from(p.x * p.y)).sum::<i64>())
+    });
+    let instance = instantiate(
+        &mut store,
+        r#"
+            (import "" "greet" (func $greet (param i32 i32) (result i32)))
+            (import "" "sum" (func $sum (param i32 i32) (result i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 4) "wasm")
+            (data (i32.const 8) "\02\00\00\00\03\00\00\00\04\00\00\00\05\00\00\00")
+            (func (export "greet") (result i32) (call $greet (i32.const 4) (i32.const 4)))
+            (func (export "sum") (result i64) (call $sum (i32.const 8) (i32.const 2)))
+            (func (export "oob") (result i32) (call $greet (i32.const 65530) (i32.const 10)))
+            (func (export "misaligned") (result i64) (call $sum (i32.const 6) (i32.const 1)))
+        "#,
+        &[greet.into(), sum.into()],
+    )?;
+
+    let greet = instance.get_typed_func::<(), i32, _>(&mut store, "greet")?;
+    assert_eq!(greet.call(&mut store, ())?, 1);
+    let sum = instance.get_typed_func::<(), i64, _>(&mut store, "sum")?;
+    assert_eq!(sum.call(&mut store, ())?, 26);
+
+    let oob = instance.get_typed_func::<(), i32, _>(&mut store, "oob")?;
+    let trap = oob.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("out of bounds"), "{}", trap);
+    let misaligned = instance.get_typed_func::<(), i64, _>(&mut store, "misaligned")?;
+    let trap = misaligned.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("not aligned"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn host_lowers_through_return_pointer() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let mut linker = Linker::new(store.engine());
+    linker.func_wrap_component("", "lookup", |_, (key,): (u32,)| {
+        Ok(match key {
+            0 => None,
+            n => Some(Named("x".repeat(n as usize), n as u8)),
+        })
+    })?;
+    let module = module(
+        store.engine(),
+        r#"
+            (import "" "lookup" (func $lookup (param i32 i32)))
+            (;allocator;)
+            (func (export "lookup") (param i32) (result i32)
+                (call $lookup (local.get 0) (i32.const 16))
+                i32.const 16)
+        "#,
+    )?;
+    let instance = linker.instantiate(&mut store, &module)?;
+
+    let options = Options::from_instance(&mut store, &instance)?;
+    let func = instance.get_func(&mut store, "lookup").unwrap();
+    let lookup = component::TypedFunc::<(u32,), Option<Named>>::new(&store, func, options)?;
+    assert_eq!(lookup.call(&mut store, (0,))?, None);
+    assert_eq!(
+        lookup.call(&mut store, (3,))?,
+        Some(Named("xxx".to_string(), 3))
+    );
+    Ok(())
+}
+
+#[test]
+fn call_export_with_strings() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(
+        &mut store,
+        r#"
+            (;allocator;)
+            (data (i32.const 100) "\ff\fe")
+            ;; Returns a pointer to the (ptr, len) pair of its argument, with
+            ;; the string's first byte uppercased.
+            (func (export "shout") (param i32 i32) (result i32)
+                (i32.store8 (local.get 0)
+                    (i32.sub (i32.load8_u (local.get 0)) (i32.const 32)))
+                (i32.store (i32.const 8) (local.get 0))
+                (i32.store (i32.const 12) (local.get 1))
+                i32.const 8)
+            (func (export "invalid") (result i32)
+                (i32.store (i32.const 8) (i32.const 100))
+                (i32.store (i32.const 12) (i32.const 2))
+                i32.const 8)
+        "#,
+        &[],
+    )?;
+    let options = Options::from_instance(&mut store, &instance)?;
+
+    let func = instance.get_func(&mut store, "shout").unwrap();
+    let shout = component::TypedFunc::<(&str,), String>::new(&store, func, options.clone())?;
+    assert_eq!(shout.call(&mut store, ("hello",))?, "Hello");
+
+    let func = instance.get_func(&mut store, "invalid").unwrap();
+    let invalid = component::TypedFunc::<(), String>::new(&store, func, options.clone())?;
+    let err = invalid.call(&mut store, ()).unwrap_err();
+    assert!(err.to_string().contains("invalid utf-8"), "{}", err);
+
+    // The core wasm signature must match how the arguments are flattened.
+    let func = instance.get_func(&mut store, "shout").unwrap();
+    assert!(component::TypedFunc::<(u32,), String>::new(&store, func, options).is_err());
+    Ok(())
+}
+
+#[test]
+fn call_export_with_variants() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(
+        &mut store,
+        r#"
+            (func (export "area") (param i32 i32) (result f32)
+                (block $empty
+                    (block $square
+                        (block $circle
+                            (br_table $circle $square $empty (local.get 0)))
+                        (return
+                            (f32.mul
+                                (f32.const 3)
+                                (f32.mul
+                                    (f32.reinterpret_i32 (local.get 1))
+                                    (f32.reinterpret_i32 (local.get 1))))))
+                    (return
+                        (f32.convert_i32_u (i32.mul (local.get 1) (local.get 1)))))
+                f32.const 0)
+        "#,
+        &[],
+    )?;
+    let func = instance.get_func(&mut store, "area").unwrap();
+    let area = component::TypedFunc::<(Shape,), f32>::new(&store, func, Options::new())?;
+    assert_eq!(area.call(&mut store, (Shape::Circle(2.0),))?, 12.0);
+    assert_eq!(area.call(&mut store, (Shape::Square(3),))?, 9.0);
+    assert_eq!(area.call(&mut store, (Shape::Empty,))?, 0.0);
+    Ok(())
+}
+
+#[test]
+fn many_params_are_passed_in_memory() -> Result<()> {
+    // Eight two-field records and a trailing integer flatten to 17 values.
+    type Params = (Point, Point, Point, Point, Point, Point, Point, Point, u32);
+
+    let mut store = Store::<()>::default();
+    let instance = instantiate(
+        &mut store,
+        r#"
+            (;allocator;)
+            (func (export "last") (param i32) (result i32)
+                (i32.load offset=64 (local.get 0)))
+        "#,
+        &[],
+    )?;
+    let options = Options::from_instance(&mut store, &instance)?;
+    let func = instance.get_func(&mut store, "last").unwrap();
+    let last = component::TypedFunc::<Params, u32>::new(&store, func, options)?;
+    let p = || Point { x: 1, y: 2 };
+    let params = (p(), p(), p(), p(), p(), p(), p(), p(), 17);
+    assert_eq!(last.call(&mut store, params)?, 17);
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn engine() -> Engine {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    Engine::new(&config).unwrap()
+}
+
+// Offsets of instructions within the body of the function, where offset 0 is
+// the local declarations.
+const ADD: &str = r#"
+    (module
+        (func (export "add") (param i32 i32) (result i32)
+            local.get 0     ;; offset 1
+            local.get 1     ;; offset 3
+            i32.add)        ;; offset 5, then `end` at 6
+    )
+"#;
+
+#[test]
+fn breakpoint_inspects_frame() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (global $g (mut i64) (i64.const 7))
+                (func $helper (param i32) (result i32)
+                    (local f32 i64)
+                    (global.set $g (i64.const 9))
+                    (i32.store8 (i32.const 3) (i32.const 42))
+                    (local.set 1 (f32.const 1.5))
+                    local.get 0
+                    i32.const 10
+                    i32.mul)
+                (func (export "run") (param i32) (result i32)
+                    (call $helper (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut frame, event| {
+        assert_eq!(event, DebugEvent::Breakpoint);
+        assert_eq!(frame.func_index(), 0);
+        let locals = frame
+            .locals()
+            .iter()
+            .map(|v| format!("{:?}", v))
+            .collect::<Vec<_>>();
+        let stack = frame
+            .stack()
+            .iter()
+            .map(|v| format!("{:?}", v))
+            .collect::<Vec<_>>();
+        let global = frame.global(0).unwrap().unwrap_i64();
+        assert!(frame.global(1).is_none());
+        let byte = match frame.memory(0).unwrap() {
+            Extern::Memory(m) => m.data(&frame)[3],
+            _ => unreachable!(),
+        };
+        assert!(frame.memory(1).is_none());
+        let backtrace = frame.backtrace();
+        assert_eq!(backtrace.frames().len(), 2);
+        assert_eq!(backtrace.frames()[0].func_index(), 0);
+        assert_eq!(backtrace.frames()[0].func_offset(), frame.offset() as usize);
+        assert_eq!(backtrace.frames()[1].func_index(), 1);
+        frame.data_mut().push((locals, stack, global, byte));
+        Ok(DebugAction::Continue)
+    });
+    // Stop before the `i32.mul`, after 5 bytes of local declarations and 22
+    // bytes of instructions.
+    store.add_breakpoint(&module, 0, 27)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 4)?, 40);
+
+    let stops = store.data();
+    assert_eq!(stops.len(), 1);
+    let (locals, stack, global, byte) = &stops[0];
+    assert_eq!(locals, &["I32(4)", "F32(1069547520)", "I64(0)"]);
+    assert_eq!(stack, &["I32(4)", "I32(10)"]);
+    assert_eq!(*global, 9);
+    assert_eq!(*byte, 42);
+    Ok(())
+}
+
+#[test]
+fn single_step() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, ADD)?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut frame, event| {
+        assert_eq!(event, DebugEvent::Step);
+        let offset = frame.offset();
+        frame.data_mut().push(offset);
+        Ok(DebugAction::Step)
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+
+    store.debug_single_step(true);
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(store.data(), &[1, 3, 5, 6]);
+
+    // Stepping continues across calls until the handler says otherwise.
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(store.data().len(), 8);
+    store.debug_single_step(false);
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(store.data().len(), 8);
+    Ok(())
+}
+
+#[test]
+fn breakpoints_and_steps() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, ADD)?;
+    let other = Module::new(&engine, ADD)?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut frame, event| {
+        let offset = frame.offset();
+        frame.data_mut().push((event, offset));
+        Ok(match event {
+            DebugEvent::Breakpoint => DebugAction::Step,
+            DebugEvent::Step => DebugAction::Continue,
+        })
+    });
+    store.add_breakpoint(&module, 0, 3)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(
+        store.data(),
+        &[(DebugEvent::Breakpoint, 3), (DebugEvent::Step, 5)]
+    );
+
+    // Breakpoints only apply to the module they were added to.
+    let instance = Instance::new(&mut store, &other, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(store.data().len(), 2);
+
+    assert!(store.remove_breakpoint(&module, 0, 3));
+    assert!(!store.remove_breakpoint(&module, 0, 3));
+    Ok(())
+}
+
+#[test]
+fn handler_can_trap() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, ADD)?;
+    let mut store = Store::new(&engine, ());
+    store.debug_handler(|_, _| Err(Trap::new("debugger detached")));
+    store.add_breakpoint(&module, 0, 5)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    let trap = add.call(&mut store, (1, 2)).unwrap_err();
+    assert!(trap.to_string().contains("debugger detached"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn add_breakpoint_errors() -> Result<()> {
+    let mut store = Store::new(&engine(), ());
+    let module = Module::new(store.engine(), ADD)?;
+    assert!(store.add_breakpoint(&module, 1, 0).is_err());
+
+    let module = Module::new(&Engine::default(), ADD)?;
+    assert!(store.add_breakpoint(&module, 0, 1).is_err());
+
+    let mut store = Store::new(module.engine(), ());
+    let err = store.add_breakpoint(&module, 0, 1).unwrap_err();
+    assert!(err.to_string().contains("guest_debug"), "{}", err);
+    Ok(())
+}
@@ -0,0 +1,310 @@
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread;
+use std::time::Duration;
+use wasmtime::*;
+
+fn build_engine(async_support: bool) -> Result<Engine> {
+    let mut config = Config::new();
+    config.async_support(async_support);
+    config.epoch_interruption(true);
+    Engine::new(&config)
+}
+
+/// Instantiates `wat` with a `bump_epoch` import which increments the engine's
+/// epoch and returns the exported `run` function.
+fn instantiate(store: &mut Store<()>, wat: &str) -> Result<TypedFunc<(), ()>> {
+    let module = Module::new(store.engine(), wat)?;
+    let bump = Func::wrap(&mut *store, |caller: Caller<'_, ()>| {
+        caller.engine().increment_epoch();
+    });
+    let instance = Instance::new(&mut *store, &module, &[bump.into()])?;
+    instance.get_typed_func::<(), (), _>(&mut *store, "run")
+}
+
+fn assert_interrupted(result: Result<(), Trap>) {
+    let trap = result.unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt), "{}", trap);
+}
+
+#[test]
+fn deadline_not_reached() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (local i32)
+                    i32.const 10
+                    local.set 0
+                    (loop
+                        call $bump
+                        local.get 0
+                        i32.const -1
+                        i32.add
+                        local.tee 0
+                        br_if 0)))
+        "#,
+    )?;
+    store.set_epoch_deadline(11);
+    run.call(&mut store, ())?;
+    Ok(())
+}
+
+#[test]
+fn initial_deadline_traps_on_entry() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")))
+        "#,
+    )?;
+    // Stores start out with a deadline of zero, which has already been
+    // reached.
+    assert_interrupted(run.call(&mut store, ()));
+
+    store.set_epoch_deadline(1);
+    run.call(&mut store, ())?;
+    Ok(())
+}
+
+#[test]
+fn deadline_reached_in_loop_traps() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (loop
+                        call $bump
+                        br 0)))
+        "#,
+    )?;
+    store.set_epoch_deadline(5);
+    assert_interrupted(run.call(&mut store, ()));
+
+    // Explicitly configuring the trapping behavior behaves the same.
+    store.epoch_deadline_trap();
+    store.set_epoch_deadline(5);
+    assert_interrupted(run.call(&mut store, ()));
+    Ok(())
+}
+
+#[test]
+fn deadline_reached_in_callee_traps() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func $f
+                    call $bump
+                    call $f)
+                (func (export "run")
+                    call $f))
+        "#,
+    )?;
+    store.set_epoch_deadline(3);
+    assert_interrupted(run.call(&mut store, ()));
+    Ok(())
+}
+
+#[test]
+fn interrupt_from_another_thread() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (loop br 0)))
+        "#,
+    )?;
+    store.set_epoch_deadline(1);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let ticker = {
+        let engine = engine.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                engine.increment_epoch();
+                thread::sleep(Duration::from_millis(1));
+            }
+        })
+    };
+    let result = run.call(&mut store, ());
+    done.store(true, Ordering::SeqCst);
+    ticker.join().unwrap();
+    assert_interrupted(result);
+    Ok(())
+}
+
+#[test]
+fn deadline_yields_and_updates() -> Result<()> {
+    let engine = build_engine(true)?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (local i32)
+                    i32.const 10
+                    local.set 0
+                    (loop
+                        call $bump
+                        local.get 0
+                        i32.const -1
+                        i32.add
+                        local.tee 0
+                        br_if 0)))
+        "#,
+    )?;
+    let bump = Func::wrap(&mut store, |caller: Caller<'_, ()>| {
+        caller.engine().increment_epoch();
+    });
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_async_yield_and_update(1);
+    let instance = run(Instance::new_async(&mut store, &module, &[bump.into()])).0?;
+    let func = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+
+    let (result, yields) = run(func.call_async(&mut store, ()));
+    result?;
+    // Each bump of the epoch reaches the deadline at the next loop header,
+    // except for the last one after which the loop exits.
+    assert_eq!(yields, 9);
+    Ok(())
+}
+
+#[test]
+fn deadline_callback_updates() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (local i32)
+                    i32.const 10
+                    local.set 0
+                    (loop
+                        call $bump
+                        local.get 0
+                        i32.const -1
+                        i32.add
+                        local.tee 0
+                        br_if 0)))
+        "#,
+    )?;
+    let calls = Arc::new(AtomicUsize::new(0));
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback({
+        let calls = calls.clone();
+        move |_cx| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(1)
+        }
+    });
+    run.call(&mut store, ())?;
+    // Same as with async yields, the last bump isn't observed.
+    assert_eq!(calls.load(Ordering::SeqCst), 9);
+    Ok(())
+}
+
+#[test]
+fn deadline_callback_traps() -> Result<()> {
+    let engine = build_engine(false)?;
+    let mut store = Store::new(&engine, ());
+    let run = instantiate(
+        &mut store,
+        r#"
+            (module
+                (import "" "bump_epoch" (func $bump))
+                (func (export "run")
+                    (loop
+                        call $bump
+                        br 0)))
+        "#,
+    )?;
+    let calls = Arc::new(AtomicUsize::new(0));
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback({
+        let calls = calls.clone();
+        move |_cx| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 2 {
+                Err(Trap::new("enough epochs"))
+            } else {
+                Ok(1)
+            }
+        }
+    });
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("enough epochs"), "{}", trap);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // Switching back to trapping replaces the callback.
+    store.epoch_deadline_trap();
+    store.set_epoch_deadline(1);
+    assert_interrupted(run.call(&mut store, ()));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+/// Polls `future` to completion, returning its output along with the number
+/// of times it yielded.
+fn run<F: Future>(future: F) -> (F::Output, usize) {
+    let mut f = Pin::from(Box::new(future));
+    let waker = dummy_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut yields = 0;
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => break (val, yields),
+            Poll::Pending => yields += 1,
+        }
+    }
+}
+
+fn dummy_waker() -> Waker {
+    return unsafe { Waker::from_raw(clone(5 as *const _)) };
+
+    unsafe fn clone(ptr: *const ()) -> RawWaker {
+        assert_eq!(ptr as usize, 5);
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+        RawWaker::new(ptr, &VTABLE)
+    }
+
+    unsafe fn wake(ptr: *const ()) {
+        assert_eq!(ptr as usize, 5);
+    }
+
+    unsafe fn wake_by_ref(ptr: *const ()) {
+        assert_eq!(ptr as usize, 5);
+    }
+
+    unsafe fn drop(ptr: *const ()) {
+        assert_eq!(ptr as usize, 5);
+    }
+}
@@ -0,0 +1,482 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn exceptions_engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config.wasm_exceptions(true);
+    Engine::new(&config)
+}
+
+fn get_tag(store: &mut Store<()>, instance: &Instance, name: &str) -> Tag {
+    instance
+        .get_export(&mut *store, name)
+        .and_then(Extern::into_tag)
+        .unwrap()
+}
+
+#[test]
+fn disabled_by_default() {
+    let engine = Engine::default();
+    let result = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e)
+                (func throw $e))
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "all-arch")]
+#[test]
+fn unsupported_target() -> Result<()> {
+    let mut config = Config::new();
+    config
+        .wasm_exceptions(true)
+        .target("x86_64-pc-windows-msvc")?;
+    let err = Engine::new(&config).err().expect("can't unwind on windows");
+    assert!(
+        err.to_string()
+            .contains("the exceptions proposal is not supported on windows"),
+        "{}",
+        err
+    );
+
+    config.target("x86_64-unknown-linux-gnu")?;
+    Engine::new(&config)?;
+    Ok(())
+}
+
+#[test]
+fn catch_within_function() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e (param i32))
+                (func (export "run") (param i32) (result i32)
+                    try (result i32)
+                        local.get 0
+                        throw $e
+                    catch $e
+                        i32.const 1
+                        i32.add
+                    end))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 41)?, 42);
+    Ok(())
+}
+
+#[test]
+fn catch_across_frames() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $a (param i32))
+                (tag $b (param i64 f32))
+                (func $throw (param i32)
+                    local.get 0
+                    i32.eqz
+                    if
+                        local.get 0
+                        throw $a
+                    end
+                    i64.const 7
+                    f32.const 1.5
+                    throw $b)
+                (func $middle (param i32) (result i32)
+                    local.get 0
+                    call $throw
+                    i32.const -1)
+                (func (export "run") (param i32) (result i32)
+                    try (result i32)
+                        local.get 0
+                        call $middle
+                    catch $a
+                        i32.const 100
+                        i32.add
+                    catch $b
+                        i32.trunc_f32_s
+                        drop
+                        i32.wrap_i64
+                    end))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 0)?, 100);
+    assert_eq!(run.call(&mut store, 1)?, 7);
+    Ok(())
+}
+
+#[test]
+fn locals_survive_unwinding() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e (param i32))
+                (func $throw (param i32)
+                    local.get 0
+                    throw $e)
+                (func $deep (param i32)
+                    (local i64 f64)
+                    i64.const 5
+                    local.set 1
+                    f64.const 6
+                    local.set 2
+                    local.get 0
+                    call $throw
+                    unreachable)
+                (func (export "run") (param i32) (result i32)
+                    (local $a i32) (local $b i64) (local $c f32) (local $d f64)
+                    (local $i i32) (local $sum i32)
+                    local.get 0
+                    local.set $a
+                    i64.const 20
+                    local.set $b
+                    f32.const 300
+                    local.set $c
+                    f64.const 4000
+                    local.set $d
+                    loop
+                        try
+                            local.get $i
+                            call $deep
+                        catch $e
+                            local.get $sum
+                            i32.add
+                            local.set $sum
+                        end
+                        local.get $i
+                        i32.const 1
+                        i32.add
+                        local.tee $i
+                        i32.const 1000
+                        i32.ne
+                        br_if 0
+                    end
+                    local.get $a
+                    local.get $b
+                    i32.wrap_i64
+                    i32.add
+                    local.get $c
+                    i32.trunc_f32_s
+                    i32.add
+                    local.get $d
+                    i32.trunc_f64_s
+                    i32.add
+                    local.get $sum
+                    i32.add))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 1)?, 4321 + 999 * 1000 / 2);
+    Ok(())
+}
+
+#[test]
+fn exceptions_do_not_unwind_host_frames() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e (export "e") (param i32))
+                (func (export "throw") (param i32)
+                    local.get 0
+                    throw $e))
+        "#,
+    )?;
+    let inner = Instance::new(&mut store, &module, &[])?;
+    let tag = get_tag(&mut store, &inner, "e");
+    let throw = inner.get_typed_func::<i32, (), _>(&mut store, "throw")?;
+
+    // The exception thrown by the inner wasm stops at the host function, which
+    // then rethrows it into the outer wasm by returning it.
+    let host = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>, x: i32| {
+        let trap = throw.call(&mut caller, x + 1).unwrap_err();
+        assert!(trap.exception().is_some());
+        Err::<(), _>(trap)
+    });
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "tag" (tag $e (param i32)))
+                (import "" "host" (func $host (param i32)))
+                (func $middle (param i32)
+                    local.get 0
+                    call $host)
+                (func (export "run") (param i32) (result i32)
+                    try (result i32)
+                        local.get 0
+                        call $middle
+                        i32.const -1
+                    catch $e
+                    end))
+        "#,
+    )?;
+    let outer = Instance::new(&mut store, &module, &[tag.into(), host.into()])?;
+    let run = outer.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 1)?, 2);
+    Ok(())
+}
+
+#[test]
+fn catch_all_and_unmatched_tags() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $a)
+                (tag $b)
+                (func $throw_b
+                    throw $b)
+                (func (export "run") (result i32)
+                    try (result i32)
+                        try (result i32)
+                            call $throw_b
+                            i32.const 0
+                        catch $a
+                            i32.const 1
+                        end
+                    catch_all
+                        i32.const 2
+                    end))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 2);
+    Ok(())
+}
+
+#[test]
+fn rethrow_and_delegate() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e (param i32))
+                (func (export "rethrow") (param i32) (result i32)
+                    try (result i32)
+                        try (result i32)
+                            local.get 0
+                            throw $e
+                        catch $e
+                            drop
+                            rethrow 0
+                        end
+                    catch $e
+                        i32.const 10
+                        i32.mul
+                    end)
+                (func (export "delegate") (param i32) (result i32)
+                    try (result i32)
+                        try (result i32)
+                            try (result i32)
+                                local.get 0
+                                throw $e
+                            delegate 1
+                        catch $e
+                            drop
+                            i32.const -1
+                        end
+                    catch $e
+                        i32.const 1
+                        i32.add
+                    end))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let rethrow = instance.get_typed_func::<i32, i32, _>(&mut store, "rethrow")?;
+    assert_eq!(rethrow.call(&mut store, 4)?, 40);
+    let delegate = instance.get_typed_func::<i32, i32, _>(&mut store, "delegate")?;
+    assert_eq!(delegate.call(&mut store, 4)?, 5);
+    Ok(())
+}
+
+#[test]
+fn uncaught_exception_is_a_trap() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (tag $e (export "e") (param i32 f64))
+                (func (export "run")
+                    i32.const 42
+                    f64.const 2.5
+                    throw $e)
+                (func (export "ok")))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("uncaught wasm exception"));
+    let exception = trap.exception().unwrap();
+    let e = get_tag(&mut store, &instance, "e");
+    assert!(exception.tag().same(&e, &store));
+    assert_eq!(exception.payload()[0].unwrap_i32(), 42);
+    assert_eq!(exception.payload()[1].unwrap_f64(), 2.5);
+
+    // The exception is no longer pending afterwards.
+    let ok = instance.get_typed_func::<(), (), _>(&mut store, "ok")?;
+    ok.call(&mut store, ())?;
+    Ok(())
+}
+
+#[test]
+fn host_exception_caught_by_wasm() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let tag = Tag::new(&mut store, TagType::new([ValType::I64]))?;
+    let wrapped = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>, x: i64| {
+        let exception = Exception::new(&mut caller, tag, vec![Val::I64(x * 2)]).unwrap();
+        Err::<i32, _>(Trap::throw(exception))
+    });
+    let dynamic = Func::new(
+        &mut store,
+        FuncType::new([ValType::I64], [ValType::I32]),
+        move |mut caller, params, _results| {
+            let x = params[0].unwrap_i64();
+            let exception = Exception::new(&mut caller, tag, vec![Val::I64(x * 3)])?;
+            Err(Trap::throw(exception))
+        },
+    );
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "tag" (tag $e (param i64)))
+                (import "" "wrapped" (func $wrapped (param i64) (result i32)))
+                (import "" "dynamic" (func $dynamic (param i64) (result i32)))
+                (func (export "wrapped") (param i64) (result i64)
+                    try (result i64)
+                        local.get 0
+                        call $wrapped
+                        i64.extend_i32_s
+                    catch $e
+                    end)
+                (func (export "dynamic") (param i64) (result i64)
+                    try (result i64)
+                        local.get 0
+                        call $dynamic
+                        i64.extend_i32_s
+                    catch $e
+                    end))
+        "#,
+    )?;
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &[tag.into(), wrapped.into(), dynamic.into()],
+    )?;
+    let run = instance.get_typed_func::<i64, i64, _>(&mut store, "wrapped")?;
+    assert_eq!(run.call(&mut store, 5)?, 10);
+    let run = instance.get_typed_func::<i64, i64, _>(&mut store, "dynamic")?;
+    assert_eq!(run.call(&mut store, 5)?, 15);
+    Ok(())
+}
+
+#[test]
+fn host_exception_crosses_wasm() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let tag = Tag::new(&mut store, TagType::new([ValType::I32]))?;
+    let host = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>| {
+        let exception = Exception::new(&mut caller, tag, vec![Val::I32(7)]).unwrap();
+        Err::<(), _>(Trap::throw(exception))
+    });
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "host" (func $host))
+                (func (export "run")
+                    call $host
+                    unreachable))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[host.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    let exception = trap.exception().unwrap();
+    assert!(exception.tag().same(&tag, &store));
+    assert_eq!(exception.payload()[0].unwrap_i32(), 7);
+    Ok(())
+}
+
+#[test]
+fn host_exception_without_exceptions_enabled_traps() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let tag = Tag::new(&mut store, TagType::new([]))?;
+    let host = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>| {
+        let exception = Exception::new(&mut caller, tag, vec![]).unwrap();
+        Err::<(), _>(Trap::throw(exception))
+    });
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "host" (func $host))
+                (func (export "run")
+                    call $host))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[host.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(trap.exception().is_some());
+    Ok(())
+}
+
+#[test]
+fn tag_imports_are_type_checked() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "tag" (tag (param i32))))
+        "#,
+    )?;
+    let i32_tag = Tag::new(&mut store, TagType::new([ValType::I32]))?;
+    let f64_tag = Tag::new(&mut store, TagType::new([ValType::F64]))?;
+    Instance::new(&mut store, &module, &[i32_tag.into()])?;
+    assert!(Instance::new(&mut store, &module, &[f64_tag.into()]).is_err());
+    Ok(())
+}
+
+#[test]
+fn exception_payload_is_type_checked() -> Result<()> {
+    let engine = exceptions_engine()?;
+    let mut store = Store::new(&engine, ());
+    let tag = Tag::new(&mut store, TagType::new([ValType::I32]))?;
+    assert!(Exception::new(&mut store, tag, vec![Val::I64(0)]).is_err());
+    assert!(Exception::new(&mut store, tag, vec![]).is_err());
+    assert!(Tag::new(&mut store, TagType::new([ValType::ExternRef])).is_err());
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::time::Duration;
+use wasmtime::*;
+
+/// Runs `run` from a module which calls the `sample` import three times from
+/// `$inner` and once from `$outer`, sampling the store's stack each time.
+fn profile() -> Result<GuestProfiler> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $profiled
+                (import "" "sample" (func $sample))
+                (func $outer (export "run")
+                    call $inner
+                    call $sample)
+                (func $inner
+                    call $sample
+                    call $sample
+                    call $sample)
+            )
+        "#,
+    )?;
+    let profiler = GuestProfiler::new("profiled", Duration::from_millis(1));
+    let mut store = Store::new(&engine, Some(profiler));
+    let sample = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Option<GuestProfiler>>| {
+            let mut profiler = caller.data_mut().take().unwrap();
+            profiler.sample(&caller);
+            *caller.data_mut() = Some(profiler);
+        },
+    );
+    let instance = Instance::new(&mut store, &module, &[sample.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+
+    // Samples taken outside of wasm are ignored.
+    let mut profiler = store.data_mut().take().unwrap();
+    profiler.sample(&store);
+    *store.data_mut() = Some(profiler);
+
+    run.call(&mut store, ())?;
+    Ok(store.into_data().unwrap())
+}
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn firefox_profile() -> Result<()> {
+    let mut output = Vec::new();
+    profile()?.finish(GuestProfileFormat::Firefox, &mut output)?;
+    let output = String::from_utf8(output)?;
+
+    assert!(
+        output.starts_with("{\"meta\":{\"version\":24,"),
+        "{}",
+        output
+    );
+    assert!(output.contains("\"stringTable\":[\"\",\"outer (profiled)\",\"inner (profiled)\"]"));
+    // Two stacks for `$outer` calling `$inner`, and one stack of `$outer`
+    // itself, since every call site is a different frame.
+    assert!(output.contains("\"stackTable\":{\"schema\":{\"prefix\":0,\"frame\":1},\"data\":[[null,0],[0,1],[0,2],[0,3],[null,4]]}"), "{}", output);
+    assert_eq!(output.matches("\"data\":[[").count(), 3, "{}", output);
+    assert!(
+        output.contains(
+            "\"samples\":{\"schema\":{\"stack\":0,\"time\":1,\"eventDelay\":2},\"data\":[[1,"
+        ),
+        "{}",
+        output
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn pprof_profile() -> Result<()> {
+    let mut output = Vec::new();
+    profile()?.finish(GuestProfileFormat::Pprof, &mut output)?;
+
+    // The profile starts with the `sample_type`s, the first of which is
+    // `samples`/`count`, followed by four samples each with a count of one.
+    assert_eq!(&output[..6], &[0x0a, 0x04, 0x08, 0x01, 0x10, 0x02]);
+    let contains = |needle: &[u8]| output.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"\x32\x05outer"));
+    assert!(contains(b"\x32\x05inner"));
+    assert!(contains(b"\x32\x08profiled"));
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1 4)
+        (global $counter (export "counter") (mut i32) (i32.const 0))
+        (global (export "answer") i64 (i64.const 42))
+        (table (export "table") 2 funcref)
+        (func $one (result i32) i32.const 1)
+        (func $two (result i32) i32.const 2)
+        (elem declare func $one $two)
+        (func (export "init")
+            (drop (memory.grow (i32.const 1)))
+            (i32.store (i32.const 70000) (i32.const 0xdead))
+            (i32.store (i32.const 65536) (i32.const 0xbeef))
+            (global.set $counter (i32.const 7))
+            (table.set (i32.const 0) (ref.func $two))
+            (table.set (i32.const 1) (ref.func $one)))
+        (func (export "bump") (result i32)
+            (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+            (i32.store (i32.const 0) (global.get $counter))
+            global.get $counter)
+        (func (export "call") (param i32) (result i32)
+            (call_indirect (result i32) (local.get 0)))
+    )
+"#;
+
+fn check_initialized(store: &mut Store<()>, instance: &Instance) -> Result<()> {
+    let memory = instance.get_memory(&mut *store, "memory").unwrap();
+    assert_eq!(memory.size(&*store), 2);
+    let data = memory.data(&*store);
+    assert_eq!(&data[70000..70004], &0xdead_u32.to_le_bytes());
+    assert_eq!(&data[65536..65540], &0xbeef_u32.to_le_bytes());
+    assert_eq!(&data[0..4], &[0; 4]);
+
+    let counter = instance.get_global(&mut *store, "counter").unwrap();
+    assert_eq!(counter.get(&mut *store).i32(), Some(7));
+    let answer = instance.get_global(&mut *store, "answer").unwrap();
+    assert_eq!(answer.get(&mut *store).i64(), Some(42));
+
+    let call = instance.get_typed_func::<i32, i32, _>(&mut *store, "call")?;
+    assert_eq!(call.call(&mut *store, 0)?, 2);
+    assert_eq!(call.call(&mut *store, 1)?, 1);
+    Ok(())
+}
+
+#[test]
+fn restore_into_fresh_instance() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), WAT)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "init")?
+        .call(&mut store, ())?;
+    let snapshot = instance.snapshot(&mut store)?;
+
+    let fresh = Instance::new(&mut store, &module, &[])?;
+    fresh.restore(&mut store, &snapshot)?;
+    check_initialized(&mut store, &fresh)?;
+
+    // Snapshots can also be moved between stores as bytes.
+    let snapshot = InstanceSnapshot::deserialize(&snapshot.serialize()?)?;
+    let mut store = Store::<()>::new(module.engine(), ());
+    let fresh = Instance::new(&mut store, &module, &[])?;
+    fresh.restore(&mut store, &snapshot)?;
+    check_initialized(&mut store, &fresh)?;
+    Ok(())
+}
+
+#[test]
+fn reset_to_snapshot() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), WAT)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "init")?
+        .call(&mut store, ())?;
+    let snapshot = instance.snapshot(&mut store)?;
+
+    let bump = instance.get_typed_func::<(), i32, _>(&mut store, "bump")?;
+    for _ in 0..3 {
+        assert_eq!(bump.call(&mut store, ())?, 8);
+        let table = instance.get_table(&mut store, "table").unwrap();
+        table.set(&mut store, 0, Val::FuncRef(None))?;
+        instance.restore(&mut store, &snapshot)?;
+        check_initialized(&mut store, &instance)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn restore_errors() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), WAT)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let snapshot = instance.snapshot(&mut store)?;
+
+    // Memories can't shrink back to the snapshot's size.
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    memory.grow(&mut store, 1)?;
+    let err = instance.restore(&mut store, &snapshot).unwrap_err();
+    assert!(err.to_string().contains("has grown"), "{}", err);
+
+    let other = Module::new(store.engine(), r#"(module (memory 1))"#)?;
+    let other = Instance::new(&mut store, &other, &[])?;
+    let err = other.restore(&mut store, &snapshot).unwrap_err();
+    assert!(err.to_string().contains("different module"), "{}", err);
+
+    assert!(InstanceSnapshot::deserialize(&[1, 2, 3]).is_err());
+    Ok(())
+}
+
+#[test]
+fn corrupt_snapshot_leaves_instance_unmodified() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), WAT)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "init")?
+        .call(&mut store, ())?;
+    let mut bytes = instance.snapshot(&mut store)?.serialize()?;
+
+    // Move the first memory segment far out of bounds. It follows the number
+    // of memories, the memory's size and its number of segments.
+    bytes[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+    let snapshot = InstanceSnapshot::deserialize(&bytes)?;
+
+    let fresh = Instance::new(&mut store, &module, &[])?;
+    let err = fresh.restore(&mut store, &snapshot).unwrap_err();
+    assert!(err.to_string().contains("corrupt"), "{}", err);
+    let memory = fresh.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(memory.size(&store), 1);
+    let counter = fresh.get_global(&mut store, "counter").unwrap();
+    assert_eq!(counter.get(&mut store).i32(), Some(0));
+    Ok(())
+}
+
+#[test]
+fn unsupported_references() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (table (export "funcs") 1 funcref)
+                (table (export "externs") 1 externref))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance.snapshot(&mut store)?;
+
+    let host = Func::wrap(&mut store, || {});
+    let funcs = instance.get_table(&mut store, "funcs").unwrap();
+    funcs.set(&mut store, 0, host.into())?;
+    let err = instance.snapshot(&mut store).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("another instance"),
+        "{:?}",
+        err
+    );
+    funcs.set(&mut store, 0, Val::FuncRef(None))?;
+
+    let externs = instance.get_table(&mut store, "externs").unwrap();
+    externs.set(&mut store, 0, Val::ExternRef(Some(ExternRef::new(1))))?;
+    let err = instance.snapshot(&mut store).unwrap_err();
+    assert!(format!("{:?}", err).contains("externref"), "{:?}", err);
+    Ok(())
+}
@@ -1,19 +1,25 @@
 mod async_functions;
 mod cli_tests;
+mod component_model;
 mod custom_signal_handler;
+mod debugger;
 mod debug;
+mod epoch_interruption;
+mod exceptions;
 mod externals;
 mod fuel;
 mod func;
 mod funcref;
 mod fuzzing;
 mod gc;
+mod guest_profiler;
 mod globals;
 mod host_funcs;
 mod iloop;
 mod import_calling_export;
 mod import_indexes;
 mod instance;
+mod instance_snapshot;
 mod invoke_func_via_table;
 mod limits;
 mod linker;
@@ -24,10 +30,14 @@ mod module_linking;
 mod module_serialize;
 mod name;
 mod native_hooks;
+mod perfmap;
 mod pooling_allocator;
 mod stack_overflow;
 mod store;
 mod table;
+mod tail_call;
+mod threads;
+mod trampolines;
 mod traps;
 mod wast;
 
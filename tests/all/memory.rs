@@ -321,3 +321,43 @@ fn massive_64_bit_still_limited() -> Result<()> {
         }
     }
 }
+
+#[test]
+fn memory_init_cow_on_demand() -> Result<()> {
+    // Exercise both static and dynamic memories, with and without images.
+    for (static_size, cow) in [(1 << 32, true), (0, true), (1 << 32, false)] {
+        let mut config = Config::new();
+        config.static_memory_maximum_size(static_size);
+        config.memory_init_cow(cow);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (memory (export "m") 1)
+                    (data (i32.const 10) "abc")
+                    (func (export "set") (param i32 i32)
+                        local.get 0
+                        local.get 1
+                        i32.store8))
+            "#,
+        )?;
+
+        for _ in 0..2 {
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &module, &[])?;
+            let memory = instance.get_memory(&mut store, "m").unwrap();
+            let set = instance.get_typed_func::<(i32, i32), (), _>(&mut store, "set")?;
+            assert_eq!(&memory.data(&store)[10..13], b"abc");
+            assert_eq!(memory.data(&store)[0], 0);
+
+            // Writes are private to this instance and survive growth.
+            set.call(&mut store, (10, b'x' as i32))?;
+            memory.grow(&mut store, 2)?;
+            set.call(&mut store, (65536 * 2, 1))?;
+            assert_eq!(&memory.data(&store)[10..13], b"xbc");
+            assert_eq!(memory.data(&store)[65536 * 2], 1);
+        }
+    }
+    Ok(())
+}
@@ -66,3 +66,65 @@ fn test_module_serialize_fail() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_deserialize_from_file() -> Result<()> {
+    let engine = Engine::default();
+    let buffer = serialize(
+        &engine,
+        r#"
+            (module
+                (memory (export "m") 1)
+                (data (i32.const 0) "hello")
+                (func $f (result i32) i32.const 42)
+                (func (export "run") (result i32) call $f)
+                (func (export "trap") unreachable))
+        "#,
+    )?;
+
+    let td = tempfile::TempDir::new()?;
+    let path = td.path().join("module.cwasm");
+    std::fs::write(&path, &buffer)?;
+    let module = unsafe { Module::deserialize_file(&engine, &path)? };
+
+    // Instantiate a few times to make sure that code loaded from the file
+    // stays usable.
+    for _ in 0..3 {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+        assert_eq!(run.call(&mut store, ())?, 42);
+
+        let memory = instance.get_memory(&mut store, "m").unwrap();
+        assert!(memory.data(&store).starts_with(b"hello"));
+
+        let trap = instance.get_typed_func::<(), (), _>(&mut store, "trap")?;
+        let err = trap.call(&mut store, ()).unwrap_err();
+        assert_eq!(err.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    }
+
+    // The module can be serialized again and loaded from memory.
+    let buffer = module.serialize()?;
+    let mut store = Store::new(&engine, ());
+    let instance = unsafe { deserialize_and_instantiate(&mut store, &buffer)? };
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 42);
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_from_file_malformed() -> Result<()> {
+    let engine = Engine::default();
+    let buffer = serialize(&engine, "(module (func (export \"run\")))")?;
+
+    let td = tempfile::TempDir::new()?;
+    let path = td.path().join("module.cwasm");
+    std::fs::write(&path, &buffer[..buffer.len() - 1])?;
+    assert!(unsafe { Module::deserialize_file(&engine, &path) }.is_err());
+
+    std::fs::write(&path, b"")?;
+    assert!(unsafe { Module::deserialize_file(&engine, &path) }.is_err());
+
+    assert!(unsafe { Module::deserialize_file(&engine, td.path().join("missing")) }.is_err());
+    Ok(())
+}
@@ -237,6 +237,65 @@ async fn instantiate_async() -> Result<(), Error> {
     Ok(())
 }
 
+// Throw exceptions from host funcs that wasm catches:
+#[test]
+fn host_exception_caught_by_wasm() -> Result<(), Error> {
+    let mut config = Config::new();
+    config.wasm_exceptions(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, State::default());
+    store.entering_native_code_hook(State::entering_native);
+    store.exiting_native_code_hook(State::exiting_native);
+    let tag = Tag::new(&mut store, TagType::new([]))?;
+
+    let wrapped = Func::wrap(&mut store, move |mut caller: Caller<State>| {
+        let exception = Exception::new(&mut caller, tag, vec![])?;
+        Err::<(), _>(Trap::throw(exception))
+    });
+    let dynamic = Func::new(
+        &mut store,
+        FuncType::new([], []),
+        move |mut caller, _params, _results| {
+            let exception = Exception::new(&mut caller, tag, vec![])?;
+            Err(Trap::throw(exception))
+        },
+    );
+
+    let wat = r#"
+        (module
+            (import "host" "tag" (tag $e))
+            (import "host" "wrapped" (func $wrapped))
+            (import "host" "dynamic" (func $dynamic))
+            (func (export "export") (result i32)
+                try
+                    call $wrapped
+                catch $e
+                end
+                try (result i32)
+                    call $dynamic
+                    i32.const 0
+                catch $e
+                    i32.const 1
+                end)
+        )
+    "#;
+    let module = Module::new(&engine, wat)?;
+    let inst = Instance::new(
+        &mut store,
+        &module,
+        &[tag.into(), wrapped.into(), dynamic.into()],
+    )?;
+    let export = inst.get_typed_func::<(), i32, _>(&mut store, "export")?;
+
+    assert_eq!(export.call(&mut store, ())?, 1);
+
+    // Each host func is switched into and back out of, and then the export
+    // returns to native.
+    assert_eq!(store.data().switches_into_native, 3);
+
+    Ok(())
+}
+
 enum Context {
     Native,
     Vm,
@@ -0,0 +1,60 @@
+#![cfg(target_os = "linux")]
+
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn perfmap_lists_functions_and_trampolines() -> Result<()> {
+    let mut config = Config::new();
+    config.profiler(ProfilingStrategy::PerfMap)?;
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $add (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1)))
+                (func $twice (export "twice") (param i64) (result i64)
+                    (i64.add (local.get 0) (local.get 0)))
+                (func $nop (export "nop")))
+        "#,
+    )?;
+
+    // The map is only appended to, so the module's lines are all present
+    // once `Module::new` returns.
+    let path = format!("/tmp/perf-{}.map", std::process::id());
+    let map = std::fs::read_to_string(&path)?;
+    let _ = std::fs::remove_file(&path);
+    let mut names = Vec::new();
+    for line in map.lines() {
+        let mut parts = line.splitn(3, ' ');
+        let addr = usize::from_str_radix(parts.next().unwrap(), 16)?;
+        let len = usize::from_str_radix(parts.next().expect("missing length"), 16)?;
+        let name = parts.next().expect("missing name");
+        assert!(addr != 0, "null address in `{}`", line);
+        assert!(len > 0, "empty function in `{}`", line);
+        names.push(name.to_string());
+    }
+
+    for func in &["add", "twice", "nop"] {
+        assert!(
+            names.iter().any(|n| n == func),
+            "no `{}` in {:?}",
+            func,
+            names
+        );
+    }
+    // One trampoline per exported signature.
+    let trampolines = names
+        .iter()
+        .filter(|n| n.starts_with("wasm::trampoline["))
+        .count();
+    assert!(trampolines >= 3, "missing trampolines in {:?}", names);
+
+    // The lines describe the module's code: calling it still works.
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    Ok(())
+}
@@ -511,3 +511,118 @@ fn preserve_data_segments() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn memory_init_cow_reset_on_reuse() -> Result<()> {
+    if skip_pooling_allocator_tests() {
+        return Ok(());
+    }
+
+    for cow in [true, false] {
+        let mut config = Config::new();
+        config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+            strategy: PoolingAllocationStrategy::NextAvailable,
+            module_limits: ModuleLimits {
+                memory_pages: 2,
+                table_elements: 0,
+                ..Default::default()
+            },
+            instance_limits: InstanceLimits { count: 1 },
+        });
+        config.memory_init_cow(cow);
+        config.dynamic_memory_guard_size(0);
+        config.static_memory_guard_size(0);
+        config.static_memory_maximum_size(2 * 65536);
+
+        let engine = Engine::new(&config)?;
+        let module = Module::new(
+            &engine,
+            r#"
+                (module
+                    (memory (export "m") 1 2)
+                    (data (i32.const 0) "hello")
+                    (data (i32.const 65530) "world"))
+            "#,
+        )?;
+
+        // Dirty the memory, including pages that the image doesn't cover and
+        // pages that only become accessible after growing, and make sure the
+        // next instance in the same slot starts out pristine.
+        for _ in 0..5 {
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &module, &[])?;
+            let memory = instance.get_memory(&mut store, "m").unwrap();
+            assert_eq!(memory.size(&store), 1);
+
+            let data = memory.data(&store);
+            assert_eq!(&data[0..5], b"hello");
+            assert_eq!(&data[65530..65535], b"world");
+            assert!(data[5..65530].iter().all(|b| *b == 0));
+
+            memory.grow(&mut store, 1)?;
+            for byte in memory.data_mut(&mut store) {
+                *byte = 0xFE;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn memory_init_cow_switch_modules() -> Result<()> {
+    if skip_pooling_allocator_tests() {
+        return Ok(());
+    }
+
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        module_limits: ModuleLimits {
+            memory_pages: 1,
+            table_elements: 0,
+            ..Default::default()
+        },
+        instance_limits: InstanceLimits { count: 1 },
+    });
+    config.dynamic_memory_guard_size(0);
+    config.static_memory_guard_size(0);
+    config.static_memory_maximum_size(65536);
+
+    let engine = Engine::new(&config)?;
+    let modules = [
+        Module::new(
+            &engine,
+            r#"(module (memory (export "m") 1) (data (i32.const 0) "first"))"#,
+        )?,
+        Module::new(
+            &engine,
+            r#"(module (memory (export "m") 1) (data (i32.const 100) "second"))"#,
+        )?,
+        Module::new(&engine, r#"(module (memory (export "m") 1))"#)?,
+    ];
+
+    // Alternate between modules with different images (and no image at all)
+    // in the single slot of the pool.
+    for i in 0..9 {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &modules[i % 3], &[])?;
+        let memory = instance.get_memory(&mut store, "m").unwrap();
+        let data = memory.data(&store);
+        match i % 3 {
+            0 => {
+                assert_eq!(&data[0..5], b"first");
+                assert!(data[5..].iter().all(|b| *b == 0));
+            }
+            1 => {
+                assert_eq!(&data[100..106], b"second");
+                assert!(data[..100].iter().all(|b| *b == 0));
+                assert!(data[106..].iter().all(|b| *b == 0));
+            }
+            _ => assert!(data.iter().all(|b| *b == 0)),
+        }
+        memory.data_mut(&mut store)[0] = 0xFE;
+    }
+
+    Ok(())
+}
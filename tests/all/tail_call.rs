@@ -0,0 +1,484 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmtime::*;
+
+fn tail_call_engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config.wasm_tail_call(true);
+    Engine::new(&config)
+}
+
+#[test]
+fn disabled_by_default() {
+    let engine = Engine::default();
+    let result = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $f return_call $f))
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "all-arch")]
+#[test]
+fn unsupported_target() -> Result<()> {
+    let mut config = Config::new();
+    config
+        .wasm_tail_call(true)
+        .target("s390x-unknown-linux-gnu")?;
+    let err = Engine::new(&config).err().expect("s390x can't tail call");
+    assert!(
+        err.to_string()
+            .contains("the tail-call proposal is not supported on s390x"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn deep_self_recursion() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $count (export "count") (param i64 i64) (result i64)
+                    local.get 0
+                    i64.eqz
+                    if
+                        local.get 1
+                        return
+                    end
+                    local.get 0
+                    i64.const 1
+                    i64.sub
+                    local.get 1
+                    i64.const 1
+                    i64.add
+                    return_call $count))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let count = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "count")?;
+
+    // Far deeper than the default wasm stack allows for non-tail calls.
+    assert_eq!(count.call(&mut store, (10_000_000, 0))?, 10_000_000);
+    Ok(())
+}
+
+#[test]
+fn mutual_recursion() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $even (export "even") (param i32) (result i32)
+                    local.get 0
+                    i32.eqz
+                    if
+                        i32.const 1
+                        return
+                    end
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    return_call $odd)
+                (func $odd (param i32) (result i32)
+                    local.get 0
+                    i32.eqz
+                    if
+                        i32.const 0
+                        return
+                    end
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    return_call $even))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let even = instance.get_typed_func::<i32, i32, _>(&mut store, "even")?;
+    assert_eq!(even.call(&mut store, 1_000_000)?, 1);
+    assert_eq!(even.call(&mut store, 1_000_001)?, 0);
+    Ok(())
+}
+
+#[test]
+fn stack_arguments() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+
+    // Enough parameters that some are passed on the stack on every supported
+    // target; each iteration rotates them so a misplaced argument shows up in
+    // the result.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $rotate (export "rotate")
+                    (param $n i32)
+                    (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64)
+                    (result i64)
+                    local.get $n
+                    i32.eqz
+                    if
+                        local.get 1
+                        return
+                    end
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.get 2
+                    local.get 3
+                    local.get 4
+                    local.get 5
+                    local.get 6
+                    local.get 7
+                    local.get 8
+                    local.get 9
+                    local.get 10
+                    local.get 1
+                    return_call $rotate))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let rotate = instance.get_func(&mut store, "rotate").unwrap();
+    for n in [0, 1, 7, 9, 100_003] {
+        let mut params = vec![Val::I32(n)];
+        params.extend((0..10).map(|i| Val::I64(i)));
+        let results = rotate.call(&mut store, &params)?;
+        assert_eq!(results[0].unwrap_i64(), i64::from(n % 10));
+    }
+    Ok(())
+}
+
+#[test]
+fn multiple_results() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $fib (export "fib") (param i32 i64 i64 i64 i64) (result i64 i64 i64 i64)
+                    local.get 0
+                    i32.eqz
+                    if
+                        local.get 1
+                        local.get 2
+                        local.get 3
+                        local.get 4
+                        return
+                    end
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.get 2
+                    local.get 1
+                    local.get 2
+                    i64.add
+                    local.get 3
+                    i64.const 1
+                    i64.add
+                    local.get 4
+                    return_call $fib))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let fib = instance
+        .get_typed_func::<(i32, i64, i64, i64, i64), (i64, i64, i64, i64), _>(&mut store, "fib")?;
+    assert_eq!(fib.call(&mut store, (10, 0, 1, 0, 42))?, (55, 89, 10, 42));
+    Ok(())
+}
+
+#[test]
+fn indirect() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $t (func (param i32 i32) (result i32)))
+                (table 2 funcref)
+                (elem (i32.const 0) $ping $pong)
+                (func $ping (export "run") (type $t)
+                    local.get 0
+                    i32.eqz
+                    if
+                        local.get 1
+                        return
+                    end
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.get 1
+                    i32.const 1
+                    i32.add
+                    i32.const 1
+                    return_call_indirect (type $t))
+                (func $pong (type $t)
+                    local.get 0
+                    local.get 1
+                    i32.const 2
+                    i32.mul
+                    i32.const 0
+                    return_call_indirect (type $t)))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, (0, 5))?, 5);
+    assert_eq!(run.call(&mut store, (1, 5))?, 12);
+    assert_eq!(run.call(&mut store, (1_000_000, 0))? & 1, 0);
+
+    // The signature check still applies to tail calls.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $t (func (result i32)))
+                (table 1 funcref)
+                (elem (i32.const 0) $other)
+                (func $other (param i32) (result i32) local.get 0)
+                (func (export "run") (result i32)
+                    i32.const 0
+                    return_call_indirect (type $t)))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        trap.to_string().contains("indirect call type mismatch"),
+        "{}",
+        trap
+    );
+    Ok(())
+}
+
+#[test]
+fn tail_call_host() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "double" (func $double (param i32) (result i32)))
+                (func (export "run") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add
+                    return_call $double))
+        "#,
+    )?;
+    let double = Func::wrap(&mut store, |x: i32| x * 2);
+    let instance = Instance::new(&mut store, &module, &[double.into()])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 20)?, 42);
+    Ok(())
+}
+
+#[test]
+fn more_stack_arguments() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+
+    // `run` receives no stack arguments, so it has to make room for the ones
+    // `$many` takes before tail calling it.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $many (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64) (result i64)
+                    local.get 0
+                    local.get 9
+                    i64.const 10
+                    i64.mul
+                    i64.add
+                    local.get 5
+                    i64.const 100
+                    i64.mul
+                    i64.add)
+                (func (export "run") (result i64)
+                    i64.const 1
+                    i64.const 2
+                    i64.const 3
+                    i64.const 4
+                    i64.const 5
+                    i64.const 6
+                    i64.const 7
+                    i64.const 8
+                    i64.const 9
+                    i64.const 10
+                    return_call $many))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), i64, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 701);
+    let run = instance.get_func(&mut store, "run").unwrap();
+    assert_eq!(run.call(&mut store, &[])?[0].unwrap_i64(), 701);
+    Ok(())
+}
+
+#[test]
+fn mutual_recursion_with_stack_arguments() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+
+    // `$few` and `$many` alternate, so every other tail call needs more stack
+    // arguments than the caller received, and the others need fewer. Calling
+    // `run` from another Wasm function checks that the stack is intact when
+    // the chain returns.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $few (param $n i32) (param i64 i64) (result i64)
+                    local.get $n
+                    i32.eqz
+                    if
+                        local.get 1
+                        local.get 2
+                        i64.add
+                        return
+                    end
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.get 1
+                    local.get 2
+                    i64.const 1
+                    i64.const 2
+                    i64.const 3
+                    i64.const 4
+                    i64.const 5
+                    i64.const 6
+                    i64.const 7
+                    i64.const 8
+                    return_call $many)
+                (func $many (param $n i32) (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64)
+                    (result i64)
+                    local.get $n
+                    i32.eqz
+                    if
+                        local.get 1
+                        return
+                    end
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.get 2
+                    local.get 1
+                    local.get 10
+                    i64.add
+                    local.get 3
+                    i64.add
+                    return_call $few)
+                (func $run (export "run") (param i32) (result i64)
+                    local.get 0
+                    i64.const 0
+                    i64.const 1
+                    return_call $few)
+                (func (export "run_twice") (param i32) (result i64)
+                    local.get 0
+                    call $run
+                    local.get 0
+                    call $run
+                    i64.add))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i64, _>(&mut store, "run")?;
+    let run_twice = instance.get_typed_func::<i32, i64, _>(&mut store, "run_twice")?;
+
+    // Each round trip from `$few` through `$many` turns `(x, y)` into
+    // `(y, x + 9)`.
+    let expected = |mut n: i32| {
+        let (mut x, mut y) = (0i64, 1i64);
+        loop {
+            if n == 0 {
+                return x + y;
+            }
+            n -= 1;
+            if n == 0 {
+                return x;
+            }
+            n -= 1;
+            let next = x + 9;
+            x = y;
+            y = next;
+        }
+    };
+    for n in [0, 1, 2, 3, 10, 1_000_001] {
+        assert_eq!(run.call(&mut store, n)?, expected(n), "n = {}", n);
+        assert_eq!(run_twice.call(&mut store, n)?, 2 * expected(n), "n = {}", n);
+    }
+    Ok(())
+}
+
+#[test]
+fn gc_after_growing_tail_call() -> Result<()> {
+    let engine = tail_call_engine()?;
+    let mut store = Store::new(&engine, ());
+
+    // `$run` grows its incoming argument area and tail calls `$many`, which
+    // leaves the stack pointer somewhere other than where `$outer` put it.
+    // The GC must still find the `externref` that `$outer` keeps across the
+    // call.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "gc" (func $gc))
+                (func $many (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i64) (result i64)
+                    call $gc
+                    local.get 0
+                    local.get 9
+                    i64.add)
+                (func $run (result i64)
+                    i64.const 1
+                    i64.const 2
+                    i64.const 3
+                    i64.const 4
+                    i64.const 5
+                    i64.const 6
+                    i64.const 7
+                    i64.const 8
+                    i64.const 9
+                    i64.const 10
+                    return_call $many)
+                (func (export "outer") (param externref) (result externref)
+                    call $run
+                    drop
+                    local.get 0))
+        "#,
+    )?;
+
+    let r = ExternRef::new("hello".to_string());
+    let r2 = r.clone();
+    let count_during_gc = Arc::new(AtomicUsize::new(0));
+    let count = count_during_gc.clone();
+    let gc = Func::wrap(&mut store, move |mut caller: Caller<'_, ()>| {
+        caller.gc();
+        count.store(r2.strong_count(), SeqCst);
+    });
+    let instance = Instance::new(&mut store, &module, &[gc.into()])?;
+    let outer =
+        instance.get_typed_func::<Option<ExternRef>, Option<ExternRef>, _>(&mut store, "outer")?;
+    let result = outer.call(&mut store, Some(r.clone()))?.unwrap();
+    assert!(result.ptr_eq(&r));
+
+    // Held by `r`, `r2`, and the Wasm frame's root in the activations table.
+    assert!(count_during_gc.load(SeqCst) >= 3);
+    Ok(())
+}
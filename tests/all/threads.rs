@@ -0,0 +1,239 @@
+use anyhow::Result;
+use std::thread;
+use std::time::Duration;
+use wasmtime::*;
+
+fn threads_engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config.wasm_threads(true);
+    Engine::new(&config)
+}
+
+#[test]
+fn shared_memory_requires_shared_type() -> Result<()> {
+    let engine = threads_engine()?;
+    assert!(SharedMemory::new(&engine, MemoryType::new(1, Some(1))).is_err());
+
+    let mut store = Store::new(&engine, ());
+    assert!(Memory::new(&mut store, MemoryType::shared(1, 1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn shared_memory_grow() -> Result<()> {
+    let engine = threads_engine()?;
+    let memory = SharedMemory::new(&engine, MemoryType::shared(1, 3))?;
+    let base = memory.data().as_ptr();
+    assert_eq!(memory.size(), 1);
+    assert_eq!(memory.grow(1)?, 1);
+    assert_eq!(memory.size(), 2);
+    assert_eq!(memory.data_size(), 2 * 65536);
+    assert!(memory.grow(2).is_err());
+    assert_eq!(memory.grow(1)?, 2);
+    assert_eq!(memory.grow(0)?, 3);
+
+    // Shared memories never move.
+    assert_eq!(memory.data().as_ptr(), base);
+    Ok(())
+}
+
+#[test]
+fn shared_memory_import_and_export() -> Result<()> {
+    let engine = threads_engine()?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (import "" "mem") 1 4 shared)
+                (func (export "size") (result i32) memory.size)
+                (func (export "grow") (param i32) (result i32)
+                    local.get 0
+                    memory.grow)
+                (func (export "store") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.atomic.store)
+                (func (export "load") (param i32) (result i32)
+                    local.get 0
+                    i32.atomic.load))
+        "#,
+    )?;
+    let memory = SharedMemory::new(&engine, MemoryType::shared(1, 4))?;
+
+    // Two instances in separate stores see each other's writes and growth.
+    let mut store1 = Store::new(&engine, ());
+    let instance1 = Instance::new(&mut store1, &module, &[memory.clone().into()])?;
+    let mut store2 = Store::new(&engine, ());
+    let instance2 = Instance::new(&mut store2, &module, &[memory.clone().into()])?;
+
+    let store = instance1.get_typed_func::<(i32, i32), (), _>(&mut store1, "store")?;
+    let load = instance2.get_typed_func::<i32, i32, _>(&mut store2, "load")?;
+    store.call(&mut store1, (8, 42))?;
+    assert_eq!(load.call(&mut store2, 8)?, 42);
+
+    let grow = instance1.get_typed_func::<i32, i32, _>(&mut store1, "grow")?;
+    let size = instance2.get_typed_func::<(), i32, _>(&mut store2, "size")?;
+    assert_eq!(grow.call(&mut store1, 1)?, 1);
+    assert_eq!(size.call(&mut store2, ())?, 2);
+    assert_eq!(memory.size(), 2);
+    assert_eq!(grow.call(&mut store1, 3)?, -1);
+
+    // A module-defined shared memory is exported as a `SharedMemory`.
+    let module = Module::new(&engine, r#"(module (memory (export "mem") 1 2 shared))"#)?;
+    let instance = Instance::new(&mut store1, &module, &[])?;
+    assert!(instance.get_memory(&mut store1, "mem").is_none());
+    let exported = instance.get_shared_memory(&mut store1, "mem").unwrap();
+    assert!(exported.ty().is_shared());
+    assert_eq!(exported.grow(1)?, 1);
+    Ok(())
+}
+
+#[test]
+fn shared_memory_out_of_bounds_traps() -> Result<()> {
+    let engine = threads_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1 1 shared)
+                (func (export "load") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+                (func (export "load_offset") (param i32) (result i32)
+                    local.get 0
+                    i32.load offset=0x70000000))
+        "#,
+    )?;
+
+    // Compiled code may elide bounds checks on the assumption that the whole
+    // static heap bound is reserved, so every access past the end of one small
+    // shared memory must trap rather than reach whatever is mapped after it,
+    // such as the memory of another instance.
+    let _other = Instance::new(&mut store, &module, &[])?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let load = instance.get_typed_func::<i32, i32, _>(&mut store, "load")?;
+    let load_offset = instance.get_typed_func::<i32, i32, _>(&mut store, "load_offset")?;
+    assert_eq!(load.call(&mut store, 65532)?, 0);
+    for page in 1..=u32::MAX >> 16 {
+        let addr = (page << 16) as i32;
+        assert!(load.call(&mut store, addr).is_err());
+        assert!(load_offset.call(&mut store, addr).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn shared_memory_type_mismatch() -> Result<()> {
+    let engine = threads_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, r#"(module (memory (import "" "") 1 1 shared))"#)?;
+
+    let memory = Memory::new(&mut store, MemoryType::new(1, Some(1)))?;
+    assert!(Instance::new(&mut store, &module, &[memory.into()]).is_err());
+
+    let other_engine = threads_engine()?;
+    let memory = SharedMemory::new(&other_engine, MemoryType::shared(1, 1))?;
+    assert!(Instance::new(&mut store, &module, &[memory.into()]).is_err());
+    Ok(())
+}
+
+#[test]
+fn atomic_wait_timeout_and_mismatch() -> Result<()> {
+    let engine = threads_engine()?;
+    let memory = SharedMemory::new(&engine, MemoryType::shared(1, 1))?;
+
+    assert_eq!(memory.atomic_wait32(0, 1, None)?, WaitResult::Mismatch);
+    assert_eq!(
+        memory.atomic_wait64(8, 0, Some(Duration::from_millis(10)))?,
+        WaitResult::TimedOut,
+    );
+    assert_eq!(memory.atomic_notify(0, 10)?, 0);
+
+    // Misaligned and out-of-bounds addresses trap.
+    assert!(memory.atomic_wait32(1, 0, None).is_err());
+    assert!(memory.atomic_wait64(65536, 0, None).is_err());
+    assert!(memory.atomic_notify(65536, 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn atomic_wait_notify_between_threads() -> Result<()> {
+    let engine = threads_engine()?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (import "" "mem") 1 1 shared)
+                (func (export "wait") (result i32)
+                    i32.const 0
+                    i32.const 0
+                    i64.const -1
+                    memory.atomic.wait32)
+                (func (export "notify") (param i32) (result i32)
+                    i32.const 0
+                    local.get 0
+                    memory.atomic.notify))
+        "#,
+    )?;
+    let memory = SharedMemory::new(&engine, MemoryType::shared(1, 1))?;
+
+    let waiters = (0..2)
+        .map(|_| {
+            let engine = engine.clone();
+            let module = module.clone();
+            let memory = memory.clone();
+            thread::spawn(move || -> Result<i32> {
+                let mut store = Store::new(&engine, ());
+                let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+                let wait = instance.get_typed_func::<(), i32, _>(&mut store, "wait")?;
+                Ok(wait.call(&mut store, ())?)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[memory.clone().into()])?;
+    let notify = instance.get_typed_func::<i32, i32, _>(&mut store, "notify")?;
+
+    // Keep notifying until both waiters have been woken up, since they may
+    // not have started waiting yet.
+    let mut woken = 0;
+    while woken < 2 {
+        woken += notify.call(&mut store, 2)?;
+        thread::yield_now();
+    }
+
+    for waiter in waiters {
+        assert_eq!(waiter.join().unwrap()?, 0);
+    }
+    Ok(())
+}
+
+#[test]
+fn atomic_wait_on_unshared_memory_traps() -> Result<()> {
+    let engine = threads_engine()?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1 1)
+                (func (export "wait") (result i32)
+                    i32.const 0
+                    i32.const 0
+                    i64.const 0
+                    memory.atomic.wait32)
+                (func (export "notify") (result i32)
+                    i32.const 0
+                    i32.const 1
+                    memory.atomic.notify))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let wait = instance.get_typed_func::<(), i32, _>(&mut store, "wait")?;
+    let notify = instance.get_typed_func::<(), i32, _>(&mut store, "notify")?;
+    assert!(wait.call(&mut store, ()).is_err());
+    assert_eq!(notify.call(&mut store, ())?, 0);
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Calls in and out of compiled code through each kind of code address that
+//! Wasmtime hands out: function bodies, host and Wasm trampolines, and
+//! `VMCallerCheckedAnyfunc`s in tables and in `funcref` values.
+//!
+//! These exercise nothing special on most hosts, but on 32-bit ARM every one
+//! of those addresses has to enter Thumb state, so CI runs them under
+//! `qemu-arm`.
+
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "" "add" (func $add (param i32 i32) (result i32)))
+        (type $t (func (param i32) (result i32)))
+        (table $table (export "table") 2 funcref)
+        (elem (i32.const 0) $double $add_one)
+
+        (func $double (param i32) (result i32)
+            local.get 0
+            i32.const 2
+            i32.mul)
+        (func $add_one (param i32) (result i32)
+            local.get 0
+            i32.const 1
+            call $add)
+
+        (func (export "call_host") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            call $add)
+        (func (export "call_indirect") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            call_indirect (type $t))
+        (func (export "get") (param i32) (result funcref)
+            local.get 0
+            table.get $table)
+    )
+"#;
+
+fn instantiate(store: &mut Store<()>) -> Result<Instance> {
+    let module = Module::new(store.engine(), WAT)?;
+    let add = Func::wrap(&mut *store, |a: i32, b: i32| a.wrapping_add(b));
+    Instance::new(&mut *store, &module, &[add.into()])
+}
+
+#[test]
+fn host_to_wasm() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(&mut store)?;
+
+    let get = instance.get_func(&mut store, "get").unwrap();
+    let results = get.call(&mut store, &[Val::I32(0)])?;
+    let double = results[0].unwrap_funcref().unwrap();
+    let results = double.call(&mut store, &[Val::I32(21)])?;
+    assert_eq!(results[0].unwrap_i32(), 42);
+
+    let double = double.typed::<i32, i32, _>(&store)?;
+    assert_eq!(double.call(&mut store, 5)?, 10);
+    Ok(())
+}
+
+#[test]
+fn wasm_to_host() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(&mut store)?;
+
+    let call_host = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "call_host")?;
+    assert_eq!(call_host.call(&mut store, (40, 2))?, 42);
+
+    let call_host = instance.get_func(&mut store, "call_host").unwrap();
+    let results = call_host.call(&mut store, &[Val::I32(1), Val::I32(2)])?;
+    assert_eq!(results[0].unwrap_i32(), 3);
+    Ok(())
+}
+
+#[test]
+fn wasm_to_wasm_through_table() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(&mut store)?;
+
+    let call_indirect =
+        instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "call_indirect")?;
+    assert_eq!(call_indirect.call(&mut store, (7, 0))?, 14);
+    assert_eq!(call_indirect.call(&mut store, (7, 1))?, 8);
+    Ok(())
+}
+
+#[test]
+fn wasm_to_host_through_table() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate(&mut store)?;
+
+    let triple = Func::wrap(&mut store, |a: i32| a.wrapping_mul(3));
+    let table = instance.get_table(&mut store, "table").unwrap();
+    table.set(&mut store, 1, Val::FuncRef(Some(triple)))?;
+
+    let call_indirect =
+        instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "call_indirect")?;
+    assert_eq!(call_indirect.call(&mut store, (7, 1))?, 21);
+    Ok(())
+}
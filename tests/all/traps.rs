@@ -105,6 +105,105 @@ fn test_trap_trace_cb() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn test_backtrace_from_host() -> Result<()> {
+    let mut store = Store::<Vec<WasmBacktrace>>::default();
+    let wat = r#"
+        (module $hello_mod
+            (import "" "capture" (func $capture))
+            (func (export "run") (call $hello))
+            (func $hello (call $capture))
+        )
+    "#;
+
+    let module = Module::new(store.engine(), wat)?;
+    let capture = Func::wrap(&mut store, |mut caller: Caller<'_, Vec<WasmBacktrace>>| {
+        let backtrace = caller.backtrace();
+        caller.data_mut().push(backtrace);
+    });
+    let instance = Instance::new(&mut store, &module, &[capture.into()])?;
+    let run_func = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+
+    // No wasm is executing, so there's nothing to capture.
+    assert!(store.backtrace().frames().is_empty());
+
+    run_func.call(&mut store, ())?;
+    run_func.call(&mut store, ())?;
+
+    let backtraces = store.data();
+    assert_eq!(backtraces.len(), 2);
+    for backtrace in backtraces {
+        let trace = backtrace.frames();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0].module_name().unwrap(), "hello_mod");
+        assert_eq!(trace[0].func_index(), 2);
+        assert_eq!(trace[0].func_name(), Some("hello"));
+        assert_eq!(trace[0].func_offset(), 1);
+        assert_eq!(trace[1].module_name().unwrap(), "hello_mod");
+        assert_eq!(trace[1].func_index(), 1);
+        assert_eq!(trace[1].func_name(), None);
+        assert_eq!(trace[1].func_offset(), 1);
+        assert!(backtrace.to_string().starts_with("wasm backtrace:\n"));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn test_backtrace_only_includes_own_store() -> Result<()> {
+    let engine = Engine::default();
+    let wat = |name: &str| {
+        format!(
+            r#"
+                (module ${}
+                    (import "" "f" (func $f))
+                    (func (export "run") (call $f))
+                )
+            "#,
+            name
+        )
+    };
+    let inner_module = Module::new(&engine, wat("inner"))?;
+    let outer_module = Module::new(&engine, wat("outer"))?;
+
+    // The host function of the outer store calls into the inner store, so
+    // wasm from both stores is on the stack when the inner store captures its
+    // backtrace, but only its own frames should be included.
+    let mut inner = Store::new(&engine, Vec::new());
+    let f = Func::wrap(&mut inner, |mut caller: Caller<'_, Vec<String>>| {
+        let names = caller
+            .backtrace()
+            .frames()
+            .iter()
+            .map(|frame| frame.module_name().unwrap().to_string())
+            .collect();
+        *caller.data_mut() = names;
+    });
+    let instance = Instance::new(&mut inner, &inner_module, &[f.into()])?;
+    let inner_run = instance.get_typed_func::<(), (), _>(&mut inner, "run")?;
+
+    let mut outer = Store::new(&engine, None);
+    let f = Func::wrap(
+        &mut outer,
+        move |mut caller: Caller<'_, Option<Store<Vec<String>>>>| {
+            let mut inner = caller.data_mut().take().unwrap();
+            inner_run.call(&mut inner, ()).unwrap();
+            *caller.data_mut() = Some(inner);
+        },
+    );
+    let instance = Instance::new(&mut outer, &outer_module, &[f.into()])?;
+    let outer_run = instance.get_typed_func::<(), (), _>(&mut outer, "run")?;
+    *outer.data_mut() = Some(inner);
+    outer_run.call(&mut outer, ())?;
+
+    let inner = outer.data_mut().take().unwrap();
+    assert_eq!(*inner.data(), ["inner"]);
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
 fn test_trap_stack_overflow() -> Result<()> {